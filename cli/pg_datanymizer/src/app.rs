@@ -1,70 +1,946 @@
-use anyhow::Result;
-use std::{fs::File, io};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 use url::Url;
 
-use crate::options::{Options, TransactionConfig};
+use crate::{
+    completions::TableCache,
+    compression::{self, Output},
+    inspect,
+    options::{Dialect, DumpFormat, Options, TransactionConfig},
+    prompt,
+    run_result::{RunRecorder, RunResult},
+    self_check,
+};
 
+#[cfg(unix)]
+use datanymizer_dumper::progress_socket::SocketIndicator;
 use datanymizer_dumper::{
-    indicator::{ConsoleIndicator, SilentIndicator},
-    postgres::{connector::Connector, dumper::PgDumper, IsolationLevel},
+    checkpoint::Checkpoint,
+    coverage::CoverageDiff,
+    indicator::{
+        ConsoleIndicator, Indicator, JsonLinesIndicator, LogFormat, LogIndicator, MultiIndicator,
+        ProgressFormat,
+    },
+    manifest::{self, Manifest, ManifestDiff},
+    mysql::{
+        connector::Connector as MySqlConnector,
+        dumper::{self as mysql_dumper, MySqlDumper},
+    },
+    postgres::{
+        conn_url,
+        connector::{Connection, Connector},
+        credentials::{Credentials, PasswordLookup},
+        csv_output::{self, CsvDirectory, CsvOptions},
+        dry_run::DryRunReport,
+        dumper::PgDumper,
+        output_lint::OutputLinter,
+        restore_script,
+        schema_inspector::{PgSchemaInspector, SchemaFilter},
+        IsolationLevel,
+    },
+    projection::{CountingSink, StatsProjection, DEFAULT_SAMPLE_ROWS},
     Dumper,
 };
-use datanymizer_engine::{Engine, Settings};
+use datanymizer_engine::{secrets, Engine, Pepper, Settings, TempDisk};
+
+const APPLICATION_NAME: &str = "application_name";
+const DEBUG_DUMP_SUFFIX: &str = ".debug.sql";
+/// The metrics file with `--ci` (unless `--metrics-file` is specified)
+const CI_METRICS_FILE: &str = "datanymizer-metrics.json";
 
 pub struct App {
     options: Options,
+    /// The URL without the password
     database_url: Url,
+    credentials: Credentials,
+    /// The pepper of the salted rules (random unless `--pepper-file` or `--seed`)
+    pepper: Pepper,
 }
 
 impl App {
-    pub fn from_options(options: Options) -> Result<Self> {
-        let database_url = options.database_url()?;
+    pub fn from_options(mut options: Options) -> Result<Self> {
+        if let Some(password) = options.password_value() {
+            if let Some(secret) = secrets::resolve(password)? {
+                options.password = Some(Some(secret));
+            }
+        }
+        let mut database_url = options.database_url()?;
+        // the password is taken out of the URL, so it isn't printed and passed to `pg_dump` in the arguments
+        let lookup = match options.dialect {
+            Dialect::Postgres => {
+                PasswordLookup::from_env(options.password_file.clone(), options.password_prompt())
+            }
+            // the pgpass file and `PGPASSWORD` are for PostgreSQL
+            Dialect::MySql => PasswordLookup {
+                password_file: options.password_file.clone(),
+                prompt: options.password_prompt(),
+                ..Default::default()
+            },
+        };
+        let credentials =
+            lookup.resolve(&mut database_url, &mut prompt::password, &mut |warning| {
+                eprintln!("WARNING: {}", warning)
+            })?;
+        let pepper = match (&options.pepper_file, options.seed) {
+            (Some(path), _) => Pepper::load_or_create(Path::new(path))
+                .map_err(|e| anyhow!("Can't use the pepper file {}: {}", path, e))?,
+            (None, Some(seed)) => Pepper::from_seed(seed),
+            (None, None) => Pepper::random(),
+        };
 
         Ok(App {
             options,
             database_url,
+            credentials,
+            pepper,
         })
     }
 
     pub fn run(&self) -> Result<()> {
-        let mut connection = self.connector().connect()?;
-        let engine = self.engine()?;
+        if let Some(files) = &self.options.diff_manifest {
+            return self.diff_manifest(&files[0], &files[1]);
+        }
+        if let Some(files) = &self.options.coverage_diff {
+            return self.coverage_diff(&files[0], &files[1]);
+        }
+        if self.options.dialect == Dialect::MySql {
+            return self.mysql_dump();
+        }
+        if self.options.list_tables
+            || self.options.describe_table.is_some()
+            || self.options.scan_secrets
+        {
+            return self.inspect();
+        }
+
+        if self.options.patch {
+            return self.patch();
+        }
+        if self.options.stats_only {
+            return self.stats_only();
+        }
+        if self.options.dry_run {
+            return self.dry_run();
+        }
+
+        let recorder = Arc::new(RunRecorder::default());
+        let started = Instant::now();
+        let result = self.dump_and_check(&recorder);
+        if let Some(output) = self
+            .dump_filename()
+            .or_else(|| self.options.data_file.clone())
+        {
+            let mut run_result = RunResult::new(
+                &recorder,
+                result.as_ref().err().map(|e| e.to_string()),
+                started.elapsed(),
+                output,
+                fs::read(&self.options.config)
+                    .map(|config| manifest::checksum(&config))
+                    .unwrap_or_default(),
+            );
+            run_result.schema_output = self.options.schema_file.clone();
+            run_result.pepper_fingerprint = self.persisted_pepper_fingerprint();
+            println!("{}", run_result.to_json());
+        }
+        result
+    }
+
+    fn dump_and_check(&self, recorder: &Arc<RunRecorder>) -> Result<()> {
+        let self_check_connector = self.self_check_connector()?;
+        let mut engine = self.engine()?;
+        if cfg!(not(unix)) && self.options.progress_socket.is_some() {
+            return Err(anyhow!("--progress-socket is supported on Unix only"));
+        }
+        if engine.settings.compression.is_some() && self_check_connector.is_some() {
+            return Err(anyhow!("--self-check can't restore a compressed dump"));
+        }
+        if self.options.format == DumpFormat::Directory {
+            if self.options.file.is_none() {
+                return Err(anyhow!(
+                    "--format directory requires the output directory (--file)"
+                ));
+            }
+            if self_check_connector.is_some() {
+                return Err(anyhow!("--self-check can't restore a directory dump"));
+            }
+            if engine.settings.compression.is_some() {
+                return Err(anyhow!("The directory dump can't be compressed"));
+            }
+            if self.options.restore_jobs == 0 {
+                return Err(anyhow!("--restore-jobs must be positive"));
+            }
+            self.csv_options()?;
+        }
+        if self.options.checkpoint.is_some() {
+            if engine.settings.compression.is_some() {
+                return Err(anyhow!("--checkpoint can't resume a compressed dump"));
+            }
+            if self.options.format == DumpFormat::Directory {
+                return Err(anyhow!("--checkpoint can't resume a directory dump"));
+            }
+            // the salted values of the resumed tables must match the values of the dumped ones
+            if !engine.settings.pepper().is_reproducible() {
+                return Err(anyhow!(
+                    "--checkpoint requires --pepper-file or --seed (the resumed dump must have the same pepper)"
+                ));
+            }
+        }
+        if self.options.lint_output {
+            if engine.settings.compression.is_some() {
+                return Err(anyhow!("--lint-output can't lint a compressed dump"));
+            }
+            if self.output_files().is_empty() {
+                return Err(anyhow!(
+                    "--lint-output requires the output file (--file or --schema-file and --data-file)"
+                ));
+            }
+        }
+        let mut connection = self.connector(&engine.settings)?.connect()?;
+        self.lock(&mut connection)?;
+        if self.options.debug_tag_values {
+            let tags = engine.enable_debug_tags();
+            eprintln!(
+                "WARNING: --debug-tag-values: every transformed value is prefixed with the tag of its rule, \
+                the dump is for debugging only and must not be used as a real dump ({})",
+                self.dump_filename().unwrap_or_default()
+            );
+            eprintln!("Rule tags:");
+            for tag in tags {
+                eprintln!("  {}", tag);
+            }
+        }
+        let metrics_file = self.metrics_file();
+        let rule_metrics = if self.options.rule_timing || metrics_file.is_some() {
+            Some(engine.enable_rule_timing())
+        } else {
+            None
+        };
+
+        let manifest = match (&self.options.manifest, &self_check_connector) {
+            (None, None) => None,
+            _ => {
+                let mut manifest = Manifest::new(
+                    env!("CARGO_PKG_VERSION").to_string(),
+                    manifest::checksum(&fs::read(&self.options.config)?),
+                );
+                manifest.secret_providers = secrets::used_providers();
+                manifest.pepper_fingerprint = self.persisted_pepper_fingerprint();
+                manifest.derivation_version = Some(engine.settings.derivation_version);
+                Some(Arc::new(Mutex::new(manifest)))
+            }
+        };
+
+        self.dump(engine, &mut connection, &manifest, recorder)?;
+        connection.release_advisory_lock()?;
+
+        if let (Some(metrics), Some(filename)) = (rule_metrics, &metrics_file) {
+            fs::write(filename, metrics.to_json()?)?;
+        }
+        if self.options.lint_output {
+            self.lint_output(&self.output_files())?;
+        }
+        if let Some(manifest) = manifest {
+            let manifest = manifest
+                .lock()
+                .map_err(|_| anyhow!("Can't access the manifest"))?;
+            if let Some(filename) = &self.options.manifest {
+                fs::write(filename, serde_json::to_string_pretty(&*manifest)?)?;
+            }
+            if let (Some(connector), Some(filename)) = (self_check_connector, &self.options.file) {
+                let psql = self_check::psql_location(&self.options.pg_dump_location);
+                let report = self_check::run(&connector, &psql, filename, &manifest)?;
+                eprint!("{}", report);
+                if !report.is_ok() {
+                    return Err(anyhow!("Self-check failed"));
+                }
+            }
+        }
+
+        let warnings = recorder.warnings();
+        if self.fail_on_warnings() && warnings > 0 {
+            return Err(anyhow!(
+                "The dump has {} warnings (--fail-on-warnings)",
+                warnings
+            ));
+        }
+
+        Ok(())
+    }
 
-        match &self.options.file {
-            Some(filename) => PgDumper::new(
+    // `--ci` implies `--quiet`, `--log-format json`, `--strict`, `--fail-on-warnings` and the metrics file
+    fn log_format(&self) -> Option<LogFormat> {
+        if self.options.ci {
+            Some(LogFormat::Json)
+        } else if self.options.quiet || self.options.log_format != LogFormat::Text {
+            Some(self.options.log_format)
+        } else {
+            None
+        }
+    }
+
+    fn strict(&self) -> bool {
+        self.options.ci || self.options.strict
+    }
+
+    fn fail_on_warnings(&self) -> bool {
+        self.options.ci || self.options.fail_on_warnings
+    }
+
+    fn metrics_file(&self) -> Option<String> {
+        self.options
+            .metrics_file
+            .clone()
+            .or_else(|| self.options.ci.then(|| CI_METRICS_FILE.to_string()))
+    }
+
+    // The progress bars or the milestone lines (`--quiet`), and the recorder of the run result
+    fn indicator(&self, recorder: &Arc<RunRecorder>) -> MultiIndicator {
+        let indicator = self.silent_indicator(recorder);
+        match (self.log_format(), self.options.progress) {
+            (Some(format), _) => indicator.with(LogIndicator::new(format)),
+            // the JSON progress events replace the progress bars
+            (None, ProgressFormat::Json) => indicator,
+            (None, ProgressFormat::Console) => indicator.with(ConsoleIndicator::new()),
+        }
+    }
+
+    // The recorder of the run result and the progress events (`--progress-socket` and `--progress json`),
+    // nothing is printed to stdout
+    fn silent_indicator(&self, recorder: &Arc<RunRecorder>) -> MultiIndicator {
+        let mut indicator = MultiIndicator::new().with(recorder.clone());
+        if self.options.progress == ProgressFormat::Json {
+            indicator = indicator.with(JsonLinesIndicator::stderr());
+        }
+        match &self.options.progress_socket {
+            #[cfg(unix)]
+            Some(path) => indicator.with(SocketIndicator::new(path)),
+            _ => indicator,
+        }
+    }
+
+    fn dump(
+        &self,
+        engine: Engine,
+        connection: &mut Connection,
+        manifest: &Option<Arc<Mutex<Manifest>>>,
+        recorder: &Arc<RunRecorder>,
+    ) -> Result<()> {
+        let options = &self.options;
+        let compression = engine.settings.compression;
+        if let (DumpFormat::Directory, Some(dir)) = (options.format, &options.file) {
+            let directory = CsvDirectory::new(dir, self.csv_options()?)?;
+            let schema = File::create(directory.path().join(csv_output::SCHEMA_FILE))?;
+            return PgDumper::new(
                 engine,
                 self.dump_isolation_level(),
-                self.options.pg_dump_location.clone(),
-                File::create(filename)?,
-                ConsoleIndicator::new(),
-                self.options.pg_dump_args.clone(),
-            )?
-            .dump(&mut connection),
-
-            None => PgDumper::new(
+                options.pg_dump_location.clone(),
+                schema,
+                self.indicator(recorder),
+                options.pg_dump_args.clone(),
+            )
+            .map(|d| self.configure(d, manifest))
+            .and_then(|d| d.with_csv_directory(directory).dump(connection))
+            .and_then(|_| restore_script::write_scripts(Path::new(dir), options.restore_jobs));
+        }
+        if let (Some(checkpoint), Some(filename)) = (&options.checkpoint, self.dump_filename()) {
+            let (checkpoint, output) = Checkpoint::open(
+                checkpoint,
+                Path::new(&filename),
+                &engine.settings.resolved_config(),
+                &engine.settings.pepper().fingerprint().unwrap_or_default(),
+            )?;
+            return PgDumper::new(
                 engine,
                 self.dump_isolation_level(),
-                self.options.pg_dump_location.clone(),
-                io::stdout(),
-                SilentIndicator,
-                self.options.pg_dump_args.clone(),
-            )?
-            .dump(&mut connection),
+                options.pg_dump_location.clone(),
+                output,
+                self.indicator(recorder),
+                options.pg_dump_args.clone(),
+            )
+            .map(|d| self.configure(d, manifest))
+            .and_then(|d| d.with_checkpoint(checkpoint).dump(connection));
+        }
+        match (
+            &options.schema_file,
+            &options.data_file,
+            &self.dump_filename(),
+        ) {
+            (Some(schema_filename), Some(data_filename), _) => {
+                let schema = Output::new(File::create(schema_filename)?, compression)?;
+                let data = Output::new(File::create(data_filename)?, compression)?;
+                let outputs = [schema.finisher(), data.finisher()];
+                let result = PgDumper::new(
+                    engine,
+                    self.dump_isolation_level(),
+                    options.pg_dump_location.clone(),
+                    schema,
+                    self.indicator(recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .map(|d| self.configure(d, manifest))
+                .and_then(|d| d.with_data_writer(data).dump(connection));
+                compression::finish(result, &outputs)
+            }
+
+            (_, _, Some(filename)) => {
+                let output = Output::new(File::create(filename)?, compression)?;
+                let outputs = [output.finisher()];
+                let result = PgDumper::new(
+                    engine,
+                    self.dump_isolation_level(),
+                    options.pg_dump_location.clone(),
+                    output,
+                    self.indicator(recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .map(|d| self.configure(d, manifest))
+                .and_then(|mut d| d.dump(connection));
+                compression::finish(result, &outputs)
+            }
+
+            _ => {
+                let output = Output::new(io::stdout(), compression)?;
+                let outputs = [output.finisher()];
+                let result = PgDumper::new(
+                    engine,
+                    self.dump_isolation_level(),
+                    options.pg_dump_location.clone(),
+                    output,
+                    // the messages would be mixed with the dump
+                    self.silent_indicator(recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .map(|d| self.configure(d, manifest))
+                .and_then(|mut d| d.dump(connection));
+                compression::finish(result, &outputs)
+            }
+        }
+    }
+
+    // Dumps a MySQL (or MariaDB) database with `mysqldump` and `MySqlDumper` (`--dialect mysql`)
+    fn mysql_dump(&self) -> Result<()> {
+        let options = &self.options;
+        if let Some(option) = options.postgres_only_option() {
+            return Err(anyhow!("`{}` isn't supported with --dialect mysql", option));
+        }
+        let engine = self.engine()?;
+        let compression = engine.settings.compression;
+        let mut connection = MySqlConnector::new(self.database_url.clone())?
+            .with_password(self.credentials.password().map(String::from))
+            .connect()?;
+        let recorder = Arc::new(RunRecorder::default());
+
+        match (options.format, self.dump_filename()) {
+            (DumpFormat::Directory, Some(dir)) => {
+                if compression.is_some() {
+                    return Err(anyhow!("The directory dump can't be compressed"));
+                }
+                fs::create_dir_all(&dir)?;
+                let load = File::create(Path::new(&dir).join(mysql_dumper::LOAD_FILE))?;
+                MySqlDumper::new(
+                    engine,
+                    options.mysqldump_location.clone(),
+                    load,
+                    self.indicator(&recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .and_then(|d| d.with_tsv_directory(dir).dump(&mut connection))
+            }
+            (DumpFormat::Directory, None) => Err(anyhow!(
+                "--format directory requires the output directory (--file)"
+            )),
+
+            (_, Some(filename)) => {
+                let output = Output::new(File::create(filename)?, compression)?;
+                let outputs = [output.finisher()];
+                let result = MySqlDumper::new(
+                    engine,
+                    options.mysqldump_location.clone(),
+                    output,
+                    self.indicator(&recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .and_then(|mut d| d.dump(&mut connection));
+                compression::finish(result, &outputs)
+            }
+
+            (_, None) => {
+                let output = Output::new(io::stdout(), compression)?;
+                let outputs = [output.finisher()];
+                let result = MySqlDumper::new(
+                    engine,
+                    options.mysqldump_location.clone(),
+                    output,
+                    // the messages would be mixed with the dump
+                    self.silent_indicator(&recorder),
+                    options.pg_dump_args.clone(),
+                )
+                .and_then(|mut d| d.dump(&mut connection));
+                compression::finish(result, &outputs)
+            }
+        }
+    }
+
+    /// The written dump files (the directory of the directory format), nothing for stdout
+    fn output_files(&self) -> Vec<String> {
+        match (
+            &self.options.schema_file,
+            &self.options.data_file,
+            self.dump_filename(),
+        ) {
+            (Some(schema_filename), Some(data_filename), _) => {
+                vec![schema_filename.clone(), data_filename.clone()]
+            }
+            (_, _, filename) => filename.into_iter().collect(),
+        }
+    }
+
+    /// Lints the written dump files (`--lint-output`), the report is written to stderr
+    fn lint_output(&self, files: &[String]) -> Result<()> {
+        let mut linter = OutputLinter::new(self.options.lint_max_errors);
+        for file in files {
+            if self.options.format == DumpFormat::Directory {
+                linter.lint_directory(Path::new(file))?;
+            } else {
+                linter.lint_sql_file(Path::new(file))?;
+            }
+        }
+        let report = linter.into_report();
+        eprint!("{}", report);
+        if !report.is_ok() {
+            return Err(anyhow!(
+                "The dump has {} lint errors (--lint-output)",
+                report.error_count
+            ));
+        }
+        Ok(())
+    }
+
+    // Dumps the sample rows of every table to a sink and prints the projection of the full dump,
+    // the projection is written to the metrics file with the rule timing metrics
+    fn stats_only(&self) -> Result<()> {
+        let options = &self.options;
+        let mut engine = self.engine()?;
+        let metrics_file = self.metrics_file();
+        let rule_metrics = metrics_file.as_ref().map(|_| engine.enable_rule_timing());
+        let projection = Arc::new(StatsProjection::new(
+            options.stats_sample.unwrap_or(DEFAULT_SAMPLE_ROWS),
+        ));
+        let mut connection = self.connector(&engine.settings)?.connect()?;
+        let sink = CountingSink::default();
+        let started = Instant::now();
+        PgDumper::new(
+            engine,
+            self.dump_isolation_level(),
+            options.pg_dump_location.clone(),
+            sink.clone(),
+            self.indicator(&Arc::new(RunRecorder::default()))
+                .with(projection.clone()),
+            options.pg_dump_args.clone(),
+        )
+        .map(|d| self.configure(d, &None))
+        .and_then(|d| d.with_projection(projection.clone()).dump(&mut connection))?;
+
+        let report = projection.report(sink.written(), started.elapsed());
+        if options.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+        if let (Some(metrics), Some(filename)) = (rule_metrics, &metrics_file) {
+            let json = serde_json::json!({ "rules": metrics.report(), "projection": report });
+            fs::write(filename, serde_json::to_string_pretty(&json)?)?;
+        }
+        Ok(())
+    }
+
+    // Nothing is dumped: the config is checked against the schema only
+    fn dry_run(&self) -> Result<()> {
+        let settings = Settings::new(self.options.config.clone())?;
+        let mut connection = self.connector(&settings)?.connect()?;
+        let inspector = PgSchemaInspector::new(self.schema_filter());
+        let report = DryRunReport::check(&mut connection, &inspector, &settings)?;
+        for warning in connection.take_warnings() {
+            eprintln!("WARNING: {}", warning);
+        }
+        if self.options.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+
+        if report.has_errors() {
+            return Err(anyhow!(
+                "The config refers to unknown tables or columns (--dry-run)"
+            ));
+        }
+        Ok(())
+    }
+
+    fn csv_options(&self) -> Result<CsvOptions> {
+        CsvOptions::new(&self.options.csv_delimiter, &self.options.csv_null)
+    }
+
+    // The debug dumps (with tagged values) are always written to `.debug.sql` files
+    fn dump_filename(&self) -> Option<String> {
+        self.options.file.as_ref().map(|filename| {
+            if !self.options.debug_tag_values
+                || self.options.format == DumpFormat::Directory
+                || filename.ends_with(DEBUG_DUMP_SUFFIX)
+            {
+                return filename.clone();
+            }
+            format!(
+                "{}{}",
+                filename.strip_suffix(".sql").unwrap_or(filename),
+                DEBUG_DUMP_SUFFIX
+            )
+        })
+    }
+
+    // Re-dumps the data of some tables in the existing dump. The result is written to a temporary file
+    // that replaces the dump only on success, so the patching can be safely repeated after failures.
+    fn patch(&self) -> Result<()> {
+        let options = &self.options;
+        let filename = options
+            .data_file
+            .as_ref()
+            .or(options.file.as_ref())
+            .ok_or_else(|| anyhow!("--patch requires the existing dump (--file or --data-file)"))?;
+        let manifest_filename = options
+            .manifest
+            .as_ref()
+            .ok_or_else(|| anyhow!("--patch requires the --manifest of the existing dump"))?;
+
+        let mut manifest: Manifest = serde_json::from_str(&fs::read_to_string(manifest_filename)?)?;
+        manifest.tool_version = env!("CARGO_PKG_VERSION").to_string();
+        manifest.config_checksum = manifest::checksum(&fs::read(&options.config)?);
+        let pepper_fingerprint = self.persisted_pepper_fingerprint();
+        if manifest.pepper_fingerprint != pepper_fingerprint {
+            eprintln!(
+                "WARNING: the pepper differs from the pepper of the existing dump, \
+                the salted rules give other values than in the rest of the dump"
+            );
+            manifest.pepper_fingerprint = pepper_fingerprint;
+        }
+
+        let engine = self.engine()?;
+        let derivation_version = Some(engine.settings.derivation_version);
+        if manifest.derivation_version.is_some()
+            && manifest.derivation_version != derivation_version
+        {
+            eprintln!(
+                "WARNING: the derivation_version differs from the one of the existing dump, \
+                the consistent rules give other values than in the rest of the dump"
+            );
+        }
+        manifest.derivation_version = derivation_version;
+        let manifest = Arc::new(Mutex::new(manifest));
+        if engine.settings.compression.is_some() {
+            return Err(anyhow!("--patch can't patch a compressed dump"));
+        }
+        if options.format == DumpFormat::Directory {
+            return Err(anyhow!("--patch can't patch a directory dump"));
+        }
+        let mut connection = self.connector(&engine.settings)?.connect()?;
+        self.lock(&mut connection)?;
+        let patch_filename = format!("{}.patch", filename);
+        let result = PgDumper::new(
+            engine,
+            self.dump_isolation_level(),
+            options.pg_dump_location.clone(),
+            File::create(&patch_filename)?,
+            self.indicator(&Arc::new(RunRecorder::default())),
+            options.pg_dump_args.clone(),
+        )
+        .map(|d| self.configure(d, &Some(manifest.clone())))
+        .and_then(|mut d| {
+            d.patch(
+                &mut connection,
+                BufReader::new(File::open(filename)?),
+                &options.only_tables,
+            )
+        });
+        // the dump is replaced with the linted patch only
+        let result = result.and_then(|_| match options.lint_output {
+            true => self.lint_output(std::slice::from_ref(&patch_filename)),
+            false => Ok(()),
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&patch_filename);
+            return Err(e);
+        }
+        fs::rename(&patch_filename, filename)?;
+        connection.release_advisory_lock()?;
+
+        let manifest = manifest
+            .lock()
+            .map_err(|_| anyhow!("Can't access the manifest"))?;
+        fs::write(manifest_filename, serde_json::to_string_pretty(&*manifest)?)?;
+
+        Ok(())
+    }
+
+    fn configure<W, I>(
+        &self,
+        dumper: PgDumper<W, I>,
+        manifest: &Option<Arc<Mutex<Manifest>>>,
+    ) -> PgDumper<W, I>
+    where
+        W: 'static + Write + Send,
+        I: 'static + Indicator + Send,
+    {
+        let dumper = dumper
+            .sync_sequences(!self.options.no_sync_sequences)
+            .fk_order(!self.options.no_fk_order)
+            .fail_on_index_semantics_change(
+                self.options.fail_on_index_semantics_change || self.strict(),
+            )
+            .deny_lint_warnings(self.options.deny_lint_warnings || self.strict())
+            .include_extension_tables(self.options.include_extension_tables)
+            .lenient(self.options.lenient)
+            .prefer_replica_safe(self.options.prefer_replica_safe)
+            .on_missing_table(self.options.on_missing_table)
+            .safety_override(self.options.yes_i_know.clone())
+            .min_k(self.options.min_k)
+            .with_schema_filter(self.schema_filter());
+        match manifest {
+            Some(manifest) => dumper.with_manifest(manifest.clone()),
+            None => dumper,
+        }
+    }
+
+    // Compares manifests of two dumps
+    fn diff_manifest(&self, old_filename: &str, new_filename: &str) -> Result<()> {
+        let diff = ManifestDiff::new(&read_manifest(old_filename)?, &read_manifest(new_filename)?);
+
+        if self.options.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            print!("{}", diff);
+        }
+
+        Ok(())
+    }
+
+    // Compares the anonymization coverage of two dumps, the regressions are errors
+    fn coverage_diff(&self, old_filename: &str, new_filename: &str) -> Result<()> {
+        let diff = CoverageDiff::new(&read_manifest(old_filename)?, &read_manifest(new_filename)?);
+
+        if self.options.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            print!("{}", diff);
+        }
+
+        if diff.has_regressions() {
+            return Err(anyhow!("The anonymization coverage has regressed"));
+        }
+        Ok(())
+    }
+
+    // Prints the schema info without dumping
+    fn inspect(&self) -> Result<()> {
+        let settings = self.inspection_settings()?;
+        let mut connection = match &settings {
+            Some(settings) => self.connector(settings)?,
+            None => self.connector_without_session(),
+        }
+        .connect()?;
+        let json = self.options.json;
+
+        let output = match &self.options.describe_table {
+            None if self.options.scan_secrets => {
+                let tables = inspect::scan_secrets(&mut connection, settings.as_ref())?;
+                if json {
+                    serde_json::to_string_pretty(&tables)?
+                } else {
+                    inspect::secrets_to_text(&tables)
+                }
+            }
+            Some(name) => {
+                let description =
+                    inspect::describe_table(&mut connection, name, settings.as_ref())?;
+                if json {
+                    serde_json::to_string_pretty(&description)?
+                } else {
+                    description.to_text()
+                }
+            }
+            None => {
+                let tables = inspect::list_tables(&mut connection, settings.as_ref())?;
+                self.cache_table_names(&tables);
+                if json {
+                    serde_json::to_string_pretty(&tables)?
+                } else {
+                    inspect::tables_to_text(&tables)
+                }
+            }
+        };
+        println!("{}", output.trim_end());
+
+        Ok(())
+    }
+
+    // The table names for the shell completions (best-effort)
+    fn cache_table_names(&self, tables: &[inspect::TableInfo]) {
+        if let Some(cache) = TableCache::new() {
+            let names: Vec<_> = tables
+                .iter()
+                .map(|t| format!("{}.{}", t.schema, t.table))
+                .collect();
+            let _ = cache.save(&self.database_url, &names);
+        }
+    }
+
+    // The config is optional for the schema inspection
+    fn inspection_settings(&self) -> Result<Option<Settings>> {
+        let path = &self.options.config;
+        if Path::new(path).exists() {
+            Ok(Some(Settings::new(path.clone())?))
+        } else {
+            eprintln!("Config file {} not found, rules are not shown", path);
+            Ok(None)
+        }
+    }
+
+    // Refuses to check against the source database
+    fn self_check_connector(&self) -> Result<Option<Connector>> {
+        match &self.options.self_check_url {
+            Some(url) if self.options.self_check => {
+                let url = conn_url::parse(url)?;
+                self_check::check_target(&self.database_url, &url)?;
+                Ok(Some(self.connector_without_session().with_url(url)))
+            }
+            _ => Ok(None),
         }
     }
 
-    fn connector(&self) -> Connector {
+    // Concurrent runs against the same database fail (or wait) unless `--no-advisory-lock`.
+    // If the run fails, the lock is released with the session when the connection is dropped.
+    fn lock(&self, connection: &mut Connection) -> Result<()> {
+        if self.options.no_advisory_lock {
+            return Ok(());
+        }
+        connection.acquire_advisory_lock(self.options.wait_advisory_lock)
+    }
+
+    fn connector(&self, settings: &Settings) -> Result<Connector> {
+        self.connector_without_session()
+            .with_session(&self.session(settings))
+    }
+
+    fn connector_without_session(&self) -> Connector {
         let options = &self.options;
         Connector::new(
             self.database_url.clone(),
             options.accept_invalid_hostnames,
             options.accept_invalid_certs,
         )
+        .with_credentials(self.credentials.clone())
+        .with_catalog_qps(options.catalog_qps)
+        .with_precise_size(options.precise_size)
+        .with_timeouts(options.session_timeouts())
+        .with_keepalives_idle(options.keepalives_idle)
+    }
+
+    // `application_name` is the tool name and version (unless it is in the URL),
+    // `--set` overrides the config settings
+    fn session(&self, settings: &Settings) -> BTreeMap<String, String> {
+        let mut session = BTreeMap::new();
+        if !self
+            .database_url
+            .query_pairs()
+            .any(|(key, _)| key == APPLICATION_NAME)
+        {
+            session.insert(
+                APPLICATION_NAME.to_string(),
+                format!("pg_datanymizer/{}", env!("CARGO_PKG_VERSION")),
+            );
+        }
+        session.extend(settings.session.clone());
+        session.extend(self.options.session.iter().cloned());
+        session
+    }
+
+    // Only the persisted pepper is recorded (a random pepper can't be reused anyway)
+    fn persisted_pepper_fingerprint(&self) -> Option<String> {
+        if self.pepper.is_persisted() {
+            self.pepper.fingerprint()
+        } else {
+            None
+        }
     }
 
     fn engine(&self) -> Result<Engine> {
-        let settings = Settings::new(self.options.config.clone())?;
-        Ok(Engine::new(settings))
+        let mut settings = Settings::with_pepper(self.options.config.clone(), self.pepper.clone())?;
+        if let Some(warning) = settings.pepper_warning() {
+            eprintln!("WARNING: {}", warning);
+        }
+        if let Some(seed) = self.options.seed {
+            settings.set_seed(seed)?;
+        }
+        if let Some(profile) = self.options.target_profile {
+            settings.target_profile = profile;
+        }
+        if let Some(compression) = self.options.compress {
+            settings.compression = Some(compression);
+        }
+        if let Some(jobs) = self.options.jobs {
+            settings.jobs = Some(jobs);
+        }
+        if let Some(id) = &self.options.tenant_id {
+            match &mut settings.tenant {
+                Some(tenant) => tenant.id = Some(id.clone()),
+                None => {
+                    return Err(anyhow!(
+                        "--tenant-id requires the `tenant` section in the config"
+                    ))
+                }
+            }
+        }
+        let mut engine = Engine::new(settings);
+        engine.temp_disk = Arc::new(self.temp_disk()?);
+        engine.max_memory = self.options.max_memory;
+        if engine.settings.has_quasi_identifiers() {
+            engine.enable_anonymity_check();
+        } else if self.options.min_k.is_some() {
+            return Err(anyhow!(
+                "--min-k requires `quasi_identifiers` for some tables in the config"
+            ));
+        }
+
+        Ok(engine)
+    }
+
+    fn temp_disk(&self) -> Result<TempDisk> {
+        let dir = match &self.options.temp_dir {
+            Some(dir) => dir.clone(),
+            None => env::temp_dir(),
+        };
+        if !dir.is_dir() {
+            return Err(anyhow!(
+                "The temporary directory {} doesn't exist",
+                dir.display()
+            ));
+        }
+        Ok(TempDisk::new(dir, self.options.max_temp_disk))
+    }
+
+    fn schema_filter(&self) -> SchemaFilter {
+        SchemaFilter::new(
+            self.options.schemas.clone(),
+            self.options.exclude_schemas.clone(),
+        )
     }
 
     fn dump_isolation_level(&self) -> Option<IsolationLevel> {
@@ -78,11 +954,145 @@ fn dump_isolation_level(&self) -> Option<IsolationLevel> {
     }
 }
 
+fn read_manifest(filename: &str) -> Result<Manifest> {
+    Ok(serde_json::from_str(&fs::read_to_string(filename)?)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use structopt::StructOpt;
 
+    #[test]
+    fn ci() {
+        let app = |args: Vec<&str>| {
+            let mut all = vec!["DBNAME"];
+            all.extend(args);
+            all.push("postgres://postgres@localhost/dbname");
+            App::from_options(Options::from_iter(all)).unwrap()
+        };
+
+        let default = app(vec![]);
+        assert_eq!(default.log_format(), None);
+        assert!(!default.strict());
+        assert!(!default.fail_on_warnings());
+        assert_eq!(default.metrics_file(), None);
+
+        assert_eq!(app(vec!["--quiet"]).log_format(), Some(LogFormat::Text));
+        assert_eq!(
+            app(vec!["--log-format", "json"]).log_format(),
+            Some(LogFormat::Json)
+        );
+
+        let ci = app(vec!["--ci"]);
+        assert_eq!(ci.log_format(), Some(LogFormat::Json));
+        assert!(ci.strict());
+        assert!(ci.fail_on_warnings());
+        assert_eq!(ci.metrics_file().as_deref(), Some(CI_METRICS_FILE));
+        assert_eq!(
+            app(vec!["--ci", "--metrics-file", "metrics.json"])
+                .metrics_file()
+                .as_deref(),
+            Some("metrics.json")
+        );
+    }
+
+    #[test]
+    fn dump_filename() {
+        let app = |args: Vec<&str>| {
+            let mut all = vec!["DBNAME"];
+            all.extend(args);
+            all.push("postgres://postgres@localhost/dbname");
+            App::from_options(Options::from_iter(all)).unwrap()
+        };
+
+        assert_eq!(app(vec![]).dump_filename(), None);
+        assert_eq!(
+            app(vec!["-f", "dump.sql"]).dump_filename().as_deref(),
+            Some("dump.sql")
+        );
+        assert_eq!(
+            app(vec!["--debug-tag-values", "-f", "dump.sql"])
+                .dump_filename()
+                .as_deref(),
+            Some("dump.debug.sql")
+        );
+        assert_eq!(
+            app(vec!["--debug-tag-values", "-f", "dump"])
+                .dump_filename()
+                .as_deref(),
+            Some("dump.debug.sql")
+        );
+        assert_eq!(
+            app(vec!["--debug-tag-values", "-f", "dump.debug.sql"])
+                .dump_filename()
+                .as_deref(),
+            Some("dump.debug.sql")
+        );
+    }
+
+    #[test]
+    fn output_files() {
+        let app = |args: Vec<&str>| {
+            let mut all = vec!["DBNAME", "--lint-output"];
+            all.extend(args);
+            all.push("postgres://postgres@localhost/dbname");
+            App::from_options(Options::from_iter(all)).unwrap()
+        };
+
+        assert_eq!(app(vec![]).output_files(), Vec::<String>::new());
+        assert_eq!(app(vec!["-f", "dump.sql"]).output_files(), vec!["dump.sql"]);
+        assert_eq!(
+            app(vec![
+                "--schema-file",
+                "schema.sql",
+                "--data-file",
+                "data.sql"
+            ])
+            .output_files(),
+            vec!["schema.sql", "data.sql"]
+        );
+        assert_eq!(
+            app(vec!["--format", "directory", "-f", "out"]).output_files(),
+            vec!["out"]
+        );
+    }
+
+    #[test]
+    fn session() {
+        let settings =
+            Settings::from_yaml("tables: []\nsession: {work_mem: 64MB, jit: 'off'}").unwrap();
+        let app = |args: Vec<&str>| {
+            let mut all = vec!["DBNAME"];
+            all.extend(args);
+            App::from_options(Options::from_iter(all)).unwrap()
+        };
+
+        let session = app(vec![
+            "--set",
+            "work_mem=256MB",
+            "postgres://postgres@localhost/dbname",
+        ])
+        .session(&settings);
+        assert_eq!(
+            session,
+            BTreeMap::from([
+                (
+                    "application_name".to_string(),
+                    format!("pg_datanymizer/{}", env!("CARGO_PKG_VERSION"))
+                ),
+                ("jit".to_string(), "off".to_string()),
+                ("work_mem".to_string(), "256MB".to_string()),
+            ])
+        );
+
+        let session = app(vec![
+            "postgres://postgres@localhost/dbname?application_name=etl",
+        ])
+        .session(&settings);
+        assert!(!session.contains_key("application_name"));
+    }
+
     mod isolation_level {
         use super::*;
 
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use datanymizer_engine::{Compression, CompressionMethod};
+use flate2::write::GzEncoder;
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+/// The dump output: plain or compressed by a streaming encoder
+pub enum Output<W: Write> {
+    Plain(W),
+    Compressed(Compressed<W>),
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(match compression {
+            Some(compression) => Self::Compressed(Compressed::new(writer, compression)?),
+            None => Self::Plain(writer),
+        })
+    }
+
+    /// The handle to finish the compressed stream when the dumper is done with the output
+    pub fn finisher(&self) -> Option<Compressed<W>> {
+        match self {
+            Self::Plain(_) => None,
+            Self::Compressed(compressed) => Some(compressed.clone()),
+        }
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Compressed(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Compressed(w) => w.flush(),
+        }
+    }
+}
+
+enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+/// The shared encoder: the dumper writes to it and the app finishes the stream
+/// (the end of the stream must be written even if the dump fails, otherwise the archive is truncated)
+pub struct Compressed<W: Write>(Arc<Mutex<Option<Encoder<W>>>>);
+
+impl<W: Write> Clone for Compressed<W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<W: Write> Compressed<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        let encoder = match compression.method {
+            CompressionMethod::Gzip => Encoder::Gzip(GzEncoder::new(
+                writer,
+                flate2::Compression::new(compression.level() as u32),
+            )),
+            CompressionMethod::Zstd => {
+                Encoder::Zstd(zstd::Encoder::new(writer, compression.level())?)
+            }
+        };
+        Ok(Self(Arc::new(Mutex::new(Some(encoder)))))
+    }
+
+    /// Writes the end of the stream and flushes the inner writer (the next calls do nothing)
+    pub fn finish(&self) -> io::Result<()> {
+        let encoder = self.with_encoder(|encoder| Ok(encoder.take()))?;
+        let mut writer = match encoder {
+            Some(Encoder::Gzip(encoder)) => encoder.finish()?,
+            Some(Encoder::Zstd(encoder)) => encoder.finish()?,
+            None => return Ok(()),
+        };
+        writer.flush()
+    }
+
+    fn with_encoder<T>(
+        &self,
+        f: impl FnOnce(&mut Option<Encoder<W>>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut encoder = self
+            .0
+            .lock()
+            .map_err(|_| io::Error::other("Can't access the compressed output"))?;
+        f(&mut encoder)
+    }
+}
+
+impl<W: Write> Write for Compressed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_encoder(|encoder| match encoder {
+            Some(Encoder::Gzip(e)) => e.write(buf),
+            Some(Encoder::Zstd(e)) => e.write(buf),
+            None => Err(io::Error::other("The compressed output is finished")),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with_encoder(|encoder| match encoder {
+            Some(Encoder::Gzip(e)) => e.flush(),
+            Some(Encoder::Zstd(e)) => e.flush(),
+            None => Ok(()),
+        })
+    }
+}
+
+/// Finishes the compressed outputs after the dump, whether it succeeded or not
+/// (the error of the dump takes priority)
+pub fn finish<W: Write>(result: Result<()>, outputs: &[Option<Compressed<W>>]) -> Result<()> {
+    // every output is finished even if finishing another one fails
+    let mut finished = Ok(());
+    for output in outputs.iter().flatten() {
+        let r = output.finish();
+        finished = finished.and(r);
+    }
+    result?;
+    Ok(finished?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    // The inner writer is moved into the encoder, so the tests share the buffer
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn bytes(&self) -> Vec<u8> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    fn dump(compression: &str, result: Result<()>) -> (Vec<u8>, Result<()>) {
+        let buffer = Buffer::default();
+        let mut output = Output::new(buffer.clone(), Some(compression.parse().unwrap())).unwrap();
+        let outputs = [output.finisher()];
+        output
+            .write_all(b"COPY public.users (id) FROM stdin;\n")
+            .unwrap();
+        output.write_all(b"1\n2\n\\.\n").unwrap();
+        drop(output);
+        let result = finish(result, &outputs);
+        (buffer.bytes(), result)
+    }
+
+    const DUMP: &str = "COPY public.users (id) FROM stdin;\n1\n2\n\\.\n";
+
+    #[test]
+    fn gzip() {
+        let (bytes, result) = dump("gzip:9", Ok(()));
+        assert!(result.is_ok());
+        let mut decoded = String::new();
+        GzDecoder::new(&bytes[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, DUMP);
+    }
+
+    #[test]
+    fn zstd() {
+        let (bytes, result) = dump("zstd", Ok(()));
+        assert!(result.is_ok());
+        assert_eq!(zstd::decode_all(&bytes[..]).unwrap(), DUMP.as_bytes());
+    }
+
+    #[test]
+    fn finished_on_error() {
+        for compression in ["gzip", "zstd"] {
+            let (bytes, result) = dump(compression, Err(anyhow!("Connection lost")));
+            assert_eq!(result.unwrap_err().to_string(), "Connection lost");
+            // the stream isn't truncated
+            let decoded = match compression {
+                "gzip" => {
+                    let mut decoded = vec![];
+                    GzDecoder::new(&bytes[..])
+                        .read_to_end(&mut decoded)
+                        .unwrap();
+                    decoded
+                }
+                _ => zstd::decode_all(&bytes[..]).unwrap(),
+            };
+            assert_eq!(decoded, DUMP.as_bytes());
+        }
+    }
+
+    #[test]
+    fn plain() {
+        let buffer = Buffer::default();
+        let mut output = Output::new(buffer.clone(), None).unwrap();
+        assert!(output.finisher().is_none());
+        output.write_all(DUMP.as_bytes()).unwrap();
+        assert_eq!(buffer.bytes(), DUMP.as_bytes());
+    }
+
+    #[test]
+    fn write_after_finish() {
+        let mut output = Output::new(Buffer::default(), Some("gzip".parse().unwrap())).unwrap();
+        let finisher = output.finisher().unwrap();
+        finisher.finish().unwrap();
+        assert!(output.write_all(b"1\n").is_err());
+        // finishing twice is a no-op
+        assert!(finisher.finish().is_ok());
+    }
+}
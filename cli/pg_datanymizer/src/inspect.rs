@@ -0,0 +1,558 @@
+use anyhow::{anyhow, Result};
+use datanymizer_dumper::{
+    coverage::Coverage,
+    postgres::{
+        connector::Connection,
+        schema_inspector::{ColumnDetails, PgSchemaInspector, UniqueIndex},
+        table::PgTable,
+    },
+    secret_scan::{self, SecretScan, SecretSuspect},
+    SchemaInspector, Table,
+};
+use datanymizer_engine::{SecretDetection, Settings};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TableInfo {
+    pub schema: String,
+    pub table: String,
+    pub estimated_rows: i64,
+    pub size_bytes: i64,
+    /// Foreign keys to other tables
+    pub fk_out: usize,
+    /// Foreign keys from other tables
+    pub fk_in: usize,
+    /// Whether the config has rules for the table
+    pub rules: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub sequence: Option<String>,
+    /// The effective rule (transformer) name
+    pub rule: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub primary: bool,
+    pub columns: Vec<String>,
+}
+
+impl From<UniqueIndex> for IndexInfo {
+    fn from(index: UniqueIndex) -> Self {
+        Self {
+            name: index.name,
+            primary: index.primary,
+            columns: index.columns,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TableDescription {
+    pub schema: String,
+    pub table: String,
+    /// Config tables with the rules for this table
+    pub rule_sources: Vec<String>,
+    pub columns: Vec<ColumnInfo>,
+    pub unique_indexes: Vec<IndexInfo>,
+}
+
+/// The passed through columns of a table whose sampled values look like secrets
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TableSecrets {
+    pub schema: String,
+    pub table: String,
+    pub suspects: BTreeMap<String, SecretSuspect>,
+}
+
+pub fn list_tables(
+    connection: &mut Connection,
+    settings: Option<&Settings>,
+) -> Result<Vec<TableInfo>> {
+    let inspector = PgSchemaInspector::default();
+    let fk_counts = inspector.get_foreign_key_counts(connection)?;
+
+    let mut tables = get_tables(connection)?;
+    tables.sort_by_key(|t| t.get_full_name());
+
+    let mut infos = Vec::with_capacity(tables.len());
+    for table in tables {
+        let fk = fk_counts
+            .get(&table.get_full_name())
+            .copied()
+            .unwrap_or_default();
+        infos.push(TableInfo {
+            estimated_rows: table.get_size(),
+            size_bytes: inspector.get_relation_size(connection, &table)?,
+            fk_out: fk.outgoing,
+            fk_in: fk.incoming,
+            rules: settings.is_some_and(|s| s.find_table(&table.get_names()).is_some()),
+            schema: table.schemaname,
+            table: table.tablename,
+        });
+    }
+
+    Ok(infos)
+}
+
+/// Describes the table by its full name (`schema.table`) or just by its name (if it is unique)
+pub fn describe_table(
+    connection: &mut Connection,
+    name: &str,
+    settings: Option<&Settings>,
+) -> Result<TableDescription> {
+    let inspector = PgSchemaInspector::default();
+    let tables = get_tables(connection)?;
+    let table = find_table(&tables, name)?;
+
+    let columns = inspector.get_column_details(connection, table)?;
+    let unique_indexes = inspector.get_unique_indexes(connection, table)?;
+
+    Ok(TableDescription {
+        schema: table.schemaname.clone(),
+        table: table.tablename.clone(),
+        rule_sources: settings
+            .map(|s| {
+                s.rule_sources(&table.get_names())
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        columns: column_infos(table, columns, settings),
+        unique_indexes: unique_indexes.into_iter().map(IndexInfo::from).collect(),
+    })
+}
+
+/// Samples the passed through text columns of the tables (at most `sample_size` rows of a table)
+/// and reports the columns with values that look like secrets. The values are not kept.
+pub fn scan_secrets(
+    connection: &mut Connection,
+    settings: Option<&Settings>,
+) -> Result<Vec<TableSecrets>> {
+    let default_detection = SecretDetection::default();
+    let detection = settings.map_or(&default_detection, |s| &s.secret_detection);
+    if !detection.is_enabled() {
+        return Ok(vec![]);
+    }
+
+    let mut tables = get_tables(connection)?;
+    tables.sort_by_key(|t| t.get_full_name());
+
+    let mut result = vec![];
+    for table in tables {
+        if settings
+            .is_some_and(|s| !datanymizer_dumper::filter_table(&table.get_full_name(), &s.filter))
+        {
+            continue;
+        }
+        let columns = scanned_columns(&table, settings, detection);
+        if columns.is_empty() {
+            continue;
+        }
+
+        let query = format!(
+            "SELECT {} FROM {} LIMIT {}",
+            columns
+                .iter()
+                .map(|c| format!("\"{}\"::text", c.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(", "),
+            table.quoted_full_name(),
+            detection.sample_size
+        );
+        let mut scan = SecretScan::new(detection, columns.clone());
+        for row in connection.client.query(query.as_str(), &[])? {
+            for i in 0..columns.len() {
+                if let Some(value) = row.get::<_, Option<&str>>(i) {
+                    scan.add_value(i, value);
+                }
+            }
+        }
+
+        let suspects = scan.suspects();
+        if !suspects.is_empty() {
+            result.push(TableSecrets {
+                schema: table.schemaname.clone(),
+                table: table.tablename.clone(),
+                suspects,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+// The passed through text columns (without rules or with the `none` rules, not ignored and not allowed)
+fn scanned_columns(
+    table: &PgTable,
+    settings: Option<&Settings>,
+    detection: &SecretDetection,
+) -> Vec<String> {
+    let cfg = settings.and_then(|s| s.find_table(&table.get_names()));
+    let ruled: Vec<_> = cfg
+        .map(|cfg| {
+            cfg.rules
+                .iter()
+                .filter(|(_, rule)| rule.name() != "none")
+                .map(|(column, _)| column.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let ignored: Vec<_> = cfg
+        .map(|cfg| cfg.ignore.keys().cloned().collect())
+        .unwrap_or_default();
+    let coverage = Coverage::new(&table.get_columns_names(), &ruled, &ignored);
+
+    table
+        .columns
+        .iter()
+        .filter(|c| {
+            secret_scan::TEXT_TYPES.contains(&c.data_type.as_str())
+                && coverage.passed_through.contains(&c.name)
+                && !detection.is_allowed(&table.get_names(), &c.name)
+        })
+        .map(|c| c.name.clone())
+        .collect()
+}
+
+pub fn secrets_to_text(tables: &[TableSecrets]) -> String {
+    if tables.is_empty() {
+        return "No secrets found in the passed through columns\n".to_string();
+    }
+
+    let rows: Vec<_> = tables
+        .iter()
+        .flat_map(|t| {
+            t.suspects.iter().map(move |(column, suspect)| {
+                vec![
+                    format!("{}.{}", t.schema, t.table),
+                    column.clone(),
+                    suspect.kind.clone(),
+                    format!("{}/{}", suspect.matches, suspect.sampled),
+                    suspect.example.clone(),
+                ]
+            })
+        })
+        .collect();
+
+    format_table(
+        &["table", "column", "kind", "matches", "example (masked)"],
+        &rows,
+    )
+}
+
+// The tables that can't be sized are listed anyway (with warnings)
+fn get_tables(connection: &mut Connection) -> Result<Vec<PgTable>> {
+    let tables = PgSchemaInspector::default().get_tables(connection)?;
+    for warning in connection.take_warnings() {
+        eprintln!("WARNING: {}", warning);
+    }
+    Ok(tables)
+}
+
+fn find_table<'a>(tables: &'a [PgTable], name: &str) -> Result<&'a PgTable> {
+    if let Some(table) = tables.iter().find(|t| t.get_full_name() == name) {
+        return Ok(table);
+    }
+
+    let found: Vec<_> = tables.iter().filter(|t| t.get_name() == name).collect();
+    match found.as_slice() {
+        [table] => Ok(table),
+        [] => Err(anyhow!("Table `{}` not found", name)),
+        _ => Err(anyhow!(
+            "There are several tables named `{}`: {}, please specify the schema",
+            name,
+            found
+                .iter()
+                .map(|t| t.get_full_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn column_infos(
+    table: &PgTable,
+    columns: Vec<ColumnDetails>,
+    settings: Option<&Settings>,
+) -> Vec<ColumnInfo> {
+    let cfg = settings.and_then(|s| s.find_table(&table.get_names()));
+
+    columns
+        .into_iter()
+        .map(|c| ColumnInfo {
+            sequence: table
+                .sequences
+                .iter()
+                .find(|s| s.column == c.name)
+                .map(|s| s.full_name.clone()),
+            rule: cfg
+                .and_then(|cfg| cfg.rules.get(&c.name))
+                .map(|r| r.name().to_string()),
+            name: c.name,
+            data_type: c.data_type,
+            nullable: c.nullable,
+            default: c.default,
+        })
+        .collect()
+}
+
+pub fn tables_to_text(tables: &[TableInfo]) -> String {
+    let rows: Vec<_> = tables
+        .iter()
+        .map(|t| {
+            vec![
+                t.schema.clone(),
+                t.table.clone(),
+                t.estimated_rows.to_string(),
+                t.size_bytes.to_string(),
+                t.fk_out.to_string(),
+                t.fk_in.to_string(),
+                yes_no(t.rules),
+            ]
+        })
+        .collect();
+
+    format_table(
+        &[
+            "schema",
+            "table",
+            "rows (est.)",
+            "size",
+            "fk out",
+            "fk in",
+            "rules",
+        ],
+        &rows,
+    )
+}
+
+impl TableDescription {
+    pub fn to_text(&self) -> String {
+        let columns: Vec<_> = self
+            .columns
+            .iter()
+            .map(|c| {
+                vec![
+                    c.name.clone(),
+                    c.data_type.clone(),
+                    yes_no(c.nullable),
+                    c.default.clone().unwrap_or_default(),
+                    c.sequence.clone().unwrap_or_default(),
+                    c.rule.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let indexes: Vec<_> = self
+            .unique_indexes
+            .iter()
+            .map(|i| vec![i.name.clone(), yes_no(i.primary), i.columns.join(", ")])
+            .collect();
+
+        format!(
+            "Table: {}.{}\nRules from: {}\n\n{}\nUnique indexes:\n{}",
+            self.schema,
+            self.table,
+            if self.rule_sources.is_empty() {
+                "-".to_string()
+            } else {
+                self.rule_sources.join(", ")
+            },
+            format_table(
+                &["column", "type", "nullable", "default", "sequence", "rule"],
+                &columns
+            ),
+            format_table(&["name", "primary", "columns"], &indexes)
+        )
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+/// Formats rows as a table with aligned columns
+fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<_> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            widths[i] = widths[i].max(value.chars().count());
+        }
+    }
+
+    let format_row = |values: Vec<&str>| {
+        let line: Vec<_> = values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{:w$}", v, w = w))
+            .collect();
+        format!("{}\n", line.join("  ").trim_end())
+    };
+
+    let separator: Vec<_> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut result = format_row(headers.to_vec());
+    result.push_str(&format_row(separator.iter().map(|s| s.as_str()).collect()));
+    for row in rows {
+        result.push_str(&format_row(row.iter().map(|s| s.as_str()).collect()));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datanymizer_dumper::postgres::column::PgColumn;
+
+    fn table(schema: &str, name: &str) -> PgTable {
+        PgTable::new(name.to_string(), schema.to_string())
+    }
+
+    #[test]
+    fn format_table() {
+        let rows = vec![
+            vec!["public".to_string(), "users".to_string()],
+            vec!["tenant_a".to_string(), "orders".to_string()],
+        ];
+        assert_eq!(
+            super::format_table(&["schema", "table"], &rows),
+            "schema    table\n\
+             --------  ------\n\
+             public    users\n\
+             tenant_a  orders\n"
+        );
+    }
+
+    #[test]
+    fn find_table() {
+        let tables = vec![
+            table("public", "users"),
+            table("tenant_a", "users"),
+            table("public", "orders"),
+        ];
+
+        let t = super::find_table(&tables, "tenant_a.users").unwrap();
+        assert_eq!(t.get_full_name(), "tenant_a.users");
+
+        let t = super::find_table(&tables, "orders").unwrap();
+        assert_eq!(t.get_full_name(), "public.orders");
+
+        let err = super::find_table(&tables, "users").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "There are several tables named `users`: public.users, tenant_a.users, please specify the schema"
+        );
+        assert!(super::find_table(&tables, "items").is_err());
+    }
+
+    #[test]
+    fn scanned_columns() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  name:
+                    first_name: {}
+                  api_token:
+                    none: ~
+                ignore:
+                  comment: "public notes"
+            secret_detection:
+              allow_columns: [users.public_key]
+            "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let mut t = table("public", "users");
+        t.set_columns(
+            [
+                ("id", "integer"),
+                ("name", "text"),
+                ("api_token", "character varying"),
+                ("comment", "text"),
+                ("public_key", "text"),
+                ("payload", "jsonb"),
+            ]
+            .iter()
+            .enumerate()
+            .map(|(i, (name, data_type))| PgColumn {
+                position: i as i32 + 1,
+                name: name.to_string(),
+                data_type: data_type.to_string(),
+                inner_type: None,
+                max_length: None,
+            })
+            .collect(),
+        );
+
+        assert_eq!(
+            super::scanned_columns(&t, Some(&settings), &settings.secret_detection),
+            vec!["api_token", "payload"]
+        );
+        assert_eq!(
+            super::scanned_columns(&t, None, &SecretDetection::default()),
+            vec!["name", "api_token", "comment", "public_key", "payload"]
+        );
+    }
+
+    #[test]
+    fn secrets_to_text() {
+        assert_eq!(
+            super::secrets_to_text(&[]),
+            "No secrets found in the passed through columns\n"
+        );
+        let tables = vec![TableSecrets {
+            schema: "public".to_string(),
+            table: "clients".to_string(),
+            suspects: BTreeMap::from([(
+                "token".to_string(),
+                SecretSuspect {
+                    kind: "api_key".to_string(),
+                    matches: 3,
+                    sampled: 10,
+                    example: "sk_l… (32 chars)".to_string(),
+                },
+            )]),
+        }];
+        assert_eq!(
+            super::secrets_to_text(&tables),
+            "table           column  kind     matches  example (masked)\n\
+             --------------  ------  -------  -------  ----------------\n\
+             public.clients  token   api_key  3/10     sk_l… (32 chars)\n"
+        );
+    }
+
+    #[test]
+    fn column_infos() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  name:
+                    first_name: {}
+            "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let t = table("public", "users");
+        let details = |name: &str| ColumnDetails {
+            name: name.to_string(),
+            data_type: "text".to_string(),
+            nullable: true,
+            default: None,
+        };
+
+        let infos =
+            super::column_infos(&t, vec![details("name"), details("email")], Some(&settings));
+        assert_eq!(infos[0].rule.as_deref(), Some("first_name"));
+        assert_eq!(infos[1].rule, None);
+
+        let infos = super::column_infos(&t, vec![details("name")], None);
+        assert_eq!(infos[0].rule, None);
+    }
+}
@@ -1,14 +1,37 @@
 use anyhow::Result;
+use std::{env, io};
 use structopt::StructOpt;
 
 use app::App;
 use options::Options;
 
 mod app;
+mod completions;
+mod compression;
+mod inspect;
 mod options;
+mod prompt;
+mod run_result;
+mod self_check;
+mod serve;
+mod transform_archive;
 
 fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some(completions::COMPLETE_COMMAND) {
+        return completions::complete(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some(serve::SERVE_COMMAND) {
+        return serve::serve(&args[1..]);
+    }
+    if args.get(1).map(String::as_str) == Some(transform_archive::TRANSFORM_ARCHIVE_COMMAND) {
+        return transform_archive::transform_archive(&args[1..]);
+    }
+
     let options = Options::from_args();
+    if let Some(shell) = options.completions {
+        return completions::generate(shell, &mut io::stdout());
+    }
     let app = App::from_options(options)?;
     app.run()
 }
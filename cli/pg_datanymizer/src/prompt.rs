@@ -0,0 +1,47 @@
+use std::io::{self, BufRead, Write};
+
+/// Asks for the password on the terminal (the input isn't echoed)
+#[cfg(unix)]
+pub fn password(prompt: &str) -> io::Result<String> {
+    use std::{fs::OpenOptions, io::BufReader, os::unix::io::AsRawFd};
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+
+    let fd = tty.as_raw_fd();
+    let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut silent = original;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let line = read_line(&mut BufReader::new(&tty));
+    // the echo is restored even if the reading fails
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    line
+}
+
+/// Asks for the password on stderr (the input is echoed)
+#[cfg(not(unix))]
+pub fn password(prompt: &str) -> io::Result<String> {
+    let mut stderr = io::stderr();
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()?;
+    read_line(&mut io::stdin().lock())
+}
+
+fn read_line(reader: &mut dyn BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no password is entered",
+        ));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
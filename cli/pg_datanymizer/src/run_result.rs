@@ -0,0 +1,144 @@
+use datanymizer_dumper::indicator::{DumpSummary, Indicator};
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Collects the dump summary and counts the warnings
+#[derive(Default)]
+pub struct RunRecorder {
+    summary: Mutex<Option<DumpSummary>>,
+    warnings: AtomicUsize,
+}
+
+impl RunRecorder {
+    pub fn summary(&self) -> Option<DumpSummary> {
+        self.summary.lock().ok().and_then(|s| s.clone())
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+}
+
+impl Indicator for RunRecorder {
+    fn dump_finished(&self, summary: &DumpSummary) {
+        if let Ok(mut s) = self.summary.lock() {
+            *s = Some(summary.clone());
+        }
+    }
+
+    fn warning_msg(&self, _msg: &str) {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The single-line JSON result of a dump to a file (for CI pipelines)
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct RunResult {
+    /// `ok` or `failed`
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub tables: usize,
+    pub rows: u64,
+    /// The peak of the accounted memory (see `--max-memory`)
+    pub peak_memory_bytes: u64,
+    pub warnings: usize,
+    /// The dump file (the data file with `--schema-file` and `--data-file`)
+    pub output: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_output: Option<String>,
+    pub config_checksum: String,
+    /// The fingerprint of the persisted pepper (`--pepper-file`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pepper_fingerprint: Option<String>,
+}
+
+impl RunResult {
+    pub fn new(
+        recorder: &RunRecorder,
+        error: Option<String>,
+        duration: Duration,
+        output: String,
+        config_checksum: String,
+    ) -> Self {
+        let summary = recorder.summary().unwrap_or_default();
+        Self {
+            status: if error.is_some() { "failed" } else { "ok" },
+            error,
+            duration_ms: duration.as_millis() as u64,
+            tables: summary.tables,
+            rows: summary.rows,
+            peak_memory_bytes: summary.memory.peak,
+            warnings: recorder.warnings(),
+            output,
+            schema_output: None,
+            config_checksum,
+            pepper_fingerprint: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_result() {
+        let recorder = RunRecorder::default();
+        recorder.warning_msg("some problem");
+        recorder.debug_msg("some message");
+        recorder.dump_finished(&DumpSummary {
+            tables: 2,
+            rows: 10,
+            ..DumpSummary::default()
+        });
+
+        let result = RunResult::new(
+            &recorder,
+            None,
+            Duration::from_millis(1500),
+            "dump.sql".to_string(),
+            "0123456789abcdef".to_string(),
+        );
+        assert_eq!(
+            result.to_json(),
+            r#"{"status":"ok","duration_ms":1500,"tables":2,"rows":10,"peak_memory_bytes":0,"warnings":1,"output":"dump.sql","config_checksum":"0123456789abcdef"}"#
+        );
+
+        let mut result = RunResult::new(
+            &recorder,
+            None,
+            Duration::from_millis(1500),
+            "dump.sql".to_string(),
+            "0123456789abcdef".to_string(),
+        );
+        result.pepper_fingerprint = Some("fedcba9876543210".to_string());
+        assert!(result
+            .to_json()
+            .ends_with(r#","pepper_fingerprint":"fedcba9876543210"}"#));
+
+        let result = RunResult::new(
+            &RunRecorder::default(),
+            Some("connection refused".to_string()),
+            Duration::from_millis(10),
+            "dump.sql".to_string(),
+            "0123456789abcdef".to_string(),
+        );
+        assert_eq!(result.status, "failed");
+        assert_eq!(result.tables, 0);
+        assert!(result
+            .to_json()
+            .starts_with(r#"{"status":"failed","error":"connection refused","#));
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use datanymizer_dumper::{
+    manifest::Manifest,
+    postgres::connector::{Connection, Connector},
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    path::Path,
+    process::{self, Command},
+};
+use url::Url;
+
+/// Values checked per column (the checks are time-boxed by sampling)
+const SAMPLE_SIZE: usize = 1000;
+/// Timeout for each check query
+const STATEMENT_TIMEOUT: &str = "60s";
+/// Loose email format (checked by PostgreSQL)
+const EMAIL_PATTERN: &str = r"^[^@[:space:]]+@[^@[:space:]]+\.[^@[:space:]]+$";
+
+#[derive(Debug, Default)]
+pub struct SelfCheckReport {
+    /// Names of passed checks
+    pub passed: Vec<String>,
+    /// Descriptions of failed checks
+    pub failures: Vec<String>,
+}
+
+impl SelfCheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn check(&mut self, name: String, failure: Option<String>) {
+        match failure {
+            Some(failure) => self.failures.push(format!("{}: {}", name, failure)),
+            None => self.passed.push(name),
+        }
+    }
+}
+
+impl Display for SelfCheckReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let status = if self.is_ok() { "ok" } else { "FAILED" };
+        writeln!(
+            f,
+            "Self-check {}: {} passed, {} failed",
+            status,
+            self.passed.len(),
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            writeln!(f, "  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Temporary database, it is dropped when the value is dropped (even if the checks fail)
+struct TempDatabase {
+    connection: Connection,
+    name: String,
+}
+
+impl TempDatabase {
+    fn create(connection: Connection) -> Result<Self> {
+        let mut db = Self {
+            connection,
+            name: format!("datanymizer_self_check_{}", process::id()),
+        };
+        db.connection
+            .client
+            .batch_execute(&format!("CREATE DATABASE {}", quote(&db.name)))?;
+        Ok(db)
+    }
+
+    fn url(&self) -> Url {
+        let mut url = self.connection.url.clone();
+        url.set_path(&self.name);
+        url
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        let query = format!("DROP DATABASE IF EXISTS {}", quote(&self.name));
+        if let Err(e) = self.connection.client.batch_execute(&query) {
+            eprintln!("Can't drop the self-check database {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Checks that the self-check URL doesn't point to the source database
+pub fn check_target(source: &Url, target: &Url) -> Result<()> {
+    let key = |url: &Url| {
+        (
+            url.host_str().unwrap_or("localhost").to_string(),
+            url.port().unwrap_or(5432),
+            url.path().trim_start_matches('/').to_string(),
+        )
+    };
+    if key(source) == key(target) {
+        return Err(anyhow!(
+            "The self-check URL must not point to the source database"
+        ));
+    }
+    Ok(())
+}
+
+/// Restores the dump into a temporary database on the `connector` server and verifies it:
+/// row counts match the manifest, anonymized emails are valid.
+pub fn run(
+    connector: &Connector,
+    psql: &str,
+    dump_filename: &str,
+    manifest: &Manifest,
+) -> Result<SelfCheckReport> {
+    let db = TempDatabase::create(connector.connect()?)?;
+    restore(psql, &db.url(), dump_filename)?;
+
+    let mut connection = connector.with_url(db.url()).connect()?;
+    connection
+        .client
+        .batch_execute(&format!("SET statement_timeout = '{}'", STATEMENT_TIMEOUT))?;
+
+    let mut report = SelfCheckReport::default();
+    for (table_name, table) in &manifest.tables {
+        let table_ref = quote_table(table_name);
+
+        let count: i64 = connection
+            .client
+            .query_one(format!("SELECT COUNT(*) FROM {}", table_ref).as_str(), &[])?
+            .get(0);
+        report.check(
+            format!("{} row count", table_name),
+            (count as u64 != table.rows)
+                .then(|| format!("{} rows restored, {} rows dumped", count, table.rows)),
+        );
+
+        for (column, _) in table.rules.iter().filter(|(_, rule)| *rule == "email") {
+            let invalid: i64 = connection
+                .client
+                .query_one(
+                    format!(
+                        "SELECT COUNT(*) FROM (SELECT {col}::text AS v FROM {table} WHERE {col} IS NOT NULL LIMIT {limit}) s WHERE v !~ $1",
+                        col = quote(column),
+                        table = table_ref,
+                        limit = SAMPLE_SIZE
+                    )
+                    .as_str(),
+                    &[&EMAIL_PATTERN],
+                )?
+                .get(0);
+            report.check(
+                format!("{}.{} emails", table_name, column),
+                (invalid > 0).then(|| format!("{} invalid emails in the sample", invalid)),
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+/// `psql` from the `pg_dump` directory (if `pg_dump` is specified with a path)
+pub fn psql_location(pg_dump_location: &str) -> String {
+    match Path::new(pg_dump_location).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join("psql").to_string_lossy().to_string(),
+        _ => "psql".to_string(),
+    }
+}
+
+fn restore(psql: &str, url: &Url, dump_filename: &str) -> Result<()> {
+    let output = Command::new(psql)
+        .args([
+            "-q",
+            "-v",
+            "ON_ERROR_STOP=1",
+            "-f",
+            dump_filename,
+            url.as_str(),
+        ])
+        .output()
+        .map_err(|e| anyhow!("Can't run {}: {}", psql, e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Self-check restore failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn quote_table(full_name: &str) -> String {
+    match full_name.split_once('.') {
+        Some((schema, table)) => format!("{}.{}", quote(schema), quote(table)),
+        None => quote(full_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn check_target() {
+        let source = url("postgres://user@localhost/source");
+
+        assert!(
+            super::check_target(&source, &url("postgres://admin@localhost:5432/source")).is_err()
+        );
+        assert!(super::check_target(&source, &url("postgres://user@localhost/postgres")).is_ok());
+        assert!(super::check_target(&source, &url("postgres://user@otherhost/source")).is_ok());
+    }
+
+    #[test]
+    fn psql_location() {
+        assert_eq!(super::psql_location("pg_dump"), "psql");
+        assert_eq!(
+            super::psql_location("/usr/lib/postgresql/15/bin/pg_dump"),
+            "/usr/lib/postgresql/15/bin/psql"
+        );
+    }
+
+    #[test]
+    fn quote_table() {
+        assert_eq!(super::quote_table("public.users"), r#""public"."users""#);
+        assert_eq!(super::quote_table(r#"my"table"#), r#""my""table""#);
+    }
+
+    #[test]
+    fn report() {
+        let mut report = SelfCheckReport::default();
+        report.check("users row count".to_string(), None);
+        assert!(report.is_ok());
+
+        report.check(
+            "users.email emails".to_string(),
+            Some("2 invalid emails in the sample".to_string()),
+        );
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "Self-check FAILED: 1 passed, 1 failed\n  users.email emails: 2 invalid emails in the sample\n"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use datanymizer_dumper::service::{self, Service, ServiceOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// The command of the service mode: `pg_datanymizer serve --listen :8080 --output-dir <DIR>`
+pub const SERVE_COMMAND: &str = "serve";
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "pg_datanymizer serve",
+    about = "Runs the dump jobs submitted with the HTTP/JSON API"
+)]
+pub struct ServeOptions {
+    #[structopt(
+        long,
+        help = "The address to listen on (`:8080` means all the interfaces, \
+        the non-loopback addresses require `--token-file`)",
+        default_value = "127.0.0.1:8080"
+    )]
+    listen: String,
+
+    #[structopt(long, help = "The max count of the running jobs", default_value = "2")]
+    max_jobs: usize,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "The dumps are written to this directory"
+    )]
+    output_dir: PathBuf,
+
+    #[structopt(
+        long = "pg_dump",
+        help = "pg_dump file location",
+        default_value = "pg_dump"
+    )]
+    pg_dump_location: String,
+
+    #[structopt(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        help = "The file with the bearer token of the requests (`Authorization: Bearer <token>`)"
+    )]
+    token_file: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        value_name = "VARIABLE",
+        number_of_values = 1,
+        help = "The environment variable that the jobs can read the database URL from (`env:<VARIABLE>`), \
+        can be repeated"
+    )]
+    allow_env: Vec<String>,
+
+    #[structopt(
+        long,
+        value_name = "REFERENCE",
+        number_of_values = 1,
+        help = "The secret reference that the jobs can read the database URL from (e.g. `vault:secret/data/db#url`), \
+        a prefix ending with `/` allows the references under it, can be repeated"
+    )]
+    allow_secret: Vec<String>,
+}
+
+pub fn serve(args: &[String]) -> Result<()> {
+    let options = ServeOptions::from_iter(args);
+    let token = options.token_file.as_deref().map(read_token).transpose()?;
+    let server = service::bind(&options.listen, token.is_some())?;
+    eprintln!("Listening on {}", server.server_addr());
+
+    Service::new(ServiceOptions {
+        output_dir: options.output_dir,
+        max_jobs: options.max_jobs,
+        pg_dump_location: options.pg_dump_location,
+        allowed_env: options.allow_env,
+        allowed_secrets: options.allow_secret,
+        token,
+    })
+    .serve(&server);
+    Ok(())
+}
+
+fn read_token(path: &Path) -> Result<String> {
+    let token = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Can't read the token file {}: {}", path.display(), e))?;
+    match token.trim() {
+        "" => Err(anyhow!("The token file {} is empty", path.display())),
+        token => Ok(token.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options() {
+        let options = ServeOptions::from_iter(&["serve", "--output-dir", "/dumps"]);
+        assert_eq!(options.listen, "127.0.0.1:8080");
+        assert_eq!(options.max_jobs, 2);
+        assert_eq!(options.output_dir, PathBuf::from("/dumps"));
+        assert_eq!(options.token_file, None);
+        assert!(options.allow_env.is_empty());
+        assert!(options.allow_secret.is_empty());
+
+        let options = ServeOptions::from_iter(&[
+            "serve",
+            "--listen",
+            ":9000",
+            "--max-jobs",
+            "4",
+            "--output-dir",
+            "dumps",
+            "--token-file",
+            "/etc/datanymizer/token",
+            "--allow-env",
+            "SRC_DATABASE_URL",
+            "--allow-env",
+            "REPLICA_DATABASE_URL",
+            "--allow-secret",
+            "vault:secret/data/datanymizer/",
+        ]);
+        assert_eq!(options.listen, ":9000");
+        assert_eq!(options.max_jobs, 4);
+        assert_eq!(
+            options.token_file,
+            Some(PathBuf::from("/etc/datanymizer/token"))
+        );
+        assert_eq!(
+            options.allow_env,
+            ["SRC_DATABASE_URL", "REPLICA_DATABASE_URL"]
+        );
+        assert_eq!(options.allow_secret, ["vault:secret/data/datanymizer/"]);
+    }
+}
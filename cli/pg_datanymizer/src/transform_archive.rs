@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Context, Result};
+use datanymizer_dumper::{indicator::ConsoleIndicator, postgres::archive};
+use datanymizer_engine::{Engine, Pepper, Settings};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// The command of the archive transformation:
+/// `pg_datanymizer transform-archive --input prod.dump --output anon.dump -c config.yml`
+pub const TRANSFORM_ARCHIVE_COMMAND: &str = "transform-archive";
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "pg_datanymizer transform-archive",
+    about = "Transforms the table data in a pg_dump custom-format archive (`pg_dump -Fc`) without the source database"
+)]
+pub struct TransformArchiveOptions {
+    #[structopt(long, parse(from_os_str), help = "The source archive")]
+    input: PathBuf,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "The transformed archive (it can be restored with pg_restore)"
+    )]
+    output: PathBuf,
+
+    #[structopt(
+        short,
+        long,
+        help = "Path to config file",
+        default_value = "./config.yml"
+    )]
+    config: String,
+
+    #[structopt(
+        long = "pepper-file",
+        help = "Path to the file of the pepper that is mixed into the salted rules (it is created if it doesn't exist)"
+    )]
+    pepper_file: Option<String>,
+
+    #[structopt(
+        long = "seed",
+        help = "Seeds the random values of the rules (by the tables, the columns and the row numbers) and the pepper \
+        (unless --pepper-file)"
+    )]
+    seed: Option<u64>,
+}
+
+pub fn transform_archive(args: &[String]) -> Result<()> {
+    let options = TransformArchiveOptions::from_iter(args);
+    let pepper = match (&options.pepper_file, options.seed) {
+        (Some(path), _) => Pepper::load_or_create(Path::new(path))
+            .map_err(|e| anyhow!("Can't use the pepper file {}: {}", path, e))?,
+        (None, Some(seed)) => Pepper::from_seed(seed),
+        (None, None) => Pepper::random(),
+    };
+    let mut settings = Settings::with_pepper(options.config.clone(), pepper)?;
+    if let Some(warning) = settings.pepper_warning() {
+        eprintln!("WARNING: {}", warning);
+    }
+    if let Some(seed) = options.seed {
+        settings.set_seed(seed)?;
+    }
+    let engine = Engine::new(settings);
+
+    let input = File::open(&options.input)
+        .with_context(|| format!("Can't open the archive {}", options.input.display()))?;
+    let output = File::create(&options.output)
+        .with_context(|| format!("Can't create the archive {}", options.output.display()))?;
+    let stats = archive::transform_archive(
+        &engine,
+        BufReader::new(input),
+        BufWriter::new(output),
+        &ConsoleIndicator::new(),
+    )?;
+    eprintln!(
+        "Transformed {} rows of {} tables (of {} in the archive)",
+        stats.transformed_rows, stats.transformed_tables, stats.tables
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options() {
+        let options = TransformArchiveOptions::from_iter(&[
+            "transform-archive",
+            "--input",
+            "prod.dump",
+            "--output",
+            "anon.dump",
+        ]);
+        assert_eq!(options.input, PathBuf::from("prod.dump"));
+        assert_eq!(options.output, PathBuf::from("anon.dump"));
+        assert_eq!(options.config, "./config.yml");
+        assert_eq!(options.seed, None);
+
+        let options = TransformArchiveOptions::from_iter(&[
+            "transform-archive",
+            "--input",
+            "prod.dump",
+            "--output",
+            "anon.dump",
+            "-c",
+            "rules.yml",
+            "--seed",
+            "42",
+        ]);
+        assert_eq!(options.config, "rules.yml");
+        assert_eq!(options.seed, Some(42));
+
+        assert!(TransformArchiveOptions::from_iter_safe(&[
+            "transform-archive",
+            "--input",
+            "a.dump"
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! A consumer of the progress events (`--progress-socket`).
+//!
+//! ```shell
+//! cargo run -p datanymizer_dumper --example progress_consumer -- /tmp/datanymizer.sock
+//! pg_datanymizer -c config.yml -f dump.sql --progress-socket /tmp/datanymizer.sock postgres://...
+//! ```
+
+#[cfg(unix)]
+fn main() -> std::io::Result<()> {
+    use serde_json::Value;
+    use std::{
+        env, fs,
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+    };
+
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "/tmp/datanymizer.sock".to_string());
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    println!("Waiting for the events on {}", path);
+
+    // the dumper connects again if the connection is lost
+    for stream in listener.incoming() {
+        for line in BufReader::new(stream?).lines() {
+            let event: Value = match serde_json::from_str(&line?) {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Invalid event: {}", e);
+                    continue;
+                }
+            };
+            match event["event"].as_str().unwrap_or_default() {
+                "dump_started" => println!("Dumping {} rows", event["rows"]),
+                "table_started" => println!("{}: started", event["table"]),
+                "progress" => println!(
+                    "{}: {} rows, {} bytes ({}%)",
+                    event["table"], event["rows"], event["bytes"], event["pct"]
+                ),
+                "table_finished" => println!("{}: {} rows", event["table"], event["rows"]),
+                "warning" => println!("Warning: {}", event["message"]),
+                "error" => println!("{}: failed: {}", event["table"], event["error"]),
+                "dump_finished" => {
+                    println!(
+                        "Finished: {} tables, {} rows",
+                        event["tables"], event["rows"]
+                    );
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("The progress events are supported on Unix only");
+}
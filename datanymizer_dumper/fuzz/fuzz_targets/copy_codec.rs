@@ -0,0 +1,25 @@
+//! `cargo +nightly fuzz run copy_codec` (in `datanymizer_dumper`)
+
+#![no_main]
+
+use datanymizer_dumper::postgres::copy_codec::{
+    escape, fields, parse_array, parse_composite, unescape, unescape_str,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|line: &[u8]| {
+    for field in fields(line) {
+        if let Some(value) = unescape(field) {
+            assert_eq!(unescape(&escape(&value)).unwrap(), value);
+        }
+
+        if let Ok(Some(s)) = unescape_str(field) {
+            if let Ok(array) = parse_array(&s) {
+                assert_eq!(parse_array(&array.to_string()).unwrap(), array);
+            }
+            if let Ok(composite) = parse_composite(&s) {
+                assert_eq!(parse_composite(&composite.to_string()).unwrap(), composite);
+            }
+        }
+    }
+});
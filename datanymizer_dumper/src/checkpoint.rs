@@ -0,0 +1,417 @@
+//! The checkpoints of the dumps to a file (`--checkpoint`). The checkpoint file gets the checksum of the resolved
+//! config, the pepper fingerprint, the schema fingerprint (the tables and columns) and the output offset before
+//! the first table, then
+//! the name and the output offset of every table after its data is written and flushed.
+//!
+//! A dump with an existing checkpoint is resumed: the output is truncated to the last offset, the output
+//! of the stages before the first table that isn't dumped yet is discarded (the schema before the data
+//! is already there), the dumped tables are skipped. The checkpoint is removed after the dump is finished.
+
+use crate::{manifest, Table};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The first line of the checkpoint file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    config_checksum: String,
+    /// The salted values of the resumed dump are the same only with the same pepper
+    #[serde(default)]
+    pepper_fingerprint: String,
+    schema_fingerprint: String,
+    /// The output offset before the first table
+    offset: u64,
+}
+
+/// The line of a table that is dumped (or skipped)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TableEntry {
+    table: String,
+    /// The output offset after the table
+    offset: u64,
+}
+
+struct OutputState {
+    writer: BufWriter<File>,
+    offset: u64,
+    /// The output of the resumed dump is discarded until the first table that isn't dumped yet
+    discarding: bool,
+}
+
+/// The output file of a dump with a checkpoint
+pub struct CheckpointOutput(Arc<Mutex<OutputState>>);
+
+impl CheckpointOutput {
+    fn state(&self) -> io::Result<MutexGuard<'_, OutputState>> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("Can't access the dump output"))
+    }
+}
+
+impl Write for CheckpointOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state()?;
+        if state.discarding {
+            return Ok(buf.len());
+        }
+        let written = state.writer.write(buf)?;
+        state.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state()?.writer.flush()
+    }
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    config_checksum: String,
+    pepper_fingerprint: String,
+    /// The header of the resumed dump
+    resumed: Option<Header>,
+    /// The tables dumped before the checkpoint
+    entries: Vec<TableEntry>,
+    done: HashSet<String>,
+    schema_fingerprint: Option<String>,
+    output: Arc<Mutex<OutputState>>,
+    file: Option<File>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint and the dump output. The dump is resumed if the checkpoint exists
+    /// (the output is truncated to its last offset), otherwise the output is created.
+    /// The `config` is the resolved config (`Settings::resolved_config`).
+    pub fn open(
+        path: impl Into<PathBuf>,
+        output: &Path,
+        config: &[u8],
+        pepper_fingerprint: &str,
+    ) -> Result<(Self, CheckpointOutput)> {
+        let path = path.into();
+        let config_checksum = manifest::checksum(config);
+        let (resumed, entries) = read(&path)?;
+
+        let (file, offset) = match &resumed {
+            Some(header) => {
+                if header.config_checksum != config_checksum {
+                    return Err(anyhow!(
+                        "The config is changed since the checkpoint {} is written, \
+                        remove the checkpoint to dump from the start",
+                        path.display()
+                    ));
+                }
+                if header.pepper_fingerprint != pepper_fingerprint {
+                    return Err(anyhow!(
+                        "The pepper is changed since the checkpoint {} is written (use the same \
+                        --pepper-file or --seed), remove the checkpoint to dump from the start",
+                        path.display()
+                    ));
+                }
+                let offset = entries.last().map_or(header.offset, |e| e.offset);
+                let mut file = OpenOptions::new().write(true).open(output).map_err(|e| {
+                    anyhow!(
+                        "Can't resume the dump {} from the checkpoint: {}",
+                        output.display(),
+                        e
+                    )
+                })?;
+                if file.metadata()?.len() < offset {
+                    return Err(anyhow!(
+                        "Can't resume the dump {} from the checkpoint: the dump is shorter than the checkpoint offset {}",
+                        output.display(),
+                        offset
+                    ));
+                }
+                file.set_len(offset)?;
+                file.seek(SeekFrom::End(0))?;
+                (file, offset)
+            }
+            None => (File::create(output)?, 0),
+        };
+
+        let output = Arc::new(Mutex::new(OutputState {
+            writer: BufWriter::new(file),
+            offset,
+            discarding: resumed.is_some(),
+        }));
+        let checkpoint = Self {
+            path,
+            config_checksum,
+            pepper_fingerprint: pepper_fingerprint.to_string(),
+            done: entries.iter().map(|e| e.table.clone()).collect(),
+            entries,
+            resumed,
+            schema_fingerprint: None,
+            output: output.clone(),
+            file: None,
+        };
+        Ok((checkpoint, CheckpointOutput(output)))
+    }
+
+    pub fn is_resumed(&self) -> bool {
+        self.resumed.is_some()
+    }
+
+    /// Whether the table is dumped before the checkpoint
+    pub fn is_done(&self, table: &str) -> bool {
+        self.done.contains(table)
+    }
+
+    /// The count of the tables dumped before the checkpoint
+    pub fn done_count(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Checks that the tables and their columns are the same as in the resumed dump
+    pub fn check_schema<T, D: Table<T>>(&mut self, tables: &[(D, i32)]) -> Result<()> {
+        let fingerprint = schema_fingerprint(tables);
+        if let Some(header) = &self.resumed {
+            if header.schema_fingerprint != fingerprint {
+                return Err(anyhow!(
+                    "The tables or columns are changed since the checkpoint {} is written, \
+                    remove the checkpoint to dump from the start",
+                    self.path.display()
+                ));
+            }
+        }
+        self.schema_fingerprint = Some(fingerprint);
+        Ok(())
+    }
+
+    /// Starts the checkpoint before the first table. The checkpoint of the resumed dump is written again
+    /// (without an incomplete last line).
+    pub fn begin(&mut self) -> Result<()> {
+        let header = match &self.resumed {
+            Some(header) => header.clone(),
+            None => Header {
+                config_checksum: self.config_checksum.clone(),
+                pepper_fingerprint: self.pepper_fingerprint.clone(),
+                schema_fingerprint: self.schema_fingerprint.clone().unwrap_or_default(),
+                offset: self.flush_output()?,
+            },
+        };
+        let mut file = File::create(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        for entry in &self.entries {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        file.sync_data()?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// The output of the resumed dump is written again (from the first table that isn't dumped yet)
+    pub fn continue_output(&self) -> Result<()> {
+        self.lock_output()?.discarding = false;
+        Ok(())
+    }
+
+    /// Records the table after its data is written (the output is flushed to the disk)
+    pub fn table_done(&mut self, table: &str) -> Result<()> {
+        let entry = TableEntry {
+            table: table.to_string(),
+            offset: self.flush_output()?,
+        };
+        let file = self
+            .file
+            .as_mut()
+            .ok_or_else(|| anyhow!("The checkpoint isn't started"))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Removes the checkpoint after the dump is finished
+    pub fn finish(&mut self) -> Result<()> {
+        self.flush_output()?;
+        self.file = None;
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Returns the output offset
+    fn flush_output(&self) -> Result<u64> {
+        let mut output = self.lock_output()?;
+        output.writer.flush()?;
+        output.writer.get_ref().sync_data()?;
+        Ok(output.offset)
+    }
+
+    fn lock_output(&self) -> Result<MutexGuard<'_, OutputState>> {
+        self.output
+            .lock()
+            .map_err(|_| anyhow!("Can't access the dump output"))
+    }
+}
+
+// The header and the tables of an existing checkpoint (an incomplete last line is ignored)
+fn read(path: &Path) -> Result<(Option<Header>, Vec<TableEntry>)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((None, vec![])),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next().transpose()? {
+        Some(line) => match serde_json::from_str(&line) {
+            Ok(header) => header,
+            Err(_) => return Ok((None, vec![])),
+        },
+        None => return Ok((None, vec![])),
+    };
+    let mut entries = vec![];
+    for line in lines {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => break,
+        }
+    }
+    Ok((Some(header), entries))
+}
+
+/// The checksum of the table names and their columns (in the dump order)
+pub fn schema_fingerprint<T, D: Table<T>>(tables: &[(D, i32)]) -> String {
+    let schema: String = tables
+        .iter()
+        .map(|(table, _)| {
+            format!(
+                "{}({})\n",
+                table.get_full_name(),
+                table.get_columns_names().join(",")
+            )
+        })
+        .collect();
+    manifest::checksum(schema.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn paths(name: &str) -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir();
+        let checkpoint = dir.join(format!("datanymizer_checkpoint_{}.jsonl", name));
+        let output = dir.join(format!("datanymizer_checkpoint_{}.sql", name));
+        let _ = fs::remove_file(&checkpoint);
+        (checkpoint, output)
+    }
+
+    #[test]
+    fn resume() {
+        let (path, output_path) = paths("resume");
+
+        let (mut checkpoint, mut output) =
+            Checkpoint::open(&path, &output_path, b"a", "p").unwrap();
+        assert!(!checkpoint.is_resumed());
+        output.write_all(b"schema;").unwrap();
+        checkpoint.begin().unwrap();
+        output.write_all(b"users;").unwrap();
+        checkpoint.table_done("public.users").unwrap();
+        // the table isn't finished
+        output.write_all(b"ord").unwrap();
+        drop((checkpoint, output));
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "schema;users;ord"
+        );
+
+        let (mut checkpoint, mut output) =
+            Checkpoint::open(&path, &output_path, b"a", "p").unwrap();
+        assert!(checkpoint.is_resumed());
+        assert!(checkpoint.is_done("public.users"));
+        assert!(!checkpoint.is_done("public.orders"));
+        assert_eq!(checkpoint.done_count(), 1);
+        // discarded
+        output.write_all(b"schema;").unwrap();
+        checkpoint.begin().unwrap();
+        checkpoint.continue_output().unwrap();
+        output.write_all(b"orders;").unwrap();
+        checkpoint.table_done("public.orders").unwrap();
+        output.write_all(b"indexes;").unwrap();
+        checkpoint.finish().unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "schema;users;orders;indexes;"
+        );
+        fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn changed_config() {
+        let (path, output_path) = paths("changed_config");
+        let (mut checkpoint, _output) = Checkpoint::open(&path, &output_path, b"a", "p").unwrap();
+        checkpoint.begin().unwrap();
+
+        let error = Checkpoint::open(&path, &output_path, b"b", "p")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "The config is changed since the checkpoint {} is written, \
+                remove the checkpoint to dump from the start",
+                path.display()
+            )
+        );
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn changed_pepper() {
+        let (path, output_path) = paths("changed_pepper");
+        let (mut checkpoint, _output) = Checkpoint::open(&path, &output_path, b"a", "p").unwrap();
+        checkpoint.begin().unwrap();
+
+        let error = Checkpoint::open(&path, &output_path, b"a", "q")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "The pepper is changed since the checkpoint {} is written (use the same \
+                --pepper-file or --seed), remove the checkpoint to dump from the start",
+                path.display()
+            )
+        );
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn incomplete_checkpoint() {
+        let (path, _) = paths("incomplete");
+        let header = r#"{"config_checksum":"x","schema_fingerprint":"y","offset":3}"#;
+        fs::write(
+            &path,
+            format!("{}\n{{\"table\":\"a\",\"offset\":5}}\n{{\"tab", header),
+        )
+        .unwrap();
+        let (header, entries) = read(&path).unwrap();
+        assert_eq!(header.unwrap().offset, 3);
+        assert_eq!(
+            entries,
+            [TableEntry {
+                table: "a".to_string(),
+                offset: 5
+            }]
+        );
+
+        fs::write(&path, "{\"conf").unwrap();
+        assert_eq!(read(&path).unwrap(), (None, vec![]));
+        fs::remove_file(path).unwrap();
+    }
+}
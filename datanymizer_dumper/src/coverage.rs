@@ -0,0 +1,461 @@
+use crate::{manifest::Manifest, secret_scan::SecretSuspect};
+use datanymizer_engine::RuleCategory;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+/// Anonymization coverage of a table: which columns are anonymized, ignored or dumped as is
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    /// Columns with rules (the `none` rules and the skipped rules are not counted)
+    pub ruled: Vec<String>,
+    /// Columns from the `ignore` config section
+    pub ignored: Vec<String>,
+    /// Columns that are dumped as is without a reason in the config
+    pub passed_through: Vec<String>,
+    /// Passed through columns whose names look like personal data, with the suspected categories
+    pub pii_suspects: BTreeMap<String, String>,
+    /// Passed through text columns whose sampled values look like secrets (the top columns by matches)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secret_suspects: BTreeMap<String, SecretSuspect>,
+}
+
+impl Coverage {
+    /// `columns` are all columns of the table, `ruled` and `ignored` are the column names
+    pub fn new<'a>(
+        columns: &[String],
+        ruled: impl IntoIterator<Item = &'a String>,
+        ignored: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        let ruled: BTreeSet<_> = ruled.into_iter().cloned().collect();
+        let ignored: BTreeSet<_> = ignored
+            .into_iter()
+            .filter(|c| !ruled.contains(*c))
+            .cloned()
+            .collect();
+        let mut passed_through: Vec<_> = columns
+            .iter()
+            .filter(|c| !ruled.contains(*c) && !ignored.contains(*c))
+            .cloned()
+            .collect();
+        passed_through.sort();
+        let pii_suspects = passed_through
+            .iter()
+            .filter_map(|c| RuleCategory::of_column(c).map(|cat| (c.clone(), cat.to_string())))
+            .collect();
+
+        Self {
+            ruled: ruled.into_iter().collect(),
+            ignored: ignored.into_iter().collect(),
+            passed_through,
+            pii_suspects,
+            secret_suspects: BTreeMap::new(),
+        }
+    }
+
+    fn columns(&self) -> BTreeSet<&String> {
+        self.ruled
+            .iter()
+            .chain(&self.ignored)
+            .chain(&self.passed_through)
+            .collect()
+    }
+}
+
+/// Coverage changes between two manifests. Regressions are the columns that lost their rules
+/// and the new passed through columns that look like personal data or contain secrets. Added and removed tables and columns
+/// (schema drift) are reported separately.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageDiff {
+    pub added_tables: Vec<String>,
+    pub removed_tables: Vec<String>,
+    /// Tables without coverage records in one of the manifests (made by older versions), they aren't compared
+    pub unknown_tables: Vec<String>,
+    pub tables: Vec<TableCoverageDiff>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TableCoverageDiff {
+    pub table: String,
+    /// Columns that had rules and are passed through now
+    pub lost_rules: Vec<String>,
+    /// New PII suspects with the suspected categories (except the columns with lost rules)
+    pub new_pii_suspects: BTreeMap<String, String>,
+    /// New secret suspects with the kinds of the secrets (except the columns with lost rules)
+    pub new_secret_suspects: BTreeMap<String, String>,
+    /// Columns that have rules now (and were passed through or ignored)
+    pub new_rules: Vec<String>,
+    /// Columns that are ignored now (and were passed through or had rules)
+    pub newly_ignored: Vec<String>,
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+}
+
+impl CoverageDiff {
+    pub fn new(old: &Manifest, new: &Manifest) -> Self {
+        let mut diff = Self::default();
+        let empty = Coverage::default();
+
+        for (name, new_table) in &new.tables {
+            let old_coverage = match old.tables.get(name) {
+                Some(old_table) => old_table.coverage.as_ref(),
+                None => {
+                    diff.added_tables.push(name.clone());
+                    Some(&empty)
+                }
+            };
+            match (old_coverage, &new_table.coverage) {
+                (Some(old_coverage), Some(new_coverage)) => {
+                    let mut table_diff = TableCoverageDiff::new(name, old_coverage, new_coverage);
+                    if !old.tables.contains_key(name) {
+                        // all columns of a new table are added, it is reported as a table
+                        table_diff.added_columns.clear();
+                    }
+                    if !table_diff.is_empty() {
+                        diff.tables.push(table_diff);
+                    }
+                }
+                _ => diff.unknown_tables.push(name.clone()),
+            }
+        }
+        diff.removed_tables = old
+            .tables
+            .keys()
+            .filter(|name| !new.tables.contains_key(*name))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    /// Some columns lost their rules or there are new PII or secret suspects
+    pub fn has_regressions(&self) -> bool {
+        self.tables.iter().any(|t| t.has_regressions())
+    }
+
+    fn has_schema_drift(&self) -> bool {
+        !self.added_tables.is_empty()
+            || !self.removed_tables.is_empty()
+            || self
+                .tables
+                .iter()
+                .any(|t| !t.added_columns.is_empty() || !t.removed_columns.is_empty())
+    }
+}
+
+impl TableCoverageDiff {
+    fn new(table: &str, old: &Coverage, new: &Coverage) -> Self {
+        let (old_columns, new_columns) = (old.columns(), new.columns());
+        let was = |column: &String, list: &[String]| list.contains(column);
+
+        let lost_rules: Vec<_> = new
+            .passed_through
+            .iter()
+            .filter(|c| was(c, &old.ruled))
+            .cloned()
+            .collect();
+        let new_pii_suspects = new
+            .pii_suspects
+            .iter()
+            .filter(|(c, _)| !old.pii_suspects.contains_key(*c) && !lost_rules.contains(c))
+            .map(|(c, category)| (c.clone(), category.clone()))
+            .collect();
+        let new_secret_suspects = new
+            .secret_suspects
+            .iter()
+            .filter(|(c, _)| !old.secret_suspects.contains_key(*c) && !lost_rules.contains(c))
+            .map(|(c, suspect)| (c.clone(), suspect.kind.clone()))
+            .collect();
+
+        Self {
+            table: table.to_string(),
+            lost_rules,
+            new_pii_suspects,
+            new_secret_suspects,
+            new_rules: new
+                .ruled
+                .iter()
+                .filter(|c| old_columns.contains(c) && !was(c, &old.ruled))
+                .cloned()
+                .collect(),
+            newly_ignored: new
+                .ignored
+                .iter()
+                .filter(|c| old_columns.contains(c) && !was(c, &old.ignored))
+                .cloned()
+                .collect(),
+            added_columns: new_columns
+                .difference(&old_columns)
+                .map(|c| c.to_string())
+                .collect(),
+            removed_columns: old_columns
+                .difference(&new_columns)
+                .map(|c| c.to_string())
+                .collect(),
+        }
+    }
+
+    fn has_regressions(&self) -> bool {
+        !self.lost_rules.is_empty()
+            || !self.new_pii_suspects.is_empty()
+            || !self.new_secret_suspects.is_empty()
+    }
+
+    fn has_improvements(&self) -> bool {
+        !self.new_rules.is_empty() || !self.newly_ignored.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.has_regressions()
+            && !self.has_improvements()
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+    }
+}
+
+impl Display for CoverageDiff {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.has_regressions() {
+            writeln!(f, "Coverage regressions:")?;
+            for table in self.tables.iter().filter(|t| t.has_regressions()) {
+                for column in &table.lost_rules {
+                    writeln!(f, "  {}.{}: lost its rule", table.table, column)?;
+                }
+                for (column, category) in &table.new_pii_suspects {
+                    writeln!(
+                        f,
+                        "  {}.{}: looks like {} data, but it is passed through",
+                        table.table, column, category
+                    )?;
+                }
+                for (column, kind) in &table.new_secret_suspects {
+                    writeln!(
+                        f,
+                        "  {}.{}: has values that look like secrets ({}), but it is passed through",
+                        table.table, column, kind
+                    )?;
+                }
+            }
+        } else {
+            writeln!(f, "No coverage regressions")?;
+        }
+
+        if self.tables.iter().any(|t| t.has_improvements()) {
+            writeln!(f, "Coverage improvements:")?;
+            for table in &self.tables {
+                for column in &table.new_rules {
+                    writeln!(f, "  {}.{}: has a rule", table.table, column)?;
+                }
+                for column in &table.newly_ignored {
+                    writeln!(f, "  {}.{}: ignored", table.table, column)?;
+                }
+            }
+        }
+
+        if self.has_schema_drift() {
+            writeln!(f, "Schema changes:")?;
+            for table in &self.added_tables {
+                writeln!(f, "  + {}", table)?;
+            }
+            for table in &self.removed_tables {
+                writeln!(f, "  - {}", table)?;
+            }
+            for table in &self.tables {
+                for column in &table.added_columns {
+                    writeln!(f, "  + {}.{}", table.table, column)?;
+                }
+                for column in &table.removed_columns {
+                    writeln!(f, "  - {}.{}", table.table, column)?;
+                }
+            }
+        }
+
+        if !self.unknown_tables.is_empty() {
+            writeln!(
+                f,
+                "Tables without coverage records (not compared): {}",
+                self.unknown_tables.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::TableManifest;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn coverage(columns: &[&str], ruled: &[&str], ignored: &[&str]) -> Coverage {
+        Coverage::new(&strings(columns), &strings(ruled), &strings(ignored))
+    }
+
+    fn manifest(tables: Vec<(&str, Option<Coverage>)>) -> Manifest {
+        let mut m = Manifest::new("0.5.0".to_string(), "0".to_string());
+        for (name, coverage) in tables {
+            m.tables.insert(
+                name.to_string(),
+                TableManifest {
+                    coverage,
+                    ..TableManifest::default()
+                },
+            );
+        }
+        m
+    }
+
+    #[test]
+    fn new() {
+        let c = coverage(
+            &["id", "email", "phone", "status", "created_at"],
+            &["email"],
+            &["id"],
+        );
+        assert_eq!(c.ruled, vec!["email"]);
+        assert_eq!(c.ignored, vec!["id"]);
+        assert_eq!(c.passed_through, vec!["created_at", "phone", "status"]);
+        assert_eq!(
+            c.pii_suspects,
+            BTreeMap::from([
+                ("created_at".to_string(), "date".to_string()),
+                ("phone".to_string(), "phone".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn no_changes() {
+        let m = manifest(vec![(
+            "public.users",
+            Some(coverage(&["id", "email"], &["email"], &[])),
+        )]);
+        let diff = CoverageDiff::new(&m, &m.clone());
+
+        assert!(!diff.has_regressions());
+        assert_eq!(diff, CoverageDiff::default());
+        assert_eq!(diff.to_string(), "No coverage regressions\n");
+    }
+
+    #[test]
+    fn regressions() {
+        let old = manifest(vec![
+            (
+                "public.users",
+                Some(coverage(
+                    &["id", "email", "name", "fax"],
+                    &["email"],
+                    &["id"],
+                )),
+            ),
+            ("public.orders", Some(coverage(&["id"], &[], &[]))),
+            ("public.logs", None),
+        ]);
+        let new = manifest(vec![
+            (
+                "public.users",
+                Some(coverage(
+                    &["id", "email", "name", "phone"],
+                    &["name"],
+                    &["id"],
+                )),
+            ),
+            (
+                "public.contacts",
+                Some(coverage(&["id", "first_name"], &[], &[])),
+            ),
+            ("public.logs", Some(coverage(&["id"], &[], &[]))),
+        ]);
+
+        let diff = CoverageDiff::new(&old, &new);
+        assert!(diff.has_regressions());
+        assert_eq!(diff.added_tables, vec!["public.contacts"]);
+        assert_eq!(diff.removed_tables, vec!["public.orders"]);
+        assert_eq!(diff.unknown_tables, vec!["public.logs"]);
+        assert_eq!(
+            diff.to_string(),
+            "Coverage regressions:\n  \
+             public.contacts.first_name: looks like name data, but it is passed through\n  \
+             public.users.email: lost its rule\n  \
+             public.users.phone: looks like phone data, but it is passed through\n\
+             Coverage improvements:\n  \
+             public.users.name: has a rule\n\
+             Schema changes:\n  \
+             + public.contacts\n  \
+             - public.orders\n  \
+             + public.users.phone\n  \
+             - public.users.fax\n\
+             Tables without coverage records (not compared): public.logs\n"
+        );
+    }
+
+    #[test]
+    fn secret_suspects() {
+        let suspect = |kind: &str| SecretSuspect {
+            kind: kind.to_string(),
+            matches: 3,
+            sampled: 10,
+            example: "sk_l… (32 chars)".to_string(),
+        };
+        let mut old_coverage = coverage(&["id", "token", "key"], &[], &[]);
+        old_coverage
+            .secret_suspects
+            .insert("key".to_string(), suspect("private_key"));
+        let mut new_coverage = old_coverage.clone();
+        new_coverage
+            .secret_suspects
+            .insert("token".to_string(), suspect("api_key"));
+
+        let diff = CoverageDiff::new(
+            &manifest(vec![("public.clients", Some(old_coverage))]),
+            &manifest(vec![("public.clients", Some(new_coverage))]),
+        );
+        assert!(diff.has_regressions());
+        assert_eq!(
+            diff.to_string(),
+            "Coverage regressions:\n  \
+             public.clients.token: has values that look like secrets (api_key), but it is passed through\n"
+        );
+
+        // the manifests without secret suspects
+        let json = serde_json::to_string(&coverage(&["id"], &[], &[])).unwrap();
+        assert!(!json.contains("secret_suspects"));
+        let parsed: Coverage = serde_json::from_str(&json).unwrap();
+        assert!(parsed.secret_suspects.is_empty());
+    }
+
+    #[test]
+    fn schema_drift_only() {
+        let old = manifest(vec![
+            (
+                "public.users",
+                Some(coverage(&["id", "email"], &["email"], &[])),
+            ),
+            ("public.orders", Some(coverage(&["id"], &[], &[]))),
+        ]);
+        let new = manifest(vec![
+            (
+                "public.users",
+                Some(coverage(&["id", "email", "status"], &["email"], &[])),
+            ),
+            ("public.items", Some(coverage(&["id", "title"], &[], &[]))),
+        ]);
+
+        let diff = CoverageDiff::new(&old, &new);
+        assert!(!diff.has_regressions());
+        assert_eq!(
+            diff.to_string(),
+            "No coverage regressions\n\
+             Schema changes:\n  \
+             + public.items\n  \
+             - public.orders\n  \
+             + public.users.status\n"
+        );
+    }
+}
@@ -1,78 +1,830 @@
+//! Progress reporting of the dumping.
+//!
+//! The dumper calls the hooks of an [`Indicator`] for the table lifecycle events:
+//!
+//! * [`Indicator::dump_started`] with all tables to dump,
+//! * [`Indicator::table_skipped`] for every table dumped without data (it isn't counted in the progress),
+//! * [`Indicator::table_started`], [`Indicator::data_progress`] (for every dumped row, it calls
+//!   [`Indicator::rows_progress`] by default) and [`Indicator::table_finished`] or [`Indicator::table_failed`]
+//!   for every table,
+//! * [`Indicator::dump_finished`] with the summary (it is called for failed dumps too).
+//!
+//! Messages are passed to [`Indicator::debug_msg`], and warnings to [`Indicator::warning_msg`]
+//! (it calls `debug_msg` with the `Warning: ` prefix by default).
+//!
+//! All hooks have no-op default implementations. Several indicators can be combined
+//! with [`MultiIndicator`].
+//!
+//! [`ConsoleIndicator`] shows the progress bars with the throughput and the ETA, [`JsonLinesIndicator`]
+//! writes the events as JSON lines for orchestration systems. Both write to stderr, because stdout
+//! may carry the dump.
+
+use anyhow::Error;
+use datanymizer_engine::{format_size, memory::MemoryUsage};
 use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
-use std::time::Duration;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How often the throughput of the progress bar is updated
+const CONSOLE_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// The progress events are written when the percentage changes or after this interval
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A table to dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    /// Full table name
+    pub name: String,
+    /// Estimated count of rows to dump
+    pub rows: u64,
+}
+
+/// A dumped table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableStats {
+    /// Full table name
+    pub name: String,
+    /// Count of dumped rows
+    pub rows: u64,
+    /// Size of the dumped data (in the COPY format)
+    pub bytes: u64,
+    pub duration: Duration,
+    /// The counts of the rows by the event types (only for outbox tables)
+    pub outbox_events: BTreeMap<String, u64>,
+    /// The estimated size of the omitted columns (only for the projected tables with statistics)
+    pub saved_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DumpSummary {
+    /// Count of dumped tables
+    pub tables: usize,
+    /// Count of dumped rows (in all tables)
+    pub rows: u64,
+    /// Size of the dumped data (in all tables)
+    pub bytes: u64,
+    pub duration: Duration,
+    /// The error message if the dump is failed
+    pub error: Option<String>,
+    /// Whether the source database is a replica
+    pub replica: bool,
+    /// Count of the tables dumped again after conflicts with recovery on the replica
+    pub conflict_retries: u32,
+    /// Tables dropped or renamed after the inspection (skipped with `MissingTablePolicy::Skip`)
+    pub skipped_tables: Vec<String>,
+    /// Shuffled tables with the shuffling methods (`client` or `server`)
+    pub shuffled_tables: BTreeMap<String, &'static str>,
+    /// The counts of the rows of the outbox tables by the event types
+    pub outbox_events: BTreeMap<String, BTreeMap<String, u64>>,
+    /// The estimated sizes of the omitted columns of the projected tables (`only_columns`)
+    pub saved_bytes: BTreeMap<String, u64>,
+    /// The accounted memory with the peaks of the components
+    pub memory: MemoryUsage,
+}
+
+impl DumpSummary {
+    pub fn add_table(&mut self, stats: &TableStats) {
+        self.tables += 1;
+        self.rows += stats.rows;
+        self.bytes += stats.bytes;
+        if !stats.outbox_events.is_empty() {
+            self.outbox_events
+                .insert(stats.name.clone(), stats.outbox_events.clone());
+        }
+        if let Some(saved_bytes) = stats.saved_bytes {
+            self.saved_bytes.insert(stats.name.clone(), saved_bytes);
+        }
+    }
+}
+
+/// The amount per second (`0` for the zero duration)
+pub fn per_second(amount: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        amount as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+/// The remaining time if the rate stays the same (`None` if nothing is done yet)
+pub fn eta(done: u64, estimated: u64, elapsed: Duration) -> Option<Duration> {
+    let rate = per_second(done, elapsed);
+    (rate > 0.0).then(|| Duration::from_secs_f64(estimated.saturating_sub(done) as f64 / rate))
+}
+
+// E.g. `1.5 MB/s`
+fn format_throughput(bytes_per_second: f64) -> String {
+    format!("{:.1} MB/s", bytes_per_second / (1 << 20) as f64)
+}
 
 pub trait Indicator {
-    fn start_pb(&self, _size: u64, _prefix: &str) {}
+    fn dump_started(&self, _tables: &[TableInfo]) {}
+
+    fn table_started(&self, _table: &TableInfo) {}
+
+    /// `dumped` is the count of the table rows dumped so far
+    fn rows_progress(&self, _table: &TableInfo, _dumped: u64) {}
+
+    /// `bytes` is the size of the table data dumped so far
+    fn data_progress(&self, table: &TableInfo, dumped: u64, _bytes: u64) {
+        self.rows_progress(table, dumped);
+    }
 
-    fn inc_pb(&self, _i: u64) {}
+    fn table_finished(&self, _stats: &TableStats) {}
 
-    fn finish_pb(&self, _name: &str, _duration: Duration) {}
+    fn table_failed(&self, _table: &TableInfo, _error: &Error) {}
+
+    /// The table is dumped without data (e.g., with `skip_data`), `reason` is an empty string if there are no details
+    fn table_skipped(&self, _table: &str, _reason: &str) {}
+
+    fn dump_finished(&self, _summary: &DumpSummary) {}
 
     fn debug_msg(&self, _msg: &str) {}
+
+    fn warning_msg(&self, msg: &str) {
+        self.debug_msg(&format!("Warning: {}", msg));
+    }
+}
+
+/// Allows to keep access to the indicator passed to the dumper
+impl<T: Indicator + ?Sized> Indicator for Arc<T> {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        (**self).dump_started(tables);
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        (**self).table_started(table);
+    }
+
+    fn rows_progress(&self, table: &TableInfo, dumped: u64) {
+        (**self).rows_progress(table, dumped);
+    }
+
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        (**self).data_progress(table, dumped, bytes);
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        (**self).table_finished(stats);
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &Error) {
+        (**self).table_failed(table, error);
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        (**self).table_skipped(table, reason);
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        (**self).dump_finished(summary);
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        (**self).debug_msg(msg);
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        (**self).warning_msg(msg);
+    }
 }
 
 pub struct SilentIndicator;
 
 impl Indicator for SilentIndicator {}
 
+/// The progress bars of the tables with the throughput, the table ETA and the dump ETA (by the estimated
+/// row counts), the messages are printed to stderr. If several tables are dumped at once (with `jobs`),
+/// the bar shows the first started one with the count of the others.
 pub struct ConsoleIndicator {
     pb: ProgressBar,
+    progress: Mutex<ConsoleProgress>,
+}
+
+#[derive(Default)]
+struct ConsoleProgress {
+    dump_started: Option<Instant>,
+    /// The estimated count of the rows of all tables
+    estimated_rows: u64,
+    /// The count of the rows of the finished tables
+    finished_rows: u64,
+    /// The tables that are dumped now (in the start order), the bar shows the first one
+    running: Vec<RunningTable>,
+    updated: Option<Instant>,
+}
+
+struct RunningTable {
+    info: TableInfo,
+    started: Instant,
+    dumped: u64,
+}
+
+impl ConsoleProgress {
+    // E.g. `1.5 MB/s, 2 minutes left`
+    fn message(&self, bytes: u64) -> Option<String> {
+        let throughput =
+            format_throughput(per_second(bytes, self.running.first()?.started.elapsed()));
+        let done = self.finished_rows + self.running.iter().map(|t| t.dumped).sum::<u64>();
+        match eta(done, self.estimated_rows, self.dump_started?.elapsed()) {
+            Some(eta) if self.estimated_rows > done => {
+                Some(format!("{}, {} left", throughput, HumanDuration(eta)))
+            }
+            _ => Some(throughput),
+        }
+    }
+
+    fn position(&self, table: &str) -> Option<usize> {
+        self.running.iter().position(|t| t.info.name == table)
+    }
+
+    // E.g. `public.users (+2 tables)`
+    fn prefix(&self) -> String {
+        match self.running.as_slice() {
+            [] => String::new(),
+            [table] => table.info.name.clone(),
+            [table, others @ ..] => format!("{} (+{} tables)", table.info.name, others.len()),
+        }
+    }
 }
 
 impl ConsoleIndicator {
     pub fn new() -> Self {
         Self::default()
     }
+
+    // Shows the first running table on the bar
+    fn show(&self, progress: &ConsoleProgress) {
+        let table = match progress.running.first() {
+            Some(table) => table,
+            None => return,
+        };
+        let delta = table.info.rows / 100;
+        self.pb.set_length(table.info.rows);
+        self.pb.set_draw_delta(delta);
+        self.pb.set_prefix(&progress.prefix());
+        self.pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "[Dumping: {prefix}] [|{bar:50}|] {pos} of {len} rows [{percent}%] ({eta}) {msg}",
+                )
+                .progress_chars("#>-"),
+        );
+        self.pb.set_position(table.dumped);
+    }
+
+    // Removes the table, the bar switches to the next running table if it showed this one
+    fn end_table(&self, table: &str, finish: impl FnOnce(&ProgressBar)) {
+        let mut progress = self.progress.lock().unwrap();
+        match progress.position(table) {
+            Some(0) => {
+                progress.running.remove(0);
+                finish(&self.pb);
+                self.pb.reset();
+                self.pb.set_message("");
+                progress.updated = None;
+                self.show(&progress);
+            }
+            Some(i) => {
+                progress.running.remove(i);
+                self.pb.set_prefix(&progress.prefix());
+            }
+            None => {}
+        }
+    }
 }
 
 impl Default for ConsoleIndicator {
     fn default() -> Self {
         let pb = ProgressBar::new(0);
-        Self { pb }
+        Self {
+            pb,
+            progress: Mutex::new(ConsoleProgress::default()),
+        }
     }
 }
 
 impl Indicator for ConsoleIndicator {
-    fn start_pb(&self, size: u64, name: &str) {
-        let delta = size / 100;
-        self.pb.set_length(size);
-        self.pb.set_draw_delta(delta);
-        self.pb.set_prefix(name);
-        self.pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "[Dumping: {prefix}] [|{bar:50}|] {pos} of {len} rows [{percent}%] ({eta})",
-                )
-                .progress_chars("#>-"),
-        );
+    fn dump_started(&self, tables: &[TableInfo]) {
+        let mut progress = self.progress.lock().unwrap();
+        *progress = ConsoleProgress {
+            dump_started: Some(Instant::now()),
+            estimated_rows: tables.iter().map(|t| t.rows).sum(),
+            ..ConsoleProgress::default()
+        };
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        let mut progress = self.progress.lock().unwrap();
+        if let Some(i) = progress.position(&table.name) {
+            // started again after a failed attempt
+            progress.running.remove(i);
+        }
+        progress.running.push(RunningTable {
+            info: table.clone(),
+            started: Instant::now(),
+            dumped: 0,
+        });
+        if progress.running.len() == 1 {
+            progress.updated = None;
+            self.show(&progress);
+        } else {
+            self.pb.set_prefix(&progress.prefix());
+        }
     }
 
-    fn inc_pb(&self, i: u64) {
-        self.pb.inc(i);
+    // The estimate of the rows is extended when the table has more rows (e.g. the statistics are stale)
+    fn rows_progress(&self, _table: &TableInfo, dumped: u64) {
+        if dumped > self.pb.length() {
+            self.pb.set_length(dumped);
+        }
+        self.pb.set_position(dumped);
+    }
+
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        let mut progress = self.progress.lock().unwrap();
+        match progress.position(&table.name) {
+            Some(i) => progress.running[i].dumped = dumped,
+            None => return,
+        }
+        if progress.running[0].info.name != table.name {
+            return;
+        }
+        self.rows_progress(table, dumped);
+
+        if progress
+            .updated
+            .is_some_and(|updated| updated.elapsed() < CONSOLE_UPDATE_INTERVAL)
+        {
+            return;
+        }
+        progress.updated = Some(Instant::now());
+        if let Some(message) = progress.message(bytes) {
+            self.pb.set_message(&message);
+        }
     }
 
-    fn finish_pb(&self, name: &str, duration: Duration) {
-        self.pb.finish();
-        self.pb.reset();
+    fn table_finished(&self, stats: &TableStats) {
+        self.end_table(&stats.name, |pb| pb.finish());
+        self.progress.lock().unwrap().finished_rows += stats.rows;
 
         self.debug_msg(
             format!(
-                "[Dumping: {}] Finished in {}",
-                name,
-                HumanDuration(duration)
+                "[Dumping: {}] Finished in {} ({})",
+                stats.name,
+                HumanDuration(stats.duration),
+                format_throughput(per_second(stats.bytes, stats.duration))
             )
             .as_str(),
         );
     }
 
+    fn table_failed(&self, table: &TableInfo, _error: &Error) {
+        self.end_table(&table.name, |pb| pb.abandon());
+
+        self.debug_msg(format!("[Dumping: {}] Failed", table.name).as_str());
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        eprintln!("{}", msg);
+    }
+}
+
+/// The format of the progress output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressFormat {
+    /// The progress bars (`ConsoleIndicator`)
+    #[default]
+    Console,
+    /// A JSON object per event (`JsonLinesIndicator`)
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "console" => Ok(Self::Console),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown progress format `{}` (console or json)", s)),
+        }
+    }
+}
+
+/// Writes a JSON object per event (to stderr by default) for orchestration systems:
+///
+/// ```text
+/// {"event":"dump_started","tables":2,"estimated_rows":150}
+/// {"event":"table_started","table":"public.users","estimated_rows":123}
+/// {"event":"table_progress","table":"public.users","rows":60,"bytes":4096,"estimated_rows":123,"pct":48,"elapsed_ms":500,"rows_per_sec":120.0,"bytes_per_sec":8192.0,"eta_ms":525}
+/// {"event":"table_finished","table":"public.users","rows":123,"bytes":8400,"elapsed_ms":1020,"rows_per_sec":120.6,"bytes_per_sec":8235.3}
+/// {"event":"table_failed","table":"public.orders","error":"..."}
+/// {"event":"table_skipped","table":"public.audit_log","reason":"skip_data"}
+/// {"event":"warning","message":"..."}
+/// {"event":"dump_finished","tables":1,"rows":123,"bytes":8400,"elapsed_ms":1500,"rows_per_sec":82.0,"bytes_per_sec":5600.0,"memory":{"used":0,"peak":0,"components":{}},"error":"..."}
+/// ```
+///
+/// The progress is written when the percentage changes (or every second), `pct` and `eta_ms` are `null`
+/// if the row count of the table is unknown. The debug messages are the `message` events.
+pub struct JsonLinesIndicator<W: Write + Send = io::Stderr> {
+    out: Mutex<W>,
+    /// By the table names (several tables are dumped at once with `jobs`)
+    progress: Mutex<HashMap<String, JsonProgress>>,
+}
+
+struct JsonProgress {
+    started: Instant,
+    pct: Option<u64>,
+    sent: Option<Instant>,
+}
+
+impl JsonLinesIndicator {
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+    }
+}
+
+impl<W: Write + Send> JsonLinesIndicator<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+            progress: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn write(&self, event: &str, fields: serde_json::Value) {
+        let mut line = json!({ "event": event });
+        if let (Some(line), serde_json::Value::Object(fields)) = (line.as_object_mut(), fields) {
+            line.extend(fields);
+        }
+        // the progress output never fails the dump
+        if let Ok(mut out) = self.out.lock() {
+            let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+        }
+    }
+}
+
+impl<W: Write + Send> Indicator for JsonLinesIndicator<W> {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        self.write(
+            "dump_started",
+            json!({
+                "tables": tables.len(),
+                "estimated_rows": tables.iter().map(|t| t.rows).sum::<u64>(),
+            }),
+        );
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        self.progress.lock().unwrap().insert(
+            table.name.clone(),
+            JsonProgress {
+                started: Instant::now(),
+                pct: None,
+                sent: None,
+            },
+        );
+        self.write(
+            "table_started",
+            json!({ "table": table.name, "estimated_rows": table.rows }),
+        );
+    }
+
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        let pct = (table.rows > 0).then(|| (dumped * 100 / table.rows).min(100));
+        let elapsed = {
+            let mut progress = self.progress.lock().unwrap();
+            let progress = match progress.get_mut(&table.name) {
+                Some(p) => p,
+                None => return,
+            };
+            if progress
+                .sent
+                .is_some_and(|sent| progress.pct == pct && sent.elapsed() < PROGRESS_INTERVAL)
+            {
+                return;
+            }
+            progress.pct = pct;
+            progress.sent = Some(Instant::now());
+            progress.started.elapsed()
+        };
+        self.write(
+            "table_progress",
+            json!({
+                "table": table.name,
+                "rows": dumped,
+                "bytes": bytes,
+                "estimated_rows": table.rows,
+                "pct": pct,
+                "elapsed_ms": elapsed.as_millis() as u64,
+                "rows_per_sec": per_second(dumped, elapsed),
+                "bytes_per_sec": per_second(bytes, elapsed),
+                "eta_ms": pct
+                    .and_then(|_| eta(dumped, table.rows, elapsed))
+                    .map(|eta| eta.as_millis() as u64),
+            }),
+        );
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.progress.lock().unwrap().remove(&stats.name);
+        self.write(
+            "table_finished",
+            json!({
+                "table": stats.name,
+                "rows": stats.rows,
+                "bytes": stats.bytes,
+                "elapsed_ms": stats.duration.as_millis() as u64,
+                "rows_per_sec": per_second(stats.rows, stats.duration),
+                "bytes_per_sec": per_second(stats.bytes, stats.duration),
+            }),
+        );
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &Error) {
+        self.progress.lock().unwrap().remove(&table.name);
+        self.write(
+            "table_failed",
+            json!({ "table": table.name, "error": error.to_string() }),
+        );
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        self.write("table_skipped", json!({ "table": table, "reason": reason }));
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        self.write(
+            "dump_finished",
+            json!({
+                "tables": summary.tables,
+                "rows": summary.rows,
+                "bytes": summary.bytes,
+                "elapsed_ms": summary.duration.as_millis() as u64,
+                "rows_per_sec": per_second(summary.rows, summary.duration),
+                "bytes_per_sec": per_second(summary.bytes, summary.duration),
+                "memory": summary.memory,
+                "error": summary.error,
+            }),
+        );
+    }
+
     fn debug_msg(&self, msg: &str) {
-        println!("{}", msg);
+        self.write("message", json!({ "message": msg }));
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        self.write("warning", json!({ "message": msg }));
+    }
+}
+
+/// The format of the `LogIndicator` lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// A JSON object per line (`level`, `message` and the event fields)
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown log format `{}` (text or json)", s)),
+        }
+    }
+}
+
+/// Prints the milestones only (no progress bars), for logs of non-interactive runs (e.g., CI jobs)
+#[derive(Default)]
+pub struct LogIndicator {
+    format: LogFormat,
+}
+
+impl LogIndicator {
+    pub fn new(format: LogFormat) -> Self {
+        Self { format }
+    }
+
+    fn line(&self, level: &str, message: &str, fields: serde_json::Value) -> String {
+        match self.format {
+            LogFormat::Text => message.to_string(),
+            LogFormat::Json => {
+                let mut line = json!({ "level": level, "message": message });
+                if let (Some(line), serde_json::Value::Object(fields)) =
+                    (line.as_object_mut(), fields)
+                {
+                    line.extend(fields);
+                }
+                line.to_string()
+            }
+        }
+    }
+
+    fn log(&self, level: &str, message: &str, fields: serde_json::Value) {
+        println!("{}", self.line(level, message, fields));
+    }
+}
+
+impl Indicator for LogIndicator {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        self.log(
+            "info",
+            &format!("Dumping {} tables", tables.len()),
+            json!({ "event": "dump_started", "tables": tables.len() }),
+        );
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.log(
+            "info",
+            &format!(
+                "[Dumping: {}] Finished in {} ({} rows)",
+                stats.name,
+                HumanDuration(stats.duration),
+                stats.rows
+            ),
+            json!({
+                "event": "table_finished",
+                "table": stats.name,
+                "rows": stats.rows,
+                "duration_ms": stats.duration.as_millis() as u64,
+            }),
+        );
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &Error) {
+        self.log(
+            "error",
+            &format!("[Dumping: {}] Failed: {}", table.name, error),
+            json!({ "event": "table_failed", "table": table.name, "error": error.to_string() }),
+        );
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        let message = if reason.is_empty() {
+            format!("[Dumping: {}] Skipped", table)
+        } else {
+            format!("[Dumping: {}] Skipped ({})", table, reason)
+        };
+        self.log(
+            "info",
+            &message,
+            json!({ "event": "table_skipped", "table": table, "reason": reason }),
+        );
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        let message = match &summary.error {
+            Some(e) => format!("Dump failed: {}", e),
+            None if summary.memory.peak > 0 => format!(
+                "Dump finished in {}: {} tables, {} rows, peak memory {}",
+                HumanDuration(summary.duration),
+                summary.tables,
+                summary.rows,
+                format_size(summary.memory.peak)
+            ),
+            None => format!(
+                "Dump finished in {}: {} tables, {} rows",
+                HumanDuration(summary.duration),
+                summary.tables,
+                summary.rows
+            ),
+        };
+        self.log(
+            if summary.error.is_some() {
+                "error"
+            } else {
+                "info"
+            },
+            &message,
+            json!({
+                "event": "dump_finished",
+                "tables": summary.tables,
+                "rows": summary.rows,
+                "duration_ms": summary.duration.as_millis() as u64,
+                "shuffled_tables": summary.shuffled_tables,
+                "outbox_events": summary.outbox_events,
+                "saved_bytes": summary.saved_bytes,
+                "memory": summary.memory,
+            }),
+        );
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        self.log("info", msg, json!({}));
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        match self.format {
+            LogFormat::Text => self.debug_msg(&format!("Warning: {}", msg)),
+            LogFormat::Json => self.log("warning", msg, json!({})),
+        }
+    }
+}
+
+/// Passes all events to several indicators (e.g., to the console and to a job system)
+#[derive(Default)]
+pub struct MultiIndicator {
+    indicators: Vec<Box<dyn Indicator + Send>>,
+}
+
+impl MultiIndicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<I: 'static + Indicator + Send>(mut self, indicator: I) -> Self {
+        self.indicators.push(Box::new(indicator));
+        self
+    }
+}
+
+impl Indicator for MultiIndicator {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        self.indicators.iter().for_each(|i| i.dump_started(tables));
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        self.indicators.iter().for_each(|i| i.table_started(table));
+    }
+
+    fn rows_progress(&self, table: &TableInfo, dumped: u64) {
+        self.indicators
+            .iter()
+            .for_each(|i| i.rows_progress(table, dumped));
+    }
+
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        self.indicators
+            .iter()
+            .for_each(|i| i.data_progress(table, dumped, bytes));
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.indicators.iter().for_each(|i| i.table_finished(stats));
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &Error) {
+        self.indicators
+            .iter()
+            .for_each(|i| i.table_failed(table, error));
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        self.indicators
+            .iter()
+            .for_each(|i| i.table_skipped(table, reason));
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        self.indicators
+            .iter()
+            .for_each(|i| i.dump_finished(summary));
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        self.indicators.iter().for_each(|i| i.debug_msg(msg));
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        self.indicators.iter().for_each(|i| i.warning_msg(msg));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::anyhow;
+
+    fn table() -> TableInfo {
+        TableInfo {
+            name: "public.users".to_string(),
+            rows: 100,
+        }
+    }
+
+    fn stats() -> TableStats {
+        TableStats {
+            name: "public.users".to_string(),
+            rows: 100,
+            bytes: 2 << 20,
+            duration: Duration::new(1, 0),
+            outbox_events: BTreeMap::new(),
+            saved_bytes: None,
+        }
+    }
 
     // just test that there is no panic
     mod console_indicator {
@@ -86,27 +838,371 @@ fn debug_msg() {
         #[test]
         fn pb_start_finish() {
             let ci = ConsoleIndicator::new();
-            ci.start_pb(100, "name");
-            ci.finish_pb("name", Duration::new(1, 0));
+            ci.table_started(&table());
+            ci.table_finished(&stats());
         }
 
         #[test]
         fn pb_some_progress() {
             let ci = ConsoleIndicator::new();
-            ci.start_pb(100, "name");
-            ci.inc_pb(1);
-            ci.inc_pb(10);
-            ci.finish_pb("name", Duration::new(1, 0));
+            ci.table_started(&table());
+            ci.rows_progress(&table(), 1);
+            ci.rows_progress(&table(), 11);
+            ci.table_finished(&stats());
         }
 
         #[test]
         fn pb_overflow_progress() {
             let ci = ConsoleIndicator::new();
-            ci.start_pb(100, "name");
-            ci.inc_pb(1);
-            ci.inc_pb(10);
-            ci.inc_pb(100);
-            ci.finish_pb("name", Duration::new(1, 0));
+            ci.table_started(&table());
+            ci.rows_progress(&table(), 1);
+            ci.rows_progress(&table(), 111);
+            // no more than 100%
+            assert_eq!(ci.pb.position(), 111);
+            assert_eq!(ci.pb.length(), 111);
+            ci.table_finished(&stats());
+        }
+
+        #[test]
+        fn pb_throughput() {
+            let ci = ConsoleIndicator::new();
+            ci.dump_started(&[table(), table()]);
+            ci.table_started(&table());
+            ci.data_progress(&table(), 1, 10);
+            ci.data_progress(&table(), 50, 500);
+            ci.table_finished(&stats());
+        }
+
+        #[test]
+        fn pb_interleaved_tables() {
+            let orders = TableInfo {
+                name: "public.orders".to_string(),
+                rows: 10,
+            };
+            let names = |ci: &ConsoleIndicator| -> Vec<_> {
+                let progress = ci.progress.lock().unwrap();
+                progress
+                    .running
+                    .iter()
+                    .map(|t| (t.info.name.clone(), t.dumped))
+                    .collect()
+            };
+
+            let ci = ConsoleIndicator::new();
+            ci.dump_started(&[table(), orders.clone()]);
+            ci.table_started(&table());
+            ci.table_started(&orders);
+            assert_eq!(
+                ci.progress.lock().unwrap().prefix(),
+                "public.users (+1 tables)"
+            );
+            ci.data_progress(&orders, 5, 50);
+            ci.data_progress(&table(), 20, 200);
+            assert_eq!(
+                names(&ci),
+                [
+                    ("public.users".to_string(), 20),
+                    ("public.orders".to_string(), 5)
+                ]
+            );
+            assert_eq!(ci.pb.position(), 20);
+
+            // the bar switches to the next table
+            ci.table_finished(&stats());
+            assert_eq!(names(&ci), [("public.orders".to_string(), 5)]);
+            assert_eq!(ci.pb.position(), 5);
+            assert_eq!(ci.progress.lock().unwrap().prefix(), "public.orders");
+            ci.table_failed(&orders, &anyhow!("error"));
+            assert!(names(&ci).is_empty());
+        }
+
+        #[test]
+        fn pb_failure() {
+            let ci = ConsoleIndicator::new();
+            ci.table_started(&table());
+            ci.rows_progress(&table(), 1);
+            ci.table_failed(&table(), &anyhow!("error"));
+        }
+    }
+
+    #[test]
+    fn log_indicator() {
+        let text = LogIndicator::new(LogFormat::Text);
+        assert_eq!(
+            text.line("info", "message", json!({ "event": "some" })),
+            "message"
+        );
+
+        let json = LogIndicator::new(LogFormat::Json);
+        let line: serde_json::Value =
+            serde_json::from_str(&json.line("warning", "message", json!({ "table": "users" })))
+                .unwrap();
+        assert_eq!(
+            line,
+            json!({ "level": "warning", "message": "message", "table": "users" })
+        );
+
+        // just test that there is no panic
+        json.dump_started(&[table()]);
+        json.table_finished(&stats());
+        json.table_failed(&table(), &anyhow!("error"));
+        json.table_skipped("public.logs", "skip_data");
+        json.dump_finished(&DumpSummary::default());
+        json.warning_msg("problem");
+
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!(
+            "xml".parse::<LogFormat>(),
+            Err("Unknown log format `xml` (text or json)".to_string())
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingIndicator(Mutex<Vec<String>>);
+
+    impl RecordingIndicator {
+        fn record(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl Indicator for RecordingIndicator {
+        fn table_started(&self, table: &TableInfo) {
+            self.record(format!("started {}", table.name));
+        }
+
+        fn table_failed(&self, table: &TableInfo, error: &Error) {
+            self.record(format!("failed {}: {}", table.name, error));
         }
+
+        fn debug_msg(&self, msg: &str) {
+            self.record(msg.to_string());
+        }
+    }
+
+    #[test]
+    fn multi_indicator() {
+        let first = Arc::new(RecordingIndicator::default());
+        let second = Arc::new(RecordingIndicator::default());
+        let multi = MultiIndicator::new()
+            .with(first.clone())
+            .with(SilentIndicator)
+            .with(second.clone());
+
+        multi.dump_started(&[table()]);
+        multi.table_started(&table());
+        multi.rows_progress(&table(), 1);
+        multi.table_failed(&table(), &anyhow!("error"));
+        multi.dump_finished(&DumpSummary::default());
+        multi.debug_msg("message");
+        multi.warning_msg("problem");
+
+        let expected = vec![
+            "started public.users".to_string(),
+            "failed public.users: error".to_string(),
+            "message".to_string(),
+            "Warning: problem".to_string(),
+        ];
+        assert_eq!(*first.0.lock().unwrap(), expected);
+        assert_eq!(*second.0.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn throughput() {
+        assert_eq!(per_second(300, Duration::from_secs(2)), 150.0);
+        assert_eq!(per_second(300, Duration::ZERO), 0.0);
+        assert_eq!(
+            eta(25, 100, Duration::from_secs(5)),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(eta(120, 100, Duration::from_secs(5)), Some(Duration::ZERO));
+        assert_eq!(eta(0, 100, Duration::from_secs(5)), None);
+        assert_eq!(format_throughput(1.5 * (1 << 20) as f64), "1.5 MB/s");
+
+        let started = Instant::now() - Duration::from_secs(10);
+        let progress = ConsoleProgress {
+            dump_started: Some(started),
+            estimated_rows: 1000,
+            finished_rows: 400,
+            running: vec![RunningTable {
+                info: table(),
+                started,
+                dumped: 100,
+            }],
+            updated: None,
+        };
+        let message = progress.message(10 << 20).unwrap();
+        assert!(
+            message.starts_with("1.0 MB/s, ") && message.ends_with(" left"),
+            "{}",
+            message
+        );
+        assert_eq!(
+            ConsoleProgress {
+                estimated_rows: 100,
+                ..progress
+            }
+            .message(10 << 20)
+            .unwrap(),
+            "1.0 MB/s"
+        );
+        assert_eq!(ConsoleProgress::default().message(1), None);
+    }
+
+    #[test]
+    fn progress_format() {
+        assert_eq!("console".parse(), Ok(ProgressFormat::Console));
+        assert_eq!("json".parse(), Ok(ProgressFormat::Json));
+        assert_eq!(
+            "xml".parse::<ProgressFormat>(),
+            Err("Unknown progress format `xml` (console or json)".to_string())
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines_indicator() {
+        let buffer = SharedBuffer::default();
+        let indicator = JsonLinesIndicator::new(buffer.clone());
+        indicator.dump_started(&[table(), table()]);
+        indicator.table_started(&table());
+        indicator.data_progress(&table(), 1, 10);
+        // the same percentage
+        indicator.data_progress(&table(), 1, 20);
+        indicator.data_progress(&table(), 50, 500);
+        indicator.table_finished(&stats());
+        indicator.table_failed(&table(), &anyhow!("error"));
+        indicator.warning_msg("problem");
+        indicator.debug_msg("message");
+        indicator.dump_finished(&DumpSummary {
+            tables: 1,
+            rows: 100,
+            bytes: 4000,
+            duration: Duration::from_secs(2),
+            ..DumpSummary::default()
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 9, "{}", output);
+        assert_eq!(
+            events[0],
+            json!({ "event": "dump_started", "tables": 2, "estimated_rows": 200 })
+        );
+        assert_eq!(
+            events[1],
+            json!({ "event": "table_started", "table": "public.users", "estimated_rows": 100 })
+        );
+        assert_eq!(events[2]["event"], "table_progress");
+        assert_eq!(events[2]["pct"], 1);
+        assert_eq!(events[3]["rows"], 50);
+        assert_eq!(events[3]["bytes"], 500);
+        assert_eq!(events[3]["estimated_rows"], 100);
+        assert_eq!(events[3]["pct"], 50);
+        assert!(events[3]["eta_ms"].is_u64());
+        assert!(events[3]["rows_per_sec"].is_f64());
+        assert_eq!(
+            events[4],
+            json!({
+                "event": "table_finished",
+                "table": "public.users",
+                "rows": 100,
+                "bytes": 2 << 20,
+                "elapsed_ms": 1000,
+                "rows_per_sec": 100.0,
+                "bytes_per_sec": (2 << 20) as f64,
+            })
+        );
+        assert_eq!(
+            events[5],
+            json!({ "event": "table_failed", "table": "public.users", "error": "error" })
+        );
+        assert_eq!(
+            events[6],
+            json!({ "event": "warning", "message": "problem" })
+        );
+        assert_eq!(
+            events[7],
+            json!({ "event": "message", "message": "message" })
+        );
+        assert_eq!(
+            events[8],
+            json!({
+                "event": "dump_finished",
+                "tables": 1,
+                "rows": 100,
+                "bytes": 4000,
+                "elapsed_ms": 2000,
+                "rows_per_sec": 50.0,
+                "bytes_per_sec": 2000.0,
+                "error": null,
+                "memory": { "used": 0, "peak": 0, "components": {} },
+            })
+        );
+
+        // the progress of the interleaved tables
+        let buffer = SharedBuffer::default();
+        let indicator = JsonLinesIndicator::new(buffer.clone());
+        let orders = TableInfo {
+            name: "public.orders".to_string(),
+            rows: 10,
+        };
+        indicator.table_started(&table());
+        indicator.table_started(&orders);
+        indicator.data_progress(&table(), 50, 500);
+        indicator.data_progress(&orders, 5, 50);
+        indicator.data_progress(&table(), 60, 600);
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let progress: Vec<_> = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|e| e["event"] == "table_progress")
+            .map(|e| (e["table"].as_str().unwrap().to_string(), e["pct"].as_u64()))
+            .collect();
+        assert_eq!(
+            progress,
+            [
+                ("public.users".to_string(), Some(50)),
+                ("public.orders".to_string(), Some(50)),
+                ("public.users".to_string(), Some(60)),
+            ]
+        );
+
+        // the progress of an unknown row count
+        let buffer = SharedBuffer::default();
+        let indicator = JsonLinesIndicator::new(buffer.clone());
+        let unknown = TableInfo { rows: 0, ..table() };
+        indicator.table_started(&unknown);
+        indicator.data_progress(&unknown, 5, 50);
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let progress: serde_json::Value =
+            serde_json::from_str(output.lines().last().unwrap()).unwrap();
+        assert_eq!(progress["pct"], serde_json::Value::Null);
+        assert_eq!(progress["eta_ms"], serde_json::Value::Null);
+
+        // the skipped tables
+        let buffer = SharedBuffer::default();
+        let indicator = JsonLinesIndicator::new(buffer.clone());
+        indicator.table_skipped("public.audit_log", "skip_data");
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(output.trim_end()).unwrap(),
+            json!({ "event": "table_skipped", "table": "public.audit_log", "reason": "skip_data" })
+        );
     }
 }
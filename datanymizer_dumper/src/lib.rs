@@ -2,11 +2,26 @@
 use core::iter::Iterator;
 use datanymizer_engine::{Filter, Settings};
 use indicatif::HumanDuration;
-use solvent::DepGraph;
-use std::{collections::HashMap, hash::Hash, time::Instant};
-
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Instant,
+};
+
+pub mod checkpoint;
+pub mod coverage;
 pub mod indicator;
+pub mod manifest;
+pub mod mysql;
 pub mod postgres;
+#[cfg(unix)]
+pub mod progress_socket;
+pub mod projection;
+pub mod secret_scan;
+pub mod service;
+pub mod staged;
+#[cfg(test)]
+mod test_tables;
 
 // Dumper makes dump with same stages
 pub trait Dumper: 'static + Sized + Send {
@@ -35,11 +50,7 @@ fn dump(&mut self, connection: &mut Self::Connection) -> Result<()> {
     fn post_data(&mut self, _connection: &mut Self::Connection) -> Result<()>;
 
     fn filter_table(&mut self, table: String, filter: &Option<Filter>) -> bool {
-        if let Some(f) = filter {
-            f.filter_schema(&table) && f.filter_data(&table)
-        } else {
-            true
-        }
+        filter_table(&table, filter)
     }
 
     fn schema_inspector(&self) -> Self::SchemaInspector;
@@ -51,6 +62,38 @@ fn filter_table(&mut self, table: String, filter: &Option<Filter>) -> bool {
     fn debug(&self, message: String);
 }
 
+/// Whether the schema and the data of the table are dumped
+pub fn filter_table(table: &str, filter: &Option<Filter>) -> bool {
+    if let Some(f) = filter {
+        f.filter_schema(table) && f.filter_data(table)
+    } else {
+        true
+    }
+}
+
+/// Every table (with itself) adds one to the weights of all tables it depends on,
+/// directly or transitively (the circular dependencies are visited once).
+/// So a table always weighs more than the tables that depend on it (except the tables of the same cycle),
+/// and sorting by the descending weight gives a parent-first (topological) order.
+pub fn dependency_weights<T: Hash + Eq + Clone>(
+    tables: &[T],
+    deps: &HashMap<T, Vec<T>>,
+) -> HashMap<T, i32> {
+    let mut res: HashMap<T, i32> = HashMap::new();
+    for table in tables.iter() {
+        let _ = res.entry(table.clone()).or_insert(0);
+        let mut seen = HashSet::new();
+        let mut stack = vec![table];
+        while let Some(node) = stack.pop() {
+            if seen.insert(node) {
+                *res.entry(node.clone()).or_insert(0) += 1;
+                stack.extend(deps.get(node).into_iter().flatten());
+            }
+        }
+    }
+    res
+}
+
 pub trait SchemaInspector: 'static + Sized + Send + Clone {
     type Type;
     type Connection;
@@ -72,29 +115,28 @@ fn get_dependencies(
     ) -> Result<Vec<Self::Table>>;
 
     fn ordered_tables(&self, connection: &mut Self::Connection) -> Vec<(Self::Table, i32)> {
-        let mut res: HashMap<Self::Table, i32> = HashMap::new();
-        let mut depgraph: DepGraph<Self::Table> = DepGraph::new();
         if let Ok(tables) = self.get_tables(connection) {
+            let mut deps: HashMap<Self::Table, Vec<Self::Table>> = HashMap::new();
             for table in tables.iter() {
-                let deps: Vec<Self::Table> = self
-                    .get_dependencies(connection, table)
-                    .unwrap_or_default()
-                    .into_iter()
-                    .collect();
-                depgraph.register_dependencies(table.clone(), deps);
+                deps.insert(
+                    table.clone(),
+                    self.get_dependencies(connection, table).unwrap_or_default(),
+                );
             }
 
-            for table in tables.iter() {
-                let _ = res.entry(table.clone()).or_insert(0);
-                if let Ok(nodes) = depgraph.dependencies_of(table) {
-                    for node in nodes.flatten() {
-                        let counter = res.entry(node.clone()).or_insert(0);
-                        *counter += 1;
-                    }
-                }
-            }
+            let mut res = dependency_weights(&tables, &deps);
+
+            // the tables keep the order of `get_tables` (the map order differs from run to run)
+            let mut result: Vec<(Self::Table, i32)> = tables
+                .iter()
+                .map(|t| (t.clone(), res.remove(t).unwrap_or_default()))
+                .collect();
+            let mut rest: Vec<_> = res.into_iter().collect();
+            rest.sort_by_key(|(t, _)| t.get_full_name());
+            result.extend(rest);
+            return result;
         }
-        res.iter().map(|(k, b)| (k.clone(), *b)).collect()
+        Vec::new()
     }
 
     /// Get columns for table
@@ -0,0 +1,89 @@
+use crate::ColumnData;
+use std::cmp::Ordering;
+
+/// The types with binary values (they are written as hex literals)
+const BINARY_TYPES: [&str; 14] = [
+    "binary",
+    "varbinary",
+    "tinyblob",
+    "blob",
+    "mediumblob",
+    "longblob",
+    "bit",
+    "geometry",
+    "point",
+    "linestring",
+    "polygon",
+    "multipoint",
+    "multilinestring",
+    "multipolygon",
+];
+
+#[derive(Debug, Clone, Eq)]
+pub struct MySqlColumn {
+    /// Ordinal position of column
+    pub position: u32,
+    /// Column name
+    pub name: String,
+    /// Column data type without modifiers (e.g. `varchar`)
+    pub data_type: String,
+    /// Column type with modifiers (e.g. `varchar(45)` or `int unsigned`)
+    pub column_type: String,
+}
+
+impl MySqlColumn {
+    pub fn is_binary(&self) -> bool {
+        BINARY_TYPES.contains(&self.data_type.to_lowercase().as_str())
+    }
+}
+
+impl PartialEq for MySqlColumn {
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position
+    }
+}
+
+impl PartialOrd for MySqlColumn {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MySqlColumn {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.position.cmp(&other.position)
+    }
+}
+
+impl ColumnData<String> for MySqlColumn {
+    fn position(&self) -> usize {
+        (self.position - 1) as usize
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    fn inner_kind(&self) -> Option<String> {
+        Some(self.data_type.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary() {
+        let column = |data_type: &str| MySqlColumn {
+            position: 1,
+            name: "data".to_string(),
+            data_type: data_type.to_string(),
+            column_type: data_type.to_string(),
+        };
+        assert!(column("varbinary").is_binary());
+        assert!(column("BLOB").is_binary());
+        assert!(!column("varchar").is_binary());
+        assert!(!column("json").is_binary());
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use mysql::{Conn, Opts, OptsBuilder};
+use url::Url;
+
+pub const SCHEME: &str = "mysql";
+const DEFAULT_PORT: u16 = 3306;
+
+pub struct Connection {
+    pub conn: Conn,
+    /// The URL without the password
+    pub url: Url,
+    password: Option<String>,
+}
+
+impl Connection {
+    pub fn new(conn: Conn, url: Url, password: Option<String>) -> Self {
+        Self {
+            conn,
+            url,
+            password,
+        }
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    /// The database of the URL (the tables of this database are dumped)
+    pub fn database(&self) -> Result<String> {
+        database(&self.url)
+    }
+
+    /// The connection arguments of `mysqldump` (the password is passed with `MYSQL_PWD`)
+    pub fn mysqldump_args(&self) -> Vec<String> {
+        mysqldump_args(&self.url)
+    }
+}
+
+pub struct Connector {
+    url: Url,
+    password: Option<String>,
+}
+
+impl Connector {
+    /// The URL is `mysql://user@host:port/database` (the password is set with `with_password`)
+    pub fn new(url: Url) -> Result<Self> {
+        if url.scheme() != SCHEME {
+            return Err(anyhow!(
+                "Invalid database URL {}: the scheme must be mysql",
+                url
+            ));
+        }
+        database(&url)?;
+        Ok(Self {
+            url,
+            password: None,
+        })
+    }
+
+    pub fn with_password(mut self, password: Option<String>) -> Self {
+        self.password = password;
+        self
+    }
+
+    pub fn connect(&self) -> Result<Connection> {
+        let opts = Opts::from_url(self.url.as_str())
+            .map_err(|e| anyhow!("Invalid database URL {}: {}", self.url, e))?;
+        let opts = OptsBuilder::from_opts(opts)
+            .pass(self.password.clone())
+            // the values are read as UTF-8, the timestamps are in UTC (as with `mysqldump`)
+            .init(vec!["SET NAMES utf8mb4", "SET time_zone = '+00:00'"]);
+        let conn = Conn::new(opts)?;
+
+        Ok(Connection::new(
+            conn,
+            self.url.clone(),
+            self.password.clone(),
+        ))
+    }
+}
+
+fn database(url: &Url) -> Result<String> {
+    let database = decode(url.path().trim_start_matches('/'));
+    if database.is_empty() {
+        return Err(anyhow!(
+            "The database URL {} has no database (mysql://user@host:3306/database)",
+            url
+        ));
+    }
+    Ok(database)
+}
+
+fn mysqldump_args(url: &Url) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(host) = url.host_str() {
+        args.push(format!("--host={}", host));
+    }
+    args.push(format!("--port={}", url.port().unwrap_or(DEFAULT_PORT)));
+    if !url.username().is_empty() {
+        args.push(format!("--user={}", decode(url.username())));
+    }
+    args
+}
+
+fn decode(s: &str) -> String {
+    percent_encoding::percent_decode_str(s)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url() {
+        let url = |s: &str| Connector::new(Url::parse(s).unwrap());
+        assert!(url("mysql://root@localhost:3306/shop").is_ok());
+        assert_eq!(
+            url("postgres://root@localhost/shop")
+                .err()
+                .unwrap()
+                .to_string(),
+            "Invalid database URL postgres://root@localhost/shop: the scheme must be mysql"
+        );
+        assert_eq!(
+            url("mysql://root@localhost").err().unwrap().to_string(),
+            "The database URL mysql://root@localhost has no database (mysql://user@host:3306/database)"
+        );
+    }
+
+    #[test]
+    fn connection_args() {
+        assert_eq!(
+            mysqldump_args(&Url::parse("mysql://app%40corp@db.local/shop").unwrap()),
+            ["--host=db.local", "--port=3306", "--user=app@corp"]
+        );
+        assert_eq!(
+            mysqldump_args(&Url::parse("mysql://db.local:3307/shop").unwrap()),
+            ["--host=db.local", "--port=3307"]
+        );
+    }
+
+    #[test]
+    fn database_name() {
+        assert_eq!(
+            database(&Url::parse("mysql://localhost/my%20shop").unwrap()).unwrap(),
+            "my shop"
+        );
+    }
+}
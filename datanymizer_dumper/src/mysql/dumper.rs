@@ -0,0 +1,533 @@
+use super::{
+    connector,
+    row::{self, MySqlRow, Value},
+    schema_inspector::MySqlSchemaInspector,
+    table::MySqlTable,
+};
+use crate::{
+    indicator::{DumpSummary, Indicator, TableInfo, TableStats},
+    postgres::child_process::ChildGuard,
+    Dumper, SchemaInspector, Table,
+};
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{Engine, Filter, Settings, TableList};
+use mysql::{prelude::Queryable, AccessMode, IsolationLevel, Transaction, TxOpts};
+use std::{
+    fs::File,
+    io::{prelude::*, BufWriter},
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+/// The password of `mysqldump`
+pub const PASSWORD_ENV: &str = "MYSQL_PWD";
+/// The SQL file of the directory dump (the schema and the `LOAD DATA` statements)
+pub const LOAD_FILE: &str = "load.sql";
+/// The rows of an `INSERT` statement are written until it exceeds this size
+const INSERT_BATCH_BYTES: usize = 1 << 20;
+
+// The foreign keys are checked after the data is loaded (the cyclic references can't be ordered),
+// the timestamps are in UTC (as with `mysqldump`), `0` isn't replaced with the next `AUTO_INCREMENT` value
+const DATA_PROLOGUE: &str = "SET NAMES utf8mb4;
+SET TIME_ZONE = '+00:00';
+SET FOREIGN_KEY_CHECKS = 0;
+SET UNIQUE_CHECKS = 0;
+SET SQL_MODE = 'NO_AUTO_VALUE_ON_ZERO';
+";
+const DATA_EPILOGUE: &str = "SET FOREIGN_KEY_CHECKS = 1;
+SET UNIQUE_CHECKS = 1;
+";
+
+/// The dumper of MySQL and MariaDB databases. The schema is dumped with `mysqldump` (the tables before the data,
+/// the triggers, the routines and the events after it), the rows are selected in a consistent snapshot,
+/// transformed and written as `INSERT` statements or as `LOAD DATA` files of a directory dump.
+pub struct MySqlDumper<W: Write + Send, I: Indicator + Send> {
+    schema_inspector: MySqlSchemaInspector,
+    engine: Engine,
+    dump_writer: W,
+    indicator: I,
+    mysqldump_location: String,
+    mysqldump_args: Vec<String>,
+    /// The directory of the `LOAD DATA` files (the rows are written to them instead of `INSERT` statements)
+    tsv_directory: Option<PathBuf>,
+}
+
+impl<W: 'static + Write + Send, I: 'static + Indicator + Send> MySqlDumper<W, I> {
+    pub fn new(
+        engine: Engine,
+        mysqldump_location: String,
+        dump_writer: W,
+        indicator: I,
+        mysqldump_args: Vec<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            schema_inspector: MySqlSchemaInspector,
+            engine,
+            dump_writer,
+            indicator,
+            mysqldump_location,
+            mysqldump_args,
+            tsv_directory: None,
+        })
+    }
+
+    /// Writes the rows of every table to `<database>.<table>.tsv` in the directory and the `LOAD DATA` statements
+    /// to the dump (the dump is restored from the directory with `mysql --local-infile`)
+    pub fn with_tsv_directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.tsv_directory = Some(dir.into());
+        self
+    }
+
+    fn run_mysqldump(&mut self, connection: &connector::Connection, args: &[&str]) -> Result<()> {
+        let program = &self.mysqldump_location;
+        let database_args = database_args(&connection.database()?, &self.engine.settings.filter);
+
+        let mut command = Command::new(program);
+        command
+            .args(connection.mysqldump_args())
+            .args(&self.mysqldump_args)
+            .args(args)
+            .args(&database_args);
+        // the password isn't passed in the arguments (they are visible in the process list)
+        if let Some(password) = connection.password() {
+            command.env(PASSWORD_ENV, password);
+        }
+        let dump_output = ChildGuard::spawn(&mut command)
+            .map_err(|e| anyhow!("Can't run `{}`: {}", program, e))?
+            .output()?;
+        if !dump_output.status.success() {
+            return Err(anyhow!(
+                "mysqldump error ({}). Command:\n{} {}\nOutput:\n{}",
+                dump_output.status,
+                program,
+                args.iter()
+                    .copied()
+                    .chain(database_args.iter().map(|s| s.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                String::from_utf8_lossy(&dump_output.stderr).trim_end()
+            ));
+        }
+
+        self.dump_writer
+            .write_all(&dump_output.stdout)
+            .map_err(|e| e.into())
+    }
+
+    fn dump_table(&mut self, table: &MySqlTable, tx: &mut Transaction) -> Result<TableStats> {
+        let settings = self.settings();
+        let started = Instant::now();
+        let cfg = settings.find_table(&table.get_names());
+        let info = TableInfo {
+            name: table.get_full_name(),
+            rows: table.count_of_query_to(cfg),
+        };
+        self.indicator.table_started(&info);
+        self.write_log(format!("Dump table: {}", &table.get_full_name()))?;
+
+        let binary = table.binary_columns();
+        let mut output = TableOutput::new(table, self.tsv_directory.as_deref())?;
+        let mut count: u64 = 0;
+        let mut bytes: u64 = 0;
+
+        if let Some(cfg) = cfg {
+            if let Some(transformed_query) = table.transformed_query_to(Some(cfg), count) {
+                for row in tx.query_iter(transformed_query)? {
+                    count += 1;
+                    let row = MySqlRow::new(values(row?), count, table.clone());
+                    let transformed = row.transform(&self.engine, cfg.name.as_str())?;
+                    bytes += output.write(&mut self.dump_writer, &transformed, &binary)?;
+                    self.indicator.data_progress(&info, count, bytes);
+                }
+            }
+        }
+
+        if let Some(untransformed_query) = table.untransformed_query_to(cfg, count) {
+            for row in tx.query_iter(untransformed_query)? {
+                count += 1;
+                bytes += output.write(&mut self.dump_writer, &values(row?), &binary)?;
+                self.indicator.data_progress(&info, count, bytes);
+            }
+        }
+        output.finish(&mut self.dump_writer)?;
+
+        let stats = TableStats {
+            name: table.get_full_name(),
+            rows: count,
+            bytes,
+            duration: started.elapsed(),
+            outbox_events: Default::default(),
+            saved_bytes: None,
+        };
+        self.indicator.table_finished(&stats);
+        Ok(stats)
+    }
+}
+
+impl<W: 'static + Write + Send, I: 'static + Indicator + Send> Dumper for MySqlDumper<W, I> {
+    type Table = MySqlTable;
+    type Connection = connector::Connection;
+    type SchemaInspector = MySqlSchemaInspector;
+
+    // The tables (without the triggers, they would fire while loading the data)
+    fn pre_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        self.debug("Prepare data scheme...".into());
+        self.run_mysqldump(
+            connection,
+            &["--no-data", "--skip-triggers", "--single-transaction"],
+        )
+    }
+
+    fn data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        let settings = self.settings();
+        self.write_log("Start dumping data".into())?;
+        self.dump_writer.write_all(DATA_PROLOGUE.as_bytes())?;
+        self.debug("Fetch tables metadata...".into());
+
+        let mut tables = self.schema_inspector().ordered_tables(connection);
+        sort_tables(
+            &mut tables,
+            settings.table_order.as_ref().unwrap_or(&vec![]),
+        );
+        let mut dumped_tables = vec![];
+        for (mut table, _weight) in tables {
+            if self.filter_table(table.get_full_name(), &settings.filter) {
+                table.limit = settings.row_limit(settings.find_table(&table.get_names()));
+                dumped_tables.push(table);
+            } else {
+                self.debug(format!("[Dumping: {}] --- SKIP ---", table.get_full_name()));
+            }
+        }
+        let infos: Vec<_> = dumped_tables
+            .iter()
+            .map(|t| TableInfo {
+                name: t.get_full_name(),
+                rows: t.count_of_query_to(settings.find_table(&t.get_names())),
+            })
+            .collect();
+        self.indicator.dump_started(&infos);
+
+        let started = Instant::now();
+        let mut summary = DumpSummary::default();
+        // all tables are read in the same snapshot
+        let mut tx = connection.conn.start_transaction(
+            TxOpts::default()
+                .set_with_consistent_snapshot(true)
+                .set_isolation_level(Some(IsolationLevel::RepeatableRead))
+                .set_access_mode(Some(AccessMode::ReadOnly)),
+        )?;
+        let all_tables_count = dumped_tables.len();
+        for (ind, (table, info)) in dumped_tables.iter().zip(&infos).enumerate() {
+            self.debug(format!(
+                "[{} / {}] Prepare to dump table: {}",
+                ind + 1,
+                all_tables_count,
+                table.get_full_name(),
+            ));
+            match self.dump_table(table, &mut tx) {
+                Ok(stats) => summary.add_table(&stats),
+                Err(e) => {
+                    self.indicator.table_failed(info, &e);
+                    summary.error = Some(e.to_string());
+                    summary.duration = started.elapsed();
+                    self.indicator.dump_finished(&summary);
+                    return Err(e);
+                }
+            }
+        }
+        tx.commit()?;
+        summary.duration = started.elapsed();
+        self.indicator.dump_finished(&summary);
+
+        self.dump_writer.write_all(DATA_EPILOGUE.as_bytes())?;
+        self.write_log("End dumping data".into())?;
+        Ok(())
+    }
+
+    // The triggers, the routines and the events
+    fn post_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        self.debug("Finishing with triggers and routines...".into());
+        self.run_mysqldump(
+            connection,
+            &[
+                "--no-data",
+                "--no-create-info",
+                "--triggers",
+                "--routines",
+                "--events",
+                "--single-transaction",
+            ],
+        )
+    }
+
+    fn schema_inspector(&self) -> Self::SchemaInspector {
+        self.schema_inspector.clone()
+    }
+
+    fn settings(&mut self) -> Settings {
+        self.engine.settings.clone()
+    }
+
+    fn write_log(&mut self, message: String) -> Result<()> {
+        self.dump_writer
+            .write_all(format!("\n--\n-- {}\n--\n", message).as_bytes())
+            .map_err(|e| e.into())
+    }
+
+    fn debug(&self, message: String) {
+        self.indicator.debug_msg(message.as_str());
+    }
+}
+
+/// The output of the table rows: `INSERT` statements in the dump or a `LOAD DATA` file
+enum TableOutput {
+    Insert {
+        statement: String,
+        batch: Vec<u8>,
+    },
+    LoadData {
+        statement: String,
+        file: BufWriter<File>,
+        line: Vec<u8>,
+    },
+}
+
+impl TableOutput {
+    fn new(table: &MySqlTable, tsv_directory: Option<&Path>) -> Result<Self> {
+        let columns = table.quoted_columns().join(", ");
+        Ok(match tsv_directory {
+            Some(dir) => {
+                let filename = tsv_filename(table);
+                let file = File::create(dir.join(&filename)).map_err(|e| {
+                    anyhow!(
+                        "Can't create the file {} in {}: {}",
+                        filename,
+                        dir.display(),
+                        e
+                    )
+                })?;
+                Self::LoadData {
+                    statement: format!(
+                        "LOAD DATA LOCAL INFILE '{}' INTO TABLE {} CHARACTER SET utf8mb4 ({});\n",
+                        filename.replace('\\', "\\\\").replace('\'', "\\'"),
+                        table.quoted_full_name(),
+                        columns
+                    ),
+                    file: BufWriter::new(file),
+                    line: vec![],
+                }
+            }
+            None => Self::Insert {
+                statement: format!(
+                    "INSERT INTO {} ({}) VALUES\n",
+                    table.quoted_full_name(),
+                    columns
+                ),
+                batch: vec![],
+            },
+        })
+    }
+
+    // Returns the size of the written row
+    fn write(&mut self, dump: &mut dyn Write, values: &[Value], binary: &[bool]) -> Result<u64> {
+        match self {
+            Self::Insert { statement, batch } => {
+                if batch.is_empty() {
+                    batch.extend_from_slice(statement.as_bytes());
+                } else {
+                    batch.extend_from_slice(b",\n");
+                }
+                let len = batch.len();
+                row::write_insert_row(batch, values, binary);
+                let size = batch.len() - len;
+                if batch.len() >= INSERT_BATCH_BYTES {
+                    batch.extend_from_slice(b";\n");
+                    dump.write_all(batch)?;
+                    batch.clear();
+                }
+                Ok(size as u64)
+            }
+            Self::LoadData { file, line, .. } => {
+                line.clear();
+                row::write_tsv_line(line, values);
+                file.write_all(line)?;
+                Ok(line.len() as u64)
+            }
+        }
+    }
+
+    fn finish(self, dump: &mut dyn Write) -> Result<()> {
+        match self {
+            Self::Insert { mut batch, .. } => {
+                if !batch.is_empty() {
+                    batch.extend_from_slice(b";\n");
+                    dump.write_all(&batch)?;
+                }
+            }
+            Self::LoadData {
+                statement,
+                mut file,
+                ..
+            } => {
+                file.flush()?;
+                dump.write_all(statement.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The file of the table rows in the directory dump
+pub fn tsv_filename(table: &MySqlTable) -> String {
+    format!("{}.tsv", table.get_full_name())
+}
+
+// The text protocol returns all values as bytes
+fn values(row: mysql::Row) -> Vec<Value> {
+    row.unwrap()
+        .into_iter()
+        .map(|value| match value {
+            mysql::Value::NULL => None,
+            mysql::Value::Bytes(bytes) => Some(bytes),
+            other => Some(other.as_sql(true).into_bytes()),
+        })
+        .collect()
+}
+
+/// The `mysqldump` arguments of the database and the tables of the `schema` filter
+fn database_args(database: &str, filter: &Option<Filter>) -> Vec<String> {
+    let list = filter.as_ref().and_then(|f| f.schema.as_ref());
+    // the tables of other databases aren't dumped
+    let name = |table: &str| match table.split_once('.') {
+        Some((db, name)) if db == database => Some(name.to_string()),
+        Some(_) => None,
+        None => Some(table.to_string()),
+    };
+
+    let mut args = vec![];
+    if let Some(TableList::Except(tables)) = list {
+        for table in tables.iter().filter_map(|t| name(t)) {
+            args.push(format!("--ignore-table={}.{}", database, table));
+        }
+    }
+    args.push(database.to_string());
+    if let Some(TableList::Only(tables)) = list {
+        args.extend(tables.iter().filter_map(|t| name(t)));
+    }
+    args
+}
+
+fn sort_tables(tables: &mut [(MySqlTable, i32)], order: &[String]) {
+    tables.sort_by_cached_key(|(tbl, weight)| {
+        let position = order.iter().position(|i| tbl.get_names().contains(i));
+        (position, -weight)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+
+    fn table(name: &str) -> MySqlTable {
+        test_tables::mysql_table("shop", name, &[("id", "int"), ("avatar", "blob")])
+    }
+
+    #[test]
+    fn test_database_args() {
+        assert_eq!(database_args("shop", &None), vec!["shop"]);
+
+        let filter = |list| {
+            Some(Filter {
+                schema: Some(list),
+                data: None,
+            })
+        };
+        assert_eq!(
+            database_args(
+                "shop",
+                &filter(TableList::Except(vec![
+                    "logs".to_string(),
+                    "shop.events".to_string(),
+                    "other.users".to_string()
+                ]))
+            ),
+            vec![
+                "--ignore-table=shop.logs",
+                "--ignore-table=shop.events",
+                "shop"
+            ]
+        );
+        assert_eq!(
+            database_args(
+                "shop",
+                &filter(TableList::Only(vec![
+                    "users".to_string(),
+                    "shop.orders".to_string()
+                ]))
+            ),
+            vec!["shop", "users", "orders"]
+        );
+    }
+
+    #[test]
+    fn insert_output() {
+        let table = table("users");
+        let mut output = TableOutput::new(&table, None).unwrap();
+        let mut dump = vec![];
+        let binary = table.binary_columns();
+        output
+            .write(&mut dump, &[Some(b"1".to_vec()), None], &binary)
+            .unwrap();
+        output
+            .write(&mut dump, &[Some(b"2".to_vec()), Some(vec![1])], &binary)
+            .unwrap();
+        // the statement is written when it is finished
+        assert!(dump.is_empty());
+        output.finish(&mut dump).unwrap();
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            "INSERT INTO `shop`.`users` (`id`, `avatar`) VALUES\n('1', NULL),\n('2', X'01');\n"
+        );
+
+        // no statement without rows
+        let mut dump = vec![];
+        TableOutput::new(&table, None)
+            .unwrap()
+            .finish(&mut dump)
+            .unwrap();
+        assert!(dump.is_empty());
+    }
+
+    #[test]
+    fn load_data_output() {
+        let dir = std::env::temp_dir().join("datanymizer_mysql_load_data");
+        std::fs::create_dir_all(&dir).unwrap();
+        let table = table("users");
+        let mut output = TableOutput::new(&table, Some(&dir)).unwrap();
+        let mut dump = vec![];
+        output
+            .write(&mut dump, &[Some(b"1".to_vec()), None], &[])
+            .unwrap();
+        output.finish(&mut dump).unwrap();
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            "LOAD DATA LOCAL INFILE 'shop.users.tsv' INTO TABLE `shop`.`users` CHARACTER SET utf8mb4 (`id`, `avatar`);\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("shop.users.tsv")).unwrap(),
+            "1\t\\N\n"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn table_order() {
+        let mut tables = vec![(table("a"), 1), (table("b"), 2), (table("c"), 1)];
+        sort_tables(&mut tables, &["shop.c".to_string()]);
+        let names: Vec<_> = tables.iter().map(|(t, _)| t.get_name()).collect();
+        // the listed tables are dumped after the rest
+        assert_eq!(names, vec!["b", "a", "c"]);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod column;
+pub mod connector;
+pub mod dumper;
+pub mod row;
+pub mod schema_inspector;
+pub mod table;
@@ -0,0 +1,238 @@
+//! The rows of the MySQL tables. The values are read with the text protocol, they are passed to the rules
+//! in the COPY text format of PostgreSQL (the rules expect it: NULL is `\N`, the special characters are escaped)
+//! and written as the values of `INSERT` statements or as the lines of `LOAD DATA` files.
+
+use crate::{postgres::copy_codec, Table};
+use anyhow::Result;
+use datanymizer_engine::Engine;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+/// A column value (`None` is NULL)
+pub type Value = Option<Vec<u8>>;
+
+#[derive(Debug)]
+pub struct MySqlRow<T>
+where
+    T: Table<String>,
+{
+    table: T,
+    values: Vec<Value>,
+    /// The row number in the table (for the seeded rules)
+    number: u64,
+}
+
+impl<T> MySqlRow<T>
+where
+    T: Table<String>,
+{
+    pub fn new(values: Vec<Value>, number: u64, parent_table: T) -> Self {
+        Self {
+            table: parent_table,
+            values,
+            number,
+        }
+    }
+
+    /// Applies the transform engine to every column in the row.
+    /// The values that are not changed by the rules are passed through byte-for-byte.
+    pub fn transform(&self, engine: &Engine, cfg_tbl_name: &str) -> Result<Vec<Value>> {
+        let escaped: Vec<Cow<str>> = self
+            .values
+            .iter()
+            .map(|v| match v {
+                Some(bytes) => match copy_codec::escape(bytes) {
+                    Cow::Borrowed(bytes) => String::from_utf8_lossy(bytes),
+                    Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+                },
+                None => Cow::Borrowed(r"\N"),
+            })
+            .collect();
+        let escaped: Vec<&str> = escaped.iter().map(|v| v.as_ref()).collect();
+
+        let transformed_values = engine.process_row_numbered(
+            String::from(cfg_tbl_name),
+            Some(self.number),
+            self.table.get_column_indexes(),
+            &escaped,
+            &HashSet::new(),
+            &HashMap::new(),
+        )?;
+
+        Ok(transformed_values
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| match v {
+                Cow::Owned(mut s) => {
+                    copy_codec::escape_transformed(&mut s);
+                    copy_codec::unescape(s.as_bytes()).map(|v| v.into_owned())
+                }
+                Cow::Borrowed(_) => self.values[i].clone(),
+            })
+            .collect())
+    }
+}
+
+/// Writes the values as a row of an `INSERT` statement: `(1, 'text', X'00ff', NULL)`.
+/// The binary values are hex literals, the text values are string literals with backslash escapes
+/// (without `NO_BACKSLASH_ESCAPES` in `sql_mode`).
+pub fn write_insert_row(out: &mut Vec<u8>, values: &[Value], binary: &[bool]) {
+    out.push(b'(');
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.extend_from_slice(b", ");
+        }
+        match value {
+            None => out.extend_from_slice(b"NULL"),
+            Some(bytes) if binary.get(i).copied().unwrap_or_default() => {
+                out.extend_from_slice(b"X'");
+                for b in bytes {
+                    out.extend_from_slice(format!("{:02x}", b).as_bytes());
+                }
+                out.push(b'\'');
+            }
+            Some(bytes) => {
+                out.push(b'\'');
+                for &b in bytes {
+                    match b {
+                        0 => out.extend_from_slice(br"\0"),
+                        b'\'' => out.extend_from_slice(br"\'"),
+                        b'\\' => out.extend_from_slice(br"\\"),
+                        b'\n' => out.extend_from_slice(br"\n"),
+                        b'\r' => out.extend_from_slice(br"\r"),
+                        0x1a => out.extend_from_slice(br"\Z"),
+                        b => out.push(b),
+                    }
+                }
+                out.push(b'\'');
+            }
+        }
+    }
+    out.push(b')');
+}
+
+/// Writes the values as a line of a `LOAD DATA` file with the default format
+/// (`FIELDS TERMINATED BY '\t' ESCAPED BY '\\' LINES TERMINATED BY '\n'`, NULL is `\N`)
+pub fn write_tsv_line(out: &mut Vec<u8>, values: &[Value]) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.push(b'\t');
+        }
+        match value {
+            None => out.extend_from_slice(br"\N"),
+            Some(bytes) => {
+                for &b in bytes {
+                    match b {
+                        0 => out.extend_from_slice(br"\0"),
+                        b'\\' => out.extend_from_slice(br"\\"),
+                        b'\t' => out.extend_from_slice(br"\t"),
+                        b'\n' => out.extend_from_slice(br"\n"),
+                        b'\r' => out.extend_from_slice(br"\r"),
+                        b => out.push(b),
+                    }
+                }
+            }
+        }
+    }
+    out.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mysql::{column::MySqlColumn, table::MySqlTable};
+    use datanymizer_engine::Settings;
+
+    fn value(s: &str) -> Value {
+        Some(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn transform() {
+        let config = r#"
+          tables:
+            - name: users
+              rules:
+                email:
+                  template:
+                    format: "user@example.com"
+                phone:
+                  template:
+                    format: '\N'
+                note:
+                  template:
+                    format: "line 1\nline 2\\"
+        "#;
+        let engine = Engine::new(Settings::from_yaml(config).unwrap());
+
+        let mut table = MySqlTable::new("users".to_string(), "shop".to_string());
+        let columns = ["id", "email", "phone", "note", "comment"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| MySqlColumn {
+                position: i as u32 + 1,
+                name: name.to_string(),
+                data_type: "text".to_string(),
+                column_type: "text".to_string(),
+            })
+            .collect();
+        table.set_columns(columns);
+
+        let row = MySqlRow::new(
+            vec![
+                value("1"),
+                value("john@corp.com"),
+                value("555-01"),
+                value("note"),
+                value("tab\there\\"),
+            ],
+            1,
+            table,
+        );
+        assert_eq!(
+            row.transform(&engine, "users").unwrap(),
+            vec![
+                value("1"),
+                value("user@example.com"),
+                None,
+                value("line 1\nline 2\\"),
+                value("tab\there\\"),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_row() {
+        let mut out = vec![];
+        write_insert_row(
+            &mut out,
+            &[
+                value("1"),
+                value("it's\n\\ \0"),
+                None,
+                Some(vec![0, 0xff]),
+                Some(vec![]),
+            ],
+            &[false, false, false, true, true],
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r"('1', 'it\'s\n\\ \0', NULL, X'00ff', X'')"
+        );
+    }
+
+    #[test]
+    fn tsv_line() {
+        let mut out = vec![];
+        write_tsv_line(
+            &mut out,
+            &[value("1"), value("a\tb\\c\nd"), None, value("")],
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1\ta\\tb\\\\c\\nd\t\\N\t\n"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use super::{column::MySqlColumn, connector, table::MySqlTable};
+use crate::SchemaInspector;
+use anyhow::Result;
+use mysql::prelude::Queryable;
+
+// ? - the database
+const TABLES_QUERY: &str = "SELECT table_name, table_schema, COALESCE(table_rows, 0)
+                            FROM information_schema.tables
+                            WHERE table_schema = ? AND table_type = 'BASE TABLE'
+                            ORDER BY table_name";
+
+// The approximate count of rows (the exact count for MyISAM tables)
+const TABLE_SIZE_QUERY: &str = "SELECT COALESCE(table_rows, 0)
+                                FROM information_schema.tables
+                                WHERE table_schema = ? AND table_name = ?";
+
+// The generated columns can't be inserted
+const TABLE_COLUMNS_QUERY: &str = "SELECT column_name, ordinal_position, data_type, column_type
+                                   FROM information_schema.columns
+                                   WHERE table_schema = ? AND table_name = ?
+                                   AND extra NOT LIKE '%GENERATED%'
+                                   ORDER BY ordinal_position";
+
+const TABLE_FOREIGN_KEYS: &str = "SELECT DISTINCT referenced_table_name, referenced_table_schema
+                                  FROM information_schema.key_column_usage
+                                  WHERE table_schema = ? AND table_name = ?
+                                  AND referenced_table_name IS NOT NULL";
+
+#[derive(Clone, Default)]
+pub struct MySqlSchemaInspector;
+
+impl SchemaInspector for MySqlSchemaInspector {
+    type Type = String;
+    type Connection = connector::Connection;
+    type Table = MySqlTable;
+    type Column = MySqlColumn;
+
+    // Get all tables of the database
+    fn get_tables(&self, connection: &mut Self::Connection) -> Result<Vec<Self::Table>> {
+        let database = connection.database()?;
+        let rows: Vec<(String, String, u64)> = connection.conn.exec(TABLES_QUERY, (database,))?;
+
+        let mut tables = Vec::with_capacity(rows.len());
+        for (tablename, schemaname, size) in rows {
+            let mut table = MySqlTable::new(tablename, schemaname);
+            table.set_columns(self.get_columns(connection, &table)?);
+            table.size = size as i64;
+            tables.push(table);
+        }
+        Ok(tables)
+    }
+
+    fn get_table_size(
+        &self,
+        connection: &mut Self::Connection,
+        table: &Self::Table,
+    ) -> Result<i64> {
+        let size: Option<u64> = connection
+            .conn
+            .exec_first(TABLE_SIZE_QUERY, (&table.schemaname, &table.tablename))?;
+        Ok(size.unwrap_or_default() as i64)
+    }
+
+    // Get all dependencies (by FK) for `table` in database
+    fn get_dependencies(
+        &self,
+        connection: &mut Self::Connection,
+        table: &Self::Table,
+    ) -> Result<Vec<Self::Table>> {
+        let rows: Vec<(String, String)> = connection
+            .conn
+            .exec(TABLE_FOREIGN_KEYS, (&table.schemaname, &table.tablename))?;
+
+        let mut tables = Vec::with_capacity(rows.len());
+        for (tablename, schemaname) in rows {
+            let mut table = MySqlTable::new(tablename, schemaname);
+            table.set_columns(self.get_columns(connection, &table)?);
+            table.size = self.get_table_size(connection, &table)?;
+            tables.push(table);
+        }
+        Ok(tables)
+    }
+
+    fn get_columns(
+        &self,
+        connection: &mut Self::Connection,
+        table: &Self::Table,
+    ) -> Result<Vec<Self::Column>> {
+        let rows: Vec<(String, u32, String, String)> = connection
+            .conn
+            .exec(TABLE_COLUMNS_QUERY, (&table.schemaname, &table.tablename))?;
+        Ok(rows
+            .into_iter()
+            .map(|(name, position, data_type, column_type)| MySqlColumn {
+                position,
+                name,
+                data_type,
+                column_type,
+            })
+            .collect())
+    }
+}
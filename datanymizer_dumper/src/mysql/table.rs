@@ -0,0 +1,285 @@
+use super::{column::MySqlColumn, row::MySqlRow};
+use crate::Table;
+use datanymizer_engine::{Query as QueryCfg, Table as TableCfg};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Debug, Clone, Eq)]
+pub struct MySqlTable {
+    pub tablename: String,
+    /// The database of the table
+    pub schemaname: String,
+    pub columns: Vec<MySqlColumn>,
+    column_indexes: HashMap<String, usize>,
+    /// The approximate count of rows (`table_rows` of `information_schema.tables`)
+    pub size: i64,
+    /// The row limit (the `limit` of the table or the global `default_limit`)
+    pub limit: Option<u64>,
+}
+
+impl PartialEq for MySqlTable {
+    fn eq(&self, other: &MySqlTable) -> bool {
+        self.get_full_name() == other.get_full_name()
+    }
+}
+
+impl Hash for MySqlTable {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tablename.hash(state);
+        self.schemaname.hash(state);
+    }
+}
+
+impl Table<String> for MySqlTable {
+    type Column = MySqlColumn;
+    type Row = MySqlRow<Self>;
+
+    fn get_name(&self) -> String {
+        self.tablename.clone()
+    }
+
+    fn get_full_name(&self) -> String {
+        format!("{}.{}", self.schemaname, self.tablename)
+    }
+
+    fn get_names(&self) -> Vec<String> {
+        vec![self.get_full_name(), self.get_name()]
+    }
+
+    fn get_columns(&self) -> Vec<Self::Column> {
+        self.columns.clone()
+    }
+
+    fn get_columns_names(&self) -> Vec<String> {
+        self.get_columns().into_iter().map(|c| c.name).collect()
+    }
+
+    fn get_size(&self) -> i64 {
+        self.size
+    }
+
+    fn get_column_indexes(&self) -> &HashMap<String, usize> {
+        &self.column_indexes
+    }
+}
+
+impl MySqlTable {
+    pub fn new(tablename: String, schemaname: String) -> Self {
+        Self {
+            tablename,
+            schemaname,
+            columns: vec![],
+            column_indexes: HashMap::new(),
+            size: 0,
+            limit: None,
+        }
+    }
+
+    /// Quotes the identifier with backticks
+    pub fn quote_identifier(name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    pub fn quoted_full_name(&self) -> String {
+        format!(
+            "{}.{}",
+            Self::quote_identifier(&self.schemaname),
+            Self::quote_identifier(&self.tablename)
+        )
+    }
+
+    pub fn quoted_columns(&self) -> Vec<String> {
+        let mut columns: Vec<_> = self.columns.iter().collect();
+        columns.sort();
+        columns
+            .into_iter()
+            .map(|c| Self::quote_identifier(&c.name))
+            .collect()
+    }
+
+    pub fn set_columns(&mut self, columns: Vec<MySqlColumn>) {
+        let mut map: HashMap<String, usize> = HashMap::with_capacity(columns.len());
+        let mut column_refs: Vec<_> = columns.iter().collect();
+        column_refs.sort_by_key(|c| c.position);
+        for (i, column) in column_refs.iter().enumerate() {
+            map.insert(column.name.clone(), i);
+        }
+
+        self.column_indexes = map;
+        self.columns = columns;
+    }
+
+    /// The columns with binary values in the order of the values
+    pub fn binary_columns(&self) -> Vec<bool> {
+        let mut columns: Vec<_> = self.columns.iter().collect();
+        columns.sort();
+        columns.into_iter().map(|c| c.is_binary()).collect()
+    }
+
+    /// The query of the rows to transform (`None` if the limit is already reached)
+    pub fn transformed_query_to(
+        &self,
+        cfg: Option<&TableCfg>,
+        already_dumped: u64,
+    ) -> Option<String> {
+        cfg.and_then(|c| match &c.query {
+            Some(q) => self.query_unless_already_dumped(q, |s| format!("({})", s), already_dumped),
+            None => self.limited_query(None, vec![], already_dumped),
+        })
+    }
+
+    /// The query of the rows that are dumped as they are (`None` if all rows are transformed)
+    pub fn untransformed_query_to(
+        &self,
+        cfg: Option<&TableCfg>,
+        already_dumped: u64,
+    ) -> Option<String> {
+        match cfg {
+            Some(c) => c.query.as_ref().and_then(|q| {
+                if q.transform_condition.is_some() {
+                    self.query_unless_already_dumped(q, |s| format!("NOT ({})", s), already_dumped)
+                } else {
+                    None
+                }
+            }),
+            None => self.limited_query(None, vec![], already_dumped),
+        }
+    }
+
+    pub fn count_of_query_to(&self, cfg: Option<&TableCfg>) -> u64 {
+        let number = self.get_size().max(0) as u64;
+        self.limit(cfg.and_then(|c| c.query.as_ref()))
+            .map_or(number, |limit| number.min(limit))
+    }
+
+    // The smallest of `query.limit` and the table limit
+    fn limit(&self, q: Option<&QueryCfg>) -> Option<u64> {
+        let query_limit = q.and_then(|q| q.limit).map(|l| l as u64);
+        match (query_limit, self.limit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn query_unless_already_dumped(
+        &self,
+        q: &QueryCfg,
+        tr_fmt: fn(s: &String) -> String,
+        already_dumped: u64,
+    ) -> Option<String> {
+        let conditions = vec![
+            q.dump_condition.as_ref().map(|c| format!("({})", c)),
+            q.transform_condition.as_ref().map(tr_fmt),
+        ];
+        self.limited_query(
+            Some(q),
+            conditions.into_iter().flatten().collect(),
+            already_dumped,
+        )
+    }
+
+    fn limited_query(
+        &self,
+        q: Option<&QueryCfg>,
+        conditions: Vec<String>,
+        already_dumped: u64,
+    ) -> Option<String> {
+        let limit = match self.limit(q) {
+            Some(limit) if limit <= already_dumped => return None,
+            limit => limit.map(|l| l - already_dumped),
+        };
+        Some(self.select(&conditions, limit))
+    }
+
+    fn select(&self, conditions: &[String], limit: Option<u64>) -> String {
+        let mut query = format!(
+            "SELECT {} FROM {}",
+            self.quoted_columns().join(", "),
+            self.quoted_full_name()
+        );
+        if !conditions.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&conditions.join(" AND "));
+        }
+        if let Some(limit) = limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+        query
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+    use datanymizer_engine::Settings;
+
+    fn table() -> MySqlTable {
+        let mut table = MySqlTable::new("users".to_string(), "shop".to_string());
+        let mut columns =
+            test_tables::mysql_columns(&[("id", "varchar(255)"), ("email", "varchar(255)")]);
+        // the columns are sorted by their positions
+        columns.reverse();
+        table.set_columns(columns);
+        table.size = 100;
+        table
+    }
+
+    fn cfg(config: &str) -> TableCfg {
+        Settings::from_yaml(config).unwrap().tables[0].clone()
+    }
+
+    #[test]
+    fn names() {
+        let table = table();
+        assert_eq!(table.get_names(), vec!["shop.users", "users"]);
+        assert_eq!(table.quoted_full_name(), "`shop`.`users`");
+        assert_eq!(MySqlTable::quote_identifier("a`b"), "`a``b`");
+        assert_eq!(table.quoted_columns(), vec!["`id`", "`email`"]);
+        assert_eq!(table.get_column_indexes()["email"], 1);
+    }
+
+    #[test]
+    fn queries() {
+        let table = table();
+        assert_eq!(table.transformed_query_to(None, 0), None,);
+        assert_eq!(
+            table.untransformed_query_to(None, 0).unwrap(),
+            "SELECT `id`, `email` FROM `shop`.`users`"
+        );
+
+        let cfg = cfg(r#"
+            tables:
+              - name: users
+                rules: {}
+                query:
+                  limit: 10
+                  dump_condition: "id > 5"
+                  transform_condition: "email NOT LIKE '%@corp.com'"
+        "#);
+        assert_eq!(
+            table.transformed_query_to(Some(&cfg), 0).unwrap(),
+            "SELECT `id`, `email` FROM `shop`.`users` WHERE (id > 5) AND (email NOT LIKE '%@corp.com') LIMIT 10"
+        );
+        assert_eq!(
+            table.untransformed_query_to(Some(&cfg), 4).unwrap(),
+            "SELECT `id`, `email` FROM `shop`.`users` WHERE (id > 5) AND NOT (email NOT LIKE '%@corp.com') LIMIT 6"
+        );
+        assert_eq!(table.untransformed_query_to(Some(&cfg), 10), None);
+        assert_eq!(table.count_of_query_to(Some(&cfg)), 10);
+    }
+
+    #[test]
+    fn table_limit() {
+        let mut table = table();
+        table.limit = Some(20);
+        let cfg = cfg("tables: [{name: users, rules: {}}]");
+        assert_eq!(
+            table.transformed_query_to(Some(&cfg), 0).unwrap(),
+            "SELECT `id`, `email` FROM `shop`.`users` LIMIT 20"
+        );
+        assert_eq!(table.count_of_query_to(Some(&cfg)), 20);
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use postgres::Client;
+
+/// The key of the session-level advisory lock of the dumps ("datanymz" in ASCII).
+/// Advisory locks belong to databases, so the dumps of different databases don't conflict.
+pub const KEY: i64 = 0x6461_7461_6e79_6d7a;
+
+// The bigint keys are stored in `pg_locks` as two halves, `objsubid` is 1 for them
+const HOLDER_QUERY: &str = "SELECT pid FROM pg_catalog.pg_locks
+                            WHERE locktype = 'advisory' AND granted AND objsubid = 1
+                            AND database = (SELECT oid FROM pg_catalog.pg_database WHERE datname = current_database())
+                            AND classid::bigint = ($1::bigint >> 32) AND objid::bigint = ($1::bigint & 4294967295)
+                            LIMIT 1";
+
+/// Takes the lock for the session. Waits for other dumps with `wait`, otherwise fails if another dump holds the lock.
+/// The server releases the lock when the session ends (e.g., when the dump fails and the connection is dropped).
+pub fn acquire(client: &mut Client, wait: bool) -> Result<()> {
+    if wait {
+        client.execute("SELECT pg_catalog.pg_advisory_lock($1)", &[&KEY])?;
+        return Ok(());
+    }
+
+    let locked: bool = client
+        .query_one("SELECT pg_catalog.pg_try_advisory_lock($1)", &[&KEY])?
+        .get(0);
+    if locked {
+        return Ok(());
+    }
+
+    let holder = client
+        .query_opt(HOLDER_QUERY, &[&KEY])
+        .ok()
+        .flatten()
+        .map(|row| format!(" (the server process {})", row.get::<_, i32>(0)))
+        .unwrap_or_default();
+    Err(anyhow!(
+        "Another dump of this database is running{}: use --wait-advisory-lock to wait for it \
+        or --no-advisory-lock to dump anyway",
+        holder
+    ))
+}
+
+pub fn release(client: &mut Client) -> Result<()> {
+    client.execute("SELECT pg_catalog.pg_advisory_unlock($1)", &[&KEY])?;
+    Ok(())
+}
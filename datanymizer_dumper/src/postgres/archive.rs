@@ -0,0 +1,935 @@
+//! The transformation of the pg_dump custom-format archives (`pg_dump -Fc`) without the source database.
+//!
+//! The archive is a header, the TOC (the entries of the schema objects, the `TABLE DATA` entries have
+//! the COPY statements with the column lists) and the data blocks (the COPY data of the tables
+//! as length-prefixed chunks of the compressed stream). The data of the tables with rules is decompressed,
+//! transformed row by row and compressed again, the other blocks are copied as is. The TOC is written
+//! before the data and rewritten with the offsets of the new data blocks at the end,
+//! so the output must be seekable (a file).
+
+use super::{column::PgColumn, row::PgRow, table::PgTable};
+use crate::{indicator::Indicator, Table};
+use anyhow::{anyhow, bail, Context, Result};
+use datanymizer_engine::Engine;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    mem,
+};
+
+const MAGIC: &[u8] = b"PGDMP";
+const FORMAT_CUSTOM: u8 = 1;
+/// The supported archive versions (pg_dump 9.5 to 17)
+const MIN_VERSION: (u8, u8) = (1, 12);
+const MAX_VERSION: (u8, u8) = (1, 16);
+
+const BLOCK_DATA: u8 = 1;
+const BLOCK_BLOBS: u8 = 3;
+
+const OFFSET_NOT_SET: u8 = 1;
+const OFFSET_SET: u8 = 2;
+const OFFSET_NO_DATA: u8 = 3;
+
+/// The max size of the written data chunks
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const TABLE_DATA: &str = "TABLE DATA";
+
+/// The compression of the data blocks (the same for all blocks of the archive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveCompression {
+    None,
+    /// The zlib streams with the level (`-1` is the default level)
+    Gzip(i64),
+    Lz4,
+    Zstd,
+}
+
+/// The archive version (major, minor, revision)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveVersion(pub u8, pub u8, pub u8);
+
+impl Display for ArchiveVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// The transformed data of the archive
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// The `TABLE DATA` entries
+    pub tables: usize,
+    /// The tables with rules
+    pub transformed_tables: usize,
+    /// The rows of the tables with rules
+    pub transformed_rows: u64,
+}
+
+struct Header {
+    /// The header as is (it is copied to the output)
+    raw: Vec<u8>,
+    version: ArchiveVersion,
+    int_size: usize,
+    off_size: usize,
+    compression: ArchiveCompression,
+}
+
+struct TocEntry {
+    /// The entry without the data offset (it is copied to the output)
+    raw: Vec<u8>,
+    dump_id: i64,
+    tag: String,
+    desc: String,
+    namespace: String,
+    copy_stmt: String,
+    /// The flag and the data offset in the source archive
+    offset: (u8, u64),
+}
+
+struct ArchiveReader<R> {
+    inner: R,
+    int_size: usize,
+    off_size: usize,
+    /// The read bytes are recorded here (if it is set)
+    recorded: Option<Vec<u8>>,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            int_size: 0,
+            off_size: 0,
+            recorded: None,
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        self.inner
+            .read_exact(&mut buf)
+            .context("Unexpected end of the archive")?;
+        if let Some(recorded) = &mut self.recorded {
+            recorded.extend_from_slice(&buf);
+        }
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// `None` at the end of the archive
+    fn read_block_type(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0];
+        loop {
+            match self.inner.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// The sign byte and the little-endian magnitude of `int_size` bytes
+    fn read_int(&mut self) -> Result<i64> {
+        let bytes = self.read_bytes(self.int_size + 1)?;
+        let value = le_value(&bytes[1..]) as i64;
+        Ok(if bytes[0] == 0 { value } else { -value })
+    }
+
+    /// `None` is the NULL string (the length is `-1`)
+    fn read_str(&mut self) -> Result<Option<String>> {
+        let len = self.read_int()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        let bytes = self.read_bytes(len as usize)?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn read_offset(&mut self) -> Result<(u8, u64)> {
+        let flag = self.read_u8()?;
+        let bytes = self.read_bytes(self.off_size)?;
+        Ok((flag, le_value(&bytes)))
+    }
+
+    fn read_header(&mut self) -> Result<Header> {
+        self.recorded = Some(vec![]);
+        if self.read_bytes(MAGIC.len()).ok().as_deref() != Some(MAGIC) {
+            bail!("Not a pg_dump custom-format archive");
+        }
+        let version = ArchiveVersion(self.read_u8()?, self.read_u8()?, self.read_u8()?);
+        if (version.0, version.1) < MIN_VERSION || (version.0, version.1) > MAX_VERSION {
+            bail!(
+                "Unsupported archive version {} (the versions {}.{} to {}.{} are supported)",
+                version,
+                MIN_VERSION.0,
+                MIN_VERSION.1,
+                MAX_VERSION.0,
+                MAX_VERSION.1
+            );
+        }
+        self.int_size = self.read_u8()? as usize;
+        self.off_size = self.read_u8()? as usize;
+        if !(1..=8).contains(&self.int_size) || !(1..=8).contains(&self.off_size) {
+            bail!(
+                "Unsupported integer sizes of the archive: {} and {}",
+                self.int_size,
+                self.off_size
+            );
+        }
+        let format = self.read_u8()?;
+        if format != FORMAT_CUSTOM {
+            bail!("Not a custom-format archive (the format is {})", format);
+        }
+        let compression = if version.1 >= 15 {
+            match self.read_u8()? {
+                0 => ArchiveCompression::None,
+                1 => ArchiveCompression::Gzip(-1),
+                2 => ArchiveCompression::Lz4,
+                3 => ArchiveCompression::Zstd,
+                other => bail!("Unknown compression of the archive: {}", other),
+            }
+        } else {
+            match self.read_int()? {
+                0 => ArchiveCompression::None,
+                level => ArchiveCompression::Gzip(level),
+            }
+        };
+        // the creation time
+        for _ in 0..7 {
+            self.read_int()?;
+        }
+        // the database name, the server version and the pg_dump version
+        for _ in 0..3 {
+            self.read_str()?;
+        }
+
+        Ok(Header {
+            raw: self.recorded.take().unwrap_or_default(),
+            version,
+            int_size: self.int_size,
+            off_size: self.off_size,
+            compression,
+        })
+    }
+
+    fn read_toc(&mut self, version: ArchiveVersion) -> Result<Vec<TocEntry>> {
+        let count = self.read_int()?;
+        let mut entries = Vec::with_capacity(count.max(0) as usize);
+        for _ in 0..count {
+            self.recorded = Some(vec![]);
+            let dump_id = self.read_int()?;
+            // had a data dumper, the table OID and the OID
+            self.read_int()?;
+            self.read_str()?;
+            self.read_str()?;
+            let tag = self.read_str()?.unwrap_or_default();
+            let desc = self.read_str()?.unwrap_or_default();
+            // the section, the definition and the drop statement
+            self.read_int()?;
+            self.read_str()?;
+            self.read_str()?;
+            let copy_stmt = self.read_str()?.unwrap_or_default();
+            let namespace = self.read_str()?.unwrap_or_default();
+            // the tablespace, the table access method, the relation kind, the owner and "with oids"
+            self.read_str()?;
+            if version.1 >= 14 {
+                self.read_str()?;
+            }
+            if version.1 >= 16 {
+                self.read_int()?;
+            }
+            self.read_str()?;
+            self.read_str()?;
+            // the dependencies (terminated by NULL)
+            while self.read_str()?.is_some() {}
+            let raw = self.recorded.take().unwrap_or_default();
+
+            let offset = self.read_offset()?;
+            entries.push(TocEntry {
+                raw,
+                dump_id,
+                tag,
+                desc,
+                namespace,
+                copy_stmt,
+                offset,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Copies the data chunks as is (up to the terminating empty chunk)
+    fn copy_chunks<W: Write + Seek>(&mut self, w: &mut ArchiveWriter<W>) -> Result<()> {
+        loop {
+            let len = self.read_int()?;
+            w.write_int(len)?;
+            if len <= 0 {
+                return Ok(());
+            }
+            let bytes = self.read_bytes(len as usize)?;
+            w.inner.write_all(&bytes)?;
+        }
+    }
+}
+
+fn le_value(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, b| (value << 8) | u64::from(*b))
+}
+
+/// The data of a block (the chunks are concatenated)
+struct ChunkReader<'a, R> {
+    archive: &'a mut ArchiveReader<R>,
+    remaining: usize,
+    finished: bool,
+}
+
+impl<'a, R: Read> ChunkReader<'a, R> {
+    fn new(archive: &'a mut ArchiveReader<R>) -> Self {
+        Self {
+            archive,
+            remaining: 0,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> Read for ChunkReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            if self.finished {
+                return Ok(0);
+            }
+            let len = self
+                .archive
+                .read_int()
+                .map_err(|e| io::Error::new(io::ErrorKind::UnexpectedEof, e))?;
+            if len <= 0 {
+                self.finished = true;
+            } else {
+                self.remaining = len as usize;
+            }
+        }
+        let len = buf.len().min(self.remaining);
+        let read = self.archive.inner.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Unexpected end of the archive",
+            ));
+        }
+        self.remaining -= read;
+        Ok(read)
+    }
+}
+
+struct ArchiveWriter<W> {
+    inner: W,
+    int_size: usize,
+    off_size: usize,
+}
+
+impl<W: Write + Seek> ArchiveWriter<W> {
+    fn write_int(&mut self, value: i64) -> io::Result<()> {
+        self.inner.write_all(&[u8::from(value < 0)])?;
+        self.inner
+            .write_all(&value.unsigned_abs().to_le_bytes()[..self.int_size])
+    }
+
+    fn write_offset(&mut self, flag: u8, offset: u64) -> io::Result<()> {
+        self.inner.write_all(&[flag])?;
+        self.inner.write_all(&offset.to_le_bytes()[..self.off_size])
+    }
+
+    fn write_toc(&mut self, entries: &[TocEntry], offsets: &HashMap<i64, u64>) -> io::Result<()> {
+        self.write_int(entries.len() as i64)?;
+        for entry in entries {
+            self.inner.write_all(&entry.raw)?;
+            match offsets.get(&entry.dump_id) {
+                Some(offset) => self.write_offset(OFFSET_SET, *offset)?,
+                None if entry.offset.0 == OFFSET_NO_DATA => self.write_offset(OFFSET_NO_DATA, 0)?,
+                None => self.write_offset(OFFSET_NOT_SET, 0)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the data as length-prefixed chunks
+struct ChunkWriter<'a, W> {
+    archive: &'a mut ArchiveWriter<W>,
+    buffer: Vec<u8>,
+}
+
+impl<'a, W: Write + Seek> ChunkWriter<'a, W> {
+    fn new(archive: &'a mut ArchiveWriter<W>) -> Self {
+        Self {
+            archive,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.archive.write_int(self.buffer.len() as i64)?;
+            self.archive.inner.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the rest of the data and the terminating empty chunk
+    fn finish(mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.archive.write_int(0)
+    }
+}
+
+impl<W: Write + Seek> Write for ChunkWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum DataEncoder<'a, W: Write + Seek> {
+    Plain(ChunkWriter<'a, W>),
+    Gzip(ZlibEncoder<ChunkWriter<'a, W>>),
+    Zstd(zstd::Encoder<'static, ChunkWriter<'a, W>>),
+}
+
+impl<'a, W: Write + Seek> DataEncoder<'a, W> {
+    fn new(compression: ArchiveCompression, w: ChunkWriter<'a, W>) -> Result<Self> {
+        Ok(match compression {
+            ArchiveCompression::None => Self::Plain(w),
+            ArchiveCompression::Gzip(level) => {
+                let level = match level {
+                    0..=9 => flate2::Compression::new(level as u32),
+                    _ => flate2::Compression::default(),
+                };
+                Self::Gzip(ZlibEncoder::new(w, level))
+            }
+            ArchiveCompression::Zstd => Self::Zstd(zstd::Encoder::new(w, 0)?),
+            ArchiveCompression::Lz4 => bail!("The lz4 compression of archives is not supported"),
+        })
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.finish(),
+            Self::Gzip(e) => e.finish()?.finish(),
+            Self::Zstd(e) => e.finish()?.finish(),
+        }
+    }
+}
+
+impl<W: Write + Seek> Write for DataEncoder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gzip(e) => e.write(buf),
+            Self::Zstd(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gzip(e) => e.flush(),
+            Self::Zstd(e) => e.flush(),
+        }
+    }
+}
+
+fn decoder<'a>(compression: ArchiveCompression, r: &'a mut dyn Read) -> Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        ArchiveCompression::None => Box::new(r),
+        ArchiveCompression::Gzip(_) => Box::new(ZlibDecoder::new(r)),
+        ArchiveCompression::Zstd => Box::new(zstd::Decoder::new(r)?),
+        ArchiveCompression::Lz4 => bail!("The lz4 compression of archives is not supported"),
+    })
+}
+
+/// The table of a `TABLE DATA` entry with the columns of its COPY statement
+fn entry_table(entry: &TocEntry) -> Result<PgTable> {
+    let mut table = PgTable::new(entry.tag.clone(), entry.namespace.clone());
+    let columns = copy_columns(&entry.copy_stmt).ok_or_else(|| {
+        anyhow!(
+            "Can't parse the COPY statement of {}.{} in the archive: {}",
+            entry.namespace,
+            entry.tag,
+            entry.copy_stmt.trim()
+        )
+    })?;
+    table.set_columns(
+        columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| PgColumn {
+                position: i as i32 + 1,
+                name,
+                data_type: String::new(),
+                inner_type: None,
+                max_length: None,
+            })
+            .collect(),
+    );
+    Ok(table)
+}
+
+/// The column names of `COPY schema.table (column, ...) FROM stdin;` (the identifiers can be quoted)
+fn copy_columns(stmt: &str) -> Option<Vec<String>> {
+    let mut rest = stmt.trim_start().strip_prefix("COPY ")?;
+    // the qualified table name
+    loop {
+        let (_, after) = identifier(rest)?;
+        rest = after;
+        match rest.strip_prefix('.') {
+            Some(after) => rest = after,
+            None => break,
+        }
+    }
+    let mut columns = vec![];
+    let mut rest = match rest.trim_start().strip_prefix('(') {
+        Some(list) => list,
+        // the tables without columns
+        None => return Some(columns),
+    };
+    loop {
+        let (name, after) = identifier(rest.trim_start())?;
+        columns.push(name);
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(',') {
+            rest = after;
+        } else {
+            after.strip_prefix(')')?;
+            return Some(columns);
+        }
+    }
+}
+
+/// The identifier (unquoted) and the rest of the string
+fn identifier(s: &str) -> Option<(String, &str)> {
+    match s.strip_prefix('"') {
+        Some(quoted) => {
+            let mut name = String::new();
+            let mut chars = quoted.char_indices();
+            while let Some((i, c)) = chars.next() {
+                if c != '"' {
+                    name.push(c);
+                } else if quoted[i + 1..].starts_with('"') {
+                    name.push('"');
+                    chars.next();
+                } else {
+                    return Some((name, &quoted[i + 1..]));
+                }
+            }
+            None
+        }
+        None => {
+            let end = s
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(s.len());
+            (end > 0).then(|| (s[..end].to_string(), &s[end..]))
+        }
+    }
+}
+
+/// Transforms the data of the tables with rules in the custom-format archive (the tables are matched by
+/// the names from the archive TOC, the columns by the COPY statements)
+pub fn transform_archive<R, W>(
+    engine: &Engine,
+    input: R,
+    output: W,
+    events: &dyn Indicator,
+) -> Result<ArchiveStats>
+where
+    R: Read,
+    W: Write + Seek,
+{
+    let mut reader = ArchiveReader::new(input);
+    let header = reader.read_header()?;
+    let entries = reader.read_toc(header.version)?;
+    events.debug_msg(&format!(
+        "The archive version is {}, {} TOC entries",
+        header.version,
+        entries.len()
+    ));
+
+    let mut writer = ArchiveWriter {
+        inner: output,
+        int_size: header.int_size,
+        off_size: header.off_size,
+    };
+    writer.inner.write_all(&header.raw)?;
+    let toc_position = writer.inner.stream_position()?;
+    writer.write_toc(&entries, &HashMap::new())?;
+
+    let indexes: HashMap<i64, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.dump_id, i))
+        .collect();
+    let mut stats = ArchiveStats {
+        tables: entries.iter().filter(|e| e.desc == TABLE_DATA).count(),
+        ..ArchiveStats::default()
+    };
+    let mut offsets = HashMap::new();
+    while let Some(block_type) = reader.read_block_type()? {
+        let dump_id = reader.read_int()?;
+        offsets.insert(dump_id, writer.inner.stream_position()?);
+        writer.inner.write_all(&[block_type])?;
+        writer.write_int(dump_id)?;
+
+        let entry = indexes.get(&dump_id).map(|&i| &entries[i]);
+        match block_type {
+            BLOCK_DATA => {
+                let names = entry
+                    .filter(|e| e.desc == TABLE_DATA)
+                    .map(|e| vec![format!("{}.{}", e.namespace, e.tag), e.tag.clone()]);
+                let cfg = names.and_then(|names| engine.settings.find_table(&names));
+                match (entry, cfg) {
+                    (Some(entry), Some(cfg)) => {
+                        let table = entry_table(entry)?;
+                        let rows = transform_data(
+                            engine,
+                            &cfg.name,
+                            &table,
+                            header.compression,
+                            &mut reader,
+                            &mut writer,
+                        )
+                        .with_context(|| {
+                            format!(
+                                "Can't transform the data of {} in the archive",
+                                table.get_full_name()
+                            )
+                        })?;
+                        events.debug_msg(&format!(
+                            "[Archive] {}: {} rows are transformed",
+                            table.get_full_name(),
+                            rows
+                        ));
+                        stats.transformed_tables += 1;
+                        stats.transformed_rows += rows;
+                    }
+                    _ => reader.copy_chunks(&mut writer)?,
+                }
+            }
+            BLOCK_BLOBS => loop {
+                // the large objects: the OID and the chunks of every object (terminated by the zero OID)
+                let oid = reader.read_int()?;
+                writer.write_int(oid)?;
+                if oid == 0 {
+                    break;
+                }
+                reader.copy_chunks(&mut writer)?;
+            },
+            other => bail!("Unknown block type {} in the archive", other),
+        }
+    }
+
+    writer.inner.seek(SeekFrom::Start(toc_position))?;
+    writer.write_toc(&entries, &offsets)?;
+    writer.inner.seek(SeekFrom::End(0))?;
+    writer.inner.flush()?;
+    Ok(stats)
+}
+
+/// Transforms the rows of the data block, returns the count of the rows
+fn transform_data<R: Read, W: Write + Seek>(
+    engine: &Engine,
+    cfg_name: &str,
+    table: &PgTable,
+    compression: ArchiveCompression,
+    reader: &mut ArchiveReader<R>,
+    writer: &mut ArchiveWriter<W>,
+) -> Result<u64> {
+    let mut chunks = ChunkReader::new(reader);
+    let mut encoder = DataEncoder::new(compression, ChunkWriter::new(writer))?;
+    let mut count = 0;
+    {
+        let mut data = BufReader::new(decoder(compression, &mut chunks)?);
+        let mut line = vec![];
+        while data.read_until(b'\n', &mut line)? > 0 {
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            // the end-of-data marker, the rest is copied as is
+            if line == b"\\." {
+                encoder.write_all(b"\\.\n")?;
+                io::copy(&mut data, &mut encoder)?;
+                break;
+            }
+            count += 1;
+            let row = PgRow::from_bytes_row(mem::take(&mut line), count, table.clone());
+            encoder.write_all(&row.transform(engine, cfg_name)?)?;
+            encoder.write_all(b"\n")?;
+        }
+    }
+    // the rest of the stream up to the terminating chunk
+    io::copy(&mut chunks, &mut io::sink())?;
+    encoder.finish()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::SilentIndicator;
+    use datanymizer_engine::Settings;
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    fn fixture() -> Vec<u8> {
+        let path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/postgres/dumps/common.dump");
+        fs::read(path).unwrap()
+    }
+
+    fn transform(config: &str, archive: &[u8]) -> Result<(Vec<u8>, ArchiveStats)> {
+        let engine = Engine::new(Settings::from_yaml(config).unwrap());
+        let mut output = Cursor::new(vec![]);
+        let stats = transform_archive(&engine, archive, &mut output, &SilentIndicator)?;
+        Ok((output.into_inner(), stats))
+    }
+
+    /// The TOC entries and the decompressed data of the `TABLE DATA` entries (by the table tags),
+    /// the data is read by the TOC offsets
+    fn read(archive: &[u8]) -> (Vec<TocEntry>, HashMap<String, Vec<String>>) {
+        let mut reader = ArchiveReader::new(archive);
+        let header = reader.read_header().unwrap();
+        let entries = reader.read_toc(header.version).unwrap();
+
+        let mut data = HashMap::new();
+        for entry in entries.iter().filter(|e| e.desc == TABLE_DATA) {
+            assert_eq!(entry.offset.0, OFFSET_SET, "{}", entry.tag);
+            let mut block = ArchiveReader::new(&archive[entry.offset.1 as usize..]);
+            block.int_size = header.int_size;
+            block.off_size = header.off_size;
+            assert_eq!(block.read_u8().unwrap(), BLOCK_DATA);
+            assert_eq!(block.read_int().unwrap(), entry.dump_id);
+            let mut chunks = ChunkReader::new(&mut block);
+            let mut text = String::new();
+            decoder(header.compression, &mut chunks)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            data.insert(
+                entry.tag.clone(),
+                text.lines().map(|l| l.to_string()).collect(),
+            );
+        }
+        (entries, data)
+    }
+
+    #[test]
+    fn copy_statements() {
+        assert_eq!(
+            copy_columns("COPY public.actor (actor_id, first_name, last_update) FROM stdin;\n"),
+            Some(vec![
+                "actor_id".to_string(),
+                "first_name".to_string(),
+                "last_update".to_string()
+            ])
+        );
+        assert_eq!(
+            copy_columns(r#"COPY "My Schema"."a.b" ("Name", "x""y", id) FROM stdin;"#),
+            Some(vec![
+                "Name".to_string(),
+                "x\"y".to_string(),
+                "id".to_string()
+            ])
+        );
+        assert_eq!(copy_columns("COPY public.empty  FROM stdin;"), Some(vec![]));
+        assert_eq!(copy_columns("COPY public.broken (a, FROM stdin;"), None);
+        assert_eq!(copy_columns(""), None);
+    }
+
+    #[test]
+    fn transform_fixture() {
+        let config = r#"
+            tables:
+              - name: actor
+                rules:
+                  first_name:
+                    template:
+                      format: "Name-{{ prev.actor_id }}"
+        "#;
+        let source = fixture();
+        let (output, stats) = transform(config, &source).unwrap();
+        assert_eq!(stats.transformed_tables, 1);
+        assert!(stats.tables > 1);
+
+        let (source_entries, source_data) = read(&source);
+        let (entries, data) = read(&output);
+        assert_eq!(entries.len(), source_entries.len());
+        assert!(entries
+            .iter()
+            .zip(&source_entries)
+            .all(|(a, b)| a.raw == b.raw && a.offset.0 == b.offset.0));
+
+        let actors = &data["actor"];
+        assert_eq!(actors.len(), source_data["actor"].len());
+        // the rows are followed by the end-of-data marker
+        let end = actors.iter().position(|l| l == "\\.").unwrap();
+        assert_eq!(actors[end..], source_data["actor"][end..]);
+        assert_eq!(stats.transformed_rows, end as u64);
+        for (row, source_row) in actors[..end].iter().zip(&source_data["actor"]) {
+            let fields: Vec<_> = row.split('\t').collect();
+            let source_fields: Vec<_> = source_row.split('\t').collect();
+            assert_eq!(fields[0], source_fields[0]);
+            assert_eq!(fields[1], format!("Name-{}", fields[0]));
+            assert_eq!(fields[2..], source_fields[2..]);
+        }
+        // the other tables are not changed
+        for (tag, rows) in data.iter().filter(|(tag, _)| tag.as_str() != "actor") {
+            assert_eq!(rows, &source_data[tag], "{}", tag);
+        }
+    }
+
+    #[test]
+    fn without_rules() {
+        let source = fixture();
+        let (output, stats) = transform("tables: []", &source).unwrap();
+        assert_eq!(stats.transformed_tables, 0);
+        let (_, source_data) = read(&source);
+        let (_, data) = read(&output);
+        assert_eq!(data, source_data);
+    }
+
+    /// A 1.16 archive (the compression algorithm byte and the relation kinds in the TOC)
+    fn archive_1_16(compression: u8, data: &[u8]) -> Vec<u8> {
+        let mut w = ArchiveWriter {
+            inner: Cursor::new(vec![]),
+            int_size: 4,
+            off_size: 8,
+        };
+        let write_str = |w: &mut ArchiveWriter<Cursor<Vec<u8>>>, s: Option<&str>| match s {
+            Some(s) => {
+                w.write_int(s.len() as i64).unwrap();
+                w.inner.write_all(s.as_bytes()).unwrap();
+            }
+            None => w.write_int(-1).unwrap(),
+        };
+        w.inner.write_all(b"PGDMP\x01\x10\x00\x04\x08\x01").unwrap();
+        w.inner.write_all(&[compression]).unwrap();
+        for _ in 0..7 {
+            w.write_int(0).unwrap();
+        }
+        for s in ["db", "17.0", "17.0"] {
+            write_str(&mut w, Some(s));
+        }
+
+        w.write_int(1).unwrap();
+        w.write_int(7).unwrap();
+        w.write_int(1).unwrap();
+        for s in ["0", "0", "users", TABLE_DATA] {
+            write_str(&mut w, Some(s));
+        }
+        w.write_int(3).unwrap();
+        for s in [
+            Some(""),
+            Some(""),
+            Some("COPY public.users (id, \"E-mail\") FROM stdin;\n"),
+        ] {
+            write_str(&mut w, s);
+        }
+        for s in [Some("public"), Some(""), None] {
+            write_str(&mut w, s);
+        }
+        w.write_int(i64::from(b'r')).unwrap();
+        for s in [Some("owner"), Some("false"), None] {
+            write_str(&mut w, s);
+        }
+        w.write_offset(OFFSET_NOT_SET, 0).unwrap();
+
+        w.inner.write_all(&[BLOCK_DATA]).unwrap();
+        w.write_int(7).unwrap();
+        let mut encoder = match compression {
+            3 => DataEncoder::new(ArchiveCompression::Zstd, ChunkWriter::new(&mut w)),
+            _ => DataEncoder::new(ArchiveCompression::None, ChunkWriter::new(&mut w)),
+        }
+        .unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap();
+        w.inner.into_inner()
+    }
+
+    #[test]
+    fn zstd_archive() {
+        let config = r#"
+            tables:
+              - name: public.users
+                rules:
+                  E-mail:
+                    template:
+                      format: "user{{ prev.id }}@example.com"
+        "#;
+        let source = archive_1_16(3, b"1\ta@b.c\n2\t\\N\n");
+        let (output, stats) = transform(config, &source).unwrap();
+        assert_eq!(stats.transformed_rows, 2);
+        let (_, data) = read(&output);
+        assert_eq!(
+            data["users"],
+            vec![
+                "1\tuser1@example.com".to_string(),
+                "2\tuser2@example.com".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn lz4_archive() {
+        let config = "tables: [{name: users, rules: {id: {template: {format: '1'}}}}]";
+        let err = transform(config, &archive_1_16(2, b"")).unwrap_err();
+        assert!(format!("{:#}", err).contains("lz4"), "{:#}", err);
+        // the archive without the tables with rules is copied
+        assert!(transform("tables: []", &archive_1_16(2, b"")).is_ok());
+    }
+
+    #[test]
+    fn unsupported_versions() {
+        let mut source = fixture();
+        source[6] = 9;
+        let err = transform("tables: []", &source).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported archive version 1.9.0 (the versions 1.12 to 1.16 are supported)"
+        );
+
+        let mut source = fixture();
+        source[6] = 17;
+        source[7] = 2;
+        let err = transform("tables: []", &source).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Unsupported archive version 1.17.2"));
+    }
+
+    #[test]
+    fn not_archives() {
+        let err = transform("tables: []", b"--\n-- PostgreSQL database dump\n").unwrap_err();
+        assert_eq!(err.to_string(), "Not a pg_dump custom-format archive");
+        assert!(transform("tables: []", b"").is_err());
+        // the truncated archive
+        let source = fixture();
+        assert!(transform("tables: []", &source[..source.len() / 2]).is_err());
+    }
+}
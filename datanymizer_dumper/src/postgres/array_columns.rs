@@ -0,0 +1,192 @@
+use super::{
+    copy_codec::{self, ArrayItem},
+    table::PgTable,
+};
+use datanymizer_engine::{ElementCodec, Table as TableCfg, TransformError, TransformResult};
+use postgres::types::Type;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+/// The `data_type` of the array columns in `information_schema.columns`
+const ARRAY_DATA_TYPE: &str = "ARRAY";
+
+/// The array columns with rules of a table (by the column indexes): the rules are applied
+/// to the array elements instead of the array literals
+#[derive(Debug, Default)]
+pub struct ArrayColumns {
+    columns: HashSet<usize>,
+}
+
+impl ArrayColumns {
+    /// `box[]` columns are transformed as a whole (their elements are delimited with `;`)
+    pub fn new(table: &PgTable, cfg: &TableCfg, skipped: &HashSet<String>) -> Self {
+        let columns = table
+            .columns
+            .iter()
+            .filter(|c| cfg.rules.contains_key(&c.name) && !skipped.contains(&c.name))
+            .filter(|c| {
+                c.data_type == ARRAY_DATA_TYPE && c.inner_type != Some(Type::BOX_ARRAY.oid())
+            })
+            .map(|c| (c.position - 1) as usize)
+            .collect();
+        Self { columns }
+    }
+
+    /// The codecs of the columns for `Engine::process_row_elements`
+    pub fn codecs(&self) -> HashMap<usize, &dyn ElementCodec> {
+        self.columns
+            .iter()
+            .map(|&i| (i, &ArrayCodec as &dyn ElementCodec))
+            .collect()
+    }
+}
+
+/// The elements of the one-dimensional array literals (the raw COPY field values).
+///
+/// The rules get the elements as the values of the scalar columns (escaped for COPY), their results
+/// are taken the same way (`\N` is NULL). NULL arrays, empty arrays and NULL elements are not transformed.
+pub struct ArrayCodec;
+
+impl ElementCodec for ArrayCodec {
+    fn transform_elements(
+        &self,
+        field_name: &str,
+        value: &str,
+        transform: &mut dyn FnMut(&str) -> TransformResult,
+    ) -> TransformResult {
+        let error = |reason: String| TransformError {
+            field_name: field_name.to_string(),
+            field_value: value.to_string(),
+            reason,
+        };
+
+        let literal = match copy_codec::unescape_str(value.as_bytes()) {
+            Ok(Some(literal)) => literal,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(error(e.to_string())),
+        };
+        let mut array = copy_codec::parse_array(&literal).map_err(|e| error(e.to_string()))?;
+        if array
+            .items
+            .iter()
+            .any(|item| matches!(item, ArrayItem::Array(_)))
+        {
+            return Err(error(
+                "multidimensional arrays are not supported by the element rules".to_string(),
+            ));
+        }
+
+        let mut changed = false;
+        for item in &mut array.items {
+            let element = match item {
+                ArrayItem::Value(element) => element,
+                _ => continue,
+            };
+            if let Some(mut result) = transform(&copy_codec::escape_str(element))? {
+                copy_codec::escape_transformed(&mut result);
+                *item = match copy_codec::unescape_str(result.as_bytes()) {
+                    Ok(Some(element)) => ArrayItem::Value(Cow::Owned(element.into_owned())),
+                    Ok(None) => ArrayItem::Null,
+                    Err(e) => return Err(error(e.to_string())),
+                };
+                changed = true;
+            }
+        }
+
+        Ok(changed.then(|| array.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::column::PgColumn;
+    use datanymizer_engine::Settings;
+
+    fn transform(value: &str, f: impl Fn(&str) -> Option<String>) -> TransformResult {
+        ArrayCodec.transform_elements("users.tags", value, &mut |element| Ok(f(element)))
+    }
+
+    fn upper(value: &str) -> Option<String> {
+        transform(value, |e| Some(e.to_uppercase())).unwrap()
+    }
+
+    #[test]
+    fn elements() {
+        assert_eq!(upper("{a,b}").unwrap(), "{A,B}");
+        // the quoting and the escapes of the elements
+        assert_eq!(
+            upper(r#"{"a,b","c}","d\\"e",NULL,"null"}"#).unwrap(),
+            r#"{"A,B","C}","D\"E",NULL,"NULL"}"#
+        );
+        assert_eq!(upper("[0:1]={a,b}").unwrap(), "[0:1]={A,B}");
+        // the rules get the elements escaped for COPY
+        assert_eq!(
+            transform(r#"{"a\tb"}"#, |e| Some(e.replace(r"\t", " "))).unwrap(),
+            Some(r#"{"a b"}"#.to_string())
+        );
+        // NULL results
+        assert_eq!(
+            transform("{a,b}", |e| (e == "a").then(|| r"\N".to_string())).unwrap(),
+            Some("{NULL,b}".to_string())
+        );
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(upper(r"\N"), None);
+        assert_eq!(upper("{}"), None);
+        assert_eq!(upper("{NULL,NULL}"), None);
+        assert_eq!(transform("{a,b}", |_| None).unwrap(), None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = transform("{{a,b},{c,d}}", |e| Some(e.to_string())).unwrap_err();
+        assert_eq!(error.field_name, "users.tags");
+        assert_eq!(
+            error.reason,
+            "multidimensional arrays are not supported by the element rules"
+        );
+        assert!(transform("{a,b", |e| Some(e.to_string()))
+            .unwrap_err()
+            .reason
+            .starts_with("Invalid array literal"));
+    }
+
+    #[test]
+    fn columns() {
+        let mut table = PgTable::new("users".to_string(), "public".to_string());
+        let column = |position, name: &str, data_type: &str, oid: Type| PgColumn {
+            position,
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            inner_type: Some(oid.oid()),
+            max_length: None,
+        };
+        table.set_columns(vec![
+            column(1, "id", "integer", Type::INT4),
+            column(2, "emails", "ARRAY", Type::TEXT_ARRAY),
+            column(3, "phones", "ARRAY", Type::VARCHAR_ARRAY),
+            column(4, "areas", "ARRAY", Type::BOX_ARRAY),
+            column(5, "codes", "ARRAY", Type::INT4_ARRAY),
+        ]);
+        let settings = Settings::from_yaml(
+            r#"
+            tables:
+              - name: users
+                rules:
+                  id: {random_num: {}}
+                  emails: {email: {}}
+                  phones: {phone: {}}
+                  areas: {template: {format: "{}"}}
+            "#,
+        )
+        .unwrap();
+        let skipped = ["phones".to_string()].into_iter().collect();
+        let columns = ArrayColumns::new(&table, &settings.tables[0], &skipped);
+        assert_eq!(columns.codecs().keys().collect::<Vec<_>>(), vec![&1]);
+    }
+}
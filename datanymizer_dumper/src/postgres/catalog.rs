@@ -0,0 +1,278 @@
+use super::{
+    column::PgColumn, compatibility::Compatibility, foreign_key::ForeignKey, sequence::PgSequence,
+    table::PgTable, throttle::Throttle,
+};
+use anyhow::Result;
+use postgres::Client;
+use std::collections::{HashMap, HashSet};
+
+const ALL_COLUMNS_QUERY: &str = "SELECT cc.table_schema, cc.table_name,
+                                     cc.column_name, cc.ordinal_position, cc.data_type, pt.oid,
+                                     cc.character_maximum_length::int AS character_maximum_length
+                                 FROM information_schema.columns as cc
+                                 JOIN pg_catalog.pg_type as pt
+                                 ON cc.udt_name = pt.typname
+                                 WHERE cc.table_schema != 'pg_catalog'
+                                 AND cc.table_schema != 'information_schema'
+                                 ORDER BY cc.table_schema, cc.table_name, cc.ordinal_position ASC";
+
+// The same as `TABLE_SEQUENCES` in the inspector, but for all tables
+const ALL_SEQUENCES_QUERY: &str = "SELECT tn.nspname::text, t.relname::text, a.attname,
+                                       quote_ident(n.nspname) || '.' || quote_ident(s.relname)
+                                   FROM pg_catalog.pg_depend d
+                                   JOIN pg_catalog.pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+                                   JOIN pg_catalog.pg_namespace n ON n.oid = s.relnamespace
+                                   JOIN pg_catalog.pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                                   JOIN pg_catalog.pg_class t ON t.oid = d.refobjid
+                                   JOIN pg_catalog.pg_namespace tn ON tn.oid = t.relnamespace
+                                   WHERE d.classid = 'pg_catalog.pg_class'::regclass
+                                   AND d.refclassid = 'pg_catalog.pg_class'::regclass
+                                   AND d.deptype IN ('a', 'i')
+                                   UNION
+                                   SELECT tn.nspname::text, t.relname::text, a.attname,
+                                       quote_ident(n.nspname) || '.' || quote_ident(s.relname)
+                                   FROM pg_catalog.pg_attrdef ad
+                                   JOIN pg_catalog.pg_attribute a ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+                                   JOIN pg_catalog.pg_depend d ON d.classid = 'pg_catalog.pg_attrdef'::regclass
+                                       AND d.objid = ad.oid
+                                       AND d.refclassid = 'pg_catalog.pg_class'::regclass
+                                   JOIN pg_catalog.pg_class s ON s.oid = d.refobjid AND s.relkind = 'S'
+                                   JOIN pg_catalog.pg_namespace n ON n.oid = s.relnamespace
+                                   JOIN pg_catalog.pg_class t ON t.oid = ad.adrelid
+                                   JOIN pg_catalog.pg_namespace tn ON tn.oid = t.relnamespace
+                                   ORDER BY 1, 2, 3, 4";
+
+const ALL_FOREIGN_KEYS_QUERY: &str = "SELECT
+                                        tc.table_schema,
+                                        tc.constraint_name,
+                                        tc.table_name,
+                                        kcu.column_name,
+                                        ccu.table_schema AS foreign_table_schema,
+                                        ccu.table_name AS foreign_table_name,
+                                        ccu.column_name AS foreign_column_name
+                                    FROM
+                                        information_schema.table_constraints AS tc
+                                        JOIN information_schema.key_column_usage AS kcu
+                                        ON tc.constraint_name = kcu.constraint_name
+                                        AND tc.table_schema = kcu.table_schema
+                                        JOIN information_schema.constraint_column_usage AS ccu
+                                        ON ccu.constraint_name = tc.constraint_name
+                                        AND ccu.constraint_schema = tc.constraint_schema
+                                    WHERE tc.constraint_type = 'FOREIGN KEY'";
+
+// Tables created by extensions (e.g., `spatial_ref_sys` of PostGIS)
+const EXTENSION_TABLES_QUERY: &str = "SELECT n.nspname::text, c.relname::text, e.extname::text
+                                      FROM pg_catalog.pg_depend d
+                                      JOIN pg_catalog.pg_extension e ON e.oid = d.refobjid
+                                      JOIN pg_catalog.pg_class c ON c.oid = d.objid
+                                      JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                                      WHERE d.classid = 'pg_catalog.pg_class'::regclass
+                                      AND d.refclassid = 'pg_catalog.pg_extension'::regclass
+                                      AND d.deptype = 'e'
+                                      AND c.relkind IN ('r', 'p')";
+
+const HAS_TIMESCALEDB_CHUNKS_QUERY: &str =
+    "SELECT pg_catalog.to_regclass('_timescaledb_catalog.chunk') IS NOT NULL";
+
+// TimescaleDB chunks with their hypertables
+const TIMESCALEDB_CHUNKS_QUERY: &str = "SELECT c.schema_name::text, c.table_name::text,
+                                            h.schema_name || '.' || h.table_name
+                                        FROM _timescaledb_catalog.chunk c
+                                        JOIN _timescaledb_catalog.hypertable h ON h.id = c.hypertable_id";
+
+// The partitions (the partitioned partitions of the multi-level partitioning too) with their partitioned tables
+const PARTITIONS_QUERY: &str = "SELECT n.nspname::text, c.relname::text, pn.nspname::text, p.relname::text
+                                FROM pg_catalog.pg_inherits i
+                                JOIN pg_catalog.pg_class c ON c.oid = i.inhrelid
+                                JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                                JOIN pg_catalog.pg_class p ON p.oid = i.inhparent AND p.relkind = 'p'
+                                JOIN pg_catalog.pg_namespace pn ON pn.oid = p.relnamespace";
+
+const PARTITIONED_TABLES_QUERY: &str = "SELECT n.nspname::text, c.relname::text
+                                        FROM pg_catalog.pg_class c
+                                        JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                                        WHERE c.relkind = 'p'";
+
+/// (schema, table)
+type TableKey = (String, String);
+
+/// Catalog data of all tables, fetched with a few set-based queries.
+/// The per-table inspector methods take the data from here instead of querying the catalog
+/// for every table (it is too slow for databases with thousands of tables).
+#[derive(Debug, Default)]
+pub struct CatalogCache {
+    sizes: HashMap<TableKey, i64>,
+    columns: HashMap<TableKey, Vec<PgColumn>>,
+    sequences: HashMap<TableKey, Vec<PgSequence>>,
+    /// Foreign keys by the referencing table names (without schemas, as the per-table query does)
+    foreign_keys: HashMap<String, Vec<ForeignKey>>,
+    /// The extensions that own the tables
+    extensions: HashMap<TableKey, String>,
+    /// Full names of the hypertables of TimescaleDB chunks
+    hypertables: HashMap<TableKey, String>,
+    /// The partitioned tables (the roots and the partitioned partitions)
+    partitioned: HashSet<TableKey>,
+    /// The partitioned tables of the partitions
+    partition_parents: HashMap<TableKey, TableKey>,
+}
+
+impl CatalogCache {
+    pub fn fetch(
+        client: &mut Client,
+        compatibility: Compatibility,
+        throttle: &mut Throttle,
+    ) -> Result<Self> {
+        let mut cache = Self::default();
+
+        throttle.wait();
+        for row in client.query(compatibility.all_table_sizes_query(), &[])? {
+            cache.sizes.insert((row.get(0), row.get(1)), row.get("len"));
+        }
+
+        throttle.wait();
+        for row in client.query(ALL_COLUMNS_QUERY, &[])? {
+            let key = (row.get("table_schema"), row.get("table_name"));
+            cache.columns.entry(key).or_default().push(row.into());
+        }
+
+        throttle.wait();
+        for row in client.query(ALL_SEQUENCES_QUERY, &[])? {
+            let key = (row.get(0), row.get(1));
+            cache.sequences.entry(key).or_default().push(PgSequence {
+                column: row.get(2),
+                full_name: row.get(3),
+            });
+        }
+
+        throttle.wait();
+        for row in client.query(ALL_FOREIGN_KEYS_QUERY, &[])? {
+            let fkey: ForeignKey = row.into();
+            cache
+                .foreign_keys
+                .entry(fkey.table_name.clone())
+                .or_default()
+                .push(fkey);
+        }
+
+        throttle.wait();
+        for row in client.query(EXTENSION_TABLES_QUERY, &[])? {
+            cache
+                .extensions
+                .insert((row.get(0), row.get(1)), row.get(2));
+        }
+
+        throttle.wait();
+        for row in client.query(PARTITIONED_TABLES_QUERY, &[])? {
+            cache.partitioned.insert((row.get(0), row.get(1)));
+        }
+
+        throttle.wait();
+        for row in client.query(PARTITIONS_QUERY, &[])? {
+            cache
+                .partition_parents
+                .insert((row.get(0), row.get(1)), (row.get(2), row.get(3)));
+        }
+
+        throttle.wait();
+        if client.query_one(HAS_TIMESCALEDB_CHUNKS_QUERY, &[])?.get(0) {
+            throttle.wait();
+            for row in client.query(TIMESCALEDB_CHUNKS_QUERY, &[])? {
+                cache
+                    .hypertables
+                    .insert((row.get(0), row.get(1)), row.get(2));
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Returns `None` for tables that are not in the cache (e.g., created after the fetching)
+    pub fn size(&self, table: &PgTable) -> Option<i64> {
+        self.sizes.get(&key(table)).copied()
+    }
+
+    pub fn columns(&self, table: &PgTable) -> Option<Vec<PgColumn>> {
+        self.known(table)
+            .then(|| self.columns.get(&key(table)).cloned().unwrap_or_default())
+    }
+
+    pub fn sequences(&self, table: &PgTable) -> Option<Vec<PgSequence>> {
+        self.known(table)
+            .then(|| self.sequences.get(&key(table)).cloned().unwrap_or_default())
+    }
+
+    /// Foreign keys of the tables with this name (in all schemas)
+    pub fn foreign_keys(&self, table: &PgTable) -> Option<&[ForeignKey]> {
+        self.known(table).then(|| {
+            self.foreign_keys
+                .get(&table.tablename)
+                .map(|k| k.as_slice())
+                .unwrap_or_default()
+        })
+    }
+
+    /// The extension that owns the table (if any)
+    pub fn extension(&self, table: &PgTable) -> Option<String> {
+        self.extensions.get(&key(table)).cloned()
+    }
+
+    /// The hypertable of the TimescaleDB chunk (if the table is a chunk)
+    pub fn hypertable(&self, table: &PgTable) -> Option<String> {
+        self.hypertables.get(&key(table)).cloned()
+    }
+
+    /// Whether the table is partitioned (its rows are in the partitions)
+    pub fn is_partitioned(&self, table: &PgTable) -> bool {
+        self.partitioned.contains(&key(table))
+    }
+
+    /// The root partitioned table of the partition (if the table is a partition)
+    pub fn partition_root(&self, table: &PgTable) -> Option<String> {
+        self.partition_ancestors(&key(table))
+            .last()
+            .map(|(schema, name)| format!("{}.{}", schema, name))
+    }
+
+    /// The partitions with the rows of the partitioned table (at all levels, the partitioned partitions
+    /// have no rows of their own)
+    pub fn leaf_partitions(&self, table: &PgTable) -> Vec<PgTable> {
+        let table = key(table);
+        let mut partitions: Vec<_> = self
+            .partition_parents
+            .keys()
+            .filter(|k| !self.partitioned.contains(*k))
+            .filter(|k| self.partition_ancestors(k).contains(&&table))
+            .map(|(schema, name)| PgTable::new(name.clone(), schema.clone()))
+            .collect();
+        partitions
+            .sort_by(|a, b| (&a.schemaname, &a.tablename).cmp(&(&b.schemaname, &b.tablename)));
+        partitions
+    }
+
+    // The partitioned tables of the partition from its parent to the root
+    fn partition_ancestors(&self, table: &TableKey) -> Vec<&TableKey> {
+        let mut ancestors = vec![];
+        let mut current = table;
+        while let Some(parent) = self.partition_parents.get(current) {
+            ancestors.push(parent);
+            current = parent;
+        }
+        ancestors
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    fn known(&self, table: &PgTable) -> bool {
+        self.sizes.contains_key(&key(table))
+    }
+}
+
+fn key(table: &PgTable) -> TableKey {
+    (table.schemaname.clone(), table.tablename.clone())
+}
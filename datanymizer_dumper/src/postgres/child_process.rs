@@ -0,0 +1,218 @@
+//! The spawned `pg_dump` processes.
+//!
+//! [`ChildGuard`] owns a child process and kills and reaps it when it is dropped before the process is finished
+//! (e.g., the dump fails while reading the output), so no process keeps running (and holding a snapshot on the server)
+//! or stays a zombie. It is the `kill_on_drop` behavior of async process APIs.
+//!
+//! On Unix, `SIGINT`, `SIGTERM` and `SIGHUP` of the dumper are forwarded to the running children before the dumper
+//! terminates, so the children stop with the dumper even if the signal is sent to the dumper process only
+//! (e.g., by a job system, Ctrl-C in a terminal signals the whole process group anyway). The children stay
+//! in the process group of the dumper, so `pg_dump` can still prompt for a password. The killed `pg_dump` closes
+//! its connection, so the server terminates its session.
+
+use std::{
+    io::{self, Read},
+    process::{Child, Command, Output, Stdio},
+    thread,
+};
+
+/// A child process that is killed and reaped on drop (unless it is finished)
+#[derive(Debug)]
+pub struct ChildGuard {
+    child: Option<Child>,
+}
+
+impl ChildGuard {
+    /// Spawns the command with piped stdout and stderr
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        signals::install();
+
+        let child = command.spawn()?;
+        #[cfg(unix)]
+        signals::register(child.id());
+        Ok(Self { child: Some(child) })
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(|c| c.id())
+    }
+
+    /// Reads the output and waits for the process (it is killed if reading fails)
+    pub fn output(mut self) -> io::Result<Output> {
+        let child = match self.child.as_mut() {
+            Some(child) => child,
+            None => return Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut buf = vec![];
+                stderr.read_to_end(&mut buf).map(|_| buf)
+            })
+        });
+        let mut stdout = vec![];
+        if let Some(mut out) = child.stdout.take() {
+            out.read_to_end(&mut stdout)?;
+        }
+        let stderr = match stderr {
+            Some(reader) => reader
+                .join()
+                .map_err(|_| io::Error::other("stderr reader panicked"))??,
+            None => vec![],
+        };
+        let status = child.wait()?;
+        self.release();
+
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    // The process is finished and reaped
+    fn release(&mut self) {
+        if let Some(_child) = self.child.take() {
+            #[cfg(unix)]
+            signals::unregister(_child.id());
+        }
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            // it fails only if the process is already finished, it is reaped anyway
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.release();
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::{
+        atomic::{AtomicI32, Ordering},
+        Once,
+    };
+
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// The PIDs of the running children (`0` is a free slot), the signal handler can read them
+    static CHILDREN: [AtomicI32; 16] = [const { AtomicI32::new(0) }; 16];
+    static INSTALL: Once = Once::new();
+
+    pub(super) fn install() {
+        INSTALL.call_once(|| {
+            for signal in SIGNALS {
+                // SAFETY: the handler uses async-signal-safe functions only
+                unsafe {
+                    libc::signal(
+                        signal,
+                        handle as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                    );
+                }
+            }
+        });
+    }
+
+    // If there is no free slot, the child is only killed on drop
+    pub(super) fn register(pid: u32) {
+        for child in &CHILDREN {
+            if child
+                .compare_exchange(0, pid as i32, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    pub(super) fn unregister(pid: u32) {
+        for child in &CHILDREN {
+            let _ = child.compare_exchange(pid as i32, 0, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    /// Sends the signal to the running children
+    pub(super) fn forward(signal: libc::c_int) {
+        for child in &CHILDREN {
+            let pid = child.load(Ordering::SeqCst);
+            if pid > 0 {
+                // SAFETY: `kill` is async-signal-safe
+                unsafe {
+                    libc::kill(pid, signal);
+                }
+            }
+        }
+    }
+
+    // Forwards the signal and terminates the dumper with the default action of the signal
+    extern "C" fn handle(signal: libc::c_int) {
+        forward(signal);
+        // SAFETY: `signal` and `raise` are async-signal-safe
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{os::unix::process::ExitStatusExt, sync::Mutex, thread::sleep, time::Duration};
+
+    // the signals are forwarded to the children of all tests
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    // `kill(pid, 0)` fails for the reaped processes only (it succeeds for the zombies)
+    fn is_alive(pid: u32) -> bool {
+        unsafe { libc::kill(pid as i32, 0) == 0 }
+    }
+
+    #[test]
+    fn output() {
+        let _serial = SERIAL.lock().unwrap();
+        let output =
+            ChildGuard::spawn(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+                .unwrap()
+                .output()
+                .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn killed_on_drop() {
+        let _serial = SERIAL.lock().unwrap();
+        let guard = ChildGuard::spawn(Command::new("sleep").arg("30")).unwrap();
+        let pid = guard.id().unwrap();
+        assert!(is_alive(pid));
+
+        // an early failure: the guard is dropped before the output is read
+        drop(guard);
+        assert!(!is_alive(pid));
+    }
+
+    #[test]
+    fn forwarded_signals() {
+        let _serial = SERIAL.lock().unwrap();
+        let mut guard = ChildGuard::spawn(Command::new("sleep").arg("30")).unwrap();
+        let pid = guard.id().unwrap();
+
+        signals::forward(libc::SIGTERM);
+        let status = guard.child.as_mut().unwrap().wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        guard.release();
+        sleep(Duration::from_millis(10));
+        assert!(!is_alive(pid));
+    }
+}
@@ -13,6 +13,8 @@ pub struct PgColumn {
 
     /// Inner postgres type (oid)
     pub inner_type: Option<u32>,
+    /// Maximum length of character types (e.g., `varchar(n)`)
+    pub max_length: Option<i32>,
 }
 
 impl PartialEq for PgColumn {
@@ -42,6 +44,7 @@ fn from(row: PostgresRow) -> Self {
             name: row.get("column_name"),
             data_type: row.get("data_type"),
             inner_type: Some(oid),
+            max_length: row.get("character_maximum_length"),
         }
     }
 }
@@ -75,12 +78,14 @@ fn ordering_test() {
             name: String::from("Column1"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
         let col2 = &PgColumn {
             position: 2,
             name: String::from("Column2"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
 
         let col3 = &PgColumn {
@@ -88,6 +93,7 @@ fn ordering_test() {
             name: String::from("Column1"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
 
         assert_eq!(col1, col3);
@@ -0,0 +1,342 @@
+use super::child_process::ChildGuard;
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fmt::{self, Display, Formatter},
+    process::Command,
+};
+
+/// The oldest PostgreSQL major version we can dump from
+pub const MIN_SUPPORTED_MAJOR: u32 = 11;
+
+/// The row estimate of a table: the density of the analyzed pages (`reltuples / relpages`) times the current pages,
+/// as the planner does. The tables without the page stats (never analyzed, truncated, or with all rows in TOAST)
+/// get `reltuples` as is. Since PostgreSQL 14 `reltuples` is `-1` for tables that have never been vacuumed
+/// or analyzed.
+const TABLE_SIZE_QUERY_PRE_14: &str = "SELECT
+    (CASE WHEN pg_catalog.pg_class.relpages > 0 AND pg_catalog.pg_class.reltuples > 0
+        THEN pg_catalog.pg_class.reltuples / pg_catalog.pg_class.relpages * (
+            pg_relation_size(pg_catalog.pg_class.oid) / current_setting('block_size')::bigint
+        )
+        ELSE pg_catalog.pg_class.reltuples
+    END)::bigint AS len
+    FROM pg_catalog.pg_class
+    INNER JOIN pg_catalog.pg_namespace ON pg_catalog.pg_class.relnamespace = pg_catalog.pg_namespace.oid
+    WHERE pg_catalog.pg_class.relname = $1 AND pg_catalog.pg_namespace.nspname = $2
+    AND pg_catalog.pg_class.relkind IN ('r', 'p')";
+
+const TABLE_SIZE_QUERY: &str = "SELECT
+    (CASE WHEN pg_catalog.pg_class.relpages > 0 AND pg_catalog.pg_class.reltuples > 0
+        THEN pg_catalog.pg_class.reltuples / pg_catalog.pg_class.relpages * (
+            pg_relation_size(pg_catalog.pg_class.oid) / current_setting('block_size')::bigint
+        )
+        ELSE GREATEST(pg_catalog.pg_class.reltuples, 0)
+    END)::bigint AS len
+    FROM pg_catalog.pg_class
+    INNER JOIN pg_catalog.pg_namespace ON pg_catalog.pg_class.relnamespace = pg_catalog.pg_namespace.oid
+    WHERE pg_catalog.pg_class.relname = $1 AND pg_catalog.pg_namespace.nspname = $2
+    AND pg_catalog.pg_class.relkind IN ('r', 'p')";
+
+const ALL_TABLE_SIZES_QUERY_PRE_14: &str = "SELECT
+    pg_catalog.pg_namespace.nspname::text, pg_catalog.pg_class.relname::text,
+    (CASE WHEN pg_catalog.pg_class.relpages > 0 AND pg_catalog.pg_class.reltuples > 0
+        THEN pg_catalog.pg_class.reltuples / pg_catalog.pg_class.relpages * (
+            pg_relation_size(pg_catalog.pg_class.oid) / current_setting('block_size')::bigint
+        )
+        ELSE pg_catalog.pg_class.reltuples
+    END)::bigint AS len
+    FROM pg_catalog.pg_class
+    INNER JOIN pg_catalog.pg_namespace ON pg_catalog.pg_class.relnamespace = pg_catalog.pg_namespace.oid
+    WHERE pg_catalog.pg_class.relkind IN ('r', 'p')
+    AND pg_catalog.pg_namespace.nspname != 'pg_catalog'
+    AND pg_catalog.pg_namespace.nspname != 'information_schema'";
+
+const ALL_TABLE_SIZES_QUERY: &str = "SELECT
+    pg_catalog.pg_namespace.nspname::text, pg_catalog.pg_class.relname::text,
+    (CASE WHEN pg_catalog.pg_class.relpages > 0 AND pg_catalog.pg_class.reltuples > 0
+        THEN pg_catalog.pg_class.reltuples / pg_catalog.pg_class.relpages * (
+            pg_relation_size(pg_catalog.pg_class.oid) / current_setting('block_size')::bigint
+        )
+        ELSE GREATEST(pg_catalog.pg_class.reltuples, 0)
+    END)::bigint AS len
+    FROM pg_catalog.pg_class
+    INNER JOIN pg_catalog.pg_namespace ON pg_catalog.pg_class.relnamespace = pg_catalog.pg_namespace.oid
+    WHERE pg_catalog.pg_class.relkind IN ('r', 'p')
+    AND pg_catalog.pg_namespace.nspname != 'pg_catalog'
+    AND pg_catalog.pg_namespace.nspname != 'information_schema'";
+
+/// `pg_dump` flags that appeared in some major version (the flag, the first version with it)
+const VERSIONED_PG_DUMP_FLAGS: [(&str, u32); 5] = [
+    ("--include-foreign-data", 13),
+    ("--no-toast-compression", 14),
+    ("--no-table-access-method", 15),
+    ("--table-and-children", 16),
+    ("--filter", 17),
+];
+
+/// `pg_dump` flags that break the plain SQL output we are merging with the data section
+const FORBIDDEN_PG_DUMP_FLAGS: [&str; 2] = ["-Z", "--compress"];
+const PLAIN_FORMATS: [&str; 2] = ["p", "plain"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl PgVersion {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parses the `server_version_num` setting (e.g., `160002` for 16.2 or `90624` for 9.6.24)
+    pub fn from_version_num(num: u32) -> Self {
+        if num >= 100_000 {
+            Self::new(num / 10_000, num % 10_000)
+        } else {
+            Self::new(num / 10_000, num / 100 % 100)
+        }
+    }
+
+    /// Parses the `pg_dump --version` output (e.g., `pg_dump (PostgreSQL) 16.2 (Debian 16.2-1)`)
+    pub fn from_pg_dump_output(output: &str) -> Result<Self> {
+        let version = output
+            .split_whitespace()
+            .find(|s| s.starts_with(|c: char| c.is_ascii_digit()))
+            .ok_or_else(|| anyhow!("Can't parse the pg_dump version: {}", output.trim()))?;
+        let mut numbers = version
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|s| s.parse::<u32>().unwrap_or_default());
+
+        let major = numbers.next().unwrap_or_default();
+        let minor = numbers.next().unwrap_or_default();
+
+        Ok(Self::new(major, minor))
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.major >= MIN_SUPPORTED_MAJOR
+    }
+}
+
+impl Display for PgVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Runs `pg_dump --version` and returns the version of the `pg_dump` program
+pub fn pg_dump_version(program: &str) -> Result<PgVersion> {
+    let output = ChildGuard::spawn(Command::new(program).arg("--version"))
+        .and_then(ChildGuard::output)
+        .map_err(|e| anyhow!("Can't run `{} --version`: {}", program, e))?;
+    PgVersion::from_pg_dump_output(String::from_utf8_lossy(&output.stdout).as_ref())
+}
+
+/// Catalog queries and `pg_dump` restrictions for the particular PostgreSQL server version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compatibility {
+    pub server_version: PgVersion,
+}
+
+impl Compatibility {
+    pub fn new(server_version: PgVersion) -> Result<Self> {
+        if !server_version.is_supported() {
+            bail!(
+                "PostgreSQL {} is not supported, the minimal supported version is {}",
+                server_version,
+                MIN_SUPPORTED_MAJOR
+            );
+        }
+
+        Ok(Self { server_version })
+    }
+
+    pub fn table_size_query(&self) -> &'static str {
+        if self.server_version.major >= 14 {
+            TABLE_SIZE_QUERY
+        } else {
+            TABLE_SIZE_QUERY_PRE_14
+        }
+    }
+
+    /// The size estimates of all tables (with the schema and table names)
+    pub fn all_table_sizes_query(&self) -> &'static str {
+        if self.server_version.major >= 14 {
+            ALL_TABLE_SIZES_QUERY
+        } else {
+            ALL_TABLE_SIZES_QUERY_PRE_14
+        }
+    }
+
+    /// Checks that `pg_dump` can dump this server and understands all passed arguments
+    pub fn check_pg_dump(&self, pg_dump_version: PgVersion, args: &[String]) -> Result<()> {
+        if pg_dump_version.major < self.server_version.major {
+            bail!(
+                "pg_dump {} can't dump the PostgreSQL {} server, please use pg_dump {} or newer",
+                pg_dump_version,
+                self.server_version,
+                self.server_version.major
+            );
+        }
+
+        for arg in args {
+            let flag = arg.split('=').next().unwrap_or_default();
+
+            if FORBIDDEN_PG_DUMP_FLAGS
+                .iter()
+                .any(|f| flag == *f || (f.len() == 2 && flag.starts_with(f)))
+                || is_non_plain_format(arg)
+            {
+                bail!(
+                    "The pg_dump argument `{}` is not supported: the dump is always produced in the plain format",
+                    arg
+                );
+            }
+
+            if let Some((_, since)) = VERSIONED_PG_DUMP_FLAGS.iter().find(|(f, _)| *f == flag) {
+                if pg_dump_version.major < *since {
+                    bail!(
+                        "The pg_dump argument `{}` requires pg_dump {} or newer (found {})",
+                        arg,
+                        since,
+                        pg_dump_version
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_non_plain_format(arg: &str) -> bool {
+    let format = if let Some(f) = arg.strip_prefix("--format=") {
+        f
+    } else if let Some(f) = arg.strip_prefix("-F") {
+        f
+    } else {
+        return false;
+    };
+
+    !format.is_empty() && !PLAIN_FORMATS.contains(&format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod pg_version {
+        use super::*;
+
+        #[test]
+        fn from_version_num() {
+            assert_eq!(PgVersion::from_version_num(170000), PgVersion::new(17, 0));
+            assert_eq!(PgVersion::from_version_num(160002), PgVersion::new(16, 2));
+            assert_eq!(PgVersion::from_version_num(110021), PgVersion::new(11, 21));
+            assert_eq!(PgVersion::from_version_num(90624), PgVersion::new(9, 6));
+        }
+
+        #[test]
+        fn from_pg_dump_output() {
+            let v = PgVersion::from_pg_dump_output("pg_dump (PostgreSQL) 16.2\n").unwrap();
+            assert_eq!(v, PgVersion::new(16, 2));
+
+            let v = PgVersion::from_pg_dump_output(
+                "pg_dump (PostgreSQL) 17.0 (Debian 17.0-1.pgdg120+1)",
+            )
+            .unwrap();
+            assert_eq!(v, PgVersion::new(17, 0));
+
+            let v = PgVersion::from_pg_dump_output("pg_dump (PostgreSQL) 17beta1").unwrap();
+            assert_eq!(v, PgVersion::new(17, 0));
+
+            assert!(PgVersion::from_pg_dump_output("command not found").is_err());
+        }
+
+        #[test]
+        fn is_supported() {
+            assert!(!PgVersion::new(9, 6).is_supported());
+            assert!(!PgVersion::new(10, 23).is_supported());
+            assert!(PgVersion::new(11, 0).is_supported());
+            assert!(PgVersion::new(17, 0).is_supported());
+        }
+    }
+
+    mod compatibility {
+        use super::*;
+
+        fn compatibility(major: u32) -> Compatibility {
+            Compatibility::new(PgVersion::new(major, 0)).unwrap()
+        }
+
+        fn args(args: &[&str]) -> Vec<String> {
+            args.iter().map(|s| s.to_string()).collect()
+        }
+
+        #[test]
+        fn unsupported_server() {
+            let err = Compatibility::new(PgVersion::new(10, 5)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "PostgreSQL 10.5 is not supported, the minimal supported version is 11"
+            );
+        }
+
+        #[test]
+        fn table_size_query() {
+            assert_eq!(
+                compatibility(12).table_size_query(),
+                TABLE_SIZE_QUERY_PRE_14
+            );
+            assert_eq!(compatibility(14).table_size_query(), TABLE_SIZE_QUERY);
+            assert_eq!(compatibility(17).table_size_query(), TABLE_SIZE_QUERY);
+        }
+
+        #[test]
+        fn all_table_sizes_query() {
+            assert_eq!(
+                compatibility(13).all_table_sizes_query(),
+                ALL_TABLE_SIZES_QUERY_PRE_14
+            );
+            assert_eq!(
+                compatibility(14).all_table_sizes_query(),
+                ALL_TABLE_SIZES_QUERY
+            );
+        }
+
+        #[test]
+        fn older_pg_dump() {
+            let c = compatibility(16);
+            assert!(c.check_pg_dump(PgVersion::new(16, 0), &[]).is_ok());
+            assert!(c.check_pg_dump(PgVersion::new(17, 2), &[]).is_ok());
+            assert!(c.check_pg_dump(PgVersion::new(15, 4), &[]).is_err());
+        }
+
+        #[test]
+        fn versioned_flags() {
+            let c = compatibility(12);
+            let a = args(&["--no-owner", "--no-table-access-method"]);
+            assert!(c.check_pg_dump(PgVersion::new(15, 0), &a).is_ok());
+            assert!(c.check_pg_dump(PgVersion::new(14, 0), &a).is_err());
+
+            let a = args(&["--filter=filter.txt"]);
+            assert!(c.check_pg_dump(PgVersion::new(17, 0), &a).is_ok());
+            assert!(c.check_pg_dump(PgVersion::new(16, 0), &a).is_err());
+        }
+
+        #[test]
+        fn forbidden_flags() {
+            let c = compatibility(16);
+            let v = PgVersion::new(16, 0);
+            assert!(c.check_pg_dump(v, &args(&["-Z", "5"])).is_err());
+            assert!(c.check_pg_dump(v, &args(&["-Z5"])).is_err());
+            assert!(c.check_pg_dump(v, &args(&["--compress=gzip:5"])).is_err());
+            assert!(c.check_pg_dump(v, &args(&["-Fc"])).is_err());
+            assert!(c.check_pg_dump(v, &args(&["--format=custom"])).is_err());
+            assert!(c.check_pg_dump(v, &args(&["--no-acl", "-x"])).is_ok());
+            assert!(c
+                .check_pg_dump(v, &args(&["-Fp", "--format=plain"]))
+                .is_ok());
+        }
+    }
+}
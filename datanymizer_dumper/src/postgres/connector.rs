@@ -1,47 +1,423 @@
-use anyhow::Result;
+use super::{
+    advisory_lock,
+    catalog::CatalogCache,
+    compatibility::{Compatibility, PgVersion},
+    conn_url::{self, TargetSessionAttrs},
+    credentials::Credentials,
+    throttle::Throttle,
+};
+use anyhow::{anyhow, Result};
 use native_tls::TlsConnector;
-use postgres::{Client, NoTls};
+use postgres::{Client, Config, NoTls};
 use postgres_native_tls::MakeTlsConnector;
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap, time::Duration};
 use url::Url;
 
 const SSL_MODE_PARAM: &str = "sslmode";
 const NO_SSL_MODE: &str = "disable";
+const APPLICATION_NAME_PARAM: &str = "application_name";
+const OPTIONS_PARAM: &str = "options";
+
+// The settings set by the client (the startup parameters and `SET`)
+const SESSION_SETTINGS_QUERY: &str =
+    "SELECT name, current_setting(name) FROM pg_catalog.pg_settings
+                                      WHERE source IN ('client', 'session')
+                                      ORDER BY name";
+
+/// The timeouts of the source database sessions, they are set right after connecting
+/// (before the catalog queries and the dump transaction)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionTimeouts {
+    pub statement_timeout: Option<Duration>,
+    pub lock_timeout: Option<Duration>,
+    pub idle_in_transaction_session_timeout: Option<Duration>,
+}
+
+impl SessionTimeouts {
+    /// The `SET` statements of the specified timeouts
+    pub fn statements(&self) -> String {
+        [
+            ("statement_timeout", self.statement_timeout),
+            ("lock_timeout", self.lock_timeout),
+            (
+                "idle_in_transaction_session_timeout",
+                self.idle_in_transaction_session_timeout,
+            ),
+        ]
+        .iter()
+        .filter_map(|(name, timeout)| {
+            timeout.map(|t| format!("SET {} = '{}ms';", name, t.as_millis()))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+/// Parses a duration with a unit like the server settings do: `500ms`, `30s`, `5min`, `1h`, `1d` (or `0`)
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let error = || {
+        anyhow!(
+            "Invalid duration `{}` (use a number with a unit: ms, s, min, h or d, e.g. 30s or 5min)",
+            s
+        )
+    };
+    if s == "0" {
+        return Ok(Duration::ZERO);
+    }
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .ok_or_else(error)?;
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().map_err(|_| error())?;
+    let millis = match unit.trim() {
+        "ms" => 1.0,
+        "s" => 1_000.0,
+        "min" => 60_000.0,
+        "h" => 3_600_000.0,
+        "d" => 86_400_000.0,
+        _ => return Err(error()),
+    };
+    // the server settings are integers of milliseconds
+    let millis = (value * millis).round();
+    if millis > i32::MAX as f64 {
+        return Err(anyhow!("The duration `{}` is too long", s));
+    }
+    Ok(Duration::from_millis(millis as u64))
+}
 
 pub struct Connection {
     pub client: Client,
     pub url: Url,
+    compatibility: Option<Compatibility>,
+    catalog: Option<CatalogCache>,
+    catalog_throttle: Throttle,
+    /// The advisory lock is held (`Some`), the value is whether to wait for it after reconnecting
+    advisory_lock: Option<bool>,
+    connector: Option<Connector>,
+    timeouts: SessionTimeouts,
+    /// The small tables are counted (`count(*)`) instead of estimated
+    precise_size: bool,
+    /// The problems of the schema inspection, the dumper reports them
+    warnings: Vec<String>,
 }
 
 impl Connection {
     pub fn new(client: Client, url: Url) -> Self {
-        Self { client, url }
+        Self {
+            client,
+            url,
+            compatibility: None,
+            catalog: None,
+            catalog_throttle: Throttle::default(),
+            advisory_lock: None,
+            connector: None,
+            timeouts: SessionTimeouts::default(),
+            precise_size: false,
+            warnings: vec![],
+        }
+    }
+
+    /// Counts the rows of the small tables instead of estimating them (see `--precise-size`)
+    pub fn with_precise_size(mut self, precise_size: bool) -> Self {
+        self.precise_size = precise_size;
+        self
+    }
+
+    pub fn precise_size(&self) -> bool {
+        self.precise_size
+    }
+
+    /// Keeps the warning (once) until `take_warnings`
+    pub fn warn(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// The password of the connector (it isn't in the URL)
+    pub fn password(&self) -> Option<&str> {
+        self.connector
+            .as_ref()
+            .and_then(|c| c.credentials.password())
+    }
+
+    /// Replaces the client with a new one (e.g., after the server terminated the connection).
+    /// The TLS settings of the connector are used, the connections made with `new` use the URL only.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let connector = self
+            .connector
+            .clone()
+            .unwrap_or_else(|| Connector::new(self.url.clone(), false, false));
+        self.client = connector.connect()?.client;
+        // the lock is released with the old session
+        if let Some(wait) = self.advisory_lock {
+            advisory_lock::acquire(&mut self.client, wait)?;
+        }
+        Ok(())
+    }
+
+    /// Opens another session to the same server (e.g., for the workers of the parallel dump)
+    pub fn spawn(&self) -> Result<Connection> {
+        self.connector
+            .clone()
+            .unwrap_or_else(|| Connector::new(self.url.clone(), false, false))
+            .connect_to(&self.url)
+    }
+
+    /// Takes the advisory lock of the dumps, so concurrent runs against the same database don't overlap
+    /// (see `advisory_lock::acquire`)
+    pub fn acquire_advisory_lock(&mut self, wait: bool) -> Result<()> {
+        advisory_lock::acquire(&mut self.client, wait)?;
+        self.advisory_lock = Some(wait);
+        Ok(())
+    }
+
+    pub fn release_advisory_lock(&mut self) -> Result<()> {
+        if self.advisory_lock.take().is_some() {
+            advisory_lock::release(&mut self.client)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the timeouts of the connector again (e.g., after the session options for a replica)
+    pub fn apply_timeouts(&mut self) -> Result<()> {
+        let statements = self.timeouts.statements();
+        if !statements.is_empty() {
+            self.client.batch_execute(&statements)?;
+        }
+        Ok(())
+    }
+
+    /// The client for the catalog queries of the schema inspector (they are limited by `--catalog-qps`)
+    pub fn catalog_client(&mut self) -> &mut Client {
+        self.catalog_throttle.wait();
+        &mut self.client
+    }
+
+    /// The throttle of the catalog queries (e.g., to get the time spent waiting)
+    pub fn catalog_throttle(&self) -> &Throttle {
+        &self.catalog_throttle
+    }
+
+    /// Probes the server version (once) and returns the compatibility settings for it.
+    /// Returns an error for unsupported servers.
+    pub fn compatibility(&mut self) -> Result<Compatibility> {
+        if let Some(c) = self.compatibility {
+            return Ok(c);
+        }
+
+        let version_num: String = self
+            .client
+            .query_one("SHOW server_version_num", &[])?
+            .get(0);
+        let compatibility = Compatibility::new(PgVersion::from_version_num(version_num.parse()?))?;
+        self.compatibility = Some(compatibility);
+
+        Ok(compatibility)
+    }
+
+    /// Fetches the catalog data of all tables (once), the per-table inspector methods use it
+    pub fn catalog(&mut self) -> Result<&CatalogCache> {
+        if self.catalog.is_none() {
+            let compatibility = self.compatibility()?;
+            self.catalog = Some(CatalogCache::fetch(
+                &mut self.client,
+                compatibility,
+                &mut self.catalog_throttle,
+            )?);
+        }
+
+        Ok(self.catalog.get_or_insert_with(CatalogCache::default))
+    }
+
+    /// The effective settings set by the client (e.g., `application_name` or `work_mem` from the session settings)
+    pub fn session_settings(&mut self) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .client
+            .query(SESSION_SETTINGS_QUERY, &[])?
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    /// Drops the fetched catalog data (e.g., after the schema changes)
+    pub fn reset_catalog(&mut self) {
+        self.catalog = None;
     }
 }
 
+#[derive(Clone)]
 pub struct Connector {
     url: Url,
+    credentials: Credentials,
     accept_invalid_hostnames: bool,
     accept_invalid_certs: bool,
+    catalog_qps: Option<u32>,
+    timeouts: SessionTimeouts,
+    keepalives_idle: Option<Duration>,
+    precise_size: bool,
 }
 
 impl Connector {
     pub fn new(url: Url, accept_invalid_hostnames: bool, accept_invalid_certs: bool) -> Self {
         Self {
             url,
+            credentials: Credentials::default(),
             accept_invalid_hostnames,
             accept_invalid_certs,
+            catalog_qps: None,
+            timeouts: SessionTimeouts::default(),
+            keepalives_idle: None,
+            precise_size: false,
+        }
+    }
+
+    /// The user and the password of the connections (they override the ones of the URL)
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Limits the catalog queries of the connections (queries per second)
+    pub fn with_catalog_qps(mut self, qps: Option<u32>) -> Self {
+        self.catalog_qps = qps;
+        self
+    }
+
+    /// Sets the timeouts of the sessions right after connecting
+    pub fn with_timeouts(mut self, timeouts: SessionTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sends TCP keepalives after the idle time (instead of 2 hours), so the connections through
+    /// load balancers and NAT are not dropped while the long queries run
+    pub fn with_keepalives_idle(mut self, idle: Option<Duration>) -> Self {
+        self.keepalives_idle = idle;
+        self
+    }
+
+    /// Counts the rows of the small tables instead of estimating them (the progress of the dumps is exact)
+    pub fn with_precise_size(mut self, precise_size: bool) -> Self {
+        self.precise_size = precise_size;
+        self
+    }
+
+    /// The connector with the same TLS settings for another database (the credentials are taken from the URL)
+    pub fn with_url(&self, url: Url) -> Self {
+        Self::new(
+            url,
+            self.accept_invalid_hostnames,
+            self.accept_invalid_certs,
+        )
+    }
+
+    /// Adds the session settings to the URL, so `pg_dump` gets them too. `application_name` and `options`
+    /// are the startup parameters, other settings (e.g., `work_mem`) are added to `options` as `-c name=value`.
+    /// The server refuses the connection if some settings are unknown.
+    pub fn with_session(mut self, session: &BTreeMap<String, String>) -> Result<Self> {
+        if session.is_empty() {
+            return Ok(self);
+        }
+
+        let mut application_name = None;
+        let mut options = vec![];
+        let mut params = vec![];
+        for (key, value) in self.url.query_pairs() {
+            match key.as_ref() {
+                APPLICATION_NAME_PARAM => application_name = Some(value.into_owned()),
+                OPTIONS_PARAM => options.push(value.into_owned()),
+                _ => params.push((key.into_owned(), value.into_owned())),
+            }
+        }
+
+        for (name, value) in session {
+            if !is_setting_name(name) {
+                return Err(anyhow!(
+                    "Invalid session setting name `{}` (letters, digits, `_`, `$` and `.` are allowed)",
+                    name
+                ));
+            }
+            match name.as_str() {
+                APPLICATION_NAME_PARAM => application_name = Some(value.clone()),
+                OPTIONS_PARAM => options.push(value.clone()),
+                _ => options.push(format!("-c {}={}", name, escape_option(value))),
+            }
         }
+
+        params.extend(application_name.map(|v| (APPLICATION_NAME_PARAM.to_string(), v)));
+        if !options.is_empty() {
+            params.push((OPTIONS_PARAM.to_string(), options.join(" ")));
+        }
+        self.url.query_pairs_mut().clear().extend_pairs(params);
+        // libpq and the driver don't decode `+` as a space (a literal `+` is encoded as `%2B`)
+        let query = self.url.query().map(|q| q.replace('+', "%20"));
+        self.url.set_query(query.as_deref());
+
+        Ok(self)
     }
 
+    /// Connects to the first suitable server of the URL. The driver handles a single host and the `any` and
+    /// `read-write` session attributes, otherwise the hosts are tried in order and the attributes are checked here.
+    /// The connection URL is the URL of the chosen server (so `pg_dump` uses the same server).
     pub fn connect(&self) -> Result<Connection> {
-        let url_str = self.url.as_str();
+        let attrs = TargetSessionAttrs::of(&self.url)?;
+        let urls = conn_url::single_host_urls(&self.url)?;
+        if urls.len() <= 1 && attrs.is_supported_by_driver() {
+            return self.connect_to(&self.url);
+        }
+
+        let mut failures = vec![];
+        for url in urls {
+            match self.connect_to(&url) {
+                Ok(mut connection) => match session_matches(&mut connection.client, attrs) {
+                    Ok(true) => return Ok(connection),
+                    Ok(false) => {
+                        failures.push(format!("{} is not {}", conn_url::host_label(&url), attrs))
+                    }
+                    Err(e) => failures.push(format!("{}: {}", conn_url::host_label(&url), e)),
+                },
+                Err(e) => failures.push(format!("{}: {}", conn_url::host_label(&url), e)),
+            }
+        }
+
+        Err(anyhow!(
+            "No server matches target_session_attrs={} ({})",
+            attrs,
+            failures.join("; ")
+        ))
+    }
+
+    fn connect_to(&self, url: &Url) -> Result<Connection> {
+        let mut config: Config = url.as_str().parse()?;
+        if let Some(user) = &self.credentials.user {
+            config.user(user);
+        }
+        if let Some(password) = self.credentials.password() {
+            config.password(password);
+        }
+        if let Some(idle) = self.keepalives_idle {
+            config.keepalives(true).keepalives_idle(idle);
+        }
         let client = match self.tls_connector()? {
-            Some(c) => Client::connect(url_str, c)?,
-            None => Client::connect(url_str, NoTls)?,
+            Some(c) => config.connect(c)?,
+            None => config.connect(NoTls)?,
         };
 
-        Ok(Connection::new(client, self.url.clone()))
+        let mut connection = Connection::new(client, url.clone());
+        connection.connector = Some(self.clone());
+        connection.catalog_throttle = Throttle::new(self.catalog_qps);
+        connection.timeouts = self.timeouts;
+        connection.precise_size = self.precise_size;
+        connection.apply_timeouts()?;
+        connection.compatibility()?;
+
+        Ok(connection)
     }
 
     fn tls_connector(&self) -> Result<Option<MakeTlsConnector>> {
@@ -77,10 +453,139 @@ fn tls_connector(&self) -> Result<Option<MakeTlsConnector>> {
     }
 }
 
+fn session_matches(client: &mut Client, attrs: TargetSessionAttrs) -> Result<bool> {
+    match attrs.check() {
+        Some((query, expected)) => {
+            let value: String = client.query_one(query, &[])?.get(0);
+            Ok(value == expected)
+        }
+        None => Ok(true),
+    }
+}
+
+// The names of the server settings (including the custom ones, like `myapp.tenant`)
+fn is_setting_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.')
+}
+
+// Spaces and backslashes are escaped in `options`
+fn escape_option(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(' ', "\\ ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod with_session {
+        use super::*;
+
+        fn url(db_str: &str, session: &[(&str, &str)]) -> Result<String> {
+            let session = session
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            Connector::new(Url::parse(db_str).unwrap(), false, false)
+                .with_session(&session)
+                .map(|c| c.url.to_string())
+        }
+
+        #[test]
+        fn empty() {
+            let db_str = "postgres://postgres@localhost/dbname?sslmode=require";
+            assert_eq!(url(db_str, &[]).unwrap(), db_str);
+        }
+
+        #[test]
+        fn settings() {
+            let url = url(
+                "postgres://postgres@localhost/dbname?sslmode=require&application_name=psql",
+                &[
+                    ("application_name", "pg_datanymizer/0.5.0"),
+                    ("work_mem", "256MB"),
+                    ("search_path", "app, public"),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                url,
+                "postgres://postgres@localhost/dbname?sslmode=require&application_name=pg_datanymizer%2F0.5.0\
+                &options=-c%20search_path%3Dapp%2C%5C%20public%20-c%20work_mem%3D256MB"
+            );
+        }
+
+        #[test]
+        fn options() {
+            let url = url(
+                "postgres://postgres@localhost/dbname?options=-c%20geqo%3Doff",
+                &[("options", "-c jit=off"), ("work_mem", "64MB")],
+            )
+            .unwrap();
+            assert_eq!(
+                url,
+                "postgres://postgres@localhost/dbname?options=-c%20geqo%3Doff%20-c%20jit%3Doff%20-c%20work_mem%3D64MB"
+            );
+        }
+
+        #[test]
+        fn invalid_name() {
+            let err = url(
+                "postgres://postgres@localhost/dbname",
+                &[("work mem", "64MB")],
+            )
+            .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Invalid session setting name `work mem` (letters, digits, `_`, `$` and `.` are allowed)"
+            );
+            assert!(is_setting_name("myapp.tenant_id"));
+            assert!(!is_setting_name("1work_mem"));
+            assert!(!is_setting_name(""));
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("5min").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        for invalid in ["", "30", "s", "30sec", "-5s", "1.2.3s"] {
+            assert_eq!(
+                parse_duration(invalid).unwrap_err().to_string(),
+                format!(
+                    "Invalid duration `{}` (use a number with a unit: ms, s, min, h or d, e.g. 30s or 5min)",
+                    invalid
+                )
+            );
+        }
+        assert_eq!(
+            parse_duration("30d").unwrap_err().to_string(),
+            "The duration `30d` is too long"
+        );
+    }
+
+    #[test]
+    fn timeout_statements() {
+        assert_eq!(SessionTimeouts::default().statements(), "");
+        let timeouts = SessionTimeouts {
+            statement_timeout: Some(Duration::from_secs(30)),
+            lock_timeout: None,
+            idle_in_transaction_session_timeout: Some(Duration::from_secs(600)),
+        };
+        assert_eq!(
+            timeouts.statements(),
+            "SET statement_timeout = '30000ms';\nSET idle_in_transaction_session_timeout = '600000ms';"
+        );
+    }
+
     mod tls_connector {
         use super::*;
 
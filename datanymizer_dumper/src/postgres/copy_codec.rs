@@ -0,0 +1,958 @@
+//! The PostgreSQL COPY text format: splitting lines into fields, unescaping and escaping the values,
+//! and the array and composite literals inside the fields.
+//! The format is described here: https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.2
+//!
+//! The functions borrow from the input when nothing has to be unescaped or escaped.
+
+use anyhow::{anyhow, Result};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+    str::Utf8Error,
+};
+
+/// The NULL value (the whole field)
+pub const NULL: &[u8] = br"\N";
+pub const DELIMITER: u8 = b'\t';
+
+/// Splits a COPY line (without the trailing newline) into the raw (escaped) field values.
+/// A delimiter after a backslash belongs to the value (the server never writes it, but accepts it).
+pub fn fields(line: &[u8]) -> Fields<'_> {
+    Fields {
+        line,
+        position: Some(0),
+    }
+}
+
+/// The iterator over the raw field values of a COPY line
+pub struct Fields<'a> {
+    line: &'a [u8],
+    /// `None` after the last field
+    position: Option<usize>,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position?;
+        let mut i = start;
+        while i < self.line.len() {
+            match self.line[i] {
+                b'\\' => i += 2,
+                DELIMITER => {
+                    self.position = Some(i + 1);
+                    return Some(&self.line[start..i]);
+                }
+                _ => i += 1,
+            }
+        }
+        self.position = None;
+        Some(&self.line[start..])
+    }
+}
+
+pub fn is_null(field: &[u8]) -> bool {
+    field == NULL
+}
+
+/// Unescapes a raw field value (`None` is NULL). Escapes are decoded as the server does it:
+/// `\b`, `\f`, `\n`, `\r`, `\t`, `\v`, octal (`\7`, `\101`) and hex (`\x41`) bytes,
+/// any other escaped character is taken as it is (e.g., `\\`), a trailing single backslash is dropped.
+pub fn unescape(field: &[u8]) -> Option<Cow<'_, [u8]>> {
+    if is_null(field) {
+        return None;
+    }
+    let first = match field.iter().position(|b| *b == b'\\') {
+        Some(i) => i,
+        None => return Some(Cow::Borrowed(field)),
+    };
+
+    let mut result = Vec::with_capacity(field.len());
+    result.extend_from_slice(&field[..first]);
+    let mut i = first;
+    while i < field.len() {
+        let b = field[i];
+        i += 1;
+        if b != b'\\' {
+            result.push(b);
+            continue;
+        }
+        let c = match field.get(i) {
+            Some(c) => *c,
+            None => break,
+        };
+        i += 1;
+        result.push(match c {
+            b'0'..=b'7' => {
+                let mut value = c - b'0';
+                for _ in 0..2 {
+                    match field.get(i) {
+                        Some(d @ b'0'..=b'7') => {
+                            value = value.wrapping_mul(8).wrapping_add(d - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                value
+            }
+            b'x' => match field.get(i).and_then(|d| hex_digit(*d)) {
+                Some(high) => {
+                    i += 1;
+                    match field.get(i).and_then(|d| hex_digit(*d)) {
+                        Some(low) => {
+                            i += 1;
+                            high * 16 + low
+                        }
+                        None => high,
+                    }
+                }
+                None => b'x',
+            },
+            b'b' => b'\x08',
+            b'f' => b'\x0C',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => b'\x0B',
+            other => other,
+        });
+    }
+    Some(Cow::Owned(result))
+}
+
+/// The same as `unescape`, but the value must be valid UTF-8
+pub fn unescape_str(field: &[u8]) -> Result<Option<Cow<'_, str>>, Utf8Error> {
+    match unescape(field) {
+        None => Ok(None),
+        Some(Cow::Borrowed(bytes)) => std::str::from_utf8(bytes).map(|s| Some(Cow::Borrowed(s))),
+        Some(Cow::Owned(bytes)) => String::from_utf8(bytes)
+            .map(|s| Some(Cow::Owned(s)))
+            .map_err(|e| e.utf8_error()),
+    }
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn escape_sequence(b: u8) -> Option<&'static [u8]> {
+    match b {
+        b'\\' => Some(br"\\"),
+        b'\x08' => Some(br"\b"),
+        b'\x0C' => Some(br"\f"),
+        b'\n' => Some(br"\n"),
+        b'\r' => Some(br"\r"),
+        b'\t' => Some(br"\t"),
+        b'\x0B' => Some(br"\v"),
+        _ => None,
+    }
+}
+
+/// Escapes a value as the server does it in COPY output (use `NULL` for NULL values).
+/// `unescape(&escape(v))` is always `v`.
+pub fn escape(value: &[u8]) -> Cow<'_, [u8]> {
+    if !value.iter().any(|b| escape_sequence(*b).is_some()) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut result = Vec::with_capacity(value.len() + value.len() / 4 + 1);
+    for b in value {
+        match escape_sequence(*b) {
+            Some(sequence) => result.extend_from_slice(sequence),
+            None => result.push(*b),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// The same as `escape` for strings
+pub fn escape_str(value: &str) -> Cow<'_, str> {
+    match escape(value.as_bytes()) {
+        Cow::Borrowed(_) => Cow::Borrowed(value),
+        // only ASCII characters are replaced with ASCII sequences
+        Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).expect("escaped UTF-8")),
+    }
+}
+
+/// The escaper for values from transformers.
+/// Unlike `escape`, it keeps the `\N` value as NULL.
+/// If we need a NULL value in our database, we must return `\N` from the transformer.
+/// Example:
+/// ```yaml
+/// template:
+///    format: '\N'
+/// ```
+/// If you need the `\N` literal in your database, please return `\\N` from the transformer.
+/// If you need the `\\N` literal - return `\\\N` and so on.
+///
+/// Warning! This behavior can be changed in the future.
+pub fn escape_transformed(s: &mut String) {
+    if s == r#"\N"# {
+        return;
+    }
+
+    let len = s.len();
+    let mut new_s = None;
+    let mut beginning = 0;
+    let mut slash_count = 0;
+
+    for (i, c) in s.char_indices() {
+        if let Some(replacement) = match c {
+            '\x08' => Some(r#"\b"#),
+            '\x0C' => Some(r#"\f"#),
+            '\n' => Some(r#"\n"#),
+            '\r' => Some(r#"\r"#),
+            '\t' => Some(r#"\t"#),
+            '\x0B' => Some(r#"\v"#),
+            '\\' => {
+                slash_count += 1;
+                Some(r#"\\"#)
+            }
+            _ => None,
+        } {
+            if new_s.is_none() {
+                new_s = Some(String::with_capacity(len * 2 - i));
+            }
+            if let Some(ref mut new_s) = new_s {
+                if i > beginning {
+                    new_s.push_str(&s[beginning..i])
+                }
+                new_s.push_str(replacement);
+                beginning = i + 1;
+            }
+        }
+    }
+
+    if let Some(mut new_s) = new_s {
+        if slash_count == len - 1 && s.ends_with('N') {
+            if slash_count == 2 {
+                return;
+            } else {
+                new_s.truncate((slash_count - 1) * 2);
+            }
+        }
+
+        if beginning < len {
+            new_s.push_str(&s[beginning..len])
+        }
+        *s = new_s;
+    }
+}
+
+/// Whitespace as the server sees it in array and composite literals
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0B' | '\x0C')
+}
+
+/// An array literal (the unescaped field value), e.g. `{1,NULL,"a b"}` or `[0:1]={{1,2},{3,4}}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayLiteral<'a> {
+    /// The dimension decoration without `=` (e.g., `[0:1]`), the server writes it for lower bounds other than 1
+    pub dimensions: Option<&'a str>,
+    pub items: Vec<ArrayItem<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayItem<'a> {
+    Null,
+    Value(Cow<'a, str>),
+    Array(Vec<ArrayItem<'a>>),
+}
+
+/// Parses an array literal with the `,` delimiter (it is used by all built-in types except `box`).
+/// `to_string()` writes the literal back with `,` (quoting the values as the server does it).
+pub fn parse_array(s: &str) -> Result<ArrayLiteral<'_>> {
+    parse_array_with_delimiter(s, ',')
+}
+
+pub fn parse_array_with_delimiter(s: &str, delimiter: char) -> Result<ArrayLiteral<'_>> {
+    let error = |msg: &str| anyhow!("Invalid array literal `{}`: {}", s, msg);
+
+    let trimmed = s.trim_start_matches(is_space);
+    let (dimensions, body) = if trimmed.starts_with('[') {
+        let eq = trimmed
+            .find('=')
+            .ok_or_else(|| error("no `=` after the dimensions"))?;
+        (
+            Some(trimmed[..eq].trim_end_matches(is_space)),
+            &trimmed[eq + 1..],
+        )
+    } else {
+        (None, trimmed)
+    };
+
+    let mut parser = ArrayParser {
+        s: body,
+        position: 0,
+        delimiter,
+    };
+    parser.skip_spaces();
+    let items = parser.array().map_err(|e| error(&e))?;
+    parser.skip_spaces();
+    if parser.position < body.len() {
+        return Err(error("unexpected characters after the array"));
+    }
+
+    Ok(ArrayLiteral { dimensions, items })
+}
+
+struct ArrayParser<'a> {
+    s: &'a str,
+    position: usize,
+    delimiter: char,
+}
+
+impl<'a> ArrayParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(is_space) {
+            self.bump();
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<ArrayItem<'a>>, String> {
+        if self.bump() != Some('{') {
+            return Err("`{` expected".to_string());
+        }
+        let mut items = vec![];
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(items);
+        }
+
+        loop {
+            self.skip_spaces();
+            items.push(match self.peek() {
+                Some('{') => ArrayItem::Array(self.array()?),
+                Some('"') => ArrayItem::Value(self.quoted()?),
+                Some(_) => self.unquoted()?,
+                None => return Err("unexpected end".to_string()),
+            });
+            self.skip_spaces();
+            match self.bump() {
+                Some('}') => return Ok(items),
+                Some(c) if c == self.delimiter => {}
+                _ => return Err(format!("`{}` or `}}` expected", self.delimiter)),
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<Cow<'a, str>, String> {
+        self.bump();
+        let start = self.position;
+        let mut owned: Option<String> = None;
+        loop {
+            let position = self.position;
+            match self.bump() {
+                Some('"') => {
+                    return Ok(match owned {
+                        Some(s) => Cow::Owned(s),
+                        None => Cow::Borrowed(&self.s[start..position]),
+                    })
+                }
+                Some('\\') => {
+                    let c = self.bump().ok_or("unexpected end")?;
+                    owned
+                        .get_or_insert_with(|| self.s[start..position].to_string())
+                        .push(c);
+                }
+                Some(c) => {
+                    if let Some(s) = &mut owned {
+                        s.push(c);
+                    }
+                }
+                None => return Err("unterminated quoted element".to_string()),
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> Result<ArrayItem<'a>, String> {
+        let start = self.position;
+        let mut owned: Option<String> = None;
+        // the trailing spaces are not a part of the value (but the escaped ones are)
+        let mut end = start;
+        let mut owned_end = 0;
+        loop {
+            let position = self.position;
+            match self.peek() {
+                Some(c) if c == self.delimiter || c == '}' => break,
+                Some('{') | Some('"') => {
+                    return Err("unexpected character in an element".to_string())
+                }
+                Some('\\') => {
+                    self.bump();
+                    let c = self.bump().ok_or("unexpected end")?;
+                    let s = owned.get_or_insert_with(|| self.s[start..position].to_string());
+                    s.push(c);
+                    owned_end = s.len();
+                }
+                Some(c) => {
+                    self.bump();
+                    if !is_space(c) {
+                        end = self.position;
+                    }
+                    if let Some(s) = &mut owned {
+                        s.push(c);
+                        if !is_space(c) {
+                            owned_end = s.len();
+                        }
+                    }
+                }
+                None => return Err("unexpected end".to_string()),
+            }
+        }
+
+        Ok(match owned {
+            Some(mut s) => {
+                s.truncate(owned_end);
+                ArrayItem::Value(Cow::Owned(s))
+            }
+            None => {
+                let value = &self.s[start..end];
+                if value.eq_ignore_ascii_case("NULL") {
+                    ArrayItem::Null
+                } else if value.is_empty() {
+                    return Err("empty element".to_string());
+                } else {
+                    ArrayItem::Value(Cow::Borrowed(value))
+                }
+            }
+        })
+    }
+}
+
+impl Display for ArrayLiteral<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(dimensions) = self.dimensions {
+            write!(f, "{}=", dimensions)?;
+        }
+        write_array_items(f, &self.items)
+    }
+}
+
+fn write_array_items(f: &mut Formatter, items: &[ArrayItem]) -> fmt::Result {
+    f.write_str("{")?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        match item {
+            ArrayItem::Null => f.write_str("NULL")?,
+            ArrayItem::Array(items) => write_array_items(f, items)?,
+            ArrayItem::Value(v) => {
+                let needs_quotes = v.is_empty()
+                    || v.eq_ignore_ascii_case("NULL")
+                    || v.chars()
+                        .any(|c| matches!(c, '"' | '\\' | '{' | '}' | ',') || is_space(c));
+                write_quoted(f, v, needs_quotes, "\\\"")?;
+            }
+        }
+    }
+    f.write_str("}")
+}
+
+/// `escaped_quote` is the replacement of `"` inside the quotes
+fn write_quoted(
+    f: &mut Formatter,
+    v: &str,
+    needs_quotes: bool,
+    escaped_quote: &str,
+) -> fmt::Result {
+    if !needs_quotes {
+        return f.write_str(v);
+    }
+    f.write_str("\"")?;
+    for c in v.chars() {
+        match c {
+            '"' => f.write_str(escaped_quote)?,
+            '\\' => f.write_str("\\\\")?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// A composite (row) literal (the unescaped field value), e.g. `(1,"a b",)` (the last field is NULL)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompositeLiteral<'a> {
+    /// `None` is NULL
+    pub fields: Vec<Option<Cow<'a, str>>>,
+}
+
+/// Parses a composite literal. Unlike arrays, the spaces are a part of the values, and empty unquoted fields are NULLs
+/// (so `()` is a single NULL field, the server writes the rows of types without fields the same way).
+pub fn parse_composite(s: &str) -> Result<CompositeLiteral<'_>> {
+    let error = |msg: &str| anyhow!("Invalid composite literal `{}`: {}", s, msg);
+
+    let body = s.trim_matches(is_space);
+    let body = body
+        .strip_prefix('(')
+        .ok_or_else(|| error("`(` expected"))?
+        .strip_suffix(')')
+        .ok_or_else(|| error("`)` expected"))?;
+
+    let mut fields = vec![];
+    let mut chars = body.char_indices().peekable();
+    let mut start = 0;
+    // `None` while the field can be borrowed
+    let mut owned: Option<String> = None;
+    let mut quoted = false;
+    let mut in_quotes = false;
+
+    loop {
+        match chars.next() {
+            Some((i, ',')) if !in_quotes => {
+                fields.push(composite_field(body, start, i, owned.take(), quoted));
+                start = i + 1;
+                quoted = false;
+            }
+            None if in_quotes => return Err(error("unterminated quoted field")),
+            None => {
+                fields.push(composite_field(body, start, body.len(), owned, quoted));
+                break;
+            }
+            Some((i, '"')) => {
+                if in_quotes && chars.peek().map(|(_, c)| *c) == Some('"') {
+                    chars.next();
+                    owned
+                        .get_or_insert_with(|| body[start..i].to_string())
+                        .push('"');
+                    continue;
+                }
+                // the quotes are not a part of the value
+                owned.get_or_insert_with(|| body[start..i].to_string());
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            Some((i, '\\')) => {
+                let (_, c) = chars.next().ok_or_else(|| error("unexpected end"))?;
+                owned
+                    .get_or_insert_with(|| body[start..i].to_string())
+                    .push(c);
+            }
+            Some((_, ')')) if !in_quotes => return Err(error("unexpected `)`")),
+            Some((_, c)) => {
+                if let Some(s) = &mut owned {
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    Ok(CompositeLiteral { fields })
+}
+
+fn composite_field(
+    body: &str,
+    start: usize,
+    end: usize,
+    owned: Option<String>,
+    quoted: bool,
+) -> Option<Cow<'_, str>> {
+    match owned {
+        Some(s) => Some(Cow::Owned(s)),
+        None if start == end && !quoted => None,
+        None => Some(Cow::Borrowed(&body[start..end])),
+    }
+}
+
+impl Display for CompositeLiteral<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if let Some(v) = field {
+                let needs_quotes = v.is_empty()
+                    || v.chars()
+                        .any(|c| matches!(c, '"' | '\\' | '(' | ')' | ',') || is_space(c));
+                write_quoted(f, v, needs_quotes, "\"\"")?;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// `COPY ... TO STDOUT` of adversarial values (PostgreSQL 15), the table is
+    /// `(t text, a text[], m int[], p adv_pair, b bytea)`, `adv_pair` is `(name text, note text, n int)`
+    const CAPTURED: [&[u8]; 4] = [
+        b"tab\\there\\nnew\\\\back\\rcr\\bbs\\ffvv\x01ctl\t{\"a b\",\"x,y\",\"\\\\\"q\\\\\"\",\"back\\\\\\\\slash\",NULL,\"NULL\",\"\",\"{}\"}\t{{1,2},{3,NULL}}\t(\"a,b\",\"say \"\"hi\"\"\\\\\\\\\",)\t\\\\x00ff5c",
+        b"\\\\N\t{}\t\\N\t(\"\",\" \",1)\t\\\\x",
+        b"\\N\t{\"\\\\\\\\N\"}\t[0:1]={5,6}\t\\N\t\\N",
+        "ünïcødé 😀 \t{\"tab\\there\",\"nl\\nx\"}\t{}\t(\"(x)\",y,-1)\t\\\\x".as_bytes(),
+    ];
+
+    fn str_fields(line: &[u8]) -> Vec<Option<String>> {
+        fields(line)
+            .map(|f| unescape_str(f).unwrap().map(String::from))
+            .collect()
+    }
+
+    fn random_bytes(rng: &mut impl Rng) -> Vec<u8> {
+        // the special bytes are more likely
+        const SPECIAL: &[u8] = b"\\\t\n\r\x08\x0B\x0C\x00Nx\",{}() ";
+        let len = rng.gen_range(0..16);
+        (0..len)
+            .map(|_| {
+                if rng.gen_bool(0.5) {
+                    SPECIAL[rng.gen_range(0..SPECIAL.len())]
+                } else {
+                    rng.gen()
+                }
+            })
+            .collect()
+    }
+
+    fn random_string(rng: &mut impl Rng) -> String {
+        String::from_utf8_lossy(&random_bytes(rng)).into_owned()
+    }
+
+    #[test]
+    fn captured_fields() {
+        assert_eq!(
+            str_fields(CAPTURED[0])[0].as_deref(),
+            Some("tab\there\nnew\\back\rcr\x08bs\x0Cfvv\x01ctl")
+        );
+        assert_eq!(
+            str_fields(CAPTURED[1]),
+            vec![
+                Some(r"\N".to_string()),
+                Some("{}".to_string()),
+                None,
+                Some(r#"(""," ",1)"#.to_string()),
+                Some(r"\x".to_string())
+            ]
+        );
+        assert_eq!(str_fields(CAPTURED[2])[0], None);
+        assert_eq!(str_fields(CAPTURED[3])[0].as_deref(), Some("ünïcødé 😀 "));
+
+        // the server writes the canonical form
+        for line in CAPTURED {
+            for field in fields(line).filter(|f| !is_null(f)) {
+                assert_eq!(escape(&unescape(field).unwrap()), field);
+            }
+        }
+    }
+
+    #[test]
+    fn captured_arrays() {
+        let value = |s: &str| ArrayItem::Value(Cow::Owned(s.to_string()));
+
+        let a = str_fields(CAPTURED[0])[1].clone().unwrap();
+        let array = parse_array(&a).unwrap();
+        assert_eq!(
+            array.items,
+            vec![
+                value("a b"),
+                value("x,y"),
+                value("\"q\""),
+                value("back\\slash"),
+                ArrayItem::Null,
+                value("NULL"),
+                value(""),
+                value("{}"),
+            ]
+        );
+        assert_eq!(array.to_string(), a);
+
+        let m = str_fields(CAPTURED[0])[2].clone().unwrap();
+        let array = parse_array(&m).unwrap();
+        assert_eq!(
+            array.items,
+            vec![
+                ArrayItem::Array(vec![value("1"), value("2")]),
+                ArrayItem::Array(vec![value("3"), ArrayItem::Null]),
+            ]
+        );
+        assert_eq!(array.to_string(), m);
+
+        let m = str_fields(CAPTURED[2])[2].clone().unwrap();
+        let array = parse_array(&m).unwrap();
+        assert_eq!(array.dimensions, Some("[0:1]"));
+        assert_eq!(array.to_string(), m);
+
+        for line in CAPTURED {
+            let a = str_fields(line)[1].clone().unwrap();
+            assert_eq!(parse_array(&a).unwrap().to_string(), a);
+        }
+    }
+
+    #[test]
+    fn captured_composites() {
+        let p = str_fields(CAPTURED[0])[3].clone().unwrap();
+        let composite = parse_composite(&p).unwrap();
+        assert_eq!(
+            composite.fields,
+            vec![
+                Some(Cow::Borrowed("a,b")),
+                Some(Cow::Borrowed("say \"hi\"\\")),
+                None
+            ]
+        );
+        assert_eq!(composite.to_string(), p);
+
+        for line in &CAPTURED[1..] {
+            if let Some(p) = str_fields(line)[3].clone() {
+                assert_eq!(parse_composite(&p).unwrap().to_string(), p);
+            }
+        }
+        assert_eq!(
+            parse_composite("(\"(x)\",y,-1)").unwrap().fields,
+            vec![Some("(x)".into()), Some("y".into()), Some("-1".into())]
+        );
+    }
+
+    #[test]
+    fn zero_copy() {
+        let line = b"plain\tvalue";
+        let values: Vec<_> = fields(line).map(|f| unescape(f).unwrap()).collect();
+        assert!(values.iter().all(|v| matches!(v, Cow::Borrowed(_))));
+        assert!(matches!(escape(b"plain"), Cow::Borrowed(_)));
+
+        let array = parse_array("{a,\"b c\"}").unwrap();
+        assert!(array
+            .items
+            .iter()
+            .all(|i| matches!(i, ArrayItem::Value(Cow::Borrowed(_)))));
+    }
+
+    #[test]
+    fn escaped_delimiter() {
+        let values: Vec<_> = fields(b"a\\\tb\tc\\\\\td").collect();
+        assert_eq!(values, vec![&b"a\\\tb"[..], b"c\\\\", b"d"]);
+        assert_eq!(unescape(values[0]).unwrap(), &b"a\tb"[..]);
+        assert_eq!(fields(b"").collect::<Vec<_>>(), vec![b""]);
+    }
+
+    #[test]
+    fn unescape_sequences() {
+        assert_eq!(
+            unescape(br"\101\x42\7\x7\xg\q").unwrap(),
+            &b"AB\x07\x07xgq"[..]
+        );
+        assert_eq!(unescape(br"\777").unwrap(), &b"\xff"[..]);
+        assert_eq!(unescape(b"a\\").unwrap(), &b"a"[..]);
+        assert_eq!(unescape(br"\\N").unwrap(), &b"\\N"[..]);
+        assert!(unescape_str(br"\xff").is_err());
+    }
+
+    #[test]
+    fn invalid_literals() {
+        for s in [
+            "", "{", "{a", "{a,,b}", "{\"a}", "{a}b", "[0:1]{1}", "{a\"b}",
+        ] {
+            assert!(parse_array(s).is_err(), "{}", s);
+        }
+        for s in ["", "(", "a,b", "(\"a)", "(a)b)"] {
+            assert!(parse_composite(s).is_err(), "{}", s);
+        }
+        assert_eq!(
+            parse_array("{a").unwrap_err().to_string(),
+            "Invalid array literal `{a`: unexpected end"
+        );
+    }
+
+    #[test]
+    fn array_spaces() {
+        let array = parse_array(" { a b , \\ c\\  ,\"d \" } ").unwrap();
+        assert_eq!(
+            array.items,
+            vec![
+                ArrayItem::Value("a b".into()),
+                ArrayItem::Value(" c ".into()),
+                ArrayItem::Value("d ".into()),
+            ]
+        );
+        assert_eq!(array.to_string(), r#"{"a b"," c ","d "}"#);
+        assert_eq!(parse_array("{null}").unwrap().items, vec![ArrayItem::Null]);
+        assert_eq!(
+            parse_array("{\\null}").unwrap().items,
+            vec![ArrayItem::Value("null".into())]
+        );
+    }
+
+    #[test]
+    fn composite_fields() {
+        assert_eq!(parse_composite("()").unwrap().fields, vec![None]);
+        assert_eq!(parse_composite("(,)").unwrap().fields, vec![None, None]);
+        assert_eq!(
+            parse_composite("(\"\", a\\,b ,x\"y\"z)").unwrap().fields,
+            vec![Some("".into()), Some(" a,b ".into()), Some("xyz".into())]
+        );
+    }
+
+    #[test]
+    fn random_round_trips() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let values: Vec<Vec<u8>> = (0..rng.gen_range(1..4))
+                .map(|_| random_bytes(&mut rng))
+                .collect();
+
+            let line = values
+                .iter()
+                .map(|v| escape(v).into_owned())
+                .collect::<Vec<_>>()
+                .join(&DELIMITER);
+            let parsed: Vec<_> = fields(&line).map(|f| unescape(f).unwrap()).collect();
+            assert_eq!(parsed, values, "{:?}", String::from_utf8_lossy(&line));
+
+            // any input is accepted
+            let raw = random_bytes(&mut rng);
+            for field in fields(&raw) {
+                if let Some(value) = unescape(field) {
+                    assert_eq!(unescape(&escape(&value)).unwrap(), value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_literal_round_trips() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let item = |rng: &mut rand::rngs::ThreadRng| {
+                if rng.gen_bool(0.1) {
+                    ArrayItem::Null
+                } else {
+                    ArrayItem::Value(Cow::Owned(random_string(rng)))
+                }
+            };
+            let array = ArrayLiteral {
+                dimensions: None,
+                items: (0..rng.gen_range(0..4)).map(|_| item(&mut rng)).collect(),
+            };
+            let s = array.to_string();
+            assert_eq!(parse_array(&s).unwrap(), array, "{}", s);
+
+            let composite = CompositeLiteral {
+                fields: (0..rng.gen_range(1..4))
+                    .map(|_| {
+                        Some(Cow::Owned(random_string(&mut rng))).filter(|_| rng.gen_bool(0.9))
+                    })
+                    .collect(),
+            };
+            let s = composite.to_string();
+            assert_eq!(parse_composite(&s).unwrap(), composite, "{}", s);
+
+            // any input is accepted or rejected without panics
+            let raw = random_string(&mut rng);
+            if let Ok(array) = parse_array(&raw) {
+                assert_eq!(parse_array(&array.to_string()).unwrap(), array);
+            }
+            if let Ok(composite) = parse_composite(&raw) {
+                assert_eq!(parse_composite(&composite.to_string()).unwrap(), composite);
+            }
+        }
+    }
+
+    mod escape_transformed {
+        use super::*;
+
+        #[test]
+        fn replace() {
+            let mut s = String::from("abc\ndef");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"abc\ndef"#);
+        }
+
+        #[test]
+        fn several() {
+            let mut s = String::from("abc\r\nde\tf");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"abc\r\nde\tf"#);
+        }
+
+        #[test]
+        fn empty() {
+            let mut s = String::from("");
+            escape_transformed(&mut s);
+            assert_eq!(s, "");
+        }
+
+        #[test]
+        fn at_beginning() {
+            let mut s = String::from("\t123");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"\t123"#);
+        }
+
+        #[test]
+        fn at_end() {
+            let mut s = String::from("abc\n");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"abc\n"#);
+        }
+
+        #[test]
+        fn slashes() {
+            let mut s = String::from(r#"\ab\\c\n"#);
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"\\ab\\\\c\\n"#);
+        }
+
+        #[test]
+        fn only_replacements() {
+            let mut s = String::from("\r\n");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"\r\n"#);
+        }
+
+        #[test]
+        fn all_sequences() {
+            let mut s = String::from("\ta\x0Bb\\c\x08\x0C\r\n");
+            escape_transformed(&mut s);
+            assert_eq!(s, r#"\ta\vb\\c\b\f\r\n"#);
+        }
+
+        mod null_like_sequences {
+            use super::*;
+
+            #[test]
+            fn one_slash() {
+                let mut s = String::from(r#"\N"#);
+                escape_transformed(&mut s);
+                assert_eq!(s, r#"\N"#);
+            }
+
+            #[test]
+            fn two_slashes() {
+                let mut s = String::from(r#"\\N"#);
+                escape_transformed(&mut s);
+                assert_eq!(s, r#"\\N"#);
+            }
+
+            #[test]
+            fn five_slashes() {
+                let mut s = String::from(r#"\\\\\N"#);
+                escape_transformed(&mut s);
+                assert_eq!(s, r#"\\\\\\\\N"#);
+            }
+
+            #[test]
+            fn null_sequence_inside_string() {
+                let mut s = String::from(r#"test\Nstring"#);
+                escape_transformed(&mut s);
+                assert_eq!(s, r#"test\\Nstring"#);
+            }
+        }
+    }
+}
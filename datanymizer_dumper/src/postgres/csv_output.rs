@@ -0,0 +1,432 @@
+//! The anonymized table data as CSV (or TSV) files in a directory instead of the COPY blocks of the SQL dump:
+//! a `schema.table.csv` file per table with a header row, `manifest.json` with the tables, row counts
+//! and column types, and `schema.sql` with the schema (the data is loaded before the `POST_DATA_MARKER` part).
+//!
+//! The files are in the COPY CSV format, so they are loaded back with
+//! `COPY table FROM 'file' WITH (FORMAT csv, HEADER, DELIMITER ',', NULL '')`
+//! (or with the scripts of `restore_script`).
+
+use super::{copy_codec, schema_inspector::ForeignKeyLink, table::PgTable};
+use crate::Table;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const SCHEMA_FILE: &str = "schema.sql";
+
+const QUOTE: u8 = b'"';
+
+/// The CSV dialect of the table files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// The NULL value (unquoted), the same non-NULL values are quoted
+    pub null: String,
+}
+
+impl Default for CsvOptions {
+    // The defaults of COPY CSV: NULL is an unquoted empty value, an empty string is `""`
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            null: String::new(),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// The delimiter is a single ASCII character or `tab`, NULL is an empty string or e.g. `\N`
+    pub fn new(delimiter: &str, null: &str) -> Result<Self> {
+        let delimiter = match delimiter {
+            "tab" | "\\t" | "\t" => b'\t',
+            d if d.len() == 1 && d.is_ascii() => d.as_bytes()[0],
+            d => {
+                return Err(anyhow!(
+                    "Invalid CSV delimiter `{}` (a single ASCII character or `tab`)",
+                    d
+                ))
+            }
+        };
+        if matches!(delimiter, QUOTE | b'\n' | b'\r' | b'\\') || delimiter.is_ascii_alphanumeric() {
+            return Err(anyhow!(
+                "Invalid CSV delimiter `{}`",
+                (delimiter as char).escape_default()
+            ));
+        }
+        if null
+            .bytes()
+            .any(|b| b == delimiter || b == QUOTE || b == b'\n' || b == b'\r')
+        {
+            return Err(anyhow!(
+                "The CSV NULL value `{}` can't contain the delimiter, quotes or newlines",
+                null
+            ));
+        }
+
+        Ok(Self {
+            delimiter,
+            null: null.to_string(),
+        })
+    }
+
+    /// `tsv` for the tab delimiter, `csv` otherwise
+    pub fn extension(&self) -> &'static str {
+        if self.delimiter == b'\t' {
+            "tsv"
+        } else {
+            "csv"
+        }
+    }
+
+    /// Converts a COPY text line (without the trailing newline) into a CSV record (with it)
+    pub fn encode_line(&self, line: &[u8], out: &mut Vec<u8>) {
+        for (i, field) in copy_codec::fields(line).enumerate() {
+            if i > 0 {
+                out.push(self.delimiter);
+            }
+            self.encode_field(copy_codec::unescape(field).as_deref(), out);
+        }
+        out.push(b'\n');
+    }
+
+    /// Writes a value (`None` is NULL), the values with delimiters, quotes, newlines are quoted,
+    /// as well as the values that look like NULL or the end-of-data marker
+    pub fn encode_field(&self, value: Option<&[u8]>, out: &mut Vec<u8>) {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                out.extend_from_slice(self.null.as_bytes());
+                return;
+            }
+        };
+
+        let quoted = value == self.null.as_bytes()
+            || value == b"\\."
+            || value
+                .iter()
+                .any(|b| *b == self.delimiter || matches!(*b, QUOTE | b'\n' | b'\r'));
+        if !quoted {
+            out.extend_from_slice(value);
+            return;
+        }
+        out.push(QUOTE);
+        for b in value {
+            if *b == QUOTE {
+                out.push(QUOTE);
+            }
+            out.push(*b);
+        }
+        out.push(QUOTE);
+    }
+}
+
+/// The contents of `manifest.json`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryManifest {
+    pub delimiter: String,
+    pub null: String,
+    /// The tables in the dump order
+    pub tables: Vec<TableFile>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableFile {
+    pub name: String,
+    /// The file name in the directory
+    pub file: String,
+    pub rows: u64,
+    /// The columns in the order of the file fields
+    pub columns: Vec<ColumnFile>,
+    /// The referenced tables (by the foreign keys), without the table itself
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// SHA-256 of the file (hex)
+    #[serde(default)]
+    pub sha256: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnFile {
+    pub name: String,
+    pub data_type: String,
+}
+
+/// Writes the table files. The rows are written as COPY text lines (the same as for the SQL dump)
+/// and converted into CSV records.
+pub struct CsvDirectory {
+    dir: PathBuf,
+    options: CsvOptions,
+    manifest: DirectoryManifest,
+    /// The referenced tables by the referencing ones
+    parents: HashMap<String, Vec<String>>,
+    current: Option<CsvTable>,
+}
+
+struct CsvTable {
+    info: TableFile,
+    path: PathBuf,
+    writer: BufWriter<File>,
+    hasher: Sha256,
+    /// The incomplete line
+    pending: Vec<u8>,
+    record: Vec<u8>,
+}
+
+impl CsvDirectory {
+    /// Creates the directory (if it doesn't exist)
+    pub fn new<P: Into<PathBuf>>(dir: P, options: CsvOptions) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| {
+            anyhow!(
+                "Can't create the output directory `{}`: {}",
+                dir.display(),
+                e
+            )
+        })?;
+        let manifest = DirectoryManifest {
+            delimiter: (options.delimiter as char).to_string(),
+            null: options.null.clone(),
+            tables: vec![],
+        };
+
+        Ok(Self {
+            dir,
+            options,
+            manifest,
+            parents: HashMap::new(),
+            current: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The dependencies of the table files (for the restore scripts)
+    pub(crate) fn set_foreign_keys(&mut self, links: &[ForeignKeyLink]) {
+        self.parents.clear();
+        for link in links.iter().filter(|l| l.table != l.referenced_table) {
+            let parents = self.parents.entry(link.table.clone()).or_default();
+            if !parents.contains(&link.referenced_table) {
+                parents.push(link.referenced_table.clone());
+            }
+        }
+        self.parents.values_mut().for_each(|parents| parents.sort());
+    }
+
+    /// Creates the table file with the header row (the file of the previous attempt is replaced)
+    pub(crate) fn begin_table(&mut self, table: &PgTable) -> Result<()> {
+        let name = table.get_full_name();
+        let file = format!(
+            "{}.{}",
+            name.replace(['/', '\\'], "_"),
+            self.options.extension()
+        );
+        let path = self.dir.join(&file);
+        let mut writer = BufWriter::new(File::create(&path)?);
+
+        let mut header = vec![];
+        for (i, column) in table.columns.iter().enumerate() {
+            if i > 0 {
+                header.push(self.options.delimiter);
+            }
+            self.options
+                .encode_field(Some(column.name.as_bytes()), &mut header);
+        }
+        header.push(b'\n');
+        writer.write_all(&header)?;
+        let mut hasher = Sha256::new();
+        hasher.update(&header);
+
+        self.current = Some(CsvTable {
+            info: TableFile {
+                depends_on: self.parents.get(&name).cloned().unwrap_or_default(),
+                sha256: String::new(),
+                name,
+                file,
+                rows: 0,
+                columns: table
+                    .columns
+                    .iter()
+                    .map(|c| ColumnFile {
+                        name: c.name.clone(),
+                        data_type: c.data_type.clone(),
+                    })
+                    .collect(),
+            },
+            path,
+            writer,
+            hasher,
+            pending: vec![],
+            record: vec![],
+        });
+        Ok(())
+    }
+
+    pub(crate) fn end_table(&mut self, rows: u64) -> Result<()> {
+        if let Some(mut table) = self.current.take() {
+            if !table.pending.is_empty() {
+                let line = std::mem::take(&mut table.pending);
+                table.write_line(&self.options, &line)?;
+            }
+            table.writer.flush()?;
+            table.info.rows = rows;
+            table.info.sha256 = format!("{:x}", table.hasher.finalize());
+            self.manifest.tables.retain(|t| t.name != table.info.name);
+            self.manifest.tables.push(table.info);
+        }
+        Ok(())
+    }
+
+    /// Removes the file of the failed (or skipped) table
+    pub(crate) fn discard_table(&mut self) {
+        if let Some(table) = self.current.take() {
+            drop(table.writer);
+            let _ = fs::remove_file(table.path);
+        }
+    }
+
+    /// Writes `manifest.json`
+    pub(crate) fn finish(&self) -> Result<()> {
+        fs::write(
+            self.dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.manifest)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl CsvTable {
+    fn write_line(&mut self, options: &CsvOptions, line: &[u8]) -> io::Result<()> {
+        self.record.clear();
+        options.encode_line(line, &mut self.record);
+        self.hasher.update(&self.record);
+        self.writer.write_all(&self.record)
+    }
+}
+
+impl Write for CsvDirectory {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let table = self
+            .current
+            .as_mut()
+            .ok_or_else(|| io::Error::other("No table file is started"))?;
+
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|b| *b == b'\n') {
+            if table.pending.is_empty() {
+                table.write_line(&self.options, &rest[..end])?;
+            } else {
+                let mut line = std::mem::take(&mut table.pending);
+                line.extend_from_slice(&rest[..end]);
+                table.write_line(&self.options, &line)?;
+            }
+            rest = &rest[end + 1..];
+        }
+        table.pending.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(table) => table.writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+
+    fn encode(options: &CsvOptions, line: &str) -> String {
+        let mut out = vec![];
+        options.encode_line(line.as_bytes(), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn encode_lines() {
+        let options = CsvOptions::default();
+        assert_eq!(encode(&options, "1\tJohn\t\\N"), "1,John,\n");
+        assert_eq!(encode(&options, "1\t\t"), "1,\"\",\"\"\n");
+        assert_eq!(
+            encode(&options, "2\tSmith, John\tsays \"hi\""),
+            "2,\"Smith, John\",\"says \"\"hi\"\"\"\n"
+        );
+        assert_eq!(
+            encode(&options, "3\tline 1\\nline 2\\r\ta\\tb\\\\c"),
+            "3,\"line 1\nline 2\r\",a\tb\\c\n"
+        );
+        assert_eq!(encode(&options, "\\\\."), "\"\\.\"\n");
+
+        let options = CsvOptions::new("tab", "\\N").unwrap();
+        assert_eq!(options.extension(), "tsv");
+        assert_eq!(encode(&options, "1\t\\N\t"), "1\t\\N\t\n");
+        assert_eq!(
+            encode(&options, "\\\\N\ta\\tb, c"),
+            "\"\\N\"\t\"a\tb, c\"\n"
+        );
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert_eq!(CsvOptions::new(",", "").unwrap(), CsvOptions::default());
+        assert_eq!(CsvOptions::new(";", "NULL").unwrap().delimiter, b';');
+        assert!(CsvOptions::new(",,", "").is_err());
+        assert!(CsvOptions::new("\"", "").is_err());
+        assert!(CsvOptions::new("a", "").is_err());
+        assert!(CsvOptions::new(",", "a,b").is_err());
+    }
+
+    #[test]
+    fn split_lines() {
+        let dir = std::env::temp_dir().join(format!("datanymizer_csv_{}", std::process::id()));
+        let mut directory = CsvDirectory::new(&dir, CsvOptions::default()).unwrap();
+        directory.set_foreign_keys(&[
+            ForeignKeyLink {
+                name: "users_org_fk".to_string(),
+                table: "public.users".to_string(),
+                referenced_table: "public.orgs".to_string(),
+            },
+            ForeignKeyLink {
+                name: "users_parent_fk".to_string(),
+                table: "public.users".to_string(),
+                referenced_table: "public.users".to_string(),
+            },
+        ]);
+        let table = test_tables::pg_table("public", "users", &[("id", "text"), ("name", "text")]);
+        directory.begin_table(&table).unwrap();
+        directory.write_all(b"1\ta").unwrap();
+        directory.write_all(b",b\n2\t").unwrap();
+        directory.write_all(b"\\N\n3\tc").unwrap();
+        directory.end_table(3).unwrap();
+        directory.finish().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("public.users.csv")).unwrap(),
+            "id,name\n1,\"a,b\"\n2,\n3,c\n"
+        );
+        let manifest: DirectoryManifest =
+            serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(manifest.tables[0].name, "public.users");
+        assert_eq!(manifest.tables[0].rows, 3);
+        assert_eq!(manifest.tables[0].depends_on, vec!["public.orgs"]);
+        // sha256sum of the file
+        assert_eq!(
+            manifest.tables[0].sha256,
+            "c3594b608303927a1d2792f967a0fae721b2bf72f09d4cb9dadee8bb501b6bd0"
+        );
+        assert!(directory.write_all(b"4\td\n").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,601 @@
+//! Circular foreign keys between the dumped tables (`cycle_strategy`).
+//!
+//! The tables of a cycle can't be ordered so that every table is restored after the tables it references,
+//! so the restores into a schema with the foreign keys (e.g., the data file restored into a migrated database)
+//! fail. The foreign keys of a cycle to the tables that are dumped later are the backward links:
+//! - `two_pass`: their columns are dumped as NULLs (the NOT NULL constraints are dropped before the table data),
+//!   the values are copied to a temporary staging table after the table data and restored with `UPDATE`
+//!   after all data (then the NOT NULL constraints are restored);
+//! - `defer`: the data is restored in one transaction with the deferred constraints
+//!   (the foreign keys must be deferrable);
+//! - `fail`: the dump fails.
+//!
+//! The self-references are not cycles here.
+
+use super::{copy_codec, replica::RetryBuffer, table::PgTable};
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{CycleStrategy, TempDisk};
+use postgres::Client;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    sync::Arc,
+};
+
+/// Foreign keys between different tables with their columns (in the key order) and the primary keys
+/// of the referencing tables
+const FOREIGN_KEYS_QUERY: &str = "SELECT c.conname::text,
+                                      n.nspname || '.' || t.relname,
+                                      ARRAY(
+                                          SELECT a.attname::text
+                                          FROM unnest(c.conkey) WITH ORDINALITY k(attnum, i)
+                                          JOIN pg_catalog.pg_attribute a
+                                          ON a.attrelid = c.conrelid AND a.attnum = k.attnum
+                                          ORDER BY k.i
+                                      ),
+                                      ARRAY(
+                                          SELECT a.attnotnull
+                                          FROM unnest(c.conkey) WITH ORDINALITY k(attnum, i)
+                                          JOIN pg_catalog.pg_attribute a
+                                          ON a.attrelid = c.conrelid AND a.attnum = k.attnum
+                                          ORDER BY k.i
+                                      ),
+                                      rn.nspname || '.' || rt.relname,
+                                      c.condeferrable,
+                                      ARRAY(
+                                          SELECT a.attname::text
+                                          FROM pg_catalog.pg_index i,
+                                          unnest(i.indkey::int2[]) WITH ORDINALITY k(attnum, o)
+                                          JOIN pg_catalog.pg_attribute a ON a.attnum = k.attnum
+                                          WHERE i.indrelid = c.conrelid AND i.indisprimary
+                                          AND a.attrelid = i.indrelid
+                                          ORDER BY k.o
+                                      )
+                                  FROM pg_catalog.pg_constraint c
+                                  JOIN pg_catalog.pg_class t ON t.oid = c.conrelid
+                                  JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace
+                                  JOIN pg_catalog.pg_class rt ON rt.oid = c.confrelid
+                                  JOIN pg_catalog.pg_namespace rn ON rn.oid = rt.relnamespace
+                                  WHERE c.contype = 'f' AND c.conrelid <> c.confrelid
+                                  ORDER BY 2, 1";
+
+const STAGING_TABLE_PREFIX: &str = "datanymizer_cycle_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub name: String,
+    /// Full name of the referencing table
+    pub table: String,
+    pub columns: Vec<String>,
+    /// The NOT NULL flags of the columns
+    pub not_null: Vec<bool>,
+    /// Full name of the referenced table
+    pub referenced_table: String,
+    pub deferrable: bool,
+    /// The primary key of the referencing table
+    pub key: Vec<String>,
+}
+
+/// The foreign keys of the cycles that reference the tables dumped later (`order` has the full names
+/// of the dumped tables in the dump order)
+pub fn backward_links<'a>(order: &[String], foreign_keys: &'a [ForeignKey]) -> Vec<&'a ForeignKey> {
+    let positions: HashMap<&str, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.as_str(), i))
+        .collect();
+    let dumped: Vec<_> = foreign_keys
+        .iter()
+        .filter(|fk| {
+            positions.contains_key(fk.table.as_str())
+                && positions.contains_key(fk.referenced_table.as_str())
+        })
+        .collect();
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+    for fk in &dumped {
+        edges
+            .entry(fk.table.as_str())
+            .or_default()
+            .push(fk.referenced_table.as_str());
+    }
+    let reaches = |from: &str, to: &str| {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(table) = stack.pop() {
+            if table == to {
+                return true;
+            }
+            if seen.insert(table) {
+                stack.extend(edges.get(table).into_iter().flatten());
+            }
+        }
+        false
+    };
+
+    dumped
+        .into_iter()
+        .filter(|fk| positions[fk.table.as_str()] < positions[fk.referenced_table.as_str()])
+        .filter(|fk| reaches(&fk.referenced_table, &fk.table))
+        .collect()
+}
+
+/// The columns of a table that are restored in the second pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSplit {
+    pub table: String,
+    /// The foreign keys of the columns
+    pub constraints: Vec<String>,
+    pub columns: Vec<String>,
+    /// The columns with the NOT NULL constraints (they are dropped while the data is restored)
+    pub not_null: Vec<String>,
+    pub key: Vec<String>,
+    staging_table: String,
+}
+
+impl TableSplit {
+    /// Drops the NOT NULL constraints of the columns (before the table data)
+    pub fn before_sql(&self) -> Result<String> {
+        let table = PgTable::quote_table_name(&self.table)?;
+        Ok(self
+            .not_null
+            .iter()
+            .map(|c| {
+                format!(
+                    "ALTER TABLE {} ALTER COLUMN {} DROP NOT NULL;\n",
+                    table,
+                    quote_ident(c)
+                )
+            })
+            .collect())
+    }
+
+    /// The staging rows are buffered in a file of `temp_disk`
+    pub fn rows(
+        &self,
+        column_indexes: &HashMap<String, usize>,
+        temp_disk: &Arc<TempDisk>,
+    ) -> Result<SplitRows> {
+        let position = |column: &String| {
+            column_indexes.get(column).copied().ok_or_else(|| {
+                anyhow!(
+                    "Unknown column `{}` of the table `{}` (the circular foreign keys)",
+                    column,
+                    self.table
+                )
+            })
+        };
+        Ok(SplitRows {
+            key: self.key.iter().map(position).collect::<Result<_>>()?,
+            columns: self.columns.iter().map(position).collect::<Result<_>>()?,
+            staging: RetryBuffer::new(
+                temp_disk,
+                &format!("the circular foreign keys of `{}`", self.table),
+                0,
+            )?,
+        })
+    }
+
+    /// Restores the values from the staging table and the NOT NULL constraints (after all data)
+    pub fn after_sql(&self) -> Result<String> {
+        let table = PgTable::quote_table_name(&self.table)?;
+        let set: Vec<_> = self
+            .columns
+            .iter()
+            .map(|c| format!("{} = s.{}", quote_ident(c), quote_ident(c)))
+            .collect();
+        let condition: Vec<_> = self
+            .key
+            .iter()
+            .map(|c| format!("t.{} = s.{}", quote_ident(c), quote_ident(c)))
+            .collect();
+        let mut sql = format!(
+            "UPDATE {} AS t SET {} FROM pg_temp.{} AS s WHERE {};\n",
+            table,
+            set.join(", "),
+            self.staging_table,
+            condition.join(" AND ")
+        );
+        for column in &self.not_null {
+            sql.push_str(&format!(
+                "ALTER TABLE {} ALTER COLUMN {} SET NOT NULL;\n",
+                table,
+                quote_ident(column)
+            ));
+        }
+        sql.push_str(&format!("DROP TABLE pg_temp.{};\n", self.staging_table));
+
+        Ok(sql)
+    }
+
+    fn staging_columns(&self) -> Vec<String> {
+        self.key
+            .iter()
+            .chain(&self.columns)
+            .map(|c| quote_ident(c))
+            .collect()
+    }
+}
+
+/// The rows of a split table: the values of the split columns are replaced with NULLs and saved
+/// to the staging rows (with the primary key)
+pub struct SplitRows {
+    key: Vec<usize>,
+    columns: Vec<usize>,
+    staging: RetryBuffer,
+}
+
+impl SplitRows {
+    pub fn split(&mut self, row: Vec<u8>) -> Result<Vec<u8>> {
+        let mut fields: Vec<_> = copy_codec::fields(&row).collect();
+        // the rows without the values don't need the second pass
+        if self.columns.iter().all(|&i| copy_codec::is_null(fields[i])) {
+            return Ok(row);
+        }
+
+        let staging: Vec<_> = self
+            .key
+            .iter()
+            .chain(&self.columns)
+            .map(|&i| fields[i])
+            .collect();
+        self.staging.write_all(&staging.join(&b'\t'))?;
+        self.staging.write_all(b"\n")?;
+
+        for &i in &self.columns {
+            fields[i] = b"\\N";
+        }
+        Ok(fields.join(&b'\t'))
+    }
+
+    /// Writes the staging table with the saved rows (after the table data)
+    pub fn finish(self, split: &TableSplit, w: &mut dyn Write) -> Result<()> {
+        let table = PgTable::quote_table_name(&split.table)?;
+        let columns = split.staging_columns().join(", ");
+        write!(
+            w,
+            "\nCREATE TEMP TABLE {} AS SELECT {} FROM {} WITH NO DATA;\n\
+            COPY pg_temp.{} ({}) FROM STDIN;\n",
+            split.staging_table, columns, table, split.staging_table, columns
+        )?;
+        self.staging.copy_to(w)?;
+        w.write_all(b"\\.\n")?;
+        Ok(())
+    }
+}
+
+/// How the circular foreign keys of the dumped tables are restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclePlan {
+    strategy: CycleStrategy,
+    /// By the full table names
+    splits: BTreeMap<String, TableSplit>,
+    /// The split tables whose data is dumped (in the dump order)
+    dumped: Vec<String>,
+}
+
+impl CyclePlan {
+    pub fn load(client: &mut Client, strategy: CycleStrategy, order: &[String]) -> Result<Self> {
+        let foreign_keys: Vec<_> = client
+            .query(FOREIGN_KEYS_QUERY, &[])?
+            .into_iter()
+            .map(|row| ForeignKey {
+                name: row.get(0),
+                table: row.get(1),
+                columns: row.get(2),
+                not_null: row.get(3),
+                referenced_table: row.get(4),
+                deferrable: row.get(5),
+                key: row.get(6),
+            })
+            .collect();
+
+        Self::new(strategy, order, &foreign_keys)
+    }
+
+    pub fn new(
+        strategy: CycleStrategy,
+        order: &[String],
+        foreign_keys: &[ForeignKey],
+    ) -> Result<Self> {
+        let links = backward_links(order, foreign_keys);
+        let mut splits: BTreeMap<String, TableSplit> = BTreeMap::new();
+        let describe = |fk: &ForeignKey| {
+            format!(
+                "the foreign key `{}` of `{}` references `{}` that is dumped later and references `{}` too",
+                fk.name, fk.table, fk.referenced_table, fk.table
+            )
+        };
+
+        for fk in links {
+            match strategy {
+                CycleStrategy::Fail => {
+                    return Err(anyhow!(
+                        "Circular foreign keys: {} (set `cycle_strategy` to `two_pass` or `defer`)",
+                        describe(fk)
+                    ));
+                }
+                CycleStrategy::Defer if !fk.deferrable => {
+                    return Err(anyhow!(
+                        "Circular foreign keys: {}, it isn't deferrable (use the `two_pass` cycle strategy)",
+                        describe(fk)
+                    ));
+                }
+                CycleStrategy::Defer => {}
+                CycleStrategy::TwoPass => {
+                    if fk.key.is_empty() {
+                        return Err(anyhow!(
+                            "Circular foreign keys: {}, the table `{}` has no primary key, so the values \
+                            can't be restored in the second pass (use the `defer` cycle strategy)",
+                            describe(fk),
+                            fk.table
+                        ));
+                    }
+                    if let Some(column) = fk.columns.iter().find(|c| fk.key.contains(c)) {
+                        return Err(anyhow!(
+                            "Circular foreign keys: {}, its column `{}` is a part of the primary key, \
+                            so it can't be restored in the second pass (use the `defer` cycle strategy)",
+                            describe(fk),
+                            column
+                        ));
+                    }
+
+                    let count = splits.len();
+                    let split = splits
+                        .entry(fk.table.clone())
+                        .or_insert_with(|| TableSplit {
+                            table: fk.table.clone(),
+                            constraints: vec![],
+                            columns: vec![],
+                            not_null: vec![],
+                            key: fk.key.clone(),
+                            staging_table: format!("{}{}", STAGING_TABLE_PREFIX, count),
+                        });
+                    split.constraints.push(fk.name.clone());
+                    for (column, &not_null) in fk.columns.iter().zip(&fk.not_null) {
+                        if !split.columns.contains(column) {
+                            split.columns.push(column.clone());
+                            if not_null {
+                                split.not_null.push(column.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            strategy,
+            splits,
+            dumped: vec![],
+        })
+    }
+
+    pub fn split(&self, table: &str) -> Option<&TableSplit> {
+        self.splits.get(table)
+    }
+
+    /// The data of the split table is dumped (its values are restored after all data)
+    pub fn add_dumped(&mut self, table: &str) {
+        if self.splits.contains_key(table) && !self.dumped.iter().any(|t| t == table) {
+            self.dumped.push(table.to_string());
+        }
+    }
+
+    /// The tables whose foreign keys are restored in the second pass
+    pub fn split_tables(&self) -> Vec<&str> {
+        self.splits.keys().map(|t| t.as_str()).collect()
+    }
+
+    /// Before the data
+    pub fn begin_sql(&self) -> String {
+        match self.strategy {
+            CycleStrategy::Defer => "\nBEGIN;\nSET CONSTRAINTS ALL DEFERRED;\n".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// After the data
+    pub fn end_sql(&self) -> Result<String> {
+        match self.strategy {
+            CycleStrategy::Defer => Ok("\nCOMMIT;\n".to_string()),
+            _ => {
+                let mut sql = String::new();
+                for table in &self.dumped {
+                    sql.push('\n');
+                    sql.push_str(&self.splits[table].after_sql()?);
+                }
+                Ok(sql)
+            }
+        }
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fk(name: &str, table: &str, column: &str, referenced_table: &str) -> ForeignKey {
+        ForeignKey {
+            name: name.to_string(),
+            table: table.to_string(),
+            columns: vec![column.to_string()],
+            not_null: vec![true],
+            referenced_table: referenced_table.to_string(),
+            deferrable: false,
+            key: vec!["id".to_string()],
+        }
+    }
+
+    fn order(tables: &[&str]) -> Vec<String> {
+        tables.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn foreign_keys() -> Vec<ForeignKey> {
+        vec![
+            fk(
+                "addresses_user_fk",
+                "public.addresses",
+                "user_id",
+                "public.users",
+            ),
+            fk(
+                "users_address_fk",
+                "public.users",
+                "primary_address_id",
+                "public.addresses",
+            ),
+            fk("orders_user_fk", "public.orders", "user_id", "public.users"),
+        ]
+    }
+
+    #[test]
+    fn backward_links() {
+        let fks = foreign_keys();
+        let names = |tables: &[&str]| -> Vec<String> {
+            super::backward_links(&order(tables), &fks)
+                .into_iter()
+                .map(|fk| fk.name.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            names(&["public.addresses", "public.users", "public.orders"]),
+            vec!["addresses_user_fk"]
+        );
+        assert_eq!(
+            names(&["public.users", "public.orders", "public.addresses"]),
+            vec!["users_address_fk"]
+        );
+        // not a cycle (the orders are dumped before the users, but nothing references the orders)
+        assert!(
+            !names(&["public.orders", "public.users", "public.addresses"])
+                .contains(&"orders_user_fk".to_string())
+        );
+        // the cycle isn't dumped
+        assert!(names(&["public.addresses", "public.orders"]).is_empty());
+    }
+
+    #[test]
+    fn strategies() {
+        let fks = foreign_keys();
+        let tables = order(&["public.addresses", "public.users"]);
+
+        let error = CyclePlan::new(CycleStrategy::Fail, &tables, &fks)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Circular foreign keys: the foreign key `addresses_user_fk` of `public.addresses` references \
+            `public.users` that is dumped later and references `public.addresses` too \
+            (set `cycle_strategy` to `two_pass` or `defer`)"
+        );
+        assert!(CyclePlan::new(CycleStrategy::Defer, &tables, &fks)
+            .unwrap_err()
+            .to_string()
+            .ends_with("it isn't deferrable (use the `two_pass` cycle strategy)"));
+
+        let mut deferrable = fks.clone();
+        deferrable[0].deferrable = true;
+        let plan = CyclePlan::new(CycleStrategy::Defer, &tables, &deferrable).unwrap();
+        assert!(plan.split_tables().is_empty());
+        assert_eq!(
+            plan.begin_sql(),
+            "\nBEGIN;\nSET CONSTRAINTS ALL DEFERRED;\n"
+        );
+        assert_eq!(plan.end_sql().unwrap(), "\nCOMMIT;\n");
+
+        let plan = CyclePlan::new(CycleStrategy::TwoPass, &tables, &fks).unwrap();
+        assert_eq!(plan.split_tables(), vec!["public.addresses"]);
+        assert_eq!(plan.begin_sql(), "");
+
+        // without cycles
+        let plan = CyclePlan::new(CycleStrategy::Fail, &order(&["public.users"]), &fks).unwrap();
+        assert!(plan.split_tables().is_empty());
+    }
+
+    #[test]
+    fn two_pass_errors() {
+        let tables = order(&["public.addresses", "public.users"]);
+
+        let mut fks = foreign_keys();
+        fks[0].key = vec![];
+        assert!(CyclePlan::new(CycleStrategy::TwoPass, &tables, &fks)
+            .unwrap_err()
+            .to_string()
+            .contains("the table `public.addresses` has no primary key"));
+
+        let mut fks = foreign_keys();
+        fks[0].key = vec!["user_id".to_string()];
+        assert!(CyclePlan::new(CycleStrategy::TwoPass, &tables, &fks)
+            .unwrap_err()
+            .to_string()
+            .contains("its column `user_id` is a part of the primary key"));
+    }
+
+    #[test]
+    fn two_pass() {
+        let tables = order(&["public.addresses", "public.users"]);
+        let mut fks = foreign_keys();
+        fks[0].columns.push("Tenant".to_string());
+        fks[0].not_null.push(false);
+        let mut plan = CyclePlan::new(CycleStrategy::TwoPass, &tables, &fks).unwrap();
+        let split = plan.split("public.addresses").unwrap().clone();
+
+        assert_eq!(
+            split.before_sql().unwrap(),
+            "ALTER TABLE \"public\".\"addresses\" ALTER COLUMN \"user_id\" DROP NOT NULL;\n"
+        );
+
+        let column_indexes: HashMap<String, usize> =
+            [("id", 0), ("user_id", 1), ("city", 2), ("Tenant", 3)]
+                .iter()
+                .map(|(c, i)| (c.to_string(), *i))
+                .collect();
+        let mut rows = split
+            .rows(&column_indexes, &Arc::new(TempDisk::default()))
+            .unwrap();
+        assert_eq!(
+            rows.split(Vec::from(&b"1\t10\tParis\t5"[..])).unwrap(),
+            b"1\t\\N\tParis\t\\N"
+        );
+        assert_eq!(
+            rows.split(Vec::from(&b"2\t\\N\tRome\t\\N"[..])).unwrap(),
+            b"2\t\\N\tRome\t\\N"
+        );
+        assert_eq!(
+            rows.split(Vec::from(&b"3\t11\tOslo\\twest\t\\N"[..]))
+                .unwrap(),
+            b"3\t\\N\tOslo\\twest\t\\N"
+        );
+        let mut out = vec![];
+        rows.finish(&split, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "
+CREATE TEMP TABLE datanymizer_cycle_0 AS SELECT \"id\", \"user_id\", \"Tenant\" FROM \"public\".\"addresses\" WITH NO DATA;
+COPY pg_temp.datanymizer_cycle_0 (\"id\", \"user_id\", \"Tenant\") FROM STDIN;
+1\t10\t5
+3\t11\t\\N
+\\.
+"
+        );
+
+        // the tables that aren't dumped are not updated
+        assert_eq!(plan.end_sql().unwrap(), "");
+        plan.add_dumped("public.addresses");
+        plan.add_dumped("public.users");
+        assert_eq!(
+            plan.end_sql().unwrap(),
+            "
+UPDATE \"public\".\"addresses\" AS t SET \"user_id\" = s.\"user_id\", \"Tenant\" = s.\"Tenant\" \
+FROM pg_temp.datanymizer_cycle_0 AS s WHERE t.\"id\" = s.\"id\";
+ALTER TABLE \"public\".\"addresses\" ALTER COLUMN \"user_id\" SET NOT NULL;
+DROP TABLE pg_temp.datanymizer_cycle_0;
+"
+        );
+    }
+}
@@ -0,0 +1,803 @@
+use datanymizer_engine::DdlReplacement;
+use regex::Regex;
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+/// DDL clauses with literals that `pg_dump` copies from the source database as is
+const CLAUSES: [(&str, &str); 2] = [("DEFAULT", " DEFAULT "), ("CHECK", "CHECK (")];
+
+/// Patterns of string literals that may contain personal or internal data
+const PII_PATTERNS: [(&str, &str); 5] = [
+    ("email", r"[\w.+-]+@[\w-]+(\.[\w-]+)+"),
+    ("url", r"(?i)\b[a-z][a-z0-9+.-]*://\S+"),
+    ("ip", r"\b\d{1,3}(\.\d{1,3}){3}\b"),
+    (
+        "hostname",
+        r"(?i)\b[a-z0-9-]+(\.[a-z0-9-]+)*\.(internal|local|localdomain|corp|lan|intra)\b",
+    ),
+    ("phone", r"\+?\d[\d ()-]{8,}\d"),
+];
+
+/// A string literal in a column default or a check constraint that looks like personal data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdlFinding {
+    /// `DEFAULT` or `CHECK`
+    pub clause: &'static str,
+    /// The matched pattern (e.g. `email`)
+    pub kind: &'static str,
+    pub literal: String,
+    /// The first line of the statement
+    pub statement: String,
+    pub line: String,
+}
+
+impl Display for DdlFinding {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' in {} ({}): {}",
+            self.kind, self.literal, self.clause, self.statement, self.line
+        )
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DdlReport {
+    /// The count of replaced literals
+    pub replaced: usize,
+    /// Literals that look like personal data (except replaced ones)
+    pub findings: Vec<DdlFinding>,
+}
+
+/// Replaces and detects string literals in `DEFAULT` and `CHECK` clauses of the `pg_dump` output.
+/// It works with literals only (without parsing expressions), one line of a statement at a time
+/// (`pg_dump` writes every column definition and constraint on its own line). The comments
+/// and the dollar-quoted strings (e.g. function bodies) are skipped.
+pub struct DdlScanner {
+    patterns: Vec<(&'static str, Regex)>,
+}
+
+impl Default for DdlScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DdlScanner {
+    pub fn new() -> Self {
+        Self {
+            patterns: PII_PATTERNS
+                .iter()
+                .map(|(kind, pattern)| (*kind, Regex::new(pattern).unwrap()))
+                .collect(),
+        }
+    }
+
+    pub fn process(&self, sql: &str, replacements: &[DdlReplacement]) -> (String, DdlReport) {
+        let mut result = String::with_capacity(sql.len());
+        let mut report = DdlReport::default();
+        let mut copied = 0;
+
+        for range in statements(sql) {
+            // the comments and the psql meta-commands between the statements
+            result.push_str(&sql[copied..range.start]);
+            copied = range.end;
+
+            let text = &sql[range];
+            let quoted = quoted_ranges(text);
+            let statement = text.lines().next().unwrap_or_default().trim();
+            let mut offset = 0;
+            for line in text.split_inclusive('\n') {
+                let line_start = offset;
+                offset += line.len();
+                match clause(line, line_start, &quoted) {
+                    Some((clause, position, end)) => {
+                        let mut new_line = String::with_capacity(line.len());
+                        let mut copied = position;
+                        new_line.push_str(&line[..position]);
+
+                        for (start, end, value) in literals(&line[position..end]) {
+                            let (start, end) = (start + position, end + position);
+                            new_line.push_str(&line[copied..start]);
+                            copied = end;
+
+                            if let Some(r) = replacements.iter().find(|r| r.from == value) {
+                                report.replaced += 1;
+                                new_line.push_str(&quote(&r.to));
+                                continue;
+                            }
+
+                            new_line.push_str(&line[start..end]);
+                            if let Some(kind) = self.detect(&value) {
+                                report.findings.push(DdlFinding {
+                                    clause,
+                                    kind,
+                                    literal: value,
+                                    statement: statement.to_string(),
+                                    line: line.trim().to_string(),
+                                });
+                            }
+                        }
+
+                        new_line.push_str(&line[copied..]);
+                        result.push_str(&new_line);
+                    }
+                    None => result.push_str(line),
+                }
+            }
+        }
+        result.push_str(&sql[copied..]);
+
+        (result, report)
+    }
+
+    fn detect(&self, value: &str) -> Option<&'static str> {
+        self.patterns
+            .iter()
+            .find(|(_, re)| re.is_match(value))
+            .map(|(kind, _)| *kind)
+    }
+}
+
+/// Returns the first clause in the line (outside of the quoted text), its position and the end
+/// of its literals (a comment or a dollar-quoted string ends them)
+fn clause(
+    line: &str,
+    line_start: usize,
+    quoted: &[Quoted],
+) -> Option<(&'static str, usize, usize)> {
+    let kind_at = |i: usize| {
+        quoted
+            .iter()
+            .find(|q| q.range.contains(&(line_start + i)))
+            .map(|q| q.literal)
+    };
+    let (name, position) = CLAUSES
+        .iter()
+        .filter_map(|(name, pattern)| {
+            line.match_indices(pattern)
+                .map(|(i, _)| i)
+                .find(|i| kind_at(*i).is_none())
+                .map(|i| (*name, i))
+        })
+        .min_by_key(|(_, i)| *i)?;
+    let end = (position..line.len())
+        .find(|i| kind_at(*i) == Some(false))
+        .unwrap_or(line.len());
+    Some((name, position, end))
+}
+
+/// A quoted part of the statement: a string literal or a quoted identifier (`literal: true`),
+/// a comment or a dollar-quoted string (`literal: false`, e.g. a function body)
+#[derive(Debug, PartialEq, Eq)]
+struct Quoted {
+    range: Range<usize>,
+    literal: bool,
+}
+
+fn quoted_ranges(statement: &str) -> Vec<Quoted> {
+    let bytes = statement.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let (end, literal) = match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => (line_end(bytes, i), false),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => (block_comment_end(bytes, i), false),
+            b'\'' => {
+                let escapes = i > 0
+                    && matches!(bytes[i - 1], b'E' | b'e')
+                    && !(i > 1 && (bytes[i - 2].is_ascii_alphanumeric() || bytes[i - 2] == b'_'));
+                (quoted_end(bytes, i, b'\'', escapes), true)
+            }
+            b'"' => (quoted_end(bytes, i, b'"', false), true),
+            b'$' => match dollar_tag(&statement[i..]) {
+                Some(tag) => (
+                    statement[i + tag.len()..]
+                        .find(tag)
+                        .map_or(bytes.len(), |end| i + tag.len() + end + tag.len()),
+                    false,
+                ),
+                None => {
+                    i += 1;
+                    continue;
+                }
+            },
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        result.push(Quoted {
+            range: start..end,
+            literal,
+        });
+        i = end;
+    }
+
+    result
+}
+
+/// Returns string literals (start, end, unescaped value) in the text
+fn literals(s: &str) -> Vec<(usize, usize, String)> {
+    let bytes = s.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\'' {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut value = String::new();
+        let mut segment = i + 1;
+        i += 1;
+        loop {
+            if i >= bytes.len() {
+                // the literal continues on the next line
+                return result;
+            }
+            if bytes[i] == b'\'' {
+                value.push_str(&s[segment..i]);
+                if bytes.get(i + 1) == Some(&b'\'') {
+                    value.push('\'');
+                    i += 2;
+                    segment = i;
+                    continue;
+                }
+                i += 1;
+                break;
+            }
+            i += 1;
+        }
+
+        result.push((start, i, value));
+    }
+
+    result
+}
+
+/// SQL keywords that `pg_get_indexdef` uses in expressions and predicates
+const KEYWORDS: [&str; 18] = [
+    "and", "any", "array", "between", "case", "else", "end", "false", "ilike", "in", "is", "like",
+    "not", "null", "or", "then", "true", "when",
+];
+
+/// Returns identifiers (unquoted ones are folded to lower case) in the SQL expression,
+/// skipping string literals and keywords
+pub fn identifiers(expr: &str) -> Vec<String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut result = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == c {
+                    if chars.get(i + 1) == Some(&c) {
+                        value.push(c);
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+            if c == '"' {
+                result.push(value);
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            let ident = chars[start..i].iter().collect::<String>().to_lowercase();
+            if !KEYWORDS.contains(&ident.as_str()) {
+                result.push(ident);
+            }
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    result
+}
+
+/// Adds `DROP ... IF EXISTS` before every `CREATE TABLE` and `CREATE INDEX` statement of the `pg_dump` output,
+/// so it can be restored over an existing database. Returns the SQL and the count of added statements.
+/// Statements are split with quotes, dollar quotes and comments in mind, so the statements in function bodies
+/// are not changed.
+pub fn add_drops_if_exists(sql: &str) -> (String, usize) {
+    let mut result = String::with_capacity(sql.len());
+    let mut count = 0;
+    let mut copied = 0;
+
+    for start in statement_starts(sql) {
+        if let Some(drop) = drop_statement(&sql[start..]) {
+            result.push_str(&sql[copied..start]);
+            result.push_str(&drop);
+            result.push('\n');
+            copied = start;
+            count += 1;
+        }
+    }
+    result.push_str(&sql[copied..]);
+
+    (result, count)
+}
+
+/// Returns the positions of the SQL statements (psql meta-commands are skipped)
+fn statement_starts(sql: &str) -> Vec<usize> {
+    statements(sql).into_iter().map(|s| s.start).collect()
+}
+
+/// Returns the ranges of the SQL statements (with the semicolons), psql meta-commands are skipped
+pub(super) fn statements(sql: &str) -> Vec<Range<usize>> {
+    let bytes = sql.as_bytes();
+    let mut result = vec![];
+    let mut start = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'-' && bytes.get(i + 1) == Some(&b'-') {
+            i = line_end(bytes, i);
+            continue;
+        }
+        if c == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            i = block_comment_end(bytes, i);
+            continue;
+        }
+        if start.is_none() {
+            if c.is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+            if c == b'\\' {
+                i = line_end(bytes, i);
+                continue;
+            }
+            start = Some(i);
+        }
+
+        i = match c {
+            b';' => {
+                if let Some(start) = start.take() {
+                    result.push(start..i + 1);
+                }
+                i + 1
+            }
+            b'\'' => {
+                let escapes = i > 0
+                    && matches!(bytes[i - 1], b'E' | b'e')
+                    && !(i > 1 && (bytes[i - 2].is_ascii_alphanumeric() || bytes[i - 2] == b'_'));
+                quoted_end(bytes, i, b'\'', escapes)
+            }
+            b'"' => quoted_end(bytes, i, b'"', false),
+            b'$' => match dollar_tag(&sql[i..]) {
+                Some(tag) => sql[i + tag.len()..]
+                    .find(tag)
+                    .map_or(bytes.len(), |end| i + tag.len() + end + tag.len()),
+                None => i + 1,
+            },
+            _ => i + 1,
+        };
+    }
+    if let Some(start) = start {
+        result.push(start..bytes.len());
+    }
+
+    result
+}
+
+pub(super) fn line_end(bytes: &[u8], from: usize) -> usize {
+    bytes[from..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |p| from + p + 1)
+}
+
+// Block comments can be nested
+pub(super) fn block_comment_end(bytes: &[u8], from: usize) -> usize {
+    let mut depth = 0;
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
+
+// Doubled quotes are the same as two quoted strings (or identifiers) in a row
+pub(super) fn quoted_end(bytes: &[u8], from: usize, quote: u8, escapes: bool) -> usize {
+    let mut i = from + 1;
+    while i < bytes.len() {
+        if escapes && bytes[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if bytes[i] == quote {
+            return i + 1;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Returns the tag of the dollar-quoted string (`$$` or `$tag$`)
+pub(super) fn dollar_tag(s: &str) -> Option<&str> {
+    let end = s[1..].find('$')? + 2;
+    let tag = &s[1..end - 1];
+    let valid = !tag.starts_with(|c: char| c.is_ascii_digit())
+        && tag.chars().all(|c| c.is_alphanumeric() || c == '_');
+    valid.then(|| &s[..end])
+}
+
+/// Returns `DROP ... IF EXISTS` for `CREATE TABLE` and `CREATE INDEX` statements
+fn drop_statement(statement: &str) -> Option<String> {
+    let tokens = head_tokens(statement, 8);
+    let keyword = |i: usize| match tokens.get(i) {
+        Some(parts) if parts.len() == 1 && !parts[0].starts_with('"') => {
+            parts[0].to_ascii_uppercase()
+        }
+        _ => String::new(),
+    };
+    let name = |i: usize| {
+        tokens
+            .get(i)
+            .filter(|_| keyword(i) != "IF")
+            .map(|parts| parts.join("."))
+    };
+
+    if keyword(0) != "CREATE" {
+        return None;
+    }
+    let mut i = 1;
+    let foreign = keyword(i) == "FOREIGN";
+    if foreign || keyword(i) == "UNLOGGED" {
+        i += 1;
+    }
+    if keyword(i) == "TABLE" {
+        return Some(format!(
+            "DROP {}TABLE IF EXISTS {} CASCADE;",
+            if foreign { "FOREIGN " } else { "" },
+            name(i + 1)?
+        ));
+    }
+
+    let mut i = 1;
+    if keyword(i) == "UNIQUE" {
+        i += 1;
+    }
+    if keyword(i) != "INDEX" || keyword(i + 2) != "ON" {
+        return None;
+    }
+    let index = name(i + 1)?;
+    let mut table = i + 3;
+    if keyword(table) == "ONLY" {
+        table += 1;
+    }
+    // the index is in the schema of its table
+    let schema = match tokens.get(table)?.split_last() {
+        Some((_, schema)) if !schema.is_empty() => format!("{}.", schema.join(".")),
+        _ => String::new(),
+    };
+    Some(format!("DROP INDEX IF EXISTS {}{};", schema, index))
+}
+
+/// The start of the `-- Name:` comment of the statement (`pg_dump` writes it before the first statement
+/// of every object), or the start of the statement
+pub(super) fn header_start(sql: &str, from: usize, statement: usize) -> usize {
+    let gap = &sql[from..statement];
+    let name = match gap.rfind("-- Name: ") {
+        Some(name) if name == 0 || gap.as_bytes()[name - 1] == b'\n' => name,
+        _ => return statement,
+    };
+    match gap[..name].strip_suffix("--\n") {
+        Some(before) if before.is_empty() || before.ends_with('\n') => from + before.len(),
+        _ => from + name,
+    }
+}
+
+/// Returns the first names (keywords and qualified names as raw, possibly quoted, parts) of the statement
+pub(super) fn head_tokens(statement: &str, max: usize) -> Vec<Vec<&str>> {
+    let mut result = vec![];
+    let mut rest = statement;
+    while result.len() < max {
+        match qualified_name(rest) {
+            Some((parts, tail)) => {
+                result.push(parts);
+                rest = tail;
+            }
+            None => break,
+        }
+    }
+    result
+}
+
+fn qualified_name(s: &str) -> Option<(Vec<&str>, &str)> {
+    let s = s.trim_start();
+    let bytes = s.as_bytes();
+    let mut parts = vec![];
+    let mut i = 0;
+    loop {
+        let start = i;
+        if bytes.get(i) == Some(&b'"') {
+            i = quoted_end(bytes, i, b'"', false);
+            while bytes.get(i) == Some(&b'"') {
+                i = quoted_end(bytes, i, b'"', false);
+            }
+        } else {
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'_'
+                    || bytes[i] == b'$'
+                    || !bytes[i].is_ascii())
+            {
+                i += 1;
+            }
+        }
+        if i == start {
+            return None;
+        }
+        parts.push(&s[start..i]);
+        if bytes.get(i) != Some(&b'.') {
+            return Some((parts, &s[i..]));
+        }
+        i += 1;
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQL: &str = "--
+-- Name: users; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    name text DEFAULT 'nobody'::text,
+    contact text DEFAULT 'ops@realcompany.com'::text,
+    note text DEFAULT 'it''s ops@realcompany.com'::text,
+    code text,
+    CONSTRAINT users_code_check CHECK ((code = ANY (ARRAY['ACME'::text, 'GLOBEX'::text])))
+);
+
+COMMENT ON TABLE public.users IS 'ops@realcompany.com';
+
+ALTER TABLE ONLY public.users ALTER COLUMN host SET DEFAULT 'db1.prod.internal'::text;
+";
+
+    fn replacement(from: &str, to: &str) -> DdlReplacement {
+        DdlReplacement {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            super::literals("x = 'a' OR x = 'b''c' OR 'd"),
+            vec![(4, 7, "a".to_string()), (15, 21, "b'c".to_string())]
+        );
+    }
+
+    #[test]
+    fn findings() {
+        let (sql, report) = DdlScanner::new().process(SQL, &[]);
+        assert_eq!(sql, SQL);
+        assert_eq!(report.replaced, 0);
+
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.clause, f.kind, f.literal.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                ("DEFAULT", "email", "ops@realcompany.com"),
+                ("DEFAULT", "email", "it's ops@realcompany.com"),
+                ("DEFAULT", "hostname", "db1.prod.internal"),
+            ]
+        );
+        assert_eq!(
+            report.findings[0].to_string(),
+            "email 'ops@realcompany.com' in DEFAULT (CREATE TABLE public.users (): \
+             contact text DEFAULT 'ops@realcompany.com'::text,"
+        );
+        assert_eq!(
+            report.findings[2].statement,
+            "ALTER TABLE ONLY public.users ALTER COLUMN host SET DEFAULT 'db1.prod.internal'::text;"
+        );
+    }
+
+    #[test]
+    fn replacements() {
+        let replacements = vec![
+            replacement("ops@realcompany.com", "ops@example.com"),
+            replacement("it's ops@realcompany.com", "it's nobody"),
+            replacement("ACME", "CODE1"),
+            replacement("nobody", "somebody"),
+        ];
+        let (sql, report) = DdlScanner::new().process(SQL, &replacements);
+
+        assert_eq!(report.replaced, 4);
+        assert!(sql.contains("    name text DEFAULT 'somebody'::text,\n"));
+        assert!(sql.contains("    contact text DEFAULT 'ops@example.com'::text,\n"));
+        assert!(sql.contains("    note text DEFAULT 'it''s nobody'::text,\n"));
+        assert!(sql.contains("CHECK ((code = ANY (ARRAY['CODE1'::text, 'GLOBEX'::text])))\n"));
+        // not in a default or a check constraint
+        assert!(sql.contains("IS 'ops@realcompany.com';\n"));
+
+        let findings: Vec<_> = report.findings.iter().map(|f| f.kind).collect();
+        assert_eq!(findings, vec!["hostname"]);
+    }
+
+    #[test]
+    fn function_bodies_and_comments() {
+        let sql = r#"CREATE FUNCTION public.notify() RETURNS text
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+    recipient text DEFAULT 'ops@realcompany.com';
+    ok boolean DEFAULT true; -- CHECK ('db1.prod.internal')
+BEGIN
+    RETURN recipient;
+END;
+$$;
+
+/* contact text DEFAULT 'ops@realcompany.com' */
+CREATE TABLE public.contacts (
+    -- note text DEFAULT 'old@realcompany.com',
+    "odd DEFAULT 'name'" text,
+    email text DEFAULT 'it@realcompany.com'::text -- DEFAULT 'x@realcompany.com'
+);
+"#;
+        let replacements = vec![replacement("ops@realcompany.com", "ops@example.com")];
+        let (result, report) = DdlScanner::new().process(sql, &replacements);
+        assert_eq!(result, sql);
+        assert_eq!(report.replaced, 0);
+
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.literal.as_str(), f.statement.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![("it@realcompany.com", "CREATE TABLE public.contacts (")]
+        );
+    }
+
+    #[test]
+    fn add_drops_if_exists() {
+        let sql = r#"--
+-- Name: users; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    note text DEFAULT 'CREATE TABLE x; -- ;'::text
+);
+
+CREATE UNLOGGED TABLE "My Schema"."Log;s" (id integer);
+CREATE FOREIGN TABLE public.remote (id integer) SERVER remote;
+
+CREATE FUNCTION public.f() RETURNS void
+    LANGUAGE plpgsql
+    AS $_$
+BEGIN
+    CREATE TABLE tmp (id integer);
+    CREATE INDEX tmp_idx ON tmp (id);
+END;
+$_$;
+
+/* CREATE TABLE in a comment; */
+\restrict key
+CREATE INDEX users_name_idx ON public.users USING btree (name);
+CREATE UNIQUE INDEX "Idx" ON ONLY "My Schema"."Log;s" USING btree (id);
+CREATE INDEX bare_idx ON users USING btree (id);
+ALTER TABLE ONLY public.users ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+"#;
+        let (result, count) = super::add_drops_if_exists(sql);
+        assert_eq!(count, 6);
+
+        let drops: Vec<_> = result
+            .lines()
+            .filter(|line| line.starts_with("DROP"))
+            .collect();
+        assert_eq!(
+            drops,
+            vec![
+                "DROP TABLE IF EXISTS public.users CASCADE;",
+                r#"DROP TABLE IF EXISTS "My Schema"."Log;s" CASCADE;"#,
+                "DROP FOREIGN TABLE IF EXISTS public.remote CASCADE;",
+                "DROP INDEX IF EXISTS public.users_name_idx;",
+                r#"DROP INDEX IF EXISTS "My Schema"."Idx";"#,
+                "DROP INDEX IF EXISTS bare_idx;",
+            ]
+        );
+        assert!(result
+            .contains("DROP INDEX IF EXISTS public.users_name_idx;\nCREATE INDEX users_name_idx"));
+        assert_eq!(
+            result
+                .lines()
+                .filter(|line| !line.starts_with("DROP"))
+                .collect::<Vec<_>>(),
+            sql.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn statement_starts() {
+        let sql = "SELECT E'it\\'s;'; SELECT $1 + $$;$$;\n-- x;\nSELECT 1";
+        let starts: Vec<_> = super::statement_starts(sql)
+            .into_iter()
+            .map(|i| &sql[i..i + 8])
+            .collect();
+        assert_eq!(starts, vec!["SELECT E", "SELECT $", "SELECT 1"]);
+    }
+
+    #[test]
+    fn statements() {
+        let sql = "SELECT ';'; -- x;\nSELECT /* ; */ 1;\n\\connect db\nSELECT 2";
+        let statements: Vec<_> = super::statements(sql)
+            .into_iter()
+            .map(|s| &sql[s])
+            .collect();
+        assert_eq!(
+            statements,
+            vec!["SELECT ';';", "SELECT /* ; */ 1;", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(
+            super::identifiers("lower((email)::text)"),
+            vec!["lower", "email", "text"]
+        );
+        assert_eq!(
+            super::identifiers("((\"Email\")::text ~~ '%@ourco.com'::text) AND (id > 10)"),
+            vec!["Email", "text", "text", "id"]
+        );
+        assert_eq!(
+            super::identifiers("(name = 'it''s email'::text) AND x1 > 1e5"),
+            vec!["name", "text", "x1"]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! The checks of `--dry-run`: the tables and columns of the config are checked against the database schema
+//! (nothing is dumped)
+
+use super::{
+    connector::Connection, dump_args, dumper::unsupported_rules,
+    schema_inspector::PgSchemaInspector, table::PgTable, table_resolution::TableResolution,
+};
+use crate::{SchemaInspector, Table};
+use anyhow::Result;
+use datanymizer_engine::{Settings, Table as TableCfg};
+use regex::Regex;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct DryRunReport {
+    /// The config tables that match no database tables (or match the tables of several schemas)
+    pub unknown_tables: Vec<UnknownTable>,
+    /// The config columns that the matched tables don't have
+    pub unknown_columns: Vec<TableColumn>,
+    /// The rules that don't support the column types (they are skipped in the dump)
+    pub incompatible_rules: Vec<IncompatibleRule>,
+    /// The dumped columns that look like personal data (by `pii_columns`) and have neither rules
+    /// nor `ignore` reasons
+    pub unruled_pii_columns: Vec<TableColumn>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct UnknownTable {
+    pub name: String,
+    pub problem: String,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TableColumn {
+    pub table: String,
+    pub column: String,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct IncompatibleRule {
+    pub table: String,
+    pub column: String,
+    pub problem: String,
+}
+
+impl DryRunReport {
+    /// Checks the config against the tables of the inspector (the tables of `filter` only)
+    pub fn check(
+        connection: &mut Connection,
+        inspector: &PgSchemaInspector,
+        settings: &Settings,
+    ) -> Result<Self> {
+        let mut tables = inspector.get_tables(connection)?;
+        tables.sort_by_key(|t| t.get_full_name());
+        let patterns = settings.pii_column_patterns().map_err(anyhow::Error::msg)?;
+
+        let mut report = Self {
+            unknown_tables: TableResolution::resolve_all(settings, &tables)
+                .into_iter()
+                .filter_map(|r| {
+                    Some(UnknownTable {
+                        problem: r.problem?,
+                        name: r.name,
+                    })
+                })
+                .collect(),
+            ..Self::default()
+        };
+        for table in &tables {
+            let full_name = table.get_full_name();
+            if !crate::filter_table(&full_name, &settings.filter) {
+                continue;
+            }
+            let cfg = settings.find_table(&table.get_names());
+            if let Some(cfg) = cfg {
+                report.check_columns(table, cfg);
+            }
+            if !skips_data(settings, cfg, table) {
+                report.check_pii_columns(table, cfg, &patterns);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Whether the config refers to the tables or columns that don't exist
+    pub fn has_errors(&self) -> bool {
+        !self.unknown_tables.is_empty() || !self.unknown_columns.is_empty()
+    }
+
+    fn check_columns(&mut self, table: &PgTable, cfg: &TableCfg) {
+        let columns = table.get_columns_names();
+        for column in cfg.referenced_columns() {
+            if !columns.iter().any(|c| c == column) {
+                self.unknown_columns.push(TableColumn {
+                    table: table.get_full_name(),
+                    column: column.to_string(),
+                });
+            }
+        }
+        for (column, problem) in unsupported_rules(table, cfg) {
+            self.incompatible_rules.push(IncompatibleRule {
+                table: table.get_full_name(),
+                column,
+                problem,
+            });
+        }
+    }
+
+    fn check_pii_columns(&mut self, table: &PgTable, cfg: Option<&TableCfg>, patterns: &[Regex]) {
+        for column in table.get_columns_names() {
+            let covered = cfg.is_some_and(|cfg| {
+                cfg.rules.contains_key(&column) || cfg.ignore.contains_key(&column)
+            });
+            if !covered && patterns.iter().any(|p| p.is_match(&column)) {
+                self.unruled_pii_columns.push(TableColumn {
+                    table: table.get_full_name(),
+                    column,
+                });
+            }
+        }
+    }
+}
+
+// The tables without data have nothing to anonymize (the table config overrides the global `skip_data`)
+fn skips_data(settings: &Settings, cfg: Option<&TableCfg>, table: &PgTable) -> bool {
+    match cfg.and_then(|cfg| cfg.skip_data.as_ref()) {
+        Some(_) => true,
+        None => settings
+            .skip_data
+            .tables
+            .iter()
+            .any(|p| dump_args::matches(p, table)),
+    }
+}
+
+impl Display for DryRunReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.unknown_tables.is_empty()
+            && self.unknown_columns.is_empty()
+            && self.incompatible_rules.is_empty()
+            && self.unruled_pii_columns.is_empty()
+        {
+            return writeln!(f, "The config matches the database schema");
+        }
+
+        if !self.unknown_tables.is_empty() {
+            writeln!(f, "Unknown tables:")?;
+            for table in &self.unknown_tables {
+                writeln!(f, "  {}", table.problem)?;
+            }
+        }
+        let sections = [
+            ("Unknown columns:", &self.unknown_columns),
+            (
+                "Columns that look like personal data without rules:",
+                &self.unruled_pii_columns,
+            ),
+        ];
+        for (title, columns) in sections {
+            if !columns.is_empty() {
+                writeln!(f, "{}", title)?;
+                for c in columns {
+                    writeln!(f, "  {}.{}", c.table, c.column)?;
+                }
+            }
+        }
+        if !self.incompatible_rules.is_empty() {
+            writeln!(f, "Rules of incompatible column types:")?;
+            for rule in &self.incompatible_rules {
+                writeln!(f, "  {}", rule.problem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(table: &str, column: &str) -> TableColumn {
+        TableColumn {
+            table: table.to_string(),
+            column: column.to_string(),
+        }
+    }
+
+    #[test]
+    fn report_text() {
+        let report = DryRunReport::default();
+        assert!(!report.has_errors());
+        assert_eq!(
+            report.to_string(),
+            "The config matches the database schema\n"
+        );
+
+        let report = DryRunReport {
+            unknown_columns: vec![column("public.users", "mail")],
+            unruled_pii_columns: vec![column("public.users", "phone")],
+            ..DryRunReport::default()
+        };
+        assert!(report.has_errors());
+        assert_eq!(
+            report.to_string(),
+            "Unknown columns:\n  public.users.mail\n\
+            Columns that look like personal data without rules:\n  public.users.phone\n"
+        );
+
+        let report = DryRunReport {
+            unruled_pii_columns: vec![column("public.users", "phone")],
+            ..DryRunReport::default()
+        };
+        assert!(!report.has_errors());
+    }
+}
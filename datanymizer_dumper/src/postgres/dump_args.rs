@@ -0,0 +1,211 @@
+use super::table::PgTable;
+use crate::Table;
+use datanymizer_engine::Settings;
+
+/// Short and old names of the flags (they are the same for `pg_dump`)
+const FLAG_ALIASES: [(&str, &str); 3] = [
+    ("-O", "--no-owner"),
+    ("-x", "--no-privileges"),
+    ("--no-acl", "--no-privileges"),
+];
+
+/// Table selection flags from the `pg_dump` arguments (they are passed to the `pg_dump` calls as is,
+/// so the data dumping must follow them too)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PgDumpTableArgs {
+    /// `-t`, `--table`
+    pub include: Vec<String>,
+    /// `-T`, `--exclude-table`
+    pub exclude: Vec<String>,
+    /// `--exclude-table-data`
+    pub exclude_data: Vec<String>,
+}
+
+impl PgDumpTableArgs {
+    pub fn parse<T: AsRef<str>>(args: &[T]) -> Self {
+        let mut result = Self::default();
+        let mut args = args.iter().map(|a| a.as_ref());
+
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ if arg.len() > 2 && (arg.starts_with("-t") || arg.starts_with("-T")) => {
+                    (&arg[..2], Some(arg[2..].to_string()))
+                }
+                _ => (arg, None),
+            };
+            let list = match flag {
+                "-t" | "--table" => &mut result.include,
+                "-T" | "--exclude-table" => &mut result.exclude,
+                "--exclude-table-data" => &mut result.exclude_data,
+                _ => continue,
+            };
+            if let Some(value) = value.or_else(|| args.next().map(|a| a.to_string())) {
+                list.push(value);
+            }
+        }
+
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.exclude_data.is_empty()
+    }
+
+    /// Returns the argument that excludes the table data (if any)
+    pub fn excluded_by(&self, table: &PgTable) -> Option<String> {
+        if !self.include.is_empty() && !self.include.iter().any(|p| matches(p, table)) {
+            return Some(format!("no matching --table ({})", self.include.join(", ")));
+        }
+        if let Some(p) = self.exclude.iter().find(|p| matches(p, table)) {
+            return Some(format!("--exclude-table={}", p));
+        }
+        self.exclude_data
+            .iter()
+            .find(|p| matches(p, table))
+            .map(|p| format!("--exclude-table-data={}", p))
+    }
+}
+
+/// Adds the `pg_dump` flags of the target profile to the arguments (the flags that are there already are skipped).
+/// The publications are dumped if some of them are kept with `publication_map`.
+pub fn with_profile_flags(settings: &Settings, mut args: Vec<String>) -> Vec<String> {
+    let canonical = |arg: &str| {
+        FLAG_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == arg)
+            .map_or(arg.to_string(), |(_, flag)| flag.to_string())
+    };
+    let present: Vec<_> = args.iter().map(|a| canonical(a)).collect();
+    let keeps_publications = !settings.publication_map.is_empty();
+    for flag in settings.target_profile.pg_dump_flags() {
+        if keeps_publications && *flag == "--no-publications" {
+            continue;
+        }
+        if !present.iter().any(|a| a == flag) {
+            args.push(flag.to_string());
+        }
+    }
+    args
+}
+
+/// Matches `pg_dump` table patterns (`*` and `?` wildcards, with or without schema)
+pub(crate) fn matches(pattern: &str, table: &PgTable) -> bool {
+    let pattern = unquote(pattern);
+    let name = if pattern.contains('.') {
+        table.get_full_name()
+    } else {
+        table.get_name()
+    };
+    wildcard_match(pattern.as_bytes(), name.as_bytes())
+}
+
+// Unquoted names are folded to lower case (as `pg_dump` does)
+fn unquote(pattern: &str) -> String {
+    if pattern.contains('"') {
+        pattern.replace('"', "")
+    } else {
+        pattern.to_lowercase()
+    }
+}
+
+pub(crate) fn wildcard_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| wildcard_match(rest, &s[i..])),
+        Some((b'?', rest)) => !s.is_empty() && wildcard_match(rest, &s[1..]),
+        Some((c, rest)) => s.first() == Some(c) && wildcard_match(rest, &s[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(schema: &str, name: &str) -> PgTable {
+        PgTable::new(name.to_string(), schema.to_string())
+    }
+
+    #[test]
+    fn parse() {
+        let args = PgDumpTableArgs::parse(&[
+            "--no-owner",
+            "--exclude-table-data=logs",
+            "--exclude-table-data",
+            "events_*",
+            "-t",
+            "public.users",
+            "-Tpublic.tmp",
+            "--table=orders",
+            "--exclude-table",
+            "audit",
+        ]);
+
+        assert_eq!(
+            args,
+            PgDumpTableArgs {
+                include: vec!["public.users".to_string(), "orders".to_string()],
+                exclude: vec!["public.tmp".to_string(), "audit".to_string()],
+                exclude_data: vec!["logs".to_string(), "events_*".to_string()],
+            }
+        );
+        assert!(PgDumpTableArgs::parse(&["--no-owner", "-x"]).is_empty());
+    }
+
+    #[test]
+    fn excluded_by() {
+        let args = PgDumpTableArgs::parse(&["--exclude-table-data=events_*", "-T", "other.logs"]);
+
+        assert_eq!(args.excluded_by(&table("public", "users")), None);
+        assert_eq!(
+            args.excluded_by(&table("public", "events_2021")),
+            Some("--exclude-table-data=events_*".to_string())
+        );
+        assert_eq!(
+            args.excluded_by(&table("other", "logs")),
+            Some("--exclude-table=other.logs".to_string())
+        );
+        assert_eq!(args.excluded_by(&table("public", "logs")), None);
+    }
+
+    #[test]
+    fn profile_flags() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        let settings =
+            |config: &str| Settings::from_yaml(&format!("tables: []\n{}", config)).unwrap();
+
+        assert_eq!(
+            with_profile_flags(&settings("target_profile: exact"), args(&["-O"])),
+            args(&["-O"])
+        );
+        assert_eq!(
+            with_profile_flags(&settings("target_profile: dev"), args(&["-O", "--no-acl"])),
+            args(&["-O", "--no-acl", "--no-publications", "--no-subscriptions"])
+        );
+        assert_eq!(
+            with_profile_flags(&settings("target_profile: staging"), vec![]),
+            args(&["--no-publications", "--no-subscriptions"])
+        );
+        // the publications from the map are kept
+        assert_eq!(
+            with_profile_flags(
+                &settings("target_profile: dev\npublication_map: {app: app}"),
+                vec![]
+            ),
+            args(&["--no-owner", "--no-privileges", "--no-subscriptions"])
+        );
+    }
+
+    #[test]
+    fn include() {
+        let args = PgDumpTableArgs::parse(&["-t", "public.user?", "-t", "\"Orders\""]);
+
+        assert_eq!(args.excluded_by(&table("public", "users")), None);
+        assert_eq!(args.excluded_by(&table("other", "Orders")), None);
+        assert_eq!(
+            args.excluded_by(&table("public", "orders")),
+            Some("no matching --table (public.user?, \"Orders\")".to_string())
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use std::{collections::VecDeque, io::BufRead};
+
+/// Lines of a log message in the dump: an empty line, `---`, `--- <message>`, `---`
+const LOG_LINES: usize = 4;
+const LOG_SEPARATOR: &[u8] = b"---\n";
+const LOG_PREFIX: &str = "--- ";
+
+/// A part of the existing dump
+#[derive(Debug, PartialEq, Eq)]
+pub enum Chunk {
+    /// A line with the trailing `\n` (if any)
+    Line(Vec<u8>),
+    /// A log message written by the dumper (e.g., `Dump table: public.users`)
+    Log(String),
+}
+
+/// Reads the existing dump line by line and recognizes the dumper log messages
+/// (the data blocks of tables start with them)
+pub struct DumpReader<R: BufRead> {
+    reader: R,
+    lines: VecDeque<Vec<u8>>,
+}
+
+impl<R: BufRead> DumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            lines: VecDeque::with_capacity(LOG_LINES),
+        }
+    }
+
+    pub fn next_chunk(&mut self) -> Result<Option<Chunk>> {
+        while self.lines.len() < LOG_LINES {
+            let mut line = vec![];
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            self.lines.push_back(line);
+        }
+
+        if let Some(message) = self.log_message() {
+            self.lines.clear();
+            return Ok(Some(Chunk::Log(message)));
+        }
+        Ok(self.lines.pop_front().map(Chunk::Line))
+    }
+
+    fn log_message(&self) -> Option<String> {
+        if self.lines.len() < LOG_LINES
+            || self.lines[0] != b"\n"
+            || self.lines[1] != LOG_SEPARATOR
+            || self.lines[3] != LOG_SEPARATOR
+        {
+            return None;
+        }
+
+        let line = std::str::from_utf8(&self.lines[2]).ok()?;
+        line.strip_prefix(LOG_PREFIX)
+            .and_then(|m| m.strip_suffix('\n'))
+            .map(|m| m.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(dump: &str) -> Vec<Chunk> {
+        let mut reader = DumpReader::new(dump.as_bytes());
+        let mut result = vec![];
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            result.push(chunk);
+        }
+        result
+    }
+
+    fn line(s: &str) -> Chunk {
+        Chunk::Line(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn logs_and_lines() {
+        let dump = "CREATE TABLE t();\n\n---\n--- Dump table: public.t\n---\n\nCOPY t FROM STDIN;\n---\n\\.\n\n---\n--- End dumping data\n---\n--";
+
+        assert_eq!(
+            chunks(dump),
+            vec![
+                line("CREATE TABLE t();\n"),
+                Chunk::Log("Dump table: public.t".to_string()),
+                line("\n"),
+                line("COPY t FROM STDIN;\n"),
+                line("---\n"),
+                line("\\.\n"),
+                Chunk::Log("End dumping data".to_string()),
+                line("--"),
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_log() {
+        assert_eq!(
+            chunks("\n---\n--- Dump table: t\n"),
+            vec![line("\n"), line("---\n"), line("--- Dump table: t\n")]
+        );
+    }
+}
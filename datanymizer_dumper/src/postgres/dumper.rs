@@ -1,208 +1,2623 @@
 use super::{
-    connector, query_wrapper::QueryWrapper, row::PgRow, schema_inspector::PgSchemaInspector,
+    array_columns::ArrayColumns,
+    child_process::ChildGuard,
+    compatibility, conn_url, connector, copy_codec, credentials,
+    csv_output::CsvDirectory,
+    cycles::CyclePlan,
+    ddl::{self, DdlReport, DdlScanner},
+    dump_args::{self, PgDumpTableArgs},
+    dump_reader::{Chunk, DumpReader},
+    existing_values, hooks,
+    int_range::{self, IntColumns},
+    learn_format,
+    materialized_views::{self, ViewName},
+    missing_objects,
+    object_names::{ObjectNameRewriter, ObjectNamesReport},
+    only_columns,
+    outbox::OutboxRows,
+    parallel::{self, Pool, Segment},
+    query_wrapper::QueryWrapper,
+    replica::{self, ReplicaSettings, RetryBuffer},
+    replication::ReplicationRewriter,
+    row::PgRow,
+    safety,
+    schema_inspector::{ForeignKeyLink, PgSchemaInspector, SchemaFilter},
+    sequence::{MaxValues, PgSequence},
+    shuffle::Shuffler,
+    subset::Subsetter,
     table::PgTable,
+    table_order::{self, TableOrder},
+    table_resolution::TableResolution,
+    tenant::{self, Scoping},
+};
+use crate::{
+    coverage::Coverage,
+    indicator::{DumpSummary, Indicator, TableInfo},
+    manifest::{self, Manifest, SafetyOverride, TableManifestBuilder},
+    projection::{StatsProjection, TableEstimate},
+    secret_scan::{self, SecretScan},
+    staged::{Backend, Epilogue, SchemaSection, StagedDumper, TableData, TableOutput, TableRun},
+    SchemaInspector, Table,
+};
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{
+    memory, CycleStrategy, Engine, Filter, MaterializedViews, Partitions, SecretDetection,
+    SequenceAction, Settings, ShuffleMethod, Table as TableCfg, TableList, TempDiskError,
+    TenantScope, Transformers, TypeClass,
 };
-use crate::{indicator::Indicator, Dumper, SchemaInspector, Table};
-use anyhow::Result;
-use datanymizer_engine::{Engine, Filter, Settings, TableList};
 use postgres::IsolationLevel;
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     io::{self, prelude::*},
-    process::{self, Command},
+    process::Command,
+    sync::{Arc, Mutex},
     time::Instant,
 };
 
-pub struct PgDumper<W: Write + Send, I: Indicator + Send> {
+const SLOWEST_RULES_COUNT: usize = 10;
+
+/// The minimum class size for the k-anonymity report (if `--min-k` is not specified)
+const DEFAULT_K: u64 = 5;
+
+/// How many times a table is dumped again after conflicts with recovery (with `prefer_replica_safe`)
+const MAX_CONFLICT_RETRIES: u32 = 3;
+
+const TIMESCALEDB_EXTENSION: &str = "timescaledb";
+
+/// The accounted memory is checked against `--max-memory` every this many rows of a table
+const MEMORY_CHECK_ROWS: u64 = 1000;
+/// The memory of the components is logged every this many rows of a table
+const MEMORY_LOG_ROWS: u64 = 1_000_000;
+
+/// The `pg_dump` sections with the schema (their checksums are saved to the manifest)
+const SCHEMA_SECTIONS: [&str; 2] = ["pre-data", "post-data"];
+
+const DUMP_TABLE_LOG: &str = "Dump table: ";
+
+/// Separates the schema file into the parts that must be applied before and after loading data
+/// (when the data is dumped to a separate file)
+pub const POST_DATA_MARKER: &str = "-- datanymizer: post-data";
+
+/// Session settings for the separate data file (the same ones that `pg_dump` emits)
+const DATA_PREAMBLE: &str = "SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET idle_in_transaction_session_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+SET xmloption = content;
+SET client_min_messages = warning;
+SET row_security = off;
+";
+
+/// Dumps PostgreSQL databases with the stages of `StagedDumper`
+pub type PgDumper<W, I> = StagedDumper<PgBackend<W>, I>;
+
+/// The PostgreSQL backend: the schema is dumped with `pg_dump`, the data is dumped with `COPY`
+pub struct PgBackend<W: Write + Send> {
     schema_inspector: PgSchemaInspector,
-    engine: Engine,
+    engine: Arc<Engine>,
     dump_writer: W,
-    indicator: I,
+    data_writer: Option<W>,
+    /// The table data is written to CSV files instead of the COPY blocks (the SQL data parts are dropped)
+    csv_directory: Option<CsvDirectory>,
+    sql_sink: io::Sink,
+    sync_sequences: bool,
+    fk_order: bool,
+    fail_on_index_semantics_change: bool,
+    deny_lint_warnings: bool,
+    include_extension_tables: bool,
+    lenient: bool,
+    prefer_replica_safe: bool,
+    min_k: Option<u64>,
+    /// The reason for dumping a database that looks like production
+    safety_override: Option<String>,
+    manifest: Option<Arc<Mutex<Manifest>>>,
     dump_isolation_level: Option<IsolationLevel>,
     pg_dump_location: String,
     pg_dump_args: Vec<String>,
+    pg_dump_table_args: PgDumpTableArgs,
+    /// The settings of the source database if it is a replica
+    replica: Option<ReplicaSettings>,
+    /// The table data is written here (instead of the data writer) when the table can be dumped again
+    retry_buffer: Option<RetryBuffer>,
+    conflict_retries: u32,
+    /// Columns whose rules don't support the column types (by the full table names)
+    skipped_rules: HashMap<String, HashSet<String>>,
+    /// The planner estimates of the rows that match the dump conditions (by the full table names)
+    filtered_rows: HashMap<String, u64>,
+    /// The tables are sampled for the projection of the full dump (`--stats-only`)
+    projection: Option<Arc<StatsProjection>>,
+    /// The tables whose data is dumped (the publications from `publication_map` get them only)
+    dumped_tables: BTreeSet<String>,
+    /// The tables dumped before the checkpoint of the resumed dump
+    resumed_tables: HashSet<String>,
+    /// How the circular foreign keys are restored (with `cycle_strategy`)
+    cycle_plan: Option<CyclePlan>,
+    /// The materialized views whose refreshes are removed from the `pg_dump` output
+    stripped_views: BTreeSet<ViewName>,
 }
 
-impl<W: 'static + Write + Send, I: 'static + Indicator + Send> PgDumper<W, I> {
-    pub fn new(
-        engine: Engine,
-        dump_isolation_level: Option<IsolationLevel>,
-        pg_dump_location: String,
-        dump_writer: W,
-        indicator: I,
-        pg_dump_args: Vec<String>,
-    ) -> Result<Self> {
-        Ok(Self {
-            engine,
-            dump_writer,
-            indicator,
-            dump_isolation_level,
-            pg_dump_location,
-            schema_inspector: PgSchemaInspector {},
-            pg_dump_args,
+impl<W: 'static + Write + Send, I: 'static + Indicator + Send> StagedDumper<PgBackend<W>, I> {
+    pub fn new(
+        engine: Engine,
+        dump_isolation_level: Option<IsolationLevel>,
+        pg_dump_location: String,
+        dump_writer: W,
+        indicator: I,
+        pg_dump_args: Vec<String>,
+    ) -> Result<Self> {
+        let pg_dump_args = dump_args::with_profile_flags(&engine.settings, pg_dump_args);
+        let backend = PgBackend {
+            engine: Arc::new(engine),
+            dump_writer,
+            data_writer: None,
+            csv_directory: None,
+            sql_sink: io::sink(),
+            sync_sequences: true,
+            fk_order: true,
+            fail_on_index_semantics_change: false,
+            deny_lint_warnings: false,
+            include_extension_tables: false,
+            lenient: false,
+            prefer_replica_safe: false,
+            min_k: None,
+            safety_override: None,
+            manifest: None,
+            dump_isolation_level,
+            pg_dump_location,
+            schema_inspector: PgSchemaInspector::default(),
+            pg_dump_table_args: PgDumpTableArgs::parse(&pg_dump_args),
+            pg_dump_args,
+            replica: None,
+            retry_buffer: None,
+            conflict_retries: 0,
+            skipped_rules: HashMap::new(),
+            filtered_rows: HashMap::new(),
+            projection: None,
+            dumped_tables: BTreeSet::new(),
+            resumed_tables: HashSet::new(),
+            cycle_plan: None,
+            stripped_views: BTreeSet::new(),
+        };
+        Ok(Self::from_backend(backend, indicator))
+    }
+
+    /// Writes data (COPY blocks) to a separate writer. The main writer gets the schema only:
+    /// pre-data, then `POST_DATA_MARKER`, then post-data.
+    pub fn with_data_writer(mut self, data_writer: W) -> Self {
+        self.backend_mut().data_writer = Some(data_writer);
+        self
+    }
+
+    /// Dumps only the tables of the schemas (the foreign keys to the excluded schemas are ignored),
+    /// the filter is passed to `pg_dump` too
+    pub fn with_schema_filter(mut self, filter: SchemaFilter) -> Self {
+        let backend = self.backend_mut();
+        backend.pg_dump_args.extend(filter.pg_dump_args());
+        backend.schema_inspector = PgSchemaInspector::new(filter);
+        self
+    }
+
+    /// Writes the table data to CSV files in the directory (with `manifest.json`). The main writer gets
+    /// the schema only: pre-data, then `POST_DATA_MARKER`, then post-data.
+    pub fn with_csv_directory(mut self, directory: CsvDirectory) -> Self {
+        self.backend_mut().csv_directory = Some(directory);
+        self
+    }
+
+    /// Sets sequence values from the source database (`true` by default).
+    /// The `sequences` config section takes precedence.
+    pub fn sync_sequences(mut self, sync_sequences: bool) -> Self {
+        self.backend_mut().sync_sequences = sync_sequences;
+        self
+    }
+
+    /// Dumps the referenced tables before the tables that reference them (`true` by default).
+    /// Without it, the tables are dumped in the catalog order (the config order hints still apply).
+    pub fn fk_order(mut self, fk_order: bool) -> Self {
+        self.backend_mut().fk_order = fk_order;
+        self
+    }
+
+    /// Fails (instead of warning) if transformed columns are used in index expressions or predicates
+    pub fn fail_on_index_semantics_change(mut self, fail: bool) -> Self {
+        self.backend_mut().fail_on_index_semantics_change = fail;
+        self
+    }
+
+    /// Fails (instead of warning) if the config has lint warnings (e.g. the `email` rule for `first_name`)
+    pub fn deny_lint_warnings(mut self, deny: bool) -> Self {
+        self.backend_mut().deny_lint_warnings = deny;
+        self
+    }
+
+    /// Dumps the data of the tables that belong to extensions (it is skipped by default,
+    /// the `extensions` config section takes precedence)
+    pub fn include_extension_tables(mut self, include: bool) -> Self {
+        self.backend_mut().include_extension_tables = include;
+        self
+    }
+
+    /// Warns (instead of failing) about the config tables that match no tables
+    /// or match tables in several schemas
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.backend_mut().lenient = lenient;
+        self
+    }
+
+    /// Dumps every table in its own transaction when the source database is a replica, the tables are dumped
+    /// again after conflicts with recovery (instead of a single snapshot for all tables)
+    pub fn prefer_replica_safe(mut self, prefer: bool) -> Self {
+        self.backend_mut().prefer_replica_safe = prefer;
+        self
+    }
+
+    /// Fails if the k-anonymity check finds equivalence classes with less than `min_k` rows
+    /// (the check itself is enabled in the engine)
+    pub fn min_k(mut self, min_k: Option<u64>) -> Self {
+        self.backend_mut().min_k = min_k;
+        self
+    }
+
+    /// Confirms the dump of a source database that matches the `safety` rules, the reason is saved to the manifest
+    pub fn safety_override(mut self, reason: Option<String>) -> Self {
+        self.backend_mut().safety_override = reason;
+        self
+    }
+
+    /// Dumps at most the sample rows of every table and records the estimates of the full dump
+    /// to the projection (it gets the samples as the indicator)
+    pub fn with_projection(mut self, projection: Arc<StatsProjection>) -> Self {
+        self.backend_mut().projection = Some(projection);
+        self
+    }
+
+    /// Collects row counts, rules and checksums of the dumped values to the manifest
+    pub fn with_manifest(mut self, manifest: Arc<Mutex<Manifest>>) -> Self {
+        self.backend_mut().manifest = Some(manifest);
+        self
+    }
+
+    /// Re-dumps the data of the `tables` in the existing dump (`existing` is the dump file or the data file).
+    /// The result is written to the dump writer: the data blocks of the `tables` are replaced,
+    /// everything else is copied as is. The manifest of the existing dump is required, the schema
+    /// must not be changed since that dump (it is checked with the schema checksums from the manifest).
+    pub fn patch<R: BufRead>(
+        &mut self,
+        connection: &mut connector::Connection,
+        existing: R,
+        tables: &[String],
+    ) -> Result<()> {
+        let (backend, events) = self.parts();
+        if backend.engine.settings.subset.is_some() {
+            return Err(anyhow!(
+                "The tables of a dump with the `subset` config section can't be patched"
+            ));
+        }
+        if backend.engine.settings.tenant.is_some() {
+            return Err(anyhow!(
+                "The tables of a dump with the `tenant` config section can't be patched"
+            ));
+        }
+        backend.check_safety(connection, events)?;
+        backend.check_schema(connection, events)?;
+        backend.check_tables(connection, events)?;
+
+        let all_tables = backend.schema_inspector().ordered_tables(connection);
+        report_inspection_warnings(connection, events);
+        let mut patched = vec![];
+        for name in tables {
+            match all_tables
+                .iter()
+                .find(|(t, _)| t.get_names().contains(name))
+            {
+                Some((table, _)) => patched.push(table.clone()),
+                None => return Err(anyhow!("Unknown table `{}` to patch", name)),
+            }
+        }
+
+        let infos: Vec<_> = patched.iter().map(|t| backend.table_info(t)).collect();
+        events.dump_started(&infos);
+
+        let started = Instant::now();
+        let mut summary = DumpSummary::default();
+        let result = self.patch_data(connection, existing, &patched, &mut summary);
+        self.finish_dump(summary, started.elapsed(), result)
+    }
+
+    fn patch_data<R: BufRead>(
+        &mut self,
+        connection: &mut connector::Connection,
+        existing: R,
+        patched: &[PgTable],
+        summary: &mut DumpSummary,
+    ) -> Result<()> {
+        let mut reader = DumpReader::new(existing);
+        let isolation_level = self.backend_mut().dump_isolation_level;
+        let mut session = PgSession::shared(QueryWrapper::with_isolation_level(
+            &mut connection.client,
+            isolation_level,
+        )?);
+        let mut found = HashSet::new();
+        let mut skip = false;
+        while let Some(chunk) = reader.next_chunk()? {
+            match chunk {
+                Chunk::Log(message) => {
+                    let table = message
+                        .strip_prefix(DUMP_TABLE_LOG)
+                        .and_then(|name| patched.iter().find(|t| t.get_full_name() == name));
+                    skip = table.is_some();
+                    match table {
+                        Some(table) => {
+                            found.insert(table.get_full_name());
+                            summary.add_table(&self.dump_table(&mut session, table)?);
+                        }
+                        None => self.backend_mut().write_log(message)?,
+                    }
+                }
+                Chunk::Line(line) if !skip => self.backend_mut().data_writer().write_all(&line)?,
+                Chunk::Line(_) => continue,
+            }
+        }
+
+        let missing: Vec<_> = patched
+            .iter()
+            .map(|t| t.get_full_name())
+            .filter(|name| !found.contains(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "The existing dump has no data of the tables: {}",
+                missing.join(", ")
+            ));
+        }
+
+        let (backend, events) = self.parts();
+        backend.report_anonymity(events)
+    }
+}
+
+impl<W: 'static + Write + Send> PgBackend<W> {
+    fn data_writer(&mut self) -> &mut dyn Write {
+        if self.csv_directory.is_some() {
+            return &mut self.sql_sink;
+        }
+        if let Some(buffer) = &mut self.retry_buffer {
+            return buffer;
+        }
+        match &mut self.data_writer {
+            Some(w) => w,
+            None => &mut self.dump_writer,
+        }
+    }
+
+    // The hooks go to the data part (the schema file with the CSV output: the data is loaded between its parts)
+    fn write_hooks(&mut self, section: &str, statements: &[String]) -> Result<()> {
+        let sql = hooks::render(section, statements);
+        if sql.is_empty() {
+            return Ok(());
+        }
+        let writer: &mut dyn Write = if self.csv_directory.is_some() {
+            &mut self.dump_writer
+        } else {
+            self.data_writer()
+        };
+        writer.write_all(sql.as_bytes()).map_err(|e| e.into())
+    }
+
+    // The table rows (COPY text lines) go to the table file with the CSV output
+    fn rows_writer(&mut self) -> &mut dyn Write {
+        if self.csv_directory.is_none() {
+            return self.data_writer();
+        }
+        match &mut self.csv_directory {
+            Some(directory) => directory,
+            None => &mut self.sql_sink,
+        }
+    }
+
+    // The schema must be the same as in the dump that is patched
+    fn check_schema(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let expected = match &self.manifest {
+            Some(manifest) => manifest
+                .lock()
+                .map_err(|_| anyhow!("Can't access the manifest"))?
+                .schema_checksums
+                .clone(),
+            None => return Err(anyhow!("The manifest of the existing dump is required")),
+        };
+        if expected.is_empty() {
+            return Err(anyhow!(
+                "The manifest has no schema checksums (it is made by an older version), make a full dump"
+            ));
+        }
+
+        for section in SCHEMA_SECTIONS {
+            let actual = schema_checksum(&self.pg_dump_output(section, connection, events)?);
+            if expected.get(section) != Some(&actual) {
+                return Err(anyhow!(
+                    "The database schema ({}) has changed since the existing dump was made, make a full dump",
+                    section
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn run_pg_dump(
+        &mut self,
+        section: &str,
+        connection: &connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let output = self.pg_dump_output(section, connection, events)?;
+        if let Some(manifest) = &self.manifest {
+            if let Ok(mut manifest) = manifest.lock() {
+                manifest
+                    .schema_checksums
+                    .insert(section.to_string(), schema_checksum(&output));
+            }
+        }
+        // after the checksum: the rewritten publications depend on the dumped tables
+        let output = match section {
+            "post-data" => {
+                let output = self.rewrite_replication(output, events);
+                self.strip_view_refreshes(output)
+            }
+            _ => output,
+        };
+
+        self.dump_writer.write_all(&output).map_err(|e| e.into())
+    }
+
+    fn rewrite_replication(&self, output: Vec<u8>, events: &dyn Indicator) -> Vec<u8> {
+        let sql = match String::from_utf8(output) {
+            Ok(sql) => sql,
+            Err(e) => return e.into_bytes(),
+        };
+        let settings = &self.engine.settings;
+        let (sql, report) = ReplicationRewriter::new(
+            settings.logical_replication,
+            &settings.publication_map,
+            &self.dumped_tables,
+        )
+        .process(&sql);
+        if !report.is_empty() {
+            events.debug_msg(&format!("[post-data] {}", report));
+        }
+        for name in settings
+            .publication_map
+            .keys()
+            .filter(|name| !report.mapped_publications.contains(*name))
+        {
+            events.warning_msg(&format!(
+                "unknown publication `{}` in the publication map config",
+                name
+            ));
+        }
+
+        sql.into_bytes()
+    }
+
+    // The refreshes are written at the end of the dump (`write_view_refreshes`)
+    fn strip_view_refreshes(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let sql = match String::from_utf8(output) {
+            Ok(sql) => sql,
+            Err(e) => return e.into_bytes(),
+        };
+        let (sql, names) = materialized_views::strip_refreshes(&sql);
+        self.stripped_views = names;
+        sql.into_bytes()
+    }
+
+    // The populated views are refreshed in the dependency order after all data and the post-data objects
+    fn write_view_refreshes(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        if self.stripped_views.is_empty() {
+            return Ok(());
+        }
+        if self.engine.settings.materialized_views == MaterializedViews::Skip {
+            events.debug_msg(&format!(
+                "[post-data] Materialized views are left unpopulated: {}",
+                self.stripped_views.len()
+            ));
+            return Ok(());
+        }
+
+        let views: Vec<_> = self
+            .schema_inspector
+            .get_materialized_views(connection)?
+            .into_iter()
+            .filter(|v| v.populated && self.stripped_views.contains(&v.full_name()))
+            .collect();
+        let mut sql = String::from("\n--\n-- Refresh materialized views\n--\n\n");
+        for view in materialized_views::refresh_order(views) {
+            events.debug_msg(&format!(
+                "[post-data] Refresh materialized view {}.{}",
+                view.schema, view.name
+            ));
+            sql.push_str(&view.refresh_sql());
+            sql.push('\n');
+        }
+        self.dump_writer.write_all(sql.as_bytes())?;
+        Ok(())
+    }
+
+    fn pg_dump_output(
+        &self,
+        section: &str,
+        connection: &connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<Vec<u8>> {
+        let program = &self.pg_dump_location;
+        let args = vec!["--section", section];
+        let table_args = table_args(&self.engine.settings.filter)?;
+        let db_url = connection.url.as_str();
+
+        let mut command = Command::new(program);
+        command
+            .args(&self.pg_dump_args)
+            .args(&args)
+            .args(&table_args)
+            .arg(db_url);
+        // the password isn't passed in the arguments (they are visible in the process list)
+        if let Some(password) = connection.password() {
+            command.env(credentials::PASSWORD_ENV, password);
+        }
+        let dump_output = ChildGuard::spawn(&mut command)
+            .map_err(|e| anyhow!("Can't run `{}`: {}", program, e))?
+            .output()?;
+        if !dump_output.status.success() {
+            // the error is returned (instead of exiting), so the connections are closed
+            return Err(anyhow!(
+                "pg_dump error ({}). Command:\n{} {} {}\nOutput:\n{}",
+                dump_output.status,
+                program,
+                args.into_iter()
+                    .chain(table_args.iter().map(|s| s.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                conn_url::redact(db_url),
+                String::from_utf8_lossy(&dump_output.stderr).trim_end()
+            ));
+        }
+
+        let output = match String::from_utf8(dump_output.stdout) {
+            Ok(sql) => {
+                let (mut sql, report) =
+                    DdlScanner::new().process(&sql, &self.engine.settings.ddl_replacements);
+                self.report_ddl(section, &report, events);
+                let rewriter = ObjectNameRewriter::new(
+                    &self.engine.settings.sensitive_object_names,
+                    &self.engine.settings.rename_objects,
+                )?;
+                if !rewriter.is_empty() {
+                    let (renamed, report) = rewriter.process(&sql)?;
+                    self.report_object_names(section, &report, events);
+                    sql = renamed;
+                }
+                if self.engine.settings.target_profile.drops_if_exists() {
+                    let (with_drops, count) = ddl::add_drops_if_exists(&sql);
+                    events.debug_msg(&format!(
+                        "[{}] Added DROP ... IF EXISTS statements: {}",
+                        section, count
+                    ));
+                    sql = with_drops;
+                }
+                sql.into_bytes()
+            }
+            Err(e) => e.into_bytes(),
+        };
+
+        Ok(output)
+    }
+
+    // Refuses to dump a database that looks like production (unless the dump is confirmed)
+    fn check_safety(
+        &self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let safety = match &self.engine.settings.safety {
+            Some(safety) => safety,
+            None => return Ok(()),
+        };
+        let findings = safety::check(safety, connection)?;
+        if findings.is_empty() {
+            return Ok(());
+        }
+
+        let reason = self
+            .safety_override
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty());
+        match reason {
+            Some(reason) if safety.allow_override => {
+                events.warning_msg(&format!(
+                    "the source database looks like production ({}), proceeding: {}",
+                    findings.join("; "),
+                    reason
+                ));
+                if let Some(manifest) = &self.manifest {
+                    if let Ok(mut manifest) = manifest.lock() {
+                        manifest.safety_override = Some(SafetyOverride {
+                            findings,
+                            reason: reason.to_string(),
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Some(_) => Err(anyhow!(
+                "The source database looks like production ({}), the `safety` config doesn't allow overrides",
+                findings.join("; ")
+            )),
+            None => Err(anyhow!(
+                "The source database looks like production ({}). Use `--yes-i-know <REASON>` to dump it anyway",
+                findings.join("; ")
+            )),
+        }
+    }
+
+    // Long queries on a replica can be cancelled by conflicts with recovery
+    fn check_replica(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        self.replica = ReplicaSettings::fetch(&mut connection.client)?;
+        let replica = match &self.replica {
+            Some(replica) => replica,
+            None => return Ok(()),
+        };
+
+        ReplicaSettings::prepare_session(&mut connection.client)?;
+        // the explicit timeouts (`--statement-timeout` etc.) win
+        connection.apply_timeouts()?;
+        events.debug_msg(&format!(
+            "The source database is a replica (hot_standby_feedback = {}, max_standby_streaming_delay = {}){}",
+            if replica.hot_standby_feedback { "on" } else { "off" },
+            replica.max_standby_streaming_delay,
+            if self.prefer_replica_safe {
+                ", every table is dumped in its own transaction"
+            } else {
+                ""
+            }
+        ));
+        if replica.has_conflicts() && !self.prefer_replica_safe {
+            events.warning_msg(&format!(
+                "long queries on the replica can be cancelled by conflicts with recovery: {}",
+                replica.remediation()
+            ));
+        }
+        Ok(())
+    }
+
+    fn report_target_profile(&self, events: &dyn Indicator) {
+        let profile = self.engine.settings.target_profile;
+        events.debug_msg(&format!(
+            "Target profile: {} (pg_dump arguments: {})",
+            profile.name(),
+            if self.pg_dump_args.is_empty() {
+                "none".to_string()
+            } else {
+                self.pg_dump_args.join(" ")
+            }
+        ));
+        if let Some(manifest) = &self.manifest {
+            if let Ok(mut manifest) = manifest.lock() {
+                manifest.target_profile = Some(profile.name().to_string());
+            }
+        }
+    }
+
+    fn report_session_settings(
+        &self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let settings = connection.session_settings()?;
+        let list: Vec<_> = settings
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        events.debug_msg(&format!("Session settings: {}", list.join(", ")));
+        if let Some(manifest) = &self.manifest {
+            if let Ok(mut manifest) = manifest.lock() {
+                manifest.session_settings = settings;
+            }
+        }
+        Ok(())
+    }
+
+    fn report_ddl(&self, section: &str, report: &DdlReport, events: &dyn Indicator) {
+        if report.replaced > 0 {
+            events.debug_msg(&format!(
+                "[{}] Replaced literals in defaults and check constraints: {}",
+                section, report.replaced
+            ));
+        }
+        if !report.findings.is_empty() {
+            events.debug_msg(&format!(
+                "[{}] Warning: possible personal data in defaults and check constraints:",
+                section
+            ));
+            for finding in &report.findings {
+                events.debug_msg(&format!("  {}", finding));
+            }
+        }
+    }
+
+    fn report_object_names(
+        &self,
+        section: &str,
+        report: &ObjectNamesReport,
+        events: &dyn Indicator,
+    ) {
+        if !report.renamed.is_empty() {
+            events.debug_msg(&format!(
+                "[{}] Renamed indexes and constraints: {}",
+                section,
+                report.renamed.len()
+            ));
+            if let Some(manifest) = &self.manifest {
+                if let Ok(mut manifest) = manifest.lock() {
+                    manifest.renamed_objects.extend(report.renamed.clone());
+                }
+            }
+        }
+        if !report.findings.is_empty() {
+            events.debug_msg(&format!(
+                "[{}] Warning: possible sensitive data in index and constraint names:",
+                section
+            ));
+            for finding in &report.findings {
+                events.debug_msg(&format!("  {}", finding));
+            }
+        }
+    }
+
+    fn report_rule_timing(&self, events: &dyn Indicator) {
+        if let Some(metrics) = &self.engine.rule_metrics {
+            events.debug_msg("Slowest rules:");
+            for report in metrics.slowest(SLOWEST_RULES_COUNT) {
+                events.debug_msg(&format!("  {}", report));
+            }
+        }
+    }
+
+    // The epsilons of the `dp_noise` rules add up within a table
+    fn report_privacy_budget(&self, events: &dyn Indicator) {
+        let budget = self.engine.settings.privacy_budget();
+        if budget.is_empty() {
+            return;
+        }
+
+        events.debug_msg("Privacy budget (the sum of `dp_noise` epsilons per table):");
+        for (table, epsilon) in budget {
+            let mut columns: Vec<_> = table
+                .rules
+                .iter()
+                .map(|(column, rule)| (column, rule.privacy_budget()))
+                .filter(|(_, epsilon)| *epsilon > 0.0)
+                .map(|(column, epsilon)| format!("{}: {}", column, epsilon))
+                .collect();
+            columns.sort();
+            events.debug_msg(&format!(
+                "  {}: {} ({})",
+                table.name,
+                epsilon,
+                columns.join(", ")
+            ));
+        }
+    }
+
+    // The values of these columns are replaced by the database (the expressions are in the dump queries)
+    fn report_sql_rules(&self, events: &dyn Indicator) {
+        let mut rules: Vec<_> = self
+            .engine
+            .settings
+            .tables
+            .iter()
+            .flat_map(|table| {
+                table.rules.iter().filter_map(move |(column, rule)| {
+                    rule.sql_expression()
+                        .map(|e| format!("  {}.{}: {}", table.name, column, e))
+                })
+            })
+            .collect();
+        if rules.is_empty() {
+            return;
+        }
+
+        rules.sort();
+        events.debug_msg("Rules executed by the database (`sql`):");
+        for rule in rules {
+            events.debug_msg(&rule);
+        }
+    }
+
+    fn report_anonymity(&self, events: &dyn Indicator) -> Result<()> {
+        let metrics = match &self.engine.anonymity_metrics {
+            Some(metrics) => metrics,
+            None => return Ok(()),
+        };
+
+        let reports = metrics.report(self.min_k.unwrap_or(DEFAULT_K))?;
+        events.debug_msg("k-anonymity of quasi-identifiers:");
+        for report in &reports {
+            events.debug_msg(&format!("  {}", report));
+        }
+
+        let failed: Vec<_> = reports.iter().filter(|r| !r.is_ok()).collect();
+        match self.min_k {
+            Some(k) if !failed.is_empty() => Err(anyhow!(
+                "{} quasi-identifier sets have equivalence classes with less than {} rows: {}",
+                failed.len(),
+                k,
+                failed
+                    .iter()
+                    .map(|r| format!("{} ({})", r.table, r.columns.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Every config table must match some tables, a table name without schema must match tables
+    // in one schema only (unless `apply_to_all_schemas`)
+    fn check_table_resolution(
+        &self,
+        settings: &Settings,
+        tables: &[PgTable],
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let resolutions = TableResolution::resolve_all(settings, tables);
+        if !resolutions.is_empty() {
+            events.debug_msg("Config tables:");
+            for resolution in &resolutions {
+                events.debug_msg(&format!("  {}", resolution));
+            }
+        }
+
+        let problems: Vec<_> = resolutions
+            .iter()
+            .filter_map(|r| r.problem.as_deref())
+            .collect();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        if self.lenient {
+            for problem in problems {
+                events.warning_msg(problem);
+            }
+            return Ok(());
+        }
+        Err(anyhow!(
+            "The config tables don't match the database tables (use --lenient to dump anyway):\n  {}",
+            problems.join("\n  ")
+        ))
+    }
+
+    // Indexes with expressions or predicates on transformed columns may change their semantics
+    // (e.g. a partial index `WHERE email LIKE '%@company.com'` doesn't match anonymized emails)
+    fn check_index_semantics(
+        &mut self,
+        connection: &mut connector::Connection,
+        tables: &[(PgTable, Vec<String>)],
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let mut count = 0;
+        for (table, columns) in tables {
+            for index in self
+                .schema_inspector
+                .get_expression_indexes(connection, table)?
+            {
+                let used: Vec<_> = columns
+                    .iter()
+                    .filter(|c| index.references(c))
+                    .map(|c| format!("`{}`", c))
+                    .collect();
+                if !used.is_empty() {
+                    count += 1;
+                    events.warning_msg(&format!(
+                        "the index `{}` of the table {} uses the transformed columns {} in expressions or predicates: {}",
+                        index.name,
+                        table.get_full_name(),
+                        used.join(", "),
+                        index.definition
+                    ));
+                }
+            }
+        }
+
+        if count > 0 && self.fail_on_index_semantics_change {
+            return Err(anyhow!(
+                "{} indexes use transformed columns in expressions or predicates (see the warnings above)",
+                count
+            ));
+        }
+        Ok(())
+    }
+
+    // The rules that generate values of another category than the column names suggest
+    fn check_lint(&self, events: &dyn Indicator) -> Result<()> {
+        let warnings = self.engine.settings.lint_warnings();
+        for warning in &warnings {
+            events.warning_msg(warning);
+        }
+
+        if !warnings.is_empty() && self.deny_lint_warnings {
+            return Err(anyhow!(
+                "The config has {} lint warnings (see the warnings above)",
+                warnings.len()
+            ));
+        }
+        Ok(())
+    }
+
+    // The config rules for the tables must not conflict with the table flags in the `pg_dump` arguments
+    fn check_pg_dump_table_args(&self, tables: &[(PgTable, Vec<String>)]) -> Result<()> {
+        for (table, _) in tables {
+            if let Some(arg) = self.pg_dump_table_args.excluded_by(table) {
+                return Err(anyhow!(
+                    "The table {} has rules in the config, but its data is excluded by the pg_dump arguments ({}). \
+                    Remove the table rules from the config (or exclude the table with the `filter` config section) \
+                    or change the pg_dump arguments",
+                    table.get_full_name(),
+                    arg
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks before dumping data
+    fn check_tables(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        self.check_lint(events)?;
+        let pg_dump_version = compatibility::pg_dump_version(&self.pg_dump_location)?;
+        connection
+            .compatibility()?
+            .check_pg_dump(pg_dump_version, &self.pg_dump_args)?;
+
+        let settings = self.engine.settings.clone();
+        let all_tables = self.schema_inspector.get_tables(connection)?;
+        report_inspection_warnings(connection, events);
+        self.check_table_resolution(&settings, &all_tables, events)?;
+        let existing_values = existing_values::targets(&settings, &all_tables)?;
+
+        let tables = self.configured_tables(&settings, all_tables);
+        self.check_explicit_columns(&tables)?;
+        self.check_column_types(&tables, events);
+        check_column_lengths(&tables)?;
+        int_range::check(&tables, &settings.sequences, &self.skipped_rules)?;
+        check_sql_rules(connection, &tables)?;
+        self.filtered_rows = check_query_conditions(connection, &tables)?;
+        learn_format::learn(connection, &tables, &self.skipped_rules, events)?;
+        existing_values::register(connection, &self.engine, &existing_values, events)?;
+
+        let tables = tables_with_rules(tables, &self.skipped_rules);
+        self.check_pg_dump_table_args(&tables)?;
+        self.check_index_semantics(connection, &tables, events)
+    }
+
+    // Tables with the config tables (the filtered tables are skipped)
+    fn configured_tables(
+        &mut self,
+        settings: &Settings,
+        tables: Vec<PgTable>,
+    ) -> Vec<(PgTable, TableCfg)> {
+        let mut result = vec![];
+        for table in tables {
+            match settings.find_table(&table.get_names()) {
+                Some(cfg) if crate::filter_table(&table.get_full_name(), &settings.filter) => {
+                    result.push((table, cfg.clone()))
+                }
+                _ => continue,
+            };
+        }
+
+        result
+    }
+
+    // The rules are skipped for the columns of unsupported types (unless the columns are in `force`),
+    // e.g. an `email` rule for a `bytea` column, and for the preset columns that the tables don't have
+    fn check_column_types(&mut self, tables: &[(PgTable, TableCfg)], events: &dyn Indicator) {
+        self.skipped_rules.clear();
+        for (table, cfg) in tables {
+            let columns = table.get_columns_names();
+            let missing: Vec<_> = cfg
+                .preset_rules
+                .iter()
+                .filter(|column| !columns.contains(column))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                events.debug_msg(&format!(
+                    "The preset rules are skipped for the columns that {} doesn't have: {}",
+                    table.get_full_name(),
+                    missing.join(", ")
+                ));
+                self.skipped_rules
+                    .entry(table.get_full_name())
+                    .or_default()
+                    .extend(missing);
+            }
+
+            for (column, warning) in unsupported_rules(table, cfg) {
+                events.warning_msg(&warning.to_string());
+                self.skipped_rules
+                    .entry(table.get_full_name())
+                    .or_default()
+                    .insert(column);
+            }
+        }
+    }
+
+    // With `explicit`, every column must have a rule or be ignored
+    fn check_explicit_columns(&mut self, tables: &[(PgTable, TableCfg)]) -> Result<()> {
+        let settings = self.engine.settings.clone();
+        let failures: Vec<_> = tables
+            .iter()
+            .filter(|(_, cfg)| settings.is_explicit(cfg))
+            .filter_map(|(table, cfg)| {
+                let columns = table.get_columns_names();
+                let missing = cfg.unmentioned_columns(&columns);
+                (!missing.is_empty())
+                    .then(|| format!("{}: {}", table.get_full_name(), missing.join(", ")))
+            })
+            .collect();
+
+        if !failures.is_empty() {
+            return Err(anyhow!(
+                "Columns without rules in the explicit tables (add rules or list them in `ignore` with reasons):\n  {}",
+                failures.join("\n  ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// `Some(reset_sequences)` if the table is dumped without data (the table config overrides
+    /// the global `skip_data`)
+    fn skip_data(&self, table: &PgTable) -> Option<bool> {
+        let settings = &self.engine.settings;
+        match settings
+            .find_table(&table.get_names())
+            .and_then(|cfg| cfg.skip_data.as_ref())
+        {
+            Some(skip_data) => Some(skip_data.reset_sequences),
+            None => settings
+                .skip_data
+                .tables
+                .iter()
+                .any(|p| dump_args::matches(p, table))
+                .then_some(settings.skip_data.reset_sequences),
+        }
+    }
+
+    fn last_value_setval(&self, seq: &PgSequence, qw: &mut QueryWrapper) -> Result<String> {
+        let last_value: i64 = qw.query_one(seq.last_value_query().as_str(), &[])?.get(0);
+        Ok(seq.setval_query(last_value))
+    }
+
+    fn plan_cycles(
+        &mut self,
+        connection: &mut connector::Connection,
+        strategy: CycleStrategy,
+        tables: &[(PgTable, i32)],
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        if self.csv_directory.is_some() && strategy != CycleStrategy::Fail {
+            return Err(anyhow!(
+                "The `two_pass` and `defer` cycle strategies can't be used with the CSV output \
+                (the files are loaded without the dump statements)"
+            ));
+        }
+        let order: Vec<_> = tables
+            .iter()
+            .filter(|(t, _)| self.skip_reason(t).is_none())
+            .map(|(t, _)| t.get_full_name())
+            .collect();
+        let plan = CyclePlan::load(connection.catalog_client(), strategy, &order)?;
+        for table in plan.split_tables() {
+            events.debug_msg(&format!(
+                "The circular foreign keys of {} are restored in the second pass",
+                table
+            ));
+        }
+        let sql = plan.begin_sql();
+        self.data_writer().write_all(sql.as_bytes())?;
+        self.cycle_plan = Some(plan);
+
+        Ok(())
+    }
+
+    fn warn_unknown_sequences(
+        &self,
+        settings: &Settings,
+        tables: &[(PgTable, i32)],
+        events: &dyn Indicator,
+    ) {
+        let mut names: Vec<_> = settings.sequences.keys().collect();
+        names.sort();
+        for name in names {
+            let known = tables.iter().any(|(t, _)| {
+                t.sequences
+                    .iter()
+                    .any(|seq| seq.config_names(&t.get_full_name()).contains(name))
+            });
+            if known {
+                continue;
+            }
+
+            let table = tables
+                .iter()
+                .map(|(t, _)| t)
+                .find(|t| name.starts_with(&format!("{}.", t.get_full_name())));
+            let discovered: Vec<_> = match table {
+                Some(t) => t.sequences.iter().collect(),
+                None => tables.iter().flat_map(|(t, _)| &t.sequences).collect(),
+            };
+            let discovered: Vec<_> = discovered
+                .into_iter()
+                .map(|seq| seq.full_name.as_str())
+                .collect();
+
+            events.warning_msg(&format!(
+                "unknown sequence `{}` in the config, discovered sequences{}: {}",
+                name,
+                table.map_or(String::new(), |t| format!(" for {}", t.get_full_name())),
+                if discovered.is_empty() {
+                    "none".to_string()
+                } else {
+                    discovered.join(", ")
+                }
+            ));
+        }
+    }
+
+    fn includes_extension_tables(&self, extension: &str, settings: &Settings) -> bool {
+        settings.includes_extension_tables(extension, self.include_extension_tables)
+    }
+
+    // The chunks can't be restored without the TimescaleDB catalog (it is an extension table),
+    // so they are skipped together
+    fn warn_skipped_chunks(
+        &self,
+        settings: &Settings,
+        tables: &[(PgTable, i32)],
+        events: &dyn Indicator,
+    ) {
+        if self.includes_extension_tables(TIMESCALEDB_EXTENSION, settings) {
+            return;
+        }
+
+        let hypertables: BTreeSet<_> = tables
+            .iter()
+            .filter_map(|(t, _)| t.hypertable.as_deref())
+            .collect();
+        if !hypertables.is_empty() {
+            events.warning_msg(&format!(
+                "the data of the TimescaleDB hypertables is not dumped ({}), \
+                include the {} extension tables to dump it",
+                hypertables.into_iter().collect::<Vec<_>>().join(", "),
+                TIMESCALEDB_EXTENSION
+            ));
+        }
+    }
+
+    // Leaves only the columns of `only_columns` in the dumped tables (before the subset, it replaces
+    // the values of the dumped columns)
+    fn project_tables(
+        &self,
+        connection: &mut connector::Connection,
+        tables: &mut [(PgTable, i32)],
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let settings = &self.engine.settings;
+        for (table, _) in tables.iter_mut() {
+            let Some(cfg) = settings.find_table(&table.get_names()) else {
+                continue;
+            };
+            if cfg.only_columns.is_empty() || self.skip_reason(table).is_some() {
+                continue;
+            }
+            only_columns::project(&mut connection.client, table, cfg)?;
+            if let Some(projection) = &table.projection {
+                events.debug_msg(&format!(
+                    "[Only columns] {}: the columns {} are not dumped",
+                    table.get_full_name(),
+                    projection.omitted.join(", ")
+                ));
+                if projection.omitted_row_bytes.is_none() {
+                    events.warning_msg(&format!(
+                        "{} has no statistics, the size saved by `only_columns` isn't estimated (run ANALYZE)",
+                        table.get_full_name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The subset rows are selected in the dump transaction
+    fn select_subset(
+        &self,
+        tables: &mut [(PgTable, i32)],
+        qw: &mut QueryWrapper,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let subset = match &self.engine.settings.subset {
+            Some(subset) => subset,
+            None => return Ok(()),
+        };
+
+        events.debug_msg("Select the subset rows...");
+        let plan = Subsetter::new(tables, qw)?.select(subset, tables, qw)?;
+        for (name, table) in &plan.tables {
+            let selections: Vec<_> = table.selections.iter().map(|s| s.to_string()).collect();
+            events.debug_msg(&format!(
+                "[Subset] {}: {} rows ({})",
+                name,
+                table.rows,
+                selections.join(", ")
+            ));
+        }
+        events.debug_msg(&format!(
+            "[Subset] {} rows in {} tables, the other tables are dumped as is",
+            plan.tables.values().map(|t| t.rows).sum::<u64>(),
+            plan.tables.len()
+        ));
+        Ok(())
+    }
+
+    // The tenant rows are selected in the dump transaction (by foreign keys, as in the subset)
+    fn select_tenant(
+        &self,
+        tables: &mut [(PgTable, i32)],
+        qw: &mut QueryWrapper,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let tenant = match &self.engine.settings.tenant {
+            Some(tenant) => tenant,
+            None => return Ok(()),
+        };
+
+        events.debug_msg("Select the tenant rows...");
+        let plan = tenant::select(tenant, tables, qw)?;
+        for (name, table) in &plan.tables {
+            events.debug_msg(&format!(
+                "[Tenant] {}: {}{}",
+                name,
+                table.scoping,
+                table
+                    .rows
+                    .map(|rows| format!(", {} rows", rows))
+                    .unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    // The row limits and sampling of the tables (the limits are applied after the subset and tenant filters),
+    // the run seed of the shuffled rows
+    fn set_row_limits(&self, tables: &mut [(PgTable, i32)], events: &dyn Indicator) {
+        let settings = &self.engine.settings;
+        for (table, _) in tables.iter_mut() {
+            let cfg = settings.find_table(&table.get_names());
+            table.filtered_rows = self.filtered_rows.get(&table.get_full_name()).copied();
+            table.limit = settings.row_limit(cfg);
+            table.sample = cfg.and_then(|c| c.sample);
+            table.seed = settings.seed;
+            if table.is_limited(cfg) {
+                events.debug_msg(&format!(
+                    "[Limit] {}: {} rows{}",
+                    table.get_full_name(),
+                    table.count_of_query_to(cfg),
+                    table
+                        .sample
+                        .map(|s| format!(" (a {}% sample)", s.percent()))
+                        .unwrap_or_default()
+                ));
+            }
+            if let Some(projection) = &self.projection {
+                projection.estimate(&table.get_full_name(), table_estimate(table, cfg));
+                let sample_rows = projection.sample_rows();
+                table.limit = Some(table.limit.map_or(sample_rows, |l| l.min(sample_rows)));
+            }
+        }
+    }
+
+    // Every table is dumped in its own transaction, to a temporary file first. The table is dumped again
+    // (with a new connection, the replica may terminate the old one) if its query is cancelled by a conflict
+    // with recovery, except the tables with quasi-identifiers: the k-anonymity check would count their rows twice.
+    // If the temporary file exceeds the temporary disk (the budget or the free space), the table is dumped again
+    // directly to the output (and it can't be dumped again after conflicts then).
+    fn dump_table_with_retries(
+        &mut self,
+        connection: &mut connector::Connection,
+        table: &PgTable,
+        run: &mut TableRun,
+    ) -> Result<TableOutput> {
+        let retriable = self.engine.anonymity_metrics.is_none()
+            || self
+                .engine
+                .settings
+                .find_table(&table.get_names())
+                .is_none_or(|cfg| cfg.quasi_identifiers.is_empty());
+
+        let mut retries = 0;
+        let component = format!("the staging of `{}`", table.get_full_name());
+        let mut staged = true;
+        loop {
+            if staged {
+                match RetryBuffer::new(&self.engine.temp_disk, &component, 0) {
+                    Ok(buffer) => self.retry_buffer = Some(buffer),
+                    Err(e) => {
+                        staged = false;
+                        run.events().warning_msg(&format!(
+                            "{}, {} is dumped without the staging file",
+                            e,
+                            table.get_full_name()
+                        ));
+                    }
+                }
+            }
+            let result = QueryWrapper::with_isolation_level(
+                &mut connection.client,
+                self.dump_isolation_level,
+            )
+            .and_then(|mut qw| self.dump_table(table, &mut qw, run));
+            let buffer = self.retry_buffer.take();
+
+            // the failed writes of the staging file itself (not of the other temporary files of the table)
+            let staging_error = match &result {
+                Err(e) => TempDiskError::find(e)
+                    .filter(|e| e.component() == component)
+                    .cloned(),
+                Ok(_) => None,
+            };
+            match result {
+                Ok(output) => {
+                    if let Some(buffer) = buffer {
+                        buffer.copy_to(self.data_writer())?;
+                    }
+                    return Ok(output);
+                }
+                Err(e) if retriable && staging_error.is_some() => {
+                    run.fail(&e);
+                    if let Some(error) = staging_error {
+                        run.events().warning_msg(&format!(
+                            "{}, {} is dumped again without the staging file",
+                            error,
+                            table.get_full_name()
+                        ));
+                    }
+                    staged = false;
+                    connection.reconnect()?;
+                    if self.replica.is_some() {
+                        ReplicaSettings::prepare_session(&mut connection.client)?;
+                    }
+                    connection.apply_timeouts()?;
+                }
+                Err(e)
+                    if retriable
+                        && buffer.is_some()
+                        && retries < MAX_CONFLICT_RETRIES
+                        && replica::is_recovery_conflict(&e) =>
+                {
+                    run.fail(&e);
+                    connection.reconnect()?;
+                    ReplicaSettings::prepare_session(&mut connection.client)?;
+                    connection.apply_timeouts()?;
+                    retries += 1;
+                    self.conflict_retries += 1;
+                    run.events().debug_msg(&format!(
+                        "[Dumping: {}] Cancelled by a conflict with recovery, dumping again ({} of {})",
+                        table.get_full_name(),
+                        retries,
+                        MAX_CONFLICT_RETRIES
+                    ));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // The sessions of the parallel dump (none with one job)
+    fn connect_workers(
+        &self,
+        connection: &connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<Vec<connector::Connection>> {
+        let settings = &self.engine.settings;
+        let jobs = settings.jobs.unwrap_or(1);
+        if jobs <= 1 {
+            return Ok(vec![]);
+        }
+        let blocker = if self.csv_directory.is_some() {
+            Some("the CSV output")
+        } else if settings.subset.is_some() {
+            Some("the `subset` config section (the subset rows are selected in the dump transaction)")
+        } else if settings.tenant.is_some() {
+            Some("the `tenant` config section (the tenant rows are selected in the dump transaction)")
+        } else if self.replica.is_some() && self.prefer_replica_safe {
+            Some("--prefer-replica-safe")
+        } else if self.projection.is_some() {
+            Some("--stats-only (the samples are measured with one connection)")
+        } else {
+            None
+        };
+        if let Some(blocker) = blocker {
+            events.warning_msg(&format!(
+                "`jobs` is ignored with {}, the data is dumped with one connection",
+                blocker
+            ));
+            return Ok(vec![]);
+        }
+
+        events.debug_msg(&format!(
+            "Dump the data with {} parallel connections...",
+            jobs
+        ));
+        (0..jobs)
+            .map(|_| {
+                let mut worker = connection.spawn()?;
+                if self.replica.is_some() {
+                    ReplicaSettings::prepare_session(&mut worker.client)?;
+                }
+                Ok(worker)
+            })
+            .collect()
+    }
+
+    // The tables whose data is dumped go to the workers in the dump order, except the tables split
+    // by the cycle plan (they are dumped by the dump session) and the tables dumped before the checkpoint
+    fn start_workers(
+        &self,
+        mut workers: Vec<connector::Connection>,
+        snapshot: Option<&str>,
+        tables: &[(PgTable, i32)],
+    ) -> Result<Pool> {
+        for worker in &mut workers {
+            parallel::begin(&mut worker.client, self.dump_isolation_level, snapshot)?;
+        }
+        let pooled = tables
+            .iter()
+            .map(|(table, _)| table)
+            .filter(|table| {
+                self.skip_reason(table).is_none()
+                    && !self.resumed_tables.contains(&table.get_full_name())
+                    && self
+                        .cycle_plan
+                        .as_ref()
+                        .and_then(|p| p.split(&table.get_full_name()))
+                        .is_none()
+            })
+            .map(|table| (table.get_full_name(), table.clone()))
+            .collect();
+        Ok(Pool::start(workers, pooled, || {
+            let mut backend = self.worker_backend();
+            move |connection: &mut connector::Connection, table: &PgTable, run: &mut TableRun| {
+                backend.dump_segment(connection, table, run)
+            }
+        }))
+    }
+
+    // The backend of a worker: it writes the tables to the segments only
+    fn worker_backend(&self) -> PgBackend<io::Sink> {
+        PgBackend {
+            schema_inspector: self.schema_inspector.clone(),
+            engine: self.engine.clone(),
+            dump_writer: io::sink(),
+            data_writer: None,
+            csv_directory: None,
+            sql_sink: io::sink(),
+            sync_sequences: self.sync_sequences,
+            fk_order: self.fk_order,
+            fail_on_index_semantics_change: self.fail_on_index_semantics_change,
+            deny_lint_warnings: self.deny_lint_warnings,
+            include_extension_tables: self.include_extension_tables,
+            lenient: self.lenient,
+            prefer_replica_safe: false,
+            min_k: None,
+            safety_override: None,
+            manifest: self.manifest.clone(),
+            dump_isolation_level: self.dump_isolation_level,
+            pg_dump_location: self.pg_dump_location.clone(),
+            pg_dump_args: vec![],
+            pg_dump_table_args: PgDumpTableArgs::default(),
+            replica: None,
+            retry_buffer: None,
+            conflict_retries: 0,
+            skipped_rules: self.skipped_rules.clone(),
+            filtered_rows: HashMap::new(),
+            projection: None,
+            dumped_tables: BTreeSet::new(),
+            resumed_tables: HashSet::new(),
+            cycle_plan: None,
+            stripped_views: BTreeSet::new(),
+        }
+    }
+
+    // Dumps the table to a temporary segment in the transaction of the worker session (see `parallel::begin`)
+    fn dump_segment(
+        &mut self,
+        connection: &mut connector::Connection,
+        table: &PgTable,
+        run: &mut TableRun,
+    ) -> (Result<TableOutput>, Option<RetryBuffer>) {
+        let component = format!("the segment of `{}`", table.get_full_name());
+        match RetryBuffer::new(&self.engine.temp_disk, &component, 0) {
+            Ok(buffer) => self.retry_buffer = Some(buffer),
+            Err(e) => return (Err(e.into()), None),
+        }
+        let mut qw = QueryWrapper::WithoutTransaction(&mut connection.client);
+        let result = self.dump_table(table, &mut qw, run);
+        (result, self.retry_buffer.take())
+    }
+
+    // The segment is written even if the table is failed (it ends with the terminated COPY block,
+    // as the table dumped by the dump session)
+    fn write_segment(&mut self, segment: Segment, run: &mut TableRun) -> Result<TableOutput> {
+        if let Some(state) = segment.run {
+            run.resume(state);
+        }
+        if let Some(buffer) = segment.buffer {
+            buffer.copy_to(self.data_writer())?;
+        }
+        segment.result
+    }
+
+    fn dump_table(
+        &mut self,
+        table: &PgTable,
+        qw: &mut QueryWrapper,
+        run: &mut TableRun,
+    ) -> Result<TableOutput> {
+        // the tables are inspected at the start of the data phase, they could be changed since then
+        let (table, dropped_columns) = missing_objects::revalidate(table, qw)?;
+        if !dropped_columns.is_empty() {
+            run.events().warning_msg(&format!(
+                "the columns of {} are dropped or renamed after the inspection, they are not dumped: {}",
+                table.get_full_name(),
+                dropped_columns.join(", ")
+            ));
+        }
+
+        run.start(self.table_info(&table));
+        let Some(directory) = &mut self.csv_directory else {
+            return self.write_table(&table, qw, run);
+        };
+        directory.begin_table(&table)?;
+        let result = self.write_table(&table, qw, run);
+        if let Some(directory) = &mut self.csv_directory {
+            match &result {
+                Ok(output) => directory.end_table(output.rows)?,
+                Err(_) => directory.discard_table(),
+            }
+        }
+        result
+    }
+
+    // The started COPY block is terminated, so the dump stays valid if the table is skipped
+    fn abort_copy(&mut self, error: postgres::Error) -> Result<TableOutput> {
+        self.data_writer().write_all(b"\\.\n")?;
+        Err(error.into())
+    }
+
+    fn write_table(
+        &mut self,
+        table: &PgTable,
+        qw: &mut QueryWrapper,
+        run: &mut TableRun,
+    ) -> Result<TableOutput> {
+        let settings = self.engine.settings.clone();
+
+        self.write_log(format!("{}{}", DUMP_TABLE_LOG, &table.get_full_name()))?;
+
+        // the columns of the circular foreign keys are restored in the second pass
+        let mut split_rows = match self
+            .cycle_plan
+            .as_ref()
+            .and_then(|p| p.split(&table.get_full_name()))
+        {
+            Some(split) => {
+                let split = split.clone();
+                let sql = split.before_sql()?;
+                self.data_writer().write_all(b"\n")?;
+                self.data_writer().write_all(sql.as_bytes())?;
+                let rows = split.rows(table.get_column_indexes(), &self.engine.temp_disk)?;
+                Some((split, rows))
+            }
+            None => None,
+        };
+
+        self.data_writer().write_all(b"\n")?;
+        self.data_writer()
+            .write_all(table.query_from().as_bytes())?;
+        self.data_writer().write_all(b"\n")?;
+
+        let cfg = settings.find_table(&table.get_names());
+        let rule_sources = settings.rule_sources(&table.get_names());
+        if !rule_sources.is_empty() {
+            run.events().debug_msg(&format!(
+                "[Dumping: {}] Rules from: {}",
+                table.get_full_name(),
+                rule_sources.join(", ")
+            ));
+        }
+
+        let skipped = self
+            .skipped_rules
+            .get(&table.get_full_name())
+            .cloned()
+            .unwrap_or_default();
+        let mut table_manifest = self
+            .manifest
+            .as_ref()
+            .map(|_| manifest_builder(table, cfg, &skipped, &settings.secret_detection));
+
+        let mut shuffler = match cfg.and_then(|c| c.shuffle_rows.as_ref()) {
+            Some(s) if s.method == ShuffleMethod::Client => Some(
+                Shuffler::new(
+                    s.seed.or(settings.seed),
+                    s.memory_limit_bytes().map_err(|e| anyhow!(e))?,
+                )
+                .with_temp_disk(self.engine.temp_disk.clone(), &table.get_full_name())
+                .with_max_memory(self.engine.max_memory),
+            ),
+            Some(_) => {
+                run.events().warning_msg(&format!(
+                    "the rows of {} are shuffled by the database (it sorts the whole table, \
+                    use the `client` method for large tables)",
+                    table.get_full_name()
+                ));
+                None
+            }
+            None => None,
+        };
+
+        let mut max_values = table.is_limited(cfg).then(|| MaxValues::new(table));
+        let mut count: u64 = 0;
+        let mut bytes: u64 = 0;
+        let mut outbox_events = BTreeMap::new();
+        if let Some(cfg) = cfg {
+            if let Some(transformed_query) = table.transformed_query_to(Some(cfg), count) {
+                let int_columns = IntColumns::new(table, cfg, &skipped, settings.on_overflow);
+                let array_columns = ArrayColumns::new(table, cfg, &skipped);
+                let mut outbox = cfg
+                    .outbox
+                    .as_ref()
+                    .map(|outbox| {
+                        OutboxRows::new(
+                            outbox,
+                            &cfg.name,
+                            table.get_column_indexes(),
+                            &settings.globals,
+                        )
+                    })
+                    .transpose()?;
+                let reader = match qw.copy_out(transformed_query.as_str()) {
+                    Ok(reader) => reader,
+                    Err(e) => return self.abort_copy(e),
+                };
+                for line in reader.split(b'\n') {
+                    let line = line?;
+                    let key = shuffler.as_ref().map(|s| s.key(&line));
+                    let row = PgRow::from_bytes_row(line, count + 1, table.clone());
+                    let transformed = row.transform_fitting(
+                        &self.engine,
+                        cfg.name.as_str(),
+                        &skipped,
+                        &int_columns,
+                        &array_columns,
+                    )?;
+                    let transformed = match &mut outbox {
+                        Some(outbox) => match outbox.apply(transformed)? {
+                            Some(transformed) => transformed,
+                            None => continue,
+                        },
+                        None => transformed,
+                    };
+                    if let Some(m) = &mut table_manifest {
+                        m.add_row(&transformed);
+                    }
+                    if let Some(values) = &mut max_values {
+                        values.add_row(&transformed);
+                    }
+                    let transformed = match &mut split_rows {
+                        Some((_, rows)) => rows.split(transformed)?,
+                        None => transformed,
+                    };
+                    bytes += transformed.len() as u64 + 1;
+                    if let (Some(shuffler), Some(key)) = (&mut shuffler, key) {
+                        shuffler.push(key, transformed)?;
+                    } else {
+                        self.rows_writer().write_all(&transformed)?;
+                        self.rows_writer().write_all(b"\n")?;
+                    }
+
+                    count += 1;
+                    run.progress(count, bytes);
+                    check_memory(self.engine.max_memory, table, count, run.events())?;
+                }
+                if int_columns.clamped() > 0 {
+                    run.events().warning_msg(&format!(
+                        "{} generated values of {} are clamped to the integer column ranges",
+                        int_columns.clamped(),
+                        table.get_full_name()
+                    ));
+                }
+                if let Some(outbox) = outbox {
+                    if let Some(warning) = outbox.unknown_warning(&table.get_full_name()) {
+                        run.events().warning_msg(&warning);
+                    }
+                    outbox_events = outbox.into_events();
+                }
+            }
+        }
+
+        if let Some(untransformed_query) = table.untransformed_query_to(cfg, count) {
+            let reader = match qw.copy_out(untransformed_query.as_str()) {
+                Ok(reader) => reader,
+                Err(e) => return self.abort_copy(e),
+            };
+            for line in reader.split(b'\n') {
+                let line = line?;
+                if let Some(cfg) = cfg.filter(|_| self.engine.anonymity_metrics.is_some()) {
+                    let values: Vec<_> = copy_codec::fields(&line)
+                        .map(String::from_utf8_lossy)
+                        .collect();
+                    self.engine.record_quasi_identifiers(
+                        &cfg.name,
+                        table.get_column_indexes(),
+                        &values,
+                    )?;
+                }
+                if let Some(m) = &mut table_manifest {
+                    m.add_row(&line);
+                }
+                if let Some(values) = &mut max_values {
+                    values.add_row(&line);
+                }
+                let line = match &mut split_rows {
+                    Some((_, rows)) => rows.split(line)?,
+                    None => line,
+                };
+                bytes += line.len() as u64 + 1;
+                if let Some(shuffler) = &mut shuffler {
+                    let key = shuffler.key(&line);
+                    shuffler.push(key, line)?;
+                } else {
+                    self.rows_writer().write_all(&line)?;
+                    self.rows_writer().write_all(b"\n")?;
+                }
+
+                count += 1;
+                run.progress(count, bytes);
+                check_memory(self.engine.max_memory, table, count, run.events())?;
+            }
+        }
+
+        if let Some(max) = self.engine.max_memory {
+            memory::accountant().check(max)?;
+        }
+        log_memory(table, run.events());
+        if let Some(shuffler) = shuffler {
+            if shuffler.runs() > 0 {
+                run.events().debug_msg(&format!(
+                    "[Dumping: {}] Shuffled with {} temporary files",
+                    table.get_full_name(),
+                    shuffler.runs()
+                ));
+            }
+            shuffler.finish(self.rows_writer())?;
+        }
+        self.data_writer().write_all(b"\\.\n")?;
+        if let Some((split, rows)) = split_rows {
+            rows.finish(&split, self.data_writer())?;
+        }
+        for (i, seq) in table.sequences.iter().enumerate() {
+            let query = match seq.find_action(&table.get_full_name(), &settings.sequences) {
+                Some(SequenceAction::Reset) => seq.restart_query(),
+                Some(SequenceAction::StartAt(value)) => seq.start_at_query(*value),
+                Some(SequenceAction::Preserve) => self.last_value_setval(seq, qw)?,
+                // only a part of the rows is dumped, the sequence follows them
+                None if self.sync_sequences && max_values.is_some() => {
+                    match max_values.as_ref().and_then(|v| v.get(i)) {
+                        Some(value) => seq.setval_query(value),
+                        None => seq.restart_query(),
+                    }
+                }
+                None if self.sync_sequences => self.last_value_setval(seq, qw)?,
+                None => continue,
+            };
+            self.data_writer().write_all(b"\n")?;
+            self.data_writer().write_all(query.as_bytes())?;
+            self.data_writer().write_all(b"\n")?;
+        }
+
+        Ok(TableOutput {
+            rows: count,
+            manifest: table_manifest.map(TableManifestBuilder::build),
+            outbox_events,
+            saved_bytes: table.projection.as_ref().and_then(|p| p.saved_bytes(count)),
         })
     }
+}
 
-    fn run_pg_dump(&mut self, section: &str, db_url: &str) -> Result<()> {
-        let program = &self.pg_dump_location;
-        let args = vec!["--section", section];
-        let table_args = table_args(&self.engine.settings.filter)?;
+impl<W: 'static + Write + Send> Backend for PgBackend<W> {
+    type Connection = connector::Connection;
+    type Type = postgres::types::Type;
+    type Table = PgTable;
+    type SchemaInspector = PgSchemaInspector;
 
-        let dump_output = Command::new(program)
-            .args(&self.pg_dump_args)
-            .args(&args)
-            .args(&table_args)
-            .arg(&db_url)
-            .output()?;
-        if !dump_output.status.success() {
-            eprintln!(
-                "pg_dump error. Command:\n{} {} {}\nOutput:",
-                program,
-                args.into_iter()
-                    .chain(table_args.iter().map(|s| s.as_str()))
-                    .collect::<Vec<_>>()
-                    .join(" "),
-                db_url
-            );
+    fn settings(&self) -> &Settings {
+        &self.engine.settings
+    }
 
-            io::stderr().write_all(&dump_output.stderr)?;
-            process::exit(1);
-        }
+    fn schema_inspector(&self) -> Self::SchemaInspector {
+        self.schema_inspector.clone()
+    }
 
-        self.dump_writer
-            .write_all(&dump_output.stdout)
+    fn manifest(&self) -> Option<&Arc<Mutex<Manifest>>> {
+        self.manifest.as_ref()
+    }
+
+    fn write_log(&mut self, message: String) -> Result<()> {
+        self.data_writer()
+            .write_all(format!("\n---\n--- {}\n---\n", message).as_bytes())
             .map_err(|e| e.into())
     }
+}
 
-    fn dump_table(&mut self, table: &PgTable, qw: &mut QueryWrapper) -> Result<()> {
-        let settings = self.settings();
-        let started = Instant::now();
+// Makes dump schema with any options
+impl<W: 'static + Write + Send> SchemaSection for PgBackend<W> {
+    fn emit_schema(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        self.check_safety(connection, events)?;
+        self.report_target_profile(events);
+        self.report_session_settings(connection, events)?;
+        self.check_tables(connection, events)?;
+
+        events.debug_msg("Prepare data scheme...");
+        self.run_pg_dump("pre-data", connection, events)
+    }
+}
 
-        self.write_log(format!("Dump table: {}", &table.get_full_name()))?;
+/// The tables share the dump transaction, or each table is dumped in its own transaction
+/// (with `prefer_replica_safe`, so the table can be dumped again after a conflict with recovery).
+/// With `jobs`, the workers dump the tables in the sessions with the snapshot of the dump transaction.
+pub struct PgSession<'c>(SessionKind<'c>, Option<Pool>);
 
-        self.dump_writer.write_all(b"\n")?;
-        self.dump_writer.write_all(table.query_from().as_bytes())?;
-        self.dump_writer.write_all(b"\n")?;
+enum SessionKind<'c> {
+    Shared(QueryWrapper<'c>),
+    PerTable(&'c mut connector::Connection),
+}
 
-        let cfg = settings.find_table(&table.get_names());
+impl<'c> PgSession<'c> {
+    pub fn shared(query_wrapper: QueryWrapper<'c>) -> Self {
+        Self(SessionKind::Shared(query_wrapper), None)
+    }
+}
 
-        self.indicator
-            .start_pb(table.count_of_query_to(cfg), &table.get_full_name());
+impl<W: 'static + Write + Send> TableData for PgBackend<W> {
+    type Session<'c> = PgSession<'c>;
 
-        let mut count: u64 = 0;
-        if let Some(cfg) = cfg {
-            if let Some(transformed_query) = table.transformed_query_to(Some(cfg), count) {
-                let reader = qw.copy_out(transformed_query.as_str())?;
-                for line in reader.lines() {
-                    self.indicator.inc_pb(1);
+    fn data_tables(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<Vec<(PgTable, i32)>> {
+        let settings = self.engine.settings.clone();
+        if let Some(w) = &mut self.data_writer {
+            w.write_all(DATA_PREAMBLE.as_bytes())?;
+        }
+        self.write_hooks("pre-data", &settings.hooks.pre_data)?;
+        self.write_log("Start dumping data".into())?;
+        self.check_replica(connection, events)?;
+        events.debug_msg("Fetch tables metadata...");
 
-                    let row = PgRow::from_string_row(line?, table.clone());
-                    let transformed = row.transform(&self.engine, cfg.name.as_str())?;
-                    self.dump_writer.write_all(transformed.as_bytes())?;
-                    self.dump_writer.write_all(b"\n")?;
+        let mut tables = self.schema_inspector.ordered_tables(connection);
+        report_inspection_warnings(connection, events);
+        self.project_tables(connection, &mut tables, events)?;
+        if !self.fk_order {
+            tables.iter_mut().for_each(|(_, weight)| *weight = 0);
+        }
+        let table_order = TableOrder::new(&settings);
+        table_order.sort(&mut tables);
+        let foreign_keys = self.schema_inspector.get_foreign_key_links(connection)?;
+        table_order.validate(&tables, &foreign_keys)?;
+        if let Some(directory) = &mut self.csv_directory {
+            directory.set_foreign_keys(&foreign_keys);
+        }
+        if self.fk_order {
+            report_foreign_key_cycles(&settings, &tables, &foreign_keys, events);
+        }
+        for name in table_order.unknown_names(&tables) {
+            events.warning_msg(&format!(
+                "unknown table `{}` in the table order config",
+                name
+            ));
+        }
 
-                    count += 1;
-                }
+        self.warn_unknown_sequences(&settings, &tables, events);
+        for warning in missing_preset_tables(&settings, &tables) {
+            events.warning_msg(&warning);
+        }
+        if let Some(strategy) = settings.cycle_strategy {
+            self.plan_cycles(connection, strategy, &tables, events)?;
+        }
+        Ok(tables)
+    }
+
+    fn begin_data<'c>(
+        &mut self,
+        connection: &'c mut connector::Connection,
+        tables: &mut [(PgTable, i32)],
+        summary: &mut DumpSummary,
+        events: &dyn Indicator,
+    ) -> Result<PgSession<'c>> {
+        summary.replica = self.replica.is_some();
+        let workers = self.connect_workers(connection, events)?;
+        let (mut session, snapshot) = if self.replica.is_some() && self.prefer_replica_safe {
+            if self.engine.settings.subset.is_some() {
+                return Err(anyhow!(
+                    "The `subset` config section can't be used with --prefer-replica-safe \
+                    (the subset rows are selected in the dump transaction)"
+                ));
+            }
+            if self.engine.settings.tenant.is_some() {
+                return Err(anyhow!(
+                    "The `tenant` config section can't be used with --prefer-replica-safe \
+                    (the tenant rows are selected in the dump transaction)"
+                ));
             }
+            (PgSession(SessionKind::PerTable(connection), None), None)
+        } else {
+            let mut query_wrapper = QueryWrapper::with_isolation_level(
+                &mut connection.client,
+                self.dump_isolation_level,
+            )?;
+            self.select_subset(tables, &mut query_wrapper, events)?;
+            self.select_tenant(tables, &mut query_wrapper, events)?;
+            let snapshot = match workers.is_empty()
+                || !parallel::exports_snapshot(self.dump_isolation_level)
+            {
+                true => None,
+                false => Some(parallel::export_snapshot(&mut query_wrapper)?),
+            };
+            (PgSession::shared(query_wrapper), snapshot)
+        };
+        self.set_row_limits(tables, events);
+        self.warn_skipped_chunks(&self.engine.settings, tables, events);
+        if !workers.is_empty() {
+            session.1 = Some(self.start_workers(workers, snapshot.as_deref(), tables)?);
         }
+        Ok(session)
+    }
 
-        if let Some(untransformed_query) = table.untransformed_query_to(cfg, count) {
-            let reader = qw.copy_out(untransformed_query.as_str())?;
-            for line in reader.lines() {
-                self.indicator.inc_pb(1);
+    fn placement(&self, table: &PgTable, weight: i32) -> String {
+        TableOrder::new(&self.engine.settings)
+            .placement(table, weight)
+            .0
+            .to_string()
+    }
+
+    fn skip_reason(&self, table: &PgTable) -> Option<String> {
+        let settings = &self.engine.settings;
+        if !crate::filter_table(&table.get_full_name(), &settings.filter) {
+            Some("".to_string())
+        } else if let Some(extension) = table
+            .extension
+            .as_ref()
+            .filter(|e| !self.includes_extension_tables(e, settings))
+        {
+            Some(format!(" (owned by the {} extension)", extension))
+        } else if let Some(hypertable) = table
+            .hypertable
+            .as_ref()
+            .filter(|_| !self.includes_extension_tables(TIMESCALEDB_EXTENSION, settings))
+        {
+            Some(format!(
+                " (a chunk of the {} hypertable, the {} extension tables are skipped)",
+                hypertable, TIMESCALEDB_EXTENSION
+            ))
+        } else if let Some(reason) = partition_skip_reason(settings.partitions, table) {
+            Some(reason)
+        } else if settings.tenant.as_ref().map(|t| tenant::scoping(t, table))
+            == Some(Scoping::Other(TenantScope::Exclude))
+        {
+            Some(" (excluded from the tenant dump)".to_string())
+        } else if let Some(arg) = self.pg_dump_table_args.excluded_by(table) {
+            Some(format!(" (pg_dump arguments: {})", arg))
+        } else {
+            self.skip_data(table).map(|_| " (skip_data)".to_string())
+        }
+    }
 
-                self.dump_writer.write_all(line?.as_bytes())?;
-                self.dump_writer.write_all(b"\n")?;
+    fn skip_table(
+        &mut self,
+        session: &mut PgSession<'_>,
+        table: &PgTable,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        let reset_sequences = match self.skip_data(table) {
+            Some(reset_sequences) => reset_sequences,
+            None => return Ok(()),
+        };
+        events.table_skipped(&table.get_full_name(), "skip_data");
+        let settings = self.engine.settings.clone();
+        for seq in &table.sequences {
+            let query = match seq.find_action(&table.get_full_name(), &settings.sequences) {
+                Some(SequenceAction::Reset) => seq.restart_query(),
+                Some(SequenceAction::StartAt(value)) => seq.start_at_query(*value),
+                Some(SequenceAction::Preserve) => match &mut session.0 {
+                    SessionKind::Shared(qw) => self.last_value_setval(seq, qw)?,
+                    SessionKind::PerTable(connection) => {
+                        let mut qw = QueryWrapper::with_isolation_level(
+                            &mut connection.client,
+                            self.dump_isolation_level,
+                        )?;
+                        self.last_value_setval(seq, &mut qw)?
+                    }
+                },
+                None if reset_sequences => seq.restart_query(),
+                None => continue,
+            };
+            self.data_writer().write_all(b"\n")?;
+            self.data_writer().write_all(query.as_bytes())?;
+            self.data_writer().write_all(b"\n")?;
+        }
 
-                count += 1;
+        Ok(())
+    }
+
+    fn table_resumed(&mut self, table: &PgTable) {
+        let name = table.get_full_name();
+        if self.skip_reason(table).is_none() {
+            if let Some(plan) = &mut self.cycle_plan {
+                plan.add_dumped(&name);
             }
+            self.dumped_tables.insert(name.clone());
         }
+        self.resumed_tables.insert(name);
+    }
 
-        self.dump_writer.write_all(b"\\.\n")?;
-        for seq in &table.sequences {
-            let last_value: i64 = qw.query_one(seq.last_value_query().as_str(), &[])?.get(0);
-            self.dump_writer.write_all(b"\n")?;
-            self.dump_writer
-                .write_all(seq.setval_query(last_value).as_bytes())?;
-            self.dump_writer.write_all(b"\n")?;
+    fn table_info(&self, table: &PgTable) -> TableInfo {
+        TableInfo {
+            name: table.get_full_name(),
+            rows: table.count_of_query_to(self.engine.settings.find_table(&table.get_names())),
+        }
+    }
+
+    fn stream_table(
+        &mut self,
+        session: &mut PgSession<'_>,
+        table: &PgTable,
+        run: &mut TableRun<'_>,
+    ) -> Result<TableOutput> {
+        let output = match session {
+            PgSession(_, Some(pool)) if pool.contains(&table.get_full_name()) => {
+                let segment = pool.wait(&table.get_full_name(), run.events())?;
+                self.write_segment(segment, run)
+            }
+            PgSession(SessionKind::Shared(query_wrapper), _) => {
+                self.dump_table(table, query_wrapper, run)
+            }
+            PgSession(SessionKind::PerTable(connection), _) => {
+                self.dump_table_with_retries(connection, table, run)
+            }
+        }?;
+        self.dumped_tables.insert(table.get_full_name());
+        if let Some(plan) = &mut self.cycle_plan {
+            plan.add_dumped(&table.get_full_name());
         }
+        Ok(output)
+    }
 
-        let finished = started.elapsed();
-        self.indicator
-            .finish_pb(table.get_full_name().as_str(), finished);
+    fn is_missing_table(&self, error: &anyhow::Error) -> bool {
+        missing_objects::is_missing_table(error)
+    }
 
+    // The table can't be skipped if its query has failed inside the transaction shared by all tables
+    // (the transaction is aborted then, the other tables can't be dumped in it)
+    fn check_skip(&self, error: &anyhow::Error) -> Result<()> {
+        let shared_transaction = self.dump_isolation_level.is_some()
+            && !(self.replica.is_some() && self.prefer_replica_safe);
+        if shared_transaction && !error.is::<missing_objects::MissingTable>() {
+            return Err(anyhow!(
+                "{}: the table is dropped while the dump transaction is running, the transaction is aborted \
+                and the dump can't be continued (the table can be skipped with the `NoTransaction` mode only)",
+                error
+            ));
+        }
         Ok(())
     }
+
+    fn end_tables(&mut self, events: &dyn Indicator) -> Result<()> {
+        if let Some(directory) = &self.csv_directory {
+            directory.finish()?;
+        }
+        if let Some(plan) = &self.cycle_plan {
+            let sql = plan.end_sql()?;
+            self.data_writer().write_all(sql.as_bytes())?;
+        }
+        self.report_rule_timing(events);
+        self.report_privacy_budget(events);
+        self.report_sql_rules(events);
+        self.report_anonymity(events)
+    }
+
+    fn end_data(
+        &mut self,
+        result: Result<()>,
+        summary: &mut DumpSummary,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        summary.conflict_retries = self.conflict_retries;
+        summary.memory = memory::accountant().usage();
+
+        match &self.replica {
+            Some(replica) => {
+                events.debug_msg(&format!(
+                    "The source database is a replica, conflict retries: {}",
+                    self.conflict_retries
+                ));
+                result.map_err(|e| {
+                    if replica::is_recovery_conflict(&e) {
+                        anyhow!(
+                            "The query is cancelled by a conflict with recovery on the replica ({}): {}",
+                            e,
+                            replica.remediation()
+                        )
+                    } else {
+                        e
+                    }
+                })
+            }
+            None => result,
+        }
+    }
 }
 
-impl<W: 'static + Write + Send, I: 'static + Indicator + Send> Dumper for PgDumper<W, I> {
-    type Table = PgTable;
-    type Connection = connector::Connection;
-    type SchemaInspector = PgSchemaInspector;
+// Makes dump foreign keys, indices and other...
+impl<W: 'static + Write + Send> Epilogue for PgBackend<W> {
+    fn emit_epilogue(
+        &mut self,
+        connection: &mut connector::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()> {
+        events.debug_msg("Finishing with indexes...");
+        let post_data = self.engine.settings.hooks.post_data.clone();
+        // after the marker with the CSV output (the data is loaded before it)
+        if self.csv_directory.is_none() {
+            self.write_hooks("post-data", &post_data)?;
+        }
+        if self.data_writer.is_some() || self.csv_directory.is_some() {
+            self.dump_writer
+                .write_all(format!("\n{}\n", POST_DATA_MARKER).as_bytes())?;
+        }
+        if self.csv_directory.is_some() {
+            self.write_hooks("post-data", &post_data)?;
+        }
+        self.run_pg_dump("post-data", connection, events)?;
+        self.write_view_refreshes(connection, events)
+    }
+}
 
-    // Stage before dumping data. It makes dump schema with any options
-    fn pre_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
-        self.debug("Prepare data scheme...".into());
-        self.run_pg_dump("pre-data", connection.url.as_str())
+// The rows of the partitions are dumped once: with the root partitioned table or with the partitions
+fn partition_skip_reason(partitions: Partitions, table: &PgTable) -> Option<String> {
+    match partitions {
+        Partitions::Parent => table
+            .partition_of
+            .as_ref()
+            .map(|root| format!(" (a partition of {}, dumped with it)", root)),
+        Partitions::Children => table
+            .partitioned
+            .then(|| " (a partitioned table, its partitions are dumped)".to_string()),
     }
+}
 
-    // This stage makes dump data only
-    fn data(&mut self, connection: &mut Self::Connection) -> Result<()> {
-        let settings = self.settings();
-        self.write_log("Start dumping data".into())?;
-        self.debug("Fetch tables metadata...".into());
+// The presets cover the typical sync tables, a database usually has only some of them (it isn't an error)
+fn missing_preset_tables(settings: &Settings, tables: &[(PgTable, i32)]) -> Vec<String> {
+    settings
+        .preset_tables()
+        .into_iter()
+        .filter_map(|(preset, names)| {
+            let missing: Vec<_> = names
+                .iter()
+                .filter(|name| !tables.iter().any(|(t, _)| t.get_name() == **name))
+                .copied()
+                .collect();
+            if missing.is_empty() {
+                None
+            } else if missing.len() == names.len() {
+                Some(format!(
+                    "none of the tables of the preset `{}` are found ({})",
+                    preset,
+                    missing.join(", ")
+                ))
+            } else {
+                Some(format!(
+                    "the tables of the preset `{}` are not found: {}",
+                    preset,
+                    missing.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
 
-        let mut tables = self.schema_inspector().ordered_tables(connection);
-        sort_tables(
-            &mut tables,
-            settings.table_order.as_ref().unwrap_or(&vec![]),
-        );
+// Tables with rules (except `none` and the skipped rules) and their transformed columns
+fn tables_with_rules(
+    tables: Vec<(PgTable, TableCfg)>,
+    skipped_rules: &HashMap<String, HashSet<String>>,
+) -> Vec<(PgTable, Vec<String>)> {
+    tables
+        .into_iter()
+        .filter_map(|(table, cfg)| {
+            let skipped = skipped_rules.get(&table.get_full_name());
+            let mut columns: Vec<_> = cfg
+                .rules
+                .iter()
+                .filter(|(_, rule)| !matches!(rule, Transformers::None(_)))
+                .filter(|(column, _)| skipped.is_none_or(|s| !s.contains(*column)))
+                .map(|(column, _)| column.clone())
+                .collect();
+            columns.sort_unstable();
+            (!columns.is_empty()).then_some((table, columns))
+        })
+        .collect()
+}
 
-        let all_tables_count = tables.len();
+// The tables of the foreign key cycles can't be dumped parent-first,
+// the multi-table cycles are only restored with `cycle_strategy` (or with the deferred constraints)
+fn report_foreign_key_cycles(
+    settings: &Settings,
+    tables: &[(PgTable, i32)],
+    foreign_keys: &[ForeignKeyLink],
+    events: &dyn Indicator,
+) {
+    let names: HashSet<String> = tables.iter().map(|(t, _)| t.get_full_name()).collect();
+    let links: Vec<_> = foreign_keys
+        .iter()
+        .filter(|fk| names.contains(&fk.table) && names.contains(&fk.referenced_table))
+        .cloned()
+        .collect();
 
-        let mut query_wrapper =
-            QueryWrapper::with_isolation_level(&mut connection.client, self.dump_isolation_level)?;
-        for (ind, (table, _weight)) in tables.iter().enumerate() {
-            self.debug(format!(
-                "[{} / {}] Prepare to dump table: {}",
-                ind + 1,
-                all_tables_count,
-                table.get_full_name(),
+    for cycle in table_order::foreign_key_cycles(&links) {
+        let list = cycle.join("`, `");
+        if cycle.len() == 1 {
+            events.debug_msg(&format!(
+                "The table `{}` references itself (its rows are dumped in the table order)",
+                list
+            ));
+        } else if settings.cycle_strategy.is_none() {
+            events.warning_msg(&format!(
+                "the tables `{}` reference each other by foreign keys, so some of them are dumped \
+                before the tables they reference (see the `cycle_strategy` config option)",
+                list
             ));
+        } else {
+            events.debug_msg(&format!(
+                "The tables `{}` reference each other by foreign keys (restored with `cycle_strategy`)",
+                list
+            ));
+        }
+    }
+}
 
-            if self.filter_table(table.get_full_name(), &settings.filter) {
-                self.dump_table(table, &mut query_wrapper)?;
-            } else {
-                self.debug(format!("[Dumping: {}] --- SKIP ---", table.get_full_name()));
+// The problems of the schema inspection (e.g., the tables that can't be sized) don't fail the dump
+fn report_inspection_warnings(connection: &mut connector::Connection, events: &dyn Indicator) {
+    for warning in connection.take_warnings() {
+        events.warning_msg(&warning);
+    }
+}
+
+// The expressions of the `sql` rules are checked by the database before dumping
+fn check_sql_rules(
+    connection: &mut connector::Connection,
+    tables: &[(PgTable, TableCfg)],
+) -> Result<()> {
+    for (table, cfg) in tables {
+        if let Some(query) = table.sql_rules_query(cfg) {
+            if let Err(e) = connection
+                .client
+                .batch_execute(&format!("EXPLAIN {}", query))
+            {
+                return Err(anyhow!(
+                    "Invalid `sql` rules of the table {}: {}",
+                    table.get_full_name(),
+                    e.as_db_error()
+                        .map_or(e.to_string(), |e| e.message().to_string())
+                ));
             }
         }
+    }
+    Ok(())
+}
 
-        self.write_log("End dumping data".into())?;
-        Ok(())
+// The estimates of the full dump of the table for the projection (`--stats-only`)
+fn table_estimate(table: &PgTable, cfg: Option<&TableCfg>) -> TableEstimate {
+    let rules = cfg
+        .map(|c| c.rules.values().collect::<Vec<_>>())
+        .unwrap_or_default();
+    TableEstimate {
+        rows: table.count_of_query_to(cfg),
+        columns: table.get_columns().len() as u64,
+        uniq_columns: rules.iter().filter(|rule| rule.is_uniq()).count() as u64,
+        cache_entries: rules
+            .iter()
+            .filter_map(|rule| match rule {
+                Transformers::Cache(cache) => Some(cache.max_entries as u64),
+                _ => None,
+            })
+            .sum(),
+        shuffle_memory: cfg
+            .and_then(|c| c.shuffle_rows.as_ref())
+            .filter(|s| s.method == ShuffleMethod::Client)
+            .and_then(|s| s.memory_limit_bytes().ok()),
     }
+}
 
-    // This stage makes dump foreign keys, indices and other...
-    fn post_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
-        self.debug("Finishing with indexes...".into());
-        self.run_pg_dump("post-data", connection.url.as_str())
+// The `query` conditions are checked by the database before dumping, the planner estimates the rows
+// that match the dump conditions (by the full table names)
+fn check_query_conditions(
+    connection: &mut connector::Connection,
+    tables: &[(PgTable, TableCfg)],
+) -> Result<HashMap<String, u64>> {
+    let mut estimates = HashMap::new();
+    for (table, cfg) in tables {
+        let query = match &cfg.query {
+            Some(query) => query,
+            None => continue,
+        };
+        for (key, condition) in [
+            ("dump_condition", &query.dump_condition),
+            ("transform_condition", &query.transform_condition),
+        ] {
+            let condition = match condition {
+                Some(condition) => condition,
+                None => continue,
+            };
+            let plan = connection
+                .client
+                .query(
+                    format!(
+                        "EXPLAIN SELECT * FROM {} WHERE ({})",
+                        table.quoted_full_name(),
+                        condition
+                    )
+                    .as_str(),
+                    &[],
+                )
+                .map_err(|e| {
+                    anyhow!(
+                        "Invalid `query.{}` of the table {}: {}",
+                        key,
+                        table.get_full_name(),
+                        e.as_db_error()
+                            .map_or(e.to_string(), |e| e.message().to_string())
+                    )
+                })?;
+            let rows = plan.first().and_then(|row| planned_rows(row.get(0)));
+            if let (Some(rows), "dump_condition") = (rows, key) {
+                estimates.insert(table.get_full_name(), rows);
+            }
+        }
     }
+    Ok(estimates)
+}
 
-    fn schema_inspector(&self) -> Self::SchemaInspector {
-        self.schema_inspector.clone()
+// The rows of the top plan node, e.g. `Seq Scan on events  (cost=0.00..35.50 rows=850 width=44)`
+fn planned_rows(plan: &str) -> Option<u64> {
+    plan.split(" rows=")
+        .nth(1)?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+// Fails if the accounted memory exceeds `--max-memory` (the components that can spill have spilled by then),
+// the memory of the components is logged on the failures and periodically
+fn check_memory(
+    max_memory: Option<u64>,
+    table: &PgTable,
+    rows: u64,
+    events: &dyn Indicator,
+) -> Result<()> {
+    if rows.is_multiple_of(MEMORY_LOG_ROWS) {
+        log_memory(table, events);
+    }
+    match max_memory {
+        Some(max) if rows.is_multiple_of(MEMORY_CHECK_ROWS) => {
+            memory::accountant().check(max).map_err(|e| {
+                log_memory(table, events);
+                e.into()
+            })
+        }
+        _ => Ok(()),
     }
+}
 
-    fn settings(&mut self) -> Settings {
-        self.engine.settings.clone()
+fn log_memory(table: &PgTable, events: &dyn Indicator) {
+    let usage = memory::accountant().usage();
+    if usage.peak > 0 {
+        events.debug_msg(&format!(
+            "[Dumping: {}] Memory: {}",
+            table.get_full_name(),
+            usage
+        ));
     }
+}
 
-    fn write_log(&mut self, message: String) -> Result<()> {
-        self.dump_writer
-            .write_all(format!("\n---\n--- {}\n---\n", message).as_bytes())
-            .map_err(|e| e.into())
+// The columns whose types are not supported by their rules (with the warnings)
+pub(crate) fn unsupported_rules(table: &PgTable, cfg: &TableCfg) -> Vec<(String, String)> {
+    let mut result = vec![];
+    for column in table.get_columns() {
+        let rule = match cfg.rules.get(&column.name) {
+            Some(rule) if !cfg.force.contains(&column.name) => rule,
+            _ => continue,
+        };
+        let supported = rule.supported_types();
+        if TypeClass::is_compatible(supported, &column.data_type) {
+            continue;
+        }
+
+        let classes: Vec<_> = supported
+            .unwrap_or_default()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let warning = format!(
+            "The rule `{}` is skipped for {}.{}: the column type `{}` is not supported \
+            (the rule generates {} values, use `force: [{}]` to apply it anyway)",
+            rule.name(),
+            table.get_full_name(),
+            column.name,
+            column.data_type,
+            classes.join(" or "),
+            column.name
+        );
+        result.push((column.name, warning));
+    }
+    result.sort();
+    result
+}
+
+// The generated values must fit the columns (only the rules with known maximum lengths are checked)
+fn check_column_lengths(tables: &[(PgTable, TableCfg)]) -> Result<()> {
+    let mut failures = vec![];
+    for (table, cfg) in tables {
+        let rows = table.count_of_query_to(Some(cfg));
+        for column in table.get_columns() {
+            let max_length = match cfg.rules.get(&column.name) {
+                Some(Transformers::Email(t)) => t.max_length(rows),
+                Some(Transformers::Redact(t)) => t.max_length(),
+                _ => None,
+            };
+            if let (Some(max_length), Some(column_length)) = (max_length, column.max_length) {
+                if max_length > column_length as usize {
+                    failures.push(format!(
+                        "{}.{}: up to {} characters, the column length is {}",
+                        table.get_full_name(),
+                        column.name,
+                        max_length,
+                        column_length
+                    ));
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "Generated values don't fit the columns:\n  {}",
+            failures.join("\n  ")
+        ));
     }
+    Ok(())
+}
+
+fn manifest_builder(
+    table: &PgTable,
+    cfg: Option<&TableCfg>,
+    skipped: &HashSet<String>,
+    secret_detection: &SecretDetection,
+) -> TableManifestBuilder {
+    let column_indexes = table.get_column_indexes();
+    let mut columns: Vec<_> = cfg
+        .map(|cfg| {
+            cfg.rules
+                .iter()
+                .filter(|(name, _)| !skipped.contains(*name))
+                .filter_map(|(name, rule)| {
+                    column_indexes
+                        .get(name)
+                        .map(|i| (name.clone(), *i, rule.name().to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    columns.sort();
+
+    let ignored: BTreeMap<_, _> = cfg
+        .map(|cfg| cfg.ignore.clone().into_iter().collect())
+        .unwrap_or_default();
+    // the `none` rules dump the values as is
+    let coverage = Coverage::new(
+        &table.get_columns_names(),
+        columns
+            .iter()
+            .filter(|(_, _, rule)| rule != "none")
+            .map(|(name, _, _)| name),
+        ignored.keys(),
+    );
+
+    // the passed through text columns are scanned for secrets
+    let scanned: Vec<_> = table
+        .columns
+        .iter()
+        .filter(|c| {
+            secret_scan::TEXT_TYPES.contains(&c.data_type.as_str())
+                && coverage.passed_through.contains(&c.name)
+                && !secret_detection.is_allowed(&table.get_names(), &c.name)
+        })
+        .filter_map(|c| Some((c.name.clone(), *column_indexes.get(&c.name)?)))
+        .collect();
+
+    let learned_formats = cfg
+        .map(|cfg| {
+            cfg.rules
+                .iter()
+                .filter(|(name, _)| !skipped.contains(*name))
+                .filter_map(|(name, rule)| {
+                    let format = rule.learn_format_rules().first()?.learned()?;
+                    Some((name.clone(), format))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
 
-    fn debug(&self, message: String) {
-        self.indicator.debug_msg(message.as_str());
+    let builder = TableManifestBuilder::new(columns)
+        .with_ignored(ignored)
+        .with_coverage(coverage)
+        .with_learned_formats(learned_formats);
+    if scanned.is_empty() || !secret_detection.is_enabled() {
+        return builder;
     }
+    let (names, indexes) = scanned.into_iter().unzip();
+    builder.with_secret_scan(SecretScan::new(secret_detection, names), indexes)
+}
+
+// Recent `pg_dump` versions emit `\restrict` and `\unrestrict` commands with random keys, they are skipped
+fn schema_checksum(output: &[u8]) -> String {
+    let lines: Vec<_> = output
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.starts_with(b"\\restrict ") && !line.starts_with(b"\\unrestrict "))
+        .collect();
+    manifest::checksum(&lines.join(&b'\n'))
 }
 
 fn table_args(filter: &Option<Filter>) -> Result<Vec<String>> {
@@ -223,16 +2638,231 @@ fn table_args(filter: &Option<Filter>) -> Result<Vec<String>> {
     Ok(args)
 }
 
-fn sort_tables(tables: &mut Vec<(PgTable, i32)>, order: &[String]) {
-    tables.sort_by_cached_key(|(tbl, weight)| {
-        let position = order.iter().position(|i| tbl.get_names().contains(i));
-        (position, -weight)
-    });
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_tables;
+
+    #[test]
+    fn test_schema_checksum() {
+        let dump = |key: &str| {
+            format!(
+                "--\n\\restrict {}\nCREATE TABLE t();\n\\unrestrict {}\n",
+                key, key
+            )
+        };
+        assert_eq!(
+            schema_checksum(dump("abc").as_bytes()),
+            schema_checksum(dump("def").as_bytes())
+        );
+        assert_ne!(
+            schema_checksum(dump("abc").as_bytes()),
+            schema_checksum(b"--\nCREATE TABLE t2();\n")
+        );
+    }
+
+    #[test]
+    fn test_skip_extension_tables() {
+        let dumper = |config: &str, include: bool| {
+            let settings = Settings::from_yaml(config).unwrap();
+            PgDumper::new(
+                Engine::new(settings),
+                None,
+                "pg_dump".to_string(),
+                io::sink(),
+                crate::indicator::SilentIndicator,
+                vec![],
+            )
+            .unwrap()
+            .include_extension_tables(include)
+        };
+        let mut extension_table = PgTable::new("spatial_ref_sys".to_string(), "public".to_string());
+        extension_table.extension = Some("postgis".to_string());
+        let mut chunk = PgTable::new("_hyper_1_1_chunk".to_string(), "_ts".to_string());
+        chunk.hypertable = Some("public.metrics".to_string());
+
+        let mut d = dumper("tables: []", false);
+        assert_eq!(
+            d.backend_mut().skip_reason(&extension_table),
+            Some(" (owned by the postgis extension)".to_string())
+        );
+        assert_eq!(
+            d.backend_mut().skip_reason(&chunk),
+            Some(
+                " (a chunk of the public.metrics hypertable, the timescaledb extension tables are skipped)"
+                    .to_string()
+            )
+        );
+
+        let mut d = dumper("tables: []", true);
+        assert_eq!(d.backend_mut().skip_reason(&extension_table), None);
+        assert_eq!(d.backend_mut().skip_reason(&chunk), None);
+
+        let mut d = dumper(
+            "{tables: [], extensions: {postgis: skip, timescaledb: include}}",
+            false,
+        );
+        assert!(d.backend_mut().skip_reason(&extension_table).is_some());
+        assert_eq!(d.backend_mut().skip_reason(&chunk), None);
+    }
+
+    #[test]
+    fn test_partition_skip_reason() {
+        let mut parent = PgTable::new("events".to_string(), "public".to_string());
+        parent.partitioned = true;
+        let mut partition = PgTable::new("events_2023_01".to_string(), "public".to_string());
+        partition.partition_of = Some("public.events".to_string());
+        let table = PgTable::new("users".to_string(), "public".to_string());
+
+        assert_eq!(partition_skip_reason(Partitions::Parent, &parent), None);
+        assert_eq!(
+            partition_skip_reason(Partitions::Parent, &partition),
+            Some(" (a partition of public.events, dumped with it)".to_string())
+        );
+        assert_eq!(
+            partition_skip_reason(Partitions::Children, &parent),
+            Some(" (a partitioned table, its partitions are dumped)".to_string())
+        );
+        assert_eq!(
+            partition_skip_reason(Partitions::Children, &partition),
+            None
+        );
+        assert_eq!(partition_skip_reason(Partitions::Parent, &table), None);
+        assert_eq!(partition_skip_reason(Partitions::Children, &table), None);
+    }
+
+    #[test]
+    fn test_missing_preset_tables() {
+        let settings = Settings::from_yaml(
+            "{tables: [], preset: [stripe_sync, zendesk_sync, salesforce_sync]}",
+        )
+        .unwrap();
+        let tables: Vec<_> = [
+            ("stripe_customers", "public"),
+            ("stripe_charges", "stripe"),
+            ("zendesk_users", "public"),
+            ("zendesk_tickets", "public"),
+            ("zendesk_ticket_comments", "public"),
+        ]
+        .iter()
+        .map(|(name, schema)| (PgTable::new(name.to_string(), schema.to_string()), 0))
+        .collect();
+
+        assert_eq!(
+            missing_preset_tables(&settings, &tables),
+            vec![
+                "the tables of the preset `stripe_sync` are not found: stripe_payment_methods",
+                "none of the tables of the preset `salesforce_sync` are found (sf_contacts, sf_leads, sf_accounts)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_column_lengths() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    email:
+                      domain: test.example.dev
+                      local_part: sequential
+                  backup_email:
+                    email: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let cfg = settings.tables[0].clone();
+        let tables = |length: i32| {
+            let mut table = PgTable::new("users".to_string(), "public".to_string());
+            let mut columns = test_tables::pg_columns(&[("email", ""), ("backup_email", "")]);
+            for column in &mut columns {
+                column.max_length = Some(length);
+            }
+            table.set_columns(columns);
+            vec![(table, cfg.clone())]
+        };
+
+        assert!(check_column_lengths(&tables(27)).is_ok());
+        assert_eq!(
+            check_column_lengths(&tables(26)).unwrap_err().to_string(),
+            "Generated values don't fit the columns:\n  public.users.email: up to 27 characters, the column length is 26"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    email: {}
+                  email_id:
+                    email: {}
+                  login:
+                    email: {}
+                  legacy_email:
+                    template:
+                      format: "{{ _1 }}"
+              - name: legacy_users
+                force: [email]
+                rules:
+                  email:
+                    email: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let table = |name: &str| {
+            let columns = [
+                ("email", "bytea"),
+                ("email_id", "bigint"),
+                ("login", "character varying"),
+                ("legacy_email", "bytea"),
+            ];
+            test_tables::pg_table("public", name, &columns)
+        };
+
+        assert_eq!(
+            unsupported_rules(&table("users"), &settings.tables[0]),
+            vec![
+                (
+                    "email".to_string(),
+                    "The rule `email` is skipped for public.users.email: the column type `bytea` is not supported \
+                    (the rule generates text values, use `force: [email]` to apply it anyway)"
+                        .to_string()
+                ),
+                (
+                    "email_id".to_string(),
+                    "The rule `email` is skipped for public.users.email_id: the column type `bigint` is not supported \
+                    (the rule generates text values, use `force: [email_id]` to apply it anyway)"
+                        .to_string()
+                )
+            ]
+        );
+        assert!(unsupported_rules(&table("legacy_users"), &settings.tables[1]).is_empty());
+
+        let skipped = HashMap::from([(
+            "public.users".to_string(),
+            HashSet::from(["email".to_string(), "email_id".to_string()]),
+        )]);
+        let tables =
+            tables_with_rules(vec![(table("users"), settings.tables[0].clone())], &skipped);
+        assert_eq!(tables[0].1, vec!["legacy_email", "login"]);
+    }
+
+    #[test]
+    fn test_planned_rows() {
+        assert_eq!(
+            planned_rows("Seq Scan on events  (cost=0.00..35.50 rows=850 width=44)"),
+            Some(850)
+        );
+        assert_eq!(
+            planned_rows(
+                "Index Scan using events_pkey on events  (cost=0.15..8.17 rows=1 width=44)"
+            ),
+            Some(1)
+        );
+        assert_eq!(planned_rows("  Filter: (created_at > now())"), None);
+    }
 
     #[test]
     fn test_table_args() {
@@ -271,36 +2901,4 @@ fn test_table_args() {
             ]
         );
     }
-
-    #[test]
-    fn test_sort_tables() {
-        let order = vec!["table2".to_string(), "public.table1".to_string()];
-
-        let mut tables = vec![
-            (PgTable::new("table1".to_string(), "public".to_string()), 0),
-            (PgTable::new("table2".to_string(), "public".to_string()), 1),
-            (PgTable::new("table3".to_string(), "public".to_string()), 2),
-            (PgTable::new("table4".to_string(), "public".to_string()), 3),
-            (PgTable::new("table1".to_string(), "other".to_string()), 4),
-            (PgTable::new("table2".to_string(), "other".to_string()), 5),
-        ];
-
-        sort_tables(&mut tables, &order);
-
-        let ordered_names: Vec<_> = tables
-            .iter()
-            .map(|(t, w)| (t.get_full_name(), *w))
-            .collect();
-        assert_eq!(
-            ordered_names,
-            vec![
-                ("other.table1".to_string(), 4),
-                ("public.table4".to_string(), 3),
-                ("public.table3".to_string(), 2),
-                ("other.table2".to_string(), 5),
-                ("public.table2".to_string(), 1),
-                ("public.table1".to_string(), 0),
-            ]
-        )
-    }
 }
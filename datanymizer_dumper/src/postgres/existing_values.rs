@@ -0,0 +1,296 @@
+use super::{connector::Connection, table::PgTable};
+use crate::{indicator::Indicator, Table};
+use anyhow::{anyhow, Context, Result};
+use datanymizer_engine::{
+    transformers::SkipIfTransformer, Engine, Settings, Transformers, UniqueAgainst,
+};
+use postgres::fallible_iterator::FallibleIterator;
+
+/// The scanned values are reported after every this count
+const PROGRESS_STEP: u64 = 1_000_000;
+
+/// A column with the existing values for the uniqueness of a rule (`unique_against` or the values
+/// that are kept by `skip_if`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// The config table and the column of the unique rule
+    pub rule: (String, String),
+    /// The full name of the table with the existing values
+    pub table: String,
+    quoted_table: String,
+    pub column: String,
+    pub max_values: u64,
+    /// The `skip_if` rule of the column: only its kept values are registered
+    pub keep: Option<SkipIfTransformer<Transformers>>,
+}
+
+impl Target {
+    fn rule_name(&self) -> String {
+        format!("{}.{}", self.rule.0, self.rule.1)
+    }
+
+    fn query(&self) -> String {
+        let column = format!("\"{}\"", self.column.replace('"', "\"\""));
+        format!(
+            "SELECT DISTINCT {}::text FROM {} WHERE {} IS NOT NULL",
+            column, self.quoted_table, column
+        )
+    }
+}
+
+/// Resolves the tables and the columns of the `unique_against` options of the rules and the columns
+/// of the `skip_if` rules with unique inner rules (the tables are found by the full or the short names)
+pub fn targets(settings: &Settings, tables: &[PgTable]) -> Result<Vec<Target>> {
+    let mut result = vec![];
+    for (cfg_table, column, rule) in settings.resolved_rules() {
+        for against in rule.unique_against() {
+            result.push(target(cfg_table, column, against, tables)?);
+        }
+        if let Some(keep) = rule.kept_values_rule() {
+            result.extend(kept_values_target(cfg_table, column, keep, tables));
+        }
+    }
+    Ok(result)
+}
+
+fn find_table<'a>(tables: &'a [PgTable], name: &str) -> Option<&'a PgTable> {
+    tables
+        .iter()
+        .find(|t| t.get_full_name() == name)
+        .or_else(|| tables.iter().find(|t| t.get_name() == name))
+}
+
+// The unknown tables and columns of the config are reported by the dumper checks
+fn kept_values_target(
+    cfg_table: &str,
+    column: &str,
+    keep: &SkipIfTransformer<Transformers>,
+    tables: &[PgTable],
+) -> Option<Target> {
+    let table = find_table(tables, cfg_table)?;
+    if !table.columns.iter().any(|c| c.name == column) {
+        return None;
+    }
+
+    Some(Target {
+        rule: (cfg_table.to_string(), column.to_string()),
+        table: table.get_full_name(),
+        quoted_table: table.quoted_full_name(),
+        column: column.to_string(),
+        max_values: u64::MAX,
+        keep: Some(keep.clone()),
+    })
+}
+
+fn target(
+    cfg_table: &str,
+    column: &str,
+    against: &UniqueAgainst,
+    tables: &[PgTable],
+) -> Result<Target> {
+    let table = find_table(tables, &against.table).ok_or_else(|| {
+        anyhow!(
+            "The table `{}` of `unique_against` of the rule `{}.{}` isn't found",
+            against.table,
+            cfg_table,
+            column
+        )
+    })?;
+    if !table.columns.iter().any(|c| c.name == against.column) {
+        return Err(anyhow!(
+            "The table {} has no column `{}` (`unique_against` of the rule `{}.{}`)",
+            table.get_full_name(),
+            against.column,
+            cfg_table,
+            column
+        ));
+    }
+
+    Ok(Target {
+        rule: (cfg_table.to_string(), column.to_string()),
+        table: table.get_full_name(),
+        quoted_table: table.quoted_full_name(),
+        column: against.column.clone(),
+        max_values: against.max_values,
+        keep: None,
+    })
+}
+
+/// Registers the distinct existing values of the columns in the uniqueness sets of the rules
+/// before the dump, so the generated values never collide with the retained ones (including the values
+/// that are kept by `skip_if`). The values are streamed, only their hashes are kept.
+pub fn register(
+    connection: &mut Connection,
+    engine: &Engine,
+    targets: &[Target],
+    events: &dyn Indicator,
+) -> Result<()> {
+    for target in targets {
+        let count = register_target(connection, engine, target, events)?;
+        events.debug_msg(&format!(
+            "{} {} values of `{}.{}` are registered for the uniqueness of `{}`",
+            count,
+            if target.keep.is_some() {
+                "kept"
+            } else {
+                "existing"
+            },
+            target.table,
+            target.column,
+            target.rule_name()
+        ));
+    }
+    Ok(())
+}
+
+fn register_target(
+    connection: &mut Connection,
+    engine: &Engine,
+    target: &Target,
+    events: &dyn Indicator,
+) -> Result<u64> {
+    let context = || {
+        format!(
+            "Can't scan the existing values of {}.{} for the uniqueness of `{}`",
+            target.table,
+            target.column,
+            target.rule_name()
+        )
+    };
+    let mut rows = connection
+        .client
+        .query_raw(target.query().as_str(), std::iter::empty::<&str>())
+        .with_context(context)?;
+
+    let mut count = 0;
+    while let Some(row) = rows.next().with_context(context)? {
+        let value: &str = row.get(0);
+        if let Some(keep) = &target.keep {
+            if keep.matches(value) {
+                engine.register_kept_value(&target.rule.0, &target.rule.1, value);
+                count += 1;
+            }
+            continue;
+        }
+        if count == target.max_values {
+            return Err(anyhow!(
+                "The column `{}.{}` has more than {} distinct values for the uniqueness of `{}` \
+                 (`unique_against`), raise its `max_values`",
+                target.table,
+                target.column,
+                target.max_values,
+                target.rule_name()
+            ));
+        }
+        engine.register_existing_value(&target.rule.0, &target.rule.1, value);
+        count += 1;
+        if count.is_multiple_of(PROGRESS_STEP) {
+            events.debug_msg(&format!(
+                "Scanning the existing values of `{}.{}` for the uniqueness of `{}`: {} values",
+                target.table,
+                target.column,
+                target.rule_name(),
+                count
+            ));
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+
+    fn table(schema: &str, name: &str, columns: &[&str]) -> PgTable {
+        let columns: Vec<_> = columns.iter().map(|&c| (c, "")).collect();
+        test_tables::pg_table(schema, name, &columns)
+    }
+
+    fn settings(table: &str, column: &str) -> Settings {
+        Settings::from_yaml(&format!(
+            r#"
+            tables:
+              - name: customers
+                rules:
+                  email:
+                    email:
+                      uniq:
+                        unique_against:
+                          table: {}
+                          column: {}
+                          max_values: 100
+            "#,
+            table, column
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve() {
+        let tables = vec![
+            table("archive", "users", &["id", "email"]),
+            table("public", "users", &["id", "email"]),
+        ];
+        let resolved = targets(&settings("public.users", "email"), &tables).unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            resolved[0].rule,
+            ("customers".to_string(), "email".to_string())
+        );
+        assert_eq!(resolved[0].table, "public.users");
+        assert_eq!(resolved[0].max_values, 100);
+        assert_eq!(
+            resolved[0].query(),
+            r#"SELECT DISTINCT "email"::text FROM "public"."users" WHERE "email" IS NOT NULL"#
+        );
+
+        // the short name
+        let resolved = targets(&settings("users", "email"), &tables).unwrap();
+        assert_eq!(resolved[0].table, "archive.users");
+    }
+
+    #[test]
+    fn kept_values() {
+        let tables = vec![table("public", "customers", &["id", "email"])];
+        let settings = |rule: &str| {
+            Settings::from_yaml(&format!(
+                "tables:\n  - {{name: customers, rules: {{email: {{skip_if: {{domains: [example.com], rule: {}}}}}}}}}",
+                rule
+            ))
+            .unwrap()
+        };
+
+        let resolved = targets(&settings("{email: {uniq: true}}"), &tables).unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].table, "public.customers");
+        assert_eq!(resolved[0].column, "email");
+        assert!(resolved[0]
+            .keep
+            .as_ref()
+            .unwrap()
+            .matches("demo@example.com"));
+
+        // the inner rule doesn't generate unique values
+        assert!(targets(&settings("{email: {}}"), &tables)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn unknown_tables_and_columns() {
+        let tables = vec![table("public", "users", &["id", "email"])];
+        assert_eq!(
+            targets(&settings("accounts", "email"), &tables)
+                .unwrap_err()
+                .to_string(),
+            "The table `accounts` of `unique_against` of the rule `customers.email` isn't found"
+        );
+        assert_eq!(
+            targets(&settings("users", "login"), &tables)
+                .unwrap_err()
+                .to_string(),
+            "The table public.users has no column `login` (`unique_against` of the rule `customers.email`)"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! The SQL hooks of the config (`hooks.pre_data` and `hooks.post_data`): raw statements that are written
+//! to the dump at the section boundaries.
+
+/// Renders the statements of a hook section (an empty string if there are no statements).
+/// The statements are written as is, a semicolon is added to the statements without it
+/// (except for the psql meta-commands).
+pub fn render(section: &str, statements: &[String]) -> String {
+    let statements: Vec<_> = statements
+        .iter()
+        .map(|s| s.trim_end())
+        .filter(|s| !s.trim_start().is_empty())
+        .collect();
+    if statements.is_empty() {
+        return String::new();
+    }
+
+    let mut sql = format!("\n--\n-- datanymizer: {} hooks\n--\n\n", section);
+    for statement in statements {
+        sql.push_str(statement);
+        if !statement.ends_with(';') && !statement.trim_start().starts_with('\\') {
+            sql.push(';');
+        }
+        sql.push_str("\n\n");
+    }
+
+    sql
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(render("pre-data", &[]), "");
+        assert_eq!(render("pre-data", &[" \n".to_string()]), "");
+    }
+
+    #[test]
+    fn statements() {
+        let statements = [
+            "TRUNCATE jobs;".to_string(),
+            "UPDATE feature_flags SET enabled = false WHERE name = 'billing'\n".to_string(),
+            "DO $$\nBEGIN\n  INSERT INTO users (email) VALUES ('admin@example.com');\nEND\n$$"
+                .to_string(),
+            "\\connect other".to_string(),
+        ];
+
+        assert_eq!(
+            render("post-data", &statements),
+            "
+--
+-- datanymizer: post-data hooks
+--
+
+TRUNCATE jobs;
+
+UPDATE feature_flags SET enabled = false WHERE name = 'billing';
+
+DO $$
+BEGIN
+  INSERT INTO users (email) VALUES ('admin@example.com');
+END
+$$;
+
+\\connect other
+
+"
+        );
+    }
+}
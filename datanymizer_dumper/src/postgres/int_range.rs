@@ -0,0 +1,324 @@
+use super::table::PgTable;
+use crate::Table;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{OnOverflow, SequenceAction, Table as TableCfg};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+};
+
+/// The integer column types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntType {
+    SmallInt,
+    Integer,
+    BigInt,
+}
+
+impl IntType {
+    /// The type by the `data_type` of `information_schema.columns` (`serial` columns are `integer`)
+    pub fn from_data_type(data_type: &str) -> Option<Self> {
+        match data_type {
+            "smallint" => Some(Self::SmallInt),
+            "integer" => Some(Self::Integer),
+            "bigint" => Some(Self::BigInt),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SmallInt => "smallint",
+            Self::Integer => "integer",
+            Self::BigInt => "bigint",
+        }
+    }
+
+    /// `[min, max]`
+    pub fn range(&self) -> (i128, i128) {
+        match self {
+            Self::SmallInt => (i16::MIN.into(), i16::MAX.into()),
+            Self::Integer => (i32::MIN.into(), i32::MAX.into()),
+            Self::BigInt => (i64::MIN.into(), i64::MAX.into()),
+        }
+    }
+
+    pub fn contains(&self, value: i128) -> bool {
+        let (min, max) = self.range();
+        (min..=max).contains(&value)
+    }
+}
+
+/// The integer columns with rules of a table (by the column indexes): the transformed values
+/// are checked against the column types
+#[derive(Debug, Default)]
+pub struct IntColumns {
+    columns: HashMap<usize, IntType>,
+    on_overflow: OnOverflow,
+    clamped: Cell<u64>,
+}
+
+impl IntColumns {
+    pub fn new(
+        table: &PgTable,
+        cfg: &TableCfg,
+        skipped: &HashSet<String>,
+        on_overflow: OnOverflow,
+    ) -> Self {
+        let columns = table
+            .columns
+            .iter()
+            .filter(|c| cfg.rules.contains_key(&c.name) && !skipped.contains(&c.name))
+            .filter_map(|c| {
+                IntType::from_data_type(&c.data_type).map(|t| ((c.position - 1) as usize, t))
+            })
+            .collect();
+        Self {
+            columns,
+            on_overflow,
+            clamped: Cell::new(0),
+        }
+    }
+
+    /// Fits the transformed value of the column to the column type
+    /// (the values that aren't integers, e.g. NULLs, are left as is)
+    pub fn fit(&self, index: usize, value: &mut String) -> Result<(), String> {
+        let int_type = match self.columns.get(&index) {
+            Some(int_type) => int_type,
+            None => return Ok(()),
+        };
+        let number: i128 = match value.trim().parse() {
+            Ok(number) => number,
+            Err(_) => return Ok(()),
+        };
+        if int_type.contains(number) {
+            return Ok(());
+        }
+
+        let (min, max) = int_type.range();
+        match self.on_overflow {
+            OnOverflow::Error => Err(format!(
+                "the value {} is out of the `{}` range ({} to {})",
+                value.trim(),
+                int_type.name(),
+                min,
+                max
+            )),
+            OnOverflow::Clamp => {
+                *value = number.clamp(min, max).to_string();
+                self.clamped.set(self.clamped.get() + 1);
+                Ok(())
+            }
+        }
+    }
+
+    /// The count of the clamped values
+    pub fn clamped(&self) -> u64 {
+        self.clamped.get()
+    }
+}
+
+/// The rules with known ranges and the sequence values of `start_at` must fit the integer column types
+pub fn check(
+    tables: &[(PgTable, TableCfg)],
+    sequences: &HashMap<String, SequenceAction>,
+    skipped_rules: &HashMap<String, HashSet<String>>,
+) -> Result<()> {
+    let mut failures = vec![];
+    for (table, cfg) in tables {
+        let skipped = skipped_rules.get(&table.get_full_name());
+        for column in &table.columns {
+            let int_type = match IntType::from_data_type(&column.data_type) {
+                Some(int_type) => int_type,
+                None => continue,
+            };
+            let (type_min, type_max) = int_type.range();
+
+            if let Some((rule, (min, max))) = cfg
+                .rules
+                .get(&column.name)
+                .filter(|_| skipped.is_none_or(|s| !s.contains(&column.name)))
+                .and_then(|rule| rule.int_range().map(|range| (rule, range)))
+            {
+                if min < type_min || max > type_max {
+                    failures.push(format!(
+                        "{}.{}: the `{}` rule generates values from {} to {}, the `{}` range is {} to {}",
+                        table.get_full_name(),
+                        column.name,
+                        rule.name(),
+                        min,
+                        max,
+                        int_type.name(),
+                        type_min,
+                        type_max
+                    ));
+                }
+            }
+
+            for seq in table.sequences.iter().filter(|s| s.column == column.name) {
+                if let Some(SequenceAction::StartAt(value)) =
+                    seq.find_action(&table.get_full_name(), sequences)
+                {
+                    if !int_type.contains((*value).into()) {
+                        failures.push(format!(
+                            "{} ({}.{}): `start_at: {}` is out of the `{}` range ({} to {})",
+                            seq.full_name,
+                            table.get_full_name(),
+                            column.name,
+                            value,
+                            int_type.name(),
+                            type_min,
+                            type_max
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "Generated values don't fit the integer columns:\n  {}",
+            failures.join("\n  ")
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{postgres::sequence::PgSequence, test_tables};
+    use datanymizer_engine::Settings;
+
+    fn table(types: &[(&str, &str)]) -> PgTable {
+        test_tables::pg_table("public", "users", types)
+    }
+
+    fn cfg(rules: &str) -> TableCfg {
+        let settings =
+            Settings::from_yaml(&format!("tables: [{{name: users, rules: {}}}]", rules)).unwrap();
+        settings.tables[0].clone()
+    }
+
+    fn fit(columns: &IntColumns, value: &str) -> Result<String, String> {
+        let mut value = value.to_string();
+        columns.fit(0, &mut value).map(|_| value)
+    }
+
+    #[test]
+    fn int_types() {
+        assert_eq!(IntType::from_data_type("smallint"), Some(IntType::SmallInt));
+        assert_eq!(IntType::from_data_type("integer"), Some(IntType::Integer));
+        assert_eq!(IntType::from_data_type("bigint"), Some(IntType::BigInt));
+        assert_eq!(IntType::from_data_type("numeric"), None);
+
+        assert!(IntType::SmallInt.contains(-32768));
+        assert!(IntType::SmallInt.contains(32767));
+        assert!(!IntType::SmallInt.contains(-32769));
+        assert!(!IntType::SmallInt.contains(32768));
+        assert!(IntType::Integer.contains(2147483647));
+        assert!(!IntType::Integer.contains(2147483648));
+        assert!(IntType::BigInt.contains(i64::MAX.into()));
+        assert!(!IntType::BigInt.contains(i128::from(i64::MAX) + 1));
+    }
+
+    #[test]
+    fn fit_smallint() {
+        let table = table(&[("level", "smallint"), ("name", "text")]);
+        let cfg = cfg("{level: {random_num: {max: 100}}, name: {first_name: {}}}");
+
+        let columns = IntColumns::new(&table, &cfg, &HashSet::new(), OnOverflow::Error);
+        assert_eq!(fit(&columns, "32767"), Ok("32767".to_string()));
+        assert_eq!(fit(&columns, "-32768"), Ok("-32768".to_string()));
+        assert_eq!(fit(&columns, r"\N"), Ok(r"\N".to_string()));
+        assert_eq!(fit(&columns, "1.5"), Ok("1.5".to_string()));
+        assert_eq!(
+            fit(&columns, "32768"),
+            Err("the value 32768 is out of the `smallint` range (-32768 to 32767)".to_string())
+        );
+        // not an integer column
+        let mut value = "99999".to_string();
+        assert_eq!(columns.fit(1, &mut value), Ok(()));
+
+        let columns = IntColumns::new(&table, &cfg, &HashSet::new(), OnOverflow::Clamp);
+        assert_eq!(fit(&columns, "32768"), Ok("32767".to_string()));
+        assert_eq!(fit(&columns, "-99999999"), Ok("-32768".to_string()));
+        assert_eq!(fit(&columns, "100"), Ok("100".to_string()));
+        assert_eq!(columns.clamped(), 2);
+
+        // the skipped rules aren't applied
+        let skipped = HashSet::from(["level".to_string()]);
+        let columns = IntColumns::new(&table, &cfg, &skipped, OnOverflow::Error);
+        assert_eq!(fit(&columns, "32768"), Ok("32768".to_string()));
+    }
+
+    #[test]
+    fn check_rules() {
+        let table = table(&[
+            ("id", "integer"),
+            ("level", "smallint"),
+            ("score", "bigint"),
+        ]);
+        let sequences = HashMap::new();
+        let check_rules = |rules: &str| {
+            check(&[(table.clone(), cfg(rules))], &sequences, &HashMap::new())
+                .map_err(|e| e.to_string())
+        };
+
+        assert!(check_rules(
+            "{id: {random_num: {max: 2147483647}}, level: {random_num: {max: 32767}}}"
+        )
+        .is_ok());
+        assert!(check_rules("{score: {random_num: {max: 9223372036854775807}}}").is_ok());
+        assert!(check_rules("{level: {dp_noise: {}}}").is_ok());
+        assert_eq!(
+            check_rules("{id: {random_num: {min: 1, max: 2147483648}}, level: {dp_noise: {clamp: [-40000, 0]}}}"),
+            Err("Generated values don't fit the integer columns:\n  \
+                public.users.id: the `random_num` rule generates values from 1 to 2147483648, \
+                the `integer` range is -2147483648 to 2147483647\n  \
+                public.users.level: the `dp_noise` rule generates values from -40000 to 0, \
+                the `smallint` range is -32768 to 32767"
+                .to_string())
+        );
+        // the default maximum doesn't fit any type
+        assert!(check_rules("{score: {random_num: {}}}").is_err());
+
+        let skipped = HashMap::from([(
+            "public.users".to_string(),
+            HashSet::from(["score".to_string()]),
+        )]);
+        assert!(super::check(
+            &[(table.clone(), cfg("{score: {random_num: {}}}"))],
+            &sequences,
+            &skipped
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn check_sequences() {
+        let mut table = table(&[("id", "integer")]);
+        table.sequences = vec![PgSequence {
+            full_name: "public.users_id_seq".to_string(),
+            column: "id".to_string(),
+        }];
+        let check_start_at = |start_at: i64| {
+            let sequences = HashMap::from([(
+                "public.users.id".to_string(),
+                SequenceAction::StartAt(start_at),
+            )]);
+            check(&[(table.clone(), cfg("{}"))], &sequences, &HashMap::new())
+                .map_err(|e| e.to_string())
+        };
+
+        assert!(check_start_at(2147483647).is_ok());
+        assert_eq!(
+            check_start_at(2147483648),
+            Err("Generated values don't fit the integer columns:\n  \
+                public.users_id_seq (public.users.id): `start_at: 2147483648` is out of the `integer` range \
+                (-2147483648 to 2147483647)"
+                .to_string())
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use super::{connector::Connection, table::PgTable};
+use crate::{indicator::Indicator, Table};
+use anyhow::{Context, Result};
+use datanymizer_engine::Table as TableCfg;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Learns the formats of the columns with the `learn_format` rules: the original (non-NULL) values
+/// are sampled with bounded queries at the start of the dump. The tables matched by the same config table
+/// (e.g., the TimescaleDB chunks) are sampled together.
+pub fn learn(
+    connection: &mut Connection,
+    tables: &[(PgTable, TableCfg)],
+    skipped_rules: &HashMap<String, HashSet<String>>,
+    events: &dyn Indicator,
+) -> Result<()> {
+    let mut columns: BTreeMap<(&str, &str), (&TableCfg, Vec<&PgTable>)> = BTreeMap::new();
+    for (table, cfg) in tables {
+        let skipped = skipped_rules.get(&table.get_full_name());
+        for column in &table.columns {
+            let learned = cfg
+                .rules
+                .get(&column.name)
+                .is_some_and(|rule| !rule.learn_format_rules().is_empty());
+            if learned && skipped.is_none_or(|s| !s.contains(&column.name)) {
+                columns
+                    .entry((cfg.name.as_str(), column.name.as_str()))
+                    .or_insert_with(|| (cfg, vec![]))
+                    .1
+                    .push(table);
+            }
+        }
+    }
+
+    for ((name, column), (cfg, tables)) in columns {
+        for rule in cfg.rules[column].learn_format_rules() {
+            let mut samples: Vec<String> = vec![];
+            for table in &tables {
+                let limit = rule.sample_size.saturating_sub(samples.len() as u64);
+                if limit == 0 {
+                    break;
+                }
+                samples.extend(sample(connection, table, column, limit)?);
+            }
+
+            let format = rule.learn(&samples);
+            let column = format!("{}.{}", name, column);
+            match (&format.template, &format.fallback) {
+                (Some(template), _) => events.debug_msg(&format!(
+                    "The format of the column `{}` is learned: `{}` ({} of {} sampled values)",
+                    column, template, format.matched, format.samples
+                )),
+                (None, Some(reason)) if format.samples > 0 => events.warning_msg(&format!(
+                    "The format of the column `{}` isn't learned, the fallback rule is used: {}",
+                    column, reason
+                )),
+                _ => events.debug_msg(&format!(
+                    "The column `{}` has no values to learn the format, the fallback rule is used",
+                    column
+                )),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn sample(
+    connection: &mut Connection,
+    table: &PgTable,
+    column: &str,
+    limit: u64,
+) -> Result<Vec<String>> {
+    let column = format!("\"{}\"", column.replace('"', "\"\""));
+    let query = format!(
+        "SELECT {}::text FROM {} WHERE {} IS NOT NULL LIMIT $1",
+        column,
+        table.quoted_full_name(),
+        column
+    );
+    let rows = connection
+        .client
+        .query(query.as_str(), &[&(limit.min(i64::MAX as u64) as i64)])
+        .with_context(|| {
+            format!(
+                "Can't sample the values of {}.{} to learn the format",
+                table.get_full_name(),
+                column
+            )
+        })?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
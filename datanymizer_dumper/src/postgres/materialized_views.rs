@@ -0,0 +1,304 @@
+//! Materialized views. `pg_dump` creates them without data (pre-data) and refreshes the populated ones
+//! in the post-data section. These refreshes are removed from the `pg_dump` output and written again
+//! at the end of the dump in the dependency order (from `pg_depend`, through the plain views too),
+//! or they are dropped with `materialized_views: skip`. The views are never dumped with `COPY`
+//! (the schema inspector lists the tables only).
+
+use super::{
+    ddl,
+    object_names::{quote_name, unquote},
+};
+use anyhow::Result;
+use postgres::Client;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+const RELATIONS: &str = "SELECT c.oid, n.nspname::text, c.relname::text,
+                                c.relkind = 'm', c.relispopulated
+                         FROM pg_catalog.pg_class c
+                         JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                         WHERE c.relkind IN ('m', 'v')
+                         AND n.nspname NOT IN ('pg_catalog', 'information_schema')";
+
+// The relations used in the rewrite rules (the queries) of the views
+const VIEW_DEPENDENCIES: &str = "SELECT DISTINCT r.ev_class, d.refobjid
+                                 FROM pg_catalog.pg_rewrite r
+                                 JOIN pg_catalog.pg_depend d ON d.objid = r.oid
+                                    AND d.classid = 'pg_catalog.pg_rewrite'::regclass
+                                    AND d.refclassid = 'pg_catalog.pg_class'::regclass
+                                 JOIN pg_catalog.pg_class c ON c.oid = d.refobjid AND c.relkind IN ('m', 'v')
+                                 WHERE d.refobjid <> r.ev_class";
+
+/// The (unquoted) schema and name
+pub type ViewName = (String, String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterializedView {
+    pub schema: String,
+    pub name: String,
+    /// The view has data in the source database (`WITH NO DATA` views are not refreshed)
+    pub populated: bool,
+    /// The materialized views that are used in the query (directly or through plain views)
+    pub depends_on: BTreeSet<ViewName>,
+}
+
+impl MaterializedView {
+    pub fn full_name(&self) -> ViewName {
+        (self.schema.clone(), self.name.clone())
+    }
+
+    pub fn refresh_sql(&self) -> String {
+        format!(
+            "REFRESH MATERIALIZED VIEW {}.{};",
+            quote_name(&self.schema),
+            quote_name(&self.name)
+        )
+    }
+}
+
+// A materialized or a plain view
+struct Relation {
+    schema: String,
+    name: String,
+    materialized: bool,
+    populated: bool,
+}
+
+/// Loads the materialized views and their dependencies
+pub fn load(client: &mut Client) -> Result<Vec<MaterializedView>> {
+    let mut relations = HashMap::new();
+    for row in client.query(RELATIONS, &[])? {
+        let oid: u32 = row.get(0);
+        let relation = Relation {
+            schema: row.get(1),
+            name: row.get(2),
+            materialized: row.get(3),
+            populated: row.get(4),
+        };
+        relations.insert(oid, relation);
+    }
+    let mut references: HashMap<u32, Vec<u32>> = HashMap::new();
+    for row in client.query(VIEW_DEPENDENCIES, &[])? {
+        references.entry(row.get(0)).or_default().push(row.get(1));
+    }
+
+    let mut views: Vec<_> = relations
+        .iter()
+        .filter(|(_, r)| r.materialized)
+        .map(|(oid, r)| MaterializedView {
+            schema: r.schema.clone(),
+            name: r.name.clone(),
+            populated: r.populated,
+            depends_on: materialized_references(*oid, &relations, &references),
+        })
+        .collect();
+    views.sort_by_key(|v| v.full_name());
+    Ok(views)
+}
+
+// The materialized views used by the view (the plain views are expanded)
+fn materialized_references(
+    oid: u32,
+    relations: &HashMap<u32, Relation>,
+    references: &HashMap<u32, Vec<u32>>,
+) -> BTreeSet<ViewName> {
+    let mut result = BTreeSet::new();
+    let mut visited = BTreeSet::from([oid]);
+    let mut stack = vec![oid];
+    while let Some(current) = stack.pop() {
+        for referenced in references.get(&current).into_iter().flatten() {
+            if !visited.insert(*referenced) {
+                continue;
+            }
+            match relations.get(referenced) {
+                Some(r) if r.materialized => {
+                    result.insert((r.schema.clone(), r.name.clone()));
+                }
+                Some(_) => stack.push(*referenced),
+                None => {}
+            }
+        }
+    }
+    result
+}
+
+/// Orders the views so every view is refreshed after the views it depends on
+/// (the independent views are ordered by names)
+pub fn refresh_order(views: Vec<MaterializedView>) -> Vec<MaterializedView> {
+    let mut pending: BTreeMap<ViewName, MaterializedView> =
+        views.into_iter().map(|v| (v.full_name(), v)).collect();
+    let mut result = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let ready: Vec<ViewName> = pending
+            .values()
+            .filter(|v| v.depends_on.iter().all(|d| !pending.contains_key(d)))
+            .map(|v| v.full_name())
+            .collect();
+        // the dependencies are acyclic in the catalog, but the rest is refreshed anyway
+        let ready = if ready.is_empty() {
+            pending.keys().cloned().collect()
+        } else {
+            ready
+        };
+        for name in ready {
+            result.extend(pending.remove(&name));
+        }
+    }
+    result
+}
+
+/// Removes the `REFRESH MATERIALIZED VIEW` statements (with their `-- Name:` comments) from the `pg_dump` output,
+/// returns the names of the removed views
+pub fn strip_refreshes(sql: &str) -> (String, BTreeSet<ViewName>) {
+    let mut result = String::with_capacity(sql.len());
+    let mut names = BTreeSet::new();
+    let mut copied = 0;
+    for range in ddl::statements(sql) {
+        let name = match refreshed_view(&sql[range.clone()]) {
+            Some(name) => name,
+            None => continue,
+        };
+        names.insert(name);
+        result.push_str(&sql[copied..ddl::header_start(sql, copied, range.start)]);
+        copied = range.end + sql[range.end..].bytes().take_while(|b| *b == b'\n').count();
+    }
+    result.push_str(&sql[copied..]);
+    (result, names)
+}
+
+fn refreshed_view(statement: &str) -> Option<ViewName> {
+    let tokens = ddl::head_tokens(statement, 5);
+    let keyword = |i: usize, keyword: &str| match tokens.get(i) {
+        Some(parts) => parts.len() == 1 && parts[0].eq_ignore_ascii_case(keyword),
+        None => false,
+    };
+    if !(keyword(0, "REFRESH") && keyword(1, "MATERIALIZED") && keyword(2, "VIEW")) {
+        return None;
+    }
+    let name = if keyword(3, "CONCURRENTLY") { 4 } else { 3 };
+    match tokens.get(name)?.as_slice() {
+        [schema, name] => Some((unquote(schema), unquote(name))),
+        [name] => Some(("public".to_string(), unquote(name))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(name: &str, depends_on: &[&str]) -> MaterializedView {
+        MaterializedView {
+            schema: "public".to_string(),
+            name: name.to_string(),
+            populated: true,
+            depends_on: depends_on
+                .iter()
+                .map(|d| ("public".to_string(), d.to_string()))
+                .collect(),
+        }
+    }
+
+    fn names(views: &[MaterializedView]) -> Vec<&str> {
+        views.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    #[test]
+    fn order() {
+        let views = vec![
+            view("a_totals", &["m_orders", "z_users"]),
+            view("m_orders", &["z_users"]),
+            view("b_plain", &[]),
+            view("z_users", &[]),
+            // a view that isn't refreshed
+            view("c_report", &["other"]),
+        ];
+        assert_eq!(
+            names(&refresh_order(views)),
+            vec!["b_plain", "c_report", "z_users", "m_orders", "a_totals"]
+        );
+    }
+
+    #[test]
+    fn order_with_cycles() {
+        let views = vec![view("b", &["a"]), view("a", &["b"]), view("c", &["a"])];
+        assert_eq!(names(&refresh_order(views)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn strip() {
+        let sql = "SET lock_timeout = 0;
+
+--
+-- Name: users_email_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX users_email_idx ON public.users USING btree (email);
+
+
+--
+-- Name: user_stats; Type: MATERIALIZED VIEW DATA; Schema: public; Owner: postgres
+--
+
+REFRESH MATERIALIZED VIEW public.user_stats;
+
+
+--
+-- Name: Totals; Type: MATERIALIZED VIEW DATA; Schema: Reports; Owner: postgres
+--
+
+REFRESH MATERIALIZED VIEW \"Reports\".\"Totals\";
+
+
+--
+-- PostgreSQL database dump complete
+--
+";
+        let (stripped, names) = strip_refreshes(sql);
+        assert_eq!(
+            stripped,
+            "SET lock_timeout = 0;
+
+--
+-- Name: users_email_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX users_email_idx ON public.users USING btree (email);
+
+
+--
+-- PostgreSQL database dump complete
+--
+"
+        );
+        assert_eq!(
+            names,
+            BTreeSet::from([
+                ("Reports".to_string(), "Totals".to_string()),
+                ("public".to_string(), "user_stats".to_string())
+            ])
+        );
+
+        let sql = "REFRESH MATERIALIZED VIEW CONCURRENTLY stats;\nSELECT 1;\n";
+        let (stripped, names) = strip_refreshes(sql);
+        assert_eq!(stripped, "SELECT 1;\n");
+        assert_eq!(
+            names,
+            BTreeSet::from([("public".to_string(), "stats".to_string())])
+        );
+    }
+
+    #[test]
+    fn refresh_sql() {
+        let mut v = view("user_stats", &[]);
+        assert_eq!(
+            v.refresh_sql(),
+            "REFRESH MATERIALIZED VIEW public.user_stats;"
+        );
+        v.schema = "Reports".to_string();
+        v.name = "order".to_string();
+        assert_eq!(
+            v.refresh_sql(),
+            "REFRESH MATERIALIZED VIEW \"Reports\".\"order\";"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use super::{query_wrapper::QueryWrapper, table::PgTable};
+use crate::Table;
+use anyhow::{Error, Result};
+use postgres::error::SqlState;
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+/// The current columns of the table (`NULL` if the table doesn't exist)
+const CURRENT_COLUMNS_QUERY: &str = "SELECT to_regclass($1)::oid,
+                                         ARRAY(
+                                             SELECT a.attname::text
+                                             FROM pg_catalog.pg_attribute a
+                                             WHERE a.attrelid = to_regclass($1) AND a.attnum > 0
+                                             AND NOT a.attisdropped
+                                             ORDER BY a.attnum
+                                         )";
+
+pub use crate::staged::MissingTablePolicy;
+
+/// The table is dropped or renamed after the tables are inspected
+#[derive(Debug)]
+pub struct MissingTable(pub String);
+
+impl Display for MissingTable {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The table {} is dropped or renamed after the tables were inspected",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for MissingTable {}
+
+/// Whether the table is found missing before dumping (`MissingTable`) or its query fails with `undefined_table`
+pub fn is_missing_table(error: &Error) -> bool {
+    error.chain().any(|e| {
+        e.is::<MissingTable>()
+            || e.downcast_ref::<postgres::Error>()
+                .or_else(|| {
+                    e.downcast_ref::<io::Error>()
+                        .and_then(|e| e.get_ref())
+                        .and_then(|e| e.downcast_ref::<postgres::Error>())
+                })
+                .and_then(|e| e.code())
+                .is_some_and(|code| *code == SqlState::UNDEFINED_TABLE)
+    })
+}
+
+/// Checks the table right before dumping its data: returns the table without the columns dropped
+/// after the inspection, and the names of the dropped columns
+pub fn revalidate(table: &PgTable, qw: &mut QueryWrapper) -> Result<(PgTable, Vec<String>)> {
+    let row = qw.query_one(CURRENT_COLUMNS_QUERY, &[&table.quoted_full_name()])?;
+    let oid: Option<u32> = row.get(0);
+    if oid.is_none() {
+        return Err(MissingTable(table.get_full_name()).into());
+    }
+
+    Ok(reconcile(table, &row.get::<_, Vec<String>>(1)))
+}
+
+fn reconcile(table: &PgTable, current_columns: &[String]) -> (PgTable, Vec<String>) {
+    let (columns, dropped): (Vec<_>, Vec<_>) = table
+        .columns
+        .iter()
+        .cloned()
+        .partition(|c| current_columns.contains(&c.name));
+    if dropped.is_empty() {
+        return (table.clone(), vec![]);
+    }
+
+    let mut table = table.clone();
+    table.set_columns(columns);
+    (table, dropped.into_iter().map(|c| c.name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::column::PgColumn;
+    use anyhow::anyhow;
+
+    fn column(position: i32, name: &str) -> PgColumn {
+        PgColumn {
+            position,
+            name: name.to_string(),
+            data_type: "text".to_string(),
+            inner_type: Some(25),
+            max_length: None,
+        }
+    }
+
+    #[test]
+    fn missing_table() {
+        let error: Error = MissingTable("public.users".to_string()).into();
+        assert!(is_missing_table(&error));
+        assert_eq!(
+            error.to_string(),
+            "The table public.users is dropped or renamed after the tables were inspected"
+        );
+        assert!(is_missing_table(&error.context("Dumping failed")));
+        assert!(!is_missing_table(&anyhow!("relation does not exist")));
+    }
+
+    #[test]
+    fn reconcile() {
+        let mut table = PgTable::new("users".to_string(), "public".to_string());
+        table.set_columns(vec![column(1, "id"), column(2, "email"), column(3, "name")]);
+
+        let current = vec!["id".to_string(), "name".to_string()];
+        let (reconciled, dropped) = super::reconcile(&table, &current);
+        assert_eq!(dropped, vec!["email"]);
+        assert_eq!(
+            reconciled.query_from(),
+            r#"COPY "public"."users"("id", "name") FROM STDIN;"#
+        );
+        assert_eq!(reconciled.get_column_indexes()["name"], 1);
+
+        let current = vec!["id".to_string(), "email".to_string(), "name".to_string()];
+        let (reconciled, dropped) = super::reconcile(&table, &current);
+        assert!(dropped.is_empty());
+        assert_eq!(reconciled.columns.len(), 3);
+    }
+}
@@ -1,14 +1,49 @@
 use crate::SchemaInspector;
 
+pub mod advisory_lock;
+pub mod archive;
+pub mod array_columns;
+pub mod catalog;
+pub mod child_process;
 pub mod column;
+pub mod compatibility;
+pub mod conn_url;
 pub mod connector;
+pub mod copy_codec;
+pub mod credentials;
+pub mod csv_output;
+pub mod cycles;
+pub mod ddl;
+pub mod dry_run;
+pub mod dump_args;
+pub mod dump_reader;
 pub mod dumper;
+pub mod existing_values;
 pub mod foreign_key;
+pub mod hooks;
+pub mod int_range;
+pub mod learn_format;
+pub mod materialized_views;
+pub mod missing_objects;
+pub mod object_names;
+pub mod only_columns;
+pub mod outbox;
+pub mod output_lint;
+pub mod replica;
+pub mod replication;
+pub mod restore_script;
 pub mod row;
+pub mod safety;
 pub mod schema_inspector;
+pub mod shuffle;
+pub mod subset;
 pub mod table;
+pub mod table_order;
+pub mod table_resolution;
+pub mod tenant;
+pub mod throttle;
 
-mod escaper;
+mod parallel;
 mod query_wrapper;
 mod sequence;
 
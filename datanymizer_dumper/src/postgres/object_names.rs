@@ -0,0 +1,694 @@
+use super::ddl::{block_comment_end, dollar_tag, line_end, quoted_end};
+use anyhow::{anyhow, Result};
+use datanymizer_engine::RenameObject;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+/// The maximum length of identifiers (`NAMEDATALEN - 1`), the server truncates longer names
+const MAX_NAME_LENGTH: usize = 63;
+
+/// The `pg_dump` comment lines with the names of the objects
+const HEADER_PREFIX: &str = "-- Name: ";
+const HEADER_NAME_END: &str = "; Type: ";
+
+/// Reserved keywords (they can't be used as unquoted names)
+const RESERVED: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "system_user",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+/// An index or constraint name that matches `sensitive_object_names`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectNameFinding {
+    pub name: String,
+    /// The first line of the statement
+    pub statement: String,
+}
+
+impl Display for ObjectNameFinding {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.statement)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ObjectNamesReport {
+    /// The original names and the new ones
+    pub renamed: BTreeMap<String, String>,
+    /// Sensitive names (after renaming), every name is reported once
+    pub findings: Vec<ObjectNameFinding>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    QuotedName,
+    Dot,
+    Semicolon,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    start: usize,
+    end: usize,
+    kind: TokenKind,
+}
+
+/// Reports and renames the index and constraint names in the `pg_dump` output.
+/// The names are found by their positions in the statements (`CREATE INDEX <name>`, `CONSTRAINT <name>`,
+/// `ALTER INDEX <name>`, `COMMENT ON INDEX <name>`, `USING INDEX <name>`, `CLUSTER ON <name>`,
+/// `ATTACH PARTITION <name>` of indexes) and in the `-- Name:` comments, so the same words in string literals,
+/// expressions and function bodies are not changed.
+pub struct ObjectNameRewriter {
+    sensitive: Vec<Regex>,
+    renames: Vec<(Regex, String)>,
+}
+
+impl ObjectNameRewriter {
+    pub fn new(sensitive: &[String], renames: &[RenameObject]) -> Result<Self> {
+        Ok(Self {
+            sensitive: sensitive
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?,
+            renames: renames
+                .iter()
+                .map(|r| Ok((Regex::new(&r.pattern)?, r.replace.clone())))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensitive.is_empty() && self.renames.is_empty()
+    }
+
+    pub fn process(&self, sql: &str) -> Result<(String, ObjectNamesReport)> {
+        let mut report = ObjectNamesReport::default();
+        if self.is_empty() {
+            return Ok((sql.to_string(), report));
+        }
+
+        let (tokens, headers) = tokenize(sql);
+        let positions = name_positions(sql, &tokens);
+        let names: BTreeSet<String> = positions
+            .iter()
+            .map(|(i, _)| token_name(sql, &tokens[*i]))
+            .collect();
+
+        for name in &names {
+            if let Some(new_name) = self.rename(name) {
+                report.renamed.insert(name.clone(), new_name);
+            }
+        }
+        check_collisions(&names, &report.renamed)?;
+
+        let mut reported = BTreeSet::new();
+        for (i, statement_start) in &positions {
+            let name = token_name(sql, &tokens[*i]);
+            let name = report.renamed.get(&name).cloned().unwrap_or(name);
+            if self.sensitive.iter().any(|re| re.is_match(&name)) && reported.insert(name.clone()) {
+                let statement = sql[tokens[*statement_start].start..]
+                    .lines()
+                    .next()
+                    .unwrap_or_default();
+                report.findings.push(ObjectNameFinding {
+                    name,
+                    statement: statement.trim().to_string(),
+                });
+            }
+        }
+
+        if report.renamed.is_empty() {
+            return Ok((sql.to_string(), report));
+        }
+
+        // (start, end, replacement)
+        let mut replacements: Vec<(usize, usize, String)> = positions
+            .iter()
+            .filter_map(|(i, _)| {
+                let token = &tokens[*i];
+                report
+                    .renamed
+                    .get(&token_name(sql, token))
+                    .map(|new_name| (token.start, token.end, quote_name(new_name)))
+            })
+            .collect();
+        for (start, end) in headers {
+            let header = rename_in_header(&sql[start..end], &report.renamed);
+            replacements.push((start, end, header));
+        }
+        replacements.sort_by_key(|(start, _, _)| *start);
+
+        let mut result = String::with_capacity(sql.len());
+        let mut copied = 0;
+        for (start, end, replacement) in replacements {
+            result.push_str(&sql[copied..start]);
+            result.push_str(&replacement);
+            copied = end;
+        }
+        result.push_str(&sql[copied..]);
+
+        Ok((result, report))
+    }
+
+    // The first matching rule is applied
+    fn rename(&self, name: &str) -> Option<String> {
+        self.renames
+            .iter()
+            .find(|(re, _)| re.is_match(name))
+            .map(|(re, replace)| re.replace_all(name, replace.as_str()).into_owned())
+            .filter(|new_name| new_name != name)
+    }
+}
+
+// The new names must not match other names (the kept ones or the new ones), they must be valid
+fn check_collisions(names: &BTreeSet<String>, renamed: &BTreeMap<String, String>) -> Result<()> {
+    let mut new_names: BTreeMap<&str, &str> = BTreeMap::new();
+    for (name, new_name) in renamed {
+        if new_name.is_empty() || new_name.len() > MAX_NAME_LENGTH {
+            return Err(anyhow!(
+                "The object `{}` can't be renamed to `{}`: the names must be from 1 to {} bytes long",
+                name,
+                new_name,
+                MAX_NAME_LENGTH
+            ));
+        }
+        if names.contains(new_name) && !renamed.contains_key(new_name) {
+            return Err(anyhow!(
+                "The object `{}` can't be renamed to `{}`: there is another object with this name",
+                name,
+                new_name
+            ));
+        }
+        if let Some(other) = new_names.insert(new_name, name) {
+            return Err(anyhow!(
+                "The objects `{}` and `{}` can't be renamed to the same name `{}`",
+                other,
+                name,
+                new_name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Splits the SQL into tokens (string literals, dollar-quoted strings, comments and psql meta-commands are skipped).
+/// Also returns the positions of the object names in the `-- Name:` comments.
+fn tokenize(sql: &str) -> (Vec<Token>, Vec<(usize, usize)>) {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut headers = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let kind = match c {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = line_end(bytes, i);
+                if sql[start..].starts_with(HEADER_PREFIX) {
+                    let name_start = start + HEADER_PREFIX.len();
+                    if let Some(len) = sql[name_start..i].find(HEADER_NAME_END) {
+                        headers.push((name_start, name_start + len));
+                    }
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = block_comment_end(bytes, i);
+                continue;
+            }
+            b'\\' => {
+                i = line_end(bytes, i);
+                continue;
+            }
+            b'\'' => {
+                let escapes = i > 0 && matches!(bytes[i - 1], b'E' | b'e');
+                i = quoted_end(bytes, i, b'\'', escapes);
+                TokenKind::Other
+            }
+            b'"' => {
+                i = quoted_end(bytes, i, b'"', false);
+                TokenKind::QuotedName
+            }
+            b'$' => {
+                i = match dollar_tag(&sql[i..]) {
+                    Some(tag) => sql[i + tag.len()..]
+                        .find(tag)
+                        .map_or(bytes.len(), |end| i + tag.len() + end + tag.len()),
+                    None => i + 1,
+                };
+                TokenKind::Other
+            }
+            b'.' => {
+                i += 1;
+                TokenKind::Dot
+            }
+            b';' => {
+                i += 1;
+                TokenKind::Semicolon
+            }
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            c if c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii() => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric()
+                        || bytes[i] == b'_'
+                        || bytes[i] == b'$'
+                        || !bytes[i].is_ascii())
+                {
+                    i += 1;
+                }
+                if c.is_ascii_digit() {
+                    TokenKind::Other
+                } else {
+                    TokenKind::Word
+                }
+            }
+            _ => {
+                i += 1;
+                TokenKind::Other
+            }
+        };
+        tokens.push(Token {
+            start,
+            end: i,
+            kind,
+        });
+    }
+
+    (tokens, headers)
+}
+
+/// Returns the indexes of the tokens with the index and constraint names
+/// and the indexes of the first tokens of their statements
+fn name_positions(sql: &str, tokens: &[Token]) -> Vec<(usize, usize)> {
+    let keyword = |i: usize| match tokens.get(i) {
+        Some(t) if t.kind == TokenKind::Word => sql[t.start..t.end].to_ascii_uppercase(),
+        _ => String::new(),
+    };
+    let is_name = |i: usize| {
+        tokens
+            .get(i)
+            .is_some_and(|t| matches!(t.kind, TokenKind::Word | TokenKind::QuotedName))
+    };
+    // the last part of the qualified name
+    let last_part = |mut i: usize| {
+        if !is_name(i) {
+            return None;
+        }
+        while tokens.get(i + 1).map(|t| t.kind) == Some(TokenKind::Dot) && is_name(i + 2) {
+            i += 2;
+        }
+        Some(i)
+    };
+
+    let mut result = vec![];
+    let mut statement_start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind == TokenKind::Semicolon {
+            statement_start = i + 1;
+            continue;
+        }
+        let position = match keyword(i).as_str() {
+            "INDEX" => {
+                let mut j = i + 1;
+                while matches!(
+                    keyword(j).as_str(),
+                    "CONCURRENTLY" | "IF" | "NOT" | "EXISTS"
+                ) {
+                    j += 1;
+                }
+                match keyword(j).as_str() {
+                    // unnamed indexes and `USING INDEX TABLESPACE`
+                    "ON" | "TABLESPACE" => None,
+                    _ => last_part(j),
+                }
+            }
+            "CONSTRAINT" if keyword(i + 1) != "TRIGGER" => last_part(i + 1),
+            "ON" if i > 0 && keyword(i - 1) == "CLUSTER" => last_part(i + 1),
+            "PARTITION"
+                if i > 0
+                    && keyword(i - 1) == "ATTACH"
+                    && keyword(statement_start) == "ALTER"
+                    && keyword(statement_start + 1) == "INDEX" =>
+            {
+                last_part(i + 1)
+            }
+            _ => None,
+        };
+        if let Some(position) = position {
+            result.push((position, statement_start));
+        }
+    }
+
+    result
+}
+
+/// The name without quotes (unquoted names are folded to lower case)
+fn token_name(sql: &str, token: &Token) -> String {
+    let s = &sql[token.start..token.end];
+    match token.kind {
+        TokenKind::QuotedName => s[1..s.len() - 1].replace("\"\"", "\""),
+        _ => s.to_lowercase(),
+    }
+}
+
+pub(super) fn quote_name(name: &str) -> String {
+    let simple = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    if simple && !RESERVED.contains(&name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// The name without quotes (unquoted names are folded to lower case)
+pub(super) fn unquote(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => name.to_lowercase(),
+    }
+}
+
+/// Renames the names in the `Name:` part of the comment (e.g., `orders fk_orders_acme_id`),
+/// the names are separated with spaces (but can contain them), so the longest names are replaced first
+fn rename_in_header(header: &str, renamed: &BTreeMap<String, String>) -> String {
+    let mut names: Vec<_> = renamed.iter().collect();
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    let mut result = String::with_capacity(header.len());
+    let mut i = 0;
+    'outer: while i < header.len() {
+        let at_word_start = i == 0 || header[..i].ends_with(' ');
+        if at_word_start {
+            for (name, new_name) in &names {
+                let end = i + name.len();
+                if header[i..].starts_with(name.as_str())
+                    && (end == header.len() || header[end..].starts_with(' '))
+                {
+                    result.push_str(new_name);
+                    i = end;
+                    continue 'outer;
+                }
+            }
+        }
+        let c = header[i..].chars().next().unwrap_or_default();
+        result.push(c);
+        i += c.len_utf8();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pg_dump --section post-data` (PostgreSQL 15)
+    const SQL: &str = r#"--
+-- Name: orders orders_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.orders
+    ADD CONSTRAINT orders_pkey PRIMARY KEY (id);
+
+--
+-- Name: Idx Acmecorp Note; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE UNIQUE INDEX "Idx Acmecorp Note" ON public.orders USING btree (note);
+
+--
+-- Name: idx_orders_acmecorp; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX idx_orders_acmecorp ON public.orders USING btree (acmecorp_id) WHERE (note <> 'idx_orders_acmecorp'::text);
+
+--
+-- Name: INDEX idx_orders_acmecorp; Type: COMMENT; Schema: public; Owner: postgres
+--
+
+COMMENT ON INDEX public.idx_orders_acmecorp IS 'for acmecorp';
+
+--
+-- Name: parted_acmecorp_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX parted_acmecorp_idx ON ONLY public.parted USING btree (id);
+
+--
+-- Name: parted_1_acmecorp_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX parted_1_acmecorp_idx ON public.parted_1 USING btree (id);
+
+--
+-- Name: parted_1_acmecorp_idx; Type: INDEX ATTACH; Schema: public; Owner: postgres
+--
+
+ALTER INDEX public.parted_acmecorp_idx ATTACH PARTITION public.parted_1_acmecorp_idx;
+
+--
+-- Name: orders fk_orders_acmecorp_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.orders
+    ADD CONSTRAINT fk_orders_acmecorp_id FOREIGN KEY (acmecorp_id) REFERENCES public.customers(id);
+
+--
+-- Name: CONSTRAINT fk_orders_acmecorp_id ON orders; Type: COMMENT; Schema: public; Owner: postgres
+--
+
+COMMENT ON CONSTRAINT fk_orders_acmecorp_id ON public.orders IS 'acmecorp link';
+
+ALTER TABLE public.orders CLUSTER ON idx_orders_acmecorp;
+"#;
+
+    fn rewriter(sensitive: &[&str], renames: &[(&str, &str)]) -> ObjectNameRewriter {
+        let sensitive: Vec<_> = sensitive.iter().map(|s| s.to_string()).collect();
+        let renames: Vec<_> = renames
+            .iter()
+            .map(|(pattern, replace)| RenameObject {
+                pattern: pattern.to_string(),
+                replace: replace.to_string(),
+            })
+            .collect();
+        ObjectNameRewriter::new(&sensitive, &renames).unwrap()
+    }
+
+    #[test]
+    fn report() {
+        let (sql, report) = rewriter(&["(?i)acmecorp"], &[]).process(SQL).unwrap();
+        assert_eq!(sql, SQL);
+        assert!(report.renamed.is_empty());
+        let names: Vec<_> = report.findings.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Idx Acmecorp Note",
+                "idx_orders_acmecorp",
+                "parted_acmecorp_idx",
+                "parted_1_acmecorp_idx",
+                "fk_orders_acmecorp_id"
+            ]
+        );
+        assert_eq!(
+            report.findings[1].to_string(),
+            "idx_orders_acmecorp (CREATE INDEX idx_orders_acmecorp ON public.orders USING btree (acmecorp_id) \
+             WHERE (note <> 'idx_orders_acmecorp'::text);)"
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let (sql, report) = rewriter(&["(?i)acmecorp"], &[("(?i)acmecorp", "customer")])
+            .process(SQL)
+            .unwrap();
+
+        assert_eq!(
+            report.renamed,
+            BTreeMap::from([
+                (
+                    "Idx Acmecorp Note".to_string(),
+                    "Idx customer Note".to_string()
+                ),
+                (
+                    "fk_orders_acmecorp_id".to_string(),
+                    "fk_orders_customer_id".to_string()
+                ),
+                (
+                    "idx_orders_acmecorp".to_string(),
+                    "idx_orders_customer".to_string()
+                ),
+                (
+                    "parted_1_acmecorp_idx".to_string(),
+                    "parted_1_customer_idx".to_string()
+                ),
+                (
+                    "parted_acmecorp_idx".to_string(),
+                    "parted_customer_idx".to_string()
+                ),
+            ])
+        );
+        assert!(report.findings.is_empty());
+
+        let expected = SQL
+            .replace("Idx Acmecorp Note", "Idx customer Note")
+            .replace("_acmecorp_", "_customer_")
+            .replace("idx_orders_acmecorp", "idx_orders_customer")
+            // string literals, expressions and columns are not changed
+            .replace("'idx_orders_customer'::text", "'idx_orders_acmecorp'::text");
+        assert_eq!(sql, expected);
+        assert!(sql.contains("(acmecorp_id)"));
+        assert!(sql.contains("'for acmecorp'"));
+        assert!(sql.contains("-- Name: orders fk_orders_customer_id; Type: FK CONSTRAINT"));
+        assert!(sql.contains("ALTER TABLE public.orders CLUSTER ON idx_orders_customer;"));
+    }
+
+    #[test]
+    fn collisions() {
+        let error =
+            |renames: &[(&str, &str)]| rewriter(&[], renames).process(SQL).unwrap_err().to_string();
+
+        assert_eq!(
+            error(&[("^idx_orders_acmecorp$", "orders_pkey")]),
+            "The object `idx_orders_acmecorp` can't be renamed to `orders_pkey`: there is another object with this name"
+        );
+        assert_eq!(
+            error(&[("^parted(_1)?_acmecorp_idx$", "parted_idx")]),
+            "The objects `parted_1_acmecorp_idx` and `parted_acmecorp_idx` can't be renamed to the same name `parted_idx`"
+        );
+        assert!(error(&[("acmecorp", &"x".repeat(64))])
+            .contains("the names must be from 1 to 63 bytes long"));
+
+        // the names can be swapped
+        let (sql, _) = rewriter(
+            &[],
+            &[
+                ("^parted_acmecorp_idx$", "parted_1_acmecorp_idx"),
+                ("^parted_1_acmecorp_idx$", "parted_acmecorp_idx"),
+            ],
+        )
+        .process(SQL)
+        .unwrap();
+        assert!(sql.contains(
+            "ALTER INDEX public.parted_1_acmecorp_idx ATTACH PARTITION public.parted_acmecorp_idx;"
+        ));
+    }
+
+    #[test]
+    fn quote_name() {
+        assert_eq!(super::quote_name("idx_1"), "idx_1");
+        assert_eq!(super::quote_name("Idx"), "\"Idx\"");
+        assert_eq!(super::quote_name("user"), "\"user\"");
+        assert_eq!(super::quote_name("a\"b"), "\"a\"\"b\"");
+    }
+}
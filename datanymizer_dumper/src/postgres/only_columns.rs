@@ -0,0 +1,180 @@
+//! The projections of the tables with `only_columns`: the COPY statements have only the listed columns,
+//! the other columns get their defaults (or NULL) on restore.
+
+use super::{column::PgColumn, table::PgTable};
+use crate::Table;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::Table as TableCfg;
+use postgres::Client;
+use std::collections::HashMap;
+
+// The columns that can't be omitted: NOT NULL without a default (the identity columns are generated on restore)
+const REQUIRED_COLUMNS_QUERY: &str = "SELECT a.attname::text
+                                      FROM pg_catalog.pg_attribute a
+                                      WHERE a.attrelid = to_regclass($1) AND a.attnum > 0
+                                      AND NOT a.attisdropped AND a.attnotnull
+                                      AND NOT a.atthasdef AND a.attidentity = ''
+                                      ORDER BY a.attnum";
+
+// The average widths of the values (there are two rows for the tables with inheritance)
+const COLUMN_WIDTHS_QUERY: &str = "SELECT attname::text, max(avg_width)
+                                   FROM pg_catalog.pg_stats
+                                   WHERE schemaname = $1 AND tablename = $2
+                                   GROUP BY attname";
+
+/// The columns of a projected table that aren't dumped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    pub omitted: Vec<String>,
+    /// The estimated size of the omitted values of a row in the COPY format
+    /// (`None` if the table has no statistics)
+    pub omitted_row_bytes: Option<u64>,
+}
+
+impl Projection {
+    /// The estimated size of the omitted values of the dumped rows
+    pub fn saved_bytes(&self, rows: u64) -> Option<u64> {
+        self.omitted_row_bytes.map(|bytes| bytes * rows)
+    }
+}
+
+/// Leaves only the columns of `only_columns` in the table (nothing is changed without them)
+pub fn project(client: &mut Client, table: &mut PgTable, cfg: &TableCfg) -> Result<()> {
+    if cfg.only_columns.is_empty() {
+        return Ok(());
+    }
+
+    let (columns, omitted) = split_columns(table, &cfg.only_columns)?;
+    let required: Vec<String> = client
+        .query(REQUIRED_COLUMNS_QUERY, &[&table.quoted_full_name()])?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    check_required(table, &omitted, &required)?;
+
+    let widths: HashMap<String, i32> = client
+        .query(COLUMN_WIDTHS_QUERY, &[&table.schemaname, &table.tablename])?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    table.set_columns(columns);
+    table.projection = Some(Projection {
+        omitted_row_bytes: omitted_row_bytes(&omitted, &widths),
+        omitted,
+    });
+    Ok(())
+}
+
+// The dumped columns (in the table order) and the names of the omitted ones
+fn split_columns(table: &PgTable, only_columns: &[String]) -> Result<(Vec<PgColumn>, Vec<String>)> {
+    let names = table.get_columns_names();
+    let unknown: Vec<_> = only_columns
+        .iter()
+        .filter(|c| !names.contains(c))
+        .map(|c| c.as_str())
+        .collect();
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "Unknown columns in `only_columns` of the table {}: {}",
+            table.get_full_name(),
+            unknown.join(", ")
+        ));
+    }
+
+    let (columns, omitted): (Vec<_>, Vec<_>) = table
+        .columns
+        .iter()
+        .cloned()
+        .partition(|c| only_columns.contains(&c.name));
+    Ok((columns, omitted.into_iter().map(|c| c.name).collect()))
+}
+
+fn check_required(table: &PgTable, omitted: &[String], required: &[String]) -> Result<()> {
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|c| omitted.contains(c))
+        .map(|c| c.as_str())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "The NOT NULL columns without defaults of the table {} are not in `only_columns`: {} \
+        (the table can't be restored without them, add them to `only_columns` or add defaults to them)",
+        table.get_full_name(),
+        missing.join(", ")
+    ))
+}
+
+// A value and its delimiter for every omitted column, `None` without the statistics of all omitted columns
+fn omitted_row_bytes(omitted: &[String], widths: &HashMap<String, i32>) -> Option<u64> {
+    omitted
+        .iter()
+        .map(|column| widths.get(column).map(|width| *width as u64 + 1))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+
+    fn table() -> PgTable {
+        let columns = ["id", "email", "bio", "avatar"].map(|name| (name, "text"));
+        test_tables::pg_table("public", "users", &columns)
+    }
+
+    fn names(columns: &[&str]) -> Vec<String> {
+        columns.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn split() {
+        let (columns, omitted) = split_columns(&table(), &names(&["email", "id"])).unwrap();
+        assert_eq!(
+            columns.into_iter().map(|c| c.name).collect::<Vec<_>>(),
+            vec!["id", "email"]
+        );
+        assert_eq!(omitted, vec!["bio", "avatar"]);
+
+        assert_eq!(
+            split_columns(&table(), &names(&["id", "name", "phone"]))
+                .unwrap_err()
+                .to_string(),
+            "Unknown columns in `only_columns` of the table public.users: name, phone"
+        );
+    }
+
+    #[test]
+    fn required() {
+        let omitted = names(&["bio", "avatar"]);
+        assert!(check_required(&table(), &omitted, &names(&["id"])).is_ok());
+        assert_eq!(
+            check_required(&table(), &omitted, &names(&["id", "avatar"]))
+                .unwrap_err()
+                .to_string(),
+            "The NOT NULL columns without defaults of the table public.users are not in `only_columns`: avatar \
+            (the table can't be restored without them, add them to `only_columns` or add defaults to them)"
+        );
+    }
+
+    #[test]
+    fn saved_bytes() {
+        let omitted = names(&["bio", "avatar"]);
+        let widths = HashMap::from([
+            ("id".to_string(), 4),
+            ("bio".to_string(), 120),
+            ("avatar".to_string(), 40),
+        ]);
+        let projection = Projection {
+            omitted_row_bytes: omitted_row_bytes(&omitted, &widths),
+            omitted,
+        };
+        assert_eq!(projection.omitted_row_bytes, Some(162));
+        assert_eq!(projection.saved_bytes(10), Some(1620));
+
+        let widths = HashMap::from([("bio".to_string(), 120)]);
+        assert_eq!(omitted_row_bytes(&names(&["bio", "avatar"]), &widths), None);
+    }
+}
@@ -0,0 +1,254 @@
+use super::copy_codec;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{Outbox, OutboxPayload, UnknownEvents};
+use serde_json::Value as JsonValue;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+/// The event type of the rows without types in the counts
+const NULL_TYPE: &str = "NULL";
+
+/// Transforms the event payloads of the rows of an outbox table (after the column rules)
+/// and counts the rows by the event types
+pub struct OutboxRows<'a> {
+    outbox: &'a Outbox,
+    /// The table name in the config (for the rule names)
+    cfg_name: &'a str,
+    globals: &'a Option<HashMap<String, JsonValue>>,
+    type_index: usize,
+    payload_index: usize,
+    events: BTreeMap<String, u64>,
+    /// The counts of the rows of the event types without rules
+    unknown: BTreeMap<String, u64>,
+}
+
+impl<'a> OutboxRows<'a> {
+    pub fn new(
+        outbox: &'a Outbox,
+        cfg_name: &'a str,
+        column_indexes: &HashMap<String, usize>,
+        globals: &'a Option<HashMap<String, JsonValue>>,
+    ) -> Result<Self> {
+        let index = |column: &str| {
+            column_indexes
+                .get(column)
+                .copied()
+                .ok_or_else(|| anyhow!("The outbox table {} has no column `{}`", cfg_name, column))
+        };
+
+        Ok(Self {
+            outbox,
+            cfg_name,
+            globals,
+            type_index: index(&outbox.type_column)?,
+            payload_index: index(&outbox.payload_column)?,
+            events: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+        })
+    }
+
+    /// Transforms the payload of the row (a COPY line), returns `None` if the row is dropped
+    pub fn apply(&mut self, line: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        let fields: Vec<_> = copy_codec::fields(&line).collect();
+        let (event_type, payload) =
+            match (fields.get(self.type_index), fields.get(self.payload_index)) {
+                (Some(event_type), Some(payload)) => (
+                    copy_codec::unescape_str(event_type)?,
+                    copy_codec::unescape_str(payload)?,
+                ),
+                _ => return Err(anyhow!("Invalid row of the outbox table {}", self.cfg_name)),
+            };
+
+        let result = self
+            .outbox
+            .transform(
+                self.cfg_name,
+                event_type.as_deref(),
+                payload.as_deref(),
+                self.globals,
+            )
+            .map_err(|e| anyhow!("Failed transform {}", e))?;
+
+        let name = event_type.as_deref().unwrap_or(NULL_TYPE);
+        *self.events.entry(name.to_string()).or_default() += 1;
+        let payload = match result {
+            OutboxPayload::Keep => return Ok(Some(line)),
+            OutboxPayload::Passthrough => {
+                *self.unknown.entry(name.to_string()).or_default() += 1;
+                return Ok(Some(line));
+            }
+            OutboxPayload::Drop => {
+                *self.unknown.entry(name.to_string()).or_default() += 1;
+                return Ok(None);
+            }
+            OutboxPayload::Null => {
+                *self.unknown.entry(name.to_string()).or_default() += 1;
+                Cow::Borrowed(br"\N".as_slice())
+            }
+            OutboxPayload::Transformed(payload) => {
+                Cow::Owned(copy_codec::escape(payload.as_bytes()).into_owned())
+            }
+        };
+
+        let mut result = Vec::with_capacity(line.len());
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                result.push(copy_codec::DELIMITER);
+            }
+            if i == self.payload_index {
+                result.extend_from_slice(&payload);
+            } else {
+                result.extend_from_slice(field);
+            }
+        }
+        Ok(Some(result))
+    }
+
+    /// The warning about the rows of the event types without rules (if there are such rows)
+    pub fn unknown_warning(&self, table: &str) -> Option<String> {
+        if self.unknown.is_empty() {
+            return None;
+        }
+        let policy = match self.outbox.unknown_events {
+            UnknownEvents::NullPayload => "the payloads are replaced with NULL",
+            UnknownEvents::Passthrough => "the payloads are dumped as is",
+            UnknownEvents::Drop => "the rows are not dumped",
+        };
+        Some(format!(
+            "{} has the event types without rules ({}): {}",
+            table,
+            policy,
+            format_counts(&self.unknown)
+        ))
+    }
+
+    /// The counts of the rows by the event types
+    pub fn into_events(self) -> BTreeMap<String, u64> {
+        self.events
+    }
+}
+
+// E.g. `order_paid: 10, user_registered: 2`
+fn format_counts(counts: &BTreeMap<String, u64>) -> String {
+    counts
+        .iter()
+        .map(|(event_type, count)| format!("{}: {}", event_type, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datanymizer_engine::Settings;
+
+    fn config(unknown_events: &str) -> Settings {
+        Settings::from_yaml(&format!(
+            r#"
+            tables:
+              - name: outbox
+                outbox:
+                  type_column: kind
+                  event_rules:
+                    signed_up:
+                      email:
+                        template:
+                          format: "user\tn@example.com"
+                    paid: {{}}
+                  unknown_events: {}
+            "#,
+            unknown_events
+        ))
+        .unwrap()
+    }
+
+    fn columns() -> HashMap<String, usize> {
+        [("id", 0), ("kind", 1), ("payload", 2)]
+            .into_iter()
+            .map(|(name, i)| (name.to_string(), i))
+            .collect()
+    }
+
+    fn apply(rows: &mut OutboxRows, line: &str) -> Option<String> {
+        rows.apply(line.as_bytes().to_vec())
+            .unwrap()
+            .map(|line| String::from_utf8(line).unwrap())
+    }
+
+    #[test]
+    fn rows() {
+        let settings = config("null_payload");
+        let outbox = settings.tables[0].outbox.as_ref().unwrap();
+        let columns = columns();
+        let mut rows = OutboxRows::new(outbox, "outbox", &columns, &None).unwrap();
+
+        assert_eq!(
+            apply(
+                &mut rows,
+                "1\tsigned_up\t{\"id\": 5, \"email\": \"john\\\\tdoe@mail.com\"}"
+            ),
+            Some("1\tsigned_up\t{\"email\":\"user\\\\tn@example.com\",\"id\":5}".to_string())
+        );
+        assert_eq!(
+            apply(&mut rows, "2\tpaid\t{\"email\": \"a@b.c\"}"),
+            Some("2\tpaid\t{\"email\": \"a@b.c\"}".to_string())
+        );
+        assert_eq!(
+            apply(&mut rows, "3\tsigned_up\t\\N"),
+            Some("3\tsigned_up\t\\N".to_string())
+        );
+        assert_eq!(
+            apply(&mut rows, "4\tdeleted\t{\"email\": \"a@b.c\"}"),
+            Some("4\tdeleted\t\\N".to_string())
+        );
+        assert_eq!(
+            apply(&mut rows, "5\t\\N\t{}"),
+            Some("5\t\\N\t\\N".to_string())
+        );
+        assert!(rows.apply(b"6\tsigned_up\t{email".to_vec()).is_err());
+
+        assert_eq!(
+            rows.unknown_warning("public.outbox").unwrap(),
+            "public.outbox has the event types without rules (the payloads are replaced with NULL): \
+            NULL: 1, deleted: 1"
+        );
+        assert_eq!(
+            format_counts(&rows.into_events()),
+            "NULL: 1, deleted: 1, paid: 1, signed_up: 2"
+        );
+    }
+
+    #[test]
+    fn policies() {
+        let columns = columns();
+        let line = "1\tdeleted\t{\"email\": \"a@b.c\"}";
+
+        let settings = config("passthrough");
+        let outbox = settings.tables[0].outbox.as_ref().unwrap();
+        let mut rows = OutboxRows::new(outbox, "outbox", &columns, &None).unwrap();
+        assert_eq!(apply(&mut rows, line), Some(line.to_string()));
+        assert!(rows.unknown_warning("outbox").is_some());
+
+        let settings = config("drop");
+        let outbox = settings.tables[0].outbox.as_ref().unwrap();
+        let mut rows = OutboxRows::new(outbox, "outbox", &columns, &None).unwrap();
+        assert_eq!(apply(&mut rows, line), None);
+        assert_eq!(
+            apply(&mut rows, "2\tpaid\t{}"),
+            Some("2\tpaid\t{}".to_string())
+        );
+        assert_eq!(format_counts(&rows.into_events()), "deleted: 1, paid: 1");
+
+        let mut columns = columns;
+        columns.remove("kind");
+        assert_eq!(
+            OutboxRows::new(outbox, "outbox", &columns, &None)
+                .err()
+                .unwrap()
+                .to_string(),
+            "The outbox table outbox has no column `kind`"
+        );
+    }
+}
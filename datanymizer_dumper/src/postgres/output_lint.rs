@@ -0,0 +1,958 @@
+//! Checks that the written dump is well-formed before the run is reported as successful (`--lint-output`).
+//!
+//! It is a lexical check (without a full SQL grammar): the statements are split with quotes, dollar quotes
+//! and comments in mind, every statement must start with a known statement keyword, have balanced parentheses
+//! and end with `;`. The COPY headers are validated, and the data rows of COPY blocks are checked
+//! by the field counts only (the values are not parsed). In the directory format the table files are checked
+//! against `manifest.json`. This catches the bugs of the statement rewriting (renames, `DROP ... IF EXISTS`,
+//! DDL replacements, etc.) before the dump is restored.
+
+use super::{
+    copy_codec,
+    csv_output::{DirectoryManifest, MANIFEST_FILE, SCHEMA_FILE},
+};
+use anyhow::{anyhow, Result};
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// The reported errors (the rest are counted only)
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+/// The length of the statement beginnings in the errors
+const SNIPPET_LENGTH: usize = 60;
+
+/// The first keywords of the PostgreSQL statements
+const STATEMENT_KEYWORDS: [&str; 52] = [
+    "ABORT",
+    "ALTER",
+    "ANALYZE",
+    "BEGIN",
+    "CALL",
+    "CHECKPOINT",
+    "CLOSE",
+    "CLUSTER",
+    "COMMENT",
+    "COMMIT",
+    "COPY",
+    "CREATE",
+    "DEALLOCATE",
+    "DECLARE",
+    "DELETE",
+    "DISCARD",
+    "DO",
+    "DROP",
+    "END",
+    "EXECUTE",
+    "EXPLAIN",
+    "FETCH",
+    "GRANT",
+    "IMPORT",
+    "INSERT",
+    "LISTEN",
+    "LOAD",
+    "LOCK",
+    "MERGE",
+    "MOVE",
+    "NOTIFY",
+    "PREPARE",
+    "REASSIGN",
+    "REFRESH",
+    "REINDEX",
+    "RELEASE",
+    "RESET",
+    "REVOKE",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "SECURITY",
+    "SELECT",
+    "SET",
+    "SHOW",
+    "START",
+    "TABLE",
+    "TRUNCATE",
+    "UNLISTEN",
+    "UPDATE",
+    "VACUUM",
+    "VALUES",
+    "WITH",
+];
+
+/// `COPY name(columns) FROM STDIN options;`
+const COPY_HEADER: &str = r#"(?is)^COPY\s+(?:"(?:[^"]|"")*"|[^\s("])+\s*(?:\(((?:"(?:[^"]|"")*"|[^()"])*)\))?\s*FROM\s+STDIN\b\s*([^;]*);$"#;
+
+/// The column names in the COPY headers
+const COLUMN_NAME: &str = r#"^(?:"(?:[^"]|"")+"|[\p{L}_][\p{L}\p{N}_$]*)$"#;
+
+const COPY_FROM_STDIN: &str = r"(?is)^COPY\b.*\bFROM\s+STDIN\b";
+
+/// A place in a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Position {
+    /// The byte offset (from 0)
+    pub offset: u64,
+    /// The line number (from 1)
+    pub line: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintError {
+    pub file: String,
+    #[serde(flatten)]
+    pub position: Position,
+    pub message: String,
+    /// The beginning of the statement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+}
+
+impl Display for LintError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} (byte {}): {}",
+            self.file, self.position.line, self.position.offset, self.message
+        )?;
+        if let Some(statement) = &self.statement {
+            write!(f, " in `{}`", statement)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct LintReport {
+    pub files: u64,
+    pub statements: u64,
+    pub copy_blocks: u64,
+    /// The rows of the COPY blocks and the table files
+    pub rows: u64,
+    /// The first errors
+    pub errors: Vec<LintError>,
+    /// All errors (including the not reported ones)
+    pub error_count: u64,
+}
+
+impl LintReport {
+    pub fn is_ok(&self) -> bool {
+        self.error_count == 0
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let status = if self.is_ok() { "ok" } else { "FAILED" };
+        writeln!(
+            f,
+            "Output lint {}: {} files, {} statements, {} COPY blocks, {} rows, {} errors",
+            status, self.files, self.statements, self.copy_blocks, self.rows, self.error_count
+        )?;
+        for error in &self.errors {
+            writeln!(f, "  {}", error)?;
+        }
+        let more = self.error_count - self.errors.len() as u64;
+        if more > 0 {
+            writeln!(f, "  ... and {} more errors", more)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lints the dump files and collects the results in one report
+pub struct OutputLinter {
+    max_errors: usize,
+    report: LintReport,
+    copy_header: Regex,
+    copy_from_stdin: Regex,
+    column_name: regex::Regex,
+}
+
+impl Default for OutputLinter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ERRORS)
+    }
+}
+
+impl OutputLinter {
+    pub fn new(max_errors: usize) -> Self {
+        Self {
+            max_errors,
+            report: LintReport::default(),
+            copy_header: Regex::new(COPY_HEADER).expect("valid regex"),
+            copy_from_stdin: Regex::new(COPY_FROM_STDIN).expect("valid regex"),
+            column_name: regex::Regex::new(COLUMN_NAME).expect("valid regex"),
+        }
+    }
+
+    /// Lints an SQL dump file (the schema, the data or the whole dump)
+    pub fn lint_sql_file(&mut self, path: &Path) -> Result<()> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Can't read the dump `{}`: {}", path.display(), e))?;
+        self.lint_sql(&path.display().to_string(), BufReader::new(file))
+    }
+
+    pub fn lint_sql<R: BufRead>(&mut self, file: &str, mut reader: R) -> Result<()> {
+        self.report.files += 1;
+        let mut lexer = SqlLexer::new(file);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            lexer.line(self, &line);
+        }
+        lexer.finish(self);
+        Ok(())
+    }
+
+    /// Lints a directory dump: `schema.sql` and the table files listed in `manifest.json`
+    pub fn lint_directory(&mut self, dir: &Path) -> Result<()> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest: DirectoryManifest = fs::read(&manifest_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(serde_json::from_slice(&content)?))
+            .map_err(|e| anyhow!("Can't read `{}`: {}", manifest_path.display(), e))?;
+        self.lint_sql_file(&dir.join(SCHEMA_FILE))?;
+
+        let delimiter = match manifest.delimiter.as_bytes() {
+            [delimiter] => *delimiter,
+            _ => {
+                return Err(anyhow!(
+                    "Invalid delimiter `{}` in `{}`",
+                    manifest.delimiter,
+                    manifest_path.display()
+                ))
+            }
+        };
+        for table in &manifest.tables {
+            let path = dir.join(&table.file);
+            let name = path.display().to_string();
+            match File::open(&path) {
+                Ok(file) => self.lint_csv(
+                    &name,
+                    BufReader::new(file),
+                    delimiter,
+                    table.columns.len(),
+                    table.rows,
+                )?,
+                Err(e) => {
+                    self.report.files += 1;
+                    self.error(
+                        &name,
+                        Position::default(),
+                        format!("can't read the file of `{}`: {}", table.name, e),
+                        None,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the field counts of the records (the first one is the header) and the row count
+    pub fn lint_csv<R: BufRead>(
+        &mut self,
+        file: &str,
+        mut reader: R,
+        delimiter: u8,
+        columns: usize,
+        rows: u64,
+    ) -> Result<()> {
+        self.report.files += 1;
+        let mut position = Position { offset: 0, line: 1 };
+        let mut record_start = position;
+        let mut fields = 1;
+        let mut quoted = false;
+        let mut records = 0;
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            for b in &line {
+                match *b {
+                    b'"' => quoted = !quoted,
+                    b if b == delimiter && !quoted => fields += 1,
+                    _ => (),
+                }
+            }
+            position.offset += line.len() as u64;
+            position.line += 1;
+            if quoted {
+                continue;
+            }
+
+            if fields != columns {
+                let what = if records == 0 { "header" } else { "record" };
+                self.error(
+                    file,
+                    record_start,
+                    format!(
+                        "the {} has {} fields, the table has {} columns",
+                        what, fields, columns
+                    ),
+                    None,
+                );
+            }
+            records += 1;
+            record_start = position;
+            fields = 1;
+        }
+
+        let data_rows = records.max(1) - 1;
+        self.report.rows += data_rows;
+        // the row count is not checked after an unterminated record
+        if quoted {
+            self.error(
+                file,
+                record_start,
+                "the quoted field is not terminated".to_string(),
+                None,
+            );
+        } else if records == 0 {
+            self.error(file, position, "the header is missing".to_string(), None);
+        } else if data_rows != rows {
+            self.error(
+                file,
+                position,
+                format!("the file has {} rows, the manifest has {}", data_rows, rows),
+                None,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn report(&self) -> &LintReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> LintReport {
+        self.report
+    }
+
+    fn error(
+        &mut self,
+        file: &str,
+        position: Position,
+        message: String,
+        statement: Option<String>,
+    ) {
+        self.report.error_count += 1;
+        if self.report.errors.len() < self.max_errors {
+            self.report.errors.push(LintError {
+                file: file.to_string(),
+                position,
+                message,
+                statement,
+            });
+        }
+    }
+}
+
+/// The quoted parts of the statements
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Quoted {
+    None,
+    String { escapes: bool },
+    Identifier,
+    Dollar(Vec<u8>),
+    Comment(usize),
+}
+
+impl Quoted {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::String { .. } => "string",
+            Self::Identifier => "quoted identifier",
+            Self::Dollar(_) => "dollar-quoted string",
+            Self::Comment(_) => "comment",
+        }
+    }
+}
+
+struct Statement {
+    start: Position,
+    /// The text without the comments
+    text: Vec<u8>,
+    parens: i64,
+    /// `)` without `(`
+    unbalanced: bool,
+}
+
+/// A COPY block
+struct Copy {
+    start: Position,
+    statement: String,
+    /// The column count if the header has the column list (and the text format)
+    columns: Option<usize>,
+}
+
+/// Splits the dump into statements line by line (the COPY data lines are not split)
+struct SqlLexer {
+    file: String,
+    position: Position,
+    quoted: Quoted,
+    quoted_start: Position,
+    statement: Option<Statement>,
+    copy: Option<Copy>,
+}
+
+impl SqlLexer {
+    fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            position: Position { offset: 0, line: 1 },
+            quoted: Quoted::None,
+            quoted_start: Position::default(),
+            statement: None,
+            copy: None,
+        }
+    }
+
+    fn line(&mut self, linter: &mut OutputLinter, line: &[u8]) {
+        if self.copy.is_some() {
+            self.copy_line(linter, line);
+        } else {
+            self.sql_line(linter, line);
+        }
+        self.position.offset += line.len() as u64;
+        self.position.line += 1;
+    }
+
+    fn copy_line(&mut self, linter: &mut OutputLinter, line: &[u8]) {
+        let data = line.strip_suffix(b"\n").unwrap_or(line);
+        if data == b"\\." {
+            self.copy = None;
+            return;
+        }
+        linter.report.rows += 1;
+        if let Some(Copy {
+            columns: Some(columns),
+            statement,
+            ..
+        }) = &self.copy
+        {
+            let fields = copy_codec::fields(data).count();
+            if fields != *columns {
+                let statement = Some(statement.clone());
+                linter.error(
+                    &self.file,
+                    self.position,
+                    format!(
+                        "the row has {} fields, the COPY header has {} columns",
+                        fields, columns
+                    ),
+                    statement,
+                );
+            }
+        }
+    }
+
+    fn sql_line(&mut self, linter: &mut OutputLinter, line: &[u8]) {
+        // psql meta-commands (e.g. `\connect`)
+        if self.statement.is_none()
+            && self.quoted == Quoted::None
+            && line.trim_ascii_start().starts_with(b"\\")
+        {
+            return;
+        }
+
+        let mut i = 0;
+        while i < line.len() {
+            let b = line[i];
+            let position = Position {
+                offset: self.position.offset + i as u64,
+                line: self.position.line,
+            };
+            match &self.quoted {
+                Quoted::Comment(depth) => {
+                    let depth = *depth;
+                    if line[i..].starts_with(b"/*") {
+                        self.quoted = Quoted::Comment(depth + 1);
+                        i += 2;
+                    } else if line[i..].starts_with(b"*/") {
+                        self.quoted = match depth {
+                            1 => Quoted::None,
+                            _ => Quoted::Comment(depth - 1),
+                        };
+                        self.push(b" ");
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                    continue;
+                }
+                Quoted::String { escapes } => {
+                    let escapes = *escapes;
+                    if escapes && b == b'\\' {
+                        self.push(&line[i..(i + 2).min(line.len())]);
+                        i += 2;
+                        continue;
+                    }
+                    // doubled quotes are the same as two strings in a row
+                    if b == b'\'' {
+                        self.quoted = Quoted::None;
+                    }
+                    self.push(&[b]);
+                    i += 1;
+                    continue;
+                }
+                Quoted::Identifier => {
+                    if b == b'"' {
+                        self.quoted = Quoted::None;
+                    }
+                    self.push(&[b]);
+                    i += 1;
+                    continue;
+                }
+                Quoted::Dollar(tag) => {
+                    if line[i..].starts_with(tag) {
+                        let tag = tag.clone();
+                        self.push(&tag);
+                        self.quoted = Quoted::None;
+                        i += tag.len();
+                    } else {
+                        self.push(&[b]);
+                        i += 1;
+                    }
+                    continue;
+                }
+                Quoted::None => (),
+            }
+
+            if line[i..].starts_with(b"--") {
+                break;
+            }
+            if line[i..].starts_with(b"/*") {
+                self.quoted = Quoted::Comment(1);
+                self.quoted_start = position;
+                i += 2;
+                continue;
+            }
+            if self.statement.is_none() {
+                if b.is_ascii_whitespace() || b == b';' {
+                    i += 1;
+                    continue;
+                }
+                self.statement = Some(Statement {
+                    start: position,
+                    text: vec![],
+                    parens: 0,
+                    unbalanced: false,
+                });
+            }
+
+            match b {
+                b'\'' => {
+                    let text = self.text();
+                    let escapes = matches!(text.last(), Some(b'E' | b'e'))
+                        && !matches!(text.len().checked_sub(2).map(|i| text[i]),
+                            Some(c) if c.is_ascii_alphanumeric() || c == b'_');
+                    self.quoted = Quoted::String { escapes };
+                    self.quoted_start = position;
+                }
+                b'"' => {
+                    self.quoted = Quoted::Identifier;
+                    self.quoted_start = position;
+                }
+                b'$' => {
+                    if let Some(tag) = dollar_tag(&line[i..]) {
+                        let tag = tag.to_vec();
+                        self.push(&tag);
+                        i += tag.len();
+                        self.quoted = Quoted::Dollar(tag);
+                        self.quoted_start = position;
+                        continue;
+                    }
+                }
+                b'(' => self.statement_mut().parens += 1,
+                b')' => {
+                    let statement = self.statement_mut();
+                    statement.parens -= 1;
+                    if statement.parens < 0 {
+                        statement.unbalanced = true;
+                    }
+                }
+                _ => (),
+            }
+            self.push(&[b]);
+            i += 1;
+            if b == b';' {
+                if let Some(statement) = self.statement.take() {
+                    self.check_statement(linter, statement);
+                }
+                // the COPY data starts on the next line
+                if self.copy.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn check_statement(&mut self, linter: &mut OutputLinter, statement: Statement) {
+        linter.report.statements += 1;
+        let text = &statement.text;
+        let snippet = snippet(text);
+        let mut messages = vec![];
+
+        if statement.unbalanced {
+            messages.push("`)` without `(`".to_string());
+        } else if statement.parens > 0 {
+            messages.push("`(` is not closed".to_string());
+        }
+        let keyword: String = text
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic())
+            .map(|b| b.to_ascii_uppercase() as char)
+            .collect();
+        if keyword.is_empty() {
+            // e.g. `(SELECT 1) UNION (SELECT 2);`
+            if text[0] != b'(' {
+                messages.push(format!(
+                    "unexpected `{}` at the beginning of the statement",
+                    String::from_utf8_lossy(&text[..1])
+                ));
+            }
+        } else if !STATEMENT_KEYWORDS.contains(&keyword.as_str()) {
+            messages.push(format!("unknown statement `{}`", keyword));
+        }
+        let copy = keyword == "COPY" && linter.copy_from_stdin.is_match(text);
+        let columns = if copy {
+            self.copy_columns(linter, text, &mut messages)
+        } else {
+            None
+        };
+        for message in messages {
+            linter.error(&self.file, statement.start, message, Some(snippet.clone()));
+        }
+        if copy {
+            linter.report.copy_blocks += 1;
+            self.copy = Some(Copy {
+                start: statement.start,
+                statement: snippet,
+                columns,
+            });
+        }
+    }
+
+    /// Validates the COPY header, returns the column count of the text format rows (if it's known)
+    fn copy_columns(
+        &self,
+        linter: &OutputLinter,
+        text: &[u8],
+        messages: &mut Vec<String>,
+    ) -> Option<usize> {
+        let columns = match linter.copy_header.captures(text) {
+            Some(captures) => {
+                let options = captures.get(2).map_or(&b""[..], |m| m.as_bytes());
+                let text_format = options.trim_ascii().is_empty();
+                match captures.get(1) {
+                    Some(list) => {
+                        let names = split_list(list.as_bytes());
+                        for name in &names {
+                            let name = String::from_utf8_lossy(name.trim_ascii());
+                            if !linter.column_name.is_match(&name) {
+                                messages
+                                    .push(format!("invalid column `{}` in the COPY header", name));
+                            }
+                        }
+                        text_format.then_some(names.len())
+                    }
+                    None => None,
+                }
+            }
+            None => {
+                messages.push("invalid COPY header".to_string());
+                None
+            }
+        };
+        columns
+    }
+
+    fn finish(&mut self, linter: &mut OutputLinter) {
+        if let Some(copy) = self.copy.take() {
+            linter.error(
+                &self.file,
+                copy.start,
+                "the COPY data is not terminated with `\\.`".to_string(),
+                Some(copy.statement),
+            );
+            return;
+        }
+        let statement = self.statement.take();
+        let snippet = statement.as_ref().map(|s| snippet(&s.text));
+        if self.quoted != Quoted::None {
+            linter.error(
+                &self.file,
+                self.quoted_start,
+                format!("the {} is not terminated", self.quoted.name()),
+                snippet,
+            );
+        } else if let Some(statement) = statement {
+            linter.error(
+                &self.file,
+                statement.start,
+                "the statement is not terminated with `;`".to_string(),
+                snippet,
+            );
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if let Some(statement) = &mut self.statement {
+            statement.text.extend_from_slice(bytes);
+        }
+    }
+
+    fn text(&self) -> &[u8] {
+        self.statement.as_ref().map_or(&[], |s| &s.text)
+    }
+
+    fn statement_mut(&mut self) -> &mut Statement {
+        self.statement.as_mut().expect("the statement is started")
+    }
+}
+
+/// The tag of the dollar-quoted string (`$$` or `$tag$`) at the beginning
+fn dollar_tag(s: &[u8]) -> Option<&[u8]> {
+    let end = s[1..].iter().position(|b| *b == b'$')? + 2;
+    let tag = &s[1..end - 1];
+    let valid = !tag.first().is_some_and(|b| b.is_ascii_digit())
+        && tag
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || *b == b'_' || *b >= 0x80);
+    valid.then(|| &s[..end])
+}
+
+/// Splits the list by commas (outside quoted identifiers)
+fn split_list(list: &[u8]) -> Vec<&[u8]> {
+    let mut result = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, b) in list.iter().enumerate() {
+        match b {
+            b'"' => quoted = !quoted,
+            b',' if !quoted => {
+                result.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    result.push(&list[start..]);
+    result
+}
+
+/// The beginning of the statement (on one line)
+fn snippet(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    let mut snippet = String::new();
+    for word in text.split_whitespace() {
+        if !snippet.is_empty() {
+            snippet.push(' ');
+        }
+        snippet.push_str(word);
+        if snippet.chars().count() > SNIPPET_LENGTH {
+            let mut truncated: String = snippet.chars().take(SNIPPET_LENGTH).collect();
+            truncated.push_str("...");
+            return truncated;
+        }
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(sql: &str) -> LintReport {
+        let mut linter = OutputLinter::default();
+        linter.lint_sql("dump.sql", sql.as_bytes()).unwrap();
+        linter.into_report()
+    }
+
+    fn errors(sql: &str) -> Vec<String> {
+        lint(sql).errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn valid() {
+        let sql = r#"--
+-- PostgreSQL database dump
+--
+\restrict abc
+
+SET statement_timeout = 0;
+SELECT pg_catalog.set_config('search_path', '', false);
+
+CREATE FUNCTION public.f() RETURNS text
+    LANGUAGE plpgsql
+    AS $_$
+BEGIN
+  RETURN 'it''s; (';  -- not a comment end */
+END;
+$_$;
+
+/* a /* nested */ comment; */
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    "na""me" text DEFAULT E'\'(',
+    note text CHECK (note <> ';')
+);
+COMMENT ON TABLE public.users IS 'a ) b';
+
+---
+--- Dump table: public.users
+---
+
+COPY "public"."users"("id", "na""me", "note") FROM STDIN;
+1	Ann	\N
+2	a;b	(
+\.
+(SELECT 1);
+;
+\unrestrict abc
+--"#;
+        let report = lint(sql);
+        assert_eq!(report.errors, vec![]);
+        assert!(report.is_ok());
+        assert_eq!(report.files, 1);
+        assert_eq!(report.statements, 7);
+        assert_eq!(report.copy_blocks, 1);
+        assert_eq!(report.rows, 2);
+    }
+
+    #[test]
+    fn statement_errors() {
+        assert_eq!(
+            errors("SELECT 1;\nCREAT TABLE t (id int);\n"),
+            vec!["dump.sql:2 (byte 10): unknown statement `CREAT` in `CREAT TABLE t (id int);`"]
+        );
+        assert_eq!(
+            errors("CREATE TABLE t (id int;\nALTER TABLE t OWNER TO x);"),
+            vec![
+                "dump.sql:1 (byte 0): `(` is not closed in `CREATE TABLE t (id int;`",
+                "dump.sql:2 (byte 24): `)` without `(` in `ALTER TABLE t OWNER TO x);`",
+            ]
+        );
+        assert_eq!(
+            errors("SET a = 1;\n\"users\";"),
+            vec!["dump.sql:2 (byte 11): unexpected `\"` at the beginning of the statement in `\"users\";`"]
+        );
+        assert_eq!(
+            errors("SET a = 1;\nDROP TABLE t"),
+            vec![
+                "dump.sql:2 (byte 11): the statement is not terminated with `;` in `DROP TABLE t`"
+            ]
+        );
+        assert_eq!(
+            errors("SET a = 1;\nCOMMENT ON TABLE t IS 'x;\n"),
+            vec![
+                "dump.sql:2 (byte 33): the string is not terminated in `COMMENT ON TABLE t IS 'x;`"
+            ]
+        );
+        assert_eq!(
+            errors("CREATE FUNCTION f() AS $$ SELECT 1;"),
+            vec!["dump.sql:1 (byte 23): the dollar-quoted string is not terminated in `CREATE FUNCTION f() AS $$ SELECT 1;`"]
+        );
+        assert_eq!(
+            errors("SELECT 1; /* x"),
+            vec!["dump.sql:1 (byte 10): the comment is not terminated"]
+        );
+    }
+
+    #[test]
+    fn copy_errors() {
+        let header = "COPY \"public\".\"users\"(\"id\", \"name\") FROM STDIN;\n";
+        assert_eq!(
+            errors(&format!("{}1\tAnn\n2\n3\tBob\tx\n\\.\n", header)),
+            vec![
+                "dump.sql:3 (byte 54): the row has 1 fields, the COPY header has 2 columns in `COPY \"public\".\"users\"(\"id\", \"name\") FROM STDIN;`",
+                "dump.sql:4 (byte 56): the row has 3 fields, the COPY header has 2 columns in `COPY \"public\".\"users\"(\"id\", \"name\") FROM STDIN;`",
+            ]
+        );
+        assert_eq!(
+            errors(&format!("{}1\tAnn\n", header)),
+            vec!["dump.sql:1 (byte 0): the COPY data is not terminated with `\\.` in `COPY \"public\".\"users\"(\"id\", \"name\") FROM STDIN;`"]
+        );
+        assert_eq!(
+            errors("COPY users(id, 1x) FROM STDIN;\n\\.\nCOPY (users) FROM STDIN;\n\\.\n"),
+            vec![
+                "dump.sql:1 (byte 0): invalid column `1x` in the COPY header in `COPY users(id, 1x) FROM STDIN;`",
+                "dump.sql:3 (byte 34): invalid COPY header in `COPY (users) FROM STDIN;`",
+            ]
+        );
+        // the rows are not checked without the column list or in other formats
+        let report = lint("COPY users FROM STDIN;\n1\n1\t2\n\\.\nCOPY users(id) FROM STDIN (FORMAT csv);\n1,2\n\\.\n");
+        assert_eq!(report.errors, vec![]);
+        assert_eq!(report.copy_blocks, 2);
+        assert_eq!(report.rows, 3);
+    }
+
+    #[test]
+    fn max_errors() {
+        let mut linter = OutputLinter::new(2);
+        linter
+            .lint_sql("dump.sql", "A;\nB;\nC;\n".as_bytes())
+            .unwrap();
+        let report = linter.into_report();
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.error_count, 3);
+        assert_eq!(
+            report.to_string(),
+            "Output lint FAILED: 1 files, 3 statements, 0 COPY blocks, 0 rows, 3 errors\n  \
+            dump.sql:1 (byte 0): unknown statement `A` in `A;`\n  \
+            dump.sql:2 (byte 3): unknown statement `B` in `B;`\n  \
+            ... and 1 more errors\n"
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let lint = |csv: &str, rows| {
+            let mut linter = OutputLinter::default();
+            linter
+                .lint_csv("users.csv", csv.as_bytes(), b',', 2, rows)
+                .unwrap();
+            linter
+                .into_report()
+                .errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lint("id,name\n1,\"a,\"\"b\"\"\nc\"\n2,\n", 2),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            lint("id,name\n1,a,b\n", 1),
+            vec!["users.csv:2 (byte 8): the record has 3 fields, the table has 2 columns"]
+        );
+        assert_eq!(
+            lint("id\n1,a\n", 1),
+            vec!["users.csv:1 (byte 0): the header has 1 fields, the table has 2 columns"]
+        );
+        assert_eq!(
+            lint("id,name\n1,\"a\n", 1),
+            vec!["users.csv:2 (byte 8): the quoted field is not terminated"]
+        );
+        assert_eq!(
+            lint("id,name\n1,a\n", 2),
+            vec!["users.csv:3 (byte 12): the file has 1 rows, the manifest has 2"]
+        );
+        assert_eq!(
+            lint("", 0),
+            vec!["users.csv:1 (byte 0): the header is missing"]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use super::{query_wrapper::QueryWrapper, replica::RetryBuffer};
+use crate::{
+    indicator::{Indicator, TableInfo},
+    staged::{RunState, TableOutput, TableRun},
+};
+use anyhow::{anyhow, Result};
+use postgres::{Client, IsolationLevel};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How often the workers send the progress of their tables
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the worker sessions import the snapshot of the dump transaction (the other isolation levels
+/// don't keep one snapshot for all queries)
+pub fn exports_snapshot(level: Option<IsolationLevel>) -> bool {
+    matches!(
+        level,
+        Some(IsolationLevel::RepeatableRead | IsolationLevel::Serializable)
+    )
+}
+
+/// Exports the snapshot of the dump transaction for the worker sessions
+pub fn export_snapshot(qw: &mut QueryWrapper) -> Result<String> {
+    Ok(qw
+        .query_one("SELECT pg_catalog.pg_export_snapshot()", &[])?
+        .get(0))
+}
+
+/// Starts the transaction of a worker session with the isolation level of the dump transaction
+/// (and its snapshot). The session stays in the transaction until it is closed.
+pub fn begin(
+    client: &mut Client,
+    level: Option<IsolationLevel>,
+    snapshot: Option<&str>,
+) -> Result<()> {
+    let level = match level {
+        Some(level) => level,
+        None => return Ok(()),
+    };
+    let mut sql = format!("BEGIN ISOLATION LEVEL {};", level_sql(level));
+    if let Some(snapshot) = snapshot {
+        sql.push_str(&format!(
+            "\nSET TRANSACTION SNAPSHOT '{}';",
+            snapshot.replace('\'', "''")
+        ));
+    }
+    client.batch_execute(&sql).map_err(|e| e.into())
+}
+
+fn level_sql(level: IsolationLevel) -> &'static str {
+    match level {
+        IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+        IsolationLevel::ReadCommitted => "READ COMMITTED",
+        IsolationLevel::RepeatableRead => "REPEATABLE READ",
+        _ => "SERIALIZABLE",
+    }
+}
+
+/// The table dumped by a worker: its part of the dump (it is written in the table order),
+/// the result and the started table of the worker run
+pub struct Segment {
+    pub buffer: Option<RetryBuffer>,
+    pub result: Result<TableOutput>,
+    pub run: Option<RunState>,
+}
+
+enum Event {
+    Started(TableInfo),
+    /// The table name, the dumped rows and bytes
+    Progress(String, u64, u64),
+    Debug(String),
+    Warning(String),
+    Done(String, Box<Segment>),
+}
+
+/// The workers of the parallel dump: every worker takes the next table from the queue (in the dump order)
+/// and dumps it to a segment with its own session. The dumper waits for the segments in the table order,
+/// the events of the tables are passed to the indicator meanwhile (they are interleaved).
+pub struct Pool {
+    tables: HashSet<String>,
+    events: Receiver<Event>,
+    /// The started tables (for the progress events)
+    started: HashMap<String, TableInfo>,
+    /// The segments that are dumped before their turn
+    done: HashMap<String, Segment>,
+    cancelled: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    /// `tables` are the names and the tables in the dump order, `job` makes the dumping function
+    /// of a worker (it returns the result and the segment)
+    pub fn start<S, T, J, F>(sessions: Vec<S>, tables: Vec<(String, T)>, mut job: J) -> Self
+    where
+        S: 'static + Send,
+        T: 'static + Send,
+        J: FnMut() -> F,
+        F: 'static
+            + Send
+            + FnMut(&mut S, &T, &mut TableRun) -> (Result<TableOutput>, Option<RetryBuffer>),
+    {
+        let names = tables.iter().map(|(name, _)| name.clone()).collect();
+        let queue = Arc::new(Mutex::new(VecDeque::from(tables)));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, events) = mpsc::channel();
+        let workers = sessions
+            .into_iter()
+            .map(|mut session| {
+                let queue = queue.clone();
+                let cancelled = cancelled.clone();
+                let sender = sender.clone();
+                let mut dump = job();
+                thread::spawn(move || {
+                    while !cancelled.load(Ordering::SeqCst) {
+                        let next = match queue.lock() {
+                            Ok(mut queue) => queue.pop_front(),
+                            Err(_) => None,
+                        };
+                        let Some((name, table)) = next else {
+                            break;
+                        };
+                        let events = WorkerEvents::new(name.clone(), sender.clone());
+                        let mut run = TableRun::new(&events);
+                        let (result, buffer) = dump(&mut session, &table, &mut run);
+                        let segment = Segment {
+                            buffer,
+                            result,
+                            run: run.suspend(),
+                        };
+                        if sender.send(Event::Done(name, Box::new(segment))).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            tables: names,
+            events,
+            started: HashMap::new(),
+            done: HashMap::new(),
+            cancelled,
+            workers,
+        }
+    }
+
+    /// Whether the table is dumped by the workers
+    pub fn contains(&self, table: &str) -> bool {
+        self.tables.contains(table)
+    }
+
+    /// Waits for the segment of the table, passes the events of all tables to the indicator meanwhile
+    pub fn wait(&mut self, table: &str, events: &dyn Indicator) -> Result<Segment> {
+        loop {
+            if let Some(segment) = self.done.remove(table) {
+                self.started.remove(table);
+                return Ok(segment);
+            }
+            match self.events.recv() {
+                Ok(Event::Started(info)) => {
+                    events.table_started(&info);
+                    self.started.insert(info.name.clone(), info);
+                }
+                Ok(Event::Progress(name, dumped, bytes)) => {
+                    if let Some(info) = self.started.get(&name) {
+                        events.data_progress(info, dumped, bytes);
+                    }
+                }
+                Ok(Event::Debug(msg)) => events.debug_msg(&msg),
+                Ok(Event::Warning(msg)) => events.warning_msg(&msg),
+                Ok(Event::Done(name, segment)) => {
+                    self.done.insert(name, *segment);
+                }
+                Err(_) => {
+                    return Err(anyhow!(
+                        "The workers of the parallel dump have stopped before dumping {}",
+                        table
+                    ))
+                }
+            }
+        }
+    }
+}
+
+// The workers finish their current tables and don't take the next ones
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Sends the events of the table dumped by a worker to the dumper (the failures are reported by the dumper)
+struct WorkerEvents {
+    table: String,
+    sender: Sender<Event>,
+    sent: Mutex<Option<Instant>>,
+}
+
+impl WorkerEvents {
+    fn new(table: String, sender: Sender<Event>) -> Self {
+        Self {
+            table,
+            sender,
+            sent: Mutex::new(None),
+        }
+    }
+
+    fn send(&self, event: Event) {
+        // the dumper is gone, the worker stops after the table
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Indicator for WorkerEvents {
+    fn table_started(&self, table: &TableInfo) {
+        *self.sent.lock().unwrap() = None;
+        self.send(Event::Started(table.clone()));
+    }
+
+    fn data_progress(&self, _table: &TableInfo, dumped: u64, bytes: u64) {
+        {
+            let mut sent = self.sent.lock().unwrap();
+            if sent.is_some_and(|sent| sent.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            *sent = Some(Instant::now());
+        }
+        self.send(Event::Progress(self.table.clone(), dumped, bytes));
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        self.send(Event::Debug(msg.to_string()));
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        self.send(Event::Warning(msg.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::TableStats;
+    use datanymizer_engine::TempDisk;
+    use std::{collections::BTreeMap, io::Write};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl Indicator for Recorder {
+        fn table_started(&self, table: &TableInfo) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("started: {}", table.name));
+        }
+
+        fn table_finished(&self, stats: &TableStats) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("finished: {} {}", stats.name, stats.rows));
+        }
+
+        fn warning_msg(&self, msg: &str) {
+            self.0.lock().unwrap().push(format!("warning: {}", msg));
+        }
+    }
+
+    // The tables are dumped as `name:rows`, the table `broken` fails
+    fn pool(temp_disk: &Arc<TempDisk>, tables: &[&str]) -> Pool {
+        let tables = tables
+            .iter()
+            .map(|t| (t.to_string(), t.to_string()))
+            .collect();
+        Pool::start(vec![(); 3], tables, || {
+            let temp_disk = temp_disk.clone();
+            move |_: &mut (), table: &String, run: &mut TableRun| {
+                let mut buffer = RetryBuffer::new(&temp_disk, table, 0).unwrap();
+                run.start(TableInfo {
+                    name: table.clone(),
+                    rows: 2,
+                });
+                let result = match table.as_str() {
+                    "broken" => Err(anyhow!("{} is broken", table)),
+                    _ => {
+                        writeln!(buffer, "{}:2", table).unwrap();
+                        run.events().warning_msg(&format!("{} is dumped", table));
+                        run.progress(2, 8);
+                        Ok(TableOutput {
+                            rows: 2,
+                            manifest: None,
+                            outbox_events: BTreeMap::new(),
+                            saved_bytes: None,
+                        })
+                    }
+                };
+                (result, Some(buffer))
+            }
+        })
+    }
+
+    #[test]
+    fn segments_in_order() {
+        let temp_disk = Arc::new(TempDisk::default());
+        let tables = ["a", "b", "c", "d", "e"];
+        let mut pool = pool(&temp_disk, &tables);
+        assert!(pool.contains("c"));
+        assert!(!pool.contains("f"));
+
+        let recorder = Recorder::default();
+        let mut output = vec![];
+        for table in tables {
+            let segment = pool.wait(table, &recorder).unwrap();
+            assert_eq!(segment.result.unwrap().rows, 2);
+            let mut run = TableRun::new(&recorder);
+            run.resume(segment.run.unwrap());
+            segment.buffer.unwrap().copy_to(&mut output).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "a:2\nb:2\nc:2\nd:2\ne:2\n"
+        );
+
+        let events = recorder.take();
+        for table in tables {
+            let count = |event: String| events.iter().filter(|e| **e == event).count();
+            assert_eq!(count(format!("started: {}", table)), 1);
+            assert_eq!(count(format!("warning: {} is dumped", table)), 1);
+        }
+        drop(pool);
+        assert_eq!(temp_disk.used(), 0);
+    }
+
+    #[test]
+    fn failed_table() {
+        let temp_disk = Arc::new(TempDisk::default());
+        let mut pool = pool(&temp_disk, &["a", "broken", "c"]);
+        let recorder = Recorder::default();
+        assert!(pool.wait("a", &recorder).unwrap().result.is_ok());
+        let segment = pool.wait("broken", &recorder).unwrap();
+        assert_eq!(segment.result.unwrap_err().to_string(), "broken is broken");
+        assert!(segment.run.is_some());
+        assert!(pool.wait("c", &recorder).unwrap().result.is_ok());
+
+        // there are no more segments
+        let error = pool.wait("a", &recorder).err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "The workers of the parallel dump have stopped before dumping a"
+        );
+    }
+}
@@ -29,6 +29,41 @@ pub fn copy_out<T>(&mut self, query: &T) -> Result<CopyOutReader<'_>, postgres::
         }
     }
 
+    pub fn batch_execute(&mut self, query: &str) -> Result<(), postgres::Error> {
+        match self {
+            Self::WithTransaction(t) => t.batch_execute(query),
+            Self::WithoutTransaction(c) => c.batch_execute(query),
+        }
+    }
+
+    pub fn execute<T>(
+        &mut self,
+        query: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, postgres::Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        match self {
+            Self::WithTransaction(t) => t.execute(query, params),
+            Self::WithoutTransaction(c) => c.execute(query, params),
+        }
+    }
+
+    pub fn query<T>(
+        &mut self,
+        query: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, postgres::Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        match self {
+            Self::WithTransaction(t) => t.query(query, params),
+            Self::WithoutTransaction(c) => c.query(query, params),
+        }
+    }
+
     pub fn query_one<T>(
         &mut self,
         query: &T,
@@ -0,0 +1,177 @@
+use anyhow::{Error, Result};
+use datanymizer_engine::{TempDisk, TempDiskError, TempFile};
+use postgres::{error::SqlState, Client};
+use std::{
+    io::{self, BufWriter, Seek, Write},
+    sync::Arc,
+};
+
+/// The dumper's own timeouts are disabled, the long queries on a replica are expected
+const SESSION_OPTIONS: &str = "SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET idle_in_transaction_session_timeout = 0;";
+
+/// The message of the queries cancelled by conflicts with recovery
+/// (the code is `40001`, or `40P01` for the buffer pin deadlocks)
+const CONFLICT_MESSAGE: &str = "conflict with recovery";
+
+/// The settings of a standby server that affect the long queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaSettings {
+    pub hot_standby_feedback: bool,
+    /// E.g., `30s` (`-1` means waiting forever)
+    pub max_standby_streaming_delay: String,
+}
+
+impl ReplicaSettings {
+    /// Returns `None` if the server is not in recovery (not a replica)
+    pub fn fetch(client: &mut Client) -> Result<Option<Self>> {
+        let in_recovery: bool = client.query_one("SELECT pg_is_in_recovery()", &[])?.get(0);
+        if !in_recovery {
+            return Ok(None);
+        }
+
+        let feedback: String = client.query_one("SHOW hot_standby_feedback", &[])?.get(0);
+        let delay: String = client
+            .query_one("SHOW max_standby_streaming_delay", &[])?
+            .get(0);
+        Ok(Some(Self {
+            hot_standby_feedback: feedback == "on",
+            max_standby_streaming_delay: delay,
+        }))
+    }
+
+    /// Sets the session options for long queries (`hot_standby_feedback` and `max_standby_streaming_delay`
+    /// are the server settings, they can't be changed here)
+    pub fn prepare_session(client: &mut Client) -> Result<()> {
+        client.batch_execute(SESSION_OPTIONS).map_err(|e| e.into())
+    }
+
+    /// Long queries can be cancelled by conflicts with recovery
+    pub fn has_conflicts(&self) -> bool {
+        !self.hot_standby_feedback && self.max_standby_streaming_delay != "-1"
+    }
+
+    pub fn remediation(&self) -> String {
+        format!(
+            "enable `hot_standby_feedback` (it is `{}`) or increase `max_standby_streaming_delay` (it is `{}`) \
+            on the replica, or use --prefer-replica-safe",
+            if self.hot_standby_feedback { "on" } else { "off" },
+            self.max_standby_streaming_delay
+        )
+    }
+}
+
+/// Whether the query is cancelled by a conflict with recovery on a replica
+pub fn is_recovery_conflict(error: &Error) -> bool {
+    error.chain().any(|e| {
+        let db_error = e
+            .downcast_ref::<postgres::Error>()
+            .or_else(|| {
+                e.downcast_ref::<io::Error>()
+                    .and_then(|e| e.get_ref())
+                    .and_then(|e| e.downcast_ref::<postgres::Error>())
+            })
+            .and_then(|e| e.as_db_error());
+        db_error.is_some_and(|e| is_conflict(e.code(), e.message()))
+    })
+}
+
+fn is_conflict(code: &SqlState, message: &str) -> bool {
+    (*code == SqlState::T_R_SERIALIZATION_FAILURE || *code == SqlState::T_R_DEADLOCK_DETECTED)
+        && message.contains(CONFLICT_MESSAGE)
+}
+
+/// A temporary file for the table data, so the table can be dumped again after a conflict
+/// (the file is removed when the buffer is dropped)
+pub struct RetryBuffer {
+    writer: BufWriter<TempFile>,
+}
+
+impl RetryBuffer {
+    /// `component` names the buffer in the errors, `expected` bytes must fit in the budget and the free space
+    pub fn new(
+        temp_disk: &Arc<TempDisk>,
+        component: &str,
+        expected: u64,
+    ) -> Result<Self, TempDiskError> {
+        let writer = BufWriter::new(temp_disk.create(component, expected)?);
+        Ok(Self { writer })
+    }
+
+    /// Copies the buffered data to the writer
+    pub fn copy_to(self, w: &mut dyn Write) -> Result<()> {
+        let mut file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        file.rewind()?;
+        io::copy(&mut file, w)?;
+        Ok(())
+    }
+}
+
+impl Write for RetryBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn is_conflict() {
+        let message = "canceling statement due to conflict with recovery";
+        assert!(super::is_conflict(
+            &SqlState::T_R_SERIALIZATION_FAILURE,
+            message
+        ));
+        assert!(super::is_conflict(
+            &SqlState::T_R_DEADLOCK_DETECTED,
+            message
+        ));
+        assert!(!super::is_conflict(&SqlState::QUERY_CANCELED, message));
+        assert!(!super::is_conflict(
+            &SqlState::T_R_SERIALIZATION_FAILURE,
+            "could not serialize access due to concurrent update"
+        ));
+
+        assert!(!is_recovery_conflict(&anyhow!("{}", message)));
+    }
+
+    #[test]
+    fn has_conflicts() {
+        let settings = |feedback: bool, delay: &str| ReplicaSettings {
+            hot_standby_feedback: feedback,
+            max_standby_streaming_delay: delay.to_string(),
+        };
+        assert!(settings(false, "30s").has_conflicts());
+        assert!(!settings(true, "30s").has_conflicts());
+        assert!(!settings(false, "-1").has_conflicts());
+        assert_eq!(
+            settings(false, "30s").remediation(),
+            "enable `hot_standby_feedback` (it is `off`) or increase `max_standby_streaming_delay` (it is `30s`) \
+            on the replica, or use --prefer-replica-safe"
+        );
+    }
+
+    #[test]
+    fn retry_buffer() {
+        let temp_disk = Arc::new(TempDisk::default());
+        let mut buffer = RetryBuffer::new(&temp_disk, "the staging of `users`", 0).unwrap();
+        let path = buffer.writer.get_ref().path().to_path_buf();
+        buffer
+            .write_all(b"COPY \"public\".\"users\"\n1\tAnn\n")
+            .unwrap();
+
+        let mut out = vec![];
+        buffer.copy_to(&mut out).unwrap();
+        assert_eq!(out, b"COPY \"public\".\"users\"\n1\tAnn\n");
+        assert!(!path.exists());
+        assert_eq!(temp_disk.used(), 0);
+    }
+}
@@ -0,0 +1,712 @@
+use super::{
+    ddl,
+    object_names::{quote_name, unquote},
+};
+use datanymizer_engine::LogicalReplication;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+/// The publications and subscriptions found in the `pg_dump` output
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplicationReport {
+    pub stripped_publications: BTreeSet<String>,
+    pub stripped_subscriptions: BTreeSet<String>,
+    /// The publications from `publication_map` (by the source names)
+    pub mapped_publications: BTreeSet<String>,
+    /// The tables that are removed from the mapped publications (they aren't dumped)
+    pub removed_tables: usize,
+}
+
+impl ReplicationReport {
+    pub fn is_empty(&self) -> bool {
+        self.stripped_publications.is_empty()
+            && self.stripped_subscriptions.is_empty()
+            && self.mapped_publications.is_empty()
+    }
+}
+
+impl Display for ReplicationReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let list = |names: &BTreeSet<String>| names.iter().cloned().collect::<Vec<_>>().join(", ");
+        let mut parts = vec![];
+        if !self.stripped_publications.is_empty() || !self.stripped_subscriptions.is_empty() {
+            parts.push(format!(
+                "stripped publications: {}, subscriptions: {}",
+                self.stripped_publications.len(),
+                self.stripped_subscriptions.len()
+            ));
+        }
+        if !self.mapped_publications.is_empty() {
+            parts.push(format!(
+                "kept publications: {} (tables that aren't dumped are removed from them: {})",
+                list(&self.mapped_publications),
+                self.removed_tables
+            ));
+        }
+        write!(f, "Logical replication: {}", parts.join("; "))
+    }
+}
+
+/// The replication objects of the statements
+#[derive(Debug, PartialEq, Eq)]
+enum Object {
+    Publication { name: String, member: Member },
+    Subscription,
+}
+
+/// What the statement adds to the publication
+#[derive(Debug, PartialEq, Eq)]
+enum Member {
+    None,
+    /// `FOR ALL TABLES` of `CREATE PUBLICATION`
+    AllTables,
+    Table(String),
+    Schema(String),
+}
+
+/// What is done with the statement
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Keep,
+    Remove,
+    Replace(String),
+}
+
+/// Strips the publications and subscriptions from the `pg_dump` output (the post-data section): the restored
+/// publications would publish the anonymized data, and the subscriptions would connect to the publishers of the
+/// source database (with its credentials) and create replication slots there.
+///
+/// The publications from `publication_map` are kept (and renamed), their table lists are restricted to the dumped
+/// tables (`FOR ALL TABLES` and `TABLES IN SCHEMA` are replaced with the dumped tables).
+/// The statements are found with quotes, dollar quotes and comments in mind, the `-- Name:` comments
+/// of the removed statements are removed too.
+pub struct ReplicationRewriter<'a> {
+    policy: LogicalReplication,
+    publication_map: &'a BTreeMap<String, String>,
+    /// The full names of the dumped tables (`schema.table`)
+    dumped_tables: &'a BTreeSet<String>,
+}
+
+impl<'a> ReplicationRewriter<'a> {
+    pub fn new(
+        policy: LogicalReplication,
+        publication_map: &'a BTreeMap<String, String>,
+        dumped_tables: &'a BTreeSet<String>,
+    ) -> Self {
+        Self {
+            policy,
+            publication_map,
+            dumped_tables,
+        }
+    }
+
+    pub fn process(&self, sql: &str) -> (String, ReplicationReport) {
+        let mut result = String::with_capacity(sql.len());
+        let mut report = ReplicationReport::default();
+        // the tables of the mapped publications (so the tables of the schemas are not added twice)
+        let mut members: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut copied = 0;
+
+        for range in ddl::statements(sql) {
+            let statement = &sql[range.clone()];
+            let (action, name) = match object(statement) {
+                Some((Object::Publication { name, member }, name_range)) => {
+                    let action = match self.publication_map.get(&name) {
+                        Some(new_name) => {
+                            report.mapped_publications.insert(name.clone());
+                            let tables = members.entry(name.clone()).or_default();
+                            self.rewrite(
+                                statement,
+                                name_range,
+                                new_name,
+                                member,
+                                tables,
+                                &mut report,
+                            )
+                        }
+                        None if self.policy == LogicalReplication::Strip => {
+                            report.stripped_publications.insert(name.clone());
+                            Action::Remove
+                        }
+                        None => Action::Keep,
+                    };
+                    (action, name)
+                }
+                Some((Object::Subscription, name_range)) => {
+                    let name = unquote(&statement[name_range]);
+                    if self.policy == LogicalReplication::Strip {
+                        report.stripped_subscriptions.insert(name.clone());
+                        (Action::Remove, name)
+                    } else {
+                        (Action::Keep, name)
+                    }
+                }
+                None => continue,
+            };
+
+            match action {
+                Action::Keep => {}
+                Action::Remove => {
+                    result.push_str(&sql[copied..ddl::header_start(sql, copied, range.start)]);
+                    copied =
+                        range.end + sql[range.end..].bytes().take_while(|b| *b == b'\n').count();
+                }
+                Action::Replace(replacement) => {
+                    let header = ddl::header_start(sql, copied, range.start);
+                    result.push_str(&sql[copied..header]);
+                    match self.publication_map.get(&name) {
+                        Some(new_name) => result.push_str(&rename_in_header(
+                            &sql[header..range.start],
+                            &name,
+                            new_name,
+                        )),
+                        None => result.push_str(&sql[header..range.start]),
+                    }
+                    result.push_str(&replacement);
+                    copied = range.end;
+                }
+            }
+        }
+        result.push_str(&sql[copied..]);
+
+        (result, report)
+    }
+
+    // The statement of a mapped publication
+    fn rewrite(
+        &self,
+        statement: &str,
+        name_range: Range<usize>,
+        new_name: &str,
+        member: Member,
+        tables: &mut BTreeSet<String>,
+        report: &mut ReplicationReport,
+    ) -> Action {
+        let quoted = quote_name(new_name);
+        let renamed = || {
+            format!(
+                "{}{}{}",
+                &statement[..name_range.start],
+                quoted,
+                &statement[name_range.end..]
+            )
+        };
+        let add_tables = |tables: &[&String]| {
+            tables
+                .iter()
+                .map(|table| {
+                    let (schema, name) = table.split_once('.').unwrap_or(("", table));
+                    format!(
+                        "ALTER PUBLICATION {} ADD TABLE ONLY {}.{};",
+                        quoted,
+                        quote_name(schema),
+                        quote_name(name)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        };
+
+        match member {
+            Member::None if unquote(&statement[name_range.clone()]) == new_name => Action::Keep,
+            Member::None => Action::Replace(renamed()),
+            Member::AllTables => {
+                let statement = renamed();
+                let created = match find_keywords(&statement, &["FOR", "ALL", "TABLES"]) {
+                    Some(range) => format!(
+                        "{}{}",
+                        statement[..range.start].trim_end(),
+                        &statement[range.end..]
+                    ),
+                    None => statement,
+                };
+                let added: Vec<_> = self
+                    .dumped_tables
+                    .iter()
+                    .filter(|t| tables.insert(t.to_string()))
+                    .collect();
+                if added.is_empty() {
+                    Action::Replace(created)
+                } else {
+                    Action::Replace(format!("{}\n\n{}", created, add_tables(&added)))
+                }
+            }
+            Member::Table(table) => {
+                if !self.dumped_tables.contains(&table) {
+                    report.removed_tables += 1;
+                    Action::Remove
+                } else if !tables.insert(table) {
+                    Action::Remove
+                } else if unquote(&statement[name_range.clone()]) == new_name {
+                    Action::Keep
+                } else {
+                    Action::Replace(renamed())
+                }
+            }
+            Member::Schema(schema) => {
+                let prefix = format!("{}.", schema);
+                let added: Vec<_> = self
+                    .dumped_tables
+                    .iter()
+                    .filter(|t| t.starts_with(&prefix) && tables.insert(t.to_string()))
+                    .collect();
+                if added.is_empty() {
+                    Action::Remove
+                } else {
+                    Action::Replace(add_tables(&added))
+                }
+            }
+        }
+    }
+}
+
+/// Returns the replication object of the statement and the range of its name in the statement
+fn object(statement: &str) -> Option<(Object, Range<usize>)> {
+    let tokens = ddl::head_tokens(statement, 10);
+    let keyword = |i: usize| match tokens.get(i) {
+        Some(parts) if parts.len() == 1 && !parts[0].starts_with('"') => {
+            parts[0].to_ascii_uppercase()
+        }
+        _ => String::new(),
+    };
+    let range = |i: usize| {
+        tokens.get(i).map(|parts| {
+            let start = parts[0].as_ptr() as usize - statement.as_ptr() as usize;
+            let last = parts[parts.len() - 1];
+            start..last.as_ptr() as usize - statement.as_ptr() as usize + last.len()
+        })
+    };
+    let qualified = |i: usize| {
+        tokens.get(i).map(|parts| {
+            parts
+                .iter()
+                .map(|part| unquote(part))
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+    };
+
+    // the position of the object type keyword
+    let kind = match keyword(0).as_str() {
+        "CREATE" | "ALTER" | "DROP" => 1,
+        "COMMENT" if keyword(1) == "ON" => 2,
+        "SECURITY" if keyword(1) == "LABEL" => (2..5).find(|i| keyword(*i) == "ON")? + 1,
+        _ => return None,
+    };
+    let mut name = kind + 1;
+    if keyword(0) == "DROP" && keyword(name) == "IF" && keyword(name + 1) == "EXISTS" {
+        name += 2;
+    }
+    let name_range = range(name)?;
+
+    match keyword(kind).as_str() {
+        "SUBSCRIPTION" => Some((Object::Subscription, name_range)),
+        "PUBLICATION" => {
+            let next = name + 1;
+            let member = match (keyword(0).as_str(), keyword(next).as_str()) {
+                ("CREATE", "FOR")
+                    if keyword(next + 1) == "ALL" && keyword(next + 2) == "TABLES" =>
+                {
+                    Member::AllTables
+                }
+                ("ALTER", "ADD") if keyword(next + 1) == "TABLE" => {
+                    let table = if keyword(next + 2) == "ONLY" {
+                        next + 3
+                    } else {
+                        next + 2
+                    };
+                    Member::Table(qualified(table)?)
+                }
+                ("ALTER", "ADD")
+                    if keyword(next + 1) == "TABLES"
+                        && keyword(next + 2) == "IN"
+                        && keyword(next + 3) == "SCHEMA" =>
+                {
+                    Member::Schema(qualified(next + 4)?)
+                }
+                _ => Member::None,
+            };
+            let name = unquote(&statement[name_range.clone()]);
+            Some((Object::Publication { name, member }, name_range))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the range of the keyword sequence in the head of the statement
+fn find_keywords(statement: &str, keywords: &[&str]) -> Option<Range<usize>> {
+    let tokens = ddl::head_tokens(statement, 10);
+    let offset = |s: &str| s.as_ptr() as usize - statement.as_ptr() as usize;
+    tokens.windows(keywords.len()).find_map(|window| {
+        let matches = window
+            .iter()
+            .zip(keywords)
+            .all(|(parts, keyword)| parts.len() == 1 && parts[0].eq_ignore_ascii_case(keyword));
+        let last = window.last()?[0];
+        matches.then(|| offset(window[0][0])..offset(last) + last.len())
+    })
+}
+
+/// Renames the publication in the `-- Name:` comment (`<publication>`, `<publication> <table>`
+/// or `PUBLICATION <publication>`)
+fn rename_in_header(header: &str, name: &str, new_name: &str) -> String {
+    let rename = |line: &str, prefix: &str| {
+        let rest = line.strip_prefix(prefix)?.strip_prefix(name)?;
+        rest.starts_with([';', ' '])
+            .then(|| format!("{}{}{}", prefix, new_name, rest))
+    };
+    header
+        .split_inclusive('\n')
+        .map(|line| {
+            rename(line, "-- Name: PUBLICATION ")
+                .or_else(|| rename(line, "-- Name: "))
+                .unwrap_or_else(|| line.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The post-data section of `pg_dump` 15
+    const POST_DATA: &str = r#"
+--
+-- Name: users users_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: Pub Cols; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION "Pub Cols" WITH (publish = 'insert, update, delete, truncate');
+
+
+ALTER PUBLICATION "Pub Cols" OWNER TO postgres;
+
+--
+-- Name: pub_all; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION pub_all FOR ALL TABLES WITH (publish = 'insert, update, delete, truncate');
+
+
+ALTER PUBLICATION pub_all OWNER TO postgres;
+
+--
+-- Name: pub_schema; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION pub_schema WITH (publish = 'insert, update, delete, truncate');
+
+
+ALTER PUBLICATION pub_schema OWNER TO postgres;
+
+--
+-- Name: pub_some; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION pub_some WITH (publish = 'insert');
+
+
+ALTER PUBLICATION pub_some OWNER TO postgres;
+
+--
+-- Name: PUBLICATION pub_some; Type: COMMENT; Schema: -; Owner: postgres
+--
+
+COMMENT ON PUBLICATION pub_some IS 'to the warehouse';
+
+
+--
+-- Name: pub_schema orders; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION pub_schema ADD TABLE ONLY public.orders;
+
+
+--
+-- Name: pub_some orders; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION pub_some ADD TABLE ONLY public.orders;
+
+
+--
+-- Name: Pub Cols users; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION "Pub Cols" ADD TABLE ONLY public.users (id, name) WHERE ((id > 10));
+
+
+--
+-- Name: pub_some users; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION pub_some ADD TABLE ONLY public.users;
+
+
+--
+-- Name: pub_schema audit; Type: PUBLICATION TABLES IN SCHEMA; Schema: audit; Owner: postgres
+--
+
+ALTER PUBLICATION pub_schema ADD TABLES IN SCHEMA audit;
+
+
+--
+-- Name: sub_in; Type: SUBSCRIPTION; Schema: -; Owner: postgres
+--
+
+CREATE SUBSCRIPTION sub_in CONNECTION 'host=10.0.0.1 dbname=src user=repl password=secret' PUBLICATION pub_x WITH (connect = false, slot_name = 'warehouse_slot');
+
+
+ALTER SUBSCRIPTION sub_in OWNER TO postgres;
+
+--
+-- Name: SUBSCRIPTION sub_in; Type: COMMENT; Schema: -; Owner: postgres
+--
+
+COMMENT ON SUBSCRIPTION sub_in IS 'in';
+
+
+--
+-- PostgreSQL database dump complete
+--
+
+"#;
+
+    const CONSTRAINT: &str = r#"
+--
+-- Name: users users_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+
+"#;
+
+    const END: &str = r#"--
+-- PostgreSQL database dump complete
+--
+
+"#;
+
+    fn names(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn process(
+        policy: LogicalReplication,
+        map: &[(&str, &str)],
+        dumped: &[&str],
+    ) -> (String, ReplicationReport) {
+        let map = map
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ReplicationRewriter::new(policy, &map, &names(dumped)).process(POST_DATA)
+    }
+
+    #[test]
+    fn strip() {
+        let (sql, report) = process(LogicalReplication::Strip, &[], &["public.users"]);
+        assert_eq!(sql, format!("{}{}", CONSTRAINT, END));
+        assert_eq!(
+            report,
+            ReplicationReport {
+                stripped_publications: names(&["Pub Cols", "pub_all", "pub_schema", "pub_some"]),
+                stripped_subscriptions: names(&["sub_in"]),
+                ..ReplicationReport::default()
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "Logical replication: stripped publications: 4, subscriptions: 1"
+        );
+    }
+
+    #[test]
+    fn keep() {
+        let (sql, report) = process(LogicalReplication::Keep, &[], &["public.users"]);
+        assert_eq!(sql, POST_DATA);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn publication_map() {
+        let (sql, report) = process(
+            LogicalReplication::Strip,
+            &[("pub_some", "pub_some_dev"), ("Pub Cols", "Pub Cols")],
+            &["public.users", "audit.log"],
+        );
+        let expected = format!(
+            "{}{}{}",
+            CONSTRAINT,
+            r#"--
+-- Name: Pub Cols; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION "Pub Cols" WITH (publish = 'insert, update, delete, truncate');
+
+
+ALTER PUBLICATION "Pub Cols" OWNER TO postgres;
+
+--
+-- Name: pub_some_dev; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION pub_some_dev WITH (publish = 'insert');
+
+
+ALTER PUBLICATION pub_some_dev OWNER TO postgres;
+
+--
+-- Name: PUBLICATION pub_some_dev; Type: COMMENT; Schema: -; Owner: postgres
+--
+
+COMMENT ON PUBLICATION pub_some_dev IS 'to the warehouse';
+
+
+--
+-- Name: Pub Cols users; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION "Pub Cols" ADD TABLE ONLY public.users (id, name) WHERE ((id > 10));
+
+
+--
+-- Name: pub_some_dev users; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION pub_some_dev ADD TABLE ONLY public.users;
+
+
+"#,
+            END
+        );
+        assert_eq!(sql, expected);
+        assert_eq!(report.mapped_publications, names(&["Pub Cols", "pub_some"]));
+        assert_eq!(
+            report.stripped_publications,
+            names(&["pub_all", "pub_schema"])
+        );
+        // public.orders
+        assert_eq!(report.removed_tables, 1);
+        assert_eq!(
+            report.to_string(),
+            "Logical replication: stripped publications: 2, subscriptions: 1; \
+            kept publications: Pub Cols, pub_some (tables that aren't dumped are removed from them: 1)"
+        );
+    }
+
+    #[test]
+    fn all_tables_and_schemas() {
+        let (sql, report) = process(
+            LogicalReplication::Strip,
+            &[("pub_all", "pub_all"), ("pub_schema", "Schema Pub")],
+            &["public.orders", "audit.log", "audit.Events"],
+        );
+        let expected = format!(
+            "{}{}{}",
+            CONSTRAINT,
+            r#"--
+-- Name: pub_all; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION pub_all WITH (publish = 'insert, update, delete, truncate');
+
+ALTER PUBLICATION pub_all ADD TABLE ONLY audit."Events";
+
+ALTER PUBLICATION pub_all ADD TABLE ONLY audit.log;
+
+ALTER PUBLICATION pub_all ADD TABLE ONLY public.orders;
+
+
+ALTER PUBLICATION pub_all OWNER TO postgres;
+
+--
+-- Name: Schema Pub; Type: PUBLICATION; Schema: -; Owner: postgres
+--
+
+CREATE PUBLICATION "Schema Pub" WITH (publish = 'insert, update, delete, truncate');
+
+
+ALTER PUBLICATION "Schema Pub" OWNER TO postgres;
+
+--
+-- Name: Schema Pub orders; Type: PUBLICATION TABLE; Schema: public; Owner: postgres
+--
+
+ALTER PUBLICATION "Schema Pub" ADD TABLE ONLY public.orders;
+
+
+--
+-- Name: Schema Pub audit; Type: PUBLICATION TABLES IN SCHEMA; Schema: audit; Owner: postgres
+--
+
+ALTER PUBLICATION "Schema Pub" ADD TABLE ONLY audit."Events";
+
+ALTER PUBLICATION "Schema Pub" ADD TABLE ONLY audit.log;
+
+
+"#,
+            END
+        );
+        assert_eq!(sql, expected);
+        assert_eq!(report.removed_tables, 0);
+    }
+
+    #[test]
+    fn objects() {
+        let object =
+            |sql: &str| object(sql).map(|(object, range)| (object, sql[range].to_string()));
+        let publication = |name: &str, member| Object::Publication {
+            name: name.to_string(),
+            member,
+        };
+
+        assert_eq!(
+            object("DROP PUBLICATION IF EXISTS pub_some;"),
+            Some((
+                publication("pub_some", Member::None),
+                "pub_some".to_string()
+            ))
+        );
+        assert_eq!(
+            object("SECURITY LABEL FOR selinux ON PUBLICATION \"P\" IS 'x';"),
+            Some((publication("P", Member::None), "\"P\"".to_string()))
+        );
+        assert_eq!(
+            object("ALTER PUBLICATION p ADD TABLE \"Sales\".\"Orders\";"),
+            Some((
+                publication("p", Member::Table("Sales.Orders".to_string())),
+                "p".to_string()
+            ))
+        );
+        assert_eq!(
+            object("alter subscription s owner to x;"),
+            Some((Object::Subscription, "s".to_string()))
+        );
+        assert_eq!(object("CREATE TABLE publication (id int);"), None);
+        assert_eq!(object("COMMENT ON TABLE publication IS 'x';"), None);
+    }
+
+    #[test]
+    fn function_bodies() {
+        let sql = "CREATE FUNCTION f() RETURNS void AS $$\nALTER PUBLICATION p OWNER TO x;\n$$ LANGUAGE sql;\n";
+        let map = BTreeMap::new();
+        let dumped = BTreeSet::new();
+        let (result, report) =
+            ReplicationRewriter::new(LogicalReplication::Strip, &map, &dumped).process(sql);
+        assert_eq!(result, sql);
+        assert!(report.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! The restore scripts of the directory output (`csv_output`), written after the dump:
+//! - `pre_data.sql` and `post_data.sql`: the parts of `schema.sql` before and after `POST_DATA_MARKER`;
+//! - `checksums.sha256`: the checksums of the table files (for `sha256sum -c checksums.sha256`);
+//! - `restore_parallel.sh`: restores the dump (`./restore_parallel.sh <database URL>`). The tables are
+//!   loaded in waves: a wave starts when the tables it references are loaded, its files are verified
+//!   and loaded concurrently (`JOBS` files at a time, `--restore-jobs` by default);
+//! - `restore.sql`: the same for psql (`\i restore.sql` in the directory), the files are loaded one by one
+//!   and aren't verified.
+//!
+//! The foreign keys are created in the post-data part, so the tables of the circular references
+//! are just loaded in the last wave. The files are loaded with psql (`\copy`): the directory has
+//! no pg_dump archive, so pg_restore can't restore it.
+
+use super::{
+    csv_output::{DirectoryManifest, TableFile, MANIFEST_FILE, SCHEMA_FILE},
+    dumper::POST_DATA_MARKER,
+};
+use anyhow::{anyhow, Result};
+use std::{collections::HashSet, fs, path::Path};
+
+pub const PRE_DATA_FILE: &str = "pre_data.sql";
+pub const POST_DATA_FILE: &str = "post_data.sql";
+pub const CHECKSUMS_FILE: &str = "checksums.sha256";
+pub const SHELL_SCRIPT: &str = "restore_parallel.sh";
+pub const PSQL_SCRIPT: &str = "restore.sql";
+
+const SHELL_PRELUDE: &str = r#"#!/bin/sh
+# Restores the anonymized dump of this directory: ./restore_parallel.sh <database URL>
+# JOBS is the number of the concurrently loaded files, PSQL is the psql command.
+set -eu
+cd "$(dirname "$0")"
+DB="${1:?Usage: restore_parallel.sh <database URL>}"
+PSQL="${PSQL:-psql}"
+"#;
+
+const SHELL_FUNCTIONS: &str = r#"
+sha256() {
+  if command -v sha256sum >/dev/null 2>&1; then
+    sha256sum "$1"
+  else
+    shasum -a 256 "$1"
+  fi | cut -d ' ' -f 1
+}
+
+# load <file> <checksum> <\copy command>
+load() {
+  if [ "$(sha256 "$1")" != "$2" ]; then
+    echo "The checksum of $1 doesn't match" >&2
+    return 1
+  fi
+  "$PSQL" -X -q -v ON_ERROR_STOP=1 -d "$DB" -c "$3"
+}
+
+pids=""
+running=0
+
+wait_all() {
+  failed=0
+  for pid in $pids; do
+    wait "$pid" || failed=1
+  done
+  pids=""
+  running=0
+  if [ "$failed" != 0 ]; then
+    exit 1
+  fi
+}
+
+start() {
+  load "$@" &
+  pids="$pids $!"
+  running=$((running + 1))
+  if [ "$running" -ge "$JOBS" ]; then
+    wait_all
+  fi
+}
+
+"$PSQL" -X -q -v ON_ERROR_STOP=1 -d "$DB" -f pre_data.sql
+"#;
+
+/// The load waves: the tables of a wave reference the tables of the previous waves only
+/// (the references to the tables that aren't in the dump are ignored), the rest is the last wave
+pub fn waves(tables: &[TableFile]) -> Vec<Vec<&TableFile>> {
+    let names: HashSet<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+    let mut loaded: HashSet<&str> = HashSet::new();
+    let mut rest: Vec<&TableFile> = tables.iter().collect();
+    let mut waves = vec![];
+    while !rest.is_empty() {
+        let (wave, later): (Vec<_>, Vec<_>) = rest.into_iter().partition(|t| {
+            t.depends_on
+                .iter()
+                .all(|p| loaded.contains(p.as_str()) || !names.contains(p.as_str()))
+        });
+        if wave.is_empty() {
+            waves.push(later);
+            break;
+        }
+        loaded.extend(wave.iter().map(|t| t.name.as_str()));
+        waves.push(wave);
+        rest = later;
+    }
+    waves
+}
+
+/// Writes the scripts into the directory with the finished dump
+pub fn write_scripts(dir: &Path, jobs: usize) -> Result<()> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let manifest: DirectoryManifest = fs::read(&manifest_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_slice(&content)?))
+        .map_err(|e| anyhow!("Can't read `{}`: {}", manifest_path.display(), e))?;
+    let schema = fs::read_to_string(dir.join(SCHEMA_FILE))?;
+    let (pre_data, post_data) = schema
+        .split_once(&format!("\n{}\n", POST_DATA_MARKER))
+        .ok_or_else(|| anyhow!("`{}` has no post-data part", SCHEMA_FILE))?;
+    fs::write(dir.join(PRE_DATA_FILE), pre_data)?;
+    fs::write(dir.join(POST_DATA_FILE), post_data)?;
+
+    let checksums: String = manifest
+        .tables
+        .iter()
+        .map(|t| format!("{}  {}\n", t.sha256, t.file))
+        .collect();
+    fs::write(dir.join(CHECKSUMS_FILE), checksums)?;
+
+    let shell_path = dir.join(SHELL_SCRIPT);
+    fs::write(&shell_path, shell_script(&manifest, jobs))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&shell_path, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::write(dir.join(PSQL_SCRIPT), psql_script(&manifest))?;
+    Ok(())
+}
+
+fn shell_script(manifest: &DirectoryManifest, jobs: usize) -> String {
+    let mut script = SHELL_PRELUDE.to_string();
+    script.push_str(&format!("JOBS=\"${{JOBS:-{}}}\"\n", jobs));
+    script.push_str(SHELL_FUNCTIONS);
+    for (i, wave) in waves(&manifest.tables).iter().enumerate() {
+        script.push_str(&format!("\n# wave {}\n", i + 1));
+        for table in wave {
+            script.push_str(&format!(
+                "start {} {} {}\n",
+                shell_quote(&table.file),
+                shell_quote(&table.sha256),
+                shell_quote(&copy_command(manifest, table))
+            ));
+        }
+        script.push_str("wait_all\n");
+    }
+    script.push_str(&format!(
+        "\n\"$PSQL\" -X -q -v ON_ERROR_STOP=1 -d \"$DB\" -f {}\n",
+        POST_DATA_FILE
+    ));
+    script
+}
+
+fn psql_script(manifest: &DirectoryManifest) -> String {
+    let mut script = format!(
+        "-- Restores the anonymized dump of this directory: `\\i {}` (psql is started in the directory)\n\
+        \\set ON_ERROR_STOP on\n\\i {}\n",
+        PSQL_SCRIPT, PRE_DATA_FILE
+    );
+    for table in waves(&manifest.tables).into_iter().flatten() {
+        script.push_str(&copy_command(manifest, table));
+        script.push('\n');
+    }
+    script.push_str(&format!("\\i {}\n", POST_DATA_FILE));
+    script
+}
+
+fn copy_command(manifest: &DirectoryManifest, table: &TableFile) -> String {
+    let name = match table.name.split_once('.') {
+        Some((schema, name)) => format!("{}.{}", quote_ident(schema), quote_ident(name)),
+        None => quote_ident(&table.name),
+    };
+    let columns: Vec<_> = table.columns.iter().map(|c| quote_ident(&c.name)).collect();
+    let delimiter = if manifest.delimiter == "\t" {
+        "E'\\t'".to_string()
+    } else {
+        sql_literal(&manifest.delimiter)
+    };
+    format!(
+        "\\copy {} ({}) FROM {} WITH (FORMAT csv, HEADER, DELIMITER {}, NULL {})",
+        name,
+        columns.join(", "),
+        sql_literal(&table.file),
+        delimiter,
+        sql_literal(&manifest.null)
+    )
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::csv_output::ColumnFile;
+
+    fn table(name: &str, depends_on: &[&str]) -> TableFile {
+        TableFile {
+            name: name.to_string(),
+            file: format!("{}.csv", name),
+            rows: 1,
+            columns: vec![ColumnFile {
+                name: "id".to_string(),
+                data_type: "integer".to_string(),
+            }],
+            depends_on: depends_on.iter().map(|p| p.to_string()).collect(),
+            sha256: "abc".to_string(),
+        }
+    }
+
+    fn names<'a>(waves: &[Vec<&'a TableFile>]) -> Vec<Vec<&'a str>> {
+        waves
+            .iter()
+            .map(|wave| wave.iter().map(|t| t.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wave_order() {
+        let tables = vec![
+            table("public.orders", &["public.users", "public.items"]),
+            table("public.users", &[]),
+            table("public.items", &["public.dropped"]),
+            table("public.lines", &["public.orders"]),
+            table("public.a", &["public.b"]),
+            table("public.b", &["public.a"]),
+        ];
+        assert_eq!(
+            names(&waves(&tables)),
+            vec![
+                vec!["public.users", "public.items"],
+                vec!["public.orders"],
+                vec!["public.lines"],
+                vec!["public.a", "public.b"]
+            ]
+        );
+        assert!(waves(&[]).is_empty());
+    }
+
+    #[test]
+    fn commands() {
+        let mut manifest = DirectoryManifest {
+            delimiter: ",".to_string(),
+            null: String::new(),
+            tables: vec![table("public.users", &[])],
+        };
+        manifest.tables[0].columns[0].name = "say \"hi\"".to_string();
+        manifest.tables[0].file = "public.o'brien.csv".to_string();
+        assert_eq!(
+            copy_command(&manifest, &manifest.tables[0]),
+            "\\copy \"public\".\"users\" (\"say \"\"hi\"\"\") FROM 'public.o''brien.csv' \
+            WITH (FORMAT csv, HEADER, DELIMITER ',', NULL '')"
+        );
+        assert_eq!(shell_quote("o'brien"), "'o'\\''brien'");
+
+        manifest.delimiter = "\t".to_string();
+        manifest.null = "\\N".to_string();
+        assert!(copy_command(&manifest, &manifest.tables[0])
+            .ends_with("WITH (FORMAT csv, HEADER, DELIMITER E'\\t', NULL '\\N')"));
+    }
+
+    #[test]
+    fn scripts() {
+        let manifest = DirectoryManifest {
+            delimiter: ",".to_string(),
+            null: String::new(),
+            tables: vec![
+                table("public.orders", &["public.users"]),
+                table("public.users", &[]),
+            ],
+        };
+        let shell = shell_script(&manifest, 2);
+        assert!(shell.contains("JOBS=\"${JOBS:-2}\"\n"));
+        let users = shell
+            .find("start 'public.users.csv' 'abc' '\\copy")
+            .unwrap();
+        let orders = shell.find("start 'public.orders.csv'").unwrap();
+        assert!(shell.find("-f pre_data.sql").unwrap() < users);
+        assert!(users < shell.find("# wave 2").unwrap());
+        assert!(shell.find("# wave 2").unwrap() < orders);
+        assert!(shell.trim_end().ends_with("-f post_data.sql"));
+
+        let psql = psql_script(&manifest);
+        let lines: Vec<_> = psql.lines().skip(1).collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[..2], ["\\set ON_ERROR_STOP on", "\\i pre_data.sql"]);
+        assert!(lines[2].starts_with("\\copy \"public\".\"users\""));
+        assert!(lines[3].starts_with("\\copy \"public\".\"orders\""));
+        assert_eq!(lines[4], "\\i post_data.sql");
+    }
+}
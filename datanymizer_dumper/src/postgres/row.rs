@@ -1,9 +1,9 @@
-use super::escaper;
+use super::{array_columns::ArrayColumns, copy_codec, int_range::IntColumns};
 use crate::Table;
-use anyhow::Result;
-use datanymizer_engine::Engine;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{Engine, InvalidUtf8};
 use postgres::types::Type;
-use std::{borrow::Cow, char};
+use std::{borrow::Cow, collections::HashSet, str::Utf8Error};
 
 #[derive(Debug)]
 pub struct PgRow<T>
@@ -11,7 +11,9 @@ pub struct PgRow<T>
     T: Table<Type>,
 {
     table: T,
-    source: String,
+    source: Vec<u8>,
+    /// The row number in the COPY output (for error messages)
+    number: u64,
 }
 
 impl<T> PgRow<T>
@@ -19,37 +21,159 @@ impl<T> PgRow<T>
     T: Table<Type>,
 {
     pub fn from_string_row(source: String, parent_table: T) -> Self {
+        Self::from_bytes_row(source.into_bytes(), 0, parent_table)
+    }
+
+    pub fn from_bytes_row(source: Vec<u8>, number: u64, parent_table: T) -> Self {
         Self {
             source,
             table: parent_table,
+            number,
         }
     }
 
     /// Applies the transform engine to every column in the row
-    /// Returns a new StringRecord for store in the dump
-    pub fn transform(&self, engine: &Engine, cfg_tbl_name: &str) -> Result<String> {
-        let split_char: char = char::from_u32(0x0009).unwrap();
-        let values: Vec<_> = self.source.split(split_char).collect();
-        let mut transformed_values = engine.process_row(
+    /// Returns a new row for store in the dump.
+    /// Values that are not changed by transformers are passed through byte-for-byte,
+    /// so invalid UTF-8 only matters in the columns with rules.
+    pub fn transform(&self, engine: &Engine, cfg_tbl_name: &str) -> Result<Vec<u8>> {
+        self.transform_skipping(engine, cfg_tbl_name, &HashSet::new())
+    }
+
+    /// The same as `transform`, but the rules of the skipped columns are not applied
+    pub fn transform_skipping(
+        &self,
+        engine: &Engine,
+        cfg_tbl_name: &str,
+        skipped_columns: &HashSet<String>,
+    ) -> Result<Vec<u8>> {
+        self.transform_fitting(
+            engine,
+            cfg_tbl_name,
+            skipped_columns,
+            &IntColumns::default(),
+            &ArrayColumns::default(),
+        )
+    }
+
+    /// The same as `transform_skipping`, the transformed values of the integer columns are fitted
+    /// to the column types, the rules of the array columns are applied to the array elements
+    pub fn transform_fitting(
+        &self,
+        engine: &Engine,
+        cfg_tbl_name: &str,
+        skipped_columns: &HashSet<String>,
+        int_columns: &IntColumns,
+        array_columns: &ArrayColumns,
+    ) -> Result<Vec<u8>> {
+        let values: Vec<_> = copy_codec::fields(&self.source).collect();
+
+        let mut str_values = Vec::with_capacity(values.len());
+        for (i, v) in values.iter().enumerate() {
+            match std::str::from_utf8(v) {
+                Ok(s) => str_values.push(Cow::Borrowed(s)),
+                Err(e) => {
+                    if engine.settings.invalid_utf8 == InvalidUtf8::Error
+                        && self.has_rule(engine, cfg_tbl_name, i, skipped_columns)
+                    {
+                        return Err(self.utf8_error(i, v, e));
+                    }
+                    str_values.push(String::from_utf8_lossy(v));
+                }
+            }
+        }
+        let str_values: Vec<&str> = str_values.iter().map(|v| v.as_ref()).collect();
+
+        let transformed_values = engine.process_row_numbered(
             String::from(cfg_tbl_name),
+            Some(self.number),
             self.table.get_column_indexes(),
-            &values,
+            &str_values,
+            skipped_columns,
+            &array_columns.codecs(),
         )?;
-        for v in &mut transformed_values {
-            if let Cow::Owned(ref mut s) = v {
-                escaper::replace_chars(s);
+
+        let mut result = Vec::with_capacity(self.source.len());
+        for (i, v) in transformed_values.into_iter().enumerate() {
+            if i > 0 {
+                result.push(copy_codec::DELIMITER);
+            }
+            match v {
+                Cow::Owned(mut s) => {
+                    int_columns.fit(i, &mut s).map_err(|e| {
+                        anyhow!(
+                            "Invalid generated value in the table {}, row {}, column `{}`: {} \
+                             (use `on_overflow: clamp` in the config to clamp such values)",
+                            self.table.get_full_name(),
+                            self.number,
+                            self.column_name(i),
+                            e
+                        )
+                    })?;
+                    copy_codec::escape_transformed(&mut s);
+                    result.extend_from_slice(s.as_bytes());
+                }
+                Cow::Borrowed(_) => result.extend_from_slice(values[i]),
             }
         }
 
-        Ok(transformed_values.join("\t"))
+        Ok(result)
+    }
+
+    fn column_name(&self, index: usize) -> &str {
+        self.table
+            .get_column_indexes()
+            .iter()
+            .find(|(_, &i)| i == index)
+            .map_or("?", |(name, _)| name.as_str())
+    }
+
+    fn has_rule(
+        &self,
+        engine: &Engine,
+        cfg_tbl_name: &str,
+        index: usize,
+        skipped_columns: &HashSet<String>,
+    ) -> bool {
+        let column_indexes = self.table.get_column_indexes();
+        engine
+            .settings
+            .transformers_for(cfg_tbl_name)
+            .is_some_and(|ts| {
+                ts.iter().any(|(name, _)| {
+                    column_indexes.get(name) == Some(&index) && !skipped_columns.contains(name)
+                })
+            })
+    }
+
+    fn utf8_error(&self, index: usize, value: &[u8], e: Utf8Error) -> anyhow::Error {
+        let start = e.valid_up_to();
+        let end = e.error_len().map_or(value.len(), |len| start + len);
+        let bytes: Vec<_> = value[start..end]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        anyhow!(
+            "Invalid UTF-8 in the table {}, row {}, column `{}`: bytes [{}] at the offset {} \
+             (use `invalid_utf8: lossy` in the config to replace them)",
+            self.table.get_full_name(),
+            self.number,
+            self.column_name(index),
+            bytes.join(" "),
+            start
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::postgres::{column::PgColumn, table::PgTable};
-    use datanymizer_engine::Settings;
+    use crate::{
+        postgres::{column::PgColumn, table::PgTable},
+        test_tables,
+    };
+    use datanymizer_engine::{OnOverflow, Settings};
 
     #[test]
     fn transform() {
@@ -72,39 +196,193 @@ fn transform() {
         "#;
         let settings = Settings::from_yaml(config).unwrap();
 
-        let mut table = PgTable::new("table_name".to_string(), "public".to_string());
+        let columns = ["first_name", "middle_name", "last_name", "comment"].map(|name| (name, ""));
+        let table = test_tables::pg_table("public", "table_name", &columns);
+        let row = PgRow::from_string_row("first\tmiddle\tlast\t".to_string(), table);
 
-        let col1 = PgColumn {
-            position: 1,
-            name: String::from("first_name"),
-            data_type: String::new(),
-            inner_type: Some(0),
-        };
-        let col2 = PgColumn {
-            position: 2,
-            name: String::from("middle_name"),
-            data_type: String::new(),
-            inner_type: Some(0),
+        assert_eq!(
+            row.transform(&Engine::new(settings), "table_name").unwrap(),
+            b"First\tMiddle\tLast\tMulti\\nline\\n"
+        );
+    }
+
+    mod invalid_utf8 {
+        use super::*;
+
+        fn table() -> PgTable {
+            test_tables::pg_table(
+                "public",
+                "table_name",
+                &[("first_name", ""), ("comment", "")],
+            )
+        }
+
+        fn engine(policy: &str) -> Engine {
+            let config = format!(
+                r#"
+                invalid_utf8: {}
+                tables:
+                  - name: table_name
+                    rules:
+                      first_name:
+                        capitalize: ~
+                "#,
+                policy
+            );
+            Engine::new(Settings::from_yaml(&config).unwrap())
+        }
+
+        #[test]
+        fn untransformed_column() {
+            let source = b"first\tcaf\xe9 \x93quoted\x94".to_vec();
+            let row = PgRow::from_bytes_row(source, 1, table());
+
+            assert_eq!(
+                row.transform(&engine("error"), "table_name").unwrap(),
+                b"First\tcaf\xe9 \x93quoted\x94"
+            );
+        }
+
+        #[test]
+        fn error() {
+            let source = b"caf\xe9\tcomment".to_vec();
+            let row = PgRow::from_bytes_row(source, 42, table());
+
+            let err = row.transform(&engine("error"), "table_name").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Invalid UTF-8 in the table public.table_name, row 42, column `first_name`: bytes [e9] at the offset 3 \
+                 (use `invalid_utf8: lossy` in the config to replace them)"
+            );
+        }
+
+        #[test]
+        fn lossy() {
+            let source = b"caf\xe9\tcomment".to_vec();
+            let row = PgRow::from_bytes_row(source, 42, table());
+
+            assert_eq!(
+                row.transform(&engine("lossy"), "table_name").unwrap(),
+                "Caf\u{fffd}\tcomment".as_bytes()
+            );
+        }
+
+        #[test]
+        fn skipped_column() {
+            let source = b"caf\xe9\tcomment".to_vec();
+            let row = PgRow::from_bytes_row(source, 42, table());
+            let skipped = HashSet::from(["first_name".to_string()]);
+
+            assert_eq!(
+                row.transform_skipping(&engine("error"), "table_name", &skipped)
+                    .unwrap(),
+                b"caf\xe9\tcomment"
+            );
+        }
+    }
+
+    #[test]
+    fn int_overflow() {
+        let config = r#"
+          tables:
+            - name: table_name
+              rules:
+                id:
+                  template:
+                    format: "{{ prev.id }}00"
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let table = test_tables::pg_table("public", "table_name", &[("id", "integer")]);
+        let cfg = settings.tables[0].clone();
+        let engine = Engine::new(settings);
+        let skipped = HashSet::new();
+        let transform = |value: &str, on_overflow| {
+            let int_columns = IntColumns::new(&table, &cfg, &skipped, on_overflow);
+            PgRow::from_bytes_row(value.as_bytes().to_vec(), 7, table.clone())
+                .transform_fitting(
+                    &engine,
+                    "table_name",
+                    &skipped,
+                    &int_columns,
+                    &ArrayColumns::default(),
+                )
+                .map_err(|e| e.to_string())
         };
-        let col3 = PgColumn {
-            position: 3,
-            name: String::from("last_name"),
-            data_type: String::new(),
-            inner_type: Some(0),
+
+        assert_eq!(
+            transform("21474836", OnOverflow::Error),
+            Ok(b"2147483600".to_vec())
+        );
+        assert_eq!(
+            transform("21474837", OnOverflow::Error),
+            Err(
+                "Invalid generated value in the table public.table_name, row 7, column `id`: \
+                the value 2147483700 is out of the `integer` range (-2147483648 to 2147483647) \
+                (use `on_overflow: clamp` in the config to clamp such values)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            transform("21474837", OnOverflow::Clamp),
+            Ok(b"2147483647".to_vec())
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let config = r#"
+            tables:
+              - name: table_name
+                rules:
+                  tags:
+                    template:
+                      format: "{{ _0 | upper }}"
+                  codes:
+                    template:
+                      format: "{{ _0 }}0"
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        let mut table = PgTable::new("table_name".to_string(), "public".to_string());
+        let column = |position, name: &str, oid: Type| PgColumn {
+            position,
+            name: String::from(name),
+            data_type: String::from("ARRAY"),
+            inner_type: Some(oid.oid()),
+            max_length: None,
         };
-        let col4 = PgColumn {
-            position: 4,
-            name: String::from("comment"),
-            data_type: String::new(),
-            inner_type: Some(0),
+        table.set_columns(vec![
+            column(1, "tags", Type::TEXT_ARRAY),
+            column(2, "codes", Type::INT4_ARRAY),
+        ]);
+        let cfg = settings.tables[0].clone();
+        let engine = Engine::new(settings);
+        let skipped = HashSet::new();
+        let array_columns = ArrayColumns::new(&table, &cfg, &skipped);
+        let transform = |value: &str| {
+            PgRow::from_bytes_row(value.as_bytes().to_vec(), 1, table.clone())
+                .transform_fitting(
+                    &engine,
+                    "table_name",
+                    &skipped,
+                    &IntColumns::default(),
+                    &array_columns,
+                )
+                .map(|row| String::from_utf8(row).unwrap())
+                .map_err(|e| e.to_string())
         };
 
-        table.set_columns(vec![col1, col2, col3, col4]);
-        let row = PgRow::from_string_row("first\tmiddle\tlast\t".to_string(), table);
-
         assert_eq!(
-            row.transform(&Engine::new(settings), "table_name").unwrap(),
-            "First\tMiddle\tLast\tMulti\\nline\\n"
+            transform("{a,NULL,\"b,c\",\"d}\"}\t{1,NULL,2}"),
+            Ok("{A,NULL,\"B,C\",\"D}\"}\t{10,NULL,20}".to_string())
+        );
+        // the backslashes of the array literals are escaped for COPY
+        assert_eq!(
+            transform(concat!(r#"{"a\\"b","x y"}"#, "\t{}")),
+            Ok(concat!(r#"{"A\\"B","X Y"}"#, "\t{}").to_string())
         );
+        assert_eq!(transform("\\N\t{}"), Ok("\\N\t{}".to_string()));
+        assert!(transform("{{a}}\t{}")
+            .unwrap_err()
+            .contains("multidimensional arrays are not supported"));
     }
 }
@@ -0,0 +1,102 @@
+use super::{conn_url, connector::Connection, dump_args::wildcard_match};
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{Safety, Sentinel};
+use postgres::{Client, SimpleQueryMessage};
+
+/// The database name and its estimated size (from the catalog statistics, the data is not read)
+const DATABASE_QUERY: &str = "SELECT current_database()::text,
+                                     (SELECT COALESCE(sum(relpages), 0)::bigint FROM pg_catalog.pg_class)
+                                     * current_setting('block_size')::bigint";
+
+/// `NULL` for unknown settings
+const SETTING_QUERY: &str = "SELECT current_setting($1, true)";
+
+/// Checks the source database against the `safety` rules (it runs right after connecting, before the tables are
+/// inspected). Returns the reasons why the database looks like production (empty if no rule matches).
+pub fn check(safety: &Safety, connection: &mut Connection) -> Result<Vec<String>> {
+    let mut findings = vec![];
+
+    let row = connection.client.query_one(DATABASE_QUERY, &[])?;
+    let name: String = row.get(0);
+    let size: i64 = row.get(1);
+
+    if let Some(pattern) = matching(&safety.database_names, &name) {
+        findings.push(format!(
+            "the database name `{}` matches `{}`",
+            name, pattern
+        ));
+    }
+    for host in conn_url::hosts(&connection.url) {
+        if let Some(pattern) = matching(&safety.hosts, &host) {
+            findings.push(format!("the host `{}` matches `{}`", host, pattern));
+        }
+    }
+    if let Some(max_size) = safety.max_database_size_bytes().map_err(|e| anyhow!(e))? {
+        if size as u64 > max_size {
+            findings.push(format!(
+                "the estimated database size ({} MB) exceeds {}",
+                size >> 20,
+                safety.max_database_size.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    for sentinel in &safety.sentinels {
+        if sentinel_value(&mut connection.client, sentinel).as_ref() == Some(&sentinel.value) {
+            findings.push(format!(
+                "the sentinel `{}` is `{}`",
+                sentinel
+                    .setting
+                    .as_deref()
+                    .or(sentinel.query.as_deref())
+                    .unwrap_or_default(),
+                sentinel.value
+            ));
+        }
+    }
+
+    Ok(findings)
+}
+
+// The first matching pattern (case-insensitive)
+fn matching<'a>(patterns: &'a [String], value: &str) -> Option<&'a str> {
+    let value = value.to_lowercase();
+    patterns
+        .iter()
+        .find(|p| wildcard_match(p.to_lowercase().as_bytes(), value.as_bytes()))
+        .map(|p| p.as_str())
+}
+
+// The errors of the sentinel queries (e.g., a missing sentinel table) mean that there is no sentinel
+fn sentinel_value(client: &mut Client, sentinel: &Sentinel) -> Option<String> {
+    if let Some(setting) = &sentinel.setting {
+        return client
+            .query_one(SETTING_QUERY, &[setting])
+            .ok()
+            .and_then(|row| row.get(0));
+    }
+
+    let messages = client.simple_query(sentinel.query.as_deref()?).ok()?;
+    messages.into_iter().find_map(|message| match message {
+        SimpleQueryMessage::Row(row) => row.get(0).map(String::from),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn matching() {
+        let patterns = vec!["*prod*".to_string(), "db-?.internal".to_string()];
+        assert_eq!(
+            super::matching(&patterns, "shop_PRODUCTION"),
+            Some("*prod*")
+        );
+        assert_eq!(
+            super::matching(&patterns, "db-1.internal"),
+            Some("db-?.internal")
+        );
+        assert_eq!(super::matching(&patterns, "db-10.internal"), None);
+        assert_eq!(super::matching(&patterns, "staging"), None);
+        assert_eq!(super::matching(&[], "prod"), None);
+    }
+}
@@ -1,15 +1,49 @@
 use super::{
-    column::PgColumn, connector, foreign_key::ForeignKey, sequence::PgSequence, table::PgTable,
+    column::PgColumn,
+    connector, ddl,
+    foreign_key::ForeignKey,
+    materialized_views::{self, MaterializedView},
+    sequence::PgSequence,
+    table::PgTable,
     SchemaInspector,
 };
 use crate::Table;
 use anyhow::Result;
 use postgres::types::Type;
+use std::collections::HashMap;
 
+/// The larger tables are estimated with `--precise-size` too (bytes with TOAST and indexes)
+pub const PRECISE_SIZE_MAX_BYTES: i64 = 64 * 1024 * 1024;
+
+// $1 - the included schemas (all schemas if it is empty), $2 - the excluded schemas
 const PG_CATALOG_SCHEMA: &str = "SELECT tablename, schemaname
                                  FROM pg_catalog.pg_tables
                                  WHERE schemaname != 'pg_catalog'
-                                 AND schemaname != 'information_schema'";
+                                 AND schemaname != 'information_schema'
+                                 AND (cardinality($1::text[]) = 0 OR schemaname = ANY($1))
+                                 AND NOT schemaname = ANY($2)";
+
+// Sequences owned by the table columns (`serial` and identity columns) and sequences used in column defaults
+const TABLE_SEQUENCES: &str = "SELECT a.attname, quote_ident(n.nspname) || '.' || quote_ident(s.relname)
+                               FROM pg_catalog.pg_depend d
+                               JOIN pg_catalog.pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+                               JOIN pg_catalog.pg_namespace n ON n.oid = s.relnamespace
+                               JOIN pg_catalog.pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                               WHERE d.classid = 'pg_catalog.pg_class'::regclass
+                               AND d.refclassid = 'pg_catalog.pg_class'::regclass
+                               AND d.deptype IN ('a', 'i')
+                               AND d.refobjid = $1::text::regclass
+                               UNION
+                               SELECT a.attname, quote_ident(n.nspname) || '.' || quote_ident(s.relname)
+                               FROM pg_catalog.pg_attrdef ad
+                               JOIN pg_catalog.pg_attribute a ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+                               JOIN pg_catalog.pg_depend d ON d.classid = 'pg_catalog.pg_attrdef'::regclass
+                                   AND d.objid = ad.oid
+                                   AND d.refclassid = 'pg_catalog.pg_class'::regclass
+                               JOIN pg_catalog.pg_class s ON s.oid = d.refobjid AND s.relkind = 'S'
+                               JOIN pg_catalog.pg_namespace n ON n.oid = s.relnamespace
+                               WHERE ad.adrelid = $1::text::regclass
+                               ORDER BY 1, 2";
 
 const TABLE_FOREIGN_KEYS: &str = "SELECT
                                     tc.table_schema,
@@ -26,28 +60,152 @@
                                     AND tc.table_schema = kcu.table_schema
                                     JOIN information_schema.constraint_column_usage AS ccu
                                     ON ccu.constraint_name = tc.constraint_name
-                                    AND ccu.table_schema = tc.table_schema
+                                    AND ccu.constraint_schema = tc.constraint_schema
                                 WHERE tc.constraint_type = 'FOREIGN KEY' AND tc.table_name = $1";
 
-const TABLE_COLUMNS_QUERY: &str = "SELECT cc.column_name, cc.ordinal_position, cc.data_type, pt.oid
+const TABLE_COLUMNS_QUERY: &str =
+    "SELECT cc.column_name, cc.ordinal_position, cc.data_type, pt.oid,
+                                       cc.character_maximum_length::int AS character_maximum_length
                                    FROM information_schema.columns as cc
                                    JOIN pg_catalog.pg_type as pt
                                    ON cc.udt_name = pt.typname
                                    WHERE cc.table_schema = $1 and cc.table_name = $2
                                    ORDER BY cc.ordinal_position ASC";
 
-const TABLE_SIZE_QUERY: &str =
-    "SELECT
-    (pg_catalog.pg_class.reltuples / COALESCE(NULLIF(pg_catalog.pg_class.relpages, 0), 1))::bigint * (
-        pg_relation_size(pg_catalog.pg_class.oid)::bigint /
-        current_setting('block_size')::bigint
-    )::bigint AS len
-    FROM pg_catalog.pg_class
-    INNER JOIN pg_catalog.pg_namespace ON pg_catalog.pg_class.relnamespace = pg_catalog.pg_namespace.oid
-    WHERE pg_catalog.pg_class.relname = $1 AND pg_catalog.pg_namespace.nspname = $2";
+const TABLE_COLUMN_DETAILS: &str = "SELECT a.attname,
+                                        pg_catalog.format_type(a.atttypid, a.atttypmod),
+                                        NOT a.attnotnull,
+                                        pg_catalog.pg_get_expr(ad.adbin, ad.adrelid)
+                                    FROM pg_catalog.pg_attribute a
+                                    LEFT JOIN pg_catalog.pg_attrdef ad
+                                    ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum
+                                    WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                                    ORDER BY a.attnum";
+
+const TABLE_UNIQUE_INDEXES: &str = "SELECT i.relname::text, ix.indisprimary,
+                                        ARRAY(
+                                            SELECT pg_catalog.pg_get_indexdef(ix.indexrelid, k, true)
+                                            FROM generate_series(1, ix.indnkeyatts) k
+                                            ORDER BY k
+                                        )
+                                    FROM pg_catalog.pg_index ix
+                                    JOIN pg_catalog.pg_class i ON i.oid = ix.indexrelid
+                                    WHERE ix.indrelid = $1::text::regclass AND ix.indisunique
+                                    ORDER BY ix.indisprimary DESC, i.relname";
+
+const TABLE_EXPRESSION_INDEXES: &str =
+    "SELECT i.relname::text, pg_catalog.pg_get_indexdef(ix.indexrelid),
+                                            pg_catalog.pg_get_expr(ix.indexprs, ix.indrelid),
+                                            pg_catalog.pg_get_expr(ix.indpred, ix.indrelid)
+                                        FROM pg_catalog.pg_index ix
+                                        JOIN pg_catalog.pg_class i ON i.oid = ix.indexrelid
+                                        WHERE ix.indrelid = $1::text::regclass
+                                        AND (ix.indexprs IS NOT NULL OR ix.indpred IS NOT NULL)
+                                        ORDER BY i.relname";
+
+const FOREIGN_KEY_TABLES: &str =
+    "SELECT c.conname::text, n.nspname || '.' || t.relname, rn.nspname || '.' || rt.relname
+                                  FROM pg_catalog.pg_constraint c
+                                  JOIN pg_catalog.pg_class t ON t.oid = c.conrelid
+                                  JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace
+                                  JOIN pg_catalog.pg_class rt ON rt.oid = c.confrelid
+                                  JOIN pg_catalog.pg_namespace rn ON rn.oid = rt.relnamespace
+                                  WHERE c.contype = 'f'";
+
+/// Column details for the schema inspection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDetails {
+    pub name: String,
+    /// The data type with modifiers (e.g. `character varying(45)`)
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniqueIndex {
+    pub name: String,
+    pub primary: bool,
+    /// Column names or expressions
+    pub columns: Vec<String>,
+}
+
+/// Index with expressions or a predicate (a partial index)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionIndex {
+    pub name: String,
+    pub definition: String,
+    pub expressions: Option<String>,
+    pub predicate: Option<String>,
+}
+
+impl ExpressionIndex {
+    /// Returns `true` if the column is used in the index expressions or the predicate
+    pub fn references(&self, column: &str) -> bool {
+        [&self.expressions, &self.predicate]
+            .iter()
+            .filter_map(|e| e.as_deref())
+            .any(|e| ddl::identifiers(e).iter().any(|i| i == column))
+    }
+}
 
-#[derive(Clone)]
-pub struct PgSchemaInspector;
+/// Counts of foreign keys for a table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ForeignKeyCounts {
+    /// Foreign keys from the table to other tables
+    pub outgoing: usize,
+    /// Foreign keys from other tables to the table
+    pub incoming: usize,
+}
+
+/// Foreign key between two tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyLink {
+    /// The constraint name
+    pub name: String,
+    /// Full name of the referencing table
+    pub table: String,
+    /// Full name of the referenced table
+    pub referenced_table: String,
+}
+
+/// The schemas of the dumped tables (`--schema` and `--exclude-schema`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SchemaFilter {
+    /// All schemas if it is empty
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl SchemaFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        Self { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn accepts(&self, schema: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|s| s == schema))
+            && !self.exclude.iter().any(|s| s == schema)
+    }
+
+    /// The same filter for `pg_dump` (so the schema sections agree with the data)
+    pub fn pg_dump_args(&self) -> Vec<String> {
+        let include = self.include.iter().map(|s| format!("--schema={}", s));
+        let exclude = self
+            .exclude
+            .iter()
+            .map(|s| format!("--exclude-schema={}", s));
+        include.chain(exclude).collect()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PgSchemaInspector {
+    schema_filter: SchemaFilter,
+}
 
 impl SchemaInspector for PgSchemaInspector {
     type Type = Type;
@@ -57,10 +215,16 @@ impl SchemaInspector for PgSchemaInspector {
 
     // Get all tables in the database
     fn get_tables(&self, connection: &mut Self::Connection) -> Result<Vec<Self::Table>> {
+        // the columns, sequences and sizes of all tables are fetched at once
+        connection.catalog()?;
+
         let mut counter = 0;
-        let items: Vec<Self::Table> = connection
-            .client
-            .query(PG_CATALOG_SCHEMA, &[])?
+        let mut items: Vec<Self::Table> = connection
+            .catalog_client()
+            .query(
+                PG_CATALOG_SCHEMA,
+                &[&self.schema_filter.include, &self.schema_filter.exclude],
+            )?
             .into_iter()
             .map(|row| row.into())
             .map(|mut table| {
@@ -71,9 +235,13 @@ fn get_tables(&self, connection: &mut Self::Connection) -> Result<Vec<Self::Tabl
                     table.set_sequences(sequences);
                 };
 
-                match self.get_table_size(connection, &table) {
-                    Ok(size) => table.size = size as i64,
-                    Err(e) => panic!("ERR: {}", e),
+                table.size = self.table_size_or_warn(connection, &table);
+
+                if let Ok(catalog) = connection.catalog() {
+                    table.extension = catalog.extension(&table);
+                    table.hypertable = catalog.hypertable(&table);
+                    table.partitioned = catalog.is_partitioned(&table);
+                    table.partition_of = catalog.partition_root(&table);
                 }
 
                 counter += 1;
@@ -81,20 +249,42 @@ fn get_tables(&self, connection: &mut Self::Connection) -> Result<Vec<Self::Tabl
                 table
             })
             .collect();
+        // the catalog order isn't stable, so the dump order of the tables without dependencies would vary
+        items.sort_by(|a, b| (&a.schemaname, &a.tablename).cmp(&(&b.schemaname, &b.tablename)));
         Ok(items)
     }
 
-    /// Get table size
+    /// Get table size (the estimated count of rows). The views and the foreign tables aren't sized (`0`),
+    /// the partitioned tables get the sum of their partitions.
     fn get_table_size(
         &self,
         connection: &mut Self::Connection,
         table: &Self::Table,
     ) -> Result<i64> {
+        let catalog = connection.catalog()?;
+        if catalog.is_partitioned(table) {
+            let mut size = 0;
+            for partition in catalog.leaf_partitions(table) {
+                size += self.get_table_size(connection, &partition)?;
+            }
+            return Ok(size);
+        }
+
+        if connection.precise_size() {
+            if let Some(count) = self.count_small_table(connection, table)? {
+                return Ok(count);
+            }
+        }
+
+        if let Some(size) = connection.catalog()?.size(table) {
+            return Ok(size);
+        }
+
+        let query = connection.compatibility()?.table_size_query();
         let row = connection
-            .client
-            .query_one(TABLE_SIZE_QUERY, &[&table.tablename, &table.schemaname])?;
-        let size: i64 = row.get("len");
-        Ok(size)
+            .catalog_client()
+            .query_opt(query, &[&table.tablename, &table.schemaname])?;
+        Ok(row.map_or(0, |row| row.get("len")))
     }
 
     // Get all dependencies (by FK) for `table` in database
@@ -103,17 +293,33 @@ fn get_dependencies(
         connection: &mut Self::Connection,
         table: &Self::Table,
     ) -> Result<Vec<Self::Table>> {
-        let fkeys_iterator = connection
-            .client
-            .query(TABLE_FOREIGN_KEYS, &[&table.get_name()])?
-            .into_iter()
-            .map(|row| row.into());
+        let referenced: Vec<(String, String)> = match connection.catalog()?.foreign_keys(table) {
+            Some(fkeys) => fkeys
+                .iter()
+                .filter(|fkey| fkey.table_schema == table.schemaname)
+                .map(|fkey| {
+                    (
+                        fkey.foreign_table_name.clone(),
+                        fkey.foreign_table_schema.clone(),
+                    )
+                })
+                .collect(),
+            None => connection
+                .catalog_client()
+                .query(TABLE_FOREIGN_KEYS, &[&table.get_name()])?
+                .into_iter()
+                .map(ForeignKey::from)
+                .filter(|fkey| fkey.table_schema == table.schemaname)
+                .map(|fkey| (fkey.foreign_table_name, fkey.foreign_table_schema))
+                .collect(),
+        };
 
-        let tables: Vec<Self::Table> = fkeys_iterator
+        let tables: Vec<Self::Table> = referenced
+            .into_iter()
+            // The tables of the excluded schemas aren't dumped
+            .filter(|(_, schema)| self.schema_filter.accepts(schema))
             // Table from foreign key
-            .map(|fkey: ForeignKey| {
-                PgTable::new(fkey.foreign_table_name, fkey.foreign_table_schema)
-            })
+            .map(|(name, schema)| PgTable::new(name, schema))
             // Columns for table
             .map(|mut table| {
                 if let Ok(columns) = self.get_columns(connection, &table) {
@@ -123,10 +329,7 @@ fn get_dependencies(
                     table.set_sequences(sequences);
                 };
 
-                match self.get_table_size(connection, &table) {
-                    Ok(size) => table.size = size as i64,
-                    Err(e) => println!("ERR: {}", e),
-                }
+                table.size = self.table_size_or_warn(connection, &table);
 
                 table
             })
@@ -140,8 +343,12 @@ fn get_columns(
         connection: &mut Self::Connection,
         table: &Self::Table,
     ) -> Result<Vec<Self::Column>> {
+        if let Some(columns) = connection.catalog()?.columns(table) {
+            return Ok(columns);
+        }
+
         let items: Vec<Self::Column> = connection
-            .client
+            .catalog_client()
             .query(TABLE_COLUMNS_QUERY, &[&table.schemaname, &table.tablename])?
             .into_iter()
             .map(|row| row.into())
@@ -151,25 +358,221 @@ fn get_columns(
 }
 
 impl PgSchemaInspector {
+    pub fn new(schema_filter: SchemaFilter) -> Self {
+        Self { schema_filter }
+    }
+
+    pub fn schema_filter(&self) -> &SchemaFilter {
+        &self.schema_filter
+    }
+
+    /// The materialized views (relkind `m`) with their dependencies, they aren't in `get_tables`
+    pub fn get_materialized_views(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+    ) -> Result<Vec<MaterializedView>> {
+        materialized_views::load(connection.catalog_client())
+    }
+
+    pub fn get_column_details(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+        table: &<Self as SchemaInspector>::Table,
+    ) -> Result<Vec<ColumnDetails>> {
+        let columns = connection
+            .catalog_client()
+            .query(TABLE_COLUMN_DETAILS, &[&table.quoted_full_name()])?
+            .into_iter()
+            .map(|row| ColumnDetails {
+                name: row.get(0),
+                data_type: row.get(1),
+                nullable: row.get(2),
+                default: row.get(3),
+            })
+            .collect();
+
+        Ok(columns)
+    }
+
+    pub fn get_unique_indexes(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+        table: &<Self as SchemaInspector>::Table,
+    ) -> Result<Vec<UniqueIndex>> {
+        let indexes = connection
+            .catalog_client()
+            .query(TABLE_UNIQUE_INDEXES, &[&table.quoted_full_name()])?
+            .into_iter()
+            .map(|row| UniqueIndex {
+                name: row.get(0),
+                primary: row.get(1),
+                columns: row.get(2),
+            })
+            .collect();
+
+        Ok(indexes)
+    }
+
+    /// Returns indexes with expressions or predicates
+    pub fn get_expression_indexes(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+        table: &<Self as SchemaInspector>::Table,
+    ) -> Result<Vec<ExpressionIndex>> {
+        let indexes = connection
+            .catalog_client()
+            .query(TABLE_EXPRESSION_INDEXES, &[&table.quoted_full_name()])?
+            .into_iter()
+            .map(|row| ExpressionIndex {
+                name: row.get(0),
+                definition: row.get(1),
+                expressions: row.get(2),
+                predicate: row.get(3),
+            })
+            .collect();
+
+        Ok(indexes)
+    }
+
+    /// Returns foreign key counts for all tables with foreign keys (the keys are full table names)
+    pub fn get_foreign_key_counts(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+    ) -> Result<HashMap<String, ForeignKeyCounts>> {
+        let mut counts: HashMap<String, ForeignKeyCounts> = HashMap::new();
+        for link in self.get_foreign_key_links(connection)? {
+            counts.entry(link.table).or_default().outgoing += 1;
+            counts.entry(link.referenced_table).or_default().incoming += 1;
+        }
+
+        Ok(counts)
+    }
+
+    /// Returns all foreign keys in the database
+    pub fn get_foreign_key_links(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+    ) -> Result<Vec<ForeignKeyLink>> {
+        let links = connection
+            .catalog_client()
+            .query(FOREIGN_KEY_TABLES, &[])?
+            .into_iter()
+            .map(|row| ForeignKeyLink {
+                name: row.get(0),
+                table: row.get(1),
+                referenced_table: row.get(2),
+            })
+            .collect();
+
+        Ok(links)
+    }
+
+    // A failed estimate doesn't fail the dump, the table gets no progress estimate
+    fn table_size_or_warn(&self, connection: &mut connector::Connection, table: &PgTable) -> i64 {
+        match self.get_table_size(connection, table) {
+            Ok(size) => size,
+            Err(e) => {
+                connection.warn(format!(
+                    "Can't estimate the size of the table `{}`: {}",
+                    table.get_full_name(),
+                    e
+                ));
+                0
+            }
+        }
+    }
+
+    // The exact count of rows of the tables up to `PRECISE_SIZE_MAX_BYTES` (`None` for the larger tables,
+    // the views and the foreign tables)
+    fn count_small_table(
+        &self,
+        connection: &mut connector::Connection,
+        table: &PgTable,
+    ) -> Result<Option<i64>> {
+        let row = connection.catalog_client().query_opt(
+            "SELECT pg_catalog.pg_total_relation_size(c.oid) FROM pg_catalog.pg_class c
+             WHERE c.oid = $1::text::regclass AND c.relkind IN ('r', 'p')",
+            &[&table.quoted_full_name()],
+        )?;
+        match row.map(|row| row.get::<_, i64>(0)) {
+            Some(bytes) if bytes <= PRECISE_SIZE_MAX_BYTES => {
+                let query = format!("SELECT count(*) FROM {}", table.quoted_full_name());
+                Ok(Some(
+                    connection
+                        .catalog_client()
+                        .query_one(query.as_str(), &[])?
+                        .get(0),
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the table size on disk (in bytes)
+    pub fn get_relation_size(
+        &self,
+        connection: &mut <Self as SchemaInspector>::Connection,
+        table: &<Self as SchemaInspector>::Table,
+    ) -> Result<i64> {
+        let row = connection.catalog_client().query_one(
+            "SELECT pg_catalog.pg_total_relation_size($1::text::regclass)",
+            &[&table.quoted_full_name()],
+        )?;
+        Ok(row.get(0))
+    }
+
     pub fn get_sequences(
         &self,
         connection: &mut <Self as SchemaInspector>::Connection,
         table: &<Self as SchemaInspector>::Table,
     ) -> Result<Vec<PgSequence>> {
-        let mut sequences = vec![];
-        for col in table.columns.iter() {
-            let full_name: Option<String> = connection
-                .client
-                .query_one(
-                    "SELECT pg_catalog.pg_get_serial_sequence($1, $2)",
-                    &[&table.quoted_full_name(), &col.name],
-                )?
-                .get(0);
-            if let Some(full_name) = full_name {
-                sequences.push(PgSequence { full_name });
-            }
+        if let Some(sequences) = connection.catalog()?.sequences(table) {
+            return Ok(sequences);
         }
 
+        let sequences = connection
+            .catalog_client()
+            .query(TABLE_SEQUENCES, &[&table.quoted_full_name()])?
+            .into_iter()
+            .map(|row| PgSequence {
+                column: row.get(0),
+                full_name: row.get(1),
+            })
+            .collect();
+
         Ok(sequences)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_filter() {
+        let all = SchemaFilter::default();
+        assert!(all.is_empty());
+        assert!(all.accepts("public"));
+        assert!(all.pg_dump_args().is_empty());
+
+        let filter = SchemaFilter::new(
+            vec!["public".to_string(), "billing".to_string()],
+            vec!["billing".to_string()],
+        );
+        assert!(filter.accepts("public"));
+        assert!(!filter.accepts("billing"));
+        assert!(!filter.accepts("tenant_1"));
+        assert_eq!(
+            filter.pg_dump_args(),
+            [
+                "--schema=public",
+                "--schema=billing",
+                "--exclude-schema=billing"
+            ]
+        );
+
+        let filter = SchemaFilter::new(vec![], vec!["audit".to_string()]);
+        assert!(filter.accepts("public"));
+        assert!(!filter.accepts("audit"));
+    }
+}
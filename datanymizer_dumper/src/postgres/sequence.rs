@@ -1,6 +1,13 @@
+use super::{copy_codec, table::PgTable};
+use crate::Table;
+use datanymizer_engine::SequenceAction;
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PgSequence {
     pub full_name: String,
+    /// The column that owns the sequence
+    pub column: String,
 }
 
 impl PgSequence {
@@ -11,7 +18,152 @@ pub fn setval_query(&self, last_value: i64) -> String {
         )
     }
 
+    /// The next value will be `value`
+    pub fn start_at_query(&self, value: i64) -> String {
+        format!(
+            "SELECT pg_catalog.setval('{}', {}, false);",
+            self.full_name, value
+        )
+    }
+
+    pub fn restart_query(&self) -> String {
+        format!("ALTER SEQUENCE {} RESTART;", self.full_name)
+    }
+
     pub fn last_value_query(&self) -> String {
         format!("SELECT last_value FROM {}", self.full_name)
     }
+
+    /// Names for matching with the config: the sequence name and `table.column` (with schema)
+    pub fn config_names(&self, table_full_name: &str) -> [String; 2] {
+        [
+            self.full_name.clone(),
+            format!("{}.{}", table_full_name, self.column),
+        ]
+    }
+
+    pub fn find_action<'a>(
+        &self,
+        table_full_name: &str,
+        actions: &'a HashMap<String, SequenceAction>,
+    ) -> Option<&'a SequenceAction> {
+        self.config_names(table_full_name)
+            .iter()
+            .find_map(|name| actions.get(name))
+    }
+}
+
+/// The maximum values of the columns of the table sequences in the dumped rows (the sequences of the tables
+/// with limited rows are set to them, the source values could be much greater)
+#[derive(Debug, Default)]
+pub struct MaxValues {
+    /// The column indexes and the values (in the order of the table sequences)
+    values: Vec<(Option<usize>, Option<i64>)>,
+}
+
+impl MaxValues {
+    pub fn new(table: &PgTable) -> Self {
+        Self {
+            values: table
+                .sequences
+                .iter()
+                .map(|seq| (table.get_column_indexes().get(&seq.column).copied(), None))
+                .collect(),
+        }
+    }
+
+    /// Adds a COPY line (the NULL and non-integer values are skipped)
+    pub fn add_row(&mut self, line: &[u8]) {
+        for (index, max) in &mut self.values {
+            let value = index
+                .and_then(|i| copy_codec::fields(line).nth(i))
+                .and_then(|field| std::str::from_utf8(field).ok()?.parse::<i64>().ok());
+            if let Some(value) = value {
+                *max = Some(max.map_or(value, |m| m.max(value)));
+            }
+        }
+    }
+
+    /// The maximum value of the column of the table sequence with the index
+    pub fn get(&self, sequence: usize) -> Option<i64> {
+        self.values.get(sequence).and_then(|(_, max)| *max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+
+    fn seq() -> PgSequence {
+        PgSequence {
+            full_name: "public.users_id_seq".to_string(),
+            column: "id".to_string(),
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let s = seq();
+        assert_eq!(
+            s.setval_query(15),
+            "SELECT pg_catalog.setval('public.users_id_seq', 15, true);"
+        );
+        assert_eq!(
+            s.start_at_query(1000000),
+            "SELECT pg_catalog.setval('public.users_id_seq', 1000000, false);"
+        );
+        assert_eq!(
+            s.restart_query(),
+            "ALTER SEQUENCE public.users_id_seq RESTART;"
+        );
+    }
+
+    #[test]
+    fn max_values() {
+        let columns = ["name", "id", "code"].map(|name| (name, ""));
+        let mut table = test_tables::pg_table("public", "users", &columns);
+        table.set_sequences(vec![
+            seq(),
+            PgSequence {
+                full_name: "public.users_code_seq".to_string(),
+                column: "code".to_string(),
+            },
+        ]);
+
+        let mut values = MaxValues::new(&table);
+        assert_eq!(values.get(1), None);
+        for line in ["a\t5\t\\N", "b\t12\t3", "c\t7\t-1"] {
+            values.add_row(line.as_bytes());
+        }
+        assert_eq!(values.get(0), Some(3));
+        assert_eq!(values.get(1), Some(12));
+        assert_eq!(values.get(2), None);
+    }
+
+    #[test]
+    fn find_action() {
+        let s = seq();
+        let mut actions = HashMap::new();
+        assert_eq!(s.find_action("public.users", &actions), None);
+
+        actions.insert("public.users.id".to_string(), SequenceAction::Reset);
+        assert_eq!(
+            s.find_action("public.users", &actions),
+            Some(&SequenceAction::Reset)
+        );
+
+        actions.insert(
+            "public.users_id_seq".to_string(),
+            SequenceAction::StartAt(10),
+        );
+        assert_eq!(
+            s.find_action("public.users", &actions),
+            Some(&SequenceAction::StartAt(10))
+        );
+        assert_eq!(
+            s.find_action("other.users", &actions),
+            Some(&SequenceAction::StartAt(10))
+        );
+    }
 }
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use datanymizer_engine::{
+    fmix64, fnv1a,
+    memory::{self, Component},
+    TempDisk, TempFile,
+};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+    mem,
+    sync::Arc,
+};
+
+/// The memory used by a buffered row in addition to its bytes
+const ROW_OVERHEAD: u64 = 48;
+
+/// The bytes of the key and the length of a spilled row
+const ROW_HEADER: u64 = 16;
+
+/// Over `--max-memory`, the buffer is spilled when it has more than this part of the memory limit
+/// (the spills of a few rows don't free the memory)
+const MIN_SPILL_PART: u64 = 16;
+
+/// Shuffles the rows of a table in the dumper: every row gets a key (a random one or the keyed hash of
+/// the original row if there is a seed), the rows are sorted by the keys. The rows that don't fit in the memory
+/// limit are sorted and spilled to temporary files (runs), the runs are merged at the end.
+/// The buffer is accounted in the memory of the shuffle buffers.
+pub struct Shuffler {
+    seed: Option<u64>,
+    memory_limit: u64,
+    /// The buffer is spilled earlier when the memory of the process exceeds it (`--max-memory`)
+    max_memory: Option<u64>,
+    memory: Arc<Component>,
+    buffer: Vec<(u64, Vec<u8>)>,
+    buffered: u64,
+    /// The spilled bytes of the buffer (the rows and their headers)
+    buffered_on_disk: u64,
+    runs: Vec<TempFile>,
+    temp_disk: Arc<TempDisk>,
+    /// The name of the shuffler in the temporary disk errors
+    component: String,
+}
+
+impl Shuffler {
+    pub fn new(seed: Option<u64>, memory_limit: u64) -> Self {
+        Self {
+            seed,
+            memory_limit,
+            max_memory: None,
+            memory: memory::accountant().component(memory::SHUFFLE_BUFFERS),
+            buffer: vec![],
+            buffered: 0,
+            buffered_on_disk: 0,
+            runs: vec![],
+            temp_disk: Arc::default(),
+            component: "the shuffle".to_string(),
+        }
+    }
+
+    /// The runs are created in the directory of `temp_disk` and accounted in its budget
+    pub fn with_temp_disk(mut self, temp_disk: Arc<TempDisk>, table: &str) -> Self {
+        self.temp_disk = temp_disk;
+        self.component = format!("the shuffle of `{}` (`shuffle_rows`)", table);
+        self
+    }
+
+    pub fn with_max_memory(mut self, max_memory: Option<u64>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// The key of the row (`original` is the row before the transformation)
+    pub fn key(&self, original: &[u8]) -> u64 {
+        match self.seed {
+            Some(seed) => {
+                let mut bytes = seed.to_le_bytes().to_vec();
+                bytes.extend_from_slice(original);
+                fmix64(fnv1a(&bytes))
+            }
+            None => rand::random(),
+        }
+    }
+
+    pub fn push(&mut self, key: u64, row: Vec<u8>) -> Result<()> {
+        self.buffered += row.len() as u64 + ROW_OVERHEAD;
+        self.buffered_on_disk += row.len() as u64 + ROW_HEADER;
+        self.memory.add(row.len() as u64 + ROW_OVERHEAD);
+        self.buffer.push((key, row));
+        if self.buffered > self.memory_limit
+            || (self.buffered > self.memory_limit / MIN_SPILL_PART
+                && memory::accountant().exceeds(self.max_memory))
+        {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The count of the temporary files
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Writes the shuffled rows (every row is followed by a newline)
+    pub fn finish(mut self, w: &mut dyn Write) -> Result<()> {
+        self.buffer.sort_unstable();
+        if self.runs.is_empty() {
+            for (_, row) in &self.buffer {
+                w.write_all(row)?;
+                w.write_all(b"\n")?;
+            }
+            return Ok(());
+        }
+
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        let mut readers = mem::take(&mut self.runs)
+            .into_iter()
+            .map(|mut run| {
+                run.rewind()?;
+                Ok(BufReader::new(run))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some((key, row)) = read_row(reader)? {
+                heap.push(Reverse((key, row, i)));
+            }
+        }
+        while let Some(Reverse((_, row, i))) = heap.pop() {
+            w.write_all(&row)?;
+            w.write_all(b"\n")?;
+            if let Some((key, row)) = read_row(&mut readers[i])? {
+                heap.push(Reverse((key, row, i)));
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the sorted buffer to a new run
+    fn spill(&mut self) -> Result<()> {
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.sort_unstable();
+        self.memory.release(mem::take(&mut self.buffered));
+        let size = mem::take(&mut self.buffered_on_disk);
+
+        let mut run = self.temp_disk.create(&self.component, size)?;
+        let mut writer = BufWriter::new(&mut run);
+        for (key, row) in buffer {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&(row.len() as u64).to_le_bytes())?;
+            writer.write_all(&row)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        self.runs.push(run);
+        Ok(())
+    }
+}
+
+impl Drop for Shuffler {
+    fn drop(&mut self) {
+        self.memory.release(self.buffered);
+    }
+}
+
+fn read_row(reader: &mut impl Read) -> Result<Option<(u64, Vec<u8>)>> {
+    let mut key = [0; 8];
+    match reader.read_exact(&mut key) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let mut row = vec![0; u64::from_le_bytes(len) as usize];
+    reader.read_exact(&mut row)?;
+    Ok(Some((u64::from_le_bytes(key), row)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn temp_disk(name: &str, max_bytes: Option<u64>) -> Arc<TempDisk> {
+        let dir = env::temp_dir().join(format!("datanymizer_shuffle_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Arc::new(TempDisk::new(dir, max_bytes))
+    }
+
+    fn is_empty(temp_disk: &TempDisk) -> bool {
+        fs::read_dir(temp_disk.dir()).unwrap().next().is_none()
+    }
+
+    fn rows(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("{}\tname {}", i, i).into_bytes())
+            .collect()
+    }
+
+    fn shuffle(shuffler: &mut Shuffler, rows: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        for row in rows {
+            let key = shuffler.key(row);
+            shuffler.push(key, row.clone()).unwrap();
+        }
+        let shuffler = mem::replace(shuffler, Shuffler::new(None, 0));
+        let mut out = vec![];
+        shuffler.finish(&mut out).unwrap();
+        out.split(|b| *b == b'\n')
+            .filter(|row| !row.is_empty())
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    fn sorted(mut rows: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn in_memory() {
+        let rows = rows(1000);
+        let mut shuffler = Shuffler::new(None, 1 << 20);
+        let shuffled = shuffle(&mut shuffler, &rows);
+        assert_ne!(shuffled, rows);
+        assert_eq!(sorted(shuffled), sorted(rows));
+    }
+
+    #[test]
+    fn spilled() {
+        let rows = rows(1000);
+        let temp_disk = temp_disk("spilled", None);
+        let mut shuffler =
+            Shuffler::new(None, 1000).with_temp_disk(temp_disk.clone(), "public.users");
+        for row in &rows {
+            let key = shuffler.key(row);
+            shuffler.push(key, row.clone()).unwrap();
+        }
+        assert!(shuffler.runs() > 10);
+        assert!(temp_disk.used() > 0);
+
+        let mut out = vec![];
+        shuffler.finish(&mut out).unwrap();
+        let shuffled: Vec<_> = out
+            .split(|b| *b == b'\n')
+            .filter(|row| !row.is_empty())
+            .map(|row| row.to_vec())
+            .collect();
+        assert_ne!(shuffled, rows);
+        assert_eq!(sorted(shuffled), sorted(rows));
+        assert_eq!(temp_disk.used(), 0);
+        assert!(is_empty(&temp_disk));
+    }
+
+    #[test]
+    fn spilled_over_max_memory() {
+        let rows = rows(1000);
+        let temp_disk = temp_disk("spilled_over_max_memory", None);
+        // the memory of the process always exceeds the limit
+        let mut shuffler = Shuffler::new(None, 16_000)
+            .with_temp_disk(temp_disk.clone(), "public.users")
+            .with_max_memory(Some(0));
+        for row in &rows {
+            let key = shuffler.key(row);
+            shuffler.push(key, row.clone()).unwrap();
+        }
+        assert!(shuffler.runs() > 10);
+
+        let mut out = vec![];
+        shuffler.finish(&mut out).unwrap();
+        assert_eq!(out.split(|b| *b == b'\n').count(), 1001);
+        assert_eq!(temp_disk.used(), 0);
+    }
+
+    #[test]
+    fn over_budget() {
+        let temp_disk = temp_disk("over_budget", Some(5000));
+        let mut shuffler =
+            Shuffler::new(None, 1000).with_temp_disk(temp_disk.clone(), "public.users");
+        let error = rows(1000)
+            .into_iter()
+            .try_for_each(|row| shuffler.push(shuffler.key(&row), row))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("The temporary files of the shuffle of `public.users` (`shuffle_rows`) exceed --max-temp-disk 4.9kB"));
+
+        drop(shuffler);
+        assert_eq!(temp_disk.used(), 0);
+        assert!(is_empty(&temp_disk));
+    }
+
+    #[test]
+    fn seeded() {
+        let rows = rows(500);
+        let mut reversed = rows.clone();
+        reversed.reverse();
+
+        let first = shuffle(&mut Shuffler::new(Some(42), 1 << 20), &rows);
+        // the same order regardless of the source order and of the spilling
+        assert_eq!(
+            shuffle(&mut Shuffler::new(Some(42), 1000), &reversed),
+            first
+        );
+        assert_ne!(shuffle(&mut Shuffler::new(Some(43), 1 << 20), &rows), first);
+        assert_ne!(first, rows);
+    }
+}
@@ -0,0 +1,794 @@
+use super::{query_wrapper::QueryWrapper, table::PgTable};
+use crate::Table;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::Subset;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// Foreign keys with their columns (in the key order). A foreign key is nullable if all its columns are nullable.
+const FOREIGN_KEYS_QUERY: &str = "SELECT c.conname::text,
+                                      n.nspname || '.' || t.relname,
+                                      ARRAY(
+                                          SELECT a.attname::text
+                                          FROM unnest(c.conkey) WITH ORDINALITY k(attnum, i)
+                                          JOIN pg_catalog.pg_attribute a
+                                          ON a.attrelid = c.conrelid AND a.attnum = k.attnum
+                                          ORDER BY k.i
+                                      ),
+                                      rn.nspname || '.' || rt.relname,
+                                      ARRAY(
+                                          SELECT a.attname::text
+                                          FROM unnest(c.confkey) WITH ORDINALITY k(attnum, i)
+                                          JOIN pg_catalog.pg_attribute a
+                                          ON a.attrelid = c.confrelid AND a.attnum = k.attnum
+                                          ORDER BY k.i
+                                      ),
+                                      NOT EXISTS (
+                                          SELECT 1 FROM pg_catalog.pg_attribute a
+                                          WHERE a.attrelid = c.conrelid AND a.attnum = ANY(c.conkey)
+                                          AND a.attnotnull
+                                      )
+                                  FROM pg_catalog.pg_constraint c
+                                  JOIN pg_catalog.pg_class t ON t.oid = c.conrelid
+                                  JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace
+                                  JOIN pg_catalog.pg_class rt ON rt.oid = c.confrelid
+                                  JOIN pg_catalog.pg_namespace rn ON rn.oid = rt.relnamespace
+                                  WHERE c.contype = 'f'
+                                  ORDER BY 2, 1";
+
+const PRIMARY_KEYS_QUERY: &str = "SELECT n.nspname || '.' || c.relname,
+                                      ARRAY(
+                                          SELECT a.attname::text
+                                          FROM unnest(i.indkey::int2[]) WITH ORDINALITY k(attnum, i)
+                                          JOIN pg_catalog.pg_attribute a
+                                          ON a.attrelid = i.indrelid AND a.attnum = k.attnum
+                                          ORDER BY k.i
+                                      )
+                                  FROM pg_catalog.pg_index i
+                                  JOIN pg_catalog.pg_class c ON c.oid = i.indrelid
+                                  JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                                  WHERE i.indisprimary";
+
+const TEMP_TABLE_PREFIX: &str = "datanymizer_subset_";
+
+/// How the table data is restricted to the subset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsetFilter {
+    /// The condition of the selected rows (`None` if all rows are dumped)
+    pub condition: Option<String>,
+    /// The column expressions if the values of the cut foreign keys are replaced with NULL
+    pub columns: Option<Vec<String>>,
+    /// The count of the selected rows
+    pub rows: Option<u64>,
+}
+
+/// Why the rows of a table are selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Selection {
+    /// The seed rows
+    Root,
+    /// The rows referenced by the selected rows
+    Parent,
+    /// The rows pulled by the `children` rules
+    Child,
+    /// The rows of other tables that reference the selected rows (only they are dumped)
+    Referencing,
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Root => "root",
+            Self::Parent => "parents",
+            Self::Child => "children",
+            Self::Referencing => "referencing",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The selected row counts of the subset tables (by the full table names)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubsetPlan {
+    pub tables: BTreeMap<String, TablePlan>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TablePlan {
+    pub rows: u64,
+    pub selections: BTreeSet<Selection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ForeignKey {
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    referenced_table: String,
+    referenced_columns: Vec<String>,
+    nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    quoted_name: String,
+    columns: Vec<String>,
+    /// The primary key (the rows of the tables without primary keys are identified by `ctid`)
+    key: Vec<String>,
+    /// The table with the keys of the selected rows
+    temp: Option<String>,
+    selections: BTreeSet<Selection>,
+}
+
+/// A child rule with its foreign keys
+struct ChildRule {
+    foreign_keys: Vec<usize>,
+    max_depth: u32,
+    max_rows: Option<u64>,
+    pulled: u64,
+}
+
+/// Selects the subset rows: the keys of the selected rows are saved to temporary tables on the source connection
+/// (in the dump transaction, so the data is dumped from the same snapshot), then the data of the tables is dumped
+/// with the conditions that check these keys.
+///
+/// The selection goes in rounds. Every round pulls the parents of the selected rows (transitively, until there are
+/// no new rows, so cycles and self-references are closed too), then the children of the selected rows by the
+/// `children` rules whose `max_depth` is not reached yet. The selection stops when a round selects no new rows.
+/// Then the other tables that reference the selected tables get the rows whose foreign keys are NULL or point to
+/// the selected rows (these rows are removed transitively, in cycles too).
+pub struct Subsetter {
+    nodes: BTreeMap<String, Node>,
+    foreign_keys: Vec<ForeignKey>,
+    /// The foreign keys that are not followed (their values are set to NULL)
+    cut: BTreeSet<usize>,
+    temp_count: usize,
+}
+
+impl Subsetter {
+    pub fn new(tables: &[(PgTable, i32)], qw: &mut QueryWrapper) -> Result<Self> {
+        let mut nodes: BTreeMap<String, Node> = tables
+            .iter()
+            .map(|(t, _)| {
+                (
+                    t.get_full_name(),
+                    Node {
+                        quoted_name: t.quoted_full_name(),
+                        columns: t.get_columns_names(),
+                        key: vec![],
+                        temp: None,
+                        selections: BTreeSet::new(),
+                    },
+                )
+            })
+            .collect();
+        for row in qw.query(PRIMARY_KEYS_QUERY, &[])? {
+            if let Some(node) = nodes.get_mut(row.get::<_, &str>(0)) {
+                node.key = row.get(1);
+            }
+        }
+
+        let foreign_keys = qw
+            .query(FOREIGN_KEYS_QUERY, &[])?
+            .into_iter()
+            .map(|row| ForeignKey {
+                name: row.get(0),
+                table: row.get(1),
+                columns: row.get(2),
+                referenced_table: row.get(3),
+                referenced_columns: row.get(4),
+                nullable: row.get(5),
+            })
+            // the foreign keys of the tables that are not dumped are ignored
+            .filter(|fk| nodes.contains_key(&fk.table) && nodes.contains_key(&fk.referenced_table))
+            .collect();
+
+        Ok(Self {
+            nodes,
+            foreign_keys,
+            cut: BTreeSet::new(),
+            temp_count: 0,
+        })
+    }
+
+    /// Selects the rows and sets the filters of the tables
+    pub fn select(
+        mut self,
+        subset: &Subset,
+        tables: &mut [(PgTable, i32)],
+        qw: &mut QueryWrapper,
+    ) -> Result<SubsetPlan> {
+        for name in &subset.cut_foreign_keys {
+            let ids: Vec<_> = self.foreign_keys_named(name).collect();
+            if ids.is_empty() {
+                return Err(anyhow!(
+                    "Unknown foreign key `{}` in `cut_foreign_keys`",
+                    name
+                ));
+            }
+            for id in ids {
+                if !self.foreign_keys[id].nullable {
+                    return Err(anyhow!(
+                        "The foreign key `{}` in `cut_foreign_keys` is not nullable",
+                        name
+                    ));
+                }
+                self.cut.insert(id);
+            }
+        }
+
+        let mut rules = vec![];
+        for children in &subset.children {
+            let table_names = matching_tables(tables, &children.table);
+            if table_names.is_empty() {
+                return Err(anyhow!(
+                    "Unknown subset children table `{}`",
+                    children.table
+                ));
+            }
+            let foreign_keys: Vec<_> = (0..self.foreign_keys.len())
+                .filter(|id| table_names.contains(&self.foreign_keys[*id].table))
+                .filter(|id| {
+                    children
+                        .foreign_key
+                        .as_ref()
+                        .is_none_or(|name| &self.foreign_keys[*id].name == name)
+                })
+                .collect();
+            if foreign_keys.is_empty() {
+                return Err(anyhow!(
+                    "The subset children table `{}` has no foreign key{}",
+                    children.table,
+                    children
+                        .foreign_key
+                        .as_ref()
+                        .map(|name| format!(" `{}`", name))
+                        .unwrap_or_default()
+                ));
+            }
+            rules.push(ChildRule {
+                foreign_keys,
+                max_depth: children.max_depth,
+                max_rows: children.max_rows,
+                pulled: 0,
+            });
+        }
+
+        for (root, condition) in &subset.roots {
+            let table_names = matching_tables(tables, root);
+            if table_names.is_empty() {
+                return Err(anyhow!("Unknown subset root table `{}`", root));
+            }
+            for name in table_names {
+                self.select_roots(&name, condition, qw)?;
+            }
+        }
+
+        let mut round = 1;
+        loop {
+            let mut selected = self.pull_parents(qw)?;
+            for rule in rules.iter_mut().filter(|r| r.max_depth >= round) {
+                selected += self.pull_children(rule, qw)?;
+            }
+            if selected == 0 {
+                break;
+            }
+            round += 1;
+        }
+        self.select_referencing(qw)?;
+
+        let mut plan = SubsetPlan::default();
+        for (name, node) in &self.nodes {
+            if let Some(temp) = &node.temp {
+                qw.batch_execute(&format!("ANALYZE pg_temp.{}", temp))?;
+                let rows: i64 = qw
+                    .query_one(
+                        format!("SELECT count(*) FROM pg_temp.{}", temp).as_str(),
+                        &[],
+                    )?
+                    .get(0);
+                plan.tables.insert(
+                    name.clone(),
+                    TablePlan {
+                        rows: rows as u64,
+                        selections: node.selections.clone(),
+                    },
+                );
+            }
+        }
+        for (table, _) in tables.iter_mut() {
+            table.subset = self.filter(&table.get_full_name(), &plan);
+        }
+
+        Ok(plan)
+    }
+
+    fn foreign_keys_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = usize> + 'a {
+        (0..self.foreign_keys.len()).filter(move |id| self.foreign_keys[*id].name == name)
+    }
+
+    fn select_roots(&mut self, table: &str, condition: &str, qw: &mut QueryWrapper) -> Result<()> {
+        self.ensure_temp(table, Selection::Root, qw)?;
+        let node = &self.nodes[table];
+        let query = format!(
+            "INSERT INTO pg_temp.{} SELECT {} FROM {} AS t WHERE ({}) AND NOT {}",
+            node.temp.as_deref().unwrap_or_default(),
+            self.key_exprs(table, "t").join(", "),
+            node.quoted_name,
+            condition,
+            self.selected(table, "t")
+        );
+        qw.execute(query.as_str(), &[])
+            .map_err(|e| anyhow!("Error in the subset root condition of `{}`: {}", table, e))?;
+        Ok(())
+    }
+
+    // Pulls the parents of the selected rows until there are no new rows, returns the count of the new rows
+    fn pull_parents(&mut self, qw: &mut QueryWrapper) -> Result<u64> {
+        let mut total = 0;
+        loop {
+            let mut selected = 0;
+            for id in 0..self.foreign_keys.len() {
+                let fk = &self.foreign_keys[id];
+                if self.cut.contains(&id) || self.nodes[&fk.table].temp.is_none() {
+                    continue;
+                }
+                let (child, parent) = (fk.table.clone(), fk.referenced_table.clone());
+                self.ensure_temp(&parent, Selection::Parent, qw)?;
+                let query = format!(
+                    "INSERT INTO pg_temp.{} SELECT {} FROM {} AS p WHERE NOT {} \
+                    AND EXISTS (SELECT 1 FROM {} AS c WHERE {} AND {})",
+                    self.temp(&parent),
+                    self.key_exprs(&parent, "p").join(", "),
+                    self.nodes[&parent].quoted_name,
+                    self.selected(&parent, "p"),
+                    self.nodes[&child].quoted_name,
+                    self.references(id, "c", "p"),
+                    self.selected(&child, "c"),
+                );
+                let rows = qw.execute(query.as_str(), &[])?;
+                if rows > 0 {
+                    self.add_selection(&parent, Selection::Parent);
+                }
+                selected += rows;
+            }
+            if selected == 0 {
+                return Ok(total);
+            }
+            total += selected;
+        }
+    }
+
+    // Returns the count of the new rows
+    fn pull_children(&mut self, rule: &mut ChildRule, qw: &mut QueryWrapper) -> Result<u64> {
+        let mut total = 0;
+        for id in rule.foreign_keys.clone() {
+            let fk = &self.foreign_keys[id];
+            if self.nodes[&fk.referenced_table].temp.is_none() {
+                continue;
+            }
+            let limit = match rule.max_rows {
+                Some(max_rows) if rule.pulled >= max_rows => return Ok(total),
+                Some(max_rows) => format!(" LIMIT {}", max_rows - rule.pulled),
+                None => String::new(),
+            };
+            let (child, parent) = (fk.table.clone(), fk.referenced_table.clone());
+            self.ensure_temp(&child, Selection::Child, qw)?;
+            let query = format!(
+                "INSERT INTO pg_temp.{} SELECT {} FROM {} AS c WHERE NOT {} \
+                AND EXISTS (SELECT 1 FROM {} AS p WHERE {} AND {}){}",
+                self.temp(&child),
+                self.key_exprs(&child, "c").join(", "),
+                self.nodes[&child].quoted_name,
+                self.selected(&child, "c"),
+                self.nodes[&parent].quoted_name,
+                self.references(id, "c", "p"),
+                self.selected(&parent, "p"),
+                limit
+            );
+            let selected = qw.execute(query.as_str(), &[])?;
+            if selected > 0 {
+                self.add_selection(&child, Selection::Child);
+            }
+            rule.pulled += selected;
+            total += selected;
+        }
+        Ok(total)
+    }
+
+    // The tables that reference the selected tables (not by the cut foreign keys) get the rows whose foreign keys
+    // are satisfied. All rows that satisfy the foreign keys to the tables selected by the rounds are selected first,
+    // then the rows whose parents are not selected are removed until there are no such rows.
+    fn select_referencing(&mut self, qw: &mut QueryWrapper) -> Result<()> {
+        let selected: BTreeSet<String> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.temp.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        let mut referencing = BTreeSet::new();
+        loop {
+            let new: BTreeSet<String> = (0..self.foreign_keys.len())
+                .filter(|id| !self.cut.contains(id))
+                .map(|id| &self.foreign_keys[id])
+                .filter(|fk| {
+                    !selected.contains(&fk.table)
+                        && !referencing.contains(&fk.table)
+                        && (selected.contains(&fk.referenced_table)
+                            || referencing.contains(&fk.referenced_table))
+                })
+                .map(|fk| fk.table.clone())
+                .collect();
+            if new.is_empty() {
+                break;
+            }
+            referencing.extend(new);
+        }
+
+        for table in &referencing {
+            let conditions: Vec<_> = self
+                .outgoing(table)
+                .filter(|id| selected.contains(&self.foreign_keys[*id].referenced_table))
+                .map(|id| self.satisfied(id, "c"))
+                .collect();
+            self.ensure_temp(table, Selection::Referencing, qw)?;
+            let query = format!(
+                "INSERT INTO pg_temp.{} SELECT {} FROM {} AS c{}",
+                self.temp(table),
+                self.key_exprs(table, "c").join(", "),
+                self.nodes[table].quoted_name,
+                if conditions.is_empty() {
+                    String::new()
+                } else {
+                    format!(" WHERE {}", conditions.join(" AND "))
+                }
+            );
+            qw.execute(query.as_str(), &[])?;
+        }
+
+        loop {
+            let mut removed = 0;
+            for table in &referencing {
+                for id in self.outgoing(table).collect::<Vec<_>>() {
+                    if !referencing.contains(&self.foreign_keys[id].referenced_table) {
+                        continue;
+                    }
+                    let temp = self.temp(table);
+                    let query = format!(
+                        "DELETE FROM pg_temp.{} USING {} AS c WHERE {} AND NOT {}",
+                        temp,
+                        self.nodes[table].quoted_name,
+                        self.key_matches(table, temp, "c"),
+                        self.satisfied(id, "c")
+                    );
+                    removed += qw.execute(query.as_str(), &[])?;
+                }
+            }
+            if removed == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // The foreign keys of the table that are not cut
+    fn outgoing<'a>(&'a self, table: &'a str) -> impl Iterator<Item = usize> + 'a {
+        (0..self.foreign_keys.len())
+            .filter(move |id| self.foreign_keys[*id].table == table && !self.cut.contains(id))
+    }
+
+    fn ensure_temp(
+        &mut self,
+        table: &str,
+        selection: Selection,
+        qw: &mut QueryWrapper,
+    ) -> Result<()> {
+        if self.nodes[table].temp.is_none() {
+            let temp = format!("{}{}", TEMP_TABLE_PREFIX, self.temp_count);
+            self.temp_count += 1;
+            let columns: Vec<_> = self
+                .key_exprs(table, "t")
+                .into_iter()
+                .enumerate()
+                .map(|(i, expr)| format!("{} AS k{}", expr, i))
+                .collect();
+            let keys: Vec<_> = (0..columns.len()).map(|i| format!("k{}", i)).collect();
+            qw.batch_execute(&format!(
+                "DROP TABLE IF EXISTS pg_temp.{temp};
+                CREATE TEMPORARY TABLE {temp} AS SELECT {} FROM {} AS t WITH NO DATA;
+                ALTER TABLE pg_temp.{temp} ADD PRIMARY KEY ({});",
+                columns.join(", "),
+                self.nodes[table].quoted_name,
+                keys.join(", "),
+                temp = temp
+            ))
+            .map_err(|e| {
+                anyhow!(
+                    "The subset can't be selected (it needs temporary tables in the source database): {}",
+                    e
+                )
+            })?;
+            self.nodes.get_mut(table).unwrap().temp = Some(temp);
+            self.add_selection(table, selection);
+        }
+        Ok(())
+    }
+
+    fn add_selection(&mut self, table: &str, selection: Selection) {
+        if let Some(node) = self.nodes.get_mut(table) {
+            node.selections.insert(selection);
+        }
+    }
+
+    fn temp(&self, table: &str) -> &str {
+        self.nodes[table].temp.as_deref().unwrap_or_default()
+    }
+
+    fn key_exprs(&self, table: &str, alias: &str) -> Vec<String> {
+        let key = &self.nodes[table].key;
+        if key.is_empty() {
+            vec![format!("{}.ctid", alias)]
+        } else {
+            key.iter()
+                .map(|c| format!("{}.{}", alias, quote_ident(c)))
+                .collect()
+        }
+    }
+
+    fn key_matches(&self, table: &str, temp: &str, alias: &str) -> String {
+        self.key_exprs(table, alias)
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| format!("{}.k{} = {}", temp, i, expr))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    // The row of the table (by its alias) is selected
+    fn selected(&self, table: &str, alias: &str) -> String {
+        let temp = self.temp(table);
+        format!(
+            "EXISTS (SELECT 1 FROM pg_temp.{} WHERE {})",
+            temp,
+            self.key_matches(table, temp, alias)
+        )
+    }
+
+    fn references(&self, id: usize, child: &str, parent: &str) -> String {
+        let fk = &self.foreign_keys[id];
+        fk.columns
+            .iter()
+            .zip(&fk.referenced_columns)
+            .map(|(c, p)| {
+                format!(
+                    "{}.{} = {}.{}",
+                    parent,
+                    quote_ident(p),
+                    child,
+                    quote_ident(c)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    // The referenced row is selected
+    fn parent_selected(&self, id: usize, child: &str) -> String {
+        let parent = &self.foreign_keys[id].referenced_table;
+        format!(
+            "EXISTS (SELECT 1 FROM {} AS p WHERE {} AND {})",
+            self.nodes[parent].quoted_name,
+            self.references(id, child, "p"),
+            self.selected(parent, "p")
+        )
+    }
+
+    // The foreign key is satisfied (as `MATCH SIMPLE`: any NULL column satisfies it)
+    fn satisfied(&self, id: usize, child: &str) -> String {
+        let mut conditions: Vec<_> = self.foreign_keys[id]
+            .columns
+            .iter()
+            .map(|c| format!("{}.{} IS NULL", child, quote_ident(c)))
+            .collect();
+        conditions.push(self.parent_selected(id, child));
+        format!("({})", conditions.join(" OR "))
+    }
+
+    fn filter(&self, table: &str, plan: &SubsetPlan) -> Option<SubsetFilter> {
+        let node = self.nodes.get(table)?;
+        let condition = node
+            .temp
+            .as_ref()
+            .map(|_| self.selected(table, &node.quoted_name));
+
+        // the values of the cut foreign keys that reference the rows out of the subset
+        let cut: Vec<_> = self
+            .cut
+            .iter()
+            .filter(|id| {
+                let fk = &self.foreign_keys[**id];
+                fk.table == table && self.nodes[&fk.referenced_table].temp.is_some()
+            })
+            .collect();
+        let columns = if cut.is_empty() {
+            None
+        } else {
+            Some(
+                node.columns
+                    .iter()
+                    .map(|column| {
+                        let conditions: Vec<_> = cut
+                            .iter()
+                            .filter(|id| self.foreign_keys[***id].columns.contains(column))
+                            .map(|id| self.parent_selected(**id, &node.quoted_name))
+                            .collect();
+                        if conditions.is_empty() {
+                            quote_ident(column)
+                        } else {
+                            format!(
+                                "CASE WHEN {} THEN {}.{} END",
+                                conditions.join(" AND "),
+                                node.quoted_name,
+                                quote_ident(column)
+                            )
+                        }
+                    })
+                    .collect(),
+            )
+        };
+
+        if condition.is_none() && columns.is_none() {
+            return None;
+        }
+        Some(SubsetFilter {
+            condition,
+            columns,
+            rows: plan.tables.get(table).map(|t| t.rows),
+        })
+    }
+}
+
+// The tables that match the name (with or without schema)
+fn matching_tables(tables: &[(PgTable, i32)], name: &str) -> Vec<String> {
+    tables
+        .iter()
+        .filter(|(t, _)| t.get_full_name() == name || t.get_name() == name)
+        .map(|(t, _)| t.get_full_name())
+        .collect()
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(quoted_name: &str, columns: &[&str], key: &[&str], temp: Option<&str>) -> Node {
+        Node {
+            quoted_name: quoted_name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            key: key.iter().map(|c| c.to_string()).collect(),
+            temp: temp.map(String::from),
+            selections: BTreeSet::new(),
+        }
+    }
+
+    fn subsetter() -> Subsetter {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(
+            "public.users".to_string(),
+            node(
+                r#""public"."users""#,
+                &["id", "referrer_id"],
+                &["id"],
+                Some("datanymizer_subset_0"),
+            ),
+        );
+        nodes.insert(
+            "public.log".to_string(),
+            node(
+                r#""public"."log""#,
+                &["user_id", "message"],
+                &[],
+                Some("datanymizer_subset_1"),
+            ),
+        );
+        nodes.insert(
+            "public.settings".to_string(),
+            node(r#""public"."settings""#, &["name"], &["name"], None),
+        );
+        let foreign_keys = vec![
+            ForeignKey {
+                name: "users_referrer_id_fkey".to_string(),
+                table: "public.users".to_string(),
+                columns: vec!["referrer_id".to_string()],
+                referenced_table: "public.users".to_string(),
+                referenced_columns: vec!["id".to_string()],
+                nullable: true,
+            },
+            ForeignKey {
+                name: "log_user_id_fkey".to_string(),
+                table: "public.log".to_string(),
+                columns: vec!["user_id".to_string()],
+                referenced_table: "public.users".to_string(),
+                referenced_columns: vec!["id".to_string()],
+                nullable: true,
+            },
+        ];
+        Subsetter {
+            nodes,
+            foreign_keys,
+            cut: BTreeSet::from([0]),
+            temp_count: 2,
+        }
+    }
+
+    #[test]
+    fn conditions() {
+        let s = subsetter();
+        assert_eq!(
+            s.selected("public.users", "p"),
+            "EXISTS (SELECT 1 FROM pg_temp.datanymizer_subset_0 WHERE datanymizer_subset_0.k0 = p.\"id\")"
+        );
+        assert_eq!(
+            s.selected("public.log", "c"),
+            "EXISTS (SELECT 1 FROM pg_temp.datanymizer_subset_1 WHERE datanymizer_subset_1.k0 = c.ctid)"
+        );
+        assert_eq!(
+            s.satisfied(1, "c"),
+            "(c.\"user_id\" IS NULL OR EXISTS (SELECT 1 FROM \"public\".\"users\" AS p \
+            WHERE p.\"id\" = c.\"user_id\" AND EXISTS (SELECT 1 FROM pg_temp.datanymizer_subset_0 \
+            WHERE datanymizer_subset_0.k0 = p.\"id\")))"
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let s = subsetter();
+        let mut plan = SubsetPlan::default();
+        plan.tables.insert(
+            "public.users".to_string(),
+            TablePlan {
+                rows: 10,
+                selections: BTreeSet::from([Selection::Root]),
+            },
+        );
+
+        let filter = s.filter("public.users", &plan).unwrap();
+        assert_eq!(
+            filter.condition.as_deref(),
+            Some(
+                "EXISTS (SELECT 1 FROM pg_temp.datanymizer_subset_0 \
+                WHERE datanymizer_subset_0.k0 = \"public\".\"users\".\"id\")"
+            )
+        );
+        assert_eq!(
+            filter.columns,
+            Some(vec![
+                "\"id\"".to_string(),
+                "CASE WHEN EXISTS (SELECT 1 FROM \"public\".\"users\" AS p \
+                WHERE p.\"id\" = \"public\".\"users\".\"referrer_id\" \
+                AND EXISTS (SELECT 1 FROM pg_temp.datanymizer_subset_0 WHERE datanymizer_subset_0.k0 = p.\"id\")) \
+                THEN \"public\".\"users\".\"referrer_id\" END"
+                    .to_string()
+            ])
+        );
+        assert_eq!(filter.rows, Some(10));
+
+        let filter = s.filter("public.log", &plan).unwrap();
+        assert_eq!(filter.columns, None);
+        assert_eq!(filter.rows, None);
+
+        assert_eq!(s.filter("public.settings", &plan), None);
+        assert_eq!(s.filter("public.unknown", &plan), None);
+    }
+
+    #[test]
+    fn quote() {
+        assert_eq!(quote_ident("name"), "\"name\"");
+        assert_eq!(quote_ident("na\"me"), "\"na\"\"me\"");
+    }
+}
@@ -1,7 +1,10 @@
-use super::{column::PgColumn, row::PgRow, sequence::PgSequence};
+use super::{
+    column::PgColumn, only_columns::Projection, row::PgRow, sequence::PgSequence,
+    subset::SubsetFilter,
+};
 use crate::Table;
 use anyhow::{anyhow, Result};
-use datanymizer_engine::{Query as QueryCfg, Table as TableCfg};
+use datanymizer_engine::{Query as QueryCfg, Sample, ShuffleMethod, Table as TableCfg};
 use postgres::{types::Type, Row as PostgresRow};
 use std::{
     collections::HashMap,
@@ -16,6 +19,26 @@ pub struct PgTable {
     pub sequences: Vec<PgSequence>,
     column_indexes: HashMap<String, usize>,
     pub size: i64,
+    /// The extension that owns the table (its data is managed by the extension)
+    pub extension: Option<String>,
+    /// Full name of the hypertable if the table is a TimescaleDB chunk
+    pub hypertable: Option<String>,
+    /// Whether the table is partitioned (its rows are in the partitions)
+    pub partitioned: bool,
+    /// Full name of the root partitioned table if the table is a partition
+    pub partition_of: Option<String>,
+    /// The restriction of the data to the subset
+    pub subset: Option<SubsetFilter>,
+    /// The planner estimate of the rows that match `query.dump_condition`
+    pub filtered_rows: Option<u64>,
+    /// The row limit (the `limit` of the table or the global `default_limit`)
+    pub limit: Option<u64>,
+    /// Sampling of the table pages
+    pub sample: Option<Sample>,
+    /// The run seed (it seeds the shuffling of the rows unless `shuffle_rows` has its own seed)
+    pub seed: Option<u64>,
+    /// The omitted columns if only some columns are dumped (`only_columns`)
+    pub projection: Option<Projection>,
 }
 
 impl PartialEq for PgTable {
@@ -45,8 +68,17 @@ fn get_full_name(&self) -> String {
         format!("{}.{}", self.schemaname, self.tablename)
     }
 
+    // TimescaleDB chunks get the rules of their hypertables, partitions get the rules of their
+    // partitioned tables
     fn get_names(&self) -> Vec<String> {
-        vec![self.get_full_name(), self.get_name()]
+        let mut names = vec![self.get_full_name(), self.get_name()];
+        for parent in self.hypertable.iter().chain(&self.partition_of) {
+            names.push(parent.clone());
+            if let Some((_, name)) = parent.split_once('.') {
+                names.push(name.to_string());
+            }
+        }
+        names
     }
 
     fn get_columns(&self) -> Vec<Self::Column> {
@@ -75,6 +107,16 @@ pub fn new(tablename: String, schemaname: String) -> Self {
             sequences: vec![],
             column_indexes: HashMap::new(),
             size: 0,
+            extension: None,
+            hypertable: None,
+            partitioned: false,
+            partition_of: None,
+            subset: None,
+            filtered_rows: None,
+            limit: None,
+            sample: None,
+            seed: None,
+            projection: None,
         }
     }
 
@@ -103,7 +145,8 @@ pub fn set_columns(&mut self, columns: Vec<PgColumn>) {
         self.columns = columns;
     }
 
-    pub fn set_sequences(&mut self, sequences: Vec<PgSequence>) {
+    pub fn set_sequences(&mut self, mut sequences: Vec<PgSequence>) {
+        sequences.sort_by(|a, b| (&a.column, &a.full_name).cmp(&(&b.column, &b.full_name)));
         self.sequences = sequences;
     }
 
@@ -113,8 +156,10 @@ pub fn transformed_query_to(
         already_dumped: u64,
     ) -> Option<String> {
         cfg.and_then(|c| match &c.query {
-            Some(q) => self.query_unless_already_dumped(q, |s| format!("({})", s), already_dumped),
-            None => Some(self.default_query()),
+            Some(q) => {
+                self.query_unless_already_dumped(q, |s| format!("({})", s), already_dumped, c, true)
+            }
+            None => Some(self.default_query(Some(c))),
         })
     }
 
@@ -126,28 +171,49 @@ pub fn untransformed_query_to(
         match cfg {
             Some(c) => c.query.as_ref().and_then(|q| {
                 if q.transform_condition.is_some() {
-                    self.query_unless_already_dumped(q, |s| format!("NOT ({})", s), already_dumped)
+                    self.query_unless_already_dumped(
+                        q,
+                        |s| format!("NOT ({})", s),
+                        already_dumped,
+                        c,
+                        false,
+                    )
                 } else {
                     None
                 }
             }),
-            None => Some(self.default_query()),
+            None => Some(self.default_query(None)),
         }
     }
 
     pub fn count_of_query_to(&self, cfg: Option<&TableCfg>) -> u64 {
-        let number = self.get_size() as u64;
+        let mut number = self
+            .subset
+            .as_ref()
+            .and_then(|s| s.rows)
+            .unwrap_or(self.get_size() as u64);
+        if let Some(filtered_rows) = self.filtered_rows {
+            number = number.min(filtered_rows);
+        }
+        if let Some(sample) = &self.sample {
+            number = sample.rows(number);
+        }
 
-        cfg.and_then(|c| c.query.as_ref())
-            .and_then(|q| q.limit)
-            .and_then(|limit| {
-                if number > limit as u64 {
-                    Some(limit as u64)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(number)
+        self.limit(cfg.and_then(|c| c.query.as_ref()))
+            .map_or(number, |limit| number.min(limit))
+    }
+
+    /// Returns `true` if only a part of the rows is dumped (by a limit or sampling)
+    pub fn is_limited(&self, cfg: Option<&TableCfg>) -> bool {
+        self.sample.is_some() || self.limit(cfg.and_then(|c| c.query.as_ref())).is_some()
+    }
+
+    // The lesser of the query limit and the row limit of the table
+    fn limit(&self, q: Option<&QueryCfg>) -> Option<u64> {
+        match (q.and_then(|q| q.limit).map(|l| l as u64), self.limit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
     }
 
     pub fn query_from(&self) -> String {
@@ -167,10 +233,11 @@ fn query_unless_already_dumped(
         q: &QueryCfg,
         tr_fmt: fn(s: &String) -> String,
         already_dumped: u64,
+        cfg: &TableCfg,
+        transformed: bool,
     ) -> Option<String> {
-        if q.limit
-            .map_or(false, |limit| limit as u64 <= already_dumped)
-        {
+        let limit = self.limit(Some(q));
+        if limit.is_some_and(|limit| limit <= already_dumped) {
             return None;
         }
 
@@ -179,12 +246,24 @@ fn query_unless_already_dumped(
                 q.dump_condition.as_ref().map(|c| format!("({})", c)),
                 q.transform_condition.as_ref().map(tr_fmt),
             ],
-            q.limit.map(|limit| limit as u64 - already_dumped),
+            limit.map(|limit| limit - already_dumped),
+            Some(cfg),
+            transformed,
         ))
     }
 
-    fn default_query(&self) -> String {
-        if !self.quoted_columns().is_empty() {
+    // `cfg` is `None` for the rows that are dumped as is
+    fn default_query(&self, cfg: Option<&TableCfg>) -> String {
+        // the partitioned tables can't be copied directly
+        if self.partitioned
+            || self.subset.is_some()
+            || self.limit.is_some()
+            || self.sample.is_some()
+            || self.shuffle_order(cfg).is_some()
+            || !Self::sql_rules(cfg).is_empty()
+        {
+            self.query_with_select(vec![], self.limit, cfg, true)
+        } else if !self.quoted_columns().is_empty() {
             format!(
                 "COPY {}({}) TO STDOUT",
                 self.quoted_full_name(),
@@ -195,15 +274,93 @@ fn default_query(&self) -> String {
         }
     }
 
-    fn query_with_select(&self, cs: Vec<Option<String>>, limit: Option<u64>) -> String {
-        format!(
-            "COPY (SELECT * FROM {}{}{}) TO STDOUT",
+    fn query_with_select(
+        &self,
+        mut cs: Vec<Option<String>>,
+        limit: Option<u64>,
+        cfg: Option<&TableCfg>,
+        transformed: bool,
+    ) -> String {
+        cs.push(self.subset.as_ref().and_then(|s| s.condition.clone()));
+        let select = format!(
+            "SELECT {} FROM {}{}{}{}",
+            self.select_columns(cfg.filter(|_| transformed))
+                .map_or("*".to_string(), |columns| columns.join(", ")),
             self.quoted_full_name(),
+            self.sample.map_or(String::new(), |s| format!(
+                " TABLESAMPLE SYSTEM ({})",
+                s.percent()
+            )),
             Self::sql_conditions(cs),
             Self::sql_limit(limit),
+        );
+        match self.shuffle_order(cfg) {
+            // the limit is applied before shuffling
+            Some(order) => format!(
+                "COPY (SELECT * FROM ({}) AS shuffled ORDER BY {}) TO STDOUT",
+                select, order
+            ),
+            None => format!("COPY ({}) TO STDOUT", select),
+        }
+    }
+
+    /// The query that checks the `sql` rules of the table (`None` if there are no such rules)
+    pub fn sql_rules_query(&self, cfg: &TableCfg) -> Option<String> {
+        self.select_columns(Some(cfg))
+            .filter(|_| !Self::sql_rules(Some(cfg)).is_empty())
+            .map(|columns| {
+                format!(
+                    "SELECT {} FROM {}",
+                    columns.join(", "),
+                    self.quoted_full_name()
+                )
+            })
+    }
+
+    // The columns of the query: the expressions of the `sql` rules replace the columns, the subset
+    // replaces the values of the cut foreign keys, a projection lists its columns (`None` - all columns as is)
+    fn select_columns(&self, cfg: Option<&TableCfg>) -> Option<Vec<String>> {
+        let sql_rules = Self::sql_rules(cfg);
+        let subset_columns = self.subset.as_ref().and_then(|s| s.columns.as_ref());
+        if sql_rules.is_empty() {
+            return subset_columns
+                .cloned()
+                .or_else(|| self.projection.as_ref().map(|_| self.quoted_columns()));
+        }
+
+        Some(
+            self.get_columns_names()
+                .iter()
+                .enumerate()
+                .map(|(i, name)| match sql_rules.get(name.as_str()) {
+                    Some(expression) => format!("({}) AS \"{}\"", expression, name),
+                    None => subset_columns
+                        .map_or_else(|| format!("\"{}\"", name), |columns| columns[i].clone()),
+                })
+                .collect(),
         )
     }
 
+    fn sql_rules(cfg: Option<&TableCfg>) -> HashMap<&str, &str> {
+        cfg.map(|c| {
+            c.rules
+                .iter()
+                .filter_map(|(column, rule)| Some((column.as_str(), rule.sql_expression()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    // The order of the server-side shuffling (by the keyed hashes of the rows if there is a seed)
+    fn shuffle_order(&self, cfg: Option<&TableCfg>) -> Option<String> {
+        cfg.and_then(|c| c.shuffle_rows.as_ref())
+            .filter(|s| s.method == ShuffleMethod::Server)
+            .map(|s| match s.seed.or(self.seed) {
+                Some(seed) => format!("md5('{}:' || shuffled::text)", seed),
+                None => "random()".to_string(),
+            })
+    }
+
     fn sql_conditions(cs: Vec<Option<String>>) -> String {
         let conditions: Vec<String> = cs.into_iter().flatten().collect();
         if conditions.is_empty() {
@@ -235,6 +392,42 @@ fn from(row: PostgresRow) -> Self {
 mod tests {
     use super::*;
     use crate::{postgres::column::PgColumn, Table};
+    use datanymizer_engine::{transformers::SqlTransformer, ShuffleRows, Transformers};
+
+    #[test]
+    fn chunk_names() {
+        let mut table = PgTable::new(
+            "_hyper_1_2_chunk".to_string(),
+            "_timescaledb_internal".to_string(),
+        );
+        table.hypertable = Some("public.metrics".to_string());
+
+        assert_eq!(
+            table.get_names(),
+            vec![
+                "_timescaledb_internal._hyper_1_2_chunk",
+                "_hyper_1_2_chunk",
+                "public.metrics",
+                "metrics"
+            ]
+        );
+    }
+
+    #[test]
+    fn partition_names() {
+        let mut table = PgTable::new("events_2023_01".to_string(), "public".to_string());
+        table.partition_of = Some("public.events".to_string());
+
+        assert_eq!(
+            table.get_names(),
+            vec![
+                "public.events_2023_01",
+                "events_2023_01",
+                "public.events",
+                "events"
+            ]
+        );
+    }
 
     #[test]
     fn table_full_name() {
@@ -270,12 +463,14 @@ fn set_columns() {
             name: String::from("col1"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
         let col2 = PgColumn {
             position: 2,
             name: String::from("col2"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
         let col3 = PgColumn {
             // Column positions in Postgres are not always in sequence
@@ -284,6 +479,7 @@ fn set_columns() {
             name: String::from("col4"),
             data_type: String::new(),
             inner_type: Some(0),
+            max_length: None,
         };
 
         table.set_columns(vec![col1.clone(), col2.clone(), col3.clone()]);
@@ -298,6 +494,7 @@ fn set_columns() {
 
     mod query_to {
         use super::*;
+        use std::collections::BTreeSet;
 
         fn table_name() -> String {
             "some_table".to_string()
@@ -309,12 +506,14 @@ fn columns() -> Vec<PgColumn> {
                 name: String::from("col1"),
                 data_type: String::new(),
                 inner_type: Some(0),
+                max_length: None,
             };
             let col2 = PgColumn {
                 position: 2,
                 name: String::from("col2"),
                 data_type: String::new(),
                 inner_type: Some(0),
+                max_length: None,
             };
             vec![col1, col2]
         }
@@ -343,6 +542,23 @@ fn cfg(query: Option<QueryCfg>) -> TableCfg {
                 rules: HashMap::new(),
                 rule_order: None,
                 query,
+                limit: None,
+                sample: None,
+                override_rules: false,
+                quasi_identifiers: vec![],
+                ignore: HashMap::new(),
+                explicit: None,
+                timestamp_order: vec![],
+                apply_to_all_schemas: false,
+                force: vec![],
+                shuffle_rows: None,
+                only_columns: vec![],
+                outbox: None,
+                skip_data: None,
+                lint: HashMap::new(),
+                preset: None,
+                preset_rules: BTreeSet::new(),
+                declared_rules: vec![],
             }
         }
 
@@ -366,6 +582,24 @@ fn no_columns() {
             assert_eq!(table_no_columns().count_of_query_to(None), 500);
         }
 
+        #[test]
+        fn partitioned() {
+            let mut table = table();
+            table.partitioned = true;
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\") TO STDOUT"
+            );
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg(None)), 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\") TO STDOUT"
+            );
+            assert_eq!(
+                table.query_from(),
+                "COPY \"public\".\"some_table\"(\"col1\", \"col2\") FROM STDIN;"
+            );
+        }
+
         #[test]
         fn no_query() {
             let cfg = cfg(None);
@@ -408,6 +642,13 @@ fn only_dump_condition() {
             );
             assert_eq!(table().untransformed_query_to(Some(&cfg), 0), None);
             assert_eq!(table().count_of_query_to(Some(&cfg)), 1000);
+
+            // the planner estimate of the matching rows
+            let mut table = table();
+            table.filtered_rows = Some(40);
+            assert_eq!(table.count_of_query_to(Some(&cfg)), 40);
+            table.filtered_rows = Some(5000);
+            assert_eq!(table.count_of_query_to(Some(&cfg)), 1000);
         }
 
         #[test]
@@ -450,6 +691,216 @@ fn all_query_params() {
             assert_eq!(table().count_of_query_to(Some(&cfg)), 500);
         }
 
+        #[test]
+        fn subset() {
+            let mut table = table();
+            table.subset = Some(SubsetFilter {
+                condition: Some("EXISTS (SELECT 1)".to_string()),
+                columns: None,
+                rows: Some(10),
+            });
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" WHERE EXISTS (SELECT 1)) TO STDOUT"
+            );
+            assert_eq!(table.count_of_query_to(None), 10);
+
+            let cfg = cfg(Some(QueryCfg {
+                limit: Some(5),
+                dump_condition: Some("col1 = 'value'".to_string()),
+                transform_condition: None,
+            }));
+            table.subset = Some(SubsetFilter {
+                condition: None,
+                columns: Some(vec!["\"col1\"".to_string(), "NULL".to_string()]),
+                rows: None,
+            });
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT \"col1\", NULL FROM \"public\".\"some_table\" \
+                WHERE (col1 = 'value') LIMIT 5) TO STDOUT"
+            );
+            assert_eq!(table.count_of_query_to(Some(&cfg)), 5);
+        }
+
+        #[test]
+        fn projection() {
+            let mut table = table();
+            table.set_columns(columns().into_iter().take(1).collect());
+            table.projection = Some(Projection {
+                omitted: vec!["col2".to_string()],
+                omitted_row_bytes: None,
+            });
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY \"public\".\"some_table\"(\"col1\") TO STDOUT"
+            );
+            assert_eq!(
+                table.query_from(),
+                "COPY \"public\".\"some_table\"(\"col1\") FROM STDIN;"
+            );
+
+            let cfg = cfg(Some(QueryCfg {
+                limit: Some(5),
+                dump_condition: Some("col2 = 'value'".to_string()),
+                transform_condition: None,
+            }));
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT \"col1\" FROM \"public\".\"some_table\" \
+                WHERE (col2 = 'value') LIMIT 5) TO STDOUT"
+            );
+        }
+
+        #[test]
+        fn row_limits() {
+            let mut table = table();
+            table.limit = Some(200);
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" LIMIT 200) TO STDOUT"
+            );
+            assert_eq!(table.count_of_query_to(None), 200);
+            assert!(table.is_limited(None));
+
+            // the lesser limit is used
+            let cfg = cfg(Some(QueryCfg {
+                limit: Some(500),
+                dump_condition: None,
+                transform_condition: Some("col1 = 'value'".to_string()),
+            }));
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" WHERE (col1 = 'value') LIMIT 200) TO STDOUT"
+            );
+            assert_eq!(
+                table.untransformed_query_to(Some(&cfg), 150).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" WHERE NOT (col1 = 'value') LIMIT 50) TO STDOUT"
+            );
+            assert_eq!(table.untransformed_query_to(Some(&cfg), 200), None);
+            assert_eq!(table.count_of_query_to(Some(&cfg)), 200);
+
+            table.limit = None;
+            table.sample = Some(Sample::new(2.5).unwrap());
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" TABLESAMPLE SYSTEM (2.5)) TO STDOUT"
+            );
+            assert_eq!(table.count_of_query_to(None), 25);
+            assert!(table.is_limited(None));
+
+            table.limit = Some(10);
+            assert_eq!(
+                table.untransformed_query_to(None, 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" TABLESAMPLE SYSTEM (2.5) LIMIT 10) TO STDOUT"
+            );
+            assert_eq!(table.count_of_query_to(None), 10);
+            assert!(!self::table().is_limited(None));
+        }
+
+        #[test]
+        fn shuffle_rows() {
+            let mut cfg = cfg(Some(QueryCfg {
+                limit: Some(5),
+                dump_condition: None,
+                transform_condition: None,
+            }));
+            cfg.shuffle_rows = Some(ShuffleRows::default());
+            // the client-side shuffling doesn't change the query
+            assert_eq!(
+                table().transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" LIMIT 5) TO STDOUT"
+            );
+
+            cfg.shuffle_rows = Some(ShuffleRows {
+                method: ShuffleMethod::Server,
+                ..ShuffleRows::default()
+            });
+            assert_eq!(
+                table().transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM (SELECT * FROM \"public\".\"some_table\" LIMIT 5) \
+                AS shuffled ORDER BY random()) TO STDOUT"
+            );
+
+            cfg.query = None;
+            cfg.shuffle_rows = Some(ShuffleRows {
+                method: ShuffleMethod::Server,
+                seed: Some(42),
+                memory_limit: None,
+            });
+            assert_eq!(
+                table().transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM (SELECT * FROM \"public\".\"some_table\") \
+                AS shuffled ORDER BY md5('42:' || shuffled::text)) TO STDOUT"
+            );
+
+            // the run seed is used unless the table has its own seed
+            let mut table = table();
+            table.seed = Some(7);
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM (SELECT * FROM \"public\".\"some_table\") \
+                AS shuffled ORDER BY md5('42:' || shuffled::text)) TO STDOUT"
+            );
+            cfg.shuffle_rows = Some(ShuffleRows {
+                method: ShuffleMethod::Server,
+                ..ShuffleRows::default()
+            });
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM (SELECT * FROM \"public\".\"some_table\") \
+                AS shuffled ORDER BY md5('7:' || shuffled::text)) TO STDOUT"
+            );
+        }
+
+        #[test]
+        fn sql_rules() {
+            let mut cfg = cfg(Some(QueryCfg {
+                limit: None,
+                dump_condition: None,
+                transform_condition: Some("col1 = 'value'".to_string()),
+            }));
+            cfg.rules.insert(
+                "col2".to_string(),
+                Transformers::Sql(SqlTransformer {
+                    expression: "md5(col2)".to_string(),
+                }),
+            );
+            assert_eq!(
+                table().transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT \"col1\", (md5(col2)) AS \"col2\" FROM \"public\".\"some_table\" \
+                WHERE (col1 = 'value')) TO STDOUT"
+            );
+            // the other rows are dumped as is
+            assert_eq!(
+                table().untransformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT * FROM \"public\".\"some_table\" WHERE NOT (col1 = 'value')) TO STDOUT"
+            );
+            assert_eq!(
+                table().sql_rules_query(&cfg).unwrap(),
+                "SELECT \"col1\", (md5(col2)) AS \"col2\" FROM \"public\".\"some_table\""
+            );
+
+            // with the columns of the subset
+            let mut table = table();
+            table.subset = Some(SubsetFilter {
+                condition: None,
+                columns: Some(vec![
+                    "CASE WHEN true THEN \"col1\" END".to_string(),
+                    "\"col2\"".to_string(),
+                ]),
+                rows: None,
+            });
+            cfg.query = None;
+            assert_eq!(
+                table.transformed_query_to(Some(&cfg), 0).unwrap(),
+                "COPY (SELECT CASE WHEN true THEN \"col1\" END, (md5(col2)) AS \"col2\" \
+                FROM \"public\".\"some_table\") TO STDOUT"
+            );
+
+            assert_eq!(table.sql_rules_query(&self::cfg(None)), None);
+        }
+
         mod already_dumped {
             use super::*;
 
@@ -0,0 +1,466 @@
+use super::{schema_inspector::ForeignKeyLink, table::PgTable};
+use crate::Table;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::Settings;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::{self, Display, Formatter},
+};
+
+/// Why the table is placed at its position in the dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Placement {
+    /// Listed in `dump_first`
+    DumpFirst,
+    /// Other tables depend on it (by foreign keys)
+    ForeignKey,
+    /// Not listed anywhere and no tables depend on it
+    Default,
+    /// Listed in `table_order`
+    TableOrder,
+    /// Listed in `dump_last`
+    DumpLast,
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            Self::DumpFirst => "dump_first",
+            Self::ForeignKey => "FK",
+            Self::Default => "default",
+            Self::TableOrder => "table_order",
+            Self::DumpLast => "dump_last",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Table order hints from the config: `dump_first`, `table_order` and `dump_last`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TableOrder<'a> {
+    pub first: &'a [String],
+    pub order: &'a [String],
+    pub last: &'a [String],
+}
+
+impl<'a> TableOrder<'a> {
+    pub fn new(settings: &'a Settings) -> Self {
+        let list = |l: &'a Option<Vec<String>>| l.as_deref().unwrap_or_default();
+        Self {
+            first: list(&settings.dump_first),
+            order: list(&settings.table_order),
+            last: list(&settings.dump_last),
+        }
+    }
+
+    /// Returns the placement and the position in the list (for the listed tables)
+    pub fn placement(&self, table: &PgTable, weight: i32) -> (Placement, Option<usize>) {
+        let names = table.get_names();
+        let position = |list: &[String]| list.iter().position(|i| names.contains(i));
+
+        if let Some(p) = position(self.first) {
+            (Placement::DumpFirst, Some(p))
+        } else if let Some(p) = position(self.last) {
+            (Placement::DumpLast, Some(p))
+        } else if let Some(p) = position(self.order) {
+            (Placement::TableOrder, Some(p))
+        } else if weight > 0 {
+            (Placement::ForeignKey, None)
+        } else {
+            (Placement::Default, None)
+        }
+    }
+
+    /// Sorts tables: `dump_first` tables, unlisted tables (the ones that other tables depend on first),
+    /// `table_order` tables and `dump_last` tables
+    pub fn sort(&self, tables: &mut [(PgTable, i32)]) {
+        tables.sort_by_cached_key(|(tbl, weight)| {
+            let (placement, position) = self.placement(tbl, *weight);
+            let group = match placement {
+                Placement::ForeignKey => Placement::Default,
+                p => p,
+            };
+            // the names break the ties, so the order doesn't depend on the order of the input
+            (
+                group,
+                position,
+                -weight,
+                tbl.schemaname.clone(),
+                tbl.tablename.clone(),
+            )
+        });
+    }
+
+    /// Checks that `dump_first` and `dump_last` don't place any table before the table it references
+    /// (`table_order` alone is not checked, as in previous versions)
+    pub fn validate(
+        &self,
+        tables: &[(PgTable, i32)],
+        foreign_keys: &[ForeignKeyLink],
+    ) -> Result<()> {
+        let positions: HashMap<String, (usize, Placement)> = tables
+            .iter()
+            .enumerate()
+            .map(|(i, (t, w))| (t.get_full_name(), (i, self.placement(t, *w).0)))
+            .collect();
+
+        for fk in foreign_keys
+            .iter()
+            .filter(|fk| fk.table != fk.referenced_table)
+        {
+            if let (Some((pos, placement)), Some((ref_pos, ref_placement))) = (
+                positions.get(&fk.table),
+                positions.get(&fk.referenced_table),
+            ) {
+                let hinted = [placement, ref_placement]
+                    .iter()
+                    .any(|p| matches!(p, Placement::DumpFirst | Placement::DumpLast));
+                if pos < ref_pos && hinted {
+                    return Err(anyhow!(
+                        "The table order conflicts with the foreign key `{}`: `{}` ({}) is dumped before the referenced table `{}` ({})",
+                        fk.name,
+                        fk.table,
+                        placement,
+                        fk.referenced_table,
+                        ref_placement
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the names from the lists that don't match any table
+    pub fn unknown_names(&self, tables: &[(PgTable, i32)]) -> Vec<&'a str> {
+        self.first
+            .iter()
+            .chain(self.order)
+            .chain(self.last)
+            .filter(|name| !tables.iter().any(|(t, _)| t.get_names().contains(name)))
+            .map(|name| name.as_str())
+            .collect()
+    }
+}
+
+/// Returns the tables that can't be ordered parent-first: the groups of tables that reference each other
+/// (directly or through other tables) and the tables that reference themselves.
+/// The names in a group and the groups are sorted.
+pub fn foreign_key_cycles(foreign_keys: &[ForeignKeyLink]) -> Vec<Vec<String>> {
+    let mut graph: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for fk in foreign_keys {
+        graph.entry(&fk.referenced_table).or_default();
+        graph
+            .entry(&fk.table)
+            .or_default()
+            .insert(&fk.referenced_table);
+    }
+
+    let mut cycles: Vec<Vec<String>> = StronglyConnected::new(&graph)
+        .components()
+        .into_iter()
+        .filter(|c| c.len() > 1 || graph[c[0]].contains(c[0]))
+        .map(|c| {
+            let mut names: Vec<String> = c.into_iter().map(String::from).collect();
+            names.sort();
+            names
+        })
+        .collect();
+    cycles.sort();
+    cycles
+}
+
+// Tarjan's algorithm for the strongly connected components
+struct StronglyConnected<'a, 'g> {
+    graph: &'g BTreeMap<&'a str, BTreeSet<&'a str>>,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: BTreeSet<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a, 'g> StronglyConnected<'a, 'g> {
+    fn new(graph: &'g BTreeMap<&'a str, BTreeSet<&'a str>>) -> Self {
+        Self {
+            graph,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: vec![],
+            on_stack: BTreeSet::new(),
+            components: vec![],
+        }
+    }
+
+    fn components(mut self) -> Vec<Vec<&'a str>> {
+        for node in self.graph.keys() {
+            if !self.index.contains_key(node) {
+                self.visit(node);
+            }
+        }
+        self.components
+    }
+
+    fn visit(&mut self, node: &'a str) {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.low.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        for &next in &self.graph[node] {
+            if !self.index.contains_key(next) {
+                self.visit(next);
+                let low = self.low[node].min(self.low[next]);
+                self.low.insert(node, low);
+            } else if self.on_stack.contains(next) {
+                let low = self.low[node].min(self.index[next]);
+                self.low.insert(node, low);
+            }
+        }
+
+        if self.low[node] == index {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> PgTable {
+        PgTable::new(name.to_string(), "public".to_string())
+    }
+
+    fn names(tables: &[(PgTable, i32)]) -> Vec<String> {
+        tables.iter().map(|(t, _)| t.get_name()).collect()
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn fk(name: &str, table: &str, referenced_table: &str) -> ForeignKeyLink {
+        ForeignKeyLink {
+            name: name.to_string(),
+            table: format!("public.{}", table),
+            referenced_table: format!("public.{}", referenced_table),
+        }
+    }
+
+    #[test]
+    fn sort_with_table_order() {
+        let order = vec!["table2".to_string(), "public.table1".to_string()];
+
+        let mut tables = vec![
+            (PgTable::new("table1".to_string(), "public".to_string()), 0),
+            (PgTable::new("table2".to_string(), "public".to_string()), 1),
+            (PgTable::new("table3".to_string(), "public".to_string()), 2),
+            (PgTable::new("table4".to_string(), "public".to_string()), 3),
+            (PgTable::new("table1".to_string(), "other".to_string()), 4),
+            (PgTable::new("table2".to_string(), "other".to_string()), 5),
+        ];
+
+        TableOrder {
+            order: &order,
+            ..Default::default()
+        }
+        .sort(&mut tables);
+
+        let ordered_names: Vec<_> = tables
+            .iter()
+            .map(|(t, w)| (t.get_full_name(), *w))
+            .collect();
+        assert_eq!(
+            ordered_names,
+            vec![
+                ("other.table1".to_string(), 4),
+                ("public.table4".to_string(), 3),
+                ("public.table3".to_string(), 2),
+                ("other.table2".to_string(), 5),
+                ("public.table2".to_string(), 1),
+                ("public.table1".to_string(), 0),
+            ]
+        )
+    }
+
+    #[test]
+    fn sort_with_first_and_last() {
+        let first = strings(&["countries", "currencies"]);
+        let order = strings(&["users"]);
+        let last = strings(&["events"]);
+        let order = TableOrder {
+            first: &first,
+            order: &order,
+            last: &last,
+        };
+
+        let mut tables = vec![
+            (table("events"), 0),
+            (table("users"), 2),
+            (table("orders"), 0),
+            (table("currencies"), 1),
+            (table("products"), 1),
+            (table("countries"), 1),
+        ];
+        order.sort(&mut tables);
+
+        assert_eq!(
+            names(&tables),
+            vec![
+                "countries",
+                "currencies",
+                "products",
+                "orders",
+                "users",
+                "events"
+            ]
+        );
+        assert_eq!(
+            order.placement(&table("products"), 1),
+            (Placement::ForeignKey, None)
+        );
+        assert_eq!(
+            order.placement(&table("orders"), 0),
+            (Placement::Default, None)
+        );
+        assert_eq!(
+            order.placement(&table("currencies"), 1),
+            (Placement::DumpFirst, Some(1))
+        );
+    }
+
+    #[test]
+    fn sort_ties() {
+        let order = TableOrder::default();
+        let expected = vec!["cities", "countries", "events", "users"];
+
+        let mut tables = vec![
+            (table("users"), 0),
+            (table("events"), 0),
+            (table("countries"), 1),
+            (table("cities"), 1),
+        ];
+        order.sort(&mut tables);
+        assert_eq!(names(&tables), expected);
+
+        tables.reverse();
+        order.sort(&mut tables);
+        assert_eq!(names(&tables), expected);
+    }
+
+    #[test]
+    fn validate() {
+        let first = strings(&["cities"]);
+        let last = strings(&["events"]);
+        let order = TableOrder {
+            first: &first,
+            last: &last,
+            ..Default::default()
+        };
+        let mut tables = vec![
+            (table("countries"), 1),
+            (table("cities"), 0),
+            (table("events"), 0),
+        ];
+        order.sort(&mut tables);
+
+        let ok = vec![fk("events_city_fkey", "events", "cities")];
+        assert!(order.validate(&tables, &ok).is_ok());
+
+        let conflict = vec![fk("cities_country_fkey", "cities", "countries")];
+        assert_eq!(
+            order.validate(&tables, &conflict).unwrap_err().to_string(),
+            "The table order conflicts with the foreign key `cities_country_fkey`: `public.cities` (dump_first) is dumped before the referenced table `public.countries` (FK)"
+        );
+
+        let self_reference = vec![fk("cities_parent_fkey", "cities", "cities")];
+        assert!(order.validate(&tables, &self_reference).is_ok());
+    }
+
+    #[test]
+    fn sort_diamond() {
+        // `orders` and `invoices` reference `users`, `payments` references both of them
+        let links = vec![
+            fk("orders_user_fkey", "orders", "users"),
+            fk("invoices_user_fkey", "invoices", "users"),
+            fk("payments_order_fkey", "payments", "orders"),
+            fk("payments_invoice_fkey", "payments", "invoices"),
+        ];
+        let all = vec![
+            table("payments"),
+            table("invoices"),
+            table("orders"),
+            table("users"),
+        ];
+        let mut deps: HashMap<PgTable, Vec<PgTable>> = HashMap::new();
+        for link in &links {
+            deps.entry(table(&link.table["public.".len()..]))
+                .or_default()
+                .push(table(&link.referenced_table["public.".len()..]));
+        }
+        let weights = crate::dependency_weights(&all, &deps);
+        let mut tables: Vec<_> = all.into_iter().map(|t| (t.clone(), weights[&t])).collect();
+        let order = TableOrder::default();
+        order.sort(&mut tables);
+
+        assert_eq!(
+            names(&tables),
+            vec!["users", "invoices", "orders", "payments"]
+        );
+        assert!(order.validate(&tables, &links).is_ok());
+        assert!(foreign_key_cycles(&links).is_empty());
+    }
+
+    #[test]
+    fn cycles() {
+        let links = vec![
+            fk("users_team_fkey", "users", "teams"),
+            fk("teams_owner_fkey", "teams", "users"),
+            fk("events_user_fkey", "events", "users"),
+            fk("categories_parent_fkey", "categories", "categories"),
+        ];
+        assert_eq!(
+            foreign_key_cycles(&links),
+            vec![
+                vec!["public.categories".to_string()],
+                vec!["public.teams".to_string(), "public.users".to_string()],
+            ]
+        );
+
+        // a longer cycle through other tables
+        let links = vec![
+            fk("a_b_fkey", "a", "b"),
+            fk("b_c_fkey", "b", "c"),
+            fk("c_a_fkey", "c", "a"),
+            fk("d_a_fkey", "d", "a"),
+        ];
+        assert_eq!(
+            foreign_key_cycles(&links),
+            vec![strings(&["public.a", "public.b", "public.c"])]
+        );
+    }
+
+    #[test]
+    fn unknown_names() {
+        let first = strings(&["countries"]);
+        let last = strings(&["public.events", "logs"]);
+        let order = TableOrder {
+            first: &first,
+            last: &last,
+            ..Default::default()
+        };
+        let tables = vec![(table("countries"), 0), (table("events"), 0)];
+
+        assert_eq!(order.unknown_names(&tables), vec!["logs"]);
+    }
+}
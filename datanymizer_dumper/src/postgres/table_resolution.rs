@@ -0,0 +1,235 @@
+use super::table::PgTable;
+use crate::Table;
+use datanymizer_engine::{Settings, Table as TableCfg};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+};
+
+/// The maximum edit distance of the similar table names
+const MAX_DISTANCE: usize = 2;
+const MAX_CANDIDATES: usize = 5;
+
+/// The database tables that a config table is applied to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableResolution {
+    /// The config table name
+    pub name: String,
+    /// Full names of the matched tables
+    pub tables: Vec<String>,
+    /// The problem with the matching (an error, or a warning with `--lenient`)
+    pub problem: Option<String>,
+}
+
+impl TableResolution {
+    /// Resolves all config tables against the database tables
+    pub fn resolve_all(settings: &Settings, tables: &[PgTable]) -> Vec<Self> {
+        settings
+            .tables
+            .iter()
+            .map(|cfg| Self::resolve(cfg, settings, tables))
+            .collect()
+    }
+
+    /// A table name without schema must match the tables of one schema only (unless `apply_to_all_schemas`),
+    /// the tables with their own config tables (with schema) are not counted.
+    /// The tables added by presets may match no tables (the missing preset tables are reported separately).
+    pub fn resolve(cfg: &TableCfg, settings: &Settings, tables: &[PgTable]) -> Self {
+        let mut matched: Vec<_> = tables
+            .iter()
+            .filter(|t| t.get_names().contains(&cfg.name))
+            .collect();
+        matched.sort_by_key(|t| t.get_full_name());
+
+        let problem = if matched.is_empty() {
+            cfg.preset.is_none().then(|| not_found(&cfg.name, tables))
+        } else if cfg.is_qualified() || cfg.apply_to_all_schemas {
+            None
+        } else {
+            let ambiguous: Vec<_> = matched
+                .iter()
+                .filter(|t| {
+                    t.get_name() == cfg.name && settings.get_table(&t.get_full_name()).is_none()
+                })
+                .map(|t| t.get_full_name())
+                .collect();
+            (ambiguous.len() > 1).then(|| {
+                format!(
+                    "The config table `{}` matches tables in several schemas: {} \
+                    (use names with schema or `apply_to_all_schemas: true`)",
+                    cfg.name,
+                    ambiguous.join(", ")
+                )
+            })
+        };
+
+        Self {
+            name: cfg.name.clone(),
+            tables: matched.iter().map(|t| t.get_full_name()).collect(),
+            problem,
+        }
+    }
+}
+
+impl Display for TableResolution {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let tables = if self.tables.is_empty() {
+            "no tables".to_string()
+        } else {
+            self.tables.join(", ")
+        };
+        write!(f, "{} -> {}", self.name, tables)
+    }
+}
+
+// Lists the tables with similar names and the available schemas
+fn not_found(name: &str, tables: &[PgTable]) -> String {
+    let bare_name = name.rsplit_once('.').map_or(name, |(_, n)| n);
+    let mut candidates: Vec<_> = tables
+        .iter()
+        .map(|t| (edit_distance(bare_name, &t.get_name()), t.get_full_name()))
+        .filter(|(distance, full_name)| *distance <= MAX_DISTANCE && full_name != name)
+        .collect();
+    candidates.sort();
+    let candidates: Vec<_> = candidates
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(_, full_name)| full_name)
+        .collect();
+    let schemas: BTreeSet<_> = tables.iter().map(|t| t.schemaname.as_str()).collect();
+
+    format!(
+        "The config table `{}` matches no tables ({}schemas: {})",
+        name,
+        if candidates.is_empty() {
+            String::new()
+        } else {
+            format!("similar tables: {}; ", candidates.join(", "))
+        },
+        schemas.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(names: &[&str]) -> Vec<PgTable> {
+        names
+            .iter()
+            .map(|name| {
+                let (schema, table) = name.split_once('.').unwrap();
+                PgTable::new(table.to_string(), schema.to_string())
+            })
+            .collect()
+    }
+
+    fn resolve(config: &str, names: &[&str]) -> Vec<TableResolution> {
+        let settings = Settings::from_yaml(config).unwrap();
+        TableResolution::resolve_all(&settings, &tables(names))
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(super::edit_distance("users", "users"), 0);
+        assert_eq!(super::edit_distance("users", "user"), 1);
+        assert_eq!(super::edit_distance("users", "usres"), 2);
+        assert_eq!(super::edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn matched() {
+        let r = resolve(
+            "tables: [{name: users, rules: {}}, {name: app.orders, rules: {}}]",
+            &["app.users", "app.orders", "public.orders"],
+        );
+        assert_eq!(r[0].tables, vec!["app.users"]);
+        assert_eq!(r[0].problem, None);
+        assert_eq!(r[0].to_string(), "users -> app.users");
+        assert_eq!(r[1].tables, vec!["app.orders"]);
+        assert_eq!(r[1].problem, None);
+    }
+
+    #[test]
+    fn not_found() {
+        let r = resolve(
+            "tables: [{name: public.users, rules: {}}, {name: usres, rules: {}}, {name: logs, rules: {}}]",
+            &["app.users", "app.user", "public.orders"],
+        );
+        assert_eq!(
+            r[0].problem.as_deref(),
+            Some("The config table `public.users` matches no tables (similar tables: app.users, app.user; schemas: app, public)")
+        );
+        assert_eq!(r[0].to_string(), "public.users -> no tables");
+        assert_eq!(
+            r[1].problem.as_deref(),
+            Some("The config table `usres` matches no tables (similar tables: app.user, app.users; schemas: app, public)")
+        );
+        assert_eq!(
+            r[2].problem.as_deref(),
+            Some("The config table `logs` matches no tables (schemas: app, public)")
+        );
+    }
+
+    #[test]
+    fn preset_tables() {
+        let r = resolve(
+            "{preset: zendesk_sync, tables: [{name: zendesk_users, rules: {}}]}",
+            &["public.zendesk_tickets"],
+        );
+        // the table of the config must match some tables
+        assert_eq!(r[0].name, "zendesk_users");
+        assert!(r[0].problem.is_some());
+        assert_eq!(
+            r[1].to_string(),
+            "zendesk_tickets -> public.zendesk_tickets"
+        );
+        // the missing tables of the preset are not errors
+        assert_eq!(r[2].to_string(), "zendesk_ticket_comments -> no tables");
+        assert_eq!(r[2].problem, None);
+    }
+
+    #[test]
+    fn several_schemas() {
+        let names = ["app.users", "public.users", "tenant.users"];
+        let r = resolve("tables: [{name: users, rules: {}}]", &names);
+        assert_eq!(
+            r[0].problem.as_deref(),
+            Some("The config table `users` matches tables in several schemas: app.users, public.users, tenant.users \
+                 (use names with schema or `apply_to_all_schemas: true`)")
+        );
+
+        let r = resolve(
+            "tables: [{name: users, apply_to_all_schemas: true, rules: {}}]",
+            &names,
+        );
+        assert_eq!(r[0].problem, None);
+        assert_eq!(
+            r[0].to_string(),
+            "users -> app.users, public.users, tenant.users"
+        );
+
+        // the tables with their own config tables are not counted
+        let r = resolve(
+            "tables: [{name: users, rules: {}}, {name: app.users, rules: {}}, {name: tenant.users, rules: {}}]",
+            &names,
+        );
+        assert!(r.iter().all(|r| r.problem.is_none()));
+    }
+}
@@ -0,0 +1,274 @@
+use super::{
+    query_wrapper::QueryWrapper,
+    subset::{SubsetFilter, Subsetter},
+    table::PgTable,
+};
+use crate::Table;
+use anyhow::{anyhow, Result};
+use datanymizer_engine::{Subset, Tenant, TenantScope};
+use std::{collections::BTreeMap, fmt};
+
+/// How the rows of a table are scoped to the tenant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scoping {
+    /// The rows with the tenant id in the column
+    Column(String),
+    /// The table has no tenant columns
+    Other(TenantScope),
+}
+
+impl fmt::Display for Scoping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Column(column) => write!(f, "the tenant column `{}`", column),
+            Self::Other(TenantScope::Include) => f.write_str("all rows"),
+            Self::Other(TenantScope::Exclude) => f.write_str("no rows"),
+            Self::Other(TenantScope::ForeignKeys) => f.write_str("by foreign keys"),
+        }
+    }
+}
+
+/// The scoping decisions of the tables (by the full table names)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TenantPlan {
+    pub tables: BTreeMap<String, TablePlan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TablePlan {
+    pub scoping: Scoping,
+    /// The count of the selected rows (for the tables scoped by foreign keys)
+    pub rows: Option<u64>,
+}
+
+/// The scoping of the table (the first tenant column the table has is used)
+pub fn scoping(tenant: &Tenant, table: &PgTable) -> Scoping {
+    let columns = table.get_columns_names();
+    match tenant.columns.iter().find(|c| columns.contains(c)) {
+        Some(column) => Scoping::Column(column.clone()),
+        None => Scoping::Other(tenant.scope(&table.get_names())),
+    }
+}
+
+/// Selects the rows of the tenant and sets the filters of the tables. The tables with the tenant columns get
+/// the condition on the tenant id, the tables scoped by foreign keys get the rows related to the tenant rows
+/// (as in `subset`, with the tenant rows as the roots).
+pub fn select(
+    tenant: &Tenant,
+    tables: &mut [(PgTable, i32)],
+    qw: &mut QueryWrapper,
+) -> Result<TenantPlan> {
+    let id = tenant.id.as_deref().ok_or_else(|| {
+        anyhow!("The tenant id is not set (use --tenant-id or `id` in the `tenant` config section)")
+    })?;
+    check_types(tenant, tables)?;
+
+    let mut plan = TenantPlan::default();
+    let mut roots = BTreeMap::new();
+    for (table, _) in tables.iter_mut() {
+        let scoping = scoping(tenant, table);
+        if let Scoping::Column(column) = &scoping {
+            let condition = condition(column, id);
+            // the id must be a valid value of the column type
+            qw.query(
+                format!(
+                    "SELECT 1 FROM {} WHERE {} LIMIT 0",
+                    table.quoted_full_name(),
+                    condition
+                )
+                .as_str(),
+                &[],
+            )
+            .map_err(|e| {
+                anyhow!(
+                    "The tenant id `{}` is not valid for the column `{}` of `{}`: {}",
+                    id,
+                    column,
+                    table.get_full_name(),
+                    e
+                )
+            })?;
+            table.subset = Some(SubsetFilter {
+                condition: Some(condition.clone()),
+                columns: None,
+                rows: None,
+            });
+            roots.insert(table.get_full_name(), condition);
+        }
+        plan.tables.insert(
+            table.get_full_name(),
+            TablePlan {
+                scoping,
+                rows: None,
+            },
+        );
+    }
+
+    let by_foreign_keys = plan
+        .tables
+        .values()
+        .any(|t| t.scoping == Scoping::Other(TenantScope::ForeignKeys));
+    if roots.is_empty() || !by_foreign_keys {
+        return Ok(plan);
+    }
+
+    // only the scoped tables and the tables scoped by foreign keys are subsetted
+    let mut related: Vec<(PgTable, i32)> = tables
+        .iter()
+        .filter(|(t, _)| {
+            matches!(
+                plan.tables[&t.get_full_name()].scoping,
+                Scoping::Column(_) | Scoping::Other(TenantScope::ForeignKeys)
+            )
+        })
+        .cloned()
+        .collect();
+    let subset = Subset {
+        roots,
+        children: vec![],
+        cut_foreign_keys: vec![],
+    };
+    let subset_plan = Subsetter::new(&related, qw)?.select(&subset, &mut related, qw)?;
+    for (table, _) in tables.iter_mut() {
+        let name = table.get_full_name();
+        let subsetted = match related.iter_mut().find(|(t, _)| t.get_full_name() == name) {
+            Some((subsetted, _)) => subsetted,
+            None => continue,
+        };
+        let table_plan = plan.tables.get_mut(&name).unwrap();
+        table_plan.rows = subset_plan.tables.get(&name).map(|t| t.rows);
+        table.subset = match (subsetted.subset.take(), table.subset.take()) {
+            // the parents of the tenant rows may belong to other tenants, the tenant condition is kept
+            (Some(mut filter), Some(tenant_filter)) => {
+                filter.condition = Some(format!(
+                    "({}) AND {}",
+                    filter.condition.unwrap_or_else(|| "true".to_string()),
+                    tenant_filter.condition.unwrap_or_default()
+                ));
+                filter.rows = None;
+                Some(filter)
+            }
+            (filter, tenant_filter) => filter.or(tenant_filter),
+        };
+    }
+
+    Ok(plan)
+}
+
+/// The condition of the rows of the tenant (the id is a literal, it is cast to the column type)
+fn condition(column: &str, id: &str) -> String {
+    format!(
+        "\"{}\" = '{}'",
+        column.replace('"', "\"\""),
+        id.replace('\'', "''")
+    )
+}
+
+// All tenant columns must have the same type
+fn check_types(tenant: &Tenant, tables: &[(PgTable, i32)]) -> Result<()> {
+    let columns: Vec<(String, String)> = tables
+        .iter()
+        .filter_map(|(table, _)| match scoping(tenant, table) {
+            Scoping::Column(column) => table.columns.iter().find(|c| c.name == column).map(|c| {
+                (
+                    format!("{}.{}", table.get_full_name(), column),
+                    c.data_type.clone(),
+                )
+            }),
+            Scoping::Other(_) => None,
+        })
+        .collect();
+    match columns.first() {
+        Some((_, first)) if columns.iter().any(|(_, t)| t != first) => Err(anyhow!(
+            "The tenant columns have different types: {}",
+            columns
+                .iter()
+                .map(|(name, data_type)| format!("{} ({})", name, data_type))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_tables;
+    use datanymizer_engine::Settings;
+
+    fn table(name: &str, columns: &[(&str, &str)]) -> (PgTable, i32) {
+        (test_tables::pg_table("public", name, columns), 0)
+    }
+
+    fn tenant(config: &str) -> Tenant {
+        Settings::from_yaml(&format!("{{tables: [], tenant: {}}}", config))
+            .unwrap()
+            .tenant
+            .unwrap()
+    }
+
+    #[test]
+    fn scopings() {
+        let tenant = tenant(
+            "{columns: [tenant_id, account_id], other_tables: foreign_keys, \
+            tables: {countries: include, public.audit: exclude}}",
+        );
+        let scope = |name, columns| scoping(&tenant, &table(name, columns).0);
+
+        assert_eq!(
+            scope("users", &[("id", "integer"), ("tenant_id", "integer")]),
+            Scoping::Column("tenant_id".to_string())
+        );
+        assert_eq!(
+            scope(
+                "orders",
+                &[("account_id", "integer"), ("tenant_id", "integer")]
+            ),
+            Scoping::Column("tenant_id".to_string())
+        );
+        assert_eq!(
+            scope("invoices", &[("account_id", "integer")]),
+            Scoping::Column("account_id".to_string())
+        );
+        assert_eq!(
+            scope("countries", &[("id", "integer")]),
+            Scoping::Other(TenantScope::Include)
+        );
+        assert_eq!(
+            scope("audit", &[("id", "integer")]),
+            Scoping::Other(TenantScope::Exclude)
+        );
+        assert_eq!(
+            scope("items", &[("id", "integer")]),
+            Scoping::Other(TenantScope::ForeignKeys)
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(condition("tenant_id", "42"), "\"tenant_id\" = '42'");
+        assert_eq!(
+            condition("te\"nant", "o'neil"),
+            "\"te\"\"nant\" = 'o''neil'"
+        );
+    }
+
+    #[test]
+    fn types() {
+        let tenant = tenant("{columns: [tenant_id, account_id]}");
+        let mut tables = vec![
+            table("users", &[("id", "integer"), ("tenant_id", "integer")]),
+            table("orders", &[("account_id", "integer")]),
+            table("countries", &[("id", "text")]),
+        ];
+        assert!(check_types(&tenant, &tables).is_ok());
+
+        tables.push(table("notes", &[("tenant_id", "text")]));
+        assert_eq!(
+            check_types(&tenant, &tables).unwrap_err().to_string(),
+            "The tenant columns have different types: public.users.tenant_id (integer), \
+            public.orders.account_id (integer), public.notes.tenant_id (text)"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Limits the rate of the catalog queries (`--catalog-qps`) with a token bucket: up to `qps` queries can be made
+/// at once, then the queries wait for the tokens (they are added at the `qps` rate).
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    bucket: Option<TokenBucket>,
+    /// The total time spent waiting
+    waited: Duration,
+}
+
+impl Throttle {
+    /// No limit with `None`
+    pub fn new(qps: Option<u32>) -> Self {
+        Self {
+            bucket: qps.filter(|qps| *qps > 0).map(TokenBucket::new),
+            waited: Duration::ZERO,
+        }
+    }
+
+    /// Waits for a token (before a query)
+    pub fn wait(&mut self) {
+        if let Some(bucket) = &mut self.bucket {
+            let delay = bucket.take(Instant::now());
+            if !delay.is_zero() {
+                thread::sleep(delay);
+                self.waited += delay;
+            }
+        }
+    }
+
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(qps: u32) -> Self {
+        let rate = f64::from(qps);
+        Self {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token and returns the time to wait for it (the tokens can be borrowed from the future)
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity) - 1.0;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let mut bucket = TokenBucket::new(10);
+        let start = bucket.updated;
+
+        // the burst
+        for _ in 0..10 {
+            assert_eq!(bucket.take(start), Duration::ZERO);
+        }
+        // then 10 queries per second
+        assert_eq!(bucket.take(start).as_millis(), 100);
+        assert_eq!(bucket.take(start).as_millis(), 200);
+
+        // the borrowed tokens are refilled first
+        let later = start + Duration::from_millis(250);
+        assert_eq!(bucket.take(later).as_millis(), 50);
+
+        // the bucket doesn't overflow
+        let much_later = start + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert_eq!(bucket.take(much_later), Duration::ZERO);
+        }
+        assert_eq!(bucket.take(much_later).as_millis(), 100);
+    }
+
+    #[test]
+    fn throttle() {
+        let mut throttle = Throttle::new(None);
+        for _ in 0..1000 {
+            throttle.wait();
+        }
+        assert_eq!(throttle.waited(), Duration::ZERO);
+        assert!(Throttle::new(Some(0)).bucket.is_none());
+
+        let mut throttle = Throttle::new(Some(100));
+        for _ in 0..102 {
+            throttle.wait();
+        }
+        assert!(throttle.waited() >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,466 @@
+//! Machine-readable progress events for embedding UIs.
+//!
+//! [`SocketIndicator`] writes newline-delimited JSON events to a Unix socket or a named pipe (FIFO):
+//!
+//! ```text
+//! {"seq":0,"event":"dump_started","tables":[{"table":"public.users","rows":100}],"rows":100}
+//! {"seq":1,"event":"table_started","table":"public.users","rows":100}
+//! {"seq":2,"event":"progress","table":"public.users","rows":50,"bytes":4096,"pct":50}
+//! {"seq":3,"event":"table_finished","table":"public.users","rows":100,"duration_ms":12}
+//! {"seq":4,"event":"warning","message":"..."}
+//! {"seq":5,"event":"error","table":"public.orders","error":"..."}
+//! {"seq":6,"event":"table_skipped","table":"public.audit_log","reason":"skip_data"}
+//! {"seq":7,"event":"dump_finished","tables":1,"rows":100,"duration_ms":30,"error":null}
+//! ```
+//!
+//! The events are written by a background thread, so a slow or absent consumer never stalls the dump:
+//! the events are queued in a bounded buffer and the oldest ones are dropped when it is full (the gaps
+//! in `seq` show the dropped events). The socket (or the pipe) is connected again if the consumer
+//! appears later or reconnects.
+
+use crate::indicator::{DumpSummary, Indicator, TableInfo, TableStats};
+use anyhow::Error;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::{
+        fs::{FileTypeExt, OpenOptionsExt},
+        net::UnixStream,
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// The count of the queued events (the oldest events are dropped when the queue is full)
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// How often the progress of a table is sent (besides the changes of its percentage)
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the writer waits for the consumer (or for the socket to appear) before trying again
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the queued events are written after the dump is finished
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where the events are written
+enum Target {
+    /// A Unix socket or a named pipe (connected by the writer)
+    Path(PathBuf),
+    /// A connected socket (e.g., one end of a socket pair)
+    Stream(Option<UnixStream>),
+}
+
+impl Target {
+    fn connect(&mut self) -> io::Result<Box<dyn Write + Send>> {
+        match self {
+            Self::Path(path) => connect(path),
+            Self::Stream(stream) => stream
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Write + Send>)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected)),
+        }
+    }
+}
+
+// The writes are non-blocking: a named pipe without a reader can't be opened (it is opened again later)
+fn connect(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    if fs::metadata(path)?.file_type().is_fifo() {
+        let pipe = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(Box::new(pipe))
+    } else {
+        let stream = UnixStream::connect(path)?;
+        stream.set_nonblocking(true)?;
+        Ok(Box::new(stream))
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<String>,
+    /// The sequence number of the next event
+    seq: u64,
+    dropped: u64,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    capacity: usize,
+}
+
+/// The last sent progress of a table
+struct Progress {
+    pct: Option<u64>,
+    sent: Instant,
+}
+
+/// Writes the dump events as newline-delimited JSON to a Unix socket or a named pipe (see the module docs)
+pub struct SocketIndicator {
+    shared: Arc<Shared>,
+    writer: Option<JoinHandle<()>>,
+    /// By the table names (several tables are dumped at once with `jobs`)
+    progress: Mutex<HashMap<String, Progress>>,
+}
+
+impl SocketIndicator {
+    /// The socket (or the pipe) at `path` is connected by the background writer, it may not exist yet
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self::start(Target::Path(path.into()), DEFAULT_CAPACITY)
+    }
+
+    /// Writes the events to a connected socket, at most `capacity` events are queued
+    pub fn with_stream(stream: UnixStream, capacity: usize) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self::start(Target::Stream(Some(stream)), capacity))
+    }
+
+    fn start(target: Target, capacity: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            changed: Condvar::new(),
+            capacity: capacity.max(1),
+        });
+        let writer = {
+            let shared = shared.clone();
+            thread::spawn(move || write_events(&shared, target))
+        };
+        Self {
+            shared,
+            writer: Some(writer),
+            progress: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The count of the events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.shared.queue.lock().unwrap().dropped
+    }
+
+    fn send(&self, event: &str, fields: Value) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut line = json!({ "seq": queue.seq, "event": event });
+        if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+            line.extend(fields);
+        }
+        queue.seq += 1;
+        if queue.events.len() >= self.shared.capacity {
+            queue.events.pop_front();
+            queue.dropped += 1;
+        }
+        queue.events.push_back(format!("{}\n", line));
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Drop for SocketIndicator {
+    // the queued events are written (within `FLUSH_TIMEOUT`), then the connection is closed
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.changed.notify_one();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_events(shared: &Shared, mut target: Target) {
+    let mut connection: Option<Box<dyn Write + Send>> = None;
+    // the event that is being written and how much of it is written
+    let mut pending: Option<(String, usize)> = None;
+    let mut deadline: Option<Instant> = None;
+    loop {
+        if pending.is_none() {
+            let mut queue = shared.queue.lock().unwrap();
+            while queue.events.is_empty() && !queue.closed {
+                queue = shared.changed.wait(queue).unwrap();
+            }
+            match queue.events.pop_front() {
+                Some(event) => pending = Some((event, 0)),
+                None => return,
+            }
+        }
+        if deadline.is_none() && shared.queue.lock().unwrap().closed {
+            deadline = Some(Instant::now() + FLUSH_TIMEOUT);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return;
+        }
+
+        if connection.is_none() {
+            match target.connect() {
+                Ok(c) => connection = Some(c),
+                Err(_) => {
+                    wait(shared, RETRY_INTERVAL);
+                    continue;
+                }
+            }
+        }
+        if let (Some(c), Some((event, written))) = (&mut connection, &mut pending) {
+            match c.write(&event.as_bytes()[*written..]) {
+                Ok(n) => {
+                    *written += n;
+                    if *written == event.len() {
+                        pending = None;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => wait(shared, RETRY_INTERVAL),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // the consumer is gone, the event is written again after reconnecting
+                Err(_) => {
+                    connection = None;
+                    if let Some((_, written)) = &mut pending {
+                        *written = 0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Waits for new events (at most `timeout`), so the writer wakes up when the dump is finished
+fn wait(shared: &Shared, timeout: Duration) {
+    let queue = shared.queue.lock().unwrap();
+    if !queue.closed {
+        let _ = shared.changed.wait_timeout(queue, timeout).unwrap();
+    }
+}
+
+fn table_fields(table: &TableInfo) -> Value {
+    json!({ "table": table.name, "rows": table.rows })
+}
+
+impl Indicator for SocketIndicator {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        self.send(
+            "dump_started",
+            json!({
+                "tables": tables.iter().map(table_fields).collect::<Vec<_>>(),
+                "rows": tables.iter().map(|t| t.rows).sum::<u64>(),
+            }),
+        );
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        self.progress.lock().unwrap().remove(&table.name);
+        self.send("table_started", table_fields(table));
+    }
+
+    // The progress is sent when the percentage changes (or every `PROGRESS_INTERVAL`)
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        let pct = (table.rows > 0).then(|| (dumped * 100 / table.rows).min(100));
+        let mut progress = self.progress.lock().unwrap();
+        let changed = match progress.get(&table.name) {
+            Some(p) => p.pct != pct || p.sent.elapsed() >= PROGRESS_INTERVAL,
+            None => true,
+        };
+        if changed {
+            progress.insert(
+                table.name.clone(),
+                Progress {
+                    pct,
+                    sent: Instant::now(),
+                },
+            );
+            self.send(
+                "progress",
+                json!({ "table": table.name, "rows": dumped, "bytes": bytes, "pct": pct }),
+            );
+        }
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.progress.lock().unwrap().remove(&stats.name);
+        self.send(
+            "table_finished",
+            json!({
+                "table": stats.name,
+                "rows": stats.rows,
+                "duration_ms": stats.duration.as_millis() as u64,
+            }),
+        );
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &Error) {
+        self.progress.lock().unwrap().remove(&table.name);
+        self.send(
+            "error",
+            json!({ "table": table.name, "error": error.to_string() }),
+        );
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        self.send("table_skipped", json!({ "table": table, "reason": reason }));
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        self.send(
+            "dump_finished",
+            json!({
+                "tables": summary.tables,
+                "rows": summary.rows,
+                "duration_ms": summary.duration.as_millis() as u64,
+                "error": summary.error,
+            }),
+        );
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        self.send("warning", json!({ "message": msg }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::{
+        collections::BTreeMap,
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+    };
+
+    fn table() -> TableInfo {
+        TableInfo {
+            name: "public.users".to_string(),
+            rows: 4,
+        }
+    }
+
+    fn read_events(stream: UnixStream) -> Vec<Value> {
+        BufReader::new(stream)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn events() {
+        let (stream, consumer) = UnixStream::pair().unwrap();
+        let indicator = SocketIndicator::with_stream(stream, DEFAULT_CAPACITY).unwrap();
+        indicator.dump_started(&[table()]);
+        indicator.table_started(&table());
+        for dumped in 1..=4 {
+            indicator.data_progress(&table(), dumped, dumped * 10);
+            // the percentage doesn't change
+            indicator.data_progress(&table(), dumped, dumped * 10);
+        }
+        indicator.table_finished(&TableStats {
+            name: "public.users".to_string(),
+            rows: 4,
+            bytes: 40,
+            duration: Duration::from_millis(5),
+            outbox_events: BTreeMap::new(),
+            saved_bytes: None,
+        });
+        indicator.debug_msg("not sent");
+        indicator.warning_msg("problem");
+        indicator.table_failed(&table(), &anyhow!("failure"));
+        indicator.dump_finished(&DumpSummary {
+            tables: 1,
+            rows: 4,
+            error: Some("failure".to_string()),
+            ..Default::default()
+        });
+        drop(indicator);
+
+        let events = read_events(consumer);
+        let names: Vec<_> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "dump_started",
+                "table_started",
+                "progress",
+                "progress",
+                "progress",
+                "progress",
+                "table_finished",
+                "warning",
+                "error",
+                "dump_finished"
+            ]
+        );
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event["seq"], i);
+        }
+        assert_eq!(
+            events[0],
+            json!({
+                "seq": 0,
+                "event": "dump_started",
+                "tables": [{ "table": "public.users", "rows": 4 }],
+                "rows": 4
+            })
+        );
+        assert_eq!(
+            events[3],
+            json!({ "seq": 3, "event": "progress", "table": "public.users", "rows": 2, "bytes": 20, "pct": 50 })
+        );
+        assert_eq!(events[7]["message"], "problem");
+        assert_eq!(events[8]["error"], "failure");
+        assert_eq!(events[9]["error"], "failure");
+    }
+
+    // The consumer doesn't read while dumping: the dump isn't stalled, the oldest events are dropped,
+    // the last events are delivered
+    #[test]
+    fn backpressure() {
+        let (stream, consumer) = UnixStream::pair().unwrap();
+        let indicator = SocketIndicator::with_stream(stream, 16).unwrap();
+        let message = "x".repeat(1000);
+
+        let started = Instant::now();
+        for _ in 0..20_000 {
+            indicator.warning_msg(&message);
+        }
+        indicator.dump_finished(&DumpSummary::default());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(indicator.dropped() > 0);
+
+        let reader = thread::spawn(move || read_events(consumer));
+        drop(indicator);
+        let events = reader.join().unwrap();
+
+        let seqs: Vec<u64> = events.iter().map(|e| e["seq"].as_u64().unwrap()).collect();
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+        assert!(seqs.len() < 20_001);
+        assert_eq!(*seqs.last().unwrap(), 20_000);
+        assert_eq!(events.last().unwrap()["event"], "dump_finished");
+    }
+
+    // The socket appears after the dump is started
+    #[test]
+    fn late_consumer() {
+        let path = std::env::temp_dir().join(format!(
+            "datanymizer_test_progress_{}.sock",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let indicator = SocketIndicator::new(&path);
+        indicator.dump_started(&[table()]);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        indicator.dump_finished(&DumpSummary::default());
+        let (consumer, _) = listener.accept().unwrap();
+        drop(indicator);
+
+        let names: Vec<_> = read_events(consumer)
+            .into_iter()
+            .map(|e| e["event"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["dump_started", "dump_finished"]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! The projections of `--stats-only` runs. Every table is dumped up to the sample rows with the same queries
+//! and rules as in the real dump (the output is discarded), the measured row sizes and dumping costs
+//! are multiplied by the estimated rows of the tables.
+
+use crate::indicator::{Indicator, TableStats};
+use indicatif::{HumanBytes, HumanDuration};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The sample rows of every table if they are not specified
+pub const DEFAULT_SAMPLE_ROWS: u64 = 1000;
+
+/// The memory of a value in a uniqueness set (its 64-bit hash with the spare capacity of the set)
+const UNIQ_ENTRY_BYTES: u64 = 24;
+/// The memory of a cached value besides the original and the transformed values (the LRU entry)
+const CACHE_ENTRY_BYTES: u64 = 96;
+
+/// What the projection of a table needs besides the sample
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableEstimate {
+    /// The estimated rows of the table in the full dump (with the conditions and limits)
+    pub rows: u64,
+    pub columns: u64,
+    /// The columns whose rules keep the uniqueness sets (`uniq`)
+    pub uniq_columns: u64,
+    /// The sum of `max_entries` of the `cache` rules
+    pub cache_entries: u64,
+    /// The memory limit of the client-side shuffling (`shuffle_rows`)
+    pub shuffle_memory: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    rows: u64,
+    bytes: u64,
+    duration: Duration,
+}
+
+/// Collects the estimates of the tables (from the dumper) and the samples (as the indicator)
+pub struct StatsProjection {
+    sample_rows: u64,
+    tables: Mutex<BTreeMap<String, (TableEstimate, Option<Sample>)>>,
+}
+
+impl StatsProjection {
+    pub fn new(sample_rows: u64) -> Self {
+        Self {
+            sample_rows,
+            tables: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The row limit of every table
+    pub fn sample_rows(&self) -> u64 {
+        self.sample_rows
+    }
+
+    pub fn estimate(&self, table: &str, estimate: TableEstimate) {
+        self.tables
+            .lock()
+            .unwrap()
+            .insert(table.to_string(), (estimate, None));
+    }
+
+    /// The projection of the full dump: `written` is the size of the sample dump (the schema and the other
+    /// statements are counted as written), `elapsed` is its duration (with the schema dump and the checks)
+    pub fn report(&self, written: u64, elapsed: Duration) -> ProjectionReport {
+        let tables: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, (estimate, sample))| {
+                Some(TableProjection::new(
+                    name,
+                    estimate,
+                    &(*sample)?,
+                    self.sample_rows,
+                ))
+            })
+            .collect();
+
+        let sampled_bytes: u64 = tables.iter().map(|t| t.sample_bytes).sum();
+        let sampled_duration: Duration = tables.iter().map(|t| t.sample_duration).sum();
+        let projected_duration: Duration = tables.iter().map(|t| t.duration).sum();
+        ProjectionReport {
+            sample_rows: self.sample_rows,
+            bytes: written.saturating_sub(sampled_bytes)
+                + tables.iter().map(|t| t.bytes).sum::<u64>(),
+            duration_ms: (elapsed.saturating_sub(sampled_duration) + projected_duration).as_millis()
+                as u64,
+            peak_memory_bytes: tables.iter().map(|t| t.kept_memory).sum::<u64>()
+                + tables.iter().map(|t| t.shuffle_memory).max().unwrap_or(0),
+            tables,
+        }
+    }
+}
+
+impl Indicator for StatsProjection {
+    fn table_finished(&self, stats: &TableStats) {
+        if let Some((_, sample)) = self.tables.lock().unwrap().get_mut(&stats.name) {
+            *sample = Some(Sample {
+                rows: stats.rows,
+                bytes: stats.bytes,
+                duration: stats.duration,
+            });
+        }
+    }
+}
+
+/// The writer of the sample dump: the output is discarded, only its size is counted
+#[derive(Clone, Default)]
+pub struct CountingSink(Arc<AtomicU64>);
+
+impl CountingSink {
+    pub fn written(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TableProjection {
+    pub table: String,
+    pub estimated_rows: u64,
+    pub sampled_rows: u64,
+    /// The mean size of a dumped row (in the COPY format)
+    pub row_bytes: f64,
+    /// The mean time of dumping a row (the query, the rules and the writing)
+    pub row_us: f64,
+    pub bytes: u64,
+    pub duration_ms: u64,
+    /// The uniqueness sets and the caches of the rules (they are kept till the end of the dump)
+    /// and the shuffle buffer
+    pub memory_bytes: u64,
+    #[serde(skip)]
+    sample_bytes: u64,
+    #[serde(skip)]
+    sample_duration: Duration,
+    #[serde(skip)]
+    duration: Duration,
+    #[serde(skip)]
+    kept_memory: u64,
+    #[serde(skip)]
+    shuffle_memory: u64,
+}
+
+impl TableProjection {
+    fn new(table: &str, estimate: &TableEstimate, sample: &Sample, sample_rows: u64) -> Self {
+        let (row_bytes, row_us) = match sample.rows {
+            0 => (0.0, 0.0),
+            rows => (
+                sample.bytes as f64 / rows as f64,
+                sample.duration.as_secs_f64() * 1_000_000.0 / rows as f64,
+            ),
+        };
+        // a sample with less rows than the limit is the whole table
+        let rows = match sample.rows < sample_rows {
+            true => sample.rows,
+            false => estimate.rows.max(sample.rows),
+        };
+        let scale = match sample.rows {
+            0 => 0.0,
+            sampled => rows as f64 / sampled as f64,
+        };
+        let bytes = (sample.bytes as f64 * scale) as u64;
+        let duration = sample.duration.mul_f64(scale);
+
+        let value_bytes = match estimate.columns {
+            0 => 0,
+            columns => (row_bytes / columns as f64) as u64,
+        };
+        let kept_memory = estimate.uniq_columns * rows * UNIQ_ENTRY_BYTES
+            + estimate.cache_entries.min(rows) * (CACHE_ENTRY_BYTES + 2 * value_bytes);
+        let shuffle_memory = estimate.shuffle_memory.map_or(0, |limit| limit.min(bytes));
+
+        Self {
+            table: table.to_string(),
+            estimated_rows: rows,
+            sampled_rows: sample.rows,
+            row_bytes,
+            row_us,
+            bytes,
+            duration_ms: duration.as_millis() as u64,
+            memory_bytes: kept_memory + shuffle_memory,
+            sample_bytes: sample.bytes,
+            sample_duration: sample.duration,
+            duration,
+            kept_memory,
+            shuffle_memory,
+        }
+    }
+}
+
+/// The projection of the full dump
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ProjectionReport {
+    pub sample_rows: u64,
+    pub tables: Vec<TableProjection>,
+    /// The size of the dump (uncompressed)
+    pub bytes: u64,
+    pub duration_ms: u64,
+    /// The uniqueness sets and the caches of all tables and the largest shuffle buffer
+    pub peak_memory_bytes: u64,
+}
+
+impl Display for ProjectionReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<40} {:>12} {:>10} {:>10} {:>12} {:>16} {:>12}",
+            "Table", "Rows", "Row size", "Row time", "Output", "Duration", "Memory"
+        )?;
+        for table in &self.tables {
+            writeln!(
+                f,
+                "{:<40} {:>12} {:>10} {:>10} {:>12} {:>16} {:>12}",
+                table.table,
+                table.estimated_rows,
+                format!("{:.0} B", table.row_bytes),
+                format!("{:.1} µs", table.row_us),
+                HumanBytes(table.bytes).to_string(),
+                HumanDuration(Duration::from_millis(table.duration_ms)).to_string(),
+                HumanBytes(table.memory_bytes).to_string(),
+            )?;
+        }
+        writeln!(
+            f,
+            "Total: {}, {}, peak memory {} (sampled {} rows per table)",
+            HumanBytes(self.bytes),
+            HumanDuration(Duration::from_millis(self.duration_ms)),
+            HumanBytes(self.peak_memory_bytes),
+            self.sample_rows
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(name: &str, rows: u64, bytes: u64, duration_ms: u64) -> TableStats {
+        TableStats {
+            name: name.to_string(),
+            rows,
+            bytes,
+            duration: Duration::from_millis(duration_ms),
+            outbox_events: BTreeMap::new(),
+            saved_bytes: None,
+        }
+    }
+
+    #[test]
+    fn report() {
+        let projection = StatsProjection::new(100);
+        projection.estimate(
+            "public.users",
+            TableEstimate {
+                rows: 10_000,
+                columns: 2,
+                uniq_columns: 1,
+                cache_entries: 0,
+                shuffle_memory: Some(1 << 20),
+            },
+        );
+        projection.estimate(
+            "public.countries",
+            TableEstimate {
+                rows: 50,
+                columns: 1,
+                uniq_columns: 0,
+                cache_entries: 1000,
+                shuffle_memory: None,
+            },
+        );
+        // skipped
+        projection.estimate("public.audit_log", TableEstimate::default());
+
+        projection.table_finished(&stats("public.users", 100, 5000, 20));
+        projection.table_finished(&stats("public.countries", 40, 400, 2));
+
+        let report = projection.report(10_000, Duration::from_secs(1));
+        let users = &report.tables[1];
+        assert_eq!(users.table, "public.users");
+        assert_eq!(users.estimated_rows, 10_000);
+        assert_eq!(users.row_bytes, 50.0);
+        assert_eq!(users.row_us, 200.0);
+        assert_eq!(users.bytes, 500_000);
+        assert_eq!(users.duration_ms, 2000);
+        // the uniqueness set and the shuffle buffer
+        assert_eq!(users.memory_bytes, 10_000 * UNIQ_ENTRY_BYTES + 500_000);
+
+        // the sample is the whole table
+        let countries = &report.tables[0];
+        assert_eq!(countries.estimated_rows, 40);
+        assert_eq!(countries.bytes, 400);
+        assert_eq!(countries.memory_bytes, 40 * (CACHE_ENTRY_BYTES + 20));
+
+        assert_eq!(report.tables.len(), 2);
+        assert_eq!(report.bytes, 10_000 - 5400 + 500_400);
+        assert_eq!(report.duration_ms, 1000 - 22 + 2002);
+        assert_eq!(
+            report.peak_memory_bytes,
+            10_000 * UNIQ_ENTRY_BYTES + 40 * (CACHE_ENTRY_BYTES + 20) + 500_000
+        );
+        assert!(report.to_string().starts_with("Table "));
+    }
+
+    #[test]
+    fn counting_sink() {
+        let sink = CountingSink::default();
+        let mut writer = sink.clone();
+        writer.write_all(b"COPY users FROM STDIN;\n").unwrap();
+        writer.write_all(b"\\.\n").unwrap();
+        assert_eq!(sink.written(), 26);
+    }
+}
@@ -0,0 +1,662 @@
+//! The dump jobs of the service: the requests, the state that is updated by the indicator events and the runner.
+
+use super::ServiceOptions;
+use crate::{
+    indicator::{Indicator, TableInfo, TableStats},
+    postgres::{
+        conn_url, connector::Connector, credentials::Credentials, dumper::PgDumper, IsolationLevel,
+    },
+    Dumper,
+};
+use anyhow::{anyhow, Error, Result};
+use datanymizer_engine::{
+    secrets::{self, SecretRef},
+    Engine, Pepper, Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Instant,
+};
+use url::Url;
+
+/// The prefix of the references to the environment variables
+const ENV_PREFIX: &str = "env:";
+/// At most this count of warnings is kept in the job state
+const MAX_WARNINGS: usize = 100;
+
+/// The body of `POST /jobs`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobRequest {
+    /// The reference to the database URL: `env:<VARIABLE>` or a secret reference (e.g. `vault:secret/data/db#url`)
+    pub database: String,
+    /// The config (YAML)
+    pub config: String,
+    /// The dump file, relative to the output directory of the service
+    pub output: String,
+}
+
+impl JobRequest {
+    /// Checks the request and parses the config (the job isn't started yet).
+    /// The config can't include the files and read the environment variables or the secrets.
+    pub fn prepare(self, options: &ServiceOptions) -> Result<JobSpec> {
+        match self.database.strip_prefix(ENV_PREFIX) {
+            Some(name) if !options.allowed_env.iter().any(|allowed| allowed == name) => {
+                return Err(anyhow!(
+                    "The environment variable `{}` is not allowed (see `--allow-env`)",
+                    name
+                ));
+            }
+            Some(_) => {}
+            None => match SecretRef::parse(&self.database) {
+                Some(reference) if !is_allowed_secret(&reference, &options.allowed_secrets) => {
+                    return Err(anyhow!(
+                        "The secret `{}` is not allowed (see `--allow-secret`)",
+                        reference
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(anyhow!(
+                        "`database` must be a reference to the database URL (`env:<VARIABLE>` or a secret reference), \
+                        the credentials are never accepted in the request"
+                    ));
+                }
+            },
+        }
+
+        let relative = Path::new(&self.output);
+        if self.output.is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "`output` must be a file path relative to the output directory (without `..`)"
+            ));
+        }
+
+        let settings = Settings::from_untrusted_yaml(&self.config, Pepper::random())
+            .map_err(|e| anyhow!("Invalid config: {}", e))?;
+        if settings.compression.is_some() {
+            return Err(anyhow!("The service jobs don't support `compression`"));
+        }
+
+        Ok(JobSpec {
+            database: self.database,
+            settings,
+            output: options.output_dir.join(relative),
+            output_name: self.output,
+        })
+    }
+}
+
+/// A checked job request
+pub struct JobSpec {
+    database: String,
+    settings: Settings,
+    output: PathBuf,
+    /// The output as it is in the request
+    output_name: String,
+}
+
+impl JobSpec {
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    /// The cancellation is requested, the job stops at the next write to the dump
+    Cancelling,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableStatus {
+    Pending,
+    Running,
+    Finished,
+    Failed,
+    /// Dumped without data
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableProgress {
+    pub table: String,
+    pub status: TableStatus,
+    /// The rows dumped so far
+    pub rows: u64,
+    pub estimated_rows: u64,
+    pub bytes: u64,
+}
+
+impl TableProgress {
+    fn new(table: String, status: TableStatus) -> Self {
+        Self {
+            table,
+            status,
+            rows: 0,
+            estimated_rows: 0,
+            bytes: 0,
+        }
+    }
+}
+
+/// The state of a job (the body of `GET /jobs/{id}`)
+#[derive(Debug, Clone, Serialize)]
+pub struct JobState {
+    pub id: String,
+    pub status: JobStatus,
+    pub output: String,
+    /// The tables in the dump order
+    pub tables: Vec<TableProgress>,
+    /// The rows of the finished tables
+    pub rows: u64,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl JobState {
+    fn new(id: String, output: String) -> Self {
+        Self {
+            id,
+            status: JobStatus::Running,
+            output,
+            tables: vec![],
+            rows: 0,
+            warnings: vec![],
+            error: None,
+            elapsed_ms: 0,
+            started: Instant::now(),
+        }
+    }
+
+    fn table(&mut self, name: &str) -> &mut TableProgress {
+        match self.tables.iter().position(|t| t.table == name) {
+            Some(i) => &mut self.tables[i],
+            None => {
+                self.tables
+                    .push(TableProgress::new(name.to_string(), TableStatus::Pending));
+                self.tables.last_mut().unwrap()
+            }
+        }
+    }
+
+    // The cancelled jobs fail (at the next write), unless they are finished before it
+    fn finish(&mut self, result: Result<()>) {
+        self.elapsed_ms = self.started.elapsed().as_millis() as u64;
+        self.status = match result {
+            Ok(()) => JobStatus::Succeeded,
+            Err(_) if self.status == JobStatus::Cancelling => JobStatus::Cancelled,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                JobStatus::Failed
+            }
+        };
+    }
+}
+
+/// A job that runs in its own thread
+pub struct Job {
+    state: Arc<Mutex<JobState>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    /// Starts the dump
+    pub fn start(id: String, spec: JobSpec, pg_dump_location: String) -> Self {
+        let state = Arc::new(Mutex::new(JobState::new(id, spec.output_name.clone())));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let job = Self {
+            state: state.clone(),
+            cancelled: cancelled.clone(),
+        };
+        thread::spawn(move || {
+            let output = spec.output.clone();
+            let result = run(
+                spec,
+                pg_dump_location,
+                JobIndicator(state.clone()),
+                cancelled,
+            );
+            // the partial dumps are removed
+            if result.is_err() {
+                let _ = fs::remove_file(output);
+            }
+            lock(&state).finish(result);
+        });
+
+        job
+    }
+
+    pub fn state(&self) -> JobState {
+        let mut state = lock(&self.state).clone();
+        if !state.status.is_finished() {
+            state.elapsed_ms = state.started.elapsed().as_millis() as u64;
+        }
+        state
+    }
+
+    /// Requests the cancellation, returns `false` if the job is finished already
+    pub fn cancel(&self) -> bool {
+        let mut state = lock(&self.state);
+        if state.status.is_finished() {
+            return false;
+        }
+        state.status = JobStatus::Cancelling;
+        self.cancelled.store(true, Ordering::SeqCst);
+        true
+    }
+}
+
+// The state is still consistent if a holder panicked
+fn lock(state: &Mutex<JobState>) -> MutexGuard<'_, JobState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// The errors never contain the resolved database URL and its parts
+fn run(
+    spec: JobSpec,
+    pg_dump_location: String,
+    indicator: JobIndicator,
+    cancelled: Arc<AtomicBool>,
+) -> Result<()> {
+    let (url, raw) = resolve_database(&spec.database)?;
+    let mut resolved = vec![raw, url.to_string(), conn_url::redact(url.as_str())];
+    // both the encoded and the decoded passwords
+    resolved.extend(url.password().map(ToString::to_string));
+    resolved.extend(conn_url::take_password(&mut url.clone()));
+    let reference = spec.database.clone();
+    dump(url, spec, pg_dump_location, indicator, cancelled)
+        .map_err(|e| anyhow!(scrub(&e.to_string(), &resolved, &reference)))
+}
+
+fn dump(
+    mut url: Url,
+    spec: JobSpec,
+    pg_dump_location: String,
+    indicator: JobIndicator,
+    cancelled: Arc<AtomicBool>,
+) -> Result<()> {
+    // the password isn't passed to `pg_dump` in the URL
+    let credentials = Credentials::take_from_url(&mut url);
+    let mut engine = Engine::new(spec.settings);
+    if engine.settings.has_quasi_identifiers() {
+        engine.enable_anonymity_check();
+    }
+
+    let mut session = BTreeMap::new();
+    if !url.query_pairs().any(|(key, _)| key == "application_name") {
+        session.insert(
+            "application_name".to_string(),
+            format!("pg_datanymizer/{}", env!("CARGO_PKG_VERSION")),
+        );
+    }
+    session.extend(engine.settings.session.clone());
+    let mut connection = Connector::new(url, false, false)
+        .with_credentials(credentials)
+        .with_session(&session)?
+        .connect()?;
+    connection.acquire_advisory_lock(false)?;
+
+    if let Some(dir) = spec.output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let output = CancellableWriter {
+        inner: File::create(&spec.output)?,
+        cancelled,
+    };
+    PgDumper::new(
+        engine,
+        Some(IsolationLevel::ReadCommitted),
+        pg_dump_location,
+        output,
+        indicator,
+        vec![],
+    )?
+    .dump(&mut connection)?;
+
+    connection.release_advisory_lock()
+}
+
+/// The URL and its raw value (the errors don't contain the value)
+fn resolve_database(reference: &str) -> Result<(Url, String)> {
+    let raw = match reference.strip_prefix(ENV_PREFIX) {
+        Some(name) => {
+            env::var(name).map_err(|_| anyhow!("The environment variable `{}` is not set", name))?
+        }
+        None => secrets::resolve(reference)?
+            .ok_or_else(|| anyhow!("`{}` is not a secret reference", reference))?,
+    };
+    let url = conn_url::parse(&raw)
+        .map_err(|_| anyhow!("`{}` is not a valid database URL", reference))?;
+    Ok((url, raw))
+}
+
+/// Replaces the resolved values in the message with the reference (the longer values first)
+fn scrub(message: &str, resolved: &[String], reference: &str) -> String {
+    let mut values: Vec<_> = resolved.iter().filter(|v| !v.is_empty()).collect();
+    values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    values
+        .into_iter()
+        .fold(message.to_string(), |message, value| {
+            message.replace(value.as_str(), reference)
+        })
+}
+
+/// Fails the writes after the cancellation (so the dump stops at the next write)
+struct CancellableWriter<W> {
+    inner: W,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<W: Write> Write for CancellableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(io::Error::other("the job is cancelled"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Updates the job state with the progress events
+struct JobIndicator(Arc<Mutex<JobState>>);
+
+impl Indicator for JobIndicator {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        lock(&self.0).tables = tables
+            .iter()
+            .map(|t| TableProgress {
+                estimated_rows: t.rows,
+                ..TableProgress::new(t.name.clone(), TableStatus::Pending)
+            })
+            .collect();
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        let mut state = lock(&self.0);
+        let progress = state.table(&table.name);
+        progress.status = TableStatus::Running;
+        progress.estimated_rows = table.rows;
+        progress.rows = 0;
+        progress.bytes = 0;
+    }
+
+    fn data_progress(&self, table: &TableInfo, dumped: u64, bytes: u64) {
+        let mut state = lock(&self.0);
+        let progress = state.table(&table.name);
+        progress.rows = dumped;
+        progress.bytes = bytes;
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        let mut state = lock(&self.0);
+        state.rows += stats.rows;
+        let progress = state.table(&stats.name);
+        progress.status = TableStatus::Finished;
+        progress.rows = stats.rows;
+        progress.bytes = stats.bytes;
+    }
+
+    fn table_failed(&self, table: &TableInfo, _error: &Error) {
+        lock(&self.0).table(&table.name).status = TableStatus::Failed;
+    }
+
+    fn table_skipped(&self, table: &str, _reason: &str) {
+        lock(&self.0).table(table).status = TableStatus::Skipped;
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        let mut state = lock(&self.0);
+        if state.warnings.len() < MAX_WARNINGS {
+            state.warnings.push(msg.to_string());
+        }
+    }
+}
+
+/// The secret is allowed if it is listed or it is under a listed prefix (ending with `/`),
+/// the paths with `..` are never allowed
+fn is_allowed_secret(reference: &SecretRef, allowed: &[String]) -> bool {
+    let name = reference.to_string();
+    !reference.path.split('/').any(|s| s == "..")
+        && allowed
+            .iter()
+            .any(|a| *a == name || (a.ends_with('/') && name.starts_with(a.as_str())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(database: &str, output: &str) -> JobRequest {
+        JobRequest {
+            database: database.to_string(),
+            config: "tables: []".to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    fn options() -> ServiceOptions {
+        ServiceOptions {
+            output_dir: PathBuf::from("/dumps"),
+            max_jobs: 1,
+            pg_dump_location: "pg_dump".to_string(),
+            allowed_env: vec!["SRC_DATABASE_URL".to_string(), "DB".to_string()],
+            allowed_secrets: vec![
+                "vault:secret/data/db#url".to_string(),
+                "aws-sm:datanymizer/".to_string(),
+            ],
+            token: None,
+        }
+    }
+
+    fn error(request: JobRequest) -> String {
+        request.prepare(&options()).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn prepare() {
+        let spec = request("env:SRC_DATABASE_URL", "daily/users.sql")
+            .prepare(&options())
+            .unwrap();
+        assert_eq!(spec.output(), Path::new("/dumps/daily/users.sql"));
+        for database in ["vault:secret/data/db#url", "aws-sm:datanymizer/replica#url"] {
+            assert!(request(database, "users.sql").prepare(&options()).is_ok());
+        }
+        assert_eq!(
+            error(request("env:AWS_SECRET_ACCESS_KEY", "users.sql")),
+            "The environment variable `AWS_SECRET_ACCESS_KEY` is not allowed (see `--allow-env`)"
+        );
+        for database in [
+            "vault:secret/data/db#password",
+            "aws-sm:datanymizer",
+            "aws-sm:datanymizer/../billing#key",
+            "aws-sm:datanymizer-prod/db",
+        ] {
+            assert_eq!(
+                error(request(database, "users.sql")),
+                format!(
+                    "The secret `{}` is not allowed (see `--allow-secret`)",
+                    database
+                )
+            );
+        }
+
+        for database in ["postgres://user:secret@db/app", "SRC_DATABASE_URL"] {
+            assert!(error(request(database, "users.sql"))
+                .starts_with("`database` must be a reference to the database URL"));
+        }
+        for output in [
+            "",
+            "../users.sql",
+            "/tmp/users.sql",
+            "daily/../../users.sql",
+        ] {
+            assert!(error(request("env:DB", output))
+                .starts_with("`output` must be a file path relative to the output directory"));
+        }
+
+        let mut compressed = request("env:DB", "users.sql.gz");
+        compressed.config = "{tables: [], compression: gzip}".to_string();
+        assert_eq!(
+            error(compressed),
+            "The service jobs don't support `compression`"
+        );
+
+        let mut included = request("env:DB", "users.sql");
+        included.config = "{include: [/etc/datanymizer/prod.yml], tables: []}".to_string();
+        assert_eq!(
+            error(included),
+            "Invalid config: `include` isn't allowed in this config"
+        );
+        let mut interpolated = request("env:DB", "users.sql");
+        interpolated.config = "{tables: [], globals: {url: '${DB}'}}".to_string();
+        let spec = interpolated.prepare(&options()).unwrap();
+        assert_eq!(spec.settings.globals.unwrap()["url"], "${DB}");
+    }
+
+    #[test]
+    fn resolve_database() {
+        env::set_var("DATANYMIZER_JOBS_TEST_URL", "user:s3cr3t@db/app");
+        assert_eq!(
+            super::resolve_database("env:DATANYMIZER_JOBS_TEST_URL")
+                .unwrap_err()
+                .to_string(),
+            "`env:DATANYMIZER_JOBS_TEST_URL` is not a valid database URL"
+        );
+
+        env::set_var("DATANYMIZER_JOBS_TEST_URL", "postgres://user:s3cr3t@db/app");
+        let (url, raw) = super::resolve_database("env:DATANYMIZER_JOBS_TEST_URL").unwrap();
+        assert_eq!(url.as_str(), raw);
+    }
+
+    #[test]
+    fn scrub() {
+        let resolved = [
+            "postgres://user:p%40ss@db/app".to_string(),
+            "postgres://user:***@db/app".to_string(),
+            "p%40ss".to_string(),
+            "p@ss".to_string(),
+        ];
+        assert_eq!(
+            super::scrub(
+                "error connecting to postgres://user:***@db/app: password p@ss is wrong",
+                &resolved,
+                "env:DB"
+            ),
+            "error connecting to env:DB: password env:DB is wrong"
+        );
+        assert_eq!(
+            super::scrub("relation does not exist", &resolved, "env:DB"),
+            "relation does not exist"
+        );
+    }
+
+    #[test]
+    fn indicator() {
+        let state = Arc::new(Mutex::new(JobState::new(
+            "1".to_string(),
+            "users.sql".to_string(),
+        )));
+        let indicator = JobIndicator(state.clone());
+        let info = |name: &str, rows| TableInfo {
+            name: name.to_string(),
+            rows,
+        };
+
+        indicator.dump_started(&[info("public.users", 10), info("public.orders", 5)]);
+        indicator.table_started(&info("public.users", 10));
+        indicator.data_progress(&info("public.users", 10), 4, 40);
+        {
+            let state = lock(&state);
+            assert_eq!(state.tables[0].status, TableStatus::Running);
+            assert_eq!((state.tables[0].rows, state.tables[0].bytes), (4, 40));
+            assert_eq!(state.tables[1].status, TableStatus::Pending);
+            assert_eq!(state.tables[1].estimated_rows, 5);
+        }
+
+        indicator.table_finished(&TableStats {
+            name: "public.users".to_string(),
+            rows: 10,
+            bytes: 100,
+            duration: Default::default(),
+            outbox_events: BTreeMap::new(),
+            saved_bytes: None,
+        });
+        indicator.table_skipped("public.audit_log", "skip_data");
+        indicator.warning_msg("problem");
+
+        let state = lock(&state);
+        assert_eq!(state.rows, 10);
+        assert_eq!(state.tables[0].status, TableStatus::Finished);
+        assert_eq!(
+            state.tables[2],
+            TableProgress::new("public.audit_log".to_string(), TableStatus::Skipped)
+        );
+        assert_eq!(state.warnings, ["problem"]);
+    }
+
+    #[test]
+    fn finish() {
+        let mut state = JobState::new("1".to_string(), "users.sql".to_string());
+        state.finish(Err(anyhow!("connection refused")));
+        assert_eq!(state.status, JobStatus::Failed);
+        assert_eq!(state.error.as_deref(), Some("connection refused"));
+
+        let mut state = JobState::new("2".to_string(), "users.sql".to_string());
+        state.status = JobStatus::Cancelling;
+        state.finish(Err(anyhow!("the job is cancelled")));
+        assert_eq!(state.status, JobStatus::Cancelled);
+        assert_eq!(state.error, None);
+    }
+
+    #[test]
+    fn cancellable_writer() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut writer = CancellableWriter {
+            inner: vec![],
+            cancelled: cancelled.clone(),
+        };
+        writer.write_all(b"COPY").unwrap();
+        cancelled.store(true, Ordering::SeqCst);
+        assert_eq!(
+            writer.write_all(b" users").unwrap_err().to_string(),
+            "the job is cancelled"
+        );
+        assert_eq!(writer.inner, b"COPY");
+    }
+}
@@ -0,0 +1,338 @@
+//! The service mode (`pg_datanymizer serve`): the dump jobs are submitted and watched with the HTTP/JSON API.
+//!
+//! - `POST /jobs` starts a job (the body is [`JobRequest`]);
+//! - `GET /jobs` lists the jobs;
+//! - `GET /jobs/{id}` returns the status and the progress of the tables;
+//! - `DELETE /jobs/{id}` cancels the job.
+//!
+//! The jobs run in the service process and they aren't persisted. With a token, the requests must have
+//! the `Authorization: Bearer <token>` header; without it, the service listens only on the loopback addresses.
+
+pub mod jobs;
+
+use anyhow::Result;
+use jobs::{Job, JobRequest};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    net::ToSocketAddrs,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+use tiny_http::{Header, Response, Server};
+
+/// The larger request bodies are rejected
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+pub struct ServiceOptions {
+    /// The dumps are written to this directory
+    pub output_dir: PathBuf,
+    /// The max count of the running jobs
+    pub max_jobs: usize,
+    pub pg_dump_location: String,
+    /// The environment variables that the jobs can read the database URLs from (`env:<VARIABLE>`)
+    pub allowed_env: Vec<String>,
+    /// The secret references (or their prefixes ending with `/`) that the jobs can read the database URLs from
+    pub allowed_secrets: Vec<String>,
+    /// The bearer token of the requests
+    pub token: Option<String>,
+}
+
+pub struct Service {
+    options: ServiceOptions,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+}
+
+impl Service {
+    pub fn new(options: ServiceOptions) -> Self {
+        Self {
+            options,
+            jobs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Handles the requests until the server is unblocked
+    pub fn serve(&self, server: &Server) {
+        for mut request in server.incoming_requests() {
+            let authorization = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Authorization"))
+                .map(|h| h.value.to_string());
+            let mut body = String::new();
+            let (status, response) = if !self.authorized(authorization.as_deref()) {
+                error(401, "Unauthorized")
+            } else {
+                match request
+                    .as_reader()
+                    .take(MAX_BODY_SIZE + 1)
+                    .read_to_string(&mut body)
+                {
+                    Ok(size) if size as u64 > MAX_BODY_SIZE => {
+                        error(413, "The request body is too large")
+                    }
+                    Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                    Err(e) => error(400, format!("Invalid request body: {}", e)),
+                }
+            };
+
+            let response = Response::from_string(response.to_string())
+                .with_status_code(status)
+                .with_header(
+                    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+                );
+            // the client can be gone already
+            let _ = request.respond(response);
+        }
+    }
+
+    /// Checks the `Authorization` header (any request is authorized without the token)
+    pub fn authorized(&self, authorization: Option<&str>) -> bool {
+        match &self.options.token {
+            None => true,
+            Some(token) => authorization
+                .and_then(|a| a.strip_prefix("Bearer "))
+                .is_some_and(|a| constant_time_eq(a.as_bytes(), token.as_bytes())),
+        }
+    }
+
+    /// Routes the request, returns the HTTP status and the JSON body
+    pub fn handle(&self, method: &str, url: &str, body: &str) -> (u16, Value) {
+        let path = url
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('/');
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+        match (method, segments.as_slice()) {
+            ("POST", ["jobs"]) => self.create(body),
+            ("GET", ["jobs"]) => self.list(),
+            ("GET", ["jobs", id]) => self.show(id),
+            ("DELETE", ["jobs", id]) => self.cancel(id),
+            (_, ["jobs"]) | (_, ["jobs", _]) => error(405, "Method not allowed"),
+            _ => error(404, "Not found"),
+        }
+    }
+
+    fn create(&self, body: &str) -> (u16, Value) {
+        let request: JobRequest = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => return error(400, format!("Invalid request: {}", e)),
+        };
+        let spec = match request.prepare(&self.options) {
+            Ok(spec) => spec,
+            Err(e) => return error(400, e.to_string()),
+        };
+
+        let mut jobs = self.jobs();
+        let running: Vec<_> = jobs
+            .values()
+            .filter(|job| !job.state().status.is_finished())
+            .collect();
+        if running.len() >= self.options.max_jobs {
+            return error(
+                429,
+                format!("Too many running jobs (at most {})", self.options.max_jobs),
+            );
+        }
+        if running
+            .iter()
+            .any(|job| self.options.output_dir.join(job.state().output) == spec.output())
+        {
+            return error(409, "The output is used by a running job");
+        }
+
+        let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+        let job = Job::start(id.to_string(), spec, self.options.pg_dump_location.clone());
+        let state = job.state();
+        jobs.insert(id, job);
+        (201, json!(state))
+    }
+
+    fn list(&self) -> (u16, Value) {
+        let jobs: Vec<_> = self.jobs().values().map(Job::state).collect();
+        (200, json!({ "jobs": jobs }))
+    }
+
+    fn show(&self, id: &str) -> (u16, Value) {
+        match self.find(id, |job| job.state()) {
+            Some(state) => (200, json!(state)),
+            None => error(404, "Job not found"),
+        }
+    }
+
+    fn cancel(&self, id: &str) -> (u16, Value) {
+        match self.find(id, |job| (job.cancel(), job.state())) {
+            Some((true, state)) => (202, json!(state)),
+            Some((false, _)) => error(409, "The job is finished"),
+            None => error(404, "Job not found"),
+        }
+    }
+
+    fn find<T>(&self, id: &str, f: impl FnOnce(&Job) -> T) -> Option<T> {
+        let id: u64 = id.parse().ok()?;
+        self.jobs().get(&id).map(f)
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, BTreeMap<u64, Job>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// `:8080` means all the interfaces
+pub fn listen_address(listen: &str) -> String {
+    if listen.starts_with(':') {
+        format!("0.0.0.0{}", listen)
+    } else {
+        listen.to_string()
+    }
+}
+
+/// Starts the HTTP server. Without the token, only the loopback addresses are allowed.
+pub fn bind(listen: &str, authenticated: bool) -> Result<Server> {
+    let address = listen_address(listen);
+    if !authenticated && !is_loopback(&address)? {
+        return Err(anyhow::anyhow!(
+            "Can't listen on {} without a token (only the loopback addresses are allowed, see `--token-file`)",
+            listen
+        ));
+    }
+    Server::http(address).map_err(|e| anyhow::anyhow!("Can't listen on {}: {}", listen, e))
+}
+
+fn is_loopback(address: &str) -> Result<bool> {
+    let mut addresses = address
+        .to_socket_addrs()
+        .map_err(|e| anyhow::anyhow!("Invalid address {}: {}", address, e))?
+        .peekable();
+    Ok(addresses.peek().is_some() && addresses.all(|a| a.ip().is_loopback()))
+}
+
+// The comparison time doesn't depend on the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn error(status: u16, message: impl ToString) -> (u16, Value) {
+    (status, json!({ "error": message.to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(max_jobs: usize) -> Service {
+        Service::new(ServiceOptions {
+            output_dir: PathBuf::from("/dumps"),
+            max_jobs,
+            pg_dump_location: "pg_dump".to_string(),
+            allowed_env: vec!["DATABASE_URL".to_string()],
+            allowed_secrets: vec![],
+            token: None,
+        })
+    }
+
+    #[test]
+    fn routing() {
+        let service = service(1);
+        assert_eq!(
+            service.handle("GET", "/jobs", ""),
+            (200, json!({ "jobs": [] }))
+        );
+        assert_eq!(service.handle("GET", "/jobs/?page=2", "").0, 200);
+        assert_eq!(
+            service.handle("GET", "/jobs/1", ""),
+            (404, json!({ "error": "Job not found" }))
+        );
+        assert_eq!(service.handle("DELETE", "/jobs/abc", "").0, 404);
+        assert_eq!(service.handle("PUT", "/jobs/1", "").0, 405);
+        assert_eq!(service.handle("DELETE", "/jobs", "").0, 405);
+        assert_eq!(
+            service.handle("GET", "/", ""),
+            (404, json!({ "error": "Not found" }))
+        );
+    }
+
+    #[test]
+    fn invalid_jobs() {
+        let service = service(1);
+        let create = |body: Value| service.handle("POST", "/jobs", &body.to_string());
+
+        let (status, body) = create(json!({
+            "database": "postgres://user:secret@db/app",
+            "config": "tables: []",
+            "output": "app.sql",
+        }));
+        assert_eq!(status, 400);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("the credentials are never accepted"));
+
+        let (status, body) = create(json!({
+            "database": "env:DATABASE_URL",
+            "config": "tables: []",
+            "output": "app.sql",
+            "password": "secret",
+        }));
+        assert_eq!(status, 400);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("unknown field `password`"));
+
+        let (status, body) = create(json!({
+            "database": "env:DATABASE_URL",
+            "config": "tables: 1",
+            "output": "app.sql",
+        }));
+        assert_eq!(status, 400);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid config"));
+
+        assert_eq!(service.handle("POST", "/jobs", "{").0, 400);
+        assert_eq!(
+            service.handle("GET", "/jobs", ""),
+            (200, json!({ "jobs": [] }))
+        );
+    }
+
+    #[test]
+    fn authorized() {
+        assert!(service(1).authorized(None));
+
+        let mut service = service(1);
+        service.options.token = Some("t0ken".to_string());
+        assert!(service.authorized(Some("Bearer t0ken")));
+        for authorization in [
+            None,
+            Some("Bearer t0ke"),
+            Some("t0ken"),
+            Some("Basic t0ken"),
+        ] {
+            assert!(!service.authorized(authorization));
+        }
+    }
+
+    #[test]
+    fn listen() {
+        assert_eq!(listen_address(":8080"), "0.0.0.0:8080");
+        assert_eq!(listen_address("127.0.0.1:8080"), "127.0.0.1:8080");
+
+        assert!(is_loopback("127.0.0.1:8080").unwrap());
+        assert!(is_loopback("[::1]:8080").unwrap());
+        assert!(is_loopback("localhost:8080").unwrap());
+        assert!(!is_loopback("0.0.0.0:8080").unwrap());
+        assert!(!is_loopback("10.0.0.1:8080").unwrap());
+
+        let error = bind(":0", false).err().unwrap().to_string();
+        assert!(error.starts_with("Can't listen on :0 without a token"));
+        assert!(bind("127.0.0.1:0", false).is_ok());
+        assert!(bind(":0", true).is_ok());
+    }
+}
@@ -0,0 +1,1012 @@
+//! The stages of a dump shared by the database backends: the schema before the data, the data of the tables
+//! and the epilogue (indexes, constraints, etc.). [`StagedDumper`] runs the stages, iterates the tables,
+//! dispatches the indicator events, applies the error policies and collects the summary and the manifest.
+//! The backends implement [`SchemaSection`], [`TableData`] and [`Epilogue`].
+
+use crate::{
+    checkpoint::Checkpoint,
+    indicator::{DumpSummary, Indicator, TableInfo, TableStats},
+    manifest::{Manifest, TableManifest},
+    Dumper, SchemaInspector, Table,
+};
+use anyhow::{anyhow, Error, Result};
+use datanymizer_engine::Settings;
+use indicatif::HumanBytes;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpStage {
+    /// The schema before the data (tables, types, functions, etc.)
+    PreData,
+    Data,
+    /// The schema after the data (indexes, constraints, triggers, etc.)
+    PostData,
+}
+
+impl DumpStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PreData => "pre-data",
+            Self::Data => "data",
+            Self::PostData => "post-data",
+        }
+    }
+}
+
+impl Display for DumpStage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What to do with the tables that are dropped (or renamed) after the tables are inspected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingTablePolicy {
+    #[default]
+    Fail,
+    Skip,
+}
+
+impl FromStr for MissingTablePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            _ => Err(format!(
+                "Unknown missing table policy `{}` (skip or fail)",
+                s
+            )),
+        }
+    }
+}
+
+/// The common part of the backends
+pub trait Backend: 'static + Send {
+    type Connection;
+    /// The type of the column types
+    type Type;
+    type Table: Table<Self::Type>;
+    type SchemaInspector: SchemaInspector<Connection = Self::Connection>;
+
+    fn settings(&self) -> &Settings;
+
+    fn schema_inspector(&self) -> Self::SchemaInspector;
+
+    /// The manifest for the table manifests (`None` if the manifest is not collected)
+    fn manifest(&self) -> Option<&Arc<Mutex<Manifest>>>;
+
+    /// Writes the log message to the data output
+    fn write_log(&mut self, message: String) -> Result<()>;
+}
+
+/// Emits the schema section before the data
+pub trait SchemaSection: Backend {
+    fn emit_schema(
+        &mut self,
+        connection: &mut Self::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()>;
+}
+
+/// Streams the data of the tables
+pub trait TableData: Backend {
+    /// The state shared by the tables of the data stage (e.g., the dump transaction)
+    type Session<'c>;
+
+    /// Prepares the data stage, returns the tables in the dump order with their weights
+    /// (the counts of the tables that depend on them)
+    fn data_tables(
+        &mut self,
+        connection: &mut Self::Connection,
+        events: &dyn Indicator,
+    ) -> Result<Vec<(Self::Table, i32)>>;
+
+    /// Starts the data stage (the tables can be changed, e.g., the subset rows are selected)
+    fn begin_data<'c>(
+        &mut self,
+        connection: &'c mut Self::Connection,
+        tables: &mut [(Self::Table, i32)],
+        summary: &mut DumpSummary,
+        events: &dyn Indicator,
+    ) -> Result<Self::Session<'c>>;
+
+    /// The placement of the table in the dump order (for the debug messages)
+    fn placement(&self, table: &Self::Table, weight: i32) -> String;
+
+    /// The reason to skip the table data (an empty string if there are no details),
+    /// `None` if the data is dumped
+    fn skip_reason(&self, table: &Self::Table) -> Option<String>;
+
+    /// Called before the data stage for the tables dumped before the checkpoint of a resumed dump
+    /// (they aren't dumped again)
+    fn table_resumed(&mut self, _table: &Self::Table) {}
+
+    /// Called for the tables whose data is skipped (e.g., for the sequences of the tables dumped without data)
+    fn skip_table(
+        &mut self,
+        _session: &mut Self::Session<'_>,
+        _table: &Self::Table,
+        _events: &dyn Indicator,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn table_info(&self, table: &Self::Table) -> TableInfo;
+
+    /// Streams the table data: starts the table with `run`, reports the progress and returns the count of rows
+    /// (and the table manifest if the manifest is collected)
+    fn stream_table(
+        &mut self,
+        session: &mut Self::Session<'_>,
+        table: &Self::Table,
+        run: &mut TableRun<'_>,
+    ) -> Result<TableOutput>;
+
+    /// Whether the table is dropped or renamed after the inspection
+    fn is_missing_table(&self, error: &Error) -> bool;
+
+    /// Checks that the dump can be continued without the missing table
+    fn check_skip(&self, _error: &Error) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after all tables are dumped (e.g., for the reports)
+    fn end_tables(&mut self, _events: &dyn Indicator) -> Result<()> {
+        Ok(())
+    }
+
+    /// Ends the data stage, the error can be replaced (e.g., with a more detailed one)
+    fn end_data(
+        &mut self,
+        result: Result<()>,
+        _summary: &mut DumpSummary,
+        _events: &dyn Indicator,
+    ) -> Result<()> {
+        result
+    }
+}
+
+/// Emits the end of the dump after the data
+pub trait Epilogue: Backend {
+    fn emit_epilogue(
+        &mut self,
+        connection: &mut Self::Connection,
+        events: &dyn Indicator,
+    ) -> Result<()>;
+}
+
+/// The result of a table
+#[derive(Debug)]
+pub struct TableOutput {
+    pub rows: u64,
+    pub manifest: Option<TableManifest>,
+    /// The counts of the rows of an outbox table by the event types
+    pub outbox_events: BTreeMap<String, u64>,
+    /// The estimated size of the omitted columns of a projected table (`only_columns`)
+    pub saved_bytes: Option<u64>,
+}
+
+/// Dispatches the events of a table
+pub struct TableRun<'a> {
+    events: &'a dyn Indicator,
+    started: Option<(TableInfo, Instant)>,
+    /// The size of the data dumped so far
+    bytes: u64,
+    /// The duration of the table dumped by another run (see `resume`)
+    duration: Option<Duration>,
+}
+
+/// The started table of a run, another run continues it with [`TableRun::resume`]
+/// (e.g., the table dumped by a worker thread is finished by the dumper)
+pub(crate) struct RunState {
+    info: TableInfo,
+    started: Instant,
+    bytes: u64,
+    duration: Duration,
+}
+
+impl<'a> TableRun<'a> {
+    pub(crate) fn new(events: &'a dyn Indicator) -> Self {
+        Self {
+            events,
+            started: None,
+            bytes: 0,
+            duration: None,
+        }
+    }
+
+    pub fn events(&self) -> &'a dyn Indicator {
+        self.events
+    }
+
+    /// Starts the table (again after a failed attempt)
+    pub fn start(&mut self, info: TableInfo) {
+        self.events.table_started(&info);
+        self.started = Some((info, Instant::now()));
+        self.bytes = 0;
+        self.duration = None;
+    }
+
+    /// `dumped` is the count of the table rows dumped so far, `bytes` is their size
+    pub fn progress(&mut self, dumped: u64, bytes: u64) {
+        self.bytes = bytes;
+        if let Some((info, _)) = &self.started {
+            self.events.data_progress(info, dumped, bytes);
+        }
+    }
+
+    /// Fails the started table (e.g., before the next attempt)
+    pub fn fail(&mut self, error: &Error) {
+        if let Some((info, _)) = self.started.take() {
+            self.events.table_failed(&info, error);
+        }
+    }
+
+    /// Takes the started table without finishing it (`None` if it isn't started)
+    pub(crate) fn suspend(&mut self) -> Option<RunState> {
+        let (info, started) = self.started.take()?;
+        Some(RunState {
+            info,
+            started,
+            bytes: self.bytes,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Continues the table of another run, `table_started` isn't sent again. The table is finished
+    /// with the duration of the other run.
+    pub(crate) fn resume(&mut self, state: RunState) {
+        self.started = Some((state.info, state.started));
+        self.bytes = state.bytes;
+        self.duration = Some(state.duration);
+    }
+
+    fn finish(
+        self,
+        info: impl FnOnce() -> TableInfo,
+        rows: u64,
+        outbox_events: BTreeMap<String, u64>,
+        saved_bytes: Option<u64>,
+    ) -> TableStats {
+        let (info, started) = match self.started {
+            Some(started) => started,
+            None => {
+                let info = info();
+                self.events.table_started(&info);
+                (info, Instant::now())
+            }
+        };
+        let stats = TableStats {
+            name: info.name,
+            rows,
+            bytes: self.bytes,
+            duration: self.duration.unwrap_or_else(|| started.elapsed()),
+            outbox_events,
+            saved_bytes,
+        };
+        self.events.table_finished(&stats);
+        stats
+    }
+}
+
+/// Runs the dump stages with a backend
+pub struct StagedDumper<B, I> {
+    backend: B,
+    indicator: I,
+    on_missing_table: MissingTablePolicy,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl<B, I> StagedDumper<B, I>
+where
+    B: SchemaSection + TableData + Epilogue,
+    I: 'static + Indicator + Send,
+{
+    pub fn from_backend(backend: B, indicator: I) -> Self {
+        Self {
+            backend,
+            indicator,
+            on_missing_table: MissingTablePolicy::default(),
+            checkpoint: None,
+        }
+    }
+
+    /// What to do with the tables dropped or renamed after the tables are inspected (fails by default)
+    pub fn on_missing_table(mut self, policy: MissingTablePolicy) -> Self {
+        self.on_missing_table = policy;
+        self
+    }
+
+    /// Records the dumped tables to the checkpoint, the tables dumped before it are skipped
+    /// (the output must be the output of the checkpoint)
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub(crate) fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    fn run_stage(&mut self, stage: DumpStage, connection: &mut B::Connection) -> Result<()> {
+        match stage {
+            DumpStage::PreData => self.backend.emit_schema(connection, &self.indicator),
+            DumpStage::Data => self.dump_data(connection),
+            DumpStage::PostData => {
+                self.backend.emit_epilogue(connection, &self.indicator)?;
+                match &mut self.checkpoint {
+                    Some(checkpoint) => checkpoint.finish(),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn dump_data(&mut self, connection: &mut B::Connection) -> Result<()> {
+        let mut tables = self.backend.data_tables(connection, &self.indicator)?;
+        self.resume_tables(&tables)?;
+
+        let started = Instant::now();
+        let mut summary = DumpSummary::default();
+        let result = self.dump_tables(connection, &mut tables, &mut summary);
+        let result = self.backend.end_data(result, &mut summary, &self.indicator);
+        self.finish_dump(summary, started.elapsed(), result)
+    }
+
+    // The schema must be the same as in the resumed dump, the backend gets the tables dumped before the checkpoint
+    fn resume_tables(&mut self, tables: &[(B::Table, i32)]) -> Result<()> {
+        let checkpoint = match &mut self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        checkpoint.check_schema(tables)?;
+        if !checkpoint.is_resumed() {
+            return Ok(());
+        }
+
+        self.indicator.debug_msg(&format!(
+            "Resume the dump from the checkpoint, the tables dumped before it: {}",
+            checkpoint.done_count()
+        ));
+        for (table, _) in tables {
+            if checkpoint.is_done(&table.get_full_name()) {
+                self.backend.table_resumed(table);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_resumed_table(&self, table: &B::Table) -> bool {
+        self.checkpoint
+            .as_ref()
+            .is_some_and(|c| c.is_done(&table.get_full_name()))
+    }
+
+    fn dump_tables(
+        &mut self,
+        connection: &mut B::Connection,
+        tables: &mut [(B::Table, i32)],
+        summary: &mut DumpSummary,
+    ) -> Result<()> {
+        let mut session = self
+            .backend
+            .begin_data(connection, tables, summary, &self.indicator)?;
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.begin()?;
+        }
+        self.start_dump(tables);
+
+        let all_tables_count = tables.len();
+        for (ind, (table, weight)) in tables.iter().enumerate() {
+            self.debug(format!(
+                "[{} / {}] Prepare to dump table: {} (order: {})",
+                ind + 1,
+                all_tables_count,
+                table.get_full_name(),
+                self.backend.placement(table, *weight),
+            ));
+            if self.is_resumed_table(table) {
+                self.debug(format!(
+                    "[Dumping: {}] --- SKIP (dumped before the checkpoint) ---",
+                    table.get_full_name()
+                ));
+                continue;
+            }
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint.continue_output()?;
+            }
+
+            match self.backend.skip_reason(table) {
+                Some(reason) => {
+                    self.debug(format!(
+                        "[Dumping: {}] --- SKIP{} ---",
+                        table.get_full_name(),
+                        reason
+                    ));
+                    self.backend
+                        .skip_table(&mut session, table, &self.indicator)?;
+                }
+                None => match self.dump_table(&mut session, table) {
+                    Ok(stats) => {
+                        summary.add_table(&stats);
+                        if let Some(shuffle_rows) = self
+                            .backend
+                            .settings()
+                            .find_table(&table.get_names())
+                            .and_then(|cfg| cfg.shuffle_rows.as_ref())
+                        {
+                            summary
+                                .shuffled_tables
+                                .insert(table.get_full_name(), shuffle_rows.method.name());
+                        }
+                    }
+                    Err(e) if self.backend.is_missing_table(&e) => {
+                        self.skip_missing_table(table, e)?;
+                        summary.skipped_tables.push(table.get_full_name());
+                    }
+                    Err(e) => return Err(e),
+                },
+            }
+            if let Some(checkpoint) = &mut self.checkpoint {
+                checkpoint.table_done(&table.get_full_name())?;
+            }
+        }
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint.continue_output()?;
+        }
+        if !summary.shuffled_tables.is_empty() {
+            self.debug(format!(
+                "Shuffled tables: {}",
+                summary
+                    .shuffled_tables
+                    .iter()
+                    .map(|(table, method)| format!("{} ({})", table, method))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        for (table, events) in &summary.outbox_events {
+            self.debug(format!(
+                "Outbox events of {}: {}",
+                table,
+                events
+                    .iter()
+                    .map(|(event_type, count)| format!("{} ({})", event_type, count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !summary.saved_bytes.is_empty() {
+            self.debug(format!(
+                "Saved by `only_columns` (estimated): {}",
+                summary
+                    .saved_bytes
+                    .iter()
+                    .map(|(table, bytes)| format!("{} ({})", table, HumanBytes(*bytes)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !summary.skipped_tables.is_empty() {
+            self.indicator.warning_msg(&format!(
+                "the tables dropped or renamed after the inspection are skipped: {}",
+                summary.skipped_tables.join(", ")
+            ));
+        }
+
+        self.write_log("End dumping data".into())?;
+        self.backend.end_tables(&self.indicator)
+    }
+
+    /// Dumps the table data with the indicator events, collects the table manifest
+    pub fn dump_table(
+        &mut self,
+        session: &mut B::Session<'_>,
+        table: &B::Table,
+    ) -> Result<TableStats> {
+        let mut run = TableRun::new(&self.indicator);
+        match self.backend.stream_table(session, table, &mut run) {
+            Ok(output) => {
+                if let (Some(manifest), Some(table_manifest)) =
+                    (self.backend.manifest(), output.manifest)
+                {
+                    if let Ok(mut manifest) = manifest.lock() {
+                        manifest
+                            .tables
+                            .insert(table.get_full_name(), table_manifest);
+                    }
+                }
+                Ok(run.finish(
+                    || self.backend.table_info(table),
+                    output.rows,
+                    output.outbox_events,
+                    output.saved_bytes,
+                ))
+            }
+            Err(e) => {
+                run.fail(&e);
+                Err(e)
+            }
+        }
+    }
+
+    // The table is skipped with `MissingTablePolicy::Skip`, unless the backend can't continue without it
+    fn skip_missing_table(&self, table: &B::Table, error: Error) -> Result<()> {
+        if self.on_missing_table == MissingTablePolicy::Fail {
+            return Err(anyhow!(
+                "{} (use `--on-missing-table skip` to skip such tables)",
+                error
+            ));
+        }
+        self.backend.check_skip(&error)?;
+
+        self.debug(format!(
+            "[Dumping: {}] --- SKIP (dropped or renamed after the inspection) ---",
+            table.get_full_name()
+        ));
+        Ok(())
+    }
+
+    /// Sends the tables with data to the indicator
+    pub fn start_dump(&mut self, tables: &[(B::Table, i32)]) {
+        let infos: Vec<_> = tables
+            .iter()
+            .filter(|(table, _)| {
+                self.backend.skip_reason(table).is_none() && !self.is_resumed_table(table)
+            })
+            .map(|(table, _)| self.backend.table_info(table))
+            .collect();
+        self.indicator.dump_started(&infos);
+    }
+
+    pub fn finish_dump(
+        &self,
+        mut summary: DumpSummary,
+        duration: Duration,
+        result: Result<()>,
+    ) -> Result<()> {
+        summary.duration = duration;
+        summary.error = result.as_ref().err().map(|e| e.to_string());
+        self.indicator.dump_finished(&summary);
+        result
+    }
+
+    pub(crate) fn parts(&mut self) -> (&mut B, &I) {
+        (&mut self.backend, &self.indicator)
+    }
+}
+
+impl<B, I> Dumper for StagedDumper<B, I>
+where
+    B: SchemaSection + TableData + Epilogue,
+    I: 'static + Indicator + Send,
+{
+    type Table = B::Table;
+    type Connection = B::Connection;
+    type SchemaInspector = B::SchemaInspector;
+
+    fn pre_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        self.run_stage(DumpStage::PreData, connection)
+    }
+
+    fn data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        self.run_stage(DumpStage::Data, connection)
+    }
+
+    fn post_data(&mut self, connection: &mut Self::Connection) -> Result<()> {
+        self.run_stage(DumpStage::PostData, connection)
+    }
+
+    fn schema_inspector(&self) -> Self::SchemaInspector {
+        self.backend.schema_inspector()
+    }
+
+    fn settings(&mut self) -> Settings {
+        self.backend.settings().clone()
+    }
+
+    fn write_log(&mut self, message: String) -> Result<()> {
+        self.backend.write_log(message)
+    }
+
+    fn debug(&self, message: String) {
+        self.indicator.debug_msg(message.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ColumnData;
+    use anyhow::bail;
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+    struct FakeTable(String);
+
+    struct FakeColumn;
+
+    impl ColumnData<()> for FakeColumn {
+        fn position(&self) -> usize {
+            0
+        }
+
+        fn name(&self) -> &str {
+            ""
+        }
+
+        fn inner_kind(&self) -> Option<()> {
+            None
+        }
+    }
+
+    impl Table<()> for FakeTable {
+        type Column = FakeColumn;
+        type Row = ();
+
+        fn get_name(&self) -> String {
+            self.0.clone()
+        }
+
+        fn get_full_name(&self) -> String {
+            format!("public.{}", self.0)
+        }
+
+        fn get_names(&self) -> Vec<String> {
+            vec![self.get_full_name(), self.get_name()]
+        }
+
+        fn get_columns(&self) -> Vec<Self::Column> {
+            vec![]
+        }
+
+        fn get_columns_names(&self) -> Vec<String> {
+            vec![]
+        }
+
+        fn get_size(&self) -> i64 {
+            0
+        }
+
+        fn get_column_indexes(&self) -> &HashMap<String, usize> {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Clone)]
+    struct FakeInspector;
+
+    impl SchemaInspector for FakeInspector {
+        type Type = ();
+        type Connection = ();
+        type Table = FakeTable;
+        type Column = FakeColumn;
+
+        fn get_tables(&self, _connection: &mut ()) -> Result<Vec<FakeTable>> {
+            Ok(vec![])
+        }
+
+        fn get_table_size(&self, _connection: &mut (), _table: &FakeTable) -> Result<i64> {
+            Ok(0)
+        }
+
+        fn get_dependencies(
+            &self,
+            _connection: &mut (),
+            _table: &FakeTable,
+        ) -> Result<Vec<FakeTable>> {
+            Ok(vec![])
+        }
+
+        fn get_columns(&self, _connection: &mut (), _table: &FakeTable) -> Result<Vec<FakeColumn>> {
+            Ok(vec![])
+        }
+    }
+
+    /// The tables are `(name, rows)`, the table `dropped` is missing, the table `skipped` is skipped
+    struct FakeBackend {
+        settings: Settings,
+        tables: Vec<(&'static str, u64)>,
+        output: Vec<String>,
+    }
+
+    impl FakeBackend {
+        fn new(tables: Vec<(&'static str, u64)>) -> Self {
+            Self {
+                settings: Settings::from_yaml("tables: []").unwrap(),
+                tables,
+                output: vec![],
+            }
+        }
+    }
+
+    impl Backend for FakeBackend {
+        type Connection = ();
+        type Type = ();
+        type Table = FakeTable;
+        type SchemaInspector = FakeInspector;
+
+        fn settings(&self) -> &Settings {
+            &self.settings
+        }
+
+        fn schema_inspector(&self) -> FakeInspector {
+            FakeInspector
+        }
+
+        fn manifest(&self) -> Option<&Arc<Mutex<Manifest>>> {
+            None
+        }
+
+        fn write_log(&mut self, message: String) -> Result<()> {
+            self.output.push(format!("log: {}", message));
+            Ok(())
+        }
+    }
+
+    impl SchemaSection for FakeBackend {
+        fn emit_schema(&mut self, _connection: &mut (), _events: &dyn Indicator) -> Result<()> {
+            self.output.push("schema".to_string());
+            Ok(())
+        }
+    }
+
+    impl TableData for FakeBackend {
+        type Session<'c> = u64;
+
+        fn data_tables(
+            &mut self,
+            _connection: &mut (),
+            _events: &dyn Indicator,
+        ) -> Result<Vec<(FakeTable, i32)>> {
+            Ok(self
+                .tables
+                .iter()
+                .map(|(name, _)| (FakeTable(name.to_string()), 0))
+                .collect())
+        }
+
+        fn begin_data(
+            &mut self,
+            _connection: &mut (),
+            _tables: &mut [(FakeTable, i32)],
+            _summary: &mut DumpSummary,
+            _events: &dyn Indicator,
+        ) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn placement(&self, _table: &FakeTable, weight: i32) -> String {
+            weight.to_string()
+        }
+
+        fn skip_reason(&self, table: &FakeTable) -> Option<String> {
+            (table.0 == "skipped").then(String::new)
+        }
+
+        fn skip_table(
+            &mut self,
+            _session: &mut u64,
+            table: &FakeTable,
+            events: &dyn Indicator,
+        ) -> Result<()> {
+            self.output.push(format!("skip: {}", table.0));
+            events.table_skipped(&table.get_full_name(), "");
+            Ok(())
+        }
+
+        fn table_info(&self, table: &FakeTable) -> TableInfo {
+            TableInfo {
+                name: table.get_full_name(),
+                rows: 0,
+            }
+        }
+
+        fn stream_table(
+            &mut self,
+            session: &mut u64,
+            table: &FakeTable,
+            run: &mut TableRun<'_>,
+        ) -> Result<TableOutput> {
+            if table.0 == "dropped" {
+                bail!("missing {}", table.0);
+            }
+            let rows = self.tables.iter().find(|(t, _)| *t == table.0).unwrap().1;
+            run.start(self.table_info(table));
+            run.progress(rows, rows * 10);
+            *session += rows;
+            self.output
+                .push(format!("data: {} (total {})", table.0, session));
+            Ok(TableOutput {
+                rows,
+                manifest: None,
+                outbox_events: BTreeMap::new(),
+                saved_bytes: None,
+            })
+        }
+
+        fn is_missing_table(&self, error: &Error) -> bool {
+            error.to_string().starts_with("missing")
+        }
+    }
+
+    impl Epilogue for FakeBackend {
+        fn emit_epilogue(&mut self, _connection: &mut (), _events: &dyn Indicator) -> Result<()> {
+            self.output.push("epilogue".to_string());
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    impl Events {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl Indicator for Events {
+        fn dump_started(&self, tables: &[TableInfo]) {
+            let names: Vec<_> = tables.iter().map(|t| t.name.as_str()).collect();
+            self.push(format!("started: {}", names.join(", ")));
+        }
+
+        fn table_started(&self, table: &TableInfo) {
+            self.push(format!("table started: {}", table.name));
+        }
+
+        fn rows_progress(&self, table: &TableInfo, dumped: u64) {
+            self.push(format!("progress: {} {}", table.name, dumped));
+        }
+
+        fn table_finished(&self, stats: &TableStats) {
+            self.push(format!("table finished: {} {}", stats.name, stats.rows));
+        }
+
+        fn table_failed(&self, table: &TableInfo, _error: &Error) {
+            self.push(format!("table failed: {}", table.name));
+        }
+
+        fn table_skipped(&self, table: &str, reason: &str) {
+            self.push(format!("table skipped: {} {:?}", table, reason));
+        }
+
+        fn dump_finished(&self, summary: &DumpSummary) {
+            self.push(format!(
+                "finished: {} tables, {} rows, skipped: {:?}, error: {:?}",
+                summary.tables, summary.rows, summary.skipped_tables, summary.error
+            ));
+        }
+
+        fn warning_msg(&self, msg: &str) {
+            self.push(format!("warning: {}", msg));
+        }
+    }
+
+    fn dumper(tables: Vec<(&'static str, u64)>) -> (StagedDumper<FakeBackend, Events>, Events) {
+        let events = Events::default();
+        (
+            StagedDumper::from_backend(FakeBackend::new(tables), events.clone()),
+            events,
+        )
+    }
+
+    #[test]
+    fn stage_names() {
+        assert_eq!(DumpStage::PreData.to_string(), "pre-data");
+        assert_eq!(DumpStage::Data.name(), "data");
+        assert_eq!(DumpStage::PostData.name(), "post-data");
+    }
+
+    #[test]
+    fn parse_policy() {
+        assert_eq!("skip".parse(), Ok(MissingTablePolicy::Skip));
+        assert_eq!("fail".parse(), Ok(MissingTablePolicy::Fail));
+        assert_eq!(
+            "ignore".parse::<MissingTablePolicy>(),
+            Err("Unknown missing table policy `ignore` (skip or fail)".to_string())
+        );
+    }
+
+    #[test]
+    fn stages() {
+        let (mut d, events) = dumper(vec![("users", 2), ("skipped", 5), ("orders", 3)]);
+        d.dump(&mut ()).unwrap();
+
+        assert_eq!(
+            d.backend.output,
+            [
+                "schema",
+                "data: users (total 2)",
+                "skip: skipped",
+                "data: orders (total 5)",
+                "log: End dumping data",
+                "epilogue"
+            ]
+        );
+        assert_eq!(
+            events.take(),
+            [
+                "started: public.users, public.orders",
+                "table started: public.users",
+                "progress: public.users 2",
+                "table finished: public.users 2",
+                "table skipped: public.skipped \"\"",
+                "table started: public.orders",
+                "progress: public.orders 3",
+                "table finished: public.orders 3",
+                "finished: 2 tables, 5 rows, skipped: [], error: None"
+            ]
+        );
+    }
+
+    #[test]
+    fn resumed_run() {
+        let events = Events::default();
+        let mut worker = TableRun::new(&events);
+        worker.start(TableInfo {
+            name: "public.users".to_string(),
+            rows: 2,
+        });
+        worker.progress(2, 20);
+        let state = worker.suspend().unwrap();
+        assert!(worker.suspend().is_none());
+
+        let mut run = TableRun::new(&events);
+        run.resume(state);
+        let stats = run.finish(|| unreachable!(), 2, BTreeMap::new(), None);
+        assert_eq!(stats.bytes, 20);
+        assert_eq!(
+            events.take(),
+            [
+                "table started: public.users",
+                "progress: public.users 2",
+                "table finished: public.users 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_tables() {
+        let tables = vec![("users", 2), ("dropped", 0), ("orders", 3)];
+
+        let (mut d, events) = dumper(tables.clone());
+        let error = d.data(&mut ()).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "missing dropped (use `--on-missing-table skip` to skip such tables)"
+        );
+        assert_eq!(
+            events.take().last().unwrap(),
+            &format!(
+                "finished: 1 tables, 2 rows, skipped: [], error: Some({:?})",
+                error
+            )
+        );
+
+        let (d, events) = dumper(tables);
+        let mut d = d.on_missing_table(MissingTablePolicy::Skip);
+        d.data(&mut ()).unwrap();
+        let events = events.take();
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                "warning: the tables dropped or renamed after the inspection are skipped: public.dropped",
+                "finished: 2 tables, 5 rows, skipped: [\"public.dropped\"], error: None"
+            ]
+        );
+    }
+}
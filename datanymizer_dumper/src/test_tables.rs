@@ -0,0 +1,51 @@
+//! The tables of the unit tests: the columns are numbered in the order of the list
+
+use crate::{
+    mysql::{column::MySqlColumn, table::MySqlTable},
+    postgres::{column::PgColumn, table::PgTable},
+};
+
+/// The columns by their names and data types
+pub fn pg_columns(columns: &[(&str, &str)]) -> Vec<PgColumn> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, data_type))| PgColumn {
+            position: i as i32 + 1,
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            inner_type: None,
+            max_length: None,
+        })
+        .collect()
+}
+
+pub fn pg_table(schema: &str, name: &str, columns: &[(&str, &str)]) -> PgTable {
+    let mut table = PgTable::new(name.to_string(), schema.to_string());
+    table.set_columns(pg_columns(columns));
+    table
+}
+
+/// The columns by their names and column types (the data type is the column type without modifiers)
+pub fn mysql_columns(columns: &[(&str, &str)]) -> Vec<MySqlColumn> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, column_type))| MySqlColumn {
+            position: i as u32 + 1,
+            name: name.to_string(),
+            data_type: column_type
+                .split(['(', ' '])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            column_type: column_type.to_string(),
+        })
+        .collect()
+}
+
+pub fn mysql_table(database: &str, name: &str, columns: &[(&str, &str)]) -> MySqlTable {
+    let mut table = MySqlTable::new(name.to_string(), database.to_string());
+    table.set_columns(mysql_columns(columns));
+    table
+}
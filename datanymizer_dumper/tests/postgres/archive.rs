@@ -0,0 +1,91 @@
+use super::helpers;
+
+use datanymizer_dumper::{indicator::SilentIndicator, postgres::archive};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs, process::Command};
+
+const SCHEMA: &str = r#"
+    CREATE TABLE users (id int PRIMARY KEY, "E-mail" text, name text, tags text[]);
+    CREATE TABLE notes (id int PRIMARY KEY, user_id int REFERENCES users, body text);
+    INSERT INTO users
+        SELECT i, 'user' || i || '@corp.com', 'name' || i, ARRAY['a', 'b ' || i] FROM generate_series(1, 200) i;
+    INSERT INTO notes SELECT i, i, E'line 1\nline\t2 ' || i FROM generate_series(1, 200) i;
+    CREATE INDEX users_name_idx ON users (name);
+"#;
+
+const CONFIG: &str = r#"
+tables:
+  - name: users
+    rules:
+      E-mail:
+        template:
+          format: "user{{ prev.id }}@example.com"
+      name:
+        first_name: {}
+"#;
+
+#[test]
+fn transform_pg_dump_archive() {
+    let src_url = helpers::empty_database_url("archive_src");
+    let mut client = Client::connect(src_url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let src_path = env::temp_dir().join("datanymizer_test_archive_src.dump");
+    let dst_path = env::temp_dir().join("datanymizer_test_archive_dst.dump");
+    let status = Command::new(helpers::pg_dump_path())
+        .args(["-Fc", "-f"])
+        .arg(&src_path)
+        .arg(src_url.as_str())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let engine = Engine::new(Settings::from_yaml(CONFIG).unwrap());
+    let stats = archive::transform_archive(
+        &engine,
+        fs::File::open(&src_path).unwrap(),
+        fs::File::create(&dst_path).unwrap(),
+        &SilentIndicator,
+    )
+    .unwrap();
+    assert_eq!(stats.tables, 2);
+    assert_eq!(stats.transformed_tables, 1);
+    assert_eq!(stats.transformed_rows, 200);
+
+    let dst_url = helpers::empty_database_url("archive_dst");
+    helpers::restore_archive(&dst_url, &dst_path);
+    fs::remove_file(src_path).unwrap();
+    fs::remove_file(dst_path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let rows = client
+        .query(
+            r#"SELECT id, "E-mail", name, tags FROM users ORDER BY id"#,
+            &[],
+        )
+        .unwrap();
+    assert_eq!(rows.len(), 200);
+    for row in rows {
+        let id: i32 = row.get(0);
+        let email: String = row.get(1);
+        let name: String = row.get(2);
+        let tags: Vec<String> = row.get(3);
+        assert_eq!(email, format!("user{}@example.com", id));
+        assert_ne!(name, format!("name{}", id));
+        assert_eq!(tags, vec!["a".to_string(), format!("b {}", id)]);
+    }
+    // the other tables and the schema objects are restored as is
+    let body: String = client
+        .query_one("SELECT body FROM notes WHERE id = 7", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(body, "line 1\nline\t2 7");
+    let indexes = client
+        .query(
+            "SELECT 1 FROM pg_indexes WHERE indexname = 'users_name_idx'",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(indexes.len(), 1);
+}
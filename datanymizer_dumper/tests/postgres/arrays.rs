@@ -0,0 +1,125 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs, path::Path};
+use url::Url;
+
+const SCHEMA: &str = r#"
+    CREATE TABLE users (
+        id int PRIMARY KEY,
+        alt_emails text[],
+        phones varchar(20)[],
+        codes int[]
+    );
+
+    INSERT INTO users VALUES
+        (1, '{john@mail.com,NULL,"j,d@mail.com"}', '{555-01,555-02}', '{1,2,3}'),
+        (2, '{"a}b@mail.com","quote\"d@mail.com","back\\slash@mail.com"}', '{}', '{NULL,7}'),
+        (3, NULL, NULL, NULL),
+        (4, '{}', '{" 555-03 "}', '{}');
+"#;
+
+const CONFIG: &str = r#"
+    tables:
+      - name: users
+        rules:
+          alt_emails:
+            template:
+              format: "user{{ _0 | length }}@example.com"
+          phones:
+            template:
+              format: "{{ _0 | trim }}-00"
+          codes:
+            template:
+              format: "{{ _0 }}0"
+"#;
+
+fn dump(url: &Url, config: &str, path: &Path) -> anyhow::Result<()> {
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper.dump(&mut Connection::new(
+        Client::connect(url.as_str(), NoTls).unwrap(),
+        url.clone(),
+    ))
+}
+
+type Row = (
+    i32,
+    Option<Vec<Option<String>>>,
+    Option<Vec<Option<String>>>,
+    Option<Vec<Option<i32>>>,
+);
+
+#[test]
+fn array_elements() {
+    let url = helpers::empty_database_url("arrays");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let path = env::temp_dir().join("datanymizer_test_arrays.sql");
+    dump(&url, CONFIG, &path).unwrap();
+
+    let dst_url = helpers::empty_database_url("arrays_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let rows: Vec<Row> = dst
+        .query(
+            "SELECT id, alt_emails, phones::text[], codes FROM users ORDER BY id",
+            &[],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect();
+
+    let strings =
+        |items: &[Option<&str>]| Some(items.iter().map(|s| s.map(String::from)).collect());
+    assert_eq!(
+        rows,
+        vec![
+            (
+                1,
+                strings(&[Some("user13@example.com"), None, Some("user12@example.com")]),
+                strings(&[Some("555-01-00"), Some("555-02-00")]),
+                Some(vec![Some(10), Some(20), Some(30)]),
+            ),
+            (
+                2,
+                strings(&[
+                    Some("user12@example.com"),
+                    Some("user16@example.com"),
+                    Some("user20@example.com"),
+                ]),
+                Some(vec![]),
+                Some(vec![None, Some(70)]),
+            ),
+            (3, None, None, None),
+            (4, Some(vec![]), strings(&[Some("555-03-00")]), Some(vec![])),
+        ]
+    );
+    fs::remove_file(&path).unwrap();
+
+    client
+        .batch_execute("INSERT INTO users VALUES (5, '{{a,b},{c,d}}', NULL, NULL)")
+        .unwrap();
+    let error = dump(&url, CONFIG, &path).unwrap_err().to_string();
+    assert!(
+        error.contains("multidimensional arrays are not supported by the element rules"),
+        "{}",
+        error
+    );
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,155 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    checkpoint::Checkpoint,
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use url::Url;
+
+// `users` is dumped before `orders` (by the foreign key)
+const SCHEMA: &str = r#"
+    CREATE TABLE users (id int PRIMARY KEY, email text);
+    CREATE TABLE orders (id int, user_id int REFERENCES users, details jsonb);
+    INSERT INTO users SELECT i, 'user' || i || '@corp.com' FROM generate_series(1, 10) AS i;
+    INSERT INTO orders SELECT i, i, '{"card": "4111"}' FROM generate_series(1, 10) AS i;
+    -- the dump fails on this row
+    UPDATE orders SET details = '{}' WHERE id = 5;
+"#;
+
+// The same config is used for both runs
+const CONFIG: &str = r#"
+tables:
+  - name: users
+    rules:
+      email:
+        template:
+          format: "user{{ prev.id }}@example.com"
+  - name: orders
+    rules:
+      details:
+        json:
+          fields:
+            - path: /card
+              rule:
+                template:
+                  format: "0000"
+          on_missing: error
+"#;
+
+fn paths(name: &str) -> (PathBuf, PathBuf) {
+    let dir = env::temp_dir();
+    (
+        dir.join(format!("datanymizer_test_{}.checkpoint", name)),
+        dir.join(format!("datanymizer_test_{}.sql", name)),
+    )
+}
+
+fn dump(url: &Url, checkpoint: &Path, output: &Path) -> anyhow::Result<()> {
+    let settings = Settings::from_yaml(CONFIG).unwrap();
+    let (checkpoint, output) =
+        Checkpoint::open(checkpoint, output, &settings.resolved_config(), "")?;
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        output,
+        Arc::new(SilentIndicator),
+        vec![],
+    )?
+    .with_checkpoint(checkpoint);
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper.dump(&mut Connection::new(client, url.clone()))
+}
+
+fn interrupted_dump(name: &str) -> (Url, PathBuf, PathBuf) {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let (checkpoint, output) = paths(name);
+    let _ = fs::remove_file(&checkpoint);
+    let error = dump(&url, &checkpoint, &output).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("The path `/card` is missing in the JSON value"));
+
+    let lines: Vec<String> = fs::read_to_string(&checkpoint)
+        .unwrap()
+        .lines()
+        .map(|l| l.to_string())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with(r#"{"table":"public.users","offset":"#));
+
+    (url, checkpoint, output)
+}
+
+#[test]
+fn resume() {
+    let (url, checkpoint, output) = interrupted_dump("checkpoint_resume");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client
+        .batch_execute(r#"UPDATE orders SET details = '{"card": "4111"}' WHERE id = 5"#)
+        .unwrap();
+
+    dump(&url, &checkpoint, &output).unwrap();
+    assert!(!checkpoint.exists());
+
+    // the schema and the data are written once
+    let sql = fs::read_to_string(&output).unwrap();
+    for statement in [
+        "CREATE TABLE public.users",
+        "--- Start dumping data",
+        r#"COPY "public"."users""#,
+        r#"COPY "public"."orders""#,
+        "ALTER TABLE ONLY public.orders",
+    ] {
+        assert_eq!(sql.matches(statement).count(), 1, "{}", statement);
+    }
+
+    let dst_url = helpers::empty_database_url("checkpoint_resume_restore");
+    helpers::restore(&dst_url, &output);
+    fs::remove_file(output).unwrap();
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let counts: (i64, i64) = {
+        let row = client
+            .query_one(
+                "SELECT (SELECT count(*) FROM users WHERE email LIKE '%@example.com'), \
+                (SELECT count(*) FROM orders WHERE details->>'card' = '0000')",
+                &[],
+            )
+            .unwrap();
+        (row.get(0), row.get(1))
+    };
+    assert_eq!(counts, (10, 10));
+}
+
+#[test]
+fn changed_schema() {
+    let (url, checkpoint, output) = interrupted_dump("checkpoint_changed_schema");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client
+        .batch_execute("ALTER TABLE users ADD COLUMN phone text")
+        .unwrap();
+
+    let error = dump(&url, &checkpoint, &output).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "The tables or columns are changed since the checkpoint {} is written, \
+            remove the checkpoint to dump from the start",
+            checkpoint.display()
+        )
+    );
+    fs::remove_file(checkpoint).unwrap();
+    fs::remove_file(output).unwrap();
+}
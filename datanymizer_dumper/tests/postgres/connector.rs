@@ -1,6 +1,11 @@
 use super::helpers;
 
-use datanymizer_dumper::postgres::connector::Connector;
+use datanymizer_dumper::postgres::{
+    conn_url,
+    connector::{Connection, Connector, SessionTimeouts},
+    replica::ReplicaSettings,
+};
+use std::{collections::BTreeMap, thread, time::Duration};
 
 fn test_connection(tls_mode: &str) {
     let mut database_url = helpers::src_database_url();
@@ -26,3 +31,173 @@ fn connect() {
     // requires TLS support at the test server, it is not yet implemented in the `ci.yml`
     // test_connection("require");
 }
+
+#[test]
+fn server_version() {
+    let connector = Connector::new(helpers::src_database_url(), false, false);
+    let mut connection = connector.connect().unwrap();
+    let compatibility = connection.compatibility().unwrap();
+
+    let version_num: String = connection
+        .client
+        .query_one("SHOW server_version_num", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(
+        compatibility.server_version.major,
+        version_num.parse::<u32>().unwrap() / 10_000
+    );
+}
+
+#[test]
+fn session_settings() {
+    let session = BTreeMap::from([
+        (
+            "application_name".to_string(),
+            "datanymizer_test".to_string(),
+        ),
+        ("search_path".to_string(), "public, pg_catalog".to_string()),
+        ("work_mem".to_string(), "64MB".to_string()),
+    ]);
+    let connector = Connector::new(helpers::src_database_url(), false, false)
+        .with_session(&session)
+        .unwrap();
+    let settings = connector.connect().unwrap().session_settings().unwrap();
+    for (name, value) in &session {
+        assert_eq!(settings.get(name), Some(value));
+    }
+
+    // unknown settings are refused by the server
+    let session = BTreeMap::from([("unknown_setting".to_string(), "1".to_string())]);
+    let result = Connector::new(helpers::src_database_url(), false, false)
+        .with_session(&session)
+        .unwrap()
+        .connect();
+    let err = result.err().unwrap();
+    assert!(
+        format!("{:#}", err).contains("unrecognized configuration parameter \"unknown_setting\"")
+    );
+}
+
+#[test]
+fn timeouts() {
+    let connector = Connector::new(helpers::src_database_url(), false, false)
+        .with_timeouts(SessionTimeouts {
+            statement_timeout: Some(Duration::from_secs(1)),
+            lock_timeout: Some(Duration::from_millis(1500)),
+            idle_in_transaction_session_timeout: None,
+        })
+        .with_keepalives_idle(Some(Duration::from_secs(30)));
+    let mut connection = connector.connect().unwrap();
+    let show = |connection: &mut Connection, name: &str| -> String {
+        connection
+            .client
+            .query_one(format!("SHOW {}", name).as_str(), &[])
+            .unwrap()
+            .get(0)
+    };
+    assert_eq!(show(&mut connection, "statement_timeout"), "1s");
+    assert_eq!(show(&mut connection, "lock_timeout"), "1500ms");
+    assert_eq!(
+        show(&mut connection, "idle_in_transaction_session_timeout"),
+        "0"
+    );
+
+    // the timeouts are set again after the session options for replicas and after reconnecting
+    ReplicaSettings::prepare_session(&mut connection.client).unwrap();
+    connection.apply_timeouts().unwrap();
+    assert_eq!(show(&mut connection, "statement_timeout"), "1s");
+    connection.reconnect().unwrap();
+    assert_eq!(show(&mut connection, "lock_timeout"), "1500ms");
+
+    let err = connection
+        .client
+        .batch_execute("SELECT pg_sleep(3)")
+        .unwrap_err();
+    assert!(err.to_string().contains("statement timeout"), "{}", err);
+}
+
+#[test]
+fn multiple_hosts() {
+    let src_url = helpers::src_database_url();
+    let host = format!(
+        "{}:{}",
+        src_url.host_str().unwrap(),
+        src_url.port().unwrap_or(5432)
+    );
+    // nothing listens on the port 1
+    let url = |attrs: &str| {
+        let mut url = src_url.clone();
+        url.set_host(Some("127.0.0.1")).unwrap();
+        url.set_port(Some(1)).unwrap();
+        let s = url
+            .as_str()
+            .replacen("127.0.0.1:1", &format!("127.0.0.1:1,{}", host), 1);
+        conn_url::parse(&format!("{}?target_session_attrs={}", s, attrs)).unwrap()
+    };
+
+    let connection = Connector::new(url("read-write"), false, false)
+        .connect()
+        .unwrap();
+    assert_eq!(conn_url::host_label(&connection.url), host);
+    assert!(connection.url.query().is_none());
+
+    let err = Connector::new(url("read-only"), false, false)
+        .connect()
+        .err()
+        .unwrap();
+    let message = err.to_string();
+    assert!(message.starts_with("No server matches target_session_attrs=read-only (127.0.0.1:1: "));
+    assert!(message.ends_with(&format!("; {} is not read-only)", host)));
+}
+
+#[test]
+fn advisory_lock() {
+    // a database of its own: the dumps of the other tests take the lock of the source database
+    let connector = Connector::new(helpers::empty_database_url("advisory_lock"), false, false);
+    let mut first = connector.connect().unwrap();
+    let mut second = connector.connect().unwrap();
+    first.acquire_advisory_lock(false).unwrap();
+
+    let pid: i32 = first
+        .client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .unwrap()
+        .get(0);
+    let err = second.acquire_advisory_lock(false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Another dump of this database is running (the server process {}): \
+            use --wait-advisory-lock to wait for it or --no-advisory-lock to dump anyway",
+            pid
+        )
+    );
+
+    // the lock is taken again after reconnecting
+    first.reconnect().unwrap();
+    assert!(second.acquire_advisory_lock(false).is_err());
+
+    first.release_advisory_lock().unwrap();
+    second.acquire_advisory_lock(false).unwrap();
+    // the lock is released when the session ends (the server process exits asynchronously)
+    drop(second);
+    let mut attempts = 0;
+    while first.acquire_advisory_lock(false).is_err() {
+        attempts += 1;
+        assert!(attempts < 50);
+        thread::sleep(Duration::from_millis(100));
+    }
+    first.release_advisory_lock().unwrap();
+}
+
+#[test]
+fn catalog_qps() {
+    // a database of its own: the source database is restored by the first test that needs it
+    let connector = Connector::new(helpers::empty_database_url("catalog_qps"), false, false)
+        .with_catalog_qps(Some(2));
+    let mut connection = connector.connect().unwrap();
+    // the catalog is fetched with 6 queries (2 at once, then 2 per second)
+    connection.catalog().unwrap();
+    assert!(connection.catalog_throttle().waited() >= Duration::from_millis(1500));
+}
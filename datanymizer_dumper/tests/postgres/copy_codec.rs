@@ -0,0 +1,117 @@
+use super::helpers;
+
+use datanymizer_dumper::postgres::copy_codec::{
+    escape, fields, parse_array, parse_composite, unescape, unescape_str, ArrayItem,
+};
+use postgres::{Client, NoTls};
+use std::io::Read;
+
+/// Adversarial values: every ASCII character (except NUL), escapes, quotes, delimiters and NULL-like strings
+fn values() -> Vec<String> {
+    let mut values: Vec<String> = (1u8..128).map(|b| format!("<{}>", b as char)).collect();
+    values.push((1u8..128).map(char::from).collect());
+    values.extend(
+        [
+            "",
+            " ",
+            r"\N",
+            r"\\N",
+            "NULL",
+            "null",
+            r"\",
+            r"\\",
+            "\"",
+            "\"\"",
+            "{}",
+            "()",
+            "a,b",
+            "(x)",
+            "{a}",
+            "tab\there",
+            "cr\r\nlf",
+            "ünïcødé 😀",
+            " padded ",
+            "\\x41",
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    values
+}
+
+#[test]
+fn round_trips() {
+    let url = helpers::empty_database_url("copy_codec");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS adversarial;
+            DROP TYPE IF EXISTS adversarial_pair;
+            CREATE TYPE adversarial_pair AS (name text, note text);
+            CREATE TABLE adversarial (id int, t text, a text[], p adversarial_pair);",
+        )
+        .unwrap();
+
+    let values = values();
+    for (i, value) in values.iter().enumerate() {
+        let next = &values[(i + 1) % values.len()];
+        client
+            .execute(
+                "INSERT INTO adversarial VALUES ($1, $2, ARRAY[$2, NULL, $3], ROW($2, $3))",
+                &[&(i as i32), value, next],
+            )
+            .unwrap();
+    }
+    client
+        .execute("INSERT INTO adversarial VALUES (-1, NULL, NULL, NULL)", &[])
+        .unwrap();
+
+    let mut output = vec![];
+    client
+        .copy_out("COPY (SELECT * FROM adversarial ORDER BY id) TO STDOUT")
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    let lines: Vec<_> = output
+        .split(|b| *b == b'\n')
+        .filter(|l| !l.is_empty())
+        .collect();
+    assert_eq!(lines.len(), values.len() + 1);
+
+    let null_row: Vec<_> = fields(lines[0]).map(unescape).collect();
+    assert_eq!(null_row[1..], [None, None, None]);
+
+    for (i, line) in lines[1..].iter().enumerate() {
+        let (value, next) = (&values[i], &values[(i + 1) % values.len()]);
+        let row: Vec<_> = fields(line).collect();
+        assert_eq!(row.len(), 4);
+
+        // the server writes the canonical form
+        for field in &row {
+            assert_eq!(escape(&unescape(field).unwrap()), *field);
+        }
+
+        let t = unescape_str(row[1]).unwrap().unwrap();
+        assert_eq!(&t, value);
+
+        let a = unescape_str(row[2]).unwrap().unwrap();
+        let array = parse_array(&a).unwrap();
+        assert_eq!(
+            array.items,
+            vec![
+                ArrayItem::Value(value.into()),
+                ArrayItem::Null,
+                ArrayItem::Value(next.into())
+            ]
+        );
+        assert_eq!(array.to_string(), a);
+
+        let p = unescape_str(row[3]).unwrap().unwrap();
+        let composite = parse_composite(&p).unwrap();
+        assert_eq!(
+            composite.fields,
+            vec![Some(value.into()), Some(next.into())]
+        );
+        assert_eq!(composite.to_string(), p);
+    }
+}
@@ -0,0 +1,192 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{
+        connector::Connection,
+        dumper::{PgDumper, POST_DATA_MARKER},
+    },
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE users (
+        id int PRIMARY KEY,
+        email text NOT NULL,
+        primary_address_id int NOT NULL
+    );
+    CREATE TABLE addresses (id int PRIMARY KEY, user_id int NOT NULL, city text);
+    -- not a cycle
+    CREATE TABLE orders (id int PRIMARY KEY, user_id int NOT NULL REFERENCES users);
+    INSERT INTO users VALUES (1, 'alice@corp.com', 10), (2, 'bob@corp.com', 12);
+    INSERT INTO addresses VALUES (10, 1, 'Paris'), (11, 1, 'Rome'), (12, 2, 'Oslo');
+    INSERT INTO orders VALUES (100, 1), (101, 2);
+";
+
+const ROWS_QUERY: &str = "SELECT u.id, u.primary_address_id, a.id, a.user_id, a.city
+                          FROM users u JOIN addresses a ON a.user_id = u.id
+                          ORDER BY a.id";
+
+type Row = (i32, i32, i32, i32, Option<String>);
+
+fn rows(client: &mut Client) -> Vec<Row> {
+    client
+        .query(ROWS_QUERY, &[])
+        .unwrap()
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2), r.get(3), r.get(4)))
+        .collect()
+}
+
+fn source(name: &str, deferrable: &str) -> Url {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+    client
+        .batch_execute(&format!(
+            "ALTER TABLE users ADD CONSTRAINT users_address_fk
+                FOREIGN KEY (primary_address_id) REFERENCES addresses {d};
+            ALTER TABLE addresses ADD CONSTRAINT addresses_user_fk
+                FOREIGN KEY (user_id) REFERENCES users {d};",
+            d = deferrable
+        ))
+        .unwrap();
+    url
+}
+
+fn config(strategy: &str) -> String {
+    format!(
+        "
+cycle_strategy: {}
+tables:
+  - name: users
+    rules:
+      email:
+        email: {{}}
+",
+        strategy
+    )
+}
+
+/// Dumps the schema and the data to separate files
+fn dump(url: &Url, strategy: &str) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let schema_path = env::temp_dir().join(format!("datanymizer_test_cycles_{}.sql", strategy));
+    let data_path = env::temp_dir().join(format!("datanymizer_test_cycles_{}_data.sql", strategy));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(&config(strategy)).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&schema_path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_data_writer(fs::File::create(&data_path).unwrap());
+    dumper.dump(&mut Connection::new(
+        Client::connect(url.as_str(), NoTls).unwrap(),
+        url.clone(),
+    ))?;
+    Ok((schema_path, data_path))
+}
+
+/// Restores the whole schema (with the foreign keys) and then the data
+fn restore(name: &str, schema_path: &Path, data_path: &Path) -> Client {
+    let url = helpers::empty_database_url(name);
+    helpers::restore(&url, schema_path);
+    helpers::restore(&url, data_path);
+    Client::connect(url.as_str(), NoTls).unwrap()
+}
+
+#[test]
+fn two_pass() {
+    let url = source("cycles_two_pass", "NOT DEFERRABLE");
+    let expected = rows(&mut Client::connect(url.as_str(), NoTls).unwrap());
+
+    let (schema_path, data_path) = dump(&url, "two_pass").unwrap();
+    let schema = fs::read_to_string(&schema_path).unwrap();
+    let data = fs::read_to_string(&data_path).unwrap();
+    assert!(schema.contains(POST_DATA_MARKER));
+    // the addresses are dumped first (the names break the ties)
+    assert!(data
+        .contains("ALTER TABLE \"public\".\"addresses\" ALTER COLUMN \"user_id\" DROP NOT NULL;"));
+    assert!(data.contains("10\t\\N\tParis\n"));
+    assert!(data
+        .contains("COPY pg_temp.datanymizer_cycle_0 (\"id\", \"user_id\") FROM STDIN;\n10\t1\n"));
+    assert!(!data.contains("ALTER TABLE \"public\".\"users\""));
+
+    let mut client = restore("cycles_two_pass_dst", &schema_path, &data_path);
+    assert_eq!(rows(&mut client), expected);
+    let not_null: bool = client
+        .query_one(
+            "SELECT attnotnull FROM pg_attribute
+            WHERE attrelid = 'addresses'::regclass AND attname = 'user_id'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert!(not_null);
+    let orders: i64 = client
+        .query_one("SELECT COUNT(*) FROM orders", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(orders, 2);
+
+    // the whole dump in one file
+    helpers::restore(&helpers::empty_database_url("cycles_two_pass_full"), &{
+        let path = env::temp_dir().join("datanymizer_test_cycles_full.sql");
+        let (pre_data, post_data) = schema.split_once(POST_DATA_MARKER).unwrap();
+        fs::write(&path, format!("{}{}{}", pre_data, data, post_data)).unwrap();
+        path
+    });
+
+    fs::remove_file(schema_path).unwrap();
+    fs::remove_file(data_path).unwrap();
+}
+
+#[test]
+fn defer() {
+    let url = source("cycles_defer", "DEFERRABLE");
+    let expected = rows(&mut Client::connect(url.as_str(), NoTls).unwrap());
+
+    let (schema_path, data_path) = dump(&url, "defer").unwrap();
+    let data = fs::read_to_string(&data_path).unwrap();
+    assert!(data.contains("BEGIN;\nSET CONSTRAINTS ALL DEFERRED;\n"));
+    assert!(data.trim_end().ends_with("COMMIT;"));
+
+    let mut client = restore("cycles_defer_dst", &schema_path, &data_path);
+    assert_eq!(rows(&mut client), expected);
+
+    // the foreign keys must be deferrable
+    let url = source("cycles_not_deferrable", "NOT DEFERRABLE");
+    let error = dump(&url, "defer").unwrap_err().to_string();
+    assert!(
+        error.starts_with(
+            "Circular foreign keys: the foreign key `addresses_user_fk` of `public.addresses`"
+        ),
+        "{}",
+        error
+    );
+    assert!(error.ends_with("it isn't deferrable (use the `two_pass` cycle strategy)"));
+
+    fs::remove_file(schema_path).unwrap();
+    fs::remove_file(data_path).unwrap();
+}
+
+#[test]
+fn fail() {
+    let url = source("cycles_fail", "NOT DEFERRABLE");
+    let error = dump(&url, "fail").unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "Circular foreign keys: the foreign key `addresses_user_fk` of `public.addresses` references \
+        `public.users` that is dumped later and references `public.addresses` too \
+        (set `cycle_strategy` to `two_pass` or `defer`)"
+    );
+}
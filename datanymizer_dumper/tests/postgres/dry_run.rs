@@ -0,0 +1,89 @@
+use super::helpers;
+
+use datanymizer_dumper::postgres::{
+    connector::Connection,
+    dry_run::{DryRunReport, TableColumn},
+    schema_inspector::PgSchemaInspector,
+};
+use datanymizer_engine::Settings;
+use postgres::{Client, NoTls};
+
+const SCHEMA: &str = "
+    CREATE TABLE users (id serial PRIMARY KEY, email_address text, age int, phone text, notes text);
+    CREATE TABLE audit_log (id serial PRIMARY KEY, ip_addr text);
+    INSERT INTO users (email_address, age, phone) VALUES ('user@corp.com', 30, '+1 555 0100');
+";
+
+const CONFIG: &str = r#"
+skip_data: [audit_log]
+tables:
+  - name: users
+    rules:
+      email:
+        email: {}
+      age:
+        email: {}
+    ignore:
+      notes: free text without personal data
+  - name: orders
+    rules:
+      note:
+        words: {}
+"#;
+
+fn column(table: &str, column: &str) -> TableColumn {
+    TableColumn {
+        table: table.to_string(),
+        column: column.to_string(),
+    }
+}
+
+#[test]
+fn dry_run() {
+    let url = helpers::empty_database_url("dry_run");
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let mut connection = Connection::new(client, url);
+    connection.client.batch_execute(SCHEMA).unwrap();
+
+    let settings = Settings::from_yaml(CONFIG).unwrap();
+    let report =
+        DryRunReport::check(&mut connection, &PgSchemaInspector::default(), &settings).unwrap();
+    assert!(report.has_errors());
+    assert_eq!(report.unknown_tables.len(), 1);
+    assert_eq!(report.unknown_tables[0].name, "orders");
+    // the renamed column
+    assert_eq!(
+        report.unknown_columns,
+        vec![column("public.users", "email")]
+    );
+    assert_eq!(report.incompatible_rules.len(), 1);
+    assert_eq!(report.incompatible_rules[0].column, "age");
+    // the tables without data are not reported
+    assert_eq!(
+        report.unruled_pii_columns,
+        vec![
+            column("public.users", "email_address"),
+            column("public.users", "phone")
+        ]
+    );
+
+    let settings = Settings::from_yaml(
+        r#"
+pii_columns: ["^notes$"]
+tables:
+  - name: users
+    rules:
+      email_address:
+        email: {}
+"#,
+    )
+    .unwrap();
+    let report =
+        DryRunReport::check(&mut connection, &PgSchemaInspector::default(), &settings).unwrap();
+    assert!(!report.has_errors());
+    assert!(report.incompatible_rules.is_empty());
+    assert_eq!(
+        report.unruled_pii_columns,
+        vec![column("public.users", "notes")]
+    );
+}
@@ -1,11 +1,82 @@
 use super::helpers;
 
 use datanymizer_dumper::{
-    indicator::SilentIndicator,
-    postgres::{connector::Connection, dumper::PgDumper},
+    indicator::{DumpSummary, Indicator, SilentIndicator, TableInfo, TableStats},
+    manifest::Manifest,
+    postgres::{
+        connector::Connection,
+        csv_output::{self, CsvDirectory, CsvOptions, DirectoryManifest},
+        dumper::{PgDumper, POST_DATA_MARKER},
+        missing_objects::MissingTablePolicy,
+        output_lint::OutputLinter,
+        replica::ReplicaSettings,
+        restore_script,
+        table::PgTable,
+    },
     Dumper,
 };
 use datanymizer_engine::{Engine, Settings};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, BufReader, Write},
+    sync::{Arc, Mutex},
+};
+
+// Records the indicator events (only the last progress event of a table)
+#[derive(Default)]
+struct RecordingIndicator(Mutex<Vec<String>>);
+
+impl RecordingIndicator {
+    fn record(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Indicator for RecordingIndicator {
+    fn dump_started(&self, tables: &[TableInfo]) {
+        let names: Vec<_> = tables.iter().map(|t| t.name.as_str()).collect();
+        self.record(format!("dump_started: {}", names.join(", ")));
+    }
+
+    fn table_started(&self, table: &TableInfo) {
+        self.record(format!("table_started: {}", table.name));
+    }
+
+    fn rows_progress(&self, table: &TableInfo, dumped: u64) {
+        let mut events = self.0.lock().unwrap();
+        if events.last().unwrap().starts_with("rows_progress") {
+            events.pop();
+        }
+        events.push(format!("rows_progress: {} {}", table.name, dumped));
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.record(format!("table_finished: {} {}", stats.name, stats.rows));
+    }
+
+    fn table_failed(&self, table: &TableInfo, error: &anyhow::Error) {
+        self.record(format!("table_failed: {}: {}", table.name, error));
+    }
+
+    fn table_skipped(&self, table: &str, reason: &str) {
+        self.record(format!("table_skipped: {} ({})", table, reason));
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        self.record(match &summary.error {
+            Some(e) => format!("dump_finished: {}", e),
+            None => format!(
+                "dump_finished: {} tables, {} rows",
+                summary.tables, summary.rows
+            ),
+        });
+    }
+}
 
 fn dump(name: &str) {
     let mut dst = helpers::dst_wrapper(name);
@@ -24,8 +95,10 @@ fn dump(name: &str) {
     .unwrap();
     let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
     dumper.dump(&mut connection).unwrap();
+    // closes the psql input, so psql loads all data and exits
+    drop(dumper);
 
-    dst.close();
+    dst.wait();
 }
 
 #[test]
@@ -40,6 +113,11 @@ fn simple_dump() {
     let dst_count: i64 = dst_client.query_one(count_query, &[]).unwrap().get(0);
     assert_eq!(src_count, dst_count);
 
+    let seq_query = "SELECT last_value FROM public.actor_actor_id_seq";
+    let src_last_value: i64 = src_client.query_one(seq_query, &[]).unwrap().get(0);
+    let dst_last_value: i64 = dst_client.query_one(seq_query, &[]).unwrap().get(0);
+    assert_eq!(src_last_value, dst_last_value);
+
     let rows_query = "SELECT * FROM actor";
     let src_rows = src_client.query(rows_query, &[]).unwrap();
     let dst_rows = dst_client.query(rows_query, &[]).unwrap();
@@ -60,3 +138,2419 @@ fn simple_dump() {
         );
     }
 }
+
+#[test]
+fn schema_and_data_files() {
+    let schema_path = env::temp_dir().join("datanymizer_test_schema.sql");
+    let data_path = env::temp_dir().join("datanymizer_test_data.sql");
+
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&schema_path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_data_writer(fs::File::create(&data_path).unwrap());
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    dumper.dump(&mut connection).unwrap();
+    drop(dumper);
+
+    let schema = fs::read_to_string(&schema_path).unwrap();
+    let data = fs::read_to_string(&data_path).unwrap();
+    let (pre_data, post_data) = schema.split_once(POST_DATA_MARKER).unwrap();
+    assert!(pre_data.contains("CREATE TABLE public.actor"));
+    assert!(!pre_data.contains("COPY"));
+    assert!(post_data.contains("ADD CONSTRAINT actor_pkey"));
+    assert!(!data.contains("CREATE TABLE"));
+    assert!(data.contains("COPY \"public\".\"actor\""));
+
+    // before data, data, after data
+    let mut dst = helpers::dst_wrapper("split");
+    let mut io = dst.io();
+    for part in [pre_data, data.as_str(), post_data] {
+        io.write_all(part.as_bytes()).unwrap();
+    }
+    drop(io);
+    dst.wait();
+
+    let count_query = "SELECT COUNT(*) FROM actor";
+    let src_count: i64 = helpers::src_client()
+        .query_one(count_query, &[])
+        .unwrap()
+        .get(0);
+    let dst_count: i64 = helpers::dst_client("split")
+        .query_one(count_query, &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(src_count, dst_count);
+
+    fs::remove_file(schema_path).unwrap();
+    fs::remove_file(data_path).unwrap();
+}
+
+#[test]
+fn csv_directory() {
+    let url = helpers::empty_database_url("csv_directory");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    let table_sql = "CREATE TABLE notes (id int PRIMARY KEY, title text, body text, tags text[]);
+        CREATE TABLE \"odd, names\" (\"a, b\" text);";
+    client
+        .batch_execute(&format!(
+            "{}
+            INSERT INTO notes VALUES
+                (1, 'hello', 'commas, \"quotes\" and
+newlines\r', '{{a,b}}'),
+                (2, 'world', '', NULL),
+                (3, NULL, '\\N', '{{}}'),
+                (4, 'bar', '\\.', '{{\"x y\"}}'),
+                (5, NULL, E'tab\\there\\\\', NULL);
+            INSERT INTO \"odd, names\" VALUES ('x'), (NULL);",
+            table_sql
+        ))
+        .unwrap();
+    let config = r#"
+tables:
+  - name: notes
+    rules:
+      title:
+        capitalize: ~
+"#;
+
+    let query = "SELECT id, title, body, tags::text FROM notes ORDER BY id";
+    type Note = (i32, Option<String>, Option<String>, Option<String>);
+    let mut expected: Vec<Note> = client
+        .query(query, &[])
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect();
+    expected[0].1 = Some("Hello".to_string());
+    expected[1].1 = Some("World".to_string());
+    expected[3].1 = Some("Bar".to_string());
+
+    for (delimiter, null, extension) in [(",", "", "csv"), ("tab", "\\N", "tsv")] {
+        let dir = env::temp_dir().join(format!("datanymizer_test_csv_{}", extension));
+        let _ = fs::remove_dir_all(&dir);
+        let directory = CsvDirectory::new(&dir, CsvOptions::new(delimiter, null).unwrap()).unwrap();
+        let indicator = Arc::new(RecordingIndicator::default());
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(dir.join(csv_output::SCHEMA_FILE)).unwrap(),
+            indicator.clone(),
+            vec![],
+        )
+        .unwrap()
+        .with_csv_directory(directory);
+        dumper
+            .dump(&mut Connection::new(
+                postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap(),
+                url.clone(),
+            ))
+            .unwrap();
+        drop(dumper);
+
+        assert!(indicator
+            .events()
+            .contains(&"table_finished: public.notes 5".to_string()));
+        let schema = fs::read_to_string(dir.join(csv_output::SCHEMA_FILE)).unwrap();
+        let (pre_data, _) = schema.split_once(POST_DATA_MARKER).unwrap();
+        assert!(pre_data.contains("CREATE TABLE public.notes"));
+        assert!(!schema.contains("COPY"));
+
+        let manifest: DirectoryManifest =
+            serde_json::from_str(&fs::read_to_string(dir.join(csv_output::MANIFEST_FILE)).unwrap())
+                .unwrap();
+        let notes = manifest
+            .tables
+            .iter()
+            .find(|t| t.name == "public.notes")
+            .unwrap();
+        assert_eq!(notes.file, format!("public.notes.{}", extension));
+        assert_eq!(notes.rows, 5);
+        let columns: Vec<_> = notes
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.data_type.as_str()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("id", "integer"),
+                ("title", "text"),
+                ("body", "text"),
+                ("tags", "ARRAY")
+            ]
+        );
+        let data = fs::read_to_string(dir.join(&notes.file)).unwrap();
+        assert!(data.starts_with(&format!(
+            "id{}title",
+            if extension == "csv" { "," } else { "\t" }
+        )));
+
+        // the files are loaded with COPY
+        let dst_url = helpers::empty_database_url(&format!("csv_directory_{}", extension));
+        let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+        dst_client.batch_execute(table_sql).unwrap();
+        for table in &manifest.tables {
+            let name = PgTable::quote_table_name(&table.name).unwrap();
+            let mut writer = dst_client
+                .copy_in(
+                    format!(
+                        "COPY {} FROM STDIN WITH (FORMAT csv, HEADER, DELIMITER E'{}', NULL '{}')",
+                        name,
+                        manifest.delimiter.escape_default(),
+                        manifest.null
+                    )
+                    .as_str(),
+                )
+                .unwrap();
+            writer
+                .write_all(&fs::read(dir.join(&table.file)).unwrap())
+                .unwrap();
+            writer.finish().unwrap();
+        }
+        let rows: Vec<Note> = dst_client
+            .query(query, &[])
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+            .collect();
+        assert_eq!(rows, expected);
+        let odd: Vec<Option<String>> = dst_client
+            .query("SELECT \"a, b\" FROM \"odd, names\" ORDER BY 1", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(odd, vec![Some("x".to_string()), None]);
+
+        let mut linter = OutputLinter::default();
+        linter.lint_directory(&dir).unwrap();
+        let report = linter.into_report();
+        assert_eq!(report.errors, vec![]);
+        assert_eq!((report.files, report.rows), (3, 7));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn restore_scripts() {
+    let url = helpers::empty_database_url("restore_scripts");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE orgs (id int PRIMARY KEY, name text);
+            CREATE TABLE users (
+                id int PRIMARY KEY,
+                org_id int REFERENCES orgs,
+                manager_id int REFERENCES users,
+                \"e'mail\" text
+            );
+            CREATE TABLE notes (id int PRIMARY KEY, body text);
+            INSERT INTO orgs VALUES (1, 'a'), (2, 'b');
+            INSERT INTO users VALUES (1, 1, NULL, 'x@example.com'), (2, 2, 1, NULL);
+            INSERT INTO notes VALUES (1, 'it''s, \"quoted\"'), (2, '');",
+        )
+        .unwrap();
+
+    let dir = env::temp_dir().join("datanymizer_test_restore_scripts");
+    let _ = fs::remove_dir_all(&dir);
+    let directory = CsvDirectory::new(&dir, CsvOptions::default()).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml("tables: []").unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(dir.join(csv_output::SCHEMA_FILE)).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_csv_directory(directory);
+    dumper
+        .dump(&mut Connection::new(
+            postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap(),
+            url.clone(),
+        ))
+        .unwrap();
+    drop(dumper);
+    restore_script::write_scripts(&dir, 2).unwrap();
+
+    let manifest: DirectoryManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(csv_output::MANIFEST_FILE)).unwrap())
+            .unwrap();
+    let users = manifest
+        .tables
+        .iter()
+        .find(|t| t.name == "public.users")
+        .unwrap();
+    assert_eq!(users.depends_on, vec!["public.orgs"]);
+    assert_eq!(users.sha256.len(), 64);
+    let checksums = fs::read_to_string(dir.join(restore_script::CHECKSUMS_FILE)).unwrap();
+    assert!(checksums.contains(&format!("{}  public.users.csv\n", users.sha256)));
+
+    let query = "SELECT (SELECT COUNT(*) FROM orgs), (SELECT COUNT(*) FROM users), \
+        (SELECT string_agg(body, '|' ORDER BY id) FROM notes), \
+        (SELECT COUNT(*) FROM pg_constraint WHERE contype = 'f')";
+    type Counts = (i64, i64, Option<String>, i64);
+    let row = client.query_one(query, &[]).unwrap();
+    let expected: Counts = (row.get(0), row.get(1), row.get(2), row.get(3));
+
+    let dst_url = helpers::empty_database_url("restore_scripts_dst");
+    assert!(helpers::restore_directory(&dst_url, &dir));
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let row = dst_client.query_one(query, &[]).unwrap();
+    let restored: Counts = (row.get(0), row.get(1), row.get(2), row.get(3));
+    assert_eq!(restored, expected);
+
+    // a changed file isn't loaded
+    let dst_url = helpers::empty_database_url("restore_scripts_changed");
+    let mut data = fs::read_to_string(dir.join(&users.file)).unwrap();
+    data.push_str("3,1,,\n");
+    fs::write(dir.join(&users.file), data).unwrap();
+    assert!(!helpers::restore_directory(&dst_url, &dir));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sequences() {
+    dump("sequences");
+
+    let mut dst_client = helpers::dst_client("sequences");
+    let next_value: i64 = dst_client
+        .query_one("SELECT nextval('public.actor_actor_id_seq')", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(next_value, 1000000);
+}
+
+#[test]
+fn manifest() {
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    dumper.dump(&mut connection).unwrap();
+
+    let src_count: i64 = helpers::src_client()
+        .query_one("SELECT COUNT(*) FROM actor", &[])
+        .unwrap()
+        .get(0);
+    let manifest = manifest.lock().unwrap();
+    let actor = &manifest.tables["public.actor"];
+    assert_eq!(actor.rows, src_count as u64);
+    assert_eq!(actor.rules["first_name"], "first_name");
+    assert!(actor.checksums.contains_key("first_name"));
+    assert!(actor.checksums.contains_key("last_name"));
+}
+
+#[test]
+fn secret_suspects() {
+    let url = helpers::empty_database_url("secret_suspects");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE clients (id int, name text, token text, public_key text, note text);
+            INSERT INTO clients
+            SELECT i, 'client ' || i, 'sk_live_' || md5(i::text), 'pk_' || md5(i::text), 'hello'
+            FROM generate_series(1, 50) AS i;",
+        )
+        .unwrap();
+
+    let config = r#"
+        tables:
+          - name: clients
+            rules:
+              name:
+                first_name: {}
+        secret_detection:
+          sample_size: 20
+          min_entropy: 3.5
+          allow_columns: [public_key]
+        "#;
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    dumper.dump(&mut Connection::new(client, url)).unwrap();
+
+    let manifest = manifest.lock().unwrap();
+    let coverage = manifest.tables["public.clients"].coverage.as_ref().unwrap();
+    assert_eq!(
+        coverage.secret_suspects.keys().collect::<Vec<_>>(),
+        vec!["token"]
+    );
+    let token = &coverage.secret_suspects["token"];
+    assert_eq!(token.kind, "api_key");
+    assert_eq!((token.matches, token.sampled), (20, 20));
+    assert_eq!(token.example, "sk_l… (40 chars)");
+    // only masked values are saved
+    let json = serde_json::to_string(&*manifest).unwrap();
+    assert!(!json.contains("sk_live_"));
+}
+
+// A failed pg_dump fails the dump with its output (instead of exiting), and no pg_dump process remains
+#[cfg(unix)]
+#[test]
+fn pg_dump_failure() {
+    use std::{os::unix::fs::PermissionsExt, process::Command};
+
+    let dir = env::temp_dir().join(format!(
+        "datanymizer_pg_dump_failure_{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let pid_file = dir.join("pid");
+    let script = dir.join("pg_dump");
+    fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+            if [ \"$1\" = \"--version\" ]; then exec {} --version; fi\n\
+            echo $$ > {}\n\
+            echo 'CREATE TABLE partial ('\n\
+            echo 'connection lost' >&2\n\
+            exit 2\n",
+            helpers::pg_dump_path(),
+            pid_file.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        script.display().to_string(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    let err = dumper.dump(&mut connection).unwrap_err().to_string();
+    assert!(err.starts_with("pg_dump error (exit status: 2)"), "{}", err);
+    assert!(err.ends_with("Output:\nconnection lost"), "{}", err);
+
+    let pid = fs::read_to_string(&pid_file).unwrap();
+    let alive = Command::new("kill")
+        .args(["-0", pid.trim()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap()
+        .success();
+    assert!(!alive);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pg_dump_table_args_conflict() {
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec!["--exclude-table-data=actor".to_string()],
+    )
+    .unwrap();
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let err = dumper.dump(&mut connection).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("The table public.actor has rules in the config, but its data is excluded by the pg_dump arguments (--exclude-table-data=actor)"));
+}
+
+#[test]
+fn min_k() {
+    let config = r#"
+        filter:
+          only:
+            - public.actor
+        tables:
+          - name: actor
+            quasi_identifiers:
+              - [actor_id]
+            rules:
+              first_name:
+                first_name: {}
+    "#;
+    let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+    let metrics = engine.enable_anonymity_check();
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .min_k(Some(2));
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let err = dumper.dump(&mut connection).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1 quasi-identifier sets have equivalence classes with less than 2 rows: actor (actor_id)"
+    );
+
+    let src_count: i64 = helpers::src_client()
+        .query_one("SELECT COUNT(*) FROM actor", &[])
+        .unwrap()
+        .get(0);
+    let report = &metrics.report(2).unwrap()[0];
+    assert_eq!(report.rows, src_count as u64);
+    assert_eq!(report.classes_below_k, src_count as u64);
+}
+
+#[test]
+fn explicit_columns() {
+    let config = r#"
+        filter:
+          only:
+            - public.actor
+        tables:
+          - name: actor
+            explicit: true
+            ignore:
+              actor_id: surrogate key
+            rules:
+              first_name:
+                first_name: {}
+    "#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let err = dumper.dump(&mut connection).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Columns without rules in the explicit tables (add rules or list them in `ignore` with reasons):\n  public.actor: last_name, last_update"
+    );
+
+    let config = config.replacen(
+        "              actor_id: surrogate key\n",
+        "              actor_id: surrogate key\n              last_name: not PII\n              last_update: timestamp, not PII\n",
+        1,
+    );
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(&config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    dumper.dump(&mut connection).unwrap();
+
+    let manifest = manifest.lock().unwrap();
+    let actor = &manifest.tables["public.actor"];
+    assert_eq!(actor.ignored["last_update"], "timestamp, not PII");
+    assert_eq!(actor.ignored.len(), 3);
+}
+
+#[test]
+fn patch() {
+    let dump_path = env::temp_dir().join("datanymizer_test_patch.sql");
+    let patched_path = env::temp_dir().join("datanymizer_test_patched.sql");
+    let settings = || Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let patch_dumper = |manifest: &Arc<Mutex<Manifest>>| {
+        PgDumper::new(
+            Engine::new(settings()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&patched_path).unwrap(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap()
+        .with_manifest(manifest.clone())
+    };
+    let existing = || BufReader::new(fs::File::open(&dump_path).unwrap());
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let mut dumper = PgDumper::new(
+        Engine::new(settings()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&dump_path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    dumper.dump(&mut connection).unwrap();
+    drop(dumper);
+    let original = fs::read_to_string(&dump_path).unwrap();
+    let actor_rows = manifest.lock().unwrap().tables["public.actor"].rows;
+    assert_eq!(
+        manifest
+            .lock()
+            .unwrap()
+            .schema_checksums
+            .keys()
+            .collect::<Vec<_>>(),
+        vec!["post-data", "pre-data"]
+    );
+
+    let mut dumper = patch_dumper(&manifest);
+    dumper
+        .patch(&mut connection, existing(), &["actor".to_string()])
+        .unwrap();
+    drop(dumper);
+    let patched = fs::read_to_string(&patched_path).unwrap();
+
+    // only the table data is changed
+    let (before, _) = original.split_once("--- Dump table: public.actor").unwrap();
+    let (_, after) = original.split_once("--- End dumping data").unwrap();
+    assert!(patched.starts_with(before));
+    assert!(patched.ends_with(after));
+    assert_eq!(patched.lines().count(), original.lines().count());
+    assert_ne!(patched, original);
+    assert_eq!(
+        manifest.lock().unwrap().tables["public.actor"].rows,
+        actor_rows
+    );
+
+    let err = patch_dumper(&manifest)
+        .patch(&mut connection, existing(), &["unknown".to_string()])
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Unknown table `unknown` to patch");
+
+    manifest
+        .lock()
+        .unwrap()
+        .schema_checksums
+        .insert("post-data".to_string(), "0".to_string());
+    let err = patch_dumper(&manifest)
+        .patch(&mut connection, existing(), &["actor".to_string()])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The database schema (post-data) has changed since the existing dump was made, make a full dump"
+    );
+
+    fs::remove_file(dump_path).unwrap();
+    fs::remove_file(patched_path).unwrap();
+}
+
+#[test]
+fn indicator_events() {
+    let src_count: i64 = helpers::src_client()
+        .query_one("SELECT COUNT(*) FROM actor", &[])
+        .unwrap()
+        .get(0);
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let indicator = Arc::new(RecordingIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    dumper.dump(&mut connection).unwrap();
+    assert_eq!(
+        indicator.events(),
+        vec![
+            "dump_started: public.actor".to_string(),
+            "table_started: public.actor".to_string(),
+            format!("rows_progress: public.actor {}", src_count),
+            format!("table_finished: public.actor {}", src_count),
+            format!("dump_finished: 1 tables, {} rows", src_count),
+        ]
+    );
+
+    let config = r#"
+        filter:
+          only:
+            - public.actor
+        tables:
+          - name: actor
+            quasi_identifiers:
+              - [unknown]
+            rules:
+              first_name:
+                first_name: {}
+    "#;
+    let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+    engine.enable_anonymity_check();
+    let indicator = Arc::new(RecordingIndicator::default());
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    let err = dumper.dump(&mut connection).unwrap_err();
+    assert_eq!(
+        indicator.events(),
+        vec![
+            "dump_started: public.actor".to_string(),
+            "table_started: public.actor".to_string(),
+            format!("table_failed: public.actor: {}", err),
+            format!("dump_finished: {}", err),
+        ]
+    );
+}
+
+#[test]
+fn target_profile() {
+    let path = env::temp_dir().join("datanymizer_test_target_profile.sql");
+    let config = fs::read_to_string("tests/postgres/configs/simple.yml").unwrap();
+    let settings = Settings::from_yaml(&format!("{}\ntarget_profile: dev\n", config)).unwrap();
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    dumper.dump(&mut connection).unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    assert!(dump.contains("DROP TABLE IF EXISTS public.actor CASCADE;\nCREATE TABLE public.actor"));
+    assert!(dump.contains("DROP INDEX IF EXISTS public.idx_actor_last_name;\nCREATE INDEX"));
+    assert!(!dump.contains("OWNER TO"));
+    assert_eq!(
+        manifest.lock().unwrap().target_profile,
+        Some("dev".to_string())
+    );
+
+    // the dump can be restored over the previous one
+    let mut dst = helpers::dst_wrapper("target_profile");
+    let mut io = dst.io();
+    io.write_all(dump.as_bytes()).unwrap();
+    io.write_all(dump.as_bytes()).unwrap();
+    drop(io);
+    dst.wait();
+
+    let count_query = "SELECT COUNT(*) FROM actor";
+    let src_count: i64 = helpers::src_client()
+        .query_one(count_query, &[])
+        .unwrap()
+        .get(0);
+    let dst_count: i64 = helpers::dst_client("target_profile")
+        .query_one(count_query, &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(src_count, dst_count);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unresolved_tables() {
+    let config = r#"
+        filter:
+          only:
+            - public.actor
+        tables:
+          - name: actors
+            rules:
+              first_name:
+                first_name: {}
+    "#;
+    let dumper = |lenient: bool| {
+        PgDumper::new(
+            Engine::new(Settings::from_yaml(config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            io::sink(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap()
+        .lenient(lenient)
+    };
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+
+    let err = dumper(false).dump(&mut connection).unwrap_err().to_string();
+    assert!(err.starts_with(
+        "The config tables don't match the database tables (use --lenient to dump anyway):\n  \
+        The config table `actors` matches no tables (similar tables: public.actor"
+    ));
+    assert!(err.ends_with("schemas: public)"));
+
+    dumper(true).dump(&mut connection).unwrap();
+}
+
+#[test]
+fn prefer_replica_safe() {
+    // the test database is a primary, so all tables are dumped in a single transaction
+    assert_eq!(
+        ReplicaSettings::fetch(&mut helpers::src_client()).unwrap(),
+        None
+    );
+
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let indicator = Arc::new(RecordingIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap()
+    .prefer_replica_safe(true);
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    dumper.dump(&mut connection).unwrap();
+    assert!(indicator
+        .events()
+        .last()
+        .unwrap()
+        .starts_with("dump_finished: 1 tables"));
+}
+
+#[test]
+fn unsupported_column_types() {
+    let path = env::temp_dir().join("datanymizer_test_unsupported_column_types.sql");
+    let dump = |force: &str| {
+        let config = format!(
+            r#"
+            filter:
+              only:
+                - public.actor
+            tables:
+              - name: actor
+                force: {}
+                rules:
+                  last_update:
+                    email: {{}}
+            "#,
+            force
+        );
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(&config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap();
+        let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+        dumper.dump(&mut connection).unwrap();
+        drop(dumper);
+        fs::read_to_string(&path).unwrap()
+    };
+
+    // `last_update` is a timestamp column, so the `email` rule is skipped
+    let data = dump("[]");
+    assert!(data.contains("COPY \"public\".\"actor\""));
+    assert!(!data.contains('@'));
+
+    let data = dump("[last_update]");
+    assert!(data.contains('@'));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[derive(Default)]
+struct WarningIndicator(Mutex<Vec<String>>);
+
+impl Indicator for WarningIndicator {
+    fn warning_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+#[test]
+fn lint_warnings() {
+    let path = env::temp_dir().join("datanymizer_test_lint_warnings.sql");
+    let dump = |lint: &str, deny: bool| {
+        let config = format!(
+            r#"
+            filter:
+              only:
+                - public.actor
+            tables:
+              - name: actor
+                lint: {}
+                rules:
+                  first_name:
+                    email: {{}}
+            "#,
+            lint
+        );
+        let indicator = Arc::new(WarningIndicator::default());
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(&config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            indicator.clone(),
+            vec![],
+        )
+        .unwrap()
+        .deny_lint_warnings(deny);
+        let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+        let result = dumper.dump(&mut connection).map_err(|e| e.to_string());
+        let warnings = indicator.0.lock().unwrap().clone();
+        (result, warnings)
+    };
+
+    let warning = "the column `first_name` of the table `actor` looks like name data, \
+        but its rule `email` generates email data (use `lint: {first_name: allow_mismatch}` if it is intended)";
+    let (result, warnings) = dump("{}", false);
+    assert!(result.is_ok());
+    assert_eq!(warnings, vec![warning]);
+
+    let (result, warnings) = dump("{}", true);
+    assert_eq!(
+        result,
+        Err("The config has 1 lint warnings (see the warnings above)".to_string())
+    );
+    assert_eq!(warnings, vec![warning]);
+
+    let (result, warnings) = dump("{first_name: allow_mismatch}", true);
+    assert!(result.is_ok());
+    assert!(warnings.is_empty());
+
+    fs::remove_file(path).unwrap();
+}
+
+// Runs the migration when the dump starts (after the tables are inspected)
+struct MigratingIndicator {
+    client: Mutex<postgres::Client>,
+    migration: &'static str,
+    summary: Mutex<Option<DumpSummary>>,
+}
+
+impl Indicator for MigratingIndicator {
+    fn dump_started(&self, _tables: &[TableInfo]) {
+        self.client
+            .lock()
+            .unwrap()
+            .batch_execute(self.migration)
+            .unwrap();
+    }
+
+    fn dump_finished(&self, summary: &DumpSummary) {
+        *self.summary.lock().unwrap() = Some(summary.clone());
+    }
+}
+
+#[test]
+fn tables_changed_while_dumping() {
+    let url = helpers::empty_database_url("tables_changed_while_dumping");
+    let path = env::temp_dir().join("datanymizer_test_tables_changed_while_dumping.sql");
+    let dump = |policy: MissingTablePolicy| {
+        let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+        client
+            .batch_execute(
+                "DROP TABLE IF EXISTS orders, users;
+                CREATE TABLE users (id int, email text, name text);
+                INSERT INTO users VALUES (1, 'ann@example.com', 'Ann');
+                CREATE TABLE orders (id int, total int);
+                INSERT INTO orders VALUES (1, 100);",
+            )
+            .unwrap();
+        let indicator = Arc::new(MigratingIndicator {
+            client: Mutex::new(postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap()),
+            migration: "DROP TABLE orders; ALTER TABLE users DROP COLUMN email;",
+            summary: Mutex::new(None),
+        });
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml("tables: []").unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            indicator.clone(),
+            vec![],
+        )
+        .unwrap()
+        .on_missing_table(policy);
+        let result = dumper.dump(&mut Connection::new(client, url.clone()));
+        drop(dumper);
+        let summary = indicator.summary.lock().unwrap().clone().unwrap();
+        (result, summary, fs::read_to_string(&path).unwrap())
+    };
+
+    let (result, _, _) = dump(MissingTablePolicy::Fail);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "The table public.orders is dropped or renamed after the tables were inspected \
+        (use `--on-missing-table skip` to skip such tables)"
+    );
+
+    let (result, summary, data) = dump(MissingTablePolicy::Skip);
+    result.unwrap();
+    assert_eq!(summary.skipped_tables, vec!["public.orders"]);
+    assert_eq!(summary.tables, 1);
+    assert!(!data.contains("COPY \"public\".\"orders\""));
+    // the dropped column is not dumped
+    assert!(data.contains("COPY \"public\".\"users\"(\"id\", \"name\") FROM STDIN;\n1\tAnn\n\\.\n"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn safety() {
+    let url = helpers::empty_database_url("safety");
+    let db_name = url.path().trim_start_matches('/').to_string();
+    let dump = |allow_override: bool, reason: Option<&str>| {
+        let config = format!(
+            "tables: []
+safety:
+  database_names: [\"*SAFETY*\"]
+  hosts: [\"db-primary.internal\"]
+  allow_override: {}
+  sentinels:
+    - setting: app.environment
+      value: production
+    - query: SELECT env FROM missing_sentinel_table
+      value: production",
+            allow_override
+        );
+        let manifest = Arc::new(Mutex::new(Manifest::new(
+            "test".to_string(),
+            "config".to_string(),
+        )));
+        let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+        client
+            .batch_execute("SET app.environment = 'production'")
+            .unwrap();
+        let result = PgDumper::new(
+            Engine::new(Settings::from_yaml(&config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            io::sink(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap()
+        .safety_override(reason.map(String::from))
+        .with_manifest(manifest.clone())
+        .dump(&mut Connection::new(client, url.clone()));
+        let safety_override = manifest.lock().unwrap().safety_override.clone();
+        (result, safety_override)
+    };
+    let findings = vec![
+        format!("the database name `{}` matches `*SAFETY*`", db_name),
+        "the sentinel `app.environment` is `production`".to_string(),
+    ];
+
+    let (result, _) = dump(true, None);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "The source database looks like production ({}). Use `--yes-i-know <REASON>` to dump it anyway",
+            findings.join("; ")
+        )
+    );
+
+    let (result, safety_override) = dump(true, Some("anonymized copy for INC-42"));
+    result.unwrap();
+    let safety_override = safety_override.unwrap();
+    assert_eq!(safety_override.findings, findings);
+    assert_eq!(safety_override.reason, "anonymized copy for INC-42");
+
+    let (result, _) = dump(false, Some("anonymized copy for INC-42"));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .ends_with("the `safety` config doesn't allow overrides"));
+}
+
+#[test]
+fn rename_objects() {
+    let url = helpers::empty_database_url("rename_objects");
+    let path = env::temp_dir().join("datanymizer_test_rename_objects.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE customers (id int CONSTRAINT customers_acmecorp_pkey PRIMARY KEY);
+            CREATE TABLE orders (id int, acmecorp_id int, note text);
+            ALTER TABLE orders ADD CONSTRAINT fk_orders_acmecorp_id
+                FOREIGN KEY (acmecorp_id) REFERENCES customers(id);
+            CREATE INDEX idx_orders_acmecorp ON orders (acmecorp_id);
+            CREATE INDEX idx_orders_note ON orders (note) WHERE note <> 'idx_orders_acmecorp';
+            COMMENT ON INDEX idx_orders_acmecorp IS 'idx_orders_acmecorp';
+            ALTER TABLE orders CLUSTER ON idx_orders_acmecorp;",
+        )
+        .unwrap();
+
+    let config = r#"
+tables: []
+sensitive_object_names: ["acmecorp", "note"]
+rename_objects:
+  - pattern: "^(\\w+)_acmecorp(_\\w+)?$"
+    replace: "${1}_customer${2}"
+"#;
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    assert!(!dump.contains("customers_acmecorp_pkey"));
+    assert!(!dump.contains("fk_orders_acmecorp_id"));
+    // the string literals are not changed
+    assert_eq!(dump.matches("'idx_orders_acmecorp'").count(), 2);
+    assert_eq!(
+        manifest.lock().unwrap().renamed_objects,
+        BTreeMap::from(
+            [
+                ("customers_acmecorp_pkey", "customers_customer_pkey"),
+                ("fk_orders_acmecorp_id", "fk_orders_customer_id"),
+                ("idx_orders_acmecorp", "idx_orders_customer"),
+            ]
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+        )
+    );
+
+    let dst_url = helpers::empty_database_url("rename_objects_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let names: Vec<String> = dst_client
+        .query(
+            "SELECT conname::text FROM pg_constraint WHERE connamespace = 'public'::regnamespace
+            UNION ALL SELECT indexname::text FROM pg_indexes WHERE schemaname = 'public'
+            ORDER BY 1",
+            &[],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "customers_customer_pkey",
+            "customers_customer_pkey",
+            "fk_orders_customer_id",
+            "idx_orders_customer",
+            "idx_orders_note"
+        ]
+    );
+    let clustered: String = dst_client
+        .query_one(
+            "SELECT indexrelid::regclass::text FROM pg_index WHERE indisclustered",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(clustered, "idx_orders_customer");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn logical_replication() {
+    let url = helpers::empty_database_url("logical_replication");
+    let path = env::temp_dir().join("datanymizer_test_logical_replication.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id int PRIMARY KEY, name text);
+            CREATE TABLE orders (id int PRIMARY KEY, user_id int);
+            CREATE SCHEMA audit;
+            CREATE TABLE audit.log (id int PRIMARY KEY);
+            CREATE TABLE audit.\"Events\" (id int PRIMARY KEY);
+            CREATE PUBLICATION pub_all FOR ALL TABLES;
+            CREATE PUBLICATION pub_some FOR TABLE users, orders WITH (publish = 'insert');
+            CREATE PUBLICATION pub_schema FOR TABLES IN SCHEMA audit;
+            CREATE PUBLICATION pub_stripped FOR TABLE users;
+            COMMENT ON PUBLICATION pub_some IS 'to the warehouse';",
+        )
+        .unwrap();
+
+    let config = r#"
+tables: []
+filter:
+  data:
+    except:
+      - public.orders
+      - audit.log
+publication_map:
+  pub_all: pub_all
+  pub_some: pub_some_dev
+  pub_schema: pub_schema
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    assert!(!dump.contains("pub_stripped"));
+    assert!(!dump.contains("FOR ALL TABLES"));
+    assert!(!dump.contains("ADD TABLES IN SCHEMA"));
+
+    let dst_url = helpers::empty_database_url("logical_replication_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let publications: Vec<(String, String)> = dst_client
+        .query(
+            "SELECT p.pubname::text, coalesce(string_agg(t.schemaname || '.' || t.tablename, ', '
+                ORDER BY t.schemaname, t.tablename), '')
+            FROM pg_publication p LEFT JOIN pg_publication_tables t ON t.pubname = p.pubname
+            GROUP BY p.pubname ORDER BY p.pubname",
+            &[],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    assert_eq!(
+        publications,
+        [
+            ("pub_all", "audit.Events, public.users"),
+            ("pub_schema", "audit.Events"),
+            ("pub_some_dev", "public.users"),
+        ]
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+    );
+    let comment: String = dst_client
+        .query_one(
+            "SELECT obj_description(oid, 'pg_publication') FROM pg_publication WHERE pubname = 'pub_some_dev'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(comment, "to the warehouse");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sql_rules() {
+    let url = helpers::empty_database_url("sql_rules");
+    let path = env::temp_dir().join("datanymizer_test_sql_rules.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id int PRIMARY KEY, email text, login text, age int);
+            INSERT INTO users VALUES (1, 'john@gmail.com', 'john', 34), (2, 'jane@gmail.com', 'jane', 47);",
+        )
+        .unwrap();
+
+    // the `template` rule sees the value replaced by the database
+    let config = r#"
+tables:
+  - name: users
+    rules:
+      email:
+        sql: "md5(email) || '@example.com'"
+      age:
+        sql: "age / 10 * 10"
+      login:
+        template:
+          format: "{{ prev.email | split(pat='@') | first | truncate(length=8, end='') }}"
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("sql_rules_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<(String, String, i32)> = dst_client
+        .query("SELECT email, login, age FROM users ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2)))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "1f9d9a9efc2f523b2f09629444632b5c@example.com".to_string(),
+                "1f9d9a9e".to_string(),
+                30
+            ),
+            (
+                "b26407fdbb151a3a44fceda692c92874@example.com".to_string(),
+                "b26407fd".to_string(),
+                40
+            ),
+        ]
+    );
+
+    // the expressions are checked before dumping
+    let config = r#"
+tables:
+  - name: users
+    rules:
+      email:
+        sql: "md5(mail)"
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    let client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    assert_eq!(
+        dumper
+            .dump(&mut Connection::new(client, url.clone()))
+            .unwrap_err()
+            .to_string(),
+        "Invalid `sql` rules of the table public.users: column \"mail\" does not exist"
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+// Records the estimated rows of the started tables and the rows of the finished ones
+#[derive(Default)]
+struct EstimateIndicator(Mutex<Vec<(String, u64)>>);
+
+impl Indicator for EstimateIndicator {
+    fn table_started(&self, table: &TableInfo) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((format!("started: {}", table.name), table.rows));
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((format!("finished: {}", stats.name), stats.rows));
+    }
+}
+
+#[test]
+fn dump_condition() {
+    let url = helpers::empty_database_url("dump_condition");
+    let path = env::temp_dir().join("datanymizer_test_dump_condition.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE events (id int PRIMARY KEY, created_at timestamptz, payload text);
+            INSERT INTO events
+                SELECT i, now() - i * interval '1 day', 'event ' || i FROM generate_series(1, 1000) i;
+            ANALYZE events;",
+        )
+        .unwrap();
+
+    let dump = |condition: &str, indicator: Arc<EstimateIndicator>| {
+        let config = format!(
+            r#"
+tables:
+  - name: events
+    query:
+      dump_condition: "{}"
+"#,
+            condition
+        );
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(&config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            indicator,
+            vec![],
+        )
+        .unwrap();
+        let client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+        dumper.dump(&mut Connection::new(client, url.clone()))
+    };
+
+    let indicator = Arc::new(EstimateIndicator::default());
+    dump("created_at > now() - interval '90 days'", indicator.clone()).unwrap();
+    let events = indicator.0.lock().unwrap().clone();
+    // the progress is estimated by the planner (not by the table size)
+    assert_eq!(events[0].0, "started: public.events");
+    assert!(events[0].1 < 200, "{:?}", events);
+    assert_eq!(events[1], ("finished: public.events".to_string(), 89));
+    let dump_content = fs::read_to_string(&path).unwrap();
+    assert!(dump_content.contains("89\t"));
+    assert!(!dump_content.contains("90\t"));
+
+    // the conditions are checked before dumping
+    let error = dump("created > now()", Arc::new(EstimateIndicator::default()))
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        error,
+        "Invalid `query.dump_condition` of the table public.events: column \"created\" does not exist"
+    );
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn bytea_placeholders() {
+    let url = helpers::empty_database_url("bytea_placeholders");
+    let path = env::temp_dir().join("datanymizer_test_bytea_placeholders.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id int PRIMARY KEY, avatar bytea, thumbnail bytea, passport bytea);
+            INSERT INTO users VALUES
+                (1, '\\x0102', '\\x0102', '\\x0102'),
+                (2, '\\x0304', '\\x0304', '\\x0304'),
+                (3, NULL, NULL, NULL);",
+        )
+        .unwrap();
+
+    let config = r#"
+tables:
+  - name: users
+    rules:
+      avatar:
+        bytea_placeholder:
+          size: 40x30
+      thumbnail:
+        bytea_placeholder:
+          content: jpeg
+          size: 20x20
+      passport:
+        bytea_placeholder:
+          content: pdf
+          style: solid
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("bytea_placeholders_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<[Option<Vec<u8>>; 3]> = dst_client
+        .query(
+            "SELECT avatar, thumbnail, passport FROM users ORDER BY id",
+            &[],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| [row.get(0), row.get(1), row.get(2)])
+        .collect();
+
+    for [avatar, thumbnail, passport] in &rows[..2] {
+        // the PNG signature and the header with the size
+        let avatar = avatar.as_ref().unwrap();
+        assert!(avatar.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x28\0\0\0\x1e"));
+        assert!(avatar.ends_with(b"IEND\xae\x42\x60\x82"));
+        let thumbnail = thumbnail.as_ref().unwrap();
+        assert!(thumbnail.starts_with(&[0xff, 0xd8]) && thumbnail.ends_with(&[0xff, 0xd9]));
+        let passport = passport.as_ref().unwrap();
+        assert!(passport.starts_with(b"%PDF-1.4\n") && passport.ends_with(b"%%EOF\n"));
+    }
+    // every original value has its own placeholder
+    for (first, second) in rows[0].iter().zip(&rows[1]) {
+        assert_ne!(first, second);
+    }
+    assert_eq!(rows[2], [None, None, None]);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn interval_jitter() {
+    let url = helpers::empty_database_url("interval_jitter");
+    let path = env::temp_dir().join("datanymizer_test_interval_jitter.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE subscriptions (id int PRIMARY KEY, duration interval, grace interval, note interval);
+            INSERT INTO subscriptions VALUES
+                (1, '1 year 2 mons 3 days 04:05:06.789', '2 days', '1 day'),
+                (2, '-1 mons -2 days +03:00:00', '-5 days', '1 day'),
+                (3, '00:00:00.5', '-00:30:00', '1 day'),
+                (4, NULL, NULL, NULL);",
+        )
+        .unwrap();
+
+    let config = r#"
+tables:
+  - name: subscriptions
+    rules:
+      duration:
+        interval_jitter:
+          percent: 20
+      grace:
+        interval_jitter:
+          max: 12:00:00
+          round: hour
+          non_negative: true
+      note:
+        email: {}
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("interval_jitter_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<[Option<f64>; 3]> = dst_client
+        .query(
+            // `extract` counts a year as 365.25 days, the rule counts it as 12 months of 30 days
+            "SELECT (extract(epoch FROM duration) - extract(year FROM duration) * 5.25 * 86400)::float8, \
+            extract(epoch FROM grace)::float8, extract(epoch FROM note)::float8 FROM subscriptions ORDER BY id",
+            &[],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| [row.get(0), row.get(1), row.get(2)])
+        .collect();
+
+    // the original lengths (a month is 30 days)
+    for (row, duration) in rows.iter().zip([36_561_906.789, -2_754_000.0, 0.5]) {
+        let ratio = row[0].unwrap() / duration;
+        assert!((0.8..=1.2).contains(&ratio), "{:?}", row);
+    }
+    // the lengths change by up to 12 hours, the signs are kept (the negative results are clamped)
+    let day = 86_400.0;
+    let grace = rows[0][1].unwrap();
+    assert!((grace - 2.0 * day).abs() <= day / 2.0 && grace % 3600.0 == 0.0);
+    assert_eq!(rows[1][1], Some(0.0));
+    assert_eq!(rows[2][1], Some(0.0));
+    // the `email` rule is skipped for the `interval` column
+    for row in &rows[..3] {
+        assert_eq!(row[2], Some(day));
+    }
+    assert_eq!(rows[3], [None, None, None]);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn normalize_empty() {
+    let url = helpers::empty_database_url("normalize_empty");
+    let path = env::temp_dir().join("datanymizer_test_normalize_empty.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE EXTENSION IF NOT EXISTS citext;
+            CREATE TABLE users (id int PRIMARY KEY, bio text, phone varchar(20), nickname citext);
+            INSERT INTO users VALUES
+                (1, '', ' N/A ', '-'),
+                (2, 'n/a', '', 'unknown'),
+                (3, NULL, NULL, NULL),
+                (4, 'A bio', '+123', 'nick');",
+        )
+        .unwrap();
+
+    let config = r#"
+tables:
+  - name: users
+    rules:
+      bio:
+        normalize_empty:
+          rule:
+            redact:
+              hash_suffix_len: 0
+      phone:
+        normalize_empty:
+          on_null: transform
+          rule:
+            redact:
+              label: PHONE
+              hash_suffix_len: 0
+      nickname:
+        normalize_empty:
+          values: ["-", "Unknown"]
+          to: ""
+          rule:
+            none: ~
+"#;
+    let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+    let metrics = engine.enable_rule_timing();
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("normalize_empty_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<[Option<String>; 3]> = dst_client
+        .query("SELECT bio, phone, nickname FROM users ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| [row.get(0), row.get(1), row.get(2)])
+        .collect();
+
+    let s = |v: &str| Some(v.to_string());
+    assert_eq!(rows[0], [None, s("[PHONE]"), s("")]);
+    assert_eq!(rows[1], [None, s("[PHONE]"), s("")]);
+    assert_eq!(rows[2], [None, s("[PHONE]"), None]);
+    assert_eq!(rows[3], [s("[REDACTED]"), s("[PHONE]"), s("nick")]);
+
+    let normalized = |rule: &str| metrics.get(rule).unwrap().normalized;
+    assert_eq!(normalized("users.bio"), Some(2));
+    assert_eq!(normalized("users.phone"), Some(2));
+    assert_eq!(normalized("users.nickname"), Some(2));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn skip_if() {
+    let url = helpers::empty_database_url("skip_if");
+    let path = env::temp_dir().join("datanymizer_test_skip_if.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id int PRIMARY KEY, email text UNIQUE, name text, login text);
+            INSERT INTO users VALUES
+                (1, 'demo+1@example.com', 'Demo User', 'demo_1'),
+                (2, 'alice@corp.com', 'Alice Smith', 'alice'),
+                (3, 'qa@eu.example.com', 'Bob Jones', 'demo_bob');",
+        )
+        .unwrap();
+
+    let config = r#"
+skip_domains: ["example.com"]
+skip_patterns: ["^Demo "]
+tables:
+  - name: users
+    rules:
+      email:
+        email:
+          uniq: true
+      name:
+        person_name: {}
+      login:
+        skip_if:
+          prefixes: ["demo_"]
+          rule:
+            redact:
+              hash_suffix_len: 0
+"#;
+    let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+    let metrics = engine.enable_rule_timing();
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("skip_if_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<[String; 3]> = dst_client
+        .query("SELECT email, name, login FROM users ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| [row.get(0), row.get(1), row.get(2)])
+        .collect();
+
+    assert_eq!(rows[0], ["demo+1@example.com", "Demo User", "demo_1"]);
+    assert_ne!(rows[1][0], "alice@corp.com");
+    assert_ne!(rows[1][1], "Alice Smith");
+    assert_eq!(rows[1][2], "[REDACTED]");
+    assert_eq!(rows[2][0], "qa@eu.example.com");
+    assert_ne!(rows[2][1], "Bob Jones");
+    assert_eq!(rows[2][2], "demo_bob");
+
+    let skipped = |rule: &str| metrics.get(rule).unwrap().skipped;
+    assert_eq!(skipped("users.email"), Some(2));
+    assert_eq!(skipped("users.name"), Some(1));
+    assert_eq!(skipped("users.login"), Some(2));
+
+    fs::remove_file(path).unwrap();
+}
+
+// The kept values are registered before the dump, so the inner rule never generates them
+#[test]
+fn skip_if_uniq() {
+    let url = helpers::empty_database_url("skip_if_uniq");
+    let path = env::temp_dir().join("datanymizer_test_skip_if_uniq.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE skip_if_codes (id int PRIMARY KEY, code int UNIQUE);
+            INSERT INTO skip_if_codes VALUES (1, 5), (2, 1), (3, 7);",
+        )
+        .unwrap();
+
+    let config = r#"
+tables:
+  - name: skip_if_codes
+    rules:
+      code:
+        skip_if:
+          patterns: ["^1$"]
+          rule:
+            random_num:
+              min: 1
+              max: 3
+              uniq:
+                required: true
+                try_count: 1000
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("skip_if_uniq_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let mut codes: Vec<i32> = dst_client
+        .query("SELECT code FROM skip_if_codes ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    assert_eq!(codes[1], 1);
+    codes.sort_unstable();
+    assert_eq!(codes, [1, 2, 3]);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn hooks() {
+    let url = helpers::empty_database_url("hooks");
+    let path = env::temp_dir().join("datanymizer_test_hooks.sql");
+    let data_path = env::temp_dir().join("datanymizer_test_hooks_data.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE jobs (id int PRIMARY KEY, payload text);
+            CREATE TABLE feature_flags (name text PRIMARY KEY, enabled boolean);
+            CREATE TABLE users (id serial PRIMARY KEY, email text UNIQUE);
+            INSERT INTO jobs VALUES (1, 'a'), (2, 'b');
+            INSERT INTO feature_flags VALUES ('billing', true);
+            INSERT INTO users (email) VALUES ('alice@corp.com');",
+        )
+        .unwrap();
+
+    let config = r#"
+hooks:
+  pre_data:
+    - CREATE TABLE public.restore_log (step text)
+  post_data:
+    - TRUNCATE public.jobs;
+    - |
+      UPDATE public.feature_flags
+      SET enabled = false
+      WHERE name = 'billing';
+    - |
+      DO $$
+      BEGIN
+        INSERT INTO public.users (email) VALUES ('admin@example.com');
+        INSERT INTO public.restore_log VALUES ('post-data');
+      END
+      $$;
+tables:
+  - name: users
+    rules:
+      email:
+        email: {}
+"#;
+    let dump = |data_writer: Option<fs::File>| {
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap();
+        if let Some(w) = data_writer {
+            dumper = dumper.with_data_writer(w);
+        }
+        dumper
+            .dump(&mut Connection::new(
+                postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap(),
+                url.clone(),
+            ))
+            .unwrap();
+    };
+
+    dump(None);
+    let sql = fs::read_to_string(&path).unwrap();
+    let position = |s: &str| sql.find(s).unwrap();
+    // after the schema, before the data
+    assert!(
+        position("CREATE TABLE public.users")
+            < position("CREATE TABLE public.restore_log (step text);")
+    );
+    assert!(
+        position("CREATE TABLE public.restore_log (step text);")
+            < position("COPY \"public\".\"jobs\"")
+    );
+    // after the data and the sequence values, before the constraints
+    assert!(position("pg_catalog.setval") < position("TRUNCATE public.jobs;"));
+    assert!(position("END\n$$;") < position("ADD CONSTRAINT users_pkey"));
+
+    let dst_url = helpers::empty_database_url("hooks_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let count = |client: &mut postgres::Client, query: &str| -> i64 {
+        client.query_one(query, &[]).unwrap().get(0)
+    };
+    assert_eq!(count(&mut dst_client, "SELECT COUNT(*) FROM jobs"), 0);
+    assert_eq!(
+        count(
+            &mut dst_client,
+            "SELECT COUNT(*) FROM feature_flags WHERE enabled"
+        ),
+        0
+    );
+    assert_eq!(
+        count(
+            &mut dst_client,
+            "SELECT COUNT(*) FROM users WHERE email = 'admin@example.com'"
+        ),
+        1
+    );
+    assert_eq!(
+        count(&mut dst_client, "SELECT COUNT(*) FROM restore_log"),
+        1
+    );
+    // the source database isn't changed
+    assert_eq!(count(&mut client, "SELECT COUNT(*) FROM jobs"), 2);
+
+    // the hooks are in the data file
+    dump(Some(fs::File::create(&data_path).unwrap()));
+    let schema = fs::read_to_string(&path).unwrap();
+    let data = fs::read_to_string(&data_path).unwrap();
+    assert!(!schema.contains("hooks"));
+    assert!(
+        data.find("CREATE TABLE public.restore_log (step text);")
+            .unwrap()
+            < data.find("COPY").unwrap()
+    );
+    assert!(data.trim_end().ends_with("END\n$$;"));
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(data_path).unwrap();
+}
+
+#[test]
+fn presets() {
+    let url = helpers::empty_database_url("presets");
+    let path = env::temp_dir().join("datanymizer_test_presets.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    // the tables like the ones that ETL tools sync (only some of the preset tables)
+    client
+        .batch_execute(
+            "CREATE TABLE stripe_customers (
+                id text PRIMARY KEY, object text, email text, name text, phone text, description text,
+                address_city text, currency text, created bigint, livemode boolean,
+                _fivetran_synced timestamptz);
+            CREATE TABLE stripe_charges (
+                id text PRIMARY KEY, customer text, amount bigint, currency text, receipt_email text,
+                billing_details_name text, payment_method_details_card_last4 text, status text,
+                created bigint, _fivetran_synced timestamptz);
+            CREATE TABLE zendesk_tickets (
+                id bigint PRIMARY KEY, status text, subject text, description text, requester_id bigint,
+                requester_email text, created_at timestamptz, _sdc_batched_at timestamptz);
+            INSERT INTO stripe_customers VALUES
+                ('cus_1', 'customer', 'jane@example.com', 'Jane Roe', '+15550100', 'VIP', 'Boston', 'usd',
+                 1700000000, false, now());
+            INSERT INTO stripe_charges VALUES
+                ('ch_1', 'cus_1', 1000, 'usd', 'jane@example.com', 'Jane Roe', '4242', 'succeeded',
+                 1700000001, now());
+            INSERT INTO zendesk_tickets VALUES
+                (1, 'open', 'Refund for Jane Roe', 'Call me at +15550100', 7, 'jane@example.com', now(), now());",
+        )
+        .unwrap();
+
+    let config = r#"
+explicit: true
+preset: [stripe_sync, zendesk_sync]
+tables:
+  - name: stripe_customers
+    rules:
+      description:
+        none: ~
+"#;
+    let indicator = Arc::new(WarningIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    // all columns of the fixture tables are covered (`explicit`)
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+    assert_eq!(
+        indicator.0.lock().unwrap().clone(),
+        vec![
+            "the tables of the preset `stripe_sync` are not found: stripe_payment_methods",
+            "the tables of the preset `zendesk_sync` are not found: zendesk_users, zendesk_ticket_comments",
+        ]
+    );
+
+    let dst_url = helpers::empty_database_url("presets_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let customer = dst_client
+        .query_one(
+            "SELECT id, email, name, description, currency FROM stripe_customers",
+            &[],
+        )
+        .unwrap();
+    let email: String = customer.get("email");
+    assert_eq!(customer.get::<_, String>("id"), "cus_1");
+    assert_ne!(email, "jane@example.com");
+    assert_ne!(customer.get::<_, String>("name"), "Jane Roe");
+    // the user rule overrides the preset rule
+    assert_eq!(customer.get::<_, String>("description"), "VIP");
+    assert_eq!(customer.get::<_, String>("currency"), "usd");
+
+    let charge = dst_client
+        .query_one(
+            "SELECT receipt_email, payment_method_details_card_last4, amount FROM stripe_charges",
+            &[],
+        )
+        .unwrap();
+    // the emails are consistent in all tables
+    assert_eq!(charge.get::<_, String>("receipt_email"), email);
+    let last4: String = charge.get("payment_method_details_card_last4");
+    assert_eq!(last4.len(), 4);
+    assert!(last4.chars().all(|c| c.is_ascii_digit()));
+    assert_eq!(charge.get::<_, i64>("amount"), 1000);
+
+    let ticket = dst_client
+        .query_one(
+            "SELECT requester_email, subject, description FROM zendesk_tickets",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(ticket.get::<_, String>("requester_email"), email);
+    assert_ne!(ticket.get::<_, String>("subject"), "Refund for Jane Roe");
+    assert_ne!(
+        ticket.get::<_, String>("description"),
+        "Call me at +15550100"
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn int_ranges() {
+    let url = helpers::empty_database_url("int_ranges");
+    let path = env::temp_dir().join("datanymizer_test_int_ranges.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE items (id serial PRIMARY KEY, level smallint, ref integer);
+            SELECT setval('items_id_seq', 2147483640);
+            INSERT INTO items (level, ref) VALUES (32767, 300000000), (-32768, 1), (NULL, -300000000);",
+        )
+        .unwrap();
+
+    let dump = |config: &str| {
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap();
+        let client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+        dumper
+            .dump(&mut Connection::new(client, url.clone()))
+            .map_err(|e| e.to_string())
+    };
+    let config = |on_overflow: &str, start_at: i64| {
+        format!(
+            r#"
+on_overflow: {}
+sequences:
+  public.items.id:
+    start_at: {}
+tables:
+  - name: items
+    rules:
+      level:
+        random_num:
+          max: 32767
+      ref:
+        template:
+          format: "{{{{ prev.ref }}}}0"
+"#,
+            on_overflow, start_at
+        )
+    };
+
+    // the sequence value doesn't fit the serial column
+    let error = dump(&config("clamp", 2147483648)).unwrap_err();
+    assert!(
+        error.contains(
+            "public.items_id_seq (public.items.id): `start_at: 2147483648` is out of the `integer` range"
+        ),
+        "{}",
+        error
+    );
+
+    let error = dump(&config("error", 2147483647)).unwrap_err();
+    assert!(
+        error.contains(
+            "Invalid generated value in the table public.items, row 1, column `ref`: \
+            the value 3000000000 is out of the `integer` range"
+        ),
+        "{}",
+        error
+    );
+
+    dump(&config("clamp", 2147483647)).unwrap();
+    let dst_url = helpers::empty_database_url("int_ranges_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let rows: Vec<(i32, i32)> = dst_client
+        .query("SELECT id, ref FROM items ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            (2147483641, 2147483647),
+            (2147483642, 10),
+            (2147483643, -2147483648)
+        ]
+    );
+    let next: i64 = dst_client
+        .query_one("SELECT nextval('items_id_seq')", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(next, 2147483647);
+
+    fs::remove_file(path).unwrap();
+}
+
+// The data file of a fixed database is compared with the golden file (the output must not change with
+// refactoring). Set `DATANYMIZER_UPDATE_GOLDEN` to write the golden file again.
+#[test]
+fn golden_data() {
+    let url = helpers::empty_database_url("golden_data");
+    let schema_path = env::temp_dir().join("datanymizer_test_golden_schema.sql");
+    let data_path = env::temp_dir().join("datanymizer_test_golden_data.sql");
+    let golden_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/postgres/dumps/golden_data.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id serial PRIMARY KEY, name text, email varchar(50), note text);
+            CREATE TABLE orders (id serial PRIMARY KEY, user_id int REFERENCES users, total numeric(8, 2));
+            CREATE TABLE items (id int PRIMARY KEY, order_id int REFERENCES orders, title text);
+            CREATE TABLE audit (id int, message text);
+            INSERT INTO users (name, email, note) VALUES
+                ('Alice', 'alice@example.com', 'first note'),
+                ('Bob', NULL, 'second note'),
+                ('Carol', 'carol@example.com', NULL);
+            INSERT INTO orders (user_id, total) VALUES (1, 10.5), (1, 20), (3, 7.25);
+            INSERT INTO items SELECT i, i % 3 + 1, 'item ' || i FROM generate_series(1, 20) i;
+            INSERT INTO audit VALUES (1, 'secret');",
+        )
+        .unwrap();
+
+    let config = r#"
+filter:
+  data:
+    except:
+      - public.audit
+tables:
+  - name: users
+    rules:
+      name:
+        template:
+          format: "User {{ prev.id }}"
+      email:
+        redact:
+          salt: golden
+      note:
+        none: ~
+  - name: items
+    rules:
+      title:
+        redact:
+          label: ITEM
+          salt: golden
+    shuffle_rows:
+      method: client
+      seed: 42
+"#;
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&schema_path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_data_writer(fs::File::create(&data_path).unwrap());
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let data = fs::read_to_string(&data_path).unwrap();
+    if env::var_os("DATANYMIZER_UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_path, &data).unwrap();
+    }
+    assert_eq!(data, fs::read_to_string(&golden_path).unwrap());
+    let schema = fs::read_to_string(&schema_path).unwrap();
+    assert_eq!(schema.matches(POST_DATA_MARKER).count(), 1);
+
+    fs::remove_file(schema_path).unwrap();
+    fs::remove_file(data_path).unwrap();
+}
+
+#[test]
+fn reproducible_dumps() {
+    let url = helpers::empty_database_url("reproducible");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    // the tables without foreign keys have equal weights, `accounts` has two sequences
+    client
+        .batch_execute(
+            "CREATE SCHEMA billing;
+            CREATE TABLE accounts (
+                id serial PRIMARY KEY, number bigint GENERATED BY DEFAULT AS IDENTITY,
+                email text, name text, greeting text);
+            CREATE TABLE billing.accounts (id int PRIMARY KEY, email text);
+            CREATE TABLE notes (id int, body text);
+            CREATE TABLE tags (id int, title text);
+            CREATE TABLE events (id int, account_id int REFERENCES accounts, title text);
+            INSERT INTO accounts (email, name) SELECT 'user' || i || '@example.com', 'User ' || i
+                FROM generate_series(1, 30) i;
+            INSERT INTO billing.accounts SELECT i, 'user' || i || '@example.com' FROM generate_series(1, 10) i;
+            INSERT INTO notes SELECT i, 'note ' || i FROM generate_series(1, 5) i;
+            INSERT INTO tags SELECT i, 'tag ' || i FROM generate_series(1, 5) i;
+            INSERT INTO events SELECT i, i % 30 + 1, 'event ' || i FROM generate_series(1, 50) i;",
+        )
+        .unwrap();
+
+    let config = r#"
+consistent_salt: reproducible
+tables:
+  - name: public.accounts
+    rule_order:
+      - greeting
+    rules:
+      greeting:
+        template:
+          format: "Dear {{ final.name }} <{{ final.email }}>"
+      name:
+        consistent:
+          rule:
+            first_name: {}
+      email:
+        consistent:
+          rule:
+            email: {}
+  - name: billing.accounts
+    rules:
+      email:
+        consistent:
+          rule:
+            email: {}
+  - name: events
+    rules:
+      title:
+        redact:
+          salt: reproducible
+"#;
+    let dump = |name: &str| {
+        let path = env::temp_dir().join(format!("datanymizer_test_{}.sql", name));
+        let manifest = Arc::new(Mutex::new(Manifest::new(
+            "test".to_string(),
+            "config".to_string(),
+        )));
+        let client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            SilentIndicator,
+            vec![],
+        )
+        .unwrap()
+        .with_manifest(manifest.clone());
+        dumper
+            .dump(&mut Connection::new(client, url.clone()))
+            .unwrap();
+        drop(dumper);
+
+        // recent `pg_dump` versions emit `\restrict` and `\unrestrict` commands with random keys
+        let dump: Vec<_> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with("\\restrict ") && !line.starts_with("\\unrestrict "))
+            .map(|line| line.to_string())
+            .collect();
+        fs::remove_file(path).unwrap();
+        let manifest = manifest.lock().unwrap().clone();
+        (dump, manifest)
+    };
+
+    let (first, first_manifest) = dump("reproducible_1");
+    let (second, second_manifest) = dump("reproducible_2");
+    assert_eq!(first, second);
+    assert_eq!(first_manifest, second_manifest);
+
+    let copies: Vec<_> = first
+        .iter()
+        .filter(|line| line.starts_with("COPY "))
+        .map(|line| line.split_once('(').unwrap().0)
+        .collect();
+    assert_eq!(
+        copies,
+        vec![
+            "COPY \"public\".\"accounts\"",
+            "COPY \"billing\".\"accounts\"",
+            "COPY \"public\".\"events\"",
+            "COPY \"public\".\"notes\"",
+            "COPY \"public\".\"tags\"",
+        ]
+    );
+    assert!(!first.iter().any(|line| line.contains("user1@example.com")));
+}
+
+#[test]
+fn skip_data() {
+    let url = helpers::empty_database_url("skip_data");
+    let path = env::temp_dir().join("datanymizer_test_skip_data.sql");
+    let mut client = postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE users (id serial PRIMARY KEY, email text);
+            CREATE TABLE audit_logins (id serial PRIMARY KEY, user_id int REFERENCES users);
+            CREATE TABLE audit_exports (id serial PRIMARY KEY, payload text);
+            CREATE TABLE versions (id serial PRIMARY KEY, item text);
+            INSERT INTO users (email) VALUES ('alice@corp.com'), ('bob@corp.com');
+            INSERT INTO audit_logins (user_id) VALUES (1), (2), (1);
+            INSERT INTO audit_exports (payload) VALUES ('a'), ('b');
+            INSERT INTO versions (item) VALUES ('user 1'), ('user 2');",
+        )
+        .unwrap();
+
+    let config = r#"
+skip_data:
+  tables: [audit_*]
+  reset_sequences: false
+tables:
+  - name: users
+    rules:
+      email:
+        email: {}
+  - name: versions
+    skip_data: true
+"#;
+    let indicator = Arc::new(RecordingIndicator::default());
+    PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap()
+    .dump(&mut Connection::new(
+        postgres::Client::connect(url.as_str(), postgres::NoTls).unwrap(),
+        url.clone(),
+    ))
+    .unwrap();
+
+    let events = indicator.events();
+    assert_eq!(events[0], "dump_started: public.users");
+    for table in ["audit_exports", "audit_logins", "versions"] {
+        assert!(events.contains(&format!("table_skipped: public.{} (skip_data)", table)));
+    }
+    assert_eq!(events.last().unwrap(), "dump_finished: 1 tables, 2 rows");
+
+    let sql = fs::read_to_string(&path).unwrap();
+    // the DDL is kept
+    assert!(sql.contains("CREATE TABLE public.audit_logins"));
+    assert!(sql.contains("CREATE TABLE public.versions"));
+    assert!(!sql.contains("COPY \"public\".\"audit_logins\""));
+    assert!(!sql.contains("COPY \"public\".\"versions\""));
+    // the sequence of `versions` is restarted, the sequences of the `audit_*` tables aren't set
+    assert!(sql.contains("ALTER SEQUENCE public.versions_id_seq RESTART;"));
+    assert!(sql.contains("setval('public.users_id_seq', 2, true)"));
+    assert!(!sql.contains("audit_logins_id_seq', "));
+    assert!(!sql.contains("audit_logins_id_seq RESTART"));
+
+    let dst_url = helpers::empty_database_url("skip_data_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst_client = postgres::Client::connect(dst_url.as_str(), postgres::NoTls).unwrap();
+    let count = |client: &mut postgres::Client, query: &str| -> i64 {
+        client.query_one(query, &[]).unwrap().get(0)
+    };
+    assert_eq!(count(&mut dst_client, "SELECT COUNT(*) FROM users"), 2);
+    assert_eq!(
+        count(&mut dst_client, "SELECT COUNT(*) FROM audit_logins"),
+        0
+    );
+    assert_eq!(count(&mut dst_client, "SELECT COUNT(*) FROM versions"), 0);
+    // the sequences start from the beginning
+    let next_id: i32 = dst_client
+        .query_one(
+            "INSERT INTO versions (item) VALUES ('new') RETURNING id",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(next_id, 1);
+
+    fs::remove_file(path).unwrap();
+}
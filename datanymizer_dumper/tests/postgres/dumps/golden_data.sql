@@ -0,0 +1,69 @@
+SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET idle_in_transaction_session_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+SET xmloption = content;
+SET client_min_messages = warning;
+SET row_security = off;
+
+---
+--- Start dumping data
+---
+
+---
+--- Dump table: public.users
+---
+
+COPY "public"."users"("id", "name", "email", "note") FROM STDIN;
+1	User 1	[REDACTED-e9c0]	first note
+2	User 2	[REDACTED]	second note
+3	User 3	[REDACTED-19ab]	\N
+\.
+
+SELECT pg_catalog.setval('public.users_id_seq', 3, true);
+
+---
+--- Dump table: public.orders
+---
+
+COPY "public"."orders"("id", "user_id", "total") FROM STDIN;
+1	1	10.50
+2	1	20.00
+3	3	7.25
+\.
+
+SELECT pg_catalog.setval('public.orders_id_seq', 3, true);
+
+---
+--- Dump table: public.items
+---
+
+COPY "public"."items"("id", "order_id", "title") FROM STDIN;
+15	1	[ITEM-ed85]
+16	2	[ITEM-b9ce]
+11	3	[ITEM-5a22]
+2	3	[ITEM-9889]
+13	2	[ITEM-9205]
+19	2	[ITEM-e742]
+5	3	[ITEM-7125]
+12	1	[ITEM-d37e]
+1	2	[ITEM-226b]
+18	1	[ITEM-c8b4]
+10	2	[ITEM-7495]
+8	3	[ITEM-bd55]
+6	1	[ITEM-5734]
+9	1	[ITEM-ee4e]
+4	2	[ITEM-2d8c]
+20	3	[ITEM-7da7]
+17	3	[ITEM-8517]
+7	2	[ITEM-d2ce]
+14	3	[ITEM-c8f7]
+3	1	[ITEM-aa02]
+\.
+
+---
+--- End dumping data
+---
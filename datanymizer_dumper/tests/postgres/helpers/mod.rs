@@ -1,6 +1,7 @@
 use postgres::{Client, NoTls};
 use std::{
     env,
+    path::Path,
     process::{Child, ChildStdin, Command, Stdio},
     sync::Once,
 };
@@ -21,8 +22,8 @@ pub fn io(&mut self) -> ChildStdin {
         self.0.stdin.take().unwrap()
     }
 
-    pub fn close(&mut self) {
-        self.0.kill().unwrap();
+    pub fn wait(&mut self) {
+        self.0.wait().unwrap();
     }
 }
 
@@ -33,7 +34,7 @@ pub fn pg_dump_path() -> String {
 pub fn src_database_url() -> Url {
     Url::parse(
         env::var(DB_ENV_KEY)
-            .expect(format!("No {} environment variable", DB_ENV_KEY).as_str())
+            .unwrap_or_else(|_| panic!("No {} environment variable", DB_ENV_KEY))
             .as_str(),
     )
     .expect("Invalid database URL")
@@ -52,7 +53,7 @@ pub fn create_src_db() {
         let database_url = src_database_url();
         create_db(&database_url);
         pg_restore_command()
-            .args(&["-d", database_url.as_str()])
+            .args(["-d", database_url.as_str()])
             .arg(SRC_DUMP_PATH)
             .status()
             .expect("Error when restoring the test source database");
@@ -68,6 +69,13 @@ pub fn dst_client(name: &str) -> Client {
     client(&dst_database_url(name))
 }
 
+/// Creates an empty database for the tests that change tables
+pub fn empty_database_url(name: &str) -> Url {
+    let url = dst_database_url(name);
+    create_db(&url);
+    url
+}
+
 pub fn dst_wrapper(name: &str) -> DstWrapper {
     create_src_db();
 
@@ -83,6 +91,41 @@ pub fn dst_wrapper(name: &str) -> DstWrapper {
     )
 }
 
+/// Restores the SQL dump (it fails on errors)
+pub fn restore(url: &Url, path: &Path) {
+    let status = psql_command()
+        .args(["-v", "ON_ERROR_STOP=1", "-q", "-f"])
+        .arg(path)
+        .arg(url.as_str())
+        .status()
+        .unwrap();
+    assert!(status.success(), "Error when restoring {}", path.display());
+}
+
+/// Restores the custom-format archive (it fails on errors)
+pub fn restore_archive(url: &Url, path: &Path) {
+    let status = pg_restore_command()
+        .args(["--exit-on-error", "--no-owner", "-d", url.as_str()])
+        .arg(path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "Error when restoring {}", path.display());
+}
+
+/// Restores the directory dump with its `restore_parallel.sh`, returns whether it is succeeded
+pub fn restore_directory(url: &Url, dir: &Path) -> bool {
+    let psql_path = env::var(PSQL_PATH_KEY).unwrap_or("psql".to_string());
+    Command::new("sh")
+        .arg(dir.join("restore_parallel.sh"))
+        .arg(url.as_str())
+        .env("PSQL", psql_path)
+        .env("JOBS", "2")
+        .stdout(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
 fn create_db(url: &Url) {
     let db_name = url.path_segments().unwrap().next().unwrap().to_string();
 
@@ -115,16 +158,15 @@ fn pg_restore_command() -> Command {
 
 fn run_sql(cmd: &str, db_url: &str) {
     psql_command()
-        .args(&["-c", cmd])
+        .args(["-c", cmd])
         .arg(db_url)
         .status()
-        .expect(
-            format!(
+        .unwrap_or_else(|_| {
+            panic!(
                 "Error when running sql command, db: {}, cmd: {}",
                 db_url, cmd
             )
-            .as_str(),
-        );
+        });
 }
 
 fn client(url: &Url) -> Client {
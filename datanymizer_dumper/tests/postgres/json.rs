@@ -0,0 +1,118 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use serde_json::{json, Value as JsonValue};
+use std::{env, fs, sync::Arc};
+
+// `data` is `jsonb`, `meta` is `json` (with a newline and a quote that are escaped in the COPY format)
+const SCHEMA: &str = r#"
+    CREATE TABLE profiles (id int, data jsonb, meta json);
+    INSERT INTO profiles VALUES
+        (1, '{"email": "john@corp.com", "address": {"street": "Elm St", "zip": "12345"}, "age": 30,
+            "contacts": [{"phone": "555-01"}, {"phone": "555-02"}]}',
+            '{"note": "line 1\nline \"2\"", "token": "secret"}'),
+        (2, '{"email": "jane@corp.com", "contacts": []}', '{"note": "none"}'),
+        (3, NULL, NULL);
+"#;
+
+const CONFIG: &str = r#"
+tables:
+  - name: profiles
+    rules:
+      data:
+        json:
+          fields:
+            - path: /email
+              rule:
+                template:
+                  format: "user{{ prev.id }}@example.com"
+            - path: /address/street
+              rule:
+                template:
+                  format: "Main St"
+            - path: /age
+              rule:
+                random_num:
+                  min: 18
+                  max: 18
+            - path: /contacts/*/phone
+              rule:
+                template:
+                  format: "555-00"
+      meta:
+        json:
+          fields:
+            - path: /token
+              rule:
+                template:
+                  format: '\N'
+"#;
+
+#[test]
+fn fields() {
+    let src_url = helpers::empty_database_url("json_fields");
+    let mut client = Client::connect(src_url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let path = env::temp_dir().join("datanymizer_test_json_fields.sql");
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(CONFIG).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        Arc::new(SilentIndicator),
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, src_url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dst_url = helpers::empty_database_url("json_fields_restore");
+    helpers::restore(&dst_url, &path);
+    fs::remove_file(path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let rows: Vec<(i32, Option<String>, Option<String>)> = client
+        .query(
+            "SELECT id, data::text, meta::text FROM profiles ORDER BY id",
+            &[],
+        )
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2)))
+        .collect();
+    let parse = |value: &Option<String>| -> JsonValue {
+        serde_json::from_str(value.as_ref().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        parse(&rows[0].1),
+        json!({
+            "email": "user1@example.com",
+            "address": {"street": "Main St", "zip": "12345"},
+            "age": 18,
+            "contacts": [{"phone": "555-00"}, {"phone": "555-00"}],
+        })
+    );
+    assert_eq!(
+        parse(&rows[0].2),
+        json!({"note": "line 1\nline \"2\"", "token": null})
+    );
+
+    // the missing paths are skipped, the unchanged values are kept as they are
+    assert_eq!(
+        parse(&rows[1].1),
+        json!({"email": "user2@example.com", "contacts": []})
+    );
+    assert_eq!(rows[1].2.as_deref(), Some(r#"{"note": "none"}"#));
+
+    assert_eq!((rows[2].1.as_ref(), rows[2].2.as_ref()), (None, None));
+}
@@ -0,0 +1,131 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    manifest::Manifest,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use regex::Regex;
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE policies (id int PRIMARY KEY, number text, note text);
+    INSERT INTO policies
+        SELECT i,
+            'DE-' || (2000 + i % 20) || '-' || chr(65 + i % 26) || chr(90 - i % 26) || '-'
+                || lpad((i * 37 % 100000)::text, 5, '0'),
+            md5(i::text)
+        FROM generate_series(1, 200) i;
+    INSERT INTO policies VALUES (201, NULL, NULL);
+";
+
+const CONFIG: &str = r#"
+tables:
+  - name: policies
+    rules:
+      number:
+        learn_format:
+          sample_size: 50
+          uniq: true
+      note:
+        learn_format:
+          fallback:
+            redact: {}
+"#;
+
+#[derive(Default)]
+struct MessageIndicator(Mutex<Vec<String>>);
+
+impl Indicator for MessageIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+
+    fn warning_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(format!("WARNING: {}", msg));
+    }
+}
+
+#[test]
+fn learned_formats() {
+    let url = helpers::empty_database_url("learn_format");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let path = env::temp_dir().join("datanymizer_test_learn_format.sql");
+    let indicator = Arc::new(MessageIndicator::default());
+    let manifest = Arc::new(Mutex::new(Manifest::new(
+        "test".to_string(),
+        "config".to_string(),
+    )));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(CONFIG).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap()
+    .with_manifest(manifest.clone());
+    dumper.dump(&mut Connection::new(client, url)).unwrap();
+    drop(dumper);
+
+    let template = r"DE\-[0-9]{4}\-[A-Z]{2}\-[0-9]{5}";
+    let messages = indicator.0.lock().unwrap();
+    assert!(
+        messages.contains(&format!(
+            "The format of the column `policies.number` is learned: `{}` (50 of 50 sampled values)",
+            template
+        )),
+        "{}",
+        messages.join("\n")
+    );
+    assert!(
+        messages.iter().any(|m| m.starts_with(
+            "WARNING: The format of the column `policies.note` isn't learned, the fallback rule is used: "
+        )),
+        "{}",
+        messages.join("\n")
+    );
+
+    let manifest = manifest.lock().unwrap();
+    let formats = &manifest.tables["public.policies"].learned_formats;
+    assert_eq!(formats["number"].template.as_deref(), Some(template));
+    assert_eq!(formats["number"].samples, 50);
+    assert_eq!(formats["note"].template, None);
+    assert!(formats["note"].fallback.is_some());
+
+    let dump = fs::read_to_string(&path).unwrap();
+    let rows: Vec<Vec<&str>> = dump
+        .lines()
+        .skip_while(|line| !line.starts_with("COPY \"public\".\"policies\""))
+        .skip(1)
+        .take_while(|line| *line != "\\.")
+        .map(|line| line.split('\t').collect())
+        .collect();
+    assert_eq!(rows.len(), 201);
+
+    let re = Regex::new(&format!("^{}$", template)).unwrap();
+    let mut numbers = vec![];
+    for row in &rows {
+        if row[0] == "201" {
+            assert_eq!(row[1..], [r"\N", r"\N"]);
+            continue;
+        }
+        assert!(re.is_match(row[1]), "{}", row[1]);
+        assert!(row[2].starts_with("[REDACTED"), "{}", row[2]);
+        numbers.push(row[1]);
+    }
+    numbers.sort_unstable();
+    numbers.dedup();
+    assert_eq!(numbers.len(), 200);
+
+    fs::remove_file(path).unwrap();
+}
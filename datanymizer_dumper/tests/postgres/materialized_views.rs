@@ -0,0 +1,153 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper, schema_inspector::PgSchemaInspector},
+    Dumper, SchemaInspector,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs, sync::Arc};
+use url::Url;
+
+// `a_summary` depends on `b_domains` through the plain view `v_domains`,
+// so it must be refreshed after it (but it is first by name)
+const SCHEMA: &str = "
+    CREATE TABLE users (id int PRIMARY KEY, email text);
+    INSERT INTO users SELECT i, 'user' || i || '@corp.com' FROM generate_series(1, 20) i;
+    CREATE MATERIALIZED VIEW b_domains AS SELECT id, split_part(email, '@', 2) AS domain FROM users;
+    CREATE VIEW v_domains AS SELECT domain FROM b_domains;
+    CREATE MATERIALIZED VIEW a_summary AS SELECT domain, count(*) AS users FROM v_domains GROUP BY domain;
+    CREATE MATERIALIZED VIEW c_unpopulated AS SELECT email FROM users WITH NO DATA;
+";
+
+fn dump(url: &Url, name: &str, materialized_views: &str) -> String {
+    let path = env::temp_dir().join(format!("datanymizer_test_{}.sql", name));
+    let config = format!(
+        r#"
+materialized_views: {}
+tables:
+  - name: users
+    rules:
+      email:
+        template:
+          format: "user{{{{ prev.id }}}}@example.com"
+"#,
+        materialized_views
+    );
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(&config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        Arc::new(SilentIndicator),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    dump
+}
+
+fn src_url(name: &str) -> Url {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+    url
+}
+
+fn populated(client: &mut Client, name: &str) -> bool {
+    client
+        .query_one(
+            "SELECT relispopulated FROM pg_class WHERE relname = $1",
+            &[&name],
+        )
+        .unwrap()
+        .get(0)
+}
+
+#[test]
+fn refresh() {
+    let url = src_url("matviews_refresh_src");
+    let dump = dump(&url, "matviews_refresh", "refresh");
+    assert!(!dump.contains("COPY \"public\".\"b_domains\""));
+    assert!(!dump.contains("Type: MATERIALIZED VIEW DATA"));
+    let b = dump
+        .find("REFRESH MATERIALIZED VIEW public.b_domains;")
+        .unwrap();
+    let a = dump
+        .find("REFRESH MATERIALIZED VIEW public.a_summary;")
+        .unwrap();
+    assert!(b < a);
+    // after the data and the post-data objects
+    assert!(dump.find("users_pkey").unwrap() < b);
+    assert!(!dump.contains("REFRESH MATERIALIZED VIEW public.c_unpopulated"));
+
+    let dst_url = helpers::empty_database_url("matviews_refresh");
+    let path = env::temp_dir().join("datanymizer_test_matviews_refresh_restore.sql");
+    fs::write(&path, dump).unwrap();
+    helpers::restore(&dst_url, &path);
+    fs::remove_file(path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let rows = client
+        .query("SELECT domain, users FROM a_summary", &[])
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, String>(0), "example.com");
+    assert_eq!(rows[0].get::<_, i64>(1), 20);
+    assert!(!populated(&mut client, "c_unpopulated"));
+}
+
+#[test]
+fn skip() {
+    let url = src_url("matviews_skip_src");
+    let dump = dump(&url, "matviews_skip", "skip");
+    assert!(!dump.contains("REFRESH MATERIALIZED VIEW"));
+    assert!(dump.contains("CREATE MATERIALIZED VIEW public.b_domains"));
+
+    let dst_url = helpers::empty_database_url("matviews_skip");
+    let path = env::temp_dir().join("datanymizer_test_matviews_skip_restore.sql");
+    fs::write(&path, dump).unwrap();
+    helpers::restore(&dst_url, &path);
+    fs::remove_file(path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    assert!(!populated(&mut client, "b_domains"));
+    assert!(!populated(&mut client, "a_summary"));
+}
+
+#[test]
+fn inspector() {
+    let url = src_url("matviews_inspector");
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let mut connection = Connection::new(client, url.clone());
+    let inspector = PgSchemaInspector::default();
+
+    let tables = inspector.get_tables(&mut connection).unwrap();
+    let names: Vec<_> = tables.iter().map(|t| t.tablename.as_str()).collect();
+    assert_eq!(names, vec!["users"]);
+
+    let views = inspector.get_materialized_views(&mut connection).unwrap();
+    let summary: Vec<_> = views
+        .iter()
+        .map(|v| (v.name.as_str(), v.populated, v.depends_on.len()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("a_summary", true, 1),
+            ("b_domains", true, 0),
+            ("c_unpopulated", false, 0)
+        ]
+    );
+    assert!(views[0]
+        .depends_on
+        .contains(&("public".to_string(), "b_domains".to_string())));
+}
@@ -0,0 +1,91 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::{DumpSummary, Indicator},
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{memory, Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE contacts (id serial PRIMARY KEY, email text, backup_email text);
+    INSERT INTO contacts (email, backup_email)
+        SELECT 'user' || i || '@corp.com', 'backup' || i || '@corp.com' FROM generate_series(1, 1000) AS i;
+";
+
+// Every dump fills the uniqueness set of its own column (the sets are kept for the process)
+fn config(column: &str) -> String {
+    format!(
+        r#"
+tables:
+  - name: contacts
+    rules:
+      {}:
+        random_num:
+          uniq: true
+"#,
+        column
+    )
+}
+
+#[derive(Default)]
+struct Summary(Mutex<Option<DumpSummary>>);
+
+impl Indicator for Summary {
+    fn dump_finished(&self, summary: &DumpSummary) {
+        *self.0.lock().unwrap() = Some(summary.clone());
+    }
+}
+
+fn dump(
+    url: &url::Url,
+    column: &str,
+    max_memory: Option<u64>,
+) -> (anyhow::Result<()>, DumpSummary) {
+    let mut engine = Engine::new(Settings::from_yaml(&config(column)).unwrap());
+    engine.max_memory = max_memory;
+    let summary = Arc::new(Summary::default());
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        io::sink(),
+        summary.clone(),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let result = dumper.dump(&mut Connection::new(client, url.clone()));
+    drop(dumper);
+    let summary = summary.0.lock().unwrap().take().unwrap();
+    (result, summary)
+}
+
+#[test]
+fn max_memory() {
+    let url = helpers::empty_database_url("max_memory");
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+
+    let (result, summary) = dump(&url, "email", None);
+    result.unwrap();
+    // the values of all tests of the process are accounted
+    assert!(summary.memory.components[memory::UNIQUENESS_SETS].peak >= 1000 * 16);
+    assert!(summary.memory.peak >= 1000 * 16);
+
+    let (result, summary) = dump(&url, "backup_email", Some(1 << 10));
+    let error = result.unwrap_err().to_string();
+    assert!(
+        error.contains("The memory of the dump exceeds --max-memory 1kB"),
+        "{}",
+        error
+    );
+    assert_eq!(summary.error, Some(error));
+}
@@ -2,6 +2,31 @@
 
 mod helpers;
 
+mod archive;
+mod arrays;
+mod checkpoint;
 mod connector;
+mod copy_codec;
+mod cycles;
+mod dry_run;
 mod dumper;
+mod json;
+mod learn_format;
+mod materialized_views;
+mod memory;
+mod only_columns;
+mod outbox;
+mod output_lint;
+mod parallel;
+mod partitions;
+mod row_limits;
+mod schema_filter;
 mod schema_inspector;
+mod seed;
+mod service;
+mod shuffle;
+mod stats_only;
+mod subset;
+mod temp_disk;
+mod tenant;
+mod unique_against;
@@ -0,0 +1,122 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::{DumpSummary, Indicator},
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = r#"
+    CREATE TABLE events (
+        id int PRIMARY KEY,
+        kind text NOT NULL,
+        payload text,
+        note text NOT NULL DEFAULT 'n/a',
+        serial int GENERATED ALWAYS AS IDENTITY
+    );
+
+    INSERT INTO events (id, kind, payload, note)
+    SELECT i, 'kind ' || i, repeat('x', 100), 'note ' || i FROM generate_series(1, 10) AS i;
+    ANALYZE events;
+"#;
+
+#[derive(Default)]
+struct Summary(Mutex<Option<DumpSummary>>);
+
+impl Indicator for Summary {
+    fn dump_finished(&self, summary: &DumpSummary) {
+        *self.0.lock().unwrap() = Some(summary.clone());
+    }
+}
+
+fn dump(url: &url::Url, config: &str, name: &str) -> (anyhow::Result<()>, String, DumpSummary) {
+    let indicator = Arc::new(Summary::default());
+    let path = env::temp_dir().join(format!("datanymizer_test_only_columns_{}.sql", name));
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    let result = dumper.dump(&mut Connection::new(
+        Client::connect(url.as_str(), NoTls).unwrap(),
+        url.clone(),
+    ));
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    let summary = indicator.0.lock().unwrap().clone().unwrap_or_default();
+    (result, dump, summary)
+}
+
+#[test]
+fn projection() {
+    let url = helpers::empty_database_url("only_columns");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let config = r#"
+        tables:
+          - name: events
+            only_columns: [kind, id]
+            rules:
+              kind:
+                template:
+                  format: "event {{ prev.id }}"
+    "#;
+    let (result, dump, summary) = dump(&url, config, "projection");
+    result.unwrap();
+    assert!(dump.contains("COPY \"public\".\"events\"(\"id\", \"kind\") FROM STDIN;\n1\tevent 1\n"));
+    // payload, note and serial are omitted (the estimate is by the stored sizes, about 110 bytes per row)
+    let saved = summary.saved_bytes["public.events"];
+    assert!((1000..=1200).contains(&saved), "{}", saved);
+
+    let dst_url = helpers::empty_database_url("only_columns_restore");
+    let path = env::temp_dir().join("datanymizer_test_only_columns_restore.sql");
+    fs::write(&path, dump).unwrap();
+    helpers::restore(&dst_url, &path);
+    fs::remove_file(path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let row = client
+        .query_one(
+            "SELECT kind, payload, note, serial FROM events WHERE id = 3",
+            &[],
+        )
+        .unwrap();
+    let (kind, payload, note, serial): (String, Option<String>, String, i32) =
+        (row.get(0), row.get(1), row.get(2), row.get(3));
+    assert_eq!(kind, "event 3");
+    assert_eq!(payload, None);
+    assert_eq!(note, "n/a");
+    assert!(serial > 0);
+}
+
+#[test]
+fn required_columns() {
+    let url = helpers::empty_database_url("only_columns_required");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let config = r#"
+        tables:
+          - name: events
+            only_columns: [id, payload]
+    "#;
+    let (result, _, _) = dump(&url, config, "required");
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "The NOT NULL columns without defaults of the table public.events are not in `only_columns`: kind \
+        (the table can't be restored without them, add them to `only_columns` or add defaults to them)"
+    );
+}
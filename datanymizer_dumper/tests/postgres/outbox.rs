@@ -0,0 +1,183 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::{DumpSummary, Indicator},
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = r#"
+    CREATE TABLE outbox (
+        id int PRIMARY KEY,
+        event_type text,
+        payload jsonb,
+        created_by text NOT NULL
+    );
+
+    INSERT INTO outbox VALUES
+        (1, 'user_registered', '{"email": "john@mail.com", "name": "John", "phones": ["555-01", "555-02"]}', 'john'),
+        (2, 'user_registered', '{"email": "ann\\@mail.com", "name": "Ann\tSmith", "age": 30}', 'ann'),
+        (3, 'order_paid', '{"order_id": 7, "amount": 10.5}', 'ann'),
+        (4, 'user_deleted', '{"email": "john@mail.com"}', 'john'),
+        (5, NULL, '{"email": "old@mail.com"}', 'john'),
+        (6, 'user_registered', NULL, 'bob');
+"#;
+
+#[derive(Default)]
+struct RecordingIndicator {
+    messages: Mutex<Vec<String>>,
+    summary: Mutex<Option<DumpSummary>>,
+}
+
+impl Indicator for RecordingIndicator {
+    fn dump_finished(&self, summary: &DumpSummary) {
+        *self.summary.lock().unwrap() = Some(summary.clone());
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        self.messages.lock().unwrap().push(msg.to_string());
+    }
+}
+
+fn payloads(client: &mut Client) -> Vec<(i32, Option<Value>)> {
+    client
+        .query("SELECT id, payload::text FROM outbox ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let payload: Option<String> = row.get(1);
+            (
+                row.get(0),
+                payload.map(|p| serde_json::from_str(&p).unwrap()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn outbox_events() {
+    let url = helpers::empty_database_url("outbox");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    for (policy, expected_ids) in [
+        ("null_payload", vec![1, 2, 3, 4, 5, 6]),
+        ("drop", vec![1, 2, 3, 6]),
+    ] {
+        let config = format!(
+            r#"
+            tables:
+              - name: outbox
+                rules:
+                  created_by:
+                    template:
+                      format: user
+                outbox:
+                  event_rules:
+                    user_registered:
+                      email:
+                        template:
+                          format: "user{{{{ _0 | length }}}}@example.com"
+                      phones.*:
+                        template:
+                          format: "555-00"
+                      age:
+                        template:
+                          format: "40"
+                    order_paid: {{}}
+                  unknown_events: {}
+            "#,
+            policy
+        );
+        let indicator = Arc::new(RecordingIndicator::default());
+        let path = env::temp_dir().join(format!("datanymizer_test_outbox_{}.sql", policy));
+        let mut dumper = PgDumper::new(
+            Engine::new(Settings::from_yaml(&config).unwrap()),
+            None,
+            helpers::pg_dump_path(),
+            fs::File::create(&path).unwrap(),
+            indicator.clone(),
+            vec![],
+        )
+        .unwrap();
+        dumper
+            .dump(&mut Connection::new(
+                Client::connect(url.as_str(), NoTls).unwrap(),
+                url.clone(),
+            ))
+            .unwrap();
+        drop(dumper);
+
+        let summary = indicator.summary.lock().unwrap().clone().unwrap();
+        let mut events = BTreeMap::new();
+        events.insert(
+            "public.outbox".to_string(),
+            [
+                ("NULL", 1),
+                ("order_paid", 1),
+                ("user_deleted", 1),
+                ("user_registered", 3),
+            ]
+            .into_iter()
+            .map(|(event_type, count)| (event_type.to_string(), count))
+            .collect(),
+        );
+        assert_eq!(summary.outbox_events, events);
+        let messages = indicator.messages.lock().unwrap();
+        assert!(
+            messages.iter().any(|m| m
+                == "Outbox events of public.outbox: NULL (1), order_paid (1), user_deleted (1), \
+                user_registered (3)"),
+            "{}",
+            messages.join("\n")
+        );
+        assert!(
+            messages.iter().any(|m| m
+                .starts_with("Warning: public.outbox has the event types without rules")
+                && m.ends_with("NULL: 1, user_deleted: 1")),
+            "{}",
+            messages.join("\n")
+        );
+
+        let dst_url = helpers::empty_database_url(&format!("outbox_{}", policy));
+        helpers::restore(&dst_url, &path);
+        let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+        let payloads = payloads(&mut dst);
+        assert_eq!(
+            payloads.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            expected_ids
+        );
+        let payload = |id: i32| payloads.iter().find(|(i, _)| *i == id).unwrap().1.clone();
+        assert_eq!(
+            payload(1),
+            Some(
+                json!({"email": "user13@example.com", "name": "John", "phones": ["555-00", "555-00"]})
+            )
+        );
+        assert_eq!(
+            payload(2),
+            Some(json!({"email": "user13@example.com", "name": "Ann\tSmith", "age": 40}))
+        );
+        assert_eq!(payload(3), Some(json!({"order_id": 7, "amount": 10.5})));
+        assert_eq!(payload(6), None);
+        if policy == "null_payload" {
+            assert_eq!(payload(4), None);
+            assert_eq!(payload(5), None);
+        }
+        let created_by: i64 = dst
+            .query_one("SELECT count(*) FROM outbox WHERE created_by = 'user'", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(created_by as usize, expected_ids.len());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper, output_lint::OutputLinter},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use std::{env, fs, io::Write};
+
+#[test]
+fn source_dump() {
+    let path = env::temp_dir().join("datanymizer_test_output_lint.sql");
+    let settings = Settings::new("tests/postgres/configs/simple.yml".to_string()).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap();
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    dumper.dump(&mut connection).unwrap();
+    drop(dumper);
+
+    // the functions, the triggers, the domains, etc. of the source database (the data of `actor` only)
+    let mut linter = OutputLinter::default();
+    linter.lint_sql_file(&path).unwrap();
+    let report = linter.into_report();
+    assert_eq!(report.errors, vec![]);
+    assert!(report.statements > 100, "{}", report);
+    assert_eq!((report.copy_blocks, report.rows), (1, 200));
+
+    // a broken rewrite
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(file, "DROP TABLE IF EXISTS (public.actor;").unwrap();
+    drop(file);
+    let mut linter = OutputLinter::default();
+    linter.lint_sql_file(&path).unwrap();
+    let report = linter.into_report();
+    assert_eq!(report.error_count, 1, "{}", report);
+    assert_eq!(report.errors[0].message, "`(` is not closed");
+
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,124 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::{Indicator, TableInfo, TableStats},
+    postgres::{connector::Connection, dumper::PgDumper, IsolationLevel},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE customers (id serial PRIMARY KEY, email text);
+    CREATE TABLE orders (id serial PRIMARY KEY, customer_id int REFERENCES customers, note text);
+    CREATE TABLE items (id int PRIMARY KEY, order_id int REFERENCES orders, name text);
+    CREATE TABLE audit_log (id serial PRIMARY KEY, message text);
+    CREATE TABLE settings (key text PRIMARY KEY, value text);
+    INSERT INTO customers (email) SELECT 'user' || i || '@corp.com' FROM generate_series(1, 300) i;
+    INSERT INTO orders (customer_id, note) SELECT 1 + i % 300, 'note ' || i FROM generate_series(1, 500) i;
+    INSERT INTO items SELECT i, 1 + i % 500, 'item ' || i FROM generate_series(1, 800) i;
+    INSERT INTO audit_log (message) SELECT 'login ' || i FROM generate_series(1, 20) i;
+    INSERT INTO settings VALUES ('theme', 'dark');
+";
+
+fn config(jobs: usize) -> String {
+    format!(
+        r#"
+seed: 42
+jobs: {}
+skip_data: [audit_log]
+tables:
+  - name: customers
+    rules:
+      email:
+        email: {{}}
+  - name: orders
+    rules:
+      note:
+        words: {{}}
+"#,
+        jobs
+    )
+}
+
+#[derive(Default)]
+struct Events(Mutex<Vec<String>>);
+
+impl Indicator for Events {
+    fn table_started(&self, table: &TableInfo) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("started: {}", table.name));
+    }
+
+    fn table_finished(&self, stats: &TableStats) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("finished: {} {}", stats.name, stats.rows));
+    }
+
+    fn debug_msg(&self, msg: &str) {
+        if msg.contains("parallel") {
+            self.0.lock().unwrap().push(msg.to_string());
+        }
+    }
+}
+
+fn dump(url: &Url, jobs: usize, isolation_level: Option<IsolationLevel>) -> (String, Vec<String>) {
+    let path = env::temp_dir().join(format!("datanymizer_test_parallel_{}.sql", jobs));
+    let events = Arc::new(Events::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(&config(jobs)).unwrap()),
+        isolation_level,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        events.clone(),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    // without the random keys of `\restrict` (pg_dump 17.6+)
+    let dump = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with("\\restrict") && !line.starts_with("\\unrestrict"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    fs::remove_file(path).unwrap();
+    let mut events = events.0.lock().unwrap().clone();
+    events.sort();
+    (dump, events)
+}
+
+#[test]
+fn same_dump_with_jobs() {
+    let url = helpers::empty_database_url("parallel");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let (sequential, sequential_events) = dump(&url, 1, Some(IsolationLevel::RepeatableRead));
+    assert!(sequential.contains("COPY \"public\".\"items\""));
+    assert!(!sequential.contains("@corp.com"));
+    assert!(sequential.contains("SELECT pg_catalog.setval('public.orders_id_seq', 500, true);"));
+
+    for isolation_level in [Some(IsolationLevel::RepeatableRead), None] {
+        let (parallel, mut events) = dump(&url, 3, isolation_level);
+        assert_eq!(parallel, sequential);
+        assert_eq!(events[0], "Dump the data with 3 parallel connections...");
+        // every table is started and finished once
+        events.remove(0);
+        assert_eq!(events, sequential_events);
+    }
+}
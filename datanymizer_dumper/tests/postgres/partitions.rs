@@ -0,0 +1,168 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper, schema_inspector::PgSchemaInspector},
+    Dumper, SchemaInspector,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs, sync::Arc};
+use url::Url;
+
+// `events_2023_02` is partitioned too (the multi-level partitioning)
+const SCHEMA: &str = "
+    CREATE TABLE events (id int, created date, kind text, email text) PARTITION BY RANGE (created);
+    CREATE TABLE events_2023_01 PARTITION OF events FOR VALUES FROM ('2023-01-01') TO ('2023-02-01');
+    CREATE TABLE events_2023_02 PARTITION OF events FOR VALUES FROM ('2023-02-01') TO ('2023-03-01')
+        PARTITION BY LIST (kind);
+    CREATE TABLE events_2023_02_login PARTITION OF events_2023_02 FOR VALUES IN ('login');
+    CREATE TABLE events_2023_02_other PARTITION OF events_2023_02 DEFAULT;
+    INSERT INTO events
+        SELECT i, '2023-01-01'::date + i, CASE WHEN i % 2 = 0 THEN 'login' ELSE 'logout' END,
+            'user' || i || '@corp.com'
+        FROM generate_series(0, 57) AS i;
+    ANALYZE;
+";
+
+fn dump(url: &Url, name: &str, partitions: &str) -> String {
+    let path = env::temp_dir().join(format!("datanymizer_test_{}.sql", name));
+    let config = format!(
+        r#"
+partitions: {}
+tables:
+  - name: events
+    rules:
+      email:
+        template:
+          format: "user{{{{ prev.id }}}}@example.com"
+"#,
+        partitions
+    );
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(&config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        Arc::new(SilentIndicator),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    dump
+}
+
+fn src_url(name: &str) -> Url {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+    url
+}
+
+// The restored rows of the partitions (all of them are anonymized)
+fn restore(name: &str, dump: String) -> Vec<(String, i64)> {
+    let dst_url = helpers::empty_database_url(name);
+    let path = env::temp_dir().join(format!("datanymizer_test_{}_restore.sql", name));
+    fs::write(&path, dump).unwrap();
+    helpers::restore(&dst_url, &path);
+    fs::remove_file(path).unwrap();
+
+    let mut client = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let anonymized: i64 = client
+        .query_one(
+            "SELECT count(*) FROM events WHERE email = 'user' || id || '@example.com'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(anonymized, 58);
+    client
+        .query(
+            "SELECT tableoid::regclass::text, count(*) FROM events GROUP BY 1 ORDER BY 1",
+            &[],
+        )
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect()
+}
+
+fn restored_partitions() -> Vec<(String, i64)> {
+    vec![
+        ("events_2023_01".to_string(), 31),
+        ("events_2023_02_login".to_string(), 13),
+        ("events_2023_02_other".to_string(), 14),
+    ]
+}
+
+#[test]
+fn inspector() {
+    let url = src_url("partitions_inspector");
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let mut connection = Connection::new(client, url.clone());
+    let inspector = PgSchemaInspector::default();
+
+    let tables = inspector.get_tables(&mut connection).unwrap();
+    let partitions: Vec<_> = tables
+        .iter()
+        .map(|t| {
+            (
+                t.tablename.as_str(),
+                t.partitioned,
+                t.partition_of.as_deref(),
+                t.size,
+            )
+        })
+        .collect();
+    assert_eq!(
+        partitions,
+        vec![
+            ("events", true, None, 58),
+            ("events_2023_01", false, Some("public.events"), 31),
+            ("events_2023_02", true, Some("public.events"), 27),
+            ("events_2023_02_login", false, Some("public.events"), 13),
+            ("events_2023_02_other", false, Some("public.events"), 14),
+        ]
+    );
+}
+
+#[test]
+fn parent() {
+    let url = src_url("partitions_parent_src");
+    let dump = dump(&url, "partitions_parent", "parent");
+    assert_eq!(dump.matches("COPY \"public\".\"events\"(").count(), 1);
+    assert!(!dump.contains("COPY \"public\".\"events_2023"));
+    assert!(!dump.contains("@corp.com"));
+
+    assert_eq!(restore("partitions_parent", dump), restored_partitions());
+}
+
+#[test]
+fn children() {
+    let url = src_url("partitions_children_src");
+    let dump = dump(&url, "partitions_children", "children");
+    assert!(!dump.contains("COPY \"public\".\"events\"("));
+    assert!(!dump.contains("COPY \"public\".\"events_2023_02\"("));
+    for partition in [
+        "events_2023_01",
+        "events_2023_02_login",
+        "events_2023_02_other",
+    ] {
+        assert_eq!(
+            dump.matches(&format!("COPY \"public\".\"{}\"(", partition))
+                .count(),
+            1
+        );
+    }
+    // the partitions get the rules of `events`
+    assert!(!dump.contains("@corp.com"));
+
+    assert_eq!(restore("partitions_children", dump), restored_partitions());
+}
@@ -0,0 +1,121 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper, IsolationLevel},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE countries (code text PRIMARY KEY);
+    CREATE TABLE users (id bigserial PRIMARY KEY, name text);
+    CREATE TABLE events (id serial PRIMARY KEY, payload text);
+
+    INSERT INTO countries SELECT 'c' || i FROM generate_series(1, 30) AS i;
+    INSERT INTO users (name) SELECT 'user' || i FROM generate_series(1, 100) AS i;
+    INSERT INTO events (payload) SELECT repeat('x', 200) FROM generate_series(1, 1000);
+    ANALYZE;
+";
+
+const CONFIG: &str = r#"
+default_limit: 10
+tables:
+  - name: countries
+    limit: none
+  - name: events
+    limit: none
+    sample: 50%
+"#;
+
+#[derive(Default)]
+struct DebugIndicator(Mutex<Vec<String>>);
+
+impl Indicator for DebugIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+fn value(client: &mut Client, query: &str) -> i64 {
+    client.query_one(query, &[]).unwrap().get(0)
+}
+
+#[test]
+fn row_limits() {
+    let url = helpers::empty_database_url("row_limits");
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+
+    let path = env::temp_dir().join("datanymizer_test_row_limits.sql");
+    let indicator = Arc::new(DebugIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(CONFIG).unwrap()),
+        Some(IsolationLevel::RepeatableRead),
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+
+    let messages = indicator.0.lock().unwrap().clone();
+    assert!(
+        messages
+            .iter()
+            .any(|m| m == "[Limit] public.users: 10 rows"),
+        "{}",
+        messages.join("\n")
+    );
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.starts_with("[Limit] public.events:") && m.ends_with("(a 50% sample)")),
+        "{}",
+        messages.join("\n")
+    );
+    assert!(!messages
+        .iter()
+        .any(|m| m.starts_with("[Limit] public.countries")));
+
+    let dst_url = helpers::empty_database_url("row_limits_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+
+    assert_eq!(value(&mut dst, "SELECT count(*) FROM countries"), 30);
+    assert_eq!(value(&mut dst, "SELECT count(*) FROM users"), 10);
+    assert!(value(&mut dst, "SELECT count(*) FROM events") < 1000);
+
+    // the sequences follow the dumped rows (the next inserts don't collide with them)
+    for (table, sequence) in [("users", "users_id_seq"), ("events", "events_id_seq")] {
+        assert_eq!(
+            value(
+                &mut dst,
+                &format!(
+                    "SELECT CASE WHEN is_called THEN last_value ELSE 0 END FROM {}",
+                    sequence
+                )
+            ),
+            value(
+                &mut dst,
+                &format!("SELECT COALESCE(max(id), 0)::bigint FROM {}", table)
+            ),
+            "{}",
+            table
+        );
+    }
+    assert!(value(&mut dst, "SELECT last_value FROM users_id_seq") < 100);
+
+    fs::remove_file(path).unwrap();
+}
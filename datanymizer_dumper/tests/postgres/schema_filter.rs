@@ -0,0 +1,97 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{
+        connector::Connection,
+        dumper::PgDumper,
+        schema_inspector::{PgSchemaInspector, SchemaFilter},
+        table::PgTable,
+        IsolationLevel,
+    },
+    Dumper, SchemaInspector, Table,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs};
+
+const SCHEMA: &str = "
+    CREATE SCHEMA tenant_a;
+    CREATE SCHEMA tenant_b;
+    CREATE SCHEMA shared;
+    CREATE TABLE shared.users (id int PRIMARY KEY, email text);
+    CREATE TABLE tenant_a.orders (id int PRIMARY KEY, user_id int REFERENCES shared.users);
+    CREATE TABLE tenant_b.orders (id int PRIMARY KEY, user_id int REFERENCES shared.users);
+    INSERT INTO shared.users VALUES (1, 'alice@corp.com');
+    INSERT INTO tenant_a.orders VALUES (10, 1);
+    INSERT INTO tenant_b.orders VALUES (20, 1);
+";
+
+fn filter() -> SchemaFilter {
+    SchemaFilter::new(
+        vec!["tenant_a".to_string(), "shared".to_string()],
+        vec!["shared".to_string()],
+    )
+}
+
+#[test]
+fn schema_filter() {
+    let url = helpers::empty_database_url("schema_filter");
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+    let connection = || Connection::new(Client::connect(url.as_str(), NoTls).unwrap(), url.clone());
+
+    let deps = |inspector: &PgSchemaInspector| {
+        let mut connection = connection();
+        let tables = inspector.get_tables(&mut connection).unwrap();
+        let orders = tables
+            .iter()
+            .find(|t| t.get_full_name() == "tenant_a.orders")
+            .unwrap();
+        let deps = inspector.get_dependencies(&mut connection, orders).unwrap();
+        let names =
+            |tables: &[PgTable]| -> Vec<_> { tables.iter().map(|t| t.get_full_name()).collect() };
+        (names(&tables), names(&deps))
+    };
+    let (tables, dependencies) = deps(&PgSchemaInspector::new(filter()));
+    assert_eq!(tables, ["tenant_a.orders"]);
+    // the foreign keys to the excluded schemas don't bring their tables back
+    assert!(dependencies.is_empty());
+    let (tables, dependencies) = deps(&PgSchemaInspector::default());
+    assert_eq!(tables.len(), 3);
+    assert_eq!(dependencies, ["shared.users"]);
+
+    let path = env::temp_dir().join("datanymizer_test_schema_filter.sql");
+    PgDumper::new(
+        Engine::new(Settings::from_yaml("tables: []").unwrap()),
+        Some(IsolationLevel::RepeatableRead),
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        SilentIndicator,
+        vec![],
+    )
+    .unwrap()
+    .with_schema_filter(filter())
+    .dump(&mut connection())
+    .unwrap();
+
+    // the schema sections agree with the data
+    let dump = fs::read_to_string(&path).unwrap();
+    assert!(dump.contains("CREATE TABLE tenant_a.orders"));
+    assert!(dump.contains("COPY \"tenant_a\".\"orders\""));
+    for excluded in [
+        "tenant_b.orders",
+        "shared.users",
+        "\"tenant_b\"",
+        "\"shared\"",
+    ] {
+        assert!(
+            !dump.contains(&format!("CREATE TABLE {}", excluded))
+                && !dump.contains(&format!("COPY {}", excluded)),
+            "{}",
+            excluded
+        );
+    }
+}
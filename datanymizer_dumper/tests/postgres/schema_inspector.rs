@@ -1,9 +1,17 @@
 use super::helpers;
 
 use datanymizer_dumper::{
-    postgres::{connector::Connection, schema_inspector::PgSchemaInspector, table::PgTable},
+    postgres::{
+        connector::Connection,
+        schema_inspector::{PgSchemaInspector, UniqueIndex},
+        table::PgTable,
+    },
     SchemaInspector, Table,
 };
+use postgres::{Client, NoTls};
+use std::time::{Duration, Instant};
+
+const MANY_TABLES: usize = 2000;
 
 fn find_table<'a>(tables: &'a [PgTable], full_name: &str) -> &'a PgTable {
     tables
@@ -15,10 +23,226 @@ fn find_table<'a>(tables: &'a [PgTable], full_name: &str) -> &'a PgTable {
 #[test]
 fn get_tables() {
     let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
-    let inspector = PgSchemaInspector;
+    let inspector = PgSchemaInspector::default();
     let tables = inspector.get_tables(&mut connection).unwrap();
 
     let table = find_table(&tables, "public.actor");
     assert_eq!(table.tablename, "actor");
     assert_eq!(table.schemaname, "public");
+
+    let names: Vec<_> = table
+        .sequences
+        .iter()
+        .map(|s| (s.column.as_str(), s.full_name.as_str()))
+        .collect();
+    assert_eq!(names, vec![("actor_id", "public.actor_actor_id_seq")]);
+}
+
+#[test]
+fn get_column_details() {
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    let table = PgTable::new("actor".to_string(), "public".to_string());
+    let columns = PgSchemaInspector::default()
+        .get_column_details(&mut connection, &table)
+        .unwrap();
+
+    assert_eq!(columns.len(), 4);
+    assert_eq!(columns[0].name, "actor_id");
+    assert_eq!(columns[0].data_type, "integer");
+    assert!(!columns[0].nullable);
+    assert_eq!(
+        columns[0].default.as_deref(),
+        Some("nextval('actor_actor_id_seq'::regclass)")
+    );
+    assert_eq!(columns[1].name, "first_name");
+    assert_eq!(columns[1].data_type, "character varying(45)");
+    assert_eq!(columns[1].default, None);
+}
+
+#[test]
+fn get_unique_indexes() {
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    let table = PgTable::new("actor".to_string(), "public".to_string());
+    let indexes = PgSchemaInspector::default()
+        .get_unique_indexes(&mut connection, &table)
+        .unwrap();
+
+    assert_eq!(
+        indexes,
+        vec![UniqueIndex {
+            name: "actor_pkey".to_string(),
+            primary: true,
+            columns: vec!["actor_id".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn get_foreign_key_counts() {
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    let counts = PgSchemaInspector::default()
+        .get_foreign_key_counts(&mut connection)
+        .unwrap();
+
+    let film_actor = counts["public.film_actor"];
+    assert_eq!(film_actor.outgoing, 2);
+    assert_eq!(film_actor.incoming, 0);
+    assert_eq!(counts["public.actor"].outgoing, 0);
+    assert_eq!(counts["public.actor"].incoming, 1);
+}
+
+#[test]
+fn get_foreign_key_links() {
+    let mut connection = Connection::new(helpers::src_client(), helpers::src_database_url());
+    let links = PgSchemaInspector::default()
+        .get_foreign_key_links(&mut connection)
+        .unwrap();
+
+    let link = links
+        .iter()
+        .find(|l| l.table == "public.film_actor" && l.referenced_table == "public.actor")
+        .unwrap();
+    assert_eq!(link.name, "film_actor_actor_id_fkey");
+}
+
+#[test]
+fn get_expression_indexes() {
+    let mut client = helpers::src_client();
+    // the index is not visible to other tests (the transaction is rolled back)
+    client
+        .batch_execute(
+            "BEGIN;
+            CREATE INDEX actor_lower_first_name_idx ON public.actor (lower(first_name::text))
+            WHERE last_name LIKE 'A%';",
+        )
+        .unwrap();
+    let mut connection = Connection::new(client, helpers::src_database_url());
+    let actor = PgTable::new("actor".to_string(), "public".to_string());
+
+    let indexes = PgSchemaInspector::default()
+        .get_expression_indexes(&mut connection, &actor)
+        .unwrap();
+    connection.client.batch_execute("ROLLBACK").unwrap();
+
+    assert_eq!(indexes.len(), 1);
+    let index = &indexes[0];
+    assert_eq!(index.name, "actor_lower_first_name_idx");
+    assert!(index
+        .definition
+        .starts_with("CREATE INDEX actor_lower_first_name_idx"));
+    assert!(index.references("first_name"));
+    assert!(index.references("last_name"));
+    assert!(!index.references("actor_id"));
+}
+
+#[test]
+fn many_tables() {
+    let mut client = helpers::src_client();
+    // the tables are not visible to other tests (the transaction is rolled back)
+    client
+        .batch_execute(&format!(
+            "BEGIN;
+            CREATE SCHEMA many_tables;
+            DO $$
+            BEGIN
+                FOR i IN 1..{} LOOP
+                    EXECUTE format('CREATE TABLE many_tables.t%s (id int, value int)', i);
+                END LOOP;
+            END $$;",
+            MANY_TABLES
+        ))
+        .unwrap();
+    let mut connection = Connection::new(client, helpers::src_database_url());
+
+    let started = Instant::now();
+    let tables = PgSchemaInspector::default().ordered_tables(&mut connection);
+    let duration = started.elapsed();
+    connection.client.batch_execute("ROLLBACK").unwrap();
+
+    let generated: Vec<_> = tables
+        .iter()
+        .filter(|(t, _)| t.schemaname == "many_tables")
+        .collect();
+    assert_eq!(generated.len(), MANY_TABLES);
+    assert_eq!(generated[0].0.columns.len(), 2);
+    // the catalog is queried with a few set-based queries, not with several queries per table
+    assert!(
+        duration < Duration::from_secs(5),
+        "the inspection took {:?}",
+        duration
+    );
+}
+
+#[test]
+fn extension_tables() {
+    let mut client = helpers::src_client();
+    // the table is not visible to other tests (the transaction is rolled back)
+    client
+        .batch_execute(
+            "BEGIN;
+            CREATE TABLE public.extension_table (id int);
+            ALTER EXTENSION plpgsql ADD TABLE public.extension_table;",
+        )
+        .unwrap();
+    let mut connection = Connection::new(client, helpers::src_database_url());
+
+    let tables = PgSchemaInspector::default()
+        .get_tables(&mut connection)
+        .unwrap();
+    connection.client.batch_execute("ROLLBACK").unwrap();
+
+    let table = find_table(&tables, "public.extension_table");
+    assert_eq!(table.extension.as_deref(), Some("plpgsql"));
+    assert_eq!(table.hypertable, None);
+    assert_eq!(find_table(&tables, "public.actor").extension, None);
+}
+
+#[test]
+fn table_sizes() {
+    let url = helpers::empty_database_url("table_sizes");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE unanalyzed (id int) WITH (autovacuum_enabled = false);
+            INSERT INTO unanalyzed SELECT generate_series(1, 500);
+            CREATE TABLE zero_pages (id int, value text) WITH (autovacuum_enabled = false);
+            INSERT INTO zero_pages SELECT i, repeat('x', 100) FROM generate_series(1, 1000) i;
+            ANALYZE zero_pages;
+            -- the stale stats of a table without analyzed pages
+            UPDATE pg_catalog.pg_class SET relpages = 0, reltuples = 50 WHERE relname = 'zero_pages';
+            CREATE TABLE analyzed (id int) WITH (autovacuum_enabled = false);
+            INSERT INTO analyzed SELECT generate_series(1, 1000);
+            ANALYZE analyzed;
+            CREATE TABLE empty (id int);
+            ANALYZE empty;
+            CREATE VIEW analyzed_view AS SELECT * FROM analyzed;",
+        )
+        .unwrap();
+
+    let sizes = |precise_size: bool| {
+        let mut connection =
+            Connection::new(Client::connect(url.as_str(), NoTls).unwrap(), url.clone())
+                .with_precise_size(precise_size);
+        let tables = PgSchemaInspector::default()
+            .get_tables(&mut connection)
+            .unwrap();
+        assert_eq!(connection.take_warnings(), Vec::<String>::new());
+
+        let view = PgTable::new("analyzed_view".to_string(), "public".to_string());
+        assert_eq!(
+            PgSchemaInspector::default()
+                .get_table_size(&mut connection, &view)
+                .unwrap(),
+            0
+        );
+
+        ["unanalyzed", "zero_pages", "analyzed", "empty"]
+            .iter()
+            .map(|name| find_table(&tables, &format!("public.{}", name)).get_size())
+            .collect::<Vec<_>>()
+    };
+
+    // the estimates without the page stats are `reltuples` (none for the unanalyzed table)
+    assert_eq!(sizes(false), vec![0, 50, 1000, 0]);
+    assert_eq!(sizes(true), vec![500, 1000, 1000, 0]);
 }
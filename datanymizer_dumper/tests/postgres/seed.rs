@@ -0,0 +1,108 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::SilentIndicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{env, fs, sync::Arc};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE customers (id int PRIMARY KEY, name text, email text, code text, joined_at timestamptz);
+    CREATE TABLE payments (id int PRIMARY KEY, amount numeric, note text);
+    INSERT INTO customers
+        SELECT i, 'name' || i, 'user' || i || '@corp.com', 'C-' || i, '2020-01-01'::timestamptz + i * interval '1 day'
+        FROM generate_series(1, 50) i;
+    INSERT INTO payments SELECT i, i * 10, 'note ' || i FROM generate_series(1, 50) i;
+";
+
+fn config(seed: Option<u64>) -> String {
+    format!(
+        r#"
+{}
+tables:
+  - name: customers
+    rules:
+      name:
+        person_name: {{}}
+      email:
+        email: {{}}
+      code:
+        template:
+          format: "C-{{{{ get_random(start=1000, end=1000000) }}}}"
+      joined_at:
+        datetime:
+          from: 2000-01-01T00:00:00+00:00
+          to: 2020-12-31T00:00:00+00:00
+  - name: payments
+    rules:
+      amount:
+        random_num:
+          min: 1
+          max: 1000
+      note:
+        consistent:
+          rule:
+            words: {{}}
+"#,
+        seed.map(|seed| format!("seed: {}", seed))
+            .unwrap_or_default()
+    )
+}
+
+/// The data of the dump (the COPY sections)
+fn dump(url: &Url, seed: Option<u64>, dump_first: &str) -> String {
+    let path = env::temp_dir().join(format!(
+        "datanymizer_test_seed_{}.sql",
+        seed.unwrap_or_default()
+    ));
+    let settings =
+        Settings::from_yaml(&format!("dump_first: [{}]\n{}", dump_first, config(seed))).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        Arc::new(SilentIndicator),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let dump = fs::read_to_string(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    let mut sections: Vec<String> = vec![];
+    let mut lines = dump.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("COPY ") {
+            let mut section = vec![line];
+            section.extend(lines.by_ref().take_while(|line| *line != "\\."));
+            sections.push(section.join("\n"));
+        }
+    }
+    // the table order is changed by `dump_first`
+    sections.sort();
+    sections.join("\n")
+}
+
+#[test]
+fn same_seed_same_dump() {
+    let url = helpers::empty_database_url("seed");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let first = dump(&url, Some(42), "customers");
+    assert!(first.contains("COPY \"public\".\"customers\""));
+    assert!(!first.contains("@corp.com"));
+    // the table order doesn't change the values
+    assert_eq!(dump(&url, Some(42), "payments"), first);
+    assert_ne!(dump(&url, Some(43), "customers"), first);
+    assert_ne!(dump(&url, None, "customers"), dump(&url, None, "customers"));
+}
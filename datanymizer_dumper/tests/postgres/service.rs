@@ -0,0 +1,251 @@
+use super::helpers;
+
+use datanymizer_dumper::service::{self, Service, ServiceOptions};
+use postgres::{Client, NoTls};
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::Server;
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE users (id int PRIMARY KEY, email text NOT NULL);
+    INSERT INTO users VALUES (1, 'alice@corp.com'), (2, 'bob@corp.com');
+";
+
+const CONFIG: &str = "
+tables:
+  - name: users
+    rules:
+      email:
+        email: {}
+";
+
+struct TestService {
+    addr: SocketAddr,
+    server: Arc<Server>,
+    output_dir: PathBuf,
+    /// Sent in the `Authorization` header
+    token: Option<String>,
+}
+
+impl TestService {
+    fn start(name: &str, max_jobs: usize, allowed_env: &[&str], token: Option<&str>) -> Self {
+        let output_dir = env::temp_dir().join(format!("datanymizer_test_service_{}", name));
+        let _ = fs::remove_dir_all(&output_dir);
+
+        let server = Arc::new(service::bind("127.0.0.1:0", token.is_some()).unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let service = Service::new(ServiceOptions {
+            output_dir: output_dir.clone(),
+            max_jobs,
+            pg_dump_location: helpers::pg_dump_path(),
+            allowed_env: allowed_env.iter().map(ToString::to_string).collect(),
+            allowed_secrets: vec![],
+            token: token.map(ToString::to_string),
+        });
+        let s = server.clone();
+        thread::spawn(move || service.serve(&s));
+
+        Self {
+            addr,
+            server,
+            output_dir,
+            token: token.map(ToString::to_string),
+        }
+    }
+
+    /// A minimal HTTP client
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let authorization = self
+            .token
+            .as_ref()
+            .map(|t| format!("Authorization: Bearer {}\r\n", t))
+            .unwrap_or_default();
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            authorization,
+            body.len(),
+            body
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn submit(&self, database_env: &str, output: &str) -> (u16, Value) {
+        self.request(
+            "POST",
+            "/jobs",
+            Some(json!({
+                "database": format!("env:{}", database_env),
+                "config": CONFIG,
+                "output": output,
+            })),
+        )
+    }
+
+    /// Polls the job until it is finished
+    fn wait(&self, id: &str) -> Value {
+        let started = Instant::now();
+        loop {
+            let (status, job) = self.request("GET", &format!("/jobs/{}", id), None);
+            assert_eq!(status, 200);
+            if !["running", "cancelling"].contains(&job["status"].as_str().unwrap()) {
+                return job;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(60),
+                "The job isn't finished: {}",
+                job
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for TestService {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// Creates the source database, its URL is in the environment variable (the jobs refer to it)
+fn source(name: &str) -> (Url, String) {
+    let url = helpers::empty_database_url(name);
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+    let var = format!("DATANYMIZER_TEST_SERVICE_{}", name.to_uppercase());
+    env::set_var(&var, url.as_str());
+    (url, var)
+}
+
+#[test]
+fn job() {
+    let (_, var) = source("service_job");
+    let service = TestService::start("job", 2, &[&var, "DATANYMIZER_TEST_SERVICE_MISSING"], None);
+
+    let (status, job) = service.submit(&var, "daily/users.sql");
+    assert_eq!(status, 201);
+    assert_eq!(job["output"], "daily/users.sql");
+    let id = job["id"].as_str().unwrap().to_string();
+
+    let job = service.wait(&id);
+    assert_eq!(job["status"], "succeeded", "{}", job);
+    assert_eq!(job["error"], Value::Null);
+    assert_eq!(job["rows"], 2);
+    let users = job["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["table"] == "public.users")
+        .unwrap();
+    assert_eq!(users["status"], "finished");
+    assert_eq!(users["rows"], 2);
+
+    let dump = fs::read_to_string(service.output_dir.join("daily/users.sql")).unwrap();
+    assert!(dump.contains("COPY \"public\".\"users\""));
+    assert!(!dump.contains("alice@corp.com"));
+
+    let (status, jobs) = service.request("GET", "/jobs", None);
+    assert_eq!(status, 200);
+    assert_eq!(jobs["jobs"][0]["id"], id.as_str());
+
+    // the finished jobs can't be cancelled
+    let (status, _) = service.request("DELETE", &format!("/jobs/{}", id), None);
+    assert_eq!(status, 409);
+
+    let (status, body) = service.submit("DATANYMIZER_TEST_SERVICE_MISSING", "missing.sql");
+    assert_eq!(status, 201, "{}", body);
+    let job = service.wait(body["id"].as_str().unwrap());
+    assert_eq!(job["status"], "failed");
+    assert_eq!(
+        job["error"],
+        "The environment variable `DATANYMIZER_TEST_SERVICE_MISSING` is not set"
+    );
+}
+
+#[test]
+fn cancel() {
+    let (url, var) = source("service_cancel");
+    let service = TestService::start("cancel", 1, &[&var], None);
+
+    // the job waits for the lock
+    let mut locker = Client::connect(url.as_str(), NoTls).unwrap();
+    locker
+        .batch_execute("BEGIN; LOCK TABLE users IN ACCESS EXCLUSIVE MODE;")
+        .unwrap();
+
+    let (status, job) = service.submit(&var, "users.sql");
+    assert_eq!(status, 201);
+    let id = job["id"].as_str().unwrap().to_string();
+
+    let (status, body) = service.submit(&var, "other.sql");
+    assert_eq!(status, 429);
+    assert_eq!(body["error"], "Too many running jobs (at most 1)");
+
+    let (status, job) = service.request("DELETE", &format!("/jobs/{}", id), None);
+    assert_eq!(status, 202);
+    assert_eq!(job["status"], "cancelling");
+
+    locker.batch_execute("COMMIT").unwrap();
+    let job = service.wait(&id);
+    assert_eq!(job["status"], "cancelled", "{}", job);
+    assert!(!service.output_dir.join("users.sql").exists());
+}
+
+#[test]
+fn security() {
+    let (url, var) = source("service_security");
+    let mut service = TestService::start(
+        "security",
+        1,
+        &[&var, "DATANYMIZER_TEST_SERVICE_WRONG_PASSWORD"],
+        Some("t0ken"),
+    );
+
+    let (status, body) = service.submit("PATH", "users.sql");
+    assert_eq!(status, 400);
+    assert_eq!(
+        body["error"],
+        "The environment variable `PATH` is not allowed (see `--allow-env`)"
+    );
+
+    // the resolved URL and its password aren't in the errors
+    let mut wrong = url.clone();
+    wrong.set_username("datanymizer_test_nobody").unwrap();
+    wrong.set_password(Some("s3cr3t-pass")).unwrap();
+    env::set_var("DATANYMIZER_TEST_SERVICE_WRONG_PASSWORD", wrong.as_str());
+    let (status, body) = service.submit("DATANYMIZER_TEST_SERVICE_WRONG_PASSWORD", "users.sql");
+    assert_eq!(status, 201, "{}", body);
+    let job = service.wait(body["id"].as_str().unwrap());
+    assert_eq!(job["status"], "failed");
+    let error = job["error"].as_str().unwrap();
+    assert!(!error.contains("s3cr3t-pass"), "{}", error);
+    assert!(!error.contains("datanymizer_test_nobody:"), "{}", error);
+
+    service.token = Some("wrong".to_string());
+    let (status, body) = service.request("GET", "/jobs", None);
+    assert_eq!(status, 401);
+    assert_eq!(body["error"], "Unauthorized");
+    service.token = None;
+    assert_eq!(service.request("GET", "/jobs", None).0, 401);
+}
@@ -0,0 +1,148 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper, IsolationLevel},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE events (id int PRIMARY KEY, name text NOT NULL);
+    CREATE TABLE orders (id int PRIMARY KEY, amount int NOT NULL);
+    CREATE TABLE logs (id int PRIMARY KEY);
+
+    INSERT INTO events SELECT i, 'event ' || i FROM generate_series(1, 300) i;
+    INSERT INTO orders SELECT i, i * 10 FROM generate_series(1, 300) i;
+    INSERT INTO logs SELECT i FROM generate_series(1, 300) i;
+";
+
+const CONFIG: &str = r#"
+tables:
+  - name: events
+    rules:
+      name:
+        template:
+          format: "{{ _0 }}!"
+    shuffle_rows:
+      seed: 42
+      memory_limit: 1kB
+  - name: orders
+    rules: {}
+    shuffle_rows:
+      method: server
+      seed: 42
+  - name: logs
+    rules: {}
+"#;
+
+#[derive(Default)]
+struct DebugIndicator(Mutex<Vec<String>>);
+
+impl Indicator for DebugIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+fn dump(client: Client, url: &Url, path: &Path) -> Vec<String> {
+    let indicator = Arc::new(DebugIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(CONFIG).unwrap()),
+        Some(IsolationLevel::RepeatableRead),
+        helpers::pg_dump_path(),
+        fs::File::create(path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let messages = indicator.0.lock().unwrap();
+    messages.clone()
+}
+
+// The first column of the rows of the table in the dump (in the dumped order)
+fn dumped_ids(path: &Path, table: &str) -> Vec<i32> {
+    let dump = fs::read_to_string(path).unwrap();
+    let header = format!("COPY \"public\".\"{}\"", table);
+    dump.lines()
+        .skip_while(|line| !line.starts_with(&header))
+        .skip(1)
+        .take_while(|line| *line != "\\.")
+        .map(|line| line.split('\t').next().unwrap().parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn shuffled_tables() {
+    let url = helpers::empty_database_url("shuffle");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let path = env::temp_dir().join("datanymizer_test_shuffle.sql");
+    let messages = dump(client, &url, &path);
+    assert!(
+        messages
+            .iter()
+            .any(|m| m == "Shuffled tables: public.events (client), public.orders (server)"),
+        "{}",
+        messages.join("\n")
+    );
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.starts_with("[Dumping: public.events] Shuffled with")),
+        "{}",
+        messages.join("\n")
+    );
+
+    let sorted: Vec<i32> = (1..=300).collect();
+    let events = dumped_ids(&path, "events");
+    let orders = dumped_ids(&path, "orders");
+    assert_ne!(events, sorted);
+    assert_ne!(orders, sorted);
+    assert_eq!(dumped_ids(&path, "logs"), sorted);
+
+    // the same order with the same seed
+    let second_path = env::temp_dir().join("datanymizer_test_shuffle_2.sql");
+    dump(
+        Client::connect(url.as_str(), NoTls).unwrap(),
+        &url,
+        &second_path,
+    );
+    assert_eq!(dumped_ids(&second_path, "events"), events);
+    assert_eq!(dumped_ids(&second_path, "orders"), orders);
+
+    // the same rows are restored
+    let dst_url = helpers::empty_database_url("shuffle_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let row = dst
+        .query_one(
+            "SELECT count(*), sum(id)::int, count(*) FILTER (WHERE name = 'event ' || id || '!') FROM events",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0), 300);
+    assert_eq!(row.get::<_, i32>(1), 45150);
+    assert_eq!(row.get::<_, i64>(2), 300);
+    let row = dst
+        .query_one("SELECT count(*), sum(amount)::int FROM orders", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, i64>(0), 300);
+    assert_eq!(row.get::<_, i32>(1), 451500);
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(second_path).unwrap();
+}
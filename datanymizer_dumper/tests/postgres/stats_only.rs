@@ -0,0 +1,79 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    postgres::{connector::Connection, dumper::PgDumper},
+    projection::{CountingSink, StatsProjection},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{sync::Arc, time::Duration};
+
+const SCHEMA: &str = "
+    CREATE TABLE countries (code text PRIMARY KEY);
+    CREATE TABLE users (id serial PRIMARY KEY, email text, name text);
+    CREATE TABLE audit_log (id serial PRIMARY KEY, message text);
+
+    INSERT INTO countries SELECT 'c' || i FROM generate_series(1, 30) AS i;
+    INSERT INTO users (email, name) SELECT 'user' || i || '@corp.com', 'name' || i FROM generate_series(1, 2000) AS i;
+    INSERT INTO audit_log (message) SELECT 'login ' || i FROM generate_series(1, 100) AS i;
+    ANALYZE;
+";
+
+const CONFIG: &str = r#"
+skip_data: [audit_log]
+tables:
+  - name: users
+    rules:
+      email:
+        email:
+          uniq: true
+"#;
+
+#[test]
+fn stats_only() {
+    let url = helpers::empty_database_url("stats_only");
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+
+    let projection = Arc::new(StatsProjection::new(100));
+    let sink = CountingSink::default();
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(CONFIG).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        sink.clone(),
+        projection.clone(),
+        vec![],
+    )
+    .unwrap()
+    .with_projection(projection.clone());
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+
+    let written = sink.written();
+    let report = projection.report(written, Duration::from_secs(1));
+    let tables: Vec<_> = report
+        .tables
+        .iter()
+        .map(|t| (t.table.as_str(), t.estimated_rows, t.sampled_rows))
+        .collect();
+    // the skipped tables are not projected, the small tables are dumped in full
+    assert_eq!(
+        tables,
+        [("public.countries", 30, 30), ("public.users", 2000, 100)]
+    );
+
+    let users = &report.tables[1];
+    assert!(users.row_bytes > 10.0);
+    assert_eq!(users.bytes, (users.row_bytes * 2000.0) as u64);
+    assert!(users.memory_bytes >= 2000 * 16);
+    // only the sample is dumped
+    assert!(report.bytes > written);
+    assert!(report.duration_ms >= 1000);
+    assert_eq!(report.peak_memory_bytes, users.memory_bytes);
+}
@@ -0,0 +1,177 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper, IsolationLevel},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE countries (code text PRIMARY KEY);
+    CREATE TABLE users (
+        id int PRIMARY KEY,
+        country text NOT NULL REFERENCES countries,
+        referrer_id int CONSTRAINT users_referrer_id_fkey REFERENCES users,
+        manager_id int REFERENCES users,
+        created_at date NOT NULL
+    );
+    CREATE TABLE products (id int PRIMARY KEY, name text);
+    CREATE TABLE orders (id int PRIMARY KEY, user_id int NOT NULL REFERENCES users);
+    CREATE TABLE order_items (
+        order_id int REFERENCES orders,
+        position int,
+        product_id int NOT NULL REFERENCES products,
+        PRIMARY KEY (order_id, position)
+    );
+    CREATE TABLE comments (
+        id int PRIMARY KEY,
+        user_id int NOT NULL CONSTRAINT comments_user_id_fkey REFERENCES users,
+        parent_id int CONSTRAINT comments_parent_id_fkey REFERENCES comments
+    );
+    -- without a primary key
+    CREATE TABLE audit_log (user_id int REFERENCES users, message text);
+    CREATE TABLE settings (name text PRIMARY KEY);
+
+    INSERT INTO countries VALUES ('de'), ('fr'), ('it'), ('es');
+    INSERT INTO users VALUES
+        (1, 'de', NULL, NULL, '2030-01-01'),
+        (2, 'fr', 1, 3, '2030-01-02'),
+        (3, 'it', NULL, 3, '2020-01-01'),
+        (4, 'es', 5, NULL, '2020-01-01'),
+        (5, 'es', 3, NULL, '2020-01-01');
+    INSERT INTO products SELECT i, 'product ' || i FROM generate_series(1, 10) i;
+    INSERT INTO orders VALUES (1, 1), (2, 2), (3, 4), (4, 5);
+    INSERT INTO order_items VALUES (1, 1, 1), (1, 2, 2), (2, 1, 3), (3, 1, 4), (4, 1, 5);
+    INSERT INTO comments VALUES (1, 1, NULL), (2, 4, 1), (3, 5, 2), (4, 5, NULL);
+    INSERT INTO audit_log VALUES (1, 'a'), (2, 'b'), (3, 'c'), (NULL, 'd'), (5, 'e');
+    INSERT INTO settings VALUES ('x'), ('y');
+";
+
+#[derive(Default)]
+struct DebugIndicator(Mutex<Vec<String>>);
+
+impl Indicator for DebugIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+fn values(client: &mut Client, query: &str) -> Vec<String> {
+    client
+        .query(query, &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect()
+}
+
+#[test]
+fn coherent_subset() {
+    let url = helpers::empty_database_url("subset");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let config = r#"
+tables: []
+subset:
+  roots:
+    users: "created_at > '2025-01-01'"
+  children:
+    - table: orders
+    - table: order_items
+    - table: comments
+      foreign_key: comments_user_id_fkey
+    - table: comments
+      foreign_key: comments_parent_id_fkey
+      max_depth: 5
+      max_rows: 1
+  cut_foreign_keys: [users_referrer_id_fkey]
+"#;
+    let path = env::temp_dir().join("datanymizer_test_subset.sql");
+    let indicator = Arc::new(DebugIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        Some(IsolationLevel::RepeatableRead),
+        helpers::pg_dump_path(),
+        fs::File::create(&path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    dumper
+        .dump(&mut Connection::new(client, url.clone()))
+        .unwrap();
+    drop(dumper);
+
+    let messages = indicator.0.lock().unwrap().clone();
+    for line in [
+        "[Subset] public.users: 4 rows (root, parents)",
+        "[Subset] public.comments: 2 rows (children)",
+        "[Subset] public.audit_log: 4 rows (referencing)",
+        "[Subset] 22 rows in 7 tables, the other tables are dumped as is",
+    ] {
+        assert!(
+            messages.iter().any(|m| m == line),
+            "{}",
+            messages.join("\n")
+        );
+    }
+
+    // the dump is restored with all foreign keys
+    let dst_url = helpers::empty_database_url("subset_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+
+    // the roots (1, 2), the manager of 2 (3) and the author of the pulled comment 2 (4),
+    // the referrer of 4 (5) is cut, the referrer of 2 is selected anyway
+    assert_eq!(
+        values(
+            &mut dst,
+            "SELECT id || ':' || COALESCE(referrer_id::text, '-') FROM users ORDER BY id"
+        ),
+        vec!["1:-", "2:1", "3:-", "4:-"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT code FROM countries ORDER BY 1"),
+        vec!["de", "es", "fr", "it"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT id::text FROM orders ORDER BY 1"),
+        vec!["1", "2"]
+    );
+    assert_eq!(
+        values(
+            &mut dst,
+            "SELECT order_id || '.' || position FROM order_items ORDER BY 1"
+        ),
+        vec!["1.1", "1.2", "2.1"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT id::text FROM products ORDER BY id"),
+        vec!["1", "2", "3"]
+    );
+    // the comment of 1 and one reply (`max_rows`), the orders of 4 are not pulled (`max_depth`)
+    assert_eq!(
+        values(&mut dst, "SELECT id::text FROM comments ORDER BY id"),
+        vec!["1", "2"]
+    );
+    assert_eq!(
+        values(
+            &mut dst,
+            "SELECT COALESCE(user_id::text, '-') FROM audit_log ORDER BY 1"
+        ),
+        vec!["-", "1", "2", "3"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT name FROM settings ORDER BY 1"),
+        vec!["x", "y"]
+    );
+
+    fs::remove_file(path).unwrap();
+}
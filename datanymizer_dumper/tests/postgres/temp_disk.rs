@@ -0,0 +1,181 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings, TempDisk};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE events (id int PRIMARY KEY, name text NOT NULL);
+    INSERT INTO events SELECT i, 'event number ' || i FROM generate_series(1, 300) i;
+";
+
+const CONFIG: &str = r#"
+tables:
+  - name: events
+    rules:
+      name:
+        template:
+          format: "{{ _0 }}!"
+"#;
+
+const SHUFFLED_CONFIG: &str = r#"
+tables:
+  - name: events
+    rules:
+      name:
+        template:
+          format: "{{ _0 }}!"
+    shuffle_rows:
+      memory_limit: 1kB
+"#;
+
+#[derive(Default)]
+struct WarningIndicator(Mutex<Vec<String>>);
+
+impl Indicator for WarningIndicator {
+    fn warning_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+// A temporary directory of its own for every test
+fn temp_disk(name: &str, max_bytes: Option<u64>) -> Arc<TempDisk> {
+    let dir = env::temp_dir().join(format!(
+        "datanymizer_test_temp_disk_{}_{}",
+        name,
+        process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Arc::new(TempDisk::new(dir, max_bytes))
+}
+
+fn is_empty(dir: &Path) -> bool {
+    fs::read_dir(dir).unwrap().next().is_none()
+}
+
+fn database(name: &str) -> Url {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+    url
+}
+
+fn dump(
+    url: &Url,
+    config: &str,
+    temp_disk: &Arc<TempDisk>,
+    path: &PathBuf,
+) -> (anyhow::Result<()>, Vec<String>) {
+    let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+    engine.temp_disk = temp_disk.clone();
+    let indicator = Arc::new(WarningIndicator::default());
+    let mut dumper = PgDumper::new(
+        engine,
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let result = dumper.dump(&mut Connection::new(client, url.clone()));
+    drop(dumper);
+
+    let warnings = indicator.0.lock().unwrap().clone();
+    (result, warnings)
+}
+
+fn restored_events(name: &str, path: &Path) -> (i64, i64) {
+    let dst_url = helpers::empty_database_url(name);
+    helpers::restore(&dst_url, path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+    let row = dst
+        .query_one(
+            "SELECT count(*), count(*) FILTER (WHERE name = 'event number ' || id || '!') FROM events",
+            &[],
+        )
+        .unwrap();
+    (row.get(0), row.get(1))
+}
+
+#[test]
+fn temp_files_are_removed() {
+    let url = database("temp_disk_removed");
+    let temp_disk = temp_disk("removed", None);
+    let path = env::temp_dir().join("datanymizer_test_temp_disk_removed.sql");
+
+    let (result, warnings) = dump(&url, SHUFFLED_CONFIG, &temp_disk, &path);
+    result.unwrap();
+    assert!(warnings.is_empty(), "{}", warnings.join("\n"));
+    // the shuffled rows were spilled
+    assert!(temp_disk.peak() > 5000);
+    assert_eq!(temp_disk.used(), 0);
+    assert!(is_empty(temp_disk.dir()));
+
+    assert_eq!(restored_events("temp_disk_removed_dst", &path), (300, 300));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn cycle_staging_over_budget() {
+    let url = database("temp_disk_cycles");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client
+        .batch_execute(
+            "CREATE TABLE parents (id int PRIMARY KEY, child_id int);
+            CREATE TABLE children (id int PRIMARY KEY, parent_id int REFERENCES parents);
+            ALTER TABLE parents ADD FOREIGN KEY (child_id) REFERENCES children;
+            INSERT INTO parents SELECT i FROM generate_series(1, 300) i;
+            INSERT INTO children SELECT i, i FROM generate_series(1, 300) i;
+            UPDATE parents SET child_id = id;",
+        )
+        .unwrap();
+    let temp_disk = temp_disk("cycles", Some(1 << 10));
+    let path = env::temp_dir().join("datanymizer_test_temp_disk_cycles.sql");
+
+    let config = format!("cycle_strategy: two_pass\n{}", CONFIG);
+    let (result, _) = dump(&url, &config, &temp_disk, &path);
+    let error = format!("{:#}", result.unwrap_err());
+    assert!(
+        error.contains("The temporary files of the circular foreign keys of `public.")
+            && error.contains("exceed --max-temp-disk 1kB"),
+        "{}",
+        error
+    );
+    assert_eq!(temp_disk.used(), 0);
+    assert!(is_empty(temp_disk.dir()));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn shuffle_over_budget() {
+    let url = database("temp_disk_shuffle");
+    let temp_disk = temp_disk("shuffle", Some(2 << 10));
+    let path = env::temp_dir().join("datanymizer_test_temp_disk_shuffle.sql");
+
+    let (result, _) = dump(&url, SHUFFLED_CONFIG, &temp_disk, &path);
+    let error = format!("{:#}", result.unwrap_err());
+    assert!(
+        error.contains(
+            "The temporary files of the shuffle of `public.events` (`shuffle_rows`) exceed --max-temp-disk 2kB"
+        ),
+        "{}",
+        error
+    );
+    assert_eq!(temp_disk.used(), 0);
+    assert!(is_empty(temp_disk.dir()));
+    fs::remove_file(path).unwrap();
+}
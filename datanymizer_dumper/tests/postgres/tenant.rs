@@ -0,0 +1,183 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper, IsolationLevel},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE countries (code text PRIMARY KEY);
+    CREATE TABLE currencies (code text PRIMARY KEY);
+    CREATE TABLE users (
+        id int PRIMARY KEY,
+        tenant_id int NOT NULL,
+        country text REFERENCES countries
+    );
+    CREATE TABLE orders (
+        id int PRIMARY KEY,
+        tenant_id int NOT NULL,
+        user_id int NOT NULL REFERENCES users,
+        currency text NOT NULL REFERENCES currencies
+    );
+    CREATE TABLE order_items (order_id int REFERENCES orders, position int, PRIMARY KEY (order_id, position));
+    CREATE TABLE audit_log (user_id int, message text);
+    CREATE TABLE settings (name text PRIMARY KEY);
+
+    INSERT INTO countries VALUES ('de'), ('fr'), ('it');
+    INSERT INTO currencies VALUES ('eur'), ('usd');
+    INSERT INTO users VALUES (1, 1, 'de'), (2, 1, 'fr'), (3, 2, 'it');
+    INSERT INTO orders VALUES (1, 1, 1, 'eur'), (2, 1, 2, 'eur'), (3, 2, 3, 'usd');
+    INSERT INTO order_items VALUES (1, 1), (1, 2), (2, 1), (3, 1);
+    INSERT INTO audit_log VALUES (1, 'a'), (3, 'b');
+    INSERT INTO settings VALUES ('x');
+";
+
+const CONFIG: &str = r#"
+tables: []
+tenant:
+  other_tables: foreign_keys
+  tables:
+    currencies: include
+    public.audit_log: exclude
+"#;
+
+#[derive(Default)]
+struct DebugIndicator(Mutex<Vec<String>>);
+
+impl Indicator for DebugIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+fn values(client: &mut Client, query: &str) -> Vec<String> {
+    client
+        .query(query, &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect()
+}
+
+fn dump(
+    name: &str,
+    url: &Url,
+    tenant_id: Option<&str>,
+    indicator: Arc<DebugIndicator>,
+) -> anyhow::Result<()> {
+    let mut settings = Settings::from_yaml(CONFIG).unwrap();
+    settings.tenant.as_mut().unwrap().id = tenant_id.map(String::from);
+    let client = Client::connect(url.as_str(), NoTls).unwrap();
+    let mut dumper = PgDumper::new(
+        Engine::new(settings),
+        Some(IsolationLevel::RepeatableRead),
+        helpers::pg_dump_path(),
+        fs::File::create(env::temp_dir().join(format!("datanymizer_test_{}.sql", name))).unwrap(),
+        indicator,
+        vec![],
+    )
+    .unwrap();
+    dumper.dump(&mut Connection::new(client, url.clone()))
+}
+
+#[test]
+fn tenant_rows() {
+    let url = helpers::empty_database_url("tenant");
+    Client::connect(url.as_str(), NoTls)
+        .unwrap()
+        .batch_execute(SCHEMA)
+        .unwrap();
+
+    let indicator = Arc::new(DebugIndicator::default());
+    dump("tenant", &url, Some("1"), indicator.clone()).unwrap();
+    let messages = indicator.0.lock().unwrap().clone();
+    for line in [
+        "[Tenant] public.users: the tenant column `tenant_id`, 2 rows",
+        "[Tenant] public.orders: the tenant column `tenant_id`, 2 rows",
+        "[Tenant] public.order_items: by foreign keys, 3 rows",
+        "[Tenant] public.countries: by foreign keys, 2 rows",
+        "[Tenant] public.currencies: all rows",
+        "[Tenant] public.audit_log: no rows",
+        "[Tenant] public.settings: by foreign keys",
+    ] {
+        assert!(
+            messages.iter().any(|m| m == line),
+            "{}",
+            messages.join("\n")
+        );
+    }
+
+    // the dump is restored with all foreign keys
+    let path = env::temp_dir().join("datanymizer_test_tenant.sql");
+    let dst_url = helpers::empty_database_url("tenant_dst");
+    helpers::restore(&dst_url, &path);
+    let mut dst = Client::connect(dst_url.as_str(), NoTls).unwrap();
+
+    assert_eq!(
+        values(&mut dst, "SELECT id::text FROM users ORDER BY 1"),
+        vec!["1", "2"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT id::text FROM orders ORDER BY 1"),
+        vec!["1", "2"]
+    );
+    assert_eq!(
+        values(
+            &mut dst,
+            "SELECT order_id || '.' || position FROM order_items ORDER BY 1"
+        ),
+        vec!["1.1", "1.2", "2.1"]
+    );
+    // the parents of the tenant rows
+    assert_eq!(
+        values(&mut dst, "SELECT code FROM countries ORDER BY 1"),
+        vec!["de", "fr"]
+    );
+    assert_eq!(
+        values(&mut dst, "SELECT code FROM currencies ORDER BY 1"),
+        vec!["eur", "usd"]
+    );
+    assert!(values(&mut dst, "SELECT message FROM audit_log").is_empty());
+    assert_eq!(values(&mut dst, "SELECT name FROM settings"), vec!["x"]);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn tenant_errors() {
+    let url = helpers::empty_database_url("tenant_errors");
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+    let error = |tenant_id| {
+        dump("tenant_errors", &url, tenant_id, Arc::default())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        error(None),
+        "The tenant id is not set (use --tenant-id or `id` in the `tenant` config section)"
+    );
+    assert!(error(Some("abc")).starts_with(
+        "The tenant id `abc` is not valid for the column `tenant_id` of `public.users`"
+    ));
+
+    client
+        .batch_execute("CREATE TABLE notes (tenant_id text, body text)")
+        .unwrap();
+    assert_eq!(
+        error(Some("1")),
+        "The tenant columns have different types: public.users.tenant_id (integer), \
+        public.orders.tenant_id (integer), public.notes.tenant_id (text)"
+    );
+
+    fs::remove_file(env::temp_dir().join("datanymizer_test_tenant_errors.sql")).unwrap();
+}
@@ -0,0 +1,118 @@
+use super::helpers;
+
+use datanymizer_dumper::{
+    indicator::Indicator,
+    postgres::{connector::Connection, dumper::PgDumper},
+    Dumper,
+};
+use datanymizer_engine::{Engine, Settings};
+use postgres::{Client, NoTls};
+use std::{
+    env, fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+// The archived codes are retained, the new codes are generated
+const SCHEMA: &str = "
+    CREATE TABLE archived_codes (id int PRIMARY KEY, code int);
+    INSERT INTO archived_codes SELECT i, i % 10 + 1 FROM generate_series(1, 30) i;
+    INSERT INTO archived_codes VALUES (31, NULL);
+    CREATE TABLE new_codes (id int PRIMARY KEY, code int);
+    INSERT INTO new_codes SELECT i, i FROM generate_series(1, 10) i;
+";
+
+#[derive(Default)]
+struct MessageIndicator(Mutex<Vec<String>>);
+
+impl Indicator for MessageIndicator {
+    fn debug_msg(&self, msg: &str) {
+        self.0.lock().unwrap().push(msg.to_string());
+    }
+}
+
+// The uniqueness set is shared by the tests (it only gets the archived codes in `too_many_existing_values`)
+fn config(max_values: u64) -> String {
+    format!(
+        r#"
+tables:
+  - name: new_codes
+    rules:
+      code:
+        random_num:
+          min: 1
+          max: 20
+          uniq:
+            try_count: 1000
+            unique_against:
+              table: public.archived_codes
+              column: code
+              max_values: {}
+"#,
+        max_values
+    )
+}
+
+fn dump(name: &str, config: &str, path: &Path) -> (anyhow::Result<()>, Vec<String>) {
+    let url = helpers::empty_database_url(name);
+    let mut client = Client::connect(url.as_str(), NoTls).unwrap();
+    client.batch_execute(SCHEMA).unwrap();
+
+    let indicator = Arc::new(MessageIndicator::default());
+    let mut dumper = PgDumper::new(
+        Engine::new(Settings::from_yaml(config).unwrap()),
+        None,
+        helpers::pg_dump_path(),
+        fs::File::create(path).unwrap(),
+        indicator.clone(),
+        vec![],
+    )
+    .unwrap();
+    let result = dumper.dump(&mut Connection::new(client, url));
+    drop(dumper);
+
+    let messages = indicator.0.lock().unwrap().clone();
+    (result, messages)
+}
+
+#[test]
+fn no_collisions_with_existing_values() {
+    let path = env::temp_dir().join("datanymizer_test_unique_against.sql");
+    let (result, messages) = dump("unique_against", &config(10), &path);
+    result.unwrap();
+    assert!(
+        messages.contains(
+            &"10 existing values of `public.archived_codes.code` are registered for the uniqueness of `new_codes.code`"
+                .to_string()
+        ),
+        "{}",
+        messages.join("\n")
+    );
+
+    let dump = fs::read_to_string(&path).unwrap();
+    let mut codes: Vec<u32> = dump
+        .lines()
+        .skip_while(|line| !line.starts_with("COPY \"public\".\"new_codes\""))
+        .skip(1)
+        .take_while(|line| *line != "\\.")
+        .map(|line| line.split('\t').nth(1).unwrap().parse().unwrap())
+        .collect();
+    codes.sort_unstable();
+    // the only values that aren't used by the archived codes
+    assert_eq!(codes, (11..=20).collect::<Vec<_>>());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn too_many_existing_values() {
+    let path = env::temp_dir().join("datanymizer_test_unique_against_max.sql");
+    let (result, _) = dump("unique_against_max", &config(9), &path);
+    let error = format!("{:#}", result.unwrap_err());
+    assert_eq!(
+        error,
+        "The column `public.archived_codes.code` has more than 9 distinct values for the uniqueness of \
+         `new_codes.code` (`unique_against`), raise its `max_values`"
+    );
+    fs::remove_file(path).unwrap();
+}
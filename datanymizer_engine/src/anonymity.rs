@@ -0,0 +1,442 @@
+use crate::{
+    memory::{self, Component},
+    TempDisk, TempFile,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, BTreeMap, BinaryHeap, HashMap},
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    sync::{Arc, Mutex},
+};
+
+/// Equivalence classes kept in memory for every quasi-identifier set (about 40 MB),
+/// the sorted classes are spilled to a temporary file when there are more of them
+const DEFAULT_MAX_IN_MEMORY: usize = 1_000_000;
+
+/// Sorted class hashes and sizes
+type Run = Box<dyn Iterator<Item = Result<(u64, u64)>>>;
+
+/// The bytes of a spilled class
+const CLASS_SIZE: u64 = 16;
+
+/// The memory of a class in the map (with the spare capacity of the map)
+const CLASS_MEMORY: u64 = 40;
+
+/// Over `--max-memory`, the classes are spilled when there are more than this part of `max_in_memory`
+/// (the spills of a few classes don't free the memory)
+const MIN_SPILL_PART: usize = 16;
+
+/// k-anonymity check: counts the sizes of the equivalence classes (rows with the same values
+/// of the quasi-identifier columns) in the transformed output. Only the hashes of the values
+/// are kept (and spilled), the values themselves are never stored.
+#[derive(Debug)]
+pub struct AnonymityMetrics {
+    sets: Mutex<BTreeMap<(String, Vec<String>), ClassCounter>>,
+    max_in_memory: usize,
+    temp_disk: Arc<TempDisk>,
+    /// The classes are spilled earlier when the memory of the process exceeds it
+    max_memory: Option<u64>,
+}
+
+impl Default for AnonymityMetrics {
+    fn default() -> Self {
+        Self::with_max_in_memory(DEFAULT_MAX_IN_MEMORY)
+    }
+}
+
+impl AnonymityMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_in_memory(max_in_memory: usize) -> Self {
+        Self {
+            sets: Mutex::default(),
+            max_in_memory,
+            temp_disk: Arc::default(),
+            max_memory: None,
+        }
+    }
+
+    /// The classes are spilled when the accounted memory exceeds `max_memory` (`--max-memory`)
+    pub fn with_max_memory(mut self, max_memory: Option<u64>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// The spills are created in the directory of `temp_disk` and accounted in its budget
+    pub fn with_temp_disk(mut self, temp_disk: Arc<TempDisk>) -> Self {
+        self.temp_disk = temp_disk;
+        self
+    }
+
+    /// Records the values of the quasi-identifier `columns` of one row of the `table`
+    pub fn record<'a, V>(&self, table: &str, columns: &[String], values: V)
+    where
+        V: IntoIterator<Item = &'a str>,
+    {
+        let mut hasher = DefaultHasher::new();
+        for value in values {
+            value.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        if let Ok(mut sets) = self.sets.lock() {
+            let key = (table.to_string(), columns.to_vec());
+            let spill = Spill {
+                max_in_memory: self.max_in_memory,
+                temp_disk: &self.temp_disk,
+                max_memory: self.max_memory,
+                table,
+            };
+            sets.entry(key).or_default().add(hash, &spill);
+        }
+    }
+
+    /// Reports for all quasi-identifier sets, `k` is the minimum acceptable class size
+    pub fn report(&self, k: u64) -> Result<Vec<AnonymityReport>> {
+        let sets = self
+            .sets
+            .lock()
+            .map_err(|_| anyhow!("Can't access the anonymity metrics"))?;
+        sets.iter()
+            .map(|((table, columns), counter)| {
+                let mut report = AnonymityReport {
+                    table: table.clone(),
+                    columns: columns.clone(),
+                    k,
+                    rows: counter.rows,
+                    classes: 0,
+                    classes_below_k: 0,
+                    rows_below_k: 0,
+                    histogram: BTreeMap::new(),
+                };
+                counter.for_each_class(|size| report.add_class(size))?;
+                Ok(report)
+            })
+            .collect()
+    }
+}
+
+// Where and when the classes are spilled
+struct Spill<'a> {
+    max_in_memory: usize,
+    temp_disk: &'a Arc<TempDisk>,
+    max_memory: Option<u64>,
+    table: &'a str,
+}
+
+impl Spill<'_> {
+    fn is_needed(&self, classes: usize) -> bool {
+        classes > self.max_in_memory
+            || (classes > self.max_in_memory / MIN_SPILL_PART
+                && memory::accountant().exceeds(self.max_memory))
+    }
+}
+
+/// Class sizes by the value hashes (the classes in memory are accounted)
+#[derive(Debug)]
+struct ClassCounter {
+    counts: HashMap<u64, u64>,
+    spills: Vec<SpillFile>,
+    rows: u64,
+    /// The first spilling error (it is reported with the results)
+    error: Option<String>,
+    memory: Arc<Component>,
+}
+
+impl Default for ClassCounter {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::new(),
+            spills: vec![],
+            rows: 0,
+            error: None,
+            memory: memory::accountant().component(memory::ANONYMITY_CLASSES),
+        }
+    }
+}
+
+impl Drop for ClassCounter {
+    fn drop(&mut self) {
+        self.memory.release(self.counts.len() as u64 * CLASS_MEMORY);
+    }
+}
+
+impl ClassCounter {
+    fn add(&mut self, hash: u64, spill: &Spill) {
+        self.rows += 1;
+        let count = self.counts.entry(hash).or_insert(0);
+        if *count == 0 {
+            self.memory.add(CLASS_MEMORY);
+        }
+        *count += 1;
+
+        if spill.is_needed(self.counts.len()) && self.error.is_none() {
+            let component = format!("the k-anonymity check of `{}`", spill.table);
+            match SpillFile::write(spill.temp_disk, &component, sorted(&self.counts)) {
+                Ok(spill) => {
+                    self.spills.push(spill);
+                    self.memory.release(self.counts.len() as u64 * CLASS_MEMORY);
+                    self.counts.clear();
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// Calls `f` with the size of every class (the spilled runs are merged by hashes)
+    fn for_each_class<F: FnMut(u64)>(&self, mut f: F) -> Result<()> {
+        if let Some(e) = &self.error {
+            return Err(anyhow!("Can't spill the anonymity metrics: {}", e));
+        }
+
+        let mut runs: Vec<Run> = vec![];
+        for spill in &self.spills {
+            runs.push(Box::new(spill.read()?));
+        }
+        runs.push(Box::new(sorted(&self.counts).into_iter().map(Ok)));
+
+        let mut heap = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some(item) = run.next() {
+                let (hash, count) = item?;
+                heap.push(Reverse((hash, count, i)));
+            }
+        }
+
+        let mut current: Option<(u64, u64)> = None;
+        while let Some(Reverse((hash, count, i))) = heap.pop() {
+            current = match current {
+                Some((h, size)) if h == hash => Some((h, size + count)),
+                Some((_, size)) => {
+                    f(size);
+                    Some((hash, count))
+                }
+                None => Some((hash, count)),
+            };
+            if let Some(item) = runs[i].next() {
+                let (hash, count) = item?;
+                heap.push(Reverse((hash, count, i)));
+            }
+        }
+        if let Some((_, size)) = current {
+            f(size);
+        }
+
+        Ok(())
+    }
+}
+
+fn sorted(counts: &HashMap<u64, u64>) -> Vec<(u64, u64)> {
+    let mut items: Vec<_> = counts.iter().map(|(h, c)| (*h, *c)).collect();
+    items.sort_unstable();
+    items
+}
+
+/// Temporary file with the sorted class hashes and sizes, it is removed when the value is dropped
+#[derive(Debug)]
+struct SpillFile {
+    file: TempFile,
+}
+
+impl SpillFile {
+    fn write(temp_disk: &Arc<TempDisk>, component: &str, items: Vec<(u64, u64)>) -> Result<Self> {
+        let mut file = temp_disk.create(component, items.len() as u64 * CLASS_SIZE)?;
+        let mut writer = BufWriter::new(&mut file);
+        for (hash, count) in items {
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&count.to_le_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        Ok(Self { file })
+    }
+
+    fn read(&self) -> Result<impl Iterator<Item = Result<(u64, u64)>>> {
+        let mut file = self.file.try_clone()?;
+        file.rewind()?;
+        let mut reader = BufReader::new(file);
+        Ok(std::iter::from_fn(move || {
+            let mut buf = [0; 16];
+            match reader.read_exact(&mut buf) {
+                Ok(()) => {
+                    let (hash, count) = buf.split_at(8);
+                    Some(Ok((
+                        u64::from_le_bytes(hash.try_into().unwrap_or_default()),
+                        u64::from_le_bytes(count.try_into().unwrap_or_default()),
+                    )))
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+                Err(e) => Some(Err(e.into())),
+            }
+        }))
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct AnonymityReport {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub k: u64,
+    pub rows: u64,
+    /// Count of equivalence classes
+    pub classes: u64,
+    /// Classes that have less than `k` rows
+    pub classes_below_k: u64,
+    pub rows_below_k: u64,
+    /// Counts of classes by their sizes (all sizes from `k` are counted as `k`)
+    pub histogram: BTreeMap<u64, u64>,
+}
+
+impl AnonymityReport {
+    pub fn is_ok(&self) -> bool {
+        self.classes_below_k == 0
+    }
+
+    fn add_class(&mut self, size: u64) {
+        self.classes += 1;
+        if size < self.k {
+            self.classes_below_k += 1;
+            self.rows_below_k += size;
+        }
+        *self.histogram.entry(size.min(self.k)).or_insert(0) += 1;
+    }
+}
+
+impl Display for AnonymityReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} rows, {} classes, {} classes below k={} ({} rows)",
+            self.table,
+            self.columns.join(", "),
+            self.rows,
+            self.classes,
+            self.classes_below_k,
+            self.k,
+            self.rows_below_k
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<String> {
+        vec!["zip".to_string(), "gender".to_string()]
+    }
+
+    fn record_rows(metrics: &AnonymityMetrics) {
+        for i in 0..100 {
+            let zip = format!("{}", i % 10);
+            metrics.record("users", &columns(), [zip.as_str(), "f"]);
+        }
+        // unique combinations
+        metrics.record("users", &columns(), ["1", "m"]);
+        metrics.record("users", &columns(), ["2", "m"]);
+        metrics.record("users", &columns(), ["2", "m"]);
+    }
+
+    #[test]
+    fn report() {
+        let metrics = AnonymityMetrics::new();
+        record_rows(&metrics);
+
+        let report = metrics.report(5).unwrap();
+        assert_eq!(report.len(), 1);
+        let report = &report[0];
+        assert_eq!(report.rows, 103);
+        assert_eq!(report.classes, 12);
+        assert_eq!(report.classes_below_k, 2);
+        assert_eq!(report.rows_below_k, 3);
+        assert_eq!(report.histogram, BTreeMap::from([(1, 1), (2, 1), (5, 10)]));
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "users (zip, gender): 103 rows, 12 classes, 2 classes below k=5 (3 rows)"
+        );
+
+        assert!(metrics.report(1).unwrap()[0].is_ok());
+    }
+
+    #[test]
+    fn spill() {
+        let in_memory = AnonymityMetrics::new();
+        record_rows(&in_memory);
+        let dir = std::env::temp_dir().join(format!(
+            "datanymizer_anonymity_spill_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let temp_disk = Arc::new(TempDisk::new(dir.clone(), None));
+        let spilled = AnonymityMetrics::with_max_in_memory(3).with_temp_disk(temp_disk.clone());
+        record_rows(&spilled);
+
+        assert!(!spilled
+            .sets
+            .lock()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap()
+            .spills
+            .is_empty());
+        assert_eq!(spilled.report(5).unwrap(), in_memory.report(5).unwrap());
+        assert!(temp_disk.used() > 0);
+
+        drop(spilled);
+        assert_eq!(temp_disk.used(), 0);
+        assert!(std::fs::read_dir(&dir).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn spill_over_max_memory() {
+        // the memory of the process always exceeds the limit
+        let metrics = AnonymityMetrics::with_max_in_memory(32).with_max_memory(Some(0));
+        record_rows(&metrics);
+        let spills = metrics
+            .sets
+            .lock()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap()
+            .spills
+            .len();
+        assert!(spills > 0);
+
+        let in_memory = AnonymityMetrics::new();
+        record_rows(&in_memory);
+        assert_eq!(metrics.report(5).unwrap(), in_memory.report(5).unwrap());
+    }
+
+    #[test]
+    fn spill_over_budget() {
+        let temp_disk = Arc::new(TempDisk::new(std::env::temp_dir(), Some(16)));
+        let metrics = AnonymityMetrics::with_max_in_memory(3).with_temp_disk(temp_disk);
+        record_rows(&metrics);
+
+        let error = metrics.report(5).unwrap_err().to_string();
+        assert!(error.starts_with(
+            "Can't spill the anonymity metrics: The temporary files of the k-anonymity check of `users` \
+            exceed --max-temp-disk 16B"
+        ));
+    }
+
+    #[test]
+    fn values_are_separated() {
+        let metrics = AnonymityMetrics::new();
+        metrics.record("t", &columns(), ["ab", "c"]);
+        metrics.record("t", &columns(), ["a", "bc"]);
+
+        assert_eq!(metrics.report(2).unwrap()[0].classes, 2);
+    }
+}
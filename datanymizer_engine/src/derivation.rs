@@ -0,0 +1,146 @@
+//! The derivation of the seeds of the consistent values (the `consistent` rules and `learn_format`
+//! with `consistent: true`). It is stateless: the seed depends on the pepper, the scope (the salt of the rule)
+//! and the original value only, so the independent runs (other databases, machines, times) with the same pepper
+//! give the same values without a shared store.
+//!
+//! The derivation is versioned (`derivation_version` in the config, it is recorded in the dump manifest),
+//! a version never changes its seeds:
+//!
+//! - `1`: the FNV-1a based keyed hash of the salt with the pepper and the value (the releases before
+//!   the versioning);
+//! - `2`: HKDF-SHA256 (RFC 5869), the pepper is the salt of HKDF-Extract, the original value is the input key
+//!   material, `datanymizer/consistent/v2`, a zero byte and the scope are the info of HKDF-Expand,
+//!   the 32 bytes of the output are the seed.
+
+use crate::utils;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::ops::RangeInclusive;
+
+/// The derivation of the new configs
+pub const DERIVATION_VERSION: u32 = 2;
+pub const DERIVATION_VERSIONS: RangeInclusive<u32> = 1..=DERIVATION_VERSION;
+
+const HKDF_INFO_PREFIX: &[u8] = b"datanymizer/consistent/v2";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The seeds of the consistent values of a scope
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Derivation {
+    version: u32,
+    pepper: String,
+    scope: String,
+}
+
+impl Default for Derivation {
+    fn default() -> Self {
+        Self::new(DERIVATION_VERSION, "", "")
+    }
+}
+
+impl Derivation {
+    pub fn new(version: u32, pepper: &str, scope: &str) -> Self {
+        Self {
+            version,
+            pepper: pepper.to_string(),
+            scope: scope.to_string(),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The seed of the random generator for the original value
+    pub fn seed(&self, value: &str) -> [u8; 32] {
+        match self.version {
+            1 => utils::keyed_seed(&utils::peppered(&self.scope, &self.pepper), value),
+            _ => {
+                let mut info = HKDF_INFO_PREFIX.to_vec();
+                info.push(0);
+                info.extend_from_slice(self.scope.as_bytes());
+                hkdf_sha256(self.pepper.as_bytes(), value.as_bytes(), &info)
+            }
+        }
+    }
+}
+
+/// HKDF-SHA256 with 32 bytes of output (the first block of HKDF-Expand)
+fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    // an empty salt is the same as the zero-filled one for HMAC
+    let prk = hmac_sha256(salt, &[ikm]);
+    hmac_sha256(&prk, &[info, &[1]])
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_varkey(key).expect("HMAC accepts keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn rfc5869_vectors() {
+        // test case 1 (the first 32 bytes of the output)
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        assert_eq!(
+            hex(&hkdf_sha256(&salt, &ikm, &info)),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+        );
+        // test case 3 (no salt and info)
+        assert_eq!(
+            hex(&hkdf_sha256(&[], &ikm, &[])),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+        );
+    }
+
+    #[test]
+    fn versions() {
+        let v1 = Derivation::new(1, "pepper", "scope");
+        assert_eq!(
+            v1.seed("jdoe@x.com"),
+            utils::keyed_seed(&utils::peppered("scope", "pepper"), "jdoe@x.com")
+        );
+
+        let v2 = Derivation::new(2, "pepper", "scope");
+        let mut info = b"datanymizer/consistent/v2\0".to_vec();
+        info.extend_from_slice(b"scope");
+        assert_eq!(
+            v2.seed("jdoe@x.com"),
+            hkdf_sha256(b"pepper", b"jdoe@x.com", &info)
+        );
+        assert_ne!(v2.seed("jdoe@x.com"), v1.seed("jdoe@x.com"));
+        assert_eq!(Derivation::default().version(), DERIVATION_VERSION);
+    }
+
+    #[test]
+    fn stable_seeds() {
+        // the seeds of a version must never change (other runs and releases derive the same values)
+        assert_eq!(
+            hex(&Derivation::new(2, "pepper", "users").seed("jdoe@x.com")),
+            "bab6145ee56da3387c10fbb6bcafe5316c5d307601f7bc12ebd231b109b6d687"
+        );
+    }
+
+    #[test]
+    fn inputs() {
+        let seed =
+            |pepper: &str, scope: &str, value: &str| Derivation::new(2, pepper, scope).seed(value);
+        let base = seed("pepper", "scope", "value");
+        assert_ne!(seed("other", "scope", "value"), base);
+        assert_ne!(seed("pepper", "other", "value"), base);
+        assert_ne!(seed("pepper", "scope", "other"), base);
+    }
+}
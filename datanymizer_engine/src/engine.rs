@@ -1,16 +1,125 @@
 use crate::{
     errors::{EngineError, UnknownColumnError},
-    Settings, TransformContext, Transformer,
+    transformers::register_kept,
+    uniq_collector, utils, AnonymityMetrics, RuleMetrics, Settings, Table, TempDisk,
+    TransformContext, TransformResult, Transformer, Transformers,
 };
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
+};
+
+/// The NULL value in the COPY text format (it is never tagged)
+const NULL: &str = r"\N";
+
+/// Splits the column values into elements and joins the transformed elements back, so the rule of the column
+/// is applied to every element (e.g., the items of the array columns)
+pub trait ElementCodec {
+    /// Applies `transform` to every element of the value (`Ok(None)` if nothing is changed)
+    fn transform_elements(
+        &self,
+        field_name: &str,
+        value: &str,
+        transform: &mut dyn FnMut(&str) -> TransformResult,
+    ) -> TransformResult;
+}
 
 pub struct Engine {
     pub settings: Settings,
+    /// Per-rule timing metrics (collected only when enabled)
+    pub rule_metrics: Option<Arc<RuleMetrics>>,
+    /// Equivalence classes of the quasi-identifiers (collected only when enabled)
+    pub anonymity_metrics: Option<Arc<AnonymityMetrics>>,
+    /// The limit of the accounted memory (`--max-memory`, see `memory`): the components spill to disk
+    /// when it is exceeded, the dump fails if they can't
+    pub max_memory: Option<u64>,
+    /// The directory and the budget of the temporary files (the spills of the dumper and the metrics)
+    pub temp_disk: Arc<TempDisk>,
+    /// The tags of the rules by the config table names and columns (only in the debug mode)
+    debug_tags: Option<HashMap<String, HashMap<String, String>>>,
 }
 
 impl Engine {
     pub fn new(settings: Settings) -> Self {
-        Self { settings }
+        Self {
+            settings,
+            rule_metrics: None,
+            anonymity_metrics: None,
+            max_memory: None,
+            temp_disk: Arc::default(),
+            debug_tags: None,
+        }
+    }
+
+    /// Enables timing of every rule invocation and returns the metrics handle
+    pub fn enable_rule_timing(&mut self) -> Arc<RuleMetrics> {
+        self.rule_metrics
+            .get_or_insert_with(|| Arc::new(RuleMetrics::new()))
+            .clone()
+    }
+
+    /// Enables the k-anonymity check of the quasi-identifier columns and returns the metrics handle
+    pub fn enable_anonymity_check(&mut self) -> Arc<AnonymityMetrics> {
+        let max_memory = self.max_memory;
+        let temp_disk = self.temp_disk.clone();
+        self.anonymity_metrics
+            .get_or_insert_with(|| {
+                Arc::new(
+                    AnonymityMetrics::new()
+                        .with_temp_disk(temp_disk)
+                        .with_max_memory(max_memory),
+                )
+            })
+            .clone()
+    }
+
+    /// Registers an existing value of the database in the uniqueness set of the rule of the column
+    /// (with `unique_against`), so the rule never generates it. Returns `false` if it is already registered.
+    pub fn register_existing_value(&self, table: &str, column: &str, value: &str) -> bool {
+        uniq_collector::add_to_collector(&format!("{}.{}", table, column), value)
+    }
+
+    /// Registers an existing value that is kept by the `skip_if` rule of the column (its inner rule
+    /// generates unique values) before the dump, so the inner rule never generates it
+    pub fn register_kept_value(&self, table: &str, column: &str, value: &str) {
+        register_kept(&format!("{}.{}", table, column), value);
+    }
+
+    /// Enables the debug mode: every transformed value is prefixed with the tag of its rule, e.g. `«r12»`
+    /// (the number is the position in `Settings::resolved_rules`). Returns the tagged rules for printing.
+    /// The values are tagged after all checks, so the tags don't affect `timestamp_order` and quasi-identifiers.
+    pub fn enable_debug_tags(&mut self) -> Vec<String> {
+        let mut tags: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut list = vec![];
+        for (i, (table, column, rule)) in self.settings.resolved_rules().into_iter().enumerate() {
+            let tag = debug_tag(i + 1);
+            list.push(format!("{} {}.{} ({})", tag, table, column, rule.name()));
+            tags.entry(table.to_string())
+                .or_default()
+                .insert(column.to_string(), tag);
+        }
+        self.debug_tags = Some(tags);
+        list
+    }
+
+    /// Records the quasi-identifier values of the output row (if the k-anonymity check is enabled).
+    /// It is called by `process_row`, so it is needed only for the rows that are dumped without it.
+    pub fn record_quasi_identifiers<S: AsRef<str>>(
+        &self,
+        table: &str,
+        column_indexes: &HashMap<String, usize>,
+        values: &[S],
+    ) -> Result<(), EngineError> {
+        let metrics = match &self.anonymity_metrics {
+            Some(metrics) => metrics,
+            None => return Ok(()),
+        };
+        match self.settings.table_config(table) {
+            Some(cfg) => record_classes(metrics, table, cfg, column_indexes, values),
+            None => Ok(()),
+        }
     }
 
     pub fn process_row<'a>(
@@ -18,29 +127,118 @@ pub fn process_row<'a>(
         table: String,
         column_indexes: &HashMap<String, usize>,
         values: &'a [&str],
+    ) -> Result<Vec<Cow<'a, str>>, EngineError> {
+        self.process_row_skipping(table, column_indexes, values, &HashSet::new())
+    }
+
+    /// The same as `process_row`, but the rules of the skipped columns are not applied
+    /// (e.g., the column types are not supported by the rules)
+    pub fn process_row_skipping<'a>(
+        &self,
+        table: String,
+        column_indexes: &HashMap<String, usize>,
+        values: &'a [&str],
+        skipped_columns: &HashSet<String>,
+    ) -> Result<Vec<Cow<'a, str>>, EngineError> {
+        self.process_row_elements(
+            table,
+            column_indexes,
+            values,
+            skipped_columns,
+            &HashMap::new(),
+        )
+    }
+
+    /// The same as `process_row_skipping`, but the rules of the columns with codecs (by the column indexes)
+    /// are applied to the elements of the values
+    pub fn process_row_elements<'a>(
+        &self,
+        table: String,
+        column_indexes: &HashMap<String, usize>,
+        values: &'a [&str],
+        skipped_columns: &HashSet<String>,
+        codecs: &HashMap<usize, &dyn ElementCodec>,
+    ) -> Result<Vec<Cow<'a, str>>, EngineError> {
+        self.process_row_numbered(table, None, column_indexes, values, skipped_columns, codecs)
+    }
+
+    /// The same as `process_row_elements` for the row with the number (in the table). With the run `seed`
+    /// the random values of the numbered rows are seeded by the table, the column and the row number,
+    /// so the same rows get the same values regardless of the table order and parallelism.
+    pub fn process_row_numbered<'a>(
+        &self,
+        table: String,
+        row: Option<u64>,
+        column_indexes: &HashMap<String, usize>,
+        values: &'a [&str],
+        skipped_columns: &HashSet<String>,
+        codecs: &HashMap<usize, &dyn ElementCodec>,
     ) -> Result<Vec<Cow<'a, str>>, EngineError> {
         let ts = self.settings.transformers_for(&table);
+        let cfg = self.settings.table_config(&table);
+        let seed = self.settings.seed.zip(row);
 
         let mut transformed_values = Vec::with_capacity(values.len());
         for &v in values {
             transformed_values.push(Cow::from(v));
         }
+        let mut tagged = vec![];
 
         if let Some(ts) = ts {
             for (field, tr) in ts {
+                if skipped_columns.contains(field) {
+                    continue;
+                }
                 if let Some(&i) = column_indexes.get(field) {
-                    match tr.transform(
-                        &format!("{}.{}", table, field),
-                        values[i],
-                        &Some(TransformContext::new(
-                            &self.settings.globals,
-                            Some(column_indexes),
-                            Some(values),
-                            Some(&transformed_values),
-                        )),
-                    ) {
+                    let rule_name = format!("{}.{}", table, field);
+                    let started = self.rule_metrics.as_ref().map(|_| Instant::now());
+                    let ctx = Some(TransformContext::new(
+                        &self.settings.globals,
+                        Some(column_indexes),
+                        Some(values),
+                        Some(&transformed_values),
+                    ));
+                    // the outcomes of the calls (recorded with the timing)
+                    let mut outcomes = vec![];
+                    let mut transform = |value: &str| {
+                        let mut outcome = Outcome::default();
+                        let result = transform_value(tr, &rule_name, value, &ctx, &mut outcome);
+                        if self.rule_metrics.is_some() {
+                            outcomes.push(outcome);
+                        }
+                        result
+                    };
+                    let mut transform_column = || match codecs.get(&i) {
+                        Some(codec) => {
+                            codec.transform_elements(&rule_name, values[i], &mut transform)
+                        }
+                        None => transform(values[i]),
+                    };
+                    let result = match seed {
+                        Some((seed, row)) => {
+                            utils::with_seed(row_seed(seed, &table, field, row), transform_column)
+                        }
+                        None => transform_column(),
+                    };
+                    if let (Some(metrics), Some(started)) = (&self.rule_metrics, started) {
+                        metrics.record(&rule_name, started.elapsed());
+                        for outcome in outcomes {
+                            if let Some(skipped) = outcome.skipped {
+                                metrics.record_skipped(&rule_name, skipped);
+                            }
+                            if let Some(hit) = outcome.cache_hit {
+                                metrics.record_cache(&rule_name, hit);
+                            }
+                            if let Some(normalized) = outcome.normalized {
+                                metrics.record_normalized(&rule_name, normalized);
+                            }
+                        }
+                    }
+
+                    match result {
                         Ok(Some(res)) => {
                             transformed_values[i] = Cow::Owned(res);
+                            tagged.push((i, field));
                         }
                         Err(e) => return Err(EngineError::TransformFieldError(e)),
                         _ => {}
@@ -53,10 +251,106 @@ pub fn process_row<'a>(
             }
         }
 
+        if let Some(cfg) = cfg {
+            for order in &cfg.timestamp_order {
+                order.apply(&table, column_indexes, &mut transformed_values)?;
+            }
+            if let Some(metrics) = &self.anonymity_metrics {
+                record_classes(metrics, &table, cfg, column_indexes, &transformed_values)?;
+            }
+        }
+
+        if let Some(tags) = self.debug_tags.as_ref().and_then(|t| t.get(&table)) {
+            for (i, field) in tagged {
+                if let Some(tag) = tags.get(field).filter(|_| transformed_values[i] != NULL) {
+                    transformed_values[i] = Cow::Owned(format!("{}{}", tag, transformed_values[i]));
+                }
+            }
+        }
+
         Ok(transformed_values)
     }
 }
 
+// The quasi-identifier values of the row are recorded to their equivalence classes
+fn record_classes<S: AsRef<str>>(
+    metrics: &AnonymityMetrics,
+    table: &str,
+    cfg: &Table,
+    column_indexes: &HashMap<String, usize>,
+    values: &[S],
+) -> Result<(), EngineError> {
+    for columns in &cfg.quasi_identifiers {
+        let mut set_values = Vec::with_capacity(columns.len());
+        for column in columns {
+            match column_indexes.get(column).and_then(|&i| values.get(i)) {
+                Some(value) => set_values.push(value.as_ref()),
+                None => {
+                    return Err(EngineError::UnknownColumnError(UnknownColumnError {
+                        field_name: column.clone(),
+                    }))
+                }
+            }
+        }
+        metrics.record(table, columns, set_values);
+    }
+
+    Ok(())
+}
+
+/// The outcome of a rule call for the metrics (`None` if it isn't applicable to the rule)
+#[derive(Default)]
+struct Outcome {
+    cache_hit: Option<bool>,
+    normalized: Option<bool>,
+    skipped: Option<bool>,
+}
+
+// The rules of the `skip_if` rules are unwrapped, so the outcomes of the inner `cache`
+// and `normalize_empty` rules are recorded too
+fn transform_value(
+    tr: &Transformers,
+    rule_name: &str,
+    value: &str,
+    ctx: &Option<TransformContext>,
+    outcome: &mut Outcome,
+) -> TransformResult {
+    match tr {
+        Transformers::SkipIf(skip) => {
+            let skipped = skip.skip(rule_name, value)?;
+            outcome.skipped = Some(skipped);
+            if skipped {
+                Ok(None)
+            } else {
+                transform_value(&skip.rule, rule_name, value, ctx, outcome)
+            }
+        }
+        Transformers::Cache(cache) => {
+            let (result, hit) = cache.transform_cached(rule_name, value, ctx);
+            outcome.cache_hit = Some(hit);
+            result
+        }
+        Transformers::NormalizeEmpty(normalize) => {
+            let (result, normalized) = normalize.transform_normalizing(rule_name, value, ctx);
+            outcome.normalized = Some(normalized);
+            result
+        }
+        _ => tr.transform(rule_name, value, ctx),
+    }
+}
+
+/// The seed of the column value in the row (the run seed is the key of the hash)
+fn row_seed(seed: u64, table: &str, column: &str, row: u64) -> [u8; 32] {
+    utils::keyed_seed(
+        &seed.to_string(),
+        &format!("{}\0{}\0{}", table, column, row),
+    )
+}
+
+fn debug_tag(number: usize) -> String {
+    format!("«r{}»", number)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +394,516 @@ fn process_row() {
         assert_ne!(tr_values[4], "");
     }
 
+    #[test]
+    fn register_existing_value() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: engine_existing_values
+              rules:
+                code:
+                  random_num:
+                    min: 1
+                    max: 3
+                    uniq:
+                      try_count: 100
+                      unique_against:
+                        table: engine_existing_values
+                        column: code
+        "#;
+        let engine = Engine::new(Settings::from_yaml(config).unwrap());
+        let table = "engine_existing_values";
+        assert!(engine.register_existing_value(table, "code", "1"));
+        assert!(engine.register_existing_value(table, "code", "2"));
+        assert!(!engine.register_existing_value(table, "code", "2"));
+
+        let column_indexes = HashMap::from([(String::from("code"), 0)]);
+        let values = engine
+            .process_row(table.to_string(), &column_indexes, &["1"])
+            .unwrap();
+        assert_eq!(values[0], "3");
+        // all values are used
+        assert!(engine
+            .process_row(table.to_string(), &column_indexes, &["2"])
+            .is_err());
+    }
+
+    #[test]
+    fn register_kept_value() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: engine_late_kept
+              rules:
+                code: &rule
+                  skip_if:
+                    patterns: ["^1$"]
+                    rule:
+                      random_num:
+                        min: 1
+                        max: 1
+                        uniq: true
+            - name: engine_kept_values
+              rules:
+                code: *rule
+        "#;
+        let engine = Engine::new(Settings::from_yaml(config).unwrap());
+        let column_indexes = HashMap::from([(String::from("code"), 0)]);
+        let process = |table: &str, value: &str| {
+            engine
+                .process_row(table.to_string(), &column_indexes, &[value])
+                .map(|values| values[0].to_string())
+        };
+
+        // the kept value is generated before its row
+        assert_eq!(process("engine_late_kept", "5").unwrap(), "1");
+        assert!(process("engine_late_kept", "1")
+            .unwrap_err()
+            .to_string()
+            .contains("is already generated by its unique rule"));
+
+        engine.register_kept_value("engine_kept_values", "code", "1");
+        // all values are used
+        assert!(process("engine_kept_values", "5").is_err());
+        assert_eq!(process("engine_kept_values", "1").unwrap(), "1");
+    }
+
+    #[test]
+    fn process_row_skipping() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: actor
+              rules:
+                first_name:
+                  first_name: {}
+                email:
+                  email: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("first_name"), 0);
+        column_indexes.insert(String::from("email"), 1);
+        let skipped = HashSet::from([String::from("email")]);
+
+        let tr_values = Engine::new(settings)
+            .process_row_skipping(
+                String::from("actor"),
+                &column_indexes,
+                &["", "\\x01"],
+                &skipped,
+            )
+            .unwrap();
+
+        assert_ne!(tr_values[0], "");
+        assert_eq!(tr_values[1], "\\x01");
+    }
+
+    #[test]
+    fn process_row_elements() {
+        // `a|b` values
+        struct Pipes;
+
+        impl ElementCodec for Pipes {
+            fn transform_elements(
+                &self,
+                _field_name: &str,
+                value: &str,
+                transform: &mut dyn FnMut(&str) -> TransformResult,
+            ) -> TransformResult {
+                let mut elements = vec![];
+                for element in value.split('|') {
+                    elements.push(transform(element)?.unwrap_or_else(|| element.to_string()));
+                }
+                Ok(Some(elements.join("|")))
+            }
+        }
+
+        let config = r#"
+          tables:
+            - name: users
+              rules:
+                tags:
+                  template:
+                    format: "{{ _0 | upper }}"
+                name:
+                  template:
+                    format: "{{ _0 | upper }}"
+        "#;
+        let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+        let metrics = engine.enable_rule_timing();
+        let column_indexes = HashMap::from([(String::from("tags"), 0), (String::from("name"), 1)]);
+        let codecs = HashMap::from([(0, &Pipes as &dyn ElementCodec)]);
+
+        let tr_values = engine
+            .process_row_elements(
+                String::from("users"),
+                &column_indexes,
+                &["a|b|c", "x|y"],
+                &HashSet::new(),
+                &codecs,
+            )
+            .unwrap();
+        assert_eq!(tr_values, vec!["A|B|C", "X|Y"]);
+        // the rule is timed once per value
+        let report = metrics.report();
+        let tags = report.iter().find(|r| r.rule == "users.tags").unwrap();
+        assert_eq!(tags.count, 1);
+    }
+
+    #[test]
+    fn process_row_numbered() {
+        let config = |seed: &str| {
+            format!(
+                r#"
+                tables:
+                  - name: users
+                    rules:
+                      name:
+                        first_name: {{}}
+                      email:
+                        email: {{}}
+                      code:
+                        template:
+                          format: "{{{{ get_random(end=1000000) }}}}"
+                {}
+                "#,
+                seed
+            )
+        };
+        let engine = |seed: &str| Engine::new(Settings::from_yaml(&config(seed)).unwrap());
+        let column_indexes = HashMap::from([
+            (String::from("name"), 0),
+            (String::from("email"), 1),
+            (String::from("code"), 2),
+        ]);
+        let row = |engine: &Engine, table: &str, number: Option<u64>| {
+            engine
+                .process_row_numbered(
+                    table.to_string(),
+                    number,
+                    &column_indexes,
+                    &["John", "john@example.com", ""],
+                    &HashSet::new(),
+                    &HashMap::new(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect::<Vec<_>>()
+        };
+
+        // the same seed gives the same values of the same rows (in any order)
+        let first = engine("seed: 42");
+        let second = engine("seed: 42");
+        let rows: Vec<_> = (1..=3).map(|n| row(&first, "users", Some(n))).collect();
+        let reversed: Vec<_> = (1..=3)
+            .rev()
+            .map(|n| row(&second, "users", Some(n)))
+            .collect();
+        assert_eq!(rows, reversed.into_iter().rev().collect::<Vec<_>>());
+        assert_ne!(rows[0], rows[1]);
+
+        // another seed gives other values
+        let other = row(&engine("seed: 43"), "users", Some(1));
+        for i in 0..3 {
+            assert_ne!(rows[0][i], other[i]);
+        }
+
+        // the rows without numbers and the runs without a seed get random values
+        let codes: HashSet<_> = (0..5)
+            .map(|_| row(&first, "users", None)[2].clone())
+            .collect();
+        assert!(codes.len() > 1);
+        let unseeded = engine("");
+        let codes: HashSet<_> = (0..5)
+            .map(|_| row(&unseeded, "users", Some(1))[2].clone())
+            .collect();
+        assert!(codes.len() > 1);
+    }
+
+    #[test]
+    fn debug_tags() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: users
+              rules:
+                name:
+                  first_name: {}
+            - name: actor
+              rules:
+                last_name:
+                  none: ~
+                first_name:
+                  first_name: {}
+                middle_name:
+                  template:
+                    format: "\\N"
+        "#;
+        let mut engine = Engine::new(Settings::from_yaml(config).unwrap());
+        assert_eq!(
+            engine.enable_debug_tags(),
+            vec![
+                "«r1» users.name (first_name)",
+                "«r2» actor.first_name (first_name)",
+                "«r3» actor.last_name (none)",
+                "«r4» actor.middle_name (template)",
+            ]
+        );
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("first_name"), 0);
+        column_indexes.insert(String::from("last_name"), 1);
+        column_indexes.insert(String::from("middle_name"), 2);
+        column_indexes.insert(String::from("id"), 3);
+        let tr_values = engine
+            .process_row(
+                String::from("actor"),
+                &column_indexes,
+                &["Ann", "Smith", "Lee", "1"],
+            )
+            .unwrap();
+
+        assert!(tr_values[0].starts_with("«r2»"));
+        assert_ne!(tr_values[0], "«r2»Ann");
+        assert_eq!(tr_values[1], "«r3»Smith");
+        // NULLs and the columns without rules are not tagged
+        assert_eq!(tr_values[2], "\\N");
+        assert_eq!(tr_values[3], "1");
+    }
+
+    #[test]
+    fn rule_timing() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: actor
+              rules:
+                first_name:
+                  first_name: {}
+                last_name:
+                  last_name: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("first_name"), 0);
+        column_indexes.insert(String::from("last_name"), 1);
+
+        let mut engine = Engine::new(settings);
+        let metrics = engine.enable_rule_timing();
+        for _ in 0..3 {
+            engine
+                .process_row(String::from("actor"), &column_indexes, &["", ""])
+                .unwrap();
+        }
+
+        assert_eq!(metrics.get("actor.first_name").unwrap().count, 3);
+        assert_eq!(metrics.get("actor.last_name").unwrap().count, 3);
+        assert_eq!(metrics.report().len(), 2);
+    }
+
+    #[test]
+    fn normalize_empty() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: actor
+              rules:
+                first_name:
+                  normalize_empty:
+                    rule:
+                      first_name: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("first_name"), 0);
+
+        let mut engine = Engine::new(settings);
+        let metrics = engine.enable_rule_timing();
+        let mut transformed = vec![];
+        for value in ["N/A", " ", "\\N", "Alice"] {
+            let values = [value];
+            let row = engine
+                .process_row(String::from("actor"), &column_indexes, &values)
+                .unwrap();
+            transformed.push(row[0].to_string());
+        }
+
+        assert_eq!(transformed[0], NULL);
+        assert_eq!(transformed[1], NULL);
+        assert_eq!(transformed[2], NULL);
+        assert_ne!(transformed[3], NULL);
+        assert_ne!(transformed[3], "Alice");
+
+        let timing = metrics.get("actor.first_name").unwrap();
+        assert_eq!(timing.count, 4);
+        assert_eq!(timing.normalized, Some(2));
+    }
+
+    #[test]
+    fn skip_if() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: users
+              rules:
+                email:
+                  skip_if:
+                    domains: ["example.com"]
+                    rule:
+                      cache:
+                        rule:
+                          email: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("email"), 0);
+
+        let mut engine = Engine::new(settings);
+        let metrics = engine.enable_rule_timing();
+        let mut transformed = vec![];
+        for value in ["demo@example.com", "alice@corp.com", "alice@corp.com"] {
+            let values = [value];
+            let row = engine
+                .process_row(String::from("users"), &column_indexes, &values)
+                .unwrap();
+            transformed.push(row[0].to_string());
+        }
+
+        assert_eq!(transformed[0], "demo@example.com");
+        assert_ne!(transformed[1], "alice@corp.com");
+        assert_eq!(transformed[1], transformed[2]);
+
+        let timing = metrics.get("users.email").unwrap();
+        assert_eq!(timing.count, 3);
+        assert_eq!(timing.skipped, Some(1));
+        // the inner cache rule is recorded too
+        assert_eq!(timing.cache_hits, 1);
+        assert_eq!(timing.cache_misses, 1);
+    }
+
+    #[test]
+    fn consistent() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: users
+              rules:
+                email:
+                  consistent:
+                    rule:
+                      email: {}
+            - name: orders
+              rules:
+                customer_email:
+                  consistent:
+                    rule:
+                      email: {}
+          consistent_salt: secret
+        "#;
+        let column_indexes = |column: &str| HashMap::from([(column.to_string(), 0)]);
+        let emails = ["alice@example.com", "bob@example.com"];
+        // every engine is a separate run
+        let run = |table: &str, column: &str| {
+            let engine = Engine::new(Settings::from_yaml(config).unwrap());
+            emails
+                .iter()
+                .map(|email| {
+                    engine
+                        .process_row(table.to_string(), &column_indexes(column), &[*email])
+                        .unwrap()[0]
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let users = run("users", "email");
+        assert_eq!(run("orders", "customer_email"), users);
+        assert_eq!(run("users", "email"), users);
+        assert_ne!(users[0], users[1]);
+        assert!(!users.iter().any(|email| emails.contains(&email.as_str())));
+    }
+
+    #[test]
+    fn anonymity_check() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: users
+              quasi_identifiers:
+                - [zip, gender]
+              rules:
+                name:
+                  first_name: {}
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("name"), 0);
+        column_indexes.insert(String::from("zip"), 1);
+        column_indexes.insert(String::from("gender"), 2);
+
+        let mut engine = Engine::new(settings);
+        let metrics = engine.enable_anonymity_check();
+        for row in [["", "123", "f"], ["", "123", "f"], ["", "456", "m"]] {
+            engine
+                .process_row(String::from("users"), &column_indexes, &row)
+                .unwrap();
+        }
+
+        let report = metrics.report(2).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].rows, 3);
+        assert_eq!(report[0].classes, 2);
+        assert_eq!(report[0].classes_below_k, 1);
+
+        column_indexes.remove("gender");
+        assert!(matches!(
+            engine.process_row(String::from("users"), &column_indexes, &["", "1", "f"]),
+            Err(EngineError::UnknownColumnError(_))
+        ));
+    }
+
+    #[test]
+    fn timestamp_order() {
+        let config = r#"
+          source: {}
+          tables:
+            - name: posts
+              timestamp_order:
+                - columns: [created_at, updated_at]
+              rules:
+                created_at:
+                  datetime:
+                    from: 2020-01-01T00:00:00+00:00
+                    to: 2021-01-01T00:00:00+00:00
+                updated_at:
+                  datetime:
+                    from: 2020-01-01T00:00:00+00:00
+                    to: 2021-01-01T00:00:00+00:00
+        "#;
+        let settings = Settings::from_yaml(config).unwrap();
+
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert(String::from("created_at"), 0);
+        column_indexes.insert(String::from("updated_at"), 1);
+
+        let engine = Engine::new(settings);
+        for _ in 0..20 {
+            let values = engine
+                .process_row(String::from("posts"), &column_indexes, &["", ""])
+                .unwrap();
+            let created_at = chrono::DateTime::parse_from_rfc3339(&values[0]).unwrap();
+            let updated_at = chrono::DateTime::parse_from_rfc3339(&values[1]).unwrap();
+            assert!(created_at <= updated_at);
+        }
+    }
+
     mod row_refs {
         use super::*;
         use crate::transformers::CapitalizeTransformer;
@@ -1,19 +1,40 @@
+mod anonymity;
+mod derivation;
 mod engine;
 mod errors;
 mod locale;
+pub mod memory;
+mod metrics;
+mod pepper;
+pub mod secrets;
 mod settings;
 pub(crate) mod store;
+mod temp_disk;
 mod transformer;
 pub mod transformers;
 pub(crate) mod uniq_collector;
 mod utils;
 mod value;
 
-pub use engine::Engine;
+pub use anonymity::{AnonymityMetrics, AnonymityReport};
+pub use derivation::{Derivation, DERIVATION_VERSION, DERIVATION_VERSIONS};
+pub use engine::{ElementCodec, Engine};
 pub use locale::{ExtData, LocaleConfig, Localized, LocalizedFaker};
-pub use settings::{Filter, Query, Settings, Table, TableList, Tables};
+pub use metrics::{RuleMetrics, RuleReport, RuleTiming};
+pub use pepper::Pepper;
+pub use settings::{
+    parse_size, Compression, CompressionMethod, CycleStrategy, DdlReplacement, ExtensionTables,
+    Filter, Hooks, InvalidUtf8, Lint, LogicalReplication, MaterializedViews, OnOverflow,
+    OrderStrategy, Outbox, OutboxPayload, Partitions, Query, RenameObject, RowLimit, Safety,
+    Sample, SecretDetection, Sentinel, SequenceAction, Settings, ShuffleMethod, ShuffleRows,
+    SkipData, Subset, SubsetChildren, Table, TableList, TableSkipData, Tables, TargetProfile,
+    Tenant, TenantScope, TimestampOrder, UnknownEvents,
+};
+pub use temp_disk::{format_size, TempDisk, TempDiskError, TempFile};
 pub use transformer::{
-    TransformContext, TransformResult, Transformer, TransformerDefaults, TransformerInitContext,
+    RuleCategory, TransformContext, TransformError, TransformResult, Transformer,
+    TransformerDefaults, TransformerInitContext, TypeClass, UniqueAgainst,
 };
-pub use transformers::{AsSqlValue, FkTransformer, Transformers};
+pub use transformers::{AsSqlValue, FkTransformer, LearnedFormat, Transformers};
+pub use utils::{fmix64, fnv1a};
 pub use value::StringValue;
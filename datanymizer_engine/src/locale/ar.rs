@@ -0,0 +1,27 @@
+use crate::ExtData;
+use fake::locales::Data;
+
+/// Arabic (names only, other data is transliterated from English)
+#[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct AR;
+
+#[rustfmt::skip]
+impl Data for AR {
+    const NAME_FIRST_NAME: &'static [&'static str] = &[
+        "أحمد", "محمد", "علي", "عمر", "خالد", "يوسف", "إبراهيم", "حسن", "حسين", "سعيد", "طارق",
+        "كريم", "مصطفى", "ياسر", "سامي", "وليد", "فهد", "ماجد", "نبيل", "رامي", "فاطمة", "عائشة",
+        "مريم", "زينب", "خديجة", "سارة", "ليلى", "نور", "هدى", "سلمى", "رنا", "دينا", "أمل", "ياسمين",
+        "هالة", "منى",
+    ];
+
+    const NAME_LAST_NAME: &'static [&'static str] = &[
+        "العلي", "الحسن", "الخطيب", "المصري", "الشامي", "البغدادي", "الحلبي", "النجار", "الحداد",
+        "السيد", "عبد الله", "عبد الرحمن", "منصور", "سليمان", "يعقوب", "عيسى", "موسى", "داود",
+        "حمدان", "قاسم", "سالم", "ناصر", "جابر", "عثمان", "الزين", "الأحمد", "الصباغ", "العطار",
+    ];
+
+    const NAME_TITLE: &'static [&'static str] = &["السيد", "السيدة", "الآنسة", "الدكتور"];
+}
+
+impl ExtData for AR {}
@@ -0,0 +1,28 @@
+use crate::ExtData;
+use fake::locales::Data;
+
+/// Greek (names only, other data is transliterated from English)
+#[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct EL;
+
+#[rustfmt::skip]
+impl Data for EL {
+    const NAME_FIRST_NAME: &'static [&'static str] = &[
+        "Γιώργος", "Δημήτρης", "Κωνσταντίνος", "Γιάννης", "Νίκος", "Παναγιώτης", "Βασίλης",
+        "Χρήστος", "Αθανάσιος", "Μιχάλης", "Ευάγγελος", "Σπύρος", "Αντώνης", "Στέλιος", "Θοδωρής",
+        "Μαρία", "Ελένη", "Αικατερίνη", "Βασιλική", "Σοφία", "Αγγελική", "Γεωργία", "Δήμητρα",
+        "Κωνσταντίνα", "Παρασκευή", "Χριστίνα", "Ευαγγελία", "Ιωάννα", "Αναστασία", "Ειρήνη",
+    ];
+
+    const NAME_LAST_NAME: &'static [&'static str] = &[
+        "Παπαδόπουλος", "Βλάχος", "Αγγελόπουλος", "Νικολάου", "Γεωργίου", "Παπαδάκης", "Οικονόμου",
+        "Μακρής", "Παπανικολάου", "Ιωάννου", "Κωνσταντίνου", "Δημητρίου", "Πετρόπουλος", "Αντωνίου",
+        "Μαυρίδης", "Καραγιάννης", "Σταματόπουλος", "Χατζής", "Λαμπρόπουλος", "Ζαχαρίου",
+        "Αλεξίου", "Παναγιώτου", "Κυριακίδης", "Θεοδώρου", "Ραφαηλίδης",
+    ];
+
+    const NAME_TITLE: &'static [&'static str] = &["κ.", "κα", "δις", "Δρ"];
+}
+
+impl ExtData for EL {}
@@ -0,0 +1,26 @@
+use crate::ExtData;
+use fake::locales::Data;
+
+/// Hebrew (names only, other data is transliterated from English)
+#[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct HE;
+
+#[rustfmt::skip]
+impl Data for HE {
+    const NAME_FIRST_NAME: &'static [&'static str] = &[
+        "דוד", "משה", "יוסף", "אברהם", "יצחק", "יעקב", "דניאל", "אורי", "איתי", "נועם", "עומר",
+        "יונתן", "אריאל", "רועי", "אלון", "גיא", "שרה", "רחל", "לאה", "רבקה", "מיכל", "נועה",
+        "תמר", "יעל", "שירה", "מאיה", "הילה", "ענבל", "רונית", "אביגיל",
+    ];
+
+    const NAME_LAST_NAME: &'static [&'static str] = &[
+        "כהן", "לוי", "מזרחי", "פרץ", "ביטון", "דהן", "אברהם", "פרידמן", "אגבאריה", "מלכה",
+        "אזולאי", "כץ", "יוסף", "דוד", "עמר", "אוחיון", "חדד", "גבאי", "בן דוד", "שפירא", "רוזנברג",
+        "גולדברג", "שלום", "אשכנזי", "ברק",
+    ];
+
+    const NAME_TITLE: &'static [&'static str] = &["מר", "גברת", "ד\"ר"];
+}
+
+impl ExtData for HE {}
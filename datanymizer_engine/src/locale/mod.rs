@@ -3,27 +3,35 @@
 mod ext_data;
 pub use ext_data::ExtData;
 
+mod ar;
+pub use ar::AR;
+
+mod el;
+pub use el::EL;
+
+mod he;
+pub use he::HE;
+
 mod ru;
 pub use ru::RU;
 
+pub mod script;
+
 #[allow(clippy::upper_case_acronyms)]
 pub type EN = fake::locales::EN;
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub type ZH_TW = fake::locales::ZH_TW;
 
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
-#[derive(Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Default)]
 pub enum LocaleConfig {
+    #[default]
     EN,
     RU,
     ZH_TW,
-}
-
-impl Default for LocaleConfig {
-    // We need some method to take default for all tables from config
-    fn default() -> Self {
-        Self::EN
-    }
+    AR,
+    EL,
+    HE,
 }
 
 pub trait Localized {
@@ -35,10 +43,17 @@ pub trait LocalizedFaker<V>: Localized {
     fn fake<L: ExtData>(&self, l: L) -> V;
 
     fn localized_fake(&self) -> V {
-        match self.locale().unwrap_or_default() {
+        self.fake_with_locale(self.locale().unwrap_or_default())
+    }
+
+    fn fake_with_locale(&self, locale: LocaleConfig) -> V {
+        match locale {
             LocaleConfig::EN => self.fake(EN {}),
             LocaleConfig::RU => self.fake(RU {}),
             LocaleConfig::ZH_TW => self.fake(ZH_TW {}),
+            LocaleConfig::AR => self.fake(AR {}),
+            LocaleConfig::EL => self.fake(EL {}),
+            LocaleConfig::HE => self.fake(HE {}),
         }
     }
 }
@@ -0,0 +1,162 @@
+use super::LocaleConfig;
+
+/// Only the first characters are checked (it is enough for names and keeps the detection cheap)
+const DETECTION_LIMIT: usize = 32;
+
+/// Latin letters (`a`..`z`) transliterated to other scripts
+#[rustfmt::skip]
+const CYRILLIC: [&str; 26] = [
+    "а", "б", "к", "д", "е", "ф", "г", "х", "и", "й", "к", "л", "м",
+    "н", "о", "п", "к", "р", "с", "т", "у", "в", "в", "кс", "ы", "з",
+];
+#[rustfmt::skip]
+const GREEK: [&str; 26] = [
+    "α", "β", "κ", "δ", "ε", "φ", "γ", "η", "ι", "ι", "κ", "λ", "μ",
+    "ν", "ο", "π", "κ", "ρ", "σ", "τ", "υ", "β", "ω", "ξ", "υ", "ζ",
+];
+#[rustfmt::skip]
+const ARABIC: [&str; 26] = [
+    "ا", "ب", "ك", "د", "ي", "ف", "ج", "ه", "ي", "ج", "ك", "ل", "م",
+    "ن", "و", "ب", "ق", "ر", "س", "ت", "و", "ف", "و", "كس", "ي", "ز",
+];
+#[rustfmt::skip]
+const HEBREW: [&str; 26] = [
+    "א", "ב", "כ", "ד", "ה", "פ", "ג", "ה", "י", "ג", "ק", "ל", "מ",
+    "נ", "ו", "פ", "ק", "ר", "ס", "ט", "ו", "ו", "ו", "קס", "י", "ז",
+];
+
+/// Unicode scripts that we can generate values in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Han,
+}
+
+impl Script {
+    pub(crate) fn of(c: char) -> Option<Self> {
+        match c {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' if c.is_alphabetic() => {
+                Some(Self::Latin)
+            }
+            '\u{0400}'..='\u{04FF}' => Some(Self::Cyrillic),
+            '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Self::Greek),
+            '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => Some(Self::Arabic),
+            '\u{0590}'..='\u{05FF}' => Some(Self::Hebrew),
+            '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => Some(Self::Han),
+            _ => None,
+        }
+    }
+
+    /// The locale with data in this script
+    pub fn locale(&self) -> LocaleConfig {
+        match self {
+            Self::Latin => LocaleConfig::EN,
+            Self::Cyrillic => LocaleConfig::RU,
+            Self::Greek => LocaleConfig::EL,
+            Self::Arabic => LocaleConfig::AR,
+            Self::Hebrew => LocaleConfig::HE,
+            Self::Han => LocaleConfig::ZH_TW,
+        }
+    }
+
+    /// Transliterates Latin letters into this script. Locales take the data they don't have
+    /// from English, so it keeps such values in the right script (Han is not supported).
+    pub fn transliterate(&self, value: &str) -> String {
+        let table = match self {
+            Self::Cyrillic => &CYRILLIC,
+            Self::Greek => &GREEK,
+            Self::Arabic => &ARABIC,
+            Self::Hebrew => &HEBREW,
+            Self::Latin | Self::Han => return value.to_string(),
+        };
+
+        let mut result = String::with_capacity(value.len() * 2);
+        for c in value.chars() {
+            if c.is_ascii_alphabetic() {
+                let letter = table[(c.to_ascii_lowercase() as u8 - b'a') as usize];
+                if c.is_ascii_uppercase() {
+                    result.push_str(&letter.to_uppercase());
+                } else {
+                    result.push_str(letter);
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+}
+
+/// Detects the dominant script of the value (by the majority of letters among the first characters).
+/// Returns `None` if there are no letters of the supported scripts.
+pub fn detect(value: &str) -> Option<Script> {
+    let mut counts: Vec<(Script, usize)> = Vec::with_capacity(2);
+    for script in value.chars().take(DETECTION_LIMIT).filter_map(Script::of) {
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+
+    // the script that appears first wins a tie
+    counts
+        .into_iter()
+        .fold(
+            None,
+            |best: Option<(Script, usize)>, (script, count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((script, count)),
+            },
+        )
+        .map(|(script, _)| script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_scripts() {
+        assert_eq!(detect("John Smith"), Some(Script::Latin));
+        assert_eq!(detect("Jérôme"), Some(Script::Latin));
+        assert_eq!(detect("Иван Петров"), Some(Script::Cyrillic));
+        assert_eq!(detect("Γιώργος"), Some(Script::Greek));
+        assert_eq!(detect("محمد العلي"), Some(Script::Arabic));
+        assert_eq!(detect("דוד כהן"), Some(Script::Hebrew));
+        assert_eq!(detect("王小明"), Some(Script::Han));
+        assert_eq!(detect("12-34 !"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn detect_by_majority() {
+        assert_eq!(detect("Иван Petrov-Водкин"), Some(Script::Cyrillic));
+        assert_eq!(detect("John 王"), Some(Script::Latin));
+        assert_eq!(detect("ab аб"), Some(Script::Latin));
+    }
+
+    #[test]
+    fn detect_first_chars_only() {
+        let value = format!("{}{}", "a".repeat(DETECTION_LIMIT), "я".repeat(100));
+        assert_eq!(detect(&value), Some(Script::Latin));
+    }
+
+    #[test]
+    fn transliterate() {
+        assert_eq!(Script::Cyrillic.transliterate("Lorem ipsum"), "Лорем ипсум");
+        assert_eq!(Script::Greek.transliterate("dolor"), "δολορ");
+        assert_eq!(
+            detect(&Script::Arabic.transliterate("sit amet")),
+            Some(Script::Arabic)
+        );
+        assert_eq!(
+            detect(&Script::Hebrew.transliterate("sit amet")),
+            Some(Script::Hebrew)
+        );
+        assert_eq!(Script::Han.transliterate("lorem"), "lorem");
+    }
+}
@@ -0,0 +1,254 @@
+//! The memory accounting of the data that the components keep for a table or for the whole run
+//! (the uniqueness sets, the rule caches, the template store, the shuffle buffers, the k-anonymity classes).
+//!
+//! The components add the approximate bytes of their entries to the process accountant (the uniqueness sets
+//! are kept for the process too) and release them when the entries are dropped. The dumper checks the total
+//! against `--max-memory` while dumping the rows, the components that can spill to disk spill when it is exceeded.
+
+use crate::temp_disk::format_size;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use thiserror::Error;
+
+pub const UNIQUENESS_SETS: &str = "the uniqueness sets (`uniq`)";
+pub const RULE_CACHES: &str = "the caches of the `cache` rules";
+pub const TEMPLATE_STORE: &str = "the template store (`store_write`)";
+pub const SHUFFLE_BUFFERS: &str = "the shuffle buffers (`shuffle_rows`)";
+pub const ANONYMITY_CLASSES: &str = "the k-anonymity classes (`quasi_identifiers`)";
+
+static ACCOUNTANT: Lazy<MemoryAccountant> = Lazy::new(MemoryAccountant::default);
+
+/// The accountant of the process
+pub fn accountant() -> &'static MemoryAccountant {
+    &ACCOUNTANT
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "The memory of the dump exceeds --max-memory {} ({} is used, {} by {component}), \
+    raise --max-memory or reduce {component}",
+    format_size(*max),
+    format_size(*used),
+    format_size(*component_used)
+)]
+pub struct MemoryError {
+    /// The component that uses the most memory
+    pub component: String,
+    pub max: u64,
+    pub used: u64,
+    pub component_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Total {
+    used: AtomicU64,
+    peak: AtomicU64,
+}
+
+/// The memory of a component, the bytes are added to the total of the accountant too
+#[derive(Debug)]
+pub struct Component {
+    used: AtomicU64,
+    peak: AtomicU64,
+    total: Arc<Total>,
+}
+
+impl Component {
+    pub fn add(&self, bytes: u64) {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(used, Ordering::Relaxed);
+        let total = self.total.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.total.peak.fetch_max(total, Ordering::Relaxed);
+    }
+
+    pub fn release(&self, bytes: u64) {
+        // the releases never exceed the additions of the component
+        let bytes = match self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            }) {
+            Ok(used) | Err(used) => used.min(bytes),
+        };
+        self.total.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+/// The components by their names (the components with the same name share the counters)
+#[derive(Debug, Default)]
+pub struct MemoryAccountant {
+    components: Mutex<BTreeMap<String, Arc<Component>>>,
+    total: Arc<Total>,
+}
+
+impl MemoryAccountant {
+    pub fn component(&self, name: &str) -> Arc<Component> {
+        let mut components = self
+            .components
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        components
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(Component {
+                    used: AtomicU64::new(0),
+                    peak: AtomicU64::new(0),
+                    total: self.total.clone(),
+                })
+            })
+            .clone()
+    }
+
+    /// The bytes of all components
+    pub fn used(&self) -> u64 {
+        self.total.used.load(Ordering::Relaxed)
+    }
+
+    /// Whether the total exceeds `max_bytes` (there is no limit without it)
+    pub fn exceeds(&self, max_bytes: Option<u64>) -> bool {
+        max_bytes.is_some_and(|max| self.used() > max)
+    }
+
+    /// Fails if the total exceeds `max_bytes`, the error names the component that uses the most memory
+    pub fn check(&self, max_bytes: u64) -> Result<(), MemoryError> {
+        let used = self.used();
+        if used <= max_bytes {
+            return Ok(());
+        }
+
+        let (component, component_used) = self
+            .usage()
+            .components
+            .into_iter()
+            .map(|(name, c)| (name, c.used))
+            .max_by_key(|(_, used)| *used)
+            .unwrap_or_default();
+        Err(MemoryError {
+            component,
+            max: max_bytes,
+            used,
+            component_used,
+        })
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        let components = self
+            .components
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        MemoryUsage {
+            used: self.used(),
+            peak: self.total.peak.load(Ordering::Relaxed),
+            components: components
+                .iter()
+                .filter(|(_, c)| c.peak.load(Ordering::Relaxed) > 0)
+                .map(|(name, c)| {
+                    (
+                        name.clone(),
+                        ComponentUsage {
+                            used: c.used(),
+                            peak: c.peak.load(Ordering::Relaxed),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ComponentUsage {
+    pub used: u64,
+    pub peak: u64,
+}
+
+/// The memory of the components (only the used ones)
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub used: u64,
+    pub peak: u64,
+    pub components: BTreeMap<String, ComponentUsage>,
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", format_size(self.used))?;
+        if !self.components.is_empty() {
+            let components: Vec<_> = self
+                .components
+                .iter()
+                .map(|(name, c)| format!("{} {}", name, format_size(c.used)))
+                .collect();
+            write!(f, " ({})", components.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accounting() {
+        let accountant = MemoryAccountant::default();
+        let caches = accountant.component(RULE_CACHES);
+        let shuffle = accountant.component(SHUFFLE_BUFFERS);
+        caches.add(300);
+        shuffle.add(500);
+        accountant.component(RULE_CACHES).add(100);
+        assert_eq!(caches.used(), 400);
+        assert_eq!(accountant.used(), 900);
+
+        shuffle.release(500);
+        // more than added
+        caches.release(1000);
+        assert_eq!(accountant.used(), 0);
+        caches.add(50);
+
+        let usage = accountant.usage();
+        assert_eq!(usage.used, 50);
+        assert_eq!(usage.peak, 900);
+        assert_eq!(
+            usage.components[SHUFFLE_BUFFERS],
+            ComponentUsage { used: 0, peak: 500 }
+        );
+        assert_eq!(usage.components[RULE_CACHES].peak, 400);
+        assert_eq!(
+            usage.to_string(),
+            "50B (the caches of the `cache` rules 50B, the shuffle buffers (`shuffle_rows`) 0B)"
+        );
+    }
+
+    #[test]
+    fn check() {
+        let accountant = MemoryAccountant::default();
+        accountant.component(UNIQUENESS_SETS).add(3 << 20);
+        accountant.component(TEMPLATE_STORE).add(1 << 20);
+        assert!(accountant.check(4 << 20).is_ok());
+        assert!(!accountant.exceeds(Some(4 << 20)));
+        assert!(!accountant.exceeds(None));
+
+        accountant.component(TEMPLATE_STORE).add(1 << 20);
+        assert!(accountant.exceeds(Some(4 << 20)));
+        let error = accountant.check(4 << 20).unwrap_err();
+        assert_eq!(error.component, UNIQUENESS_SETS);
+        assert_eq!(
+            error.to_string(),
+            "The memory of the dump exceeds --max-memory 4MB (5MB is used, 3MB by the uniqueness sets (`uniq`)), \
+            raise --max-memory or reduce the uniqueness sets (`uniq`)"
+        );
+    }
+}
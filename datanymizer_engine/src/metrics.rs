@@ -0,0 +1,386 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// One bucket for each power of two of nanoseconds (the last one is for `u64::MAX`)
+const BUCKETS: usize = 65;
+
+/// Invocation count and latency histogram of a single rule.
+/// The histogram has power-of-two buckets, so percentiles are approximate (the upper bound
+/// of the bucket is returned), but recording is cheap and memory usage is constant.
+#[derive(Clone, Debug)]
+pub struct RuleTiming {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Cache hits and misses (for the `cache` rules)
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Normalized values (for the `normalize_empty` rules)
+    pub normalized: Option<u64>,
+    /// Kept values (for the `skip_if` rules)
+    pub skipped: Option<u64>,
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for RuleTiming {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total: Duration::default(),
+            max: Duration::default(),
+            cache_hits: 0,
+            cache_misses: 0,
+            normalized: None,
+            skipped: None,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+impl RuleTiming {
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[(u64::BITS - nanos.leading_zeros()) as usize] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::default()
+        } else {
+            // the count can exceed `u32::MAX` (`Duration` is divided by `u32` only)
+            let nanos = self.total.as_nanos() / self.count as u128;
+            Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+        }
+    }
+
+    /// Returns the approximate latency percentile (`p` is from 0 to 100)
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = if i == 0 {
+                    0
+                } else {
+                    u64::MAX >> (BUCKETS - 1 - i)
+                };
+                return Duration::from_nanos(upper).min(self.max);
+            }
+        }
+
+        self.max
+    }
+}
+
+/// Timing metrics for all rules (the keys are `table.column`)
+#[derive(Debug, Default)]
+pub struct RuleMetrics {
+    rules: Mutex<HashMap<String, RuleTiming>>,
+}
+
+impl RuleMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, rule: &str, duration: Duration) {
+        if let Ok(mut rules) = self.rules.lock() {
+            match rules.get_mut(rule) {
+                Some(timing) => timing.record(duration),
+                None => {
+                    let mut timing = RuleTiming::default();
+                    timing.record(duration);
+                    rules.insert(rule.to_string(), timing);
+                }
+            }
+        }
+    }
+
+    /// Records a cache hit or miss (the call itself is recorded with `record`)
+    pub fn record_cache(&self, rule: &str, hit: bool) {
+        if let Ok(mut rules) = self.rules.lock() {
+            if let Some(timing) = rules.get_mut(rule) {
+                if hit {
+                    timing.cache_hits += 1;
+                } else {
+                    timing.cache_misses += 1;
+                }
+            }
+        }
+    }
+
+    /// Counts a normalized value of a `normalize_empty` rule (the call itself is recorded with `record`)
+    pub fn record_normalized(&self, rule: &str, normalized: bool) {
+        if let Ok(mut rules) = self.rules.lock() {
+            if let Some(timing) = rules.get_mut(rule) {
+                *timing.normalized.get_or_insert(0) += u64::from(normalized);
+            }
+        }
+    }
+
+    /// Counts a kept value of a `skip_if` rule (the call itself is recorded with `record`)
+    pub fn record_skipped(&self, rule: &str, skipped: bool) {
+        if let Ok(mut rules) = self.rules.lock() {
+            if let Some(timing) = rules.get_mut(rule) {
+                *timing.skipped.get_or_insert(0) += u64::from(skipped);
+            }
+        }
+    }
+
+    pub fn get(&self, rule: &str) -> Option<RuleTiming> {
+        self.rules.lock().ok().and_then(|r| r.get(rule).cloned())
+    }
+
+    /// Reports for all rules sorted by the total time (the slowest rules first)
+    pub fn report(&self) -> Vec<RuleReport> {
+        let mut report: Vec<_> = match self.rules.lock() {
+            Ok(rules) => rules
+                .iter()
+                .map(|(name, timing)| RuleReport::new(name, timing))
+                .collect(),
+            Err(_) => vec![],
+        };
+        report.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms).then(a.rule.cmp(&b.rule)));
+
+        report
+    }
+
+    pub fn slowest(&self, count: usize) -> Vec<RuleReport> {
+        let mut report = self.report();
+        report.truncate(count);
+        report
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.report())
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RuleReport {
+    pub rule: String,
+    pub count: u64,
+    pub total_ms: f64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hits: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_misses: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<u64>,
+}
+
+impl RuleReport {
+    fn new(rule: &str, timing: &RuleTiming) -> Self {
+        let us = |d: Duration| d.as_secs_f64() * 1_000_000.0;
+        let cached = timing.cache_hits + timing.cache_misses > 0;
+
+        Self {
+            rule: rule.to_string(),
+            count: timing.count,
+            total_ms: timing.total.as_secs_f64() * 1000.0,
+            mean_us: us(timing.mean()),
+            p50_us: us(timing.percentile(50.0)),
+            p95_us: us(timing.percentile(95.0)),
+            p99_us: us(timing.percentile(99.0)),
+            max_us: us(timing.max),
+            cache_hits: cached.then_some(timing.cache_hits),
+            cache_misses: cached.then_some(timing.cache_misses),
+            normalized: timing.normalized,
+            skipped: timing.skipped,
+        }
+    }
+}
+
+impl Display for RuleReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} calls, total {:.1} ms, mean {:.1} µs, p50 {:.1} µs, p95 {:.1} µs, p99 {:.1} µs, max {:.1} µs",
+            self.rule,
+            self.count,
+            self.total_ms,
+            self.mean_us,
+            self.p50_us,
+            self.p95_us,
+            self.p99_us,
+            self.max_us
+        )?;
+        if let (Some(hits), Some(misses)) = (self.cache_hits, self.cache_misses) {
+            write!(f, ", cache hits {}, misses {}", hits, misses)?;
+        }
+        if let Some(normalized) = self.normalized {
+            write!(f, ", normalized {}", normalized)?;
+        }
+        if let Some(skipped) = self.skipped {
+            write!(f, ", skipped {}", skipped)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod rule_timing {
+        use super::*;
+
+        #[test]
+        fn empty() {
+            let t = RuleTiming::default();
+            assert_eq!(t.count, 0);
+            assert_eq!(t.mean(), Duration::default());
+            assert_eq!(t.percentile(99.0), Duration::default());
+        }
+
+        #[test]
+        fn record() {
+            let mut t = RuleTiming::default();
+            t.record(Duration::from_micros(10));
+            t.record(Duration::from_micros(30));
+
+            assert_eq!(t.count, 2);
+            assert_eq!(t.total, Duration::from_micros(40));
+            assert_eq!(t.max, Duration::from_micros(30));
+            assert_eq!(t.mean(), Duration::from_micros(20));
+        }
+
+        #[test]
+        fn mean_of_many() {
+            let t = RuleTiming {
+                count: 1 << 33,
+                total: Duration::from_nanos(3 << 33),
+                ..Default::default()
+            };
+            assert_eq!(t.mean(), Duration::from_nanos(3));
+        }
+
+        #[test]
+        fn percentiles() {
+            let mut t = RuleTiming::default();
+            for _ in 0..98 {
+                t.record(Duration::from_nanos(100));
+            }
+            t.record(Duration::from_millis(1));
+            t.record(Duration::from_millis(10));
+
+            // the upper bound of the [64, 128) ns bucket
+            assert_eq!(t.percentile(50.0), Duration::from_nanos(127));
+            assert_eq!(t.percentile(95.0), Duration::from_nanos(127));
+            assert!(t.percentile(99.0) >= Duration::from_millis(1));
+            assert!(t.percentile(99.0) < Duration::from_millis(2));
+            assert_eq!(t.percentile(100.0), Duration::from_millis(10));
+        }
+    }
+
+    mod rule_metrics {
+        use super::*;
+
+        fn metrics() -> RuleMetrics {
+            let m = RuleMetrics::new();
+            m.record("users.email", Duration::from_micros(5));
+            m.record("users.email", Duration::from_micros(5));
+            m.record("users.name", Duration::from_millis(1));
+            m.record("orders.comment", Duration::from_micros(1));
+            m
+        }
+
+        #[test]
+        fn get() {
+            let m = metrics();
+            assert_eq!(m.get("users.email").unwrap().count, 2);
+            assert_eq!(m.get("users.name").unwrap().count, 1);
+            assert!(m.get("users.phone").is_none());
+        }
+
+        #[test]
+        fn slowest() {
+            let names: Vec<_> = metrics().slowest(2).into_iter().map(|r| r.rule).collect();
+            assert_eq!(names, vec!["users.name", "users.email"]);
+        }
+
+        #[test]
+        fn to_json() {
+            let m = RuleMetrics::new();
+            m.record("users.email", Duration::from_micros(2));
+            let json: serde_json::Value = serde_json::from_str(&m.to_json().unwrap()).unwrap();
+
+            assert_eq!(json[0]["rule"], "users.email");
+            assert_eq!(json[0]["count"], 1);
+            assert_eq!(json[0]["max_us"], 2.0);
+        }
+
+        #[test]
+        fn record_cache() {
+            let m = RuleMetrics::new();
+            m.record("users.country", Duration::from_micros(2));
+            m.record_cache("users.country", false);
+            m.record("users.country", Duration::from_micros(2));
+            m.record_cache("users.country", true);
+
+            let timing = m.get("users.country").unwrap();
+            assert_eq!(timing.cache_hits, 1);
+            assert_eq!(timing.cache_misses, 1);
+            assert!(m.report()[0]
+                .to_string()
+                .ends_with(", cache hits 1, misses 1"));
+        }
+
+        #[test]
+        fn record_normalized() {
+            let m = RuleMetrics::new();
+            m.record("users.phone", Duration::from_micros(2));
+            m.record_normalized("users.phone", false);
+            assert_eq!(m.get("users.phone").unwrap().normalized, Some(0));
+
+            m.record("users.phone", Duration::from_micros(2));
+            m.record_normalized("users.phone", true);
+            assert_eq!(m.get("users.phone").unwrap().normalized, Some(1));
+            assert!(m.report()[0].to_string().ends_with(", normalized 1"));
+
+            let json: serde_json::Value = serde_json::from_str(&m.to_json().unwrap()).unwrap();
+            assert_eq!(json[0]["normalized"], 1);
+        }
+
+        #[test]
+        fn record_skipped() {
+            let m = RuleMetrics::new();
+            m.record("users.email", Duration::from_micros(2));
+            m.record_skipped("users.email", true);
+            m.record("users.email", Duration::from_micros(2));
+            m.record_skipped("users.email", false);
+            assert_eq!(m.get("users.email").unwrap().skipped, Some(1));
+            assert!(m.report()[0].to_string().ends_with(", skipped 1"));
+
+            let json: serde_json::Value = serde_json::from_str(&m.to_json().unwrap()).unwrap();
+            assert_eq!(json[0]["skipped"], 1);
+            assert!(json[0].get("normalized").is_none());
+        }
+
+        #[test]
+        fn display() {
+            let m = RuleMetrics::new();
+            m.record("users.email", Duration::from_micros(2));
+            assert_eq!(
+                m.report()[0].to_string(),
+                "users.email: 1 calls, total 0.0 ms, mean 2.0 µs, p50 2.0 µs, p95 2.0 µs, p99 2.0 µs, max 2.0 µs"
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! The run pepper.
+//!
+//! The pepper is a random secret that is mixed into the salts of all salted rules (`consistent`, `redact`,
+//! `bytea_placeholder`, the `consistent` modes of `boolean` and `scramble`, the `hash` local parts of `email`),
+//! so the hashes of different runs can't be linked even if the salts are weak or reused.
+//! Both the rule salt and the pepper are mixed in: the rule salt still separates the rules of one run.
+//!
+//! A new pepper is generated for every run unless it is persisted in a file (`--pepper-file`):
+//! then the runs with the same file (and the same salts) give the same values.
+//! Without a file the runs with the run seed (`--seed`) get the pepper derived from the seed.
+//! The reports identify the pepper by its fingerprint only.
+
+use crate::utils;
+use rand::RngCore;
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The random bytes of a generated pepper
+const LENGTH: usize = 32;
+
+/// The pepper of the run (there is no pepper by default, e.g. in the library use and the tests)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pepper {
+    value: String,
+    /// The file the pepper is persisted in
+    file: Option<PathBuf>,
+    /// The pepper is derived from the run seed
+    seeded: bool,
+}
+
+impl Pepper {
+    /// A random pepper for one run
+    pub fn random() -> Self {
+        let mut bytes = [0; LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let mut value = String::with_capacity(LENGTH * 2);
+        for b in bytes {
+            let _ = write!(value, "{:02x}", b);
+        }
+        Self {
+            value,
+            file: None,
+            seeded: false,
+        }
+    }
+
+    /// The pepper derived from the run seed (the runs with the same seed get the same pepper)
+    pub fn from_seed(seed: u64) -> Self {
+        let mut value = String::with_capacity(LENGTH * 2);
+        for b in utils::keyed_seed("datanymizer-pepper", &seed.to_string()) {
+            let _ = write!(value, "{:02x}", b);
+        }
+        Self {
+            value,
+            file: None,
+            seeded: true,
+        }
+    }
+
+    /// Reads the persisted pepper, or persists a new random pepper if the file doesn't exist
+    /// (the file is readable by the owner only)
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        let value = match fs::read_to_string(path) {
+            Ok(content) => {
+                let value = content.trim().to_string();
+                if value.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the file is empty",
+                    ));
+                }
+                value
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let value = Self::random().value;
+                write_private(path, &value)?;
+                value
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            value,
+            file: Some(path.to_path_buf()),
+            seeded: false,
+        })
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The file of the persisted pepper (`None` for a random pepper)
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn is_persisted(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// The pepper is the same in other runs (it is persisted or derived from the run seed)
+    pub fn is_reproducible(&self) -> bool {
+        self.is_persisted() || self.seeded
+    }
+
+    /// Identifies the pepper in the reports (a 64-bit hash of a 256-bit value, it doesn't reveal the value),
+    /// `None` if there is no pepper
+    pub fn fingerprint(&self) -> Option<String> {
+        if self.value.is_empty() {
+            return None;
+        }
+        let mut bytes = b"datanymizer-pepper\0".to_vec();
+        bytes.extend_from_slice(self.value.as_bytes());
+        Some(format!("{:016x}", utils::fmix64(utils::fnv1a(&bytes))))
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, value: &str) -> io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    writeln!(file, "{}", value)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, value: &str) -> io::Result<()> {
+    fs::write(path, format!("{}\n", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random() {
+        let pepper = Pepper::random();
+        assert_eq!(pepper.value().len(), LENGTH * 2);
+        assert_ne!(pepper, Pepper::random());
+        assert!(!pepper.is_persisted());
+        assert_eq!(pepper.fingerprint().unwrap().len(), 16);
+        assert_eq!(Pepper::default().fingerprint(), None);
+    }
+
+    #[test]
+    fn from_seed() {
+        let pepper = Pepper::from_seed(42);
+        assert_eq!(pepper.value().len(), LENGTH * 2);
+        assert_eq!(pepper, Pepper::from_seed(42));
+        assert_ne!(pepper, Pepper::from_seed(43));
+        assert!(!pepper.is_persisted());
+        assert!(pepper.is_reproducible());
+        assert!(!Pepper::random().is_reproducible());
+    }
+
+    #[test]
+    fn persisted() {
+        let dir = std::env::temp_dir().join(format!("datanymizer_pepper_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pepper");
+        let _ = fs::remove_file(&path);
+
+        let created = Pepper::load_or_create(&path).unwrap();
+        assert!(created.is_persisted());
+        assert!(created.is_reproducible());
+        assert_eq!(created.file(), Some(path.as_path()));
+        let loaded = Pepper::load_or_create(&path).unwrap();
+        assert_eq!(loaded, created);
+        assert_eq!(loaded.fingerprint(), created.fingerprint());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::write(&path, "  \n").unwrap();
+        assert_eq!(
+            Pepper::load_or_create(&path).unwrap_err().to_string(),
+            "the file is empty"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde_json::Value as JsonValue;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+};
+
+/// Prefixes of the secret references and the cargo features of the providers
+const PROVIDERS: [(&str, &str); 2] = [("vault", "vault"), ("aws-sm", "aws-sm")];
+
+static GLOBAL_SECRETS: Lazy<Mutex<Secrets>> = Lazy::new(|| Mutex::new(Secrets::new()));
+
+/// Reference to a secret in a config value, e.g. `vault:secret/data/anonymizer#salt`
+/// or `aws-sm:anonymizer/salt` (the key after `#` is optional).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretRef {
+    pub provider: String,
+    pub path: String,
+    pub key: Option<String>,
+}
+
+impl SecretRef {
+    /// Returns `None` if the value is not a secret reference (has no known provider prefix)
+    pub fn parse(value: &str) -> Option<Self> {
+        let (provider, rest) = value.split_once(':')?;
+        if !PROVIDERS.iter().any(|(name, _)| *name == provider) || rest.is_empty() {
+            return None;
+        }
+
+        let (path, key) = match rest.split_once('#') {
+            Some((path, key)) => (path, Some(key.to_string())),
+            None => (rest, None),
+        };
+
+        Some(Self {
+            provider: provider.to_string(),
+            path: path.to_string(),
+            key,
+        })
+    }
+}
+
+impl Display for SecretRef {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.provider, self.path)?;
+        if let Some(key) = &self.key {
+            write!(f, "#{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Source of secrets. Errors must not contain the secret values (even partially fetched).
+pub trait SecretProvider: Send {
+    fn fetch(&self, path: &str, key: Option<&str>) -> Result<String>;
+}
+
+/// Resolves secret references. Every reference is fetched only once.
+#[derive(Default)]
+pub struct Secrets {
+    providers: HashMap<String, Box<dyn SecretProvider>>,
+    cache: HashMap<String, String>,
+    used: BTreeSet<String>,
+}
+
+impl Secrets {
+    /// With the providers enabled by cargo features
+    pub fn new() -> Self {
+        #[allow(unused_mut)]
+        let mut secrets = Self::default();
+        #[cfg(feature = "vault")]
+        {
+            secrets = secrets.with_provider("vault", providers::Vault);
+        }
+        #[cfg(feature = "aws-sm")]
+        {
+            secrets = secrets.with_provider("aws-sm", providers::AwsSecretsManager);
+        }
+        secrets
+    }
+
+    pub fn with_provider<P: 'static + SecretProvider>(mut self, name: &str, provider: P) -> Self {
+        self.providers.insert(name.to_string(), Box::new(provider));
+        self
+    }
+
+    /// Returns `None` if the value is not a secret reference
+    pub fn resolve(&mut self, value: &str) -> Result<Option<String>> {
+        let reference = match SecretRef::parse(value) {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+
+        let name = reference.to_string();
+        if let Some(secret) = self.cache.get(&name) {
+            return Ok(Some(secret.clone()));
+        }
+
+        let provider = self.providers.get(&reference.provider).ok_or_else(|| {
+            let feature = PROVIDERS
+                .iter()
+                .find(|(name, _)| *name == reference.provider)
+                .map(|(_, feature)| *feature)
+                .unwrap_or_default();
+            anyhow!(
+                "Can't resolve the secret `{}`: the provider `{}` is not enabled (build with the `{}` feature)",
+                name,
+                reference.provider,
+                feature
+            )
+        })?;
+        let secret = provider
+            .fetch(&reference.path, reference.key.as_deref())
+            .map_err(|e| anyhow!("Can't resolve the secret `{}`: {}", name, e))?;
+
+        self.used.insert(reference.provider);
+        self.cache.insert(name, secret.clone());
+        Ok(Some(secret))
+    }
+
+    /// Replaces all string values (including nested ones) that are secret references
+    pub fn resolve_json(&mut self, value: &mut JsonValue) -> Result<()> {
+        match value {
+            JsonValue::String(s) => {
+                if let Some(secret) = self.resolve(s)? {
+                    *s = secret;
+                }
+            }
+            JsonValue::Array(items) => {
+                for item in items {
+                    self.resolve_json(item)?;
+                }
+            }
+            JsonValue::Object(map) => {
+                for (_, item) in map.iter_mut() {
+                    self.resolve_json(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Names of the providers that were used (the values are never exposed)
+    pub fn used_providers(&self) -> Vec<String> {
+        self.used.iter().cloned().collect()
+    }
+}
+
+/// Resolves the value with the global (process-wide) resolver
+pub fn resolve(value: &str) -> Result<Option<String>> {
+    global().resolve(value)
+}
+
+/// Resolves the JSON value with the global (process-wide) resolver
+pub fn resolve_json(value: &mut JsonValue) -> Result<()> {
+    global().resolve_json(value)
+}
+
+/// Names of the providers used by the global resolver
+pub fn used_providers() -> Vec<String> {
+    global().used_providers()
+}
+
+fn global() -> std::sync::MutexGuard<'static, Secrets> {
+    GLOBAL_SECRETS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(any(feature = "vault", feature = "aws-sm"))]
+mod providers {
+    use super::SecretProvider;
+    use anyhow::{anyhow, Result};
+    use serde_json::Value as JsonValue;
+    use std::process::Command;
+
+    // Runs the CLI utility, the output is never included in the errors
+    fn run(program: &str, args: &[&str]) -> Result<String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| anyhow!("can't run `{}`: {}", program, e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "`{}` failed ({}): {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        String::from_utf8(output.stdout).map_err(|_| anyhow!("the secret is not valid UTF-8"))
+    }
+
+    fn field(data: &JsonValue, key: &str) -> Result<String> {
+        match data.get(key) {
+            Some(JsonValue::String(s)) => Ok(s.clone()),
+            Some(JsonValue::Null) | None => Err(anyhow!("no key `{}` in the secret", key)),
+            Some(other) => Ok(other.to_string()),
+        }
+    }
+
+    /// HashiCorp Vault (via the `vault` CLI, so `VAULT_ADDR`, `VAULT_TOKEN`, etc. are used).
+    /// Both KV v1 and KV v2 (`secret/data/...`) paths are supported.
+    #[cfg(feature = "vault")]
+    pub struct Vault;
+
+    #[cfg(feature = "vault")]
+    impl SecretProvider for Vault {
+        fn fetch(&self, path: &str, key: Option<&str>) -> Result<String> {
+            let output = run("vault", &["read", "-format=json", path])?;
+            let response: JsonValue = serde_json::from_str(&output)
+                .map_err(|_| anyhow!("unexpected `vault` output format"))?;
+            let data = &response["data"];
+            let data = match data.get("data") {
+                Some(nested) if nested.is_object() => nested,
+                _ => data,
+            };
+
+            field(data, key.unwrap_or("value"))
+        }
+    }
+
+    /// AWS Secrets Manager (via the `aws` CLI, so the usual AWS credentials and region are used).
+    /// With a key, the secret string is parsed as a JSON object.
+    #[cfg(feature = "aws-sm")]
+    pub struct AwsSecretsManager;
+
+    #[cfg(feature = "aws-sm")]
+    impl SecretProvider for AwsSecretsManager {
+        fn fetch(&self, path: &str, key: Option<&str>) -> Result<String> {
+            let output = run(
+                "aws",
+                &[
+                    "secretsmanager",
+                    "get-secret-value",
+                    "--secret-id",
+                    path,
+                    "--query",
+                    "SecretString",
+                    "--output",
+                    "text",
+                ],
+            )?;
+            let secret = output.trim_end_matches('\n');
+
+            match key {
+                Some(key) => {
+                    let data: JsonValue = serde_json::from_str(secret)
+                        .map_err(|_| anyhow!("the secret is not a JSON object"))?;
+                    field(&data, key)
+                }
+                None => Ok(secret.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct StaticProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl SecretProvider for StaticProvider {
+        fn fetch(&self, path: &str, key: Option<&str>) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match (path, key) {
+                ("secret/data/app", Some("salt")) => Ok("s3cr3t".to_string()),
+                _ => Err(anyhow!("not found")),
+            }
+        }
+    }
+
+    fn secrets() -> (Secrets, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let secrets = Secrets::default().with_provider(
+            "vault",
+            StaticProvider {
+                calls: calls.clone(),
+            },
+        );
+        (secrets, calls)
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            SecretRef::parse("vault:secret/data/app#salt"),
+            Some(SecretRef {
+                provider: "vault".to_string(),
+                path: "secret/data/app".to_string(),
+                key: Some("salt".to_string())
+            })
+        );
+        assert_eq!(
+            SecretRef::parse("aws-sm:app/salt").unwrap().to_string(),
+            "aws-sm:app/salt"
+        );
+        assert_eq!(SecretRef::parse("plain value"), None);
+        assert_eq!(SecretRef::parse("http://example.com"), None);
+        assert_eq!(SecretRef::parse("vault:"), None);
+    }
+
+    #[test]
+    fn resolve_with_cache() {
+        let (mut secrets, calls) = secrets();
+
+        assert_eq!(secrets.resolve("just text").unwrap(), None);
+        for _ in 0..2 {
+            assert_eq!(
+                secrets.resolve("vault:secret/data/app#salt").unwrap(),
+                Some("s3cr3t".to_string())
+            );
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(secrets.used_providers(), vec!["vault".to_string()]);
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let (mut secrets, _) = secrets();
+
+        let err = secrets.resolve("vault:secret/data/app#other").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't resolve the secret `vault:secret/data/app#other`: not found"
+        );
+
+        let err = secrets.resolve("aws-sm:app/salt").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't resolve the secret `aws-sm:app/salt`: the provider `aws-sm` is not enabled (build with the `aws-sm` feature)"
+        );
+        assert!(secrets.used_providers().is_empty());
+    }
+
+    #[test]
+    fn resolve_json() {
+        let (mut secrets, _) = secrets();
+        let mut value = json!({
+            "salt": "vault:secret/data/app#salt",
+            "nested": ["vault:secret/data/app#salt", 1, "text"]
+        });
+
+        secrets.resolve_json(&mut value).unwrap();
+        assert_eq!(
+            value,
+            json!({"salt": "s3cr3t", "nested": ["s3cr3t", 1, "text"]})
+        );
+    }
+}
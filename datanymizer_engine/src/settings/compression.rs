@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+/// The compression methods of the dump output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMethod {
+    Gzip,
+    Zstd,
+}
+
+impl CompressionMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// The allowed levels (inclusive)
+    pub fn levels(&self) -> (i32, i32) {
+        match self {
+            Self::Gzip => (0, 9),
+            Self::Zstd => (1, 22),
+        }
+    }
+
+    pub fn default_level(&self) -> i32 {
+        match self {
+            Self::Gzip => 6,
+            Self::Zstd => 3,
+        }
+    }
+}
+
+/// The compression of the dump output: `gzip`, `zstd` or with a level, e.g. `zstd:19`
+/// (`--compress` overrides it)
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Compression {
+    pub method: CompressionMethod,
+    level: Option<i32>,
+}
+
+impl Compression {
+    pub fn new(method: CompressionMethod, level: Option<i32>) -> Result<Self, String> {
+        if let Some(level) = level {
+            let (min, max) = method.levels();
+            if !(min..=max).contains(&level) {
+                return Err(format!(
+                    "The {} compression level must be from {} to {} (got {})",
+                    method.name(),
+                    min,
+                    max,
+                    level
+                ));
+            }
+        }
+        Ok(Self { method, level })
+    }
+
+    /// The specified level or the default level of the method
+    pub fn level(&self) -> i32 {
+        self.level.unwrap_or_else(|| self.method.default_level())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, level) = match s.split_once(':') {
+            Some((method, level)) => (method, Some(level)),
+            None => (s, None),
+        };
+        let method = match method {
+            "gzip" => CompressionMethod::Gzip,
+            "zstd" => CompressionMethod::Zstd,
+            _ => {
+                return Err(format!(
+                "Unknown compression `{}` (gzip or zstd, optionally with a level, e.g. `zstd:19`)",
+                method
+            ))
+            }
+        };
+        let level = level
+            .map(|level| {
+                level
+                    .parse()
+                    .map_err(|_| format!("Invalid compression level `{}`", level))
+            })
+            .transpose()?;
+        Self::new(method, level)
+    }
+}
+
+impl TryFrom<String> for Compression {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.method.name(), self.level())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let c: Compression = "gzip".parse().unwrap();
+        assert_eq!(c.method, CompressionMethod::Gzip);
+        assert_eq!(c.level(), 6);
+
+        let c: Compression = "zstd:19".parse().unwrap();
+        assert_eq!(c.method, CompressionMethod::Zstd);
+        assert_eq!(c.level(), 19);
+        assert_eq!(c.to_string(), "zstd:19");
+
+        assert_eq!("zstd".parse::<Compression>().unwrap().level(), 3);
+        assert_eq!("gzip:0".parse::<Compression>().unwrap().level(), 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!("lz4".parse::<Compression>().is_err());
+        assert!("gzip:fast".parse::<Compression>().is_err());
+        assert_eq!(
+            "gzip:10".parse::<Compression>(),
+            Err("The gzip compression level must be from 0 to 9 (got 10)".to_string())
+        );
+        assert!("zstd:0".parse::<Compression>().is_err());
+        assert!("zstd:23".parse::<Compression>().is_err());
+    }
+}
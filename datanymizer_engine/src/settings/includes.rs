@@ -0,0 +1,472 @@
+use super::{interpolation, table};
+use config::{Config, ConfigError, File, FileFormat};
+use serde_json::{Map, Value as JsonValue};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const INCLUDE_KEY: &str = "include";
+const TABLES_KEY: &str = "tables";
+const NAME_KEY: &str = "name";
+const RULES_KEY: &str = "rules";
+/// The template function that reads the environment variables
+const GET_ENV: &str = "get_env";
+/// The template files (they are read from the disk)
+const TEMPLATE_FILES: &str = "/templates/files";
+/// The SQL conditions of the table queries (they are run in the source database)
+const QUERY_CONDITIONS: [&str; 2] = ["dump_condition", "transform_condition"];
+
+/// The declared rule order of the tables (from the YAML files)
+pub(super) type DeclaredRules = HashMap<String, Vec<String>>;
+
+/// The config with the environment variables replaced and the included files merged
+pub(super) struct Loaded {
+    pub config: Config,
+    pub declared_rules: DeclaredRules,
+    /// From an untrusted source (the secret references are not resolved)
+    pub restricted: bool,
+}
+
+/// Loads the config with the files from `include` (the paths are relative to the including file):
+///
+/// ```yaml
+/// include: [common/pii.yml, "rules/${ENVIRONMENT}.yml"]
+///
+/// tables:
+///   - name: users
+///     rules:
+///       # overrides the rule of the included files
+///       bio:
+///         none: ~
+/// ```
+///
+/// The files are merged in order, the later files override the earlier ones, the including file overrides
+/// all of them. The tables are merged by their names: the rules are merged per column, the other table options
+/// are replaced. The other dictionaries (e.g. `globals`) are merged per key, the other sections are replaced.
+/// The included files can include other files. The environment variables are replaced in every file.
+pub(super) fn load(
+    config: Config,
+    yaml: Option<&str>,
+    path: Option<&Path>,
+) -> Result<Loaded, ConfigError> {
+    let dir = path
+        .and_then(|p| p.parent())
+        .unwrap_or_else(|| Path::new(""));
+    let mut stack: Vec<_> = path
+        .and_then(|p| p.canonicalize().ok())
+        .into_iter()
+        .collect();
+    let root: JsonValue = config.try_into()?;
+    let (root, declared_rules) = resolve(root, yaml, dir, &mut stack)?;
+
+    let mut config = Config::new();
+    config.merge(File::from_str(&root.to_string(), FileFormat::Json))?;
+    Ok(Loaded {
+        config,
+        declared_rules,
+        restricted: false,
+    })
+}
+
+/// Loads the config from an untrusted source (e.g. a service request): `include`, the `get_env`
+/// template function, `templates.files` and the query conditions are errors, the environment variables
+/// are not replaced (`${...}` is kept as is)
+pub(super) fn load_restricted(config: Config, yaml: Option<&str>) -> Result<Loaded, ConfigError> {
+    let root: JsonValue = config.try_into()?;
+    if root.get(INCLUDE_KEY).is_some() {
+        return Err(not_allowed(INCLUDE_KEY));
+    }
+    if contains(&root, GET_ENV) {
+        return Err(not_allowed(GET_ENV));
+    }
+    if root.pointer(TEMPLATE_FILES).is_some_and(|f| !f.is_null()) {
+        return Err(not_allowed("templates.files"));
+    }
+    if let Some(condition) = query_condition(&root) {
+        return Err(not_allowed(&format!("query.{}", condition)));
+    }
+
+    let mut config = Config::new();
+    config.merge(File::from_str(&root.to_string(), FileFormat::Json))?;
+    Ok(Loaded {
+        config,
+        declared_rules: declared_rules(yaml),
+        restricted: true,
+    })
+}
+
+fn not_allowed(name: &str) -> ConfigError {
+    ConfigError::Message(format!("`{}` isn't allowed in this config", name))
+}
+
+/// Whether any string value (including nested ones) contains the pattern
+fn contains(value: &JsonValue, pattern: &str) -> bool {
+    match value {
+        JsonValue::String(s) => s.contains(pattern),
+        JsonValue::Array(items) => items.iter().any(|i| contains(i, pattern)),
+        JsonValue::Object(map) => map.values().any(|i| contains(i, pattern)),
+        _ => false,
+    }
+}
+
+/// The first SQL condition in the queries of the tables
+fn query_condition(root: &JsonValue) -> Option<&'static str> {
+    root.get(TABLES_KEY)?
+        .as_array()?
+        .iter()
+        .filter_map(|t| t.get("query"))
+        .find_map(|query| {
+            QUERY_CONDITIONS
+                .into_iter()
+                .find(|c| query.get(c).is_some_and(|v| !v.is_null()))
+        })
+}
+
+fn declared_rules(yaml: Option<&str>) -> DeclaredRules {
+    yaml.and_then(|y| serde_yaml::from_str::<serde_yaml::Value>(y).ok())
+        .map(|y| table::declared_rules(&y[TABLES_KEY]))
+        .unwrap_or_default()
+}
+
+fn resolve(
+    mut root: JsonValue,
+    yaml: Option<&str>,
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(JsonValue, DeclaredRules), ConfigError> {
+    interpolation::interpolate(&mut root)?;
+    let declared = declared_rules(yaml);
+
+    let includes = match root.as_object_mut().and_then(|r| r.remove(INCLUDE_KEY)) {
+        None | Some(JsonValue::Null) => return Ok((root, declared)),
+        Some(JsonValue::String(path)) => vec![path],
+        Some(JsonValue::Array(paths)) => paths
+            .into_iter()
+            .map(|p| match p {
+                JsonValue::String(p) => Some(p),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(include_error)?,
+        Some(_) => return Err(include_error()),
+    };
+
+    let mut merged = JsonValue::Object(Map::new());
+    let mut merged_declared = DeclaredRules::new();
+    for include in includes {
+        let path = dir.join(&include);
+        let canonical = path.canonicalize().map_err(|e| {
+            ConfigError::Message(format!(
+                "Can't read the included config file `{}`: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        if stack.contains(&canonical) {
+            return Err(ConfigError::Message(format!(
+                "The config file `{}` includes itself",
+                path.display()
+            )));
+        }
+
+        let mut config = Config::new();
+        config.merge(File::from(path.as_path()))?;
+        let is_yaml = path.extension().is_some_and(|e| e == "yml" || e == "yaml");
+        let source = if is_yaml {
+            fs::read_to_string(&path).ok()
+        } else {
+            None
+        };
+
+        stack.push(canonical);
+        let (value, declared) = resolve(
+            config.try_into()?,
+            source.as_deref(),
+            path.parent().unwrap_or(dir),
+            stack,
+        )?;
+        stack.pop();
+
+        merge(&mut merged, value);
+        merge_declared(&mut merged_declared, declared);
+    }
+    merge(&mut merged, root);
+    merge_declared(&mut merged_declared, declared);
+
+    Ok((merged, merged_declared))
+}
+
+fn include_error() -> ConfigError {
+    ConfigError::Message(format!("`{}` must be a list of file paths", INCLUDE_KEY))
+}
+
+// The sections of `over` override the sections of `base`
+fn merge(base: &mut JsonValue, over: JsonValue) {
+    let (base, over) = match (base.as_object_mut(), over) {
+        (Some(base), JsonValue::Object(over)) => (base, over),
+        (_, over) => {
+            *base = over;
+            return;
+        }
+    };
+
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(JsonValue::Array(tables)), JsonValue::Array(over)) if key == TABLES_KEY => {
+                for table in over {
+                    merge_table(tables, table);
+                }
+            }
+            (Some(JsonValue::Object(section)), JsonValue::Object(over)) => section.extend(over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// The rules of the tables with the same name are merged per column, the other options are replaced
+fn merge_table(tables: &mut Vec<JsonValue>, table: JsonValue) {
+    let name = table.get(NAME_KEY).cloned();
+    let base = tables
+        .iter_mut()
+        .filter_map(|t| t.as_object_mut())
+        .find(|t| name.is_some() && t.get(NAME_KEY) == name.as_ref());
+    let (base, table) = match (base, table) {
+        (Some(base), JsonValue::Object(table)) => (base, table),
+        (_, table) => {
+            tables.push(table);
+            return;
+        }
+    };
+
+    for (key, value) in table {
+        match (base.get_mut(&key), value) {
+            (Some(JsonValue::Object(rules)), JsonValue::Object(over)) if key == RULES_KEY => {
+                rules.extend(over)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// The columns of the earlier files go first
+fn merge_declared(base: &mut DeclaredRules, over: DeclaredRules) {
+    for (table, columns) in over {
+        let base = base.entry(table).or_default();
+        for column in columns {
+            if !base.contains(&column) {
+                base.push(column);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LocaleConfig, Settings, Transformers};
+    use serde_json::json;
+    use std::{env, process};
+
+    // A directory with the config files, it is removed when the value is dropped
+    struct ConfigDir(PathBuf);
+
+    impl ConfigDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir =
+                env::temp_dir().join(format!("datanymizer_includes_{}_{}", name, process::id()));
+            fs::create_dir_all(dir.join("rules")).unwrap();
+            for (file, content) in files {
+                fs::write(dir.join(file), content).unwrap();
+            }
+            Self(dir)
+        }
+
+        fn settings(&self) -> Result<Settings, ConfigError> {
+            Settings::new(self.0.join("config.yml").to_string_lossy().to_string())
+        }
+    }
+
+    impl Drop for ConfigDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn rule_names(settings: &Settings, table: &str) -> Vec<(String, &'static str)> {
+        let mut rules: Vec<_> = settings
+            .get_table(table)
+            .unwrap()
+            .rules
+            .iter()
+            .map(|(column, rule)| (column.clone(), rule.name()))
+            .collect();
+        rules.sort();
+        rules
+    }
+
+    #[test]
+    fn merge_sections() {
+        let mut base = json!({
+            "tables": [
+                {"name": "users", "rules": {"email": {"email": {}}, "bio": {"capitalize": null}}, "limit": 10},
+                {"name": "orders", "rules": {"note": {"none": null}}}
+            ],
+            "globals": {"a": 1, "b": 2},
+            "filter": {"data": {"except": ["logs"]}},
+            "dump_first": ["users"]
+        });
+        merge(
+            &mut base,
+            json!({
+                "tables": [
+                    {"name": "users", "rules": {"bio": {"none": null}, "phone": {"phone": {}}}, "limit": 5},
+                    {"name": "accounts", "rules": {}}
+                ],
+                "globals": {"b": 3},
+                "dump_first": ["orders"]
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "tables": [
+                    {
+                        "name": "users",
+                        "rules": {"email": {"email": {}}, "bio": {"none": null}, "phone": {"phone": {}}},
+                        "limit": 5
+                    },
+                    {"name": "orders", "rules": {"note": {"none": null}}},
+                    {"name": "accounts", "rules": {}}
+                ],
+                "globals": {"a": 1, "b": 3},
+                "filter": {"data": {"except": ["logs"]}},
+                "dump_first": ["orders"]
+            })
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let dir = ConfigDir::new(
+            "includes",
+            &[
+                (
+                    "config.yml",
+                    r#"
+include: [rules/pii.yml, rules/overrides.yml]
+tables:
+  - name: users
+    rules:
+      # disables the included rule
+      bio:
+        none: ~
+  - name: orders
+    rules:
+      note:
+        capitalize: ~
+"#,
+                ),
+                (
+                    "rules/pii.yml",
+                    r#"
+include: [common.yml]
+tables:
+  - name: users
+    rules:
+      email:
+        email: {}
+      bio:
+        capitalize: ~
+      phone:
+        phone: {}
+"#,
+                ),
+                (
+                    "rules/common.yml",
+                    r#"
+default:
+  locale: RU
+tables:
+  - name: users
+    rules:
+      first_name:
+        first_name: {}
+"#,
+                ),
+                (
+                    "rules/overrides.yml",
+                    r#"
+tables:
+  - name: users
+    rules:
+      phone:
+        none: ~
+"#,
+                ),
+            ],
+        );
+        let settings = dir.settings().unwrap();
+        assert_eq!(
+            rule_names(&settings, "users"),
+            vec![
+                ("bio".to_string(), "none"),
+                ("email".to_string(), "email"),
+                ("first_name".to_string(), "first_name"),
+                ("phone".to_string(), "none"),
+            ]
+        );
+        assert_eq!(
+            rule_names(&settings, "orders"),
+            vec![("note".to_string(), "capitalize")]
+        );
+        assert!(matches!(
+            settings.get_table("users").unwrap().rules["bio"],
+            Transformers::None(_)
+        ));
+        // the declared order of the included rules goes first
+        assert_eq!(
+            settings.get_table("users").unwrap().declared_rules,
+            vec!["first_name", "email", "bio", "phone"]
+        );
+        assert_eq!(settings.default.locale, LocaleConfig::RU);
+    }
+
+    #[test]
+    fn invalid_includes() {
+        let dir = ConfigDir::new(
+            "missing",
+            &[("config.yml", "include: [missing.yml]\ntables: []")],
+        );
+        let e = dir.settings().unwrap_err().to_string();
+        assert!(
+            e.starts_with("Can't read the included config file") && e.contains("missing.yml"),
+            "{}",
+            e
+        );
+
+        let dir = ConfigDir::new(
+            "cycle",
+            &[
+                ("config.yml", "include: [rules/a.yml]\ntables: []"),
+                ("rules/a.yml", "include: [../config.yml]\ntables: []"),
+            ],
+        );
+        let e = dir.settings().unwrap_err().to_string();
+        assert!(
+            e.starts_with("The config file") && e.ends_with("config.yml` includes itself"),
+            "{}",
+            e
+        );
+
+        let e = Settings::from_yaml("include: {a: 1}\ntables: []")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(e, "`include` must be a list of file paths");
+    }
+}
@@ -0,0 +1,251 @@
+use config::ConfigError;
+use serde_json::Value as JsonValue;
+use std::env;
+
+/// The sections with regexes and regex replacements
+const RAW_SECTIONS: [&str; 4] = [
+    "sensitive_object_names",
+    "rename_objects",
+    "skip_patterns",
+    "pii_columns",
+];
+
+/// Replaces the environment variables in the string values of the config:
+///
+/// ```yaml
+/// consistent_salt: ${ANONYMIZER_SALT}
+/// default:
+///   # the default is used if the variable is not set or empty
+///   locale: ${ANONYMIZER_LOCALE:-EN}
+/// filter:
+///   schema:
+///     only: [public, "${TENANT_SCHEMA}"]
+/// ```
+///
+/// A missing variable without a default is an error. `$${` is a literal `${`, and `${...}` that are not
+/// variable names (e.g. `${1}`) are kept as is. The regexes and their replacements (`RAW_SECTIONS`
+/// and the `patterns` of `skip_if`) are not interpolated, `${name}` is a named group there.
+pub(super) fn interpolate(value: &mut JsonValue) -> Result<(), ConfigError> {
+    interpolate_with(value, &|name| env::var(name).ok(), "").map_err(ConfigError::Message)
+}
+
+fn interpolate_with(
+    value: &mut JsonValue,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    path: &str,
+) -> Result<(), String> {
+    if is_raw(path) {
+        return Ok(());
+    }
+    match value {
+        JsonValue::String(s) if s.contains('$') => {
+            *s = interpolate_str(s, lookup).map_err(|e| format!("{} (in `{}`)", e, path))?;
+        }
+        JsonValue::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_with(item, lookup, &format!("{}[{}]", path, i))?;
+            }
+        }
+        JsonValue::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                interpolate_with(item, lookup, &path)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn interpolate_str(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let body = match rest.strip_prefix("${") {
+            Some(body) => body,
+            None => {
+                result.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let end = body
+            .find('}')
+            .ok_or_else(|| format!("Unterminated `${{` in `{}`", s))?;
+        let (name, default) = match body[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&body[..end], None),
+        };
+        // e.g. the regex groups in `rename_objects` (`${1}`)
+        if !is_variable_name(name) {
+            result.push_str("${");
+            rest = body;
+            continue;
+        }
+
+        let value = match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => {
+                return Err(format!(
+                    "The environment variable `{}` is not set (use `${{{}:-default}}` for a default value)",
+                    name, name
+                ))
+            }
+        };
+        result.push_str(&value);
+        rest = &body[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn is_raw(path: &str) -> bool {
+    RAW_SECTIONS.contains(&path) || path.ends_with(".skip_if.patterns")
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use serde_json::json;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "SALT" => Some("s3cr3t".to_string()),
+            "LOCALE" => Some("RU".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        }
+    }
+
+    fn interpolate(s: &str) -> Result<String, String> {
+        interpolate_str(s, &lookup)
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(interpolate("${SALT}"), Ok("s3cr3t".to_string()));
+        assert_eq!(
+            interpolate("${LOCALE}-${SALT}!"),
+            Ok("RU-s3cr3t!".to_string())
+        );
+        assert_eq!(interpolate("${MISSING:-EN}"), Ok("EN".to_string()));
+        assert_eq!(interpolate("${EMPTY:-EN}"), Ok("EN".to_string()));
+        assert_eq!(interpolate("${EMPTY}"), Ok("".to_string()));
+        assert_eq!(interpolate("${LOCALE:-EN}"), Ok("RU".to_string()));
+        assert_eq!(interpolate("${MISSING:-}"), Ok("".to_string()));
+        assert_eq!(
+            interpolate("$$5 and $${SALT}"),
+            Ok("$$5 and ${SALT}".to_string())
+        );
+        assert_eq!(interpolate("{{ _1 }}$"), Ok("{{ _1 }}$".to_string()));
+        assert_eq!(
+            interpolate("${1}_${SALT}${2}"),
+            Ok("${1}_s3cr3t${2}".to_string())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            interpolate("${MISSING}"),
+            Err("The environment variable `MISSING` is not set \
+                (use `${MISSING:-default}` for a default value)"
+                .to_string())
+        );
+        assert_eq!(
+            interpolate("a ${SALT"),
+            Err("Unterminated `${` in `a ${SALT`".to_string())
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let mut value = json!({"tables": [{"name": "users", "rules": {"email": {"email": {"domain": "${MISSING}"}}}}]});
+        assert_eq!(
+            interpolate_with(&mut value, &lookup, ""),
+            Err("The environment variable `MISSING` is not set \
+                (use `${MISSING:-default}` for a default value) (in `tables[0].rules.email.email.domain`)"
+                .to_string())
+        );
+
+        let mut value = json!({"default": {"locale": "${LOCALE}"}, "filter": ["${SALT}", 1]});
+        interpolate_with(&mut value, &lookup, "").unwrap();
+        assert_eq!(
+            value,
+            json!({"default": {"locale": "RU"}, "filter": ["s3cr3t", 1]})
+        );
+    }
+
+    #[test]
+    fn raw_sections() {
+        let mut value = json!({
+            "rename_objects": [{"pattern": "^(?P<name>\\w+)_acme$", "replace": "${name}_customer"}],
+            "skip_patterns": ["^${MISSING}"],
+            "rule_templates": {"t": {"email": {"skip_if": {"patterns": ["${MISSING}"], "domains": ["${SALT}"]}}}}
+        });
+        let expected = json!({
+            "rename_objects": [{"pattern": "^(?P<name>\\w+)_acme$", "replace": "${name}_customer"}],
+            "skip_patterns": ["^${MISSING}"],
+            "rule_templates": {"t": {"email": {"skip_if": {"patterns": ["${MISSING}"], "domains": ["s3cr3t"]}}}}
+        });
+        interpolate_with(&mut value, &lookup, "").unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn named_groups() {
+        // the configs written before the interpolation load as they did
+        let config = r#"
+            tables: []
+            sensitive_object_names: ["acmecorp"]
+            rename_objects:
+              - pattern: "^(?P<prefix>\\w+)_acmecorp$"
+                replace: "${prefix}_customer"
+            "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        assert_eq!(settings.rename_objects[0].replace, "${prefix}_customer");
+    }
+
+    #[test]
+    fn settings() {
+        env::set_var("DATANYMIZER_TEST_INTERPOLATION_SALT", "from env");
+        let config = r#"
+            tables: []
+            consistent_salt: ${DATANYMIZER_TEST_INTERPOLATION_SALT}
+            default_limit: ${DATANYMIZER_TEST_INTERPOLATION_LIMIT:-100}
+            "#;
+        let settings = Settings::from_yaml(config).unwrap();
+        assert_eq!(settings.consistent_salt, Some("from env".to_string()));
+        assert_eq!(settings.default_limit, Some(100));
+
+        let e = Settings::from_yaml("tables: []\nconsistent_salt: ${DATANYMIZER_TEST_MISSING}")
+            .unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("The environment variable `DATANYMIZER_TEST_MISSING` is not set"));
+    }
+}
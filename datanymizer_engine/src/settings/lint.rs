@@ -0,0 +1,98 @@
+use super::Table;
+use crate::RuleCategory;
+use serde::Deserialize;
+
+/// Lint options of a column
+///
+/// ```yaml
+/// tables:
+///   - name: users
+///     rules:
+///       # a contact, not a personal name
+///       first_name:
+///         email: {}
+///     lint:
+///       first_name: allow_mismatch
+/// ```
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Lint {
+    /// The rule may generate values of another category than the column name suggests
+    AllowMismatch,
+}
+
+/// The columns whose names strongly suggest one category (e.g. `first_name`), but whose rules generate
+/// values of another category (e.g. `email`)
+pub(super) fn mismatches(table: &Table) -> Vec<String> {
+    let mut columns: Vec<_> = table
+        .rules
+        .iter()
+        .filter(|(column, _)| table.lint.get(*column) != Some(&Lint::AllowMismatch))
+        .collect();
+    columns.sort_unstable_by_key(|(column, _)| *column);
+
+    columns
+        .into_iter()
+        .filter_map(|(column, rule)| {
+            let expected = RuleCategory::of_column(column)?;
+            let category = rule.category();
+            (category != RuleCategory::Any && category != expected).then(|| {
+                format!(
+                    "the column `{}` of the table `{}` looks like {} data, but its rule `{}` generates {} data \
+                    (use `lint: {{{}: allow_mismatch}}` if it is intended)",
+                    column,
+                    table.name,
+                    expected,
+                    rule.name(),
+                    category,
+                    column
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Settings;
+
+    #[test]
+    fn mismatches() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  first_name:
+                    email: {}
+                  last_name:
+                    last_name: {}
+                  created_at:
+                    pipeline:
+                      pipes:
+                        - datetime: {}
+                        - city: {}
+                  phone:
+                    template:
+                      format: "+1 {{ _0 }}"
+                  login:
+                    email: {}
+              - name: contacts
+                rules:
+                  first_name:
+                    email: {}
+                lint:
+                  first_name: allow_mismatch
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap().lint_warnings(),
+            vec![
+                "the column `created_at` of the table `users` looks like date data, \
+                but its rule `pipeline` generates address data \
+                (use `lint: {created_at: allow_mismatch}` if it is intended)",
+                "the column `first_name` of the table `users` looks like name data, \
+                but its rule `email` generates email data \
+                (use `lint: {first_name: allow_mismatch}` if it is intended)",
+            ]
+        );
+    }
+}
@@ -1,26 +1,246 @@
+mod compression;
 mod filter;
+mod includes;
+mod interpolation;
+mod lint;
+mod outbox;
+mod presets;
+mod row_limit;
+mod rule_templates;
+mod safety;
+mod secret_detection;
+mod sequence;
+mod shuffle_rows;
+mod skip_data;
+mod subset;
 mod table;
 mod templates;
+mod tenant;
+mod timestamp_order;
 
 use crate::{
+    pepper::Pepper,
+    secrets,
     transformer::{TransformerDefaults, TransformerInitContext},
-    transformers::Transformers,
-    Transformer,
+    transformers::{NoneTransformer, SkipIfTransformer, Transformers},
+    RuleCategory, Transformer, DERIVATION_VERSION, DERIVATION_VERSIONS,
 };
 use anyhow::Result;
 use config::{Config, ConfigError, File, FileFormat};
+use regex::Regex;
 use serde::Deserialize;
-use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use serde_json::{json, Value as JsonValue};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::Path,
+    str::FromStr,
+};
+
+/// The column names that look like personal data if `pii_columns` is not specified
+const DEFAULT_PII_COLUMNS: [&str; 12] = [
+    "e_?mail",
+    "phone|mobile",
+    "(first|last|middle|full|maiden)_?name|surname",
+    "birth|^dob$",
+    "address|street|zip_?code|postal",
+    "ssn|social_security|national_id|tax_?id",
+    "passport|driver_?licen[cs]e",
+    "iban|account_?number|card_?number",
+    "ip_?addr",
+    "password|secret",
+    "latitude|longitude",
+    "gender",
+];
 
+/// The environment variable that overrides `consistent_salt`
+const CONSISTENT_SALT_ENV: &str = "DATANYMIZER_CONSISTENT_SALT";
+
+pub use compression::{Compression, CompressionMethod};
 pub use filter::{Filter, TableList};
+pub use lint::Lint;
+pub use outbox::{Outbox, OutboxPayload, UnknownEvents};
+pub use row_limit::{RowLimit, Sample};
+pub use safety::{parse_size, Safety, Sentinel};
+pub use secret_detection::SecretDetection;
+pub use sequence::SequenceAction;
+pub use shuffle_rows::{ShuffleMethod, ShuffleRows};
+pub use skip_data::{SkipData, TableSkipData};
+pub use subset::{Subset, SubsetChildren};
 pub use table::{Query, Table};
 pub use templates::TemplatesCollection;
+pub use tenant::{Tenant, TenantScope};
+pub use timestamp_order::{OrderStrategy, TimestampOrder};
 
 pub type Tables = Vec<Table>;
 
 type TransformList = Vec<(String, Transformers)>;
 
+/// Replacement of a string literal in DDL (column defaults and check constraints)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct DdlReplacement {
+    pub from: String,
+    pub to: String,
+}
+
+/// Raw SQL statements that are written to the dump (they aren't executed against the source database)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Hooks {
+    /// After the schema (pre-data), before the table data
+    #[serde(default)]
+    pub pre_data: Vec<String>,
+    /// After the table data and the sequence values, before the indexes and constraints (post-data)
+    #[serde(default)]
+    pub post_data: Vec<String>,
+}
+
+/// Renaming of the index and constraint names that match the regex (`replace` can use the groups, e.g. `$1`)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct RenameObject {
+    pub pattern: String,
+    pub replace: String,
+}
+
+/// What to do with invalid UTF-8 in the columns with rules
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidUtf8 {
+    /// Stop dumping with an error
+    #[default]
+    Error,
+    /// Replace invalid sequences with `U+FFFD`
+    Lossy,
+}
+
+/// How the circular foreign keys between the dumped tables are handled (when the data is restored
+/// into a schema with the foreign keys)
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleStrategy {
+    /// The columns of the foreign keys to the tables that are dumped later are dumped as NULLs
+    /// and restored from a staging table after the data
+    TwoPass,
+    /// The data is restored in one transaction with the deferred constraints
+    Defer,
+    /// Stop dumping with an error
+    Fail,
+}
+
+/// What to do with the generated integers that don't fit the integer column types
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnOverflow {
+    /// Stop dumping with an error
+    #[default]
+    Error,
+    /// Replace the values with the minimum or maximum of the type
+    Clamp,
+}
+
+/// What to do with the data of the tables that belong to an extension
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionTables {
+    Include,
+    Skip,
+}
+
+/// What to do with the publications and subscriptions of the source database
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogicalReplication {
+    /// Remove them from the dump (except the publications in `publication_map`)
+    #[default]
+    Strip,
+    /// Keep them as is (the publications in `publication_map` are still rewritten)
+    Keep,
+}
+
+/// How the data of the partitioned tables (the declarative partitioning) is dumped
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Partitions {
+    /// One `COPY` of the partitioned table with the rows of all partitions (they are routed to
+    /// the partitions when the dump is restored), the partitions are skipped
+    #[default]
+    Parent,
+    /// Every partition is dumped as a table with the rules of its partitioned table (unless it has
+    /// its own config), the partitioned table is skipped
+    Children,
+}
+
+/// What to do with the materialized views (their definitions are always dumped without data)
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterializedViews {
+    /// Refresh the populated views at the end of the dump (in the dependency order),
+    /// so they are built from the anonymized data
+    #[default]
+    Refresh,
+    /// Leave them unpopulated (`REFRESH MATERIALIZED VIEW` is run after restoring)
+    Skip,
+}
+
+/// Where the dump is restored to (it sets the `pg_dump` flags and the dump post-processing)
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetProfile {
+    /// A personal development database: no owners, privileges, publications and subscriptions,
+    /// `DROP ... IF EXISTS` before every `CREATE TABLE` and `CREATE INDEX`
+    Dev,
+    /// A shared staging database: no publications and subscriptions, `DROP ... IF EXISTS`
+    /// before every `CREATE TABLE` and `CREATE INDEX` (owners and privileges are kept)
+    Staging,
+    /// Faithful `pg_dump` output
+    #[default]
+    Exact,
+}
+
+impl TargetProfile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Staging => "staging",
+            Self::Exact => "exact",
+        }
+    }
+
+    /// `pg_dump` flags of the profile
+    pub fn pg_dump_flags(&self) -> &'static [&'static str] {
+        match self {
+            Self::Dev => &[
+                "--no-owner",
+                "--no-privileges",
+                "--no-publications",
+                "--no-subscriptions",
+            ],
+            Self::Staging => &["--no-publications", "--no-subscriptions"],
+            Self::Exact => &[],
+        }
+    }
+
+    /// Adds `DROP ... IF EXISTS` before `CREATE TABLE` and `CREATE INDEX` statements
+    pub fn drops_if_exists(&self) -> bool {
+        *self != Self::Exact
+    }
+}
+
+impl FromStr for TargetProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dev" => Ok(Self::Dev),
+            "staging" => Ok(Self::Staging),
+            "exact" => Ok(Self::Exact),
+            _ => Err(format!(
+                "Unknown target profile `{}` (dev, staging or exact)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     /// Tables list with transformation rules
@@ -29,6 +249,12 @@ pub struct Settings {
     /// Table order. All tables not listed are dumping at the beginning
     pub table_order: Option<Vec<String>>,
 
+    /// Tables that are dumped before all other tables (in the specified order)
+    pub dump_first: Option<Vec<String>>,
+
+    /// Tables that are dumped after all other tables (in the specified order)
+    pub dump_last: Option<Vec<String>>,
+
     /// Default transformers configuration
     #[serde(default)]
     pub default: TransformerDefaults,
@@ -41,28 +267,240 @@ pub struct Settings {
 
     pub templates: Option<TemplatesCollection>,
 
+    /// What to do with invalid UTF-8 in the columns with rules
+    #[serde(default)]
+    pub invalid_utf8: InvalidUtf8,
+
+    /// What to do with the generated integers out of the ranges of `smallint`, `integer` and `bigint` columns
+    #[serde(default)]
+    pub on_overflow: OnOverflow,
+
+    /// Sequence value directives (the keys are sequence names or `table.column`, with schema)
+    #[serde(default)]
+    pub sequences: HashMap<String, SequenceAction>,
+
+    /// Requires every column of the tables in the config to have a rule or to be ignored
+    /// (tables can override it)
+    #[serde(default)]
+    pub explicit: bool,
+
+    /// String literal replacements in column defaults and check constraints
+    #[serde(default)]
+    pub ddl_replacements: Vec<DdlReplacement>,
+
+    /// Regexes of the column names that look like personal data (case-insensitive, `--dry-run` reports
+    /// such columns without rules), the built-in patterns are used if not specified
+    pub pii_columns: Option<Vec<String>>,
+
+    /// Regexes of the index and constraint names that may contain sensitive data (they are reported)
+    #[serde(default)]
+    pub sensitive_object_names: Vec<String>,
+
+    /// Renaming rules for the index and constraint names in the schema
+    #[serde(default)]
+    pub rename_objects: Vec<RenameObject>,
+
+    /// Overrides for the data of extension tables (the keys are extension names).
+    /// It is skipped by default.
+    #[serde(default)]
+    pub extensions: HashMap<String, ExtensionTables>,
+
+    /// Where the dump is restored to (`--target-profile` overrides it)
+    #[serde(default)]
+    pub target_profile: TargetProfile,
+
+    /// What to do with the publications and subscriptions
+    #[serde(default)]
+    pub logical_replication: LogicalReplication,
+
+    /// The publications that are kept in the dump (the keys are the source names, the values are the names
+    /// in the dump), their table lists are restricted to the dumped tables
+    #[serde(default)]
+    pub publication_map: BTreeMap<String, String>,
+
+    /// How the data of the partitioned tables is dumped
+    #[serde(default)]
+    pub partitions: Partitions,
+
+    /// What to do with the materialized views
+    #[serde(default)]
+    pub materialized_views: MaterializedViews,
+
+    /// The compression of the dump output (`--compress` overrides it)
+    pub compression: Option<Compression>,
+
+    /// Session settings of the source database connections (e.g., `work_mem`), `--set` overrides them
+    #[serde(default)]
+    pub session: BTreeMap<String, String>,
+
+    /// Rules for the source databases that look like production
+    pub safety: Option<Safety>,
+
+    /// A coherent subset of the data (the rows related to the root rows by foreign keys)
+    pub subset: Option<Subset>,
+
+    /// The count of the connections that dump the table data in parallel (`--jobs` overrides it)
+    pub jobs: Option<usize>,
+
+    /// Per-tenant dumps (only the rows of one tenant), `--tenant-id` sets the tenant
+    pub tenant: Option<Tenant>,
+
+    /// The row limit of the tables without their own `limit` (for smaller dev dumps)
+    pub default_limit: Option<u64>,
+
+    /// Detection of secrets in the columns that are dumped as is (the coverage report and `--scan-secrets`)
+    #[serde(default)]
+    pub secret_detection: SecretDetection,
+
+    /// The global salt of the `consistent` rules (`DATANYMIZER_CONSISTENT_SALT` overrides it)
+    pub consistent_salt: Option<String>,
+
+    /// The version of the seed derivation of the consistent values, the runs with the same version
+    /// and the same pepper derive the same values
+    #[serde(default = "default_derivation_version")]
+    pub derivation_version: u32,
+
+    /// The run seed: the random values of the rules are seeded by it (per table, column and row number),
+    /// so the runs over the same rows give the same dumps (`--seed` overrides it)
+    pub seed: Option<u64>,
+
+    /// The email domains of the values that are already anonymized (they are kept by all email
+    /// and name rules)
+    #[serde(default)]
+    pub skip_domains: Vec<String>,
+
+    /// Regexes of the values that are already anonymized (they are kept by all email and name rules)
+    #[serde(default)]
+    pub skip_patterns: Vec<String>,
+
+    /// How the circular foreign keys between the dumped tables are handled (they are ignored by default)
+    pub cycle_strategy: Option<CycleStrategy>,
+
+    /// Raw SQL statements that are written to the dump at the section boundaries
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// The tables that are dumped without data (the patterns of the table names, the tables can override it)
+    #[serde(default)]
+    pub skip_data: SkipData,
+
+    /// Built-in rule packs for the tables synced from SaaS products (e.g. `stripe_sync`),
+    /// the rules of the config tables override them
+    #[serde(default, deserialize_with = "presets::deserialize")]
+    pub preset: Vec<String>,
+
     #[serde(skip)]
     transform_map: Option<HashMap<String, TransformList>>,
+
+    /// The indexes of the config tables by their names (filled with the transform map)
+    #[serde(skip)]
+    table_indexes: Option<HashMap<String, usize>>,
+
+    /// The table names of the presets
+    #[serde(skip)]
+    preset_tables: Vec<(String, Vec<String>)>,
+
+    /// The run pepper of the salted rules
+    #[serde(skip)]
+    pepper: Pepper,
+
+    /// The config after the includes and the rule templates
+    #[serde(skip)]
+    expanded_config: JsonValue,
+}
+
+fn default_derivation_version() -> u32 {
+    DERIVATION_VERSION
 }
 
 impl Settings {
+    /// Without a pepper (the salts are used as is)
     pub fn new(path: String) -> Result<Self, ConfigError> {
-        Self::from_source(File::with_name(&path))
+        Self::with_pepper(path, Pepper::default())
+    }
+
+    /// The pepper is mixed into the salts of all salted rules
+    pub fn with_pepper(path: String, pepper: Pepper) -> Result<Self, ConfigError> {
+        // the rule order is taken from YAML files only (the config loader doesn't keep it)
+        let is_yaml = Path::new(&path)
+            .extension()
+            .is_some_and(|e| e == "yml" || e == "yaml");
+        let source = if is_yaml {
+            fs::read_to_string(&path).ok()
+        } else {
+            None
+        };
+        Self::from_source(
+            File::with_name(&path),
+            source.as_deref(),
+            Some(Path::new(&path)),
+            pepper,
+        )
     }
 
+    /// The paths in `include` are relative to the current directory
     pub fn from_yaml(config: &str) -> Result<Self, ConfigError> {
-        Self::from_source(File::from_str(config, FileFormat::Yaml))
+        Self::from_yaml_with_pepper(config, Pepper::default())
+    }
+
+    /// The pepper is mixed into the salts of all salted rules
+    pub fn from_yaml_with_pepper(config: &str, pepper: Pepper) -> Result<Self, ConfigError> {
+        Self::from_source(
+            File::from_str(config, FileFormat::Yaml),
+            Some(config),
+            None,
+            pepper,
+        )
     }
 
-    fn from_source<S: 'static>(source: S) -> Result<Self, ConfigError>
+    /// The config of an untrusted source (e.g. a service request): `include`, the `get_env` template
+    /// function, the secret references, `templates.files`, the `sql` rules and the query conditions
+    /// are errors, the environment variables are not replaced
+    pub fn from_untrusted_yaml(config: &str, pepper: Pepper) -> Result<Self, ConfigError> {
+        let mut s = Config::new();
+        s.merge(File::from_str(config, FileFormat::Yaml))?;
+        Self::from_loaded(includes::load_restricted(s, Some(config))?, pepper)
+    }
+
+    fn from_source<S>(
+        source: S,
+        yaml: Option<&str>,
+        path: Option<&Path>,
+        pepper: Pepper,
+    ) -> Result<Self, ConfigError>
     where
-        S: config::Source + Send + Sync,
+        S: 'static + config::Source + Send + Sync,
     {
         let mut s = Config::new();
         s.merge(source)?;
+        Self::from_loaded(includes::load(s, yaml, path)?, pepper)
+    }
 
-        let mut settings: Self = s.try_into()?;
-        settings.preprocess();
+    fn from_loaded(mut loaded: includes::Loaded, pepper: Pepper) -> Result<Self, ConfigError> {
+        let config = rule_templates::expand(loaded.config)?;
+        let expanded_config = config.clone().try_into()?;
+        let mut settings: Self = config.try_into()?;
+        settings.expanded_config = expanded_config;
+        for table in &mut settings.tables {
+            table.declared_rules = loaded
+                .declared_rules
+                .remove(&table.name)
+                .unwrap_or_default();
+        }
+        if loaded.restricted {
+            settings.reject_secrets()?;
+            settings.reject_sql_rules()?;
+        }
+        settings.override_consistent_salt(env::var(CONSISTENT_SALT_ENV).ok());
+        settings.pepper = match settings.seed {
+            // the random pepper is replaced with the pepper of the seed
+            Some(seed) if !pepper.value().is_empty() && !pepper.is_reproducible() => {
+                Pepper::from_seed(seed)
+            }
+            _ => pepper,
+        };
+        settings.resolve_secrets()?;
+        settings.preprocess()?;
 
         Ok(settings)
     }
@@ -75,10 +513,46 @@ pub fn transformers_for(&self, table: &str) -> Option<&TransformList> {
         }
     }
 
+    /// All rules (config table name, column, rule) in the order of the config tables
+    /// (the columns of a table are sorted)
+    pub fn resolved_rules(&self) -> Vec<(&str, &str, &Transformers)> {
+        let mut rules = vec![];
+        for table in &self.tables {
+            let mut list: Vec<_> = self
+                .transformers_for(&table.name)
+                .into_iter()
+                .flatten()
+                .collect();
+            list.sort_by(|a, b| a.0.cmp(&b.0));
+            rules.extend(
+                list.into_iter()
+                    .map(|(column, rule)| (table.name.as_str(), column.as_str(), rule)),
+            );
+        }
+        rules
+    }
+
+    /// The row limit of the table: its `limit` or `default_limit` (`None` if the rows aren't limited)
+    pub fn row_limit(&self, table: Option<&Table>) -> Option<u64> {
+        match table.and_then(|t| t.limit) {
+            Some(RowLimit::Rows(rows)) => Some(rows),
+            Some(RowLimit::Unlimited) => None,
+            None => self.default_limit,
+        }
+    }
+
     pub fn get_table(&self, name: &str) -> Option<&Table> {
         self.tables.iter().find(|t| t.name == name)
     }
 
+    /// The same as `get_table`, but without scanning the tables (for the per-row lookups)
+    pub fn table_config(&self, name: &str) -> Option<&Table> {
+        match &self.table_indexes {
+            Some(indexes) => indexes.get(name).and_then(|&i| self.tables.get(i)),
+            None => self.get_table(name),
+        }
+    }
+
     pub fn find_table<T: AsRef<str>>(&self, names: &[T]) -> Option<&Table> {
         for name in names {
             let table = self.get_table(name.as_ref());
@@ -89,106 +563,887 @@ pub fn find_table<T: AsRef<str>>(&self, names: &[T]) -> Option<&Table> {
         None
     }
 
-    fn preprocess(&mut self) {
+    /// The enabled presets with their table names (without schema)
+    pub fn preset_tables(&self) -> Vec<(&str, Vec<&str>)> {
+        self.preset_tables
+            .iter()
+            .map(|(preset, tables)| (preset.as_str(), tables.iter().map(|t| t.as_str()).collect()))
+            .collect()
+    }
+
+    /// Returns `true` if any table has quasi-identifiers for the k-anonymity check
+    pub fn has_quasi_identifiers(&self) -> bool {
+        self.tables.iter().any(|t| !t.quasi_identifiers.is_empty())
+    }
+
+    /// Returns the names of all config tables, whose rules are applied to the table
+    /// (the table with schema first, then the table without schema)
+    pub fn rule_sources<T: AsRef<str>>(&self, names: &[T]) -> Vec<&str> {
+        names
+            .iter()
+            .filter_map(|name| self.get_table(name.as_ref()))
+            .map(|t| t.name.as_str())
+            .collect()
+    }
+
+    pub fn pepper(&self) -> &Pepper {
+        &self.pepper
+    }
+
+    /// The resolved config as JSON (the keys are sorted): the config after the includes and the rule templates
+    /// with the options that are overridden by the environment and the command line. The dumps with the same
+    /// resolved config and the same pepper have the same values.
+    pub fn resolved_config(&self) -> Vec<u8> {
+        let mut config = self.expanded_config.clone();
+        if let Some(root) = config.as_object_mut() {
+            root.insert("consistent_salt".to_string(), json!(self.consistent_salt));
+            root.insert("seed".to_string(), json!(self.seed));
+            root.insert(
+                "target_profile".to_string(),
+                json!(format!("{:?}", self.target_profile)),
+            );
+            root.insert(
+                "tenant_id".to_string(),
+                json!(self.tenant.as_ref().and_then(|t| t.id.as_ref())),
+            );
+        }
+        config.to_string().into_bytes()
+    }
+
+    /// Sets the run seed (`--seed`) with the same checks as the `seed` of the config
+    pub fn set_seed(&mut self, seed: u64) -> Result<(), ConfigError> {
+        self.seed = Some(seed);
+        self.validate_seed()
+    }
+
+    /// The warning about the consistent rules with a random pepper: their values are consistent within the run only
+    pub fn pepper_warning(&self) -> Option<String> {
+        if self.pepper.value().is_empty() || self.pepper.is_reproducible() {
+            return None;
+        }
+        let rules: Vec<_> = self
+            .resolved_rules()
+            .into_iter()
+            .filter(|(_, _, rule)| rule.is_consistent())
+            .map(|(table, column, _)| format!("{}.{}", table, column))
+            .collect();
+        if rules.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "The consistent rules get a new random pepper in every run, so their values are not consistent \
+            across runs (persist the pepper with --pepper-file or use --seed for that): {}",
+            rules.join(", ")
+        ))
+    }
+
+    // The environment variable takes priority over the config (the salt is kept out of the config files)
+    fn override_consistent_salt(&mut self, salt: Option<String>) {
+        if salt.is_some() {
+            self.consistent_salt = salt;
+        }
+    }
+
+    // Secret references (e.g. `vault:secret/data/anonymizer#salt`) in global values
+    // and in the salt of the `consistent` rules
+    fn resolve_secrets(&mut self) -> Result<(), ConfigError> {
+        if let Some(globals) = &mut self.globals {
+            for value in globals.values_mut() {
+                secrets::resolve_json(value).map_err(|e| ConfigError::Message(e.to_string()))?;
+            }
+        }
+        if let Some(salt) = &mut self.consistent_salt {
+            if let Some(secret) =
+                secrets::resolve(salt).map_err(|e| ConfigError::Message(e.to_string()))?
+            {
+                *salt = secret;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The configs of the untrusted sources can't read the secrets (the salt from the environment is trusted)
+    fn reject_secrets(&self) -> Result<(), ConfigError> {
+        fn is_secret(value: &JsonValue) -> bool {
+            match value {
+                JsonValue::String(s) => secrets::SecretRef::parse(s).is_some(),
+                JsonValue::Array(items) => items.iter().any(is_secret),
+                JsonValue::Object(map) => map.values().any(is_secret),
+                _ => false,
+            }
+        }
+
+        let salt = self.consistent_salt.as_deref();
+        if self.globals.iter().flat_map(|g| g.values()).any(is_secret)
+            || salt.and_then(secrets::SecretRef::parse).is_some()
+        {
+            return Err(ConfigError::Message(
+                "Secret references aren't allowed in this config".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // The `sql` rules of the untrusted sources would run arbitrary SQL in the source database
+    fn reject_sql_rules(&self) -> Result<(), ConfigError> {
+        let is_sql = |r: &Transformers| r.sql_expression().is_some() || r.has_nested_sql();
+        if self.tables.iter().any(|t| t.rules.values().any(is_sql)) {
+            return Err(ConfigError::Message(
+                "The `sql` rules aren't allowed in this config".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn preprocess(&mut self) -> Result<(), ConfigError> {
+        self.apply_presets()?;
+        self.apply_global_skips();
         let mut init_ctx = TransformerInitContext::from_defaults(self.default.clone());
 
         // Assign extend templates to context
         if let Some(collection) = &self.templates {
             init_ctx.template_collection = collection.clone();
         }
+        if let Some(salt) = &self.consistent_salt {
+            init_ctx.consistent_salt = salt.clone();
+        }
+        init_ctx.pepper = self.pepper.value().to_string();
+        self.validate_derivation_version()?;
+        init_ctx.derivation_version = self.derivation_version;
 
         for table in self.tables.iter_mut() {
             for (_name, rule) in table.rules.iter_mut() {
                 rule.init(&init_ctx);
             }
+            if let Some(outbox) = &mut table.outbox {
+                outbox.init(&init_ctx);
+            }
         }
 
+        self.validate_table_order()?;
+        self.validate_cache_rules()?;
+        self.validate_dp_noise_rules()?;
+        self.validate_seed()?;
+        self.validate_bytea_placeholder_rules()?;
+        self.validate_json_rules()?;
+        self.validate_interval_jitter_rules()?;
+        self.validate_skip_if_rules()?;
+        self.validate_template_rules()?;
+        self.validate_sql_rules()?;
+        self.validate_ignore()?;
+        self.validate_safety()?;
+        self.validate_subset()?;
+        self.validate_jobs()?;
+        self.validate_tenant()?;
+        self.secret_detection
+            .validate()
+            .map_err(ConfigError::Message)?;
+        self.validate_object_names()?;
+        self.pii_column_patterns().map_err(ConfigError::Message)?;
+        self.validate_publication_map()?;
+        self.merge_bare_tables()?;
         self.fill_transform_map();
+
+        Ok(())
     }
 
-    fn fill_transform_map(&mut self) {
-        let mut map = HashMap::with_capacity(self.tables.len());
-        for table in &self.tables {
-            map.insert(table.name.clone(), table.transform_list());
+    fn apply_presets(&mut self) -> Result<(), ConfigError> {
+        for name in &self.preset {
+            if self.preset_tables.iter().any(|(preset, _)| preset == name) {
+                continue;
+            }
+            let tables = presets::tables(name).map_err(ConfigError::Message)?;
+            let names = tables.iter().map(|t| t.name.clone()).collect();
+            presets::apply(&mut self.tables, name, tables);
+            self.preset_tables.push((name.clone(), names));
         }
 
-        self.transform_map = Some(map);
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{transformers::PersonNameTransformer, LocaleConfig};
+    // `skip_domains` and `skip_patterns` are added to the `skip_if` rules of the email and name
+    // columns (the rules without them are wrapped)
+    fn apply_global_skips(&mut self) {
+        if self.skip_domains.is_empty() && self.skip_patterns.is_empty() {
+            return;
+        }
 
-    #[test]
-    fn set_defaults() {
-        let config = r#"
-            tables:
-              - name: user
-                rules:
-                  name:
-                    person_name: {}
-                  alias:
-                    person_name:
-                      locale: EN
-            default:
-              locale: RU
-            "#;
+        for table in self.tables.iter_mut() {
+            for (_name, rule) in table.rules.iter_mut() {
+                if !matches!(rule.category(), RuleCategory::Email | RuleCategory::Name) {
+                    continue;
+                }
+                if !matches!(rule, Transformers::SkipIf(_)) {
+                    let inner = std::mem::replace(rule, Transformers::None(NoneTransformer));
+                    *rule = Transformers::SkipIf(SkipIfTransformer::new(inner));
+                }
+                if let Transformers::SkipIf(t) = rule {
+                    t.domains.extend(self.skip_domains.iter().cloned());
+                    t.patterns.extend(self.skip_patterns.iter().cloned());
+                }
+            }
+        }
+    }
 
-        let s = Settings::from_yaml(config).unwrap();
-        let rules = &s.tables.first().unwrap().rules;
+    // A table can be listed only once in `dump_first`, `table_order` and `dump_last`
+    fn validate_table_order(&self) -> Result<(), ConfigError> {
+        let lists = [
+            ("dump_first", &self.dump_first),
+            ("table_order", &self.table_order),
+            ("dump_last", &self.dump_last),
+        ];
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for (list_name, list) in lists {
+            for table in list.iter().flatten() {
+                if let Some(other) = seen.insert(table, list_name) {
+                    return Err(ConfigError::Message(if other == list_name {
+                        format!("Table `{}` is listed twice in `{}`", table, list_name)
+                    } else {
+                        format!(
+                            "Table `{}` is listed in both `{}` and `{}`",
+                            table, other, list_name
+                        )
+                    }));
+                }
+            }
+        }
 
-        assert_eq!(
-            rules["name"],
-            Transformers::PersonName(PersonNameTransformer {
-                locale: Some(LocaleConfig::RU)
-            })
-        );
-        assert_eq!(
-            rules["alias"],
-            Transformers::PersonName(PersonNameTransformer {
-                locale: Some(LocaleConfig::EN)
-            })
-        );
+        Ok(())
     }
 
-    #[test]
-    fn find_table() {
-        let config = r#"
-            tables:
-              - name: companies
-                rules:
-                  name:
-                    company_name: {}
-              - name: users
-                rules:
-                  name:
-                    person_name: {}
-              - name: other_schema.users
-                rules:
-                  other_name:
-                    person_name: {}
-            "#;
-        let s = Settings::from_yaml(config).unwrap();
+    // Cached and consistent rules must not generate unique values
+    fn validate_cache_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                // the kept values are not cached
+                let rule = match rule {
+                    Transformers::SkipIf(skip) => &*skip.rule,
+                    rule => rule,
+                };
+                if let Transformers::Cache(cache) = rule {
+                    if cache.rule.is_uniq() {
+                        return Err(ConfigError::Message(format!(
+                            "The `cache` rule can't be used with unique values (table `{}`, column `{}`)",
+                            table.name, column
+                        )));
+                    }
+                    // the cached noise would be the same for the same values
+                    if !cache.rule.dp_noise_rules().is_empty() {
+                        return Err(ConfigError::Message(format!(
+                            "The `cache` rule can't be used with `dp_noise`, the noise must be fresh for every value (table `{}`, column `{}`)",
+                            table.name, column
+                        )));
+                    }
+                }
+                // the same for the consistent rules (the values are the same for the same original values)
+                if let Transformers::Consistent(consistent) = rule {
+                    if consistent.rule.is_uniq() {
+                        return Err(ConfigError::Message(format!(
+                            "The `consistent` rule can't be used with unique values (table `{}`, column `{}`)",
+                            table.name, column
+                        )));
+                    }
+                    if !consistent.rule.dp_noise_rules().is_empty() {
+                        return Err(ConfigError::Message(format!(
+                            "The `consistent` rule can't be used with `dp_noise`, the noise must be fresh for every value (table `{}`, column `{}`)",
+                            table.name, column
+                        )));
+                    }
+                }
+            }
+        }
 
-        let t = s.find_table(&["some_table"]);
-        assert!(t.is_none());
+        Ok(())
+    }
 
-        let t = s.find_table(&["some_table", "users"]);
-        assert_eq!(t.unwrap().name, "users");
+    fn validate_dp_noise_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.dp_noise_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `dp_noise` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
 
-        let t = s.find_table(&["users", "other_schema.users"]);
-        assert_eq!(t.unwrap().name, "users");
+        Ok(())
+    }
 
-        let t = s.find_table(&["other_schema.users", "users"]);
-        assert_eq!(t.unwrap().name, "other_schema.users");
+    // The seeded noise is the same in every run, so it can be reproduced with the seed and removed
+    fn validate_seed(&self) -> Result<(), ConfigError> {
+        if self.seed.is_none() {
+            return Ok(());
+        }
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                if !rule.dp_noise_rules().is_empty() {
+                    return Err(ConfigError::Message(format!(
+                        "The `dp_noise` rule can't be used with `seed` (--seed), the noise must be fresh in every run \
+                        (table `{}`, column `{}`)",
+                        table.name, column
+                    )));
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    mod transformers_for {
-        use super::*;
+    fn validate_bytea_placeholder_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.bytea_placeholder_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `bytea_placeholder` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
 
-        fn rule_names(s: &Settings, t: &str) -> Vec<String> {
+        Ok(())
+    }
+
+    fn validate_json_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.json_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `json` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_interval_jitter_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.interval_jitter_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `interval_jitter` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_skip_if_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.skip_if_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `skip_if` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_template_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                for t in rule.template_rules() {
+                    t.validate().map_err(|e| {
+                        ConfigError::Message(format!(
+                            "Invalid `template` rule (table `{}`, column `{}`): {}",
+                            table.name, column, e
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The `sql` rules replace the columns in the dump queries, so they can't be nested in other rules
+    fn validate_sql_rules(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            for (column, rule) in &table.rules {
+                let error = if rule.has_nested_sql() {
+                    Some("the `sql` rule can't be nested in other rules".to_string())
+                } else if let Transformers::Sql(t) = rule {
+                    t.validate().err()
+                } else {
+                    None
+                };
+                if let Some(e) = error {
+                    return Err(ConfigError::Message(format!(
+                        "Invalid `sql` rule (table `{}`, column `{}`): {}",
+                        table.name, column, e
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The lint warnings: the rules that generate values of another category than the column names suggest
+    pub fn lint_warnings(&self) -> Vec<String> {
+        self.tables.iter().flat_map(lint::mismatches).collect()
+    }
+
+    /// The sum of the `epsilon` values of the `dp_noise` rules for every table that has them
+    pub fn privacy_budget(&self) -> Vec<(&Table, f64)> {
+        self.tables
+            .iter()
+            .map(|t| (t, t.rules.values().map(|r| r.privacy_budget()).sum()))
+            .filter(|(_, epsilon)| *epsilon > 0.0)
+            .collect()
+    }
+
+    fn validate_safety(&self) -> Result<(), ConfigError> {
+        match &self.safety {
+            Some(safety) => safety.validate().map_err(ConfigError::Message),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_subset(&self) -> Result<(), ConfigError> {
+        match &self.subset {
+            Some(subset) => subset.validate().map_err(ConfigError::Message),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_jobs(&self) -> Result<(), ConfigError> {
+        match self.jobs {
+            Some(0) => Err(ConfigError::Message(
+                "`jobs` must be at least 1".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_tenant(&self) -> Result<(), ConfigError> {
+        match &self.tenant {
+            Some(_) if self.subset.is_some() => Err(ConfigError::Message(
+                "The `tenant` and `subset` config sections can't be used together".to_string(),
+            )),
+            Some(tenant) => tenant.validate().map_err(ConfigError::Message),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_object_names(&self) -> Result<(), ConfigError> {
+        let patterns = self
+            .sensitive_object_names
+            .iter()
+            .map(|p| ("sensitive_object_names", p))
+            .chain(
+                self.rename_objects
+                    .iter()
+                    .map(|r| ("rename_objects", &r.pattern)),
+            );
+        for (section, pattern) in patterns {
+            Regex::new(pattern).map_err(|e| {
+                ConfigError::Message(format!(
+                    "Invalid regex `{}` in `{}`: {}",
+                    pattern, section, e
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // The publications get different non-empty names in the dump
+    fn validate_derivation_version(&self) -> Result<(), ConfigError> {
+        if DERIVATION_VERSIONS.contains(&self.derivation_version) {
+            Ok(())
+        } else {
+            Err(ConfigError::Message(format!(
+                "Unsupported `derivation_version` {} (the versions {} to {} are supported)",
+                self.derivation_version,
+                DERIVATION_VERSIONS.start(),
+                DERIVATION_VERSIONS.end()
+            )))
+        }
+    }
+
+    fn validate_publication_map(&self) -> Result<(), ConfigError> {
+        let mut names = HashMap::new();
+        for (source, target) in &self.publication_map {
+            if target.is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "Empty name of the publication `{}` in `publication_map`",
+                    source
+                )));
+            }
+            if let Some(other) = names.insert(target, source) {
+                return Err(ConfigError::Message(format!(
+                    "The publications `{}` and `{}` get the same name `{}` in `publication_map`",
+                    other, source, target
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_ignore(&self) -> Result<(), ConfigError> {
+        for table in &self.tables {
+            table.validate_ignore().map_err(ConfigError::Message)?;
+            table
+                .validate_only_columns()
+                .map_err(ConfigError::Message)?;
+            for order in &table.timestamp_order {
+                order.validate(&table.name).map_err(ConfigError::Message)?;
+            }
+            if let Some(shuffle_rows) = &table.shuffle_rows {
+                shuffle_rows
+                    .validate(&table.name)
+                    .map_err(ConfigError::Message)?;
+            }
+            if let Some(outbox) = &table.outbox {
+                outbox.validate(&table.name).map_err(ConfigError::Message)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if every column of the table must have a rule or be ignored
+    pub fn is_explicit(&self, table: &Table) -> bool {
+        table.explicit.unwrap_or(self.explicit)
+    }
+
+    /// The regexes of `pii_columns` (or the built-in ones)
+    pub fn pii_column_patterns(&self) -> Result<Vec<Regex>, String> {
+        let patterns: Vec<&str> = match &self.pii_columns {
+            Some(patterns) => patterns.iter().map(|p| p.as_str()).collect(),
+            None => DEFAULT_PII_COLUMNS.to_vec(),
+        };
+        patterns
+            .into_iter()
+            .map(|pattern| {
+                Regex::new(&format!("(?i){}", pattern))
+                    .map_err(|e| format!("Invalid regex `{}` in `pii_columns`: {}", pattern, e))
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the data of the extension tables must be dumped
+    /// (`include_all` is used for the extensions without overrides)
+    pub fn includes_extension_tables(&self, extension: &str, include_all: bool) -> bool {
+        match self.extensions.get(extension) {
+            Some(t) => *t == ExtensionTables::Include,
+            None => include_all,
+        }
+    }
+
+    // Rules of tables without schema (e.g. `users`) are applied to the tables with schema (e.g. `public.users`) too
+    fn merge_bare_tables(&mut self) -> Result<(), ConfigError> {
+        let bare_tables: HashMap<_, _> = self
+            .tables
+            .iter()
+            .filter(|t| !t.is_qualified())
+            .map(|t| (t.name.clone(), t.clone()))
+            .collect();
+
+        for table in self.tables.iter_mut().filter(|t| t.is_qualified()) {
+            if let Some(bare) = bare_tables.get(table.bare_name()) {
+                table.merge_bare(bare).map_err(ConfigError::Message)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_transform_map(&mut self) {
+        let mut map = HashMap::with_capacity(self.tables.len());
+        let mut indexes = HashMap::with_capacity(self.tables.len());
+        for (i, table) in self.tables.iter().enumerate() {
+            map.insert(table.name.clone(), table.transform_list());
+            indexes.entry(table.name.clone()).or_insert(i);
+        }
+
+        self.transform_map = Some(map);
+        self.table_indexes = Some(indexes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transformers::PersonNameTransformer, LocaleConfig};
+
+    #[test]
+    fn set_defaults() {
+        let config = r#"
+            tables:
+              - name: user
+                rules:
+                  name:
+                    person_name: {}
+                  alias:
+                    person_name:
+                      locale: EN
+            default:
+              locale: RU
+            "#;
+
+        let s = Settings::from_yaml(config).unwrap();
+        let rules = &s.tables.first().unwrap().rules;
+
+        assert_eq!(
+            rules["name"],
+            Transformers::PersonName(PersonNameTransformer {
+                locale: Some(LocaleConfig::RU),
+                preserve_script: false,
+            })
+        );
+        assert_eq!(
+            rules["alias"],
+            Transformers::PersonName(PersonNameTransformer {
+                locale: Some(LocaleConfig::EN),
+                preserve_script: false,
+            })
+        );
+    }
+
+    #[test]
+    fn find_table() {
+        let config = r#"
+            tables:
+              - name: companies
+                rules:
+                  name:
+                    company_name: {}
+              - name: users
+                rules:
+                  name:
+                    person_name: {}
+              - name: other_schema.users
+                rules:
+                  other_name:
+                    person_name: {}
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+
+        let t = s.find_table(&["some_table"]);
+        assert!(t.is_none());
+
+        let t = s.find_table(&["some_table", "users"]);
+        assert_eq!(t.unwrap().name, "users");
+
+        let t = s.find_table(&["users", "other_schema.users"]);
+        assert_eq!(t.unwrap().name, "users");
+
+        let t = s.find_table(&["other_schema.users", "users"]);
+        assert_eq!(t.unwrap().name, "other_schema.users");
+    }
+
+    #[test]
+    fn table_config() {
+        let config = r#"
+            tables:
+              - name: companies
+                rules:
+                  name:
+                    company_name: {}
+              - name: users
+                rules:
+                  name:
+                    person_name: {}
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(s.table_config("users").unwrap().name, "users");
+        assert_eq!(s.table_config("companies").unwrap().name, "companies");
+        assert!(s.table_config("other_schema.users").is_none());
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.invalid_utf8, InvalidUtf8::Error);
+
+        let s = Settings::from_yaml("{tables: [], invalid_utf8: lossy}").unwrap();
+        assert_eq!(s.invalid_utf8, InvalidUtf8::Lossy);
+    }
+
+    #[test]
+    fn on_overflow() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.on_overflow, OnOverflow::Error);
+
+        let s = Settings::from_yaml("{tables: [], on_overflow: clamp}").unwrap();
+        assert_eq!(s.on_overflow, OnOverflow::Clamp);
+    }
+
+    #[test]
+    fn ddl_replacements() {
+        let config = r#"
+            tables: []
+            ddl_replacements:
+              - from: Ops@RealCompany.com
+                to: ops@example.com
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(
+            s.ddl_replacements,
+            vec![DdlReplacement {
+                from: "Ops@RealCompany.com".to_string(),
+                to: "ops@example.com".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn hooks() {
+        let config = r#"
+            tables: []
+            hooks:
+              post_data:
+                - TRUNCATE jobs;
+                - |
+                  DO $$
+                  BEGIN
+                    UPDATE feature_flags SET enabled = false;
+                  END
+                  $$;
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert!(s.hooks.pre_data.is_empty());
+        assert_eq!(s.hooks.post_data.len(), 2);
+        assert_eq!(s.hooks.post_data[0], "TRUNCATE jobs;");
+        assert!(s.hooks.post_data[1].starts_with("DO $$\nBEGIN\n"));
+
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.hooks, Hooks::default());
+    }
+
+    #[test]
+    fn cycle_strategy() {
+        let s = Settings::from_yaml("{tables: [], cycle_strategy: two_pass}").unwrap();
+        assert_eq!(s.cycle_strategy, Some(CycleStrategy::TwoPass));
+        let s = Settings::from_yaml("{tables: [], cycle_strategy: defer}").unwrap();
+        assert_eq!(s.cycle_strategy, Some(CycleStrategy::Defer));
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.cycle_strategy, None);
+        assert!(Settings::from_yaml("{tables: [], cycle_strategy: ignore}").is_err());
+    }
+
+    #[test]
+    fn object_names() {
+        let config = r#"
+            tables: []
+            sensitive_object_names: ["acmecorp"]
+            rename_objects:
+              - pattern: "_acmecorp_"
+                replace: "_customer_"
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(s.sensitive_object_names, vec!["acmecorp"]);
+        assert_eq!(
+            s.rename_objects,
+            vec![RenameObject {
+                pattern: "_acmecorp_".to_string(),
+                replace: "_customer_".to_string()
+            }]
+        );
+
+        let config = r#"
+            tables: []
+            rename_objects:
+              - pattern: "fk_("
+                replace: "fk_"
+            "#;
+        assert!(Settings::from_yaml(config)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid regex `fk_(` in `rename_objects`"));
+    }
+
+    mod qualified_tables {
+        use super::*;
+
+        #[test]
+        fn merge_rules() {
+            let config = r#"
+                tables:
+                  - name: users
+                    rules:
+                      name:
+                        first_name: {}
+                      email:
+                        email: {}
+                  - name: tenant_a.users
+                    rules:
+                      name:
+                        first_name: {}
+                      phone:
+                        phone: {}
+                "#;
+            let s = Settings::from_yaml(config).unwrap();
+
+            let t = s.find_table(&["tenant_a.users", "users"]).unwrap();
+            let mut columns: Vec<_> = t.rules.keys().collect();
+            columns.sort();
+            assert_eq!(columns, vec!["email", "name", "phone"]);
+
+            let t = s.find_table(&["tenant_b.users", "users"]).unwrap();
+            assert_eq!(t.rules.len(), 2);
+
+            assert_eq!(
+                s.rule_sources(&["tenant_a.users", "users"]),
+                vec!["tenant_a.users", "users"]
+            );
+            assert_eq!(s.rule_sources(&["tenant_b.users", "users"]), vec!["users"]);
+        }
+
+        #[test]
+        fn conflict() {
+            let config = r#"
+                tables:
+                  - name: users
+                    rules:
+                      name:
+                        first_name: {}
+                  - name: tenant_a.users
+                    rules:
+                      name:
+                        last_name: {}
+                "#;
+            assert!(Settings::from_yaml(config).is_err());
+
+            let config = format!("{}    override: true\n", config);
+            let s = Settings::from_yaml(&config).unwrap();
+            let t = s.get_table("tenant_a.users").unwrap();
+            assert!(matches!(t.rules["name"], Transformers::LastName(_)));
+        }
+    }
+
+    mod transformers_for {
+        use super::*;
+
+        fn rule_names(s: &Settings, t: &str) -> Vec<String> {
             s.transformers_for(t)
                 .unwrap()
                 .iter()
@@ -249,8 +1504,8 @@ fn get_raw_templates(s: &Settings) -> Vec<String> {
                 .unwrap()
                 .raw
                 .unwrap()
-                .iter()
-                .map(|(name, _)| name.to_string())
+                .keys()
+                .map(|name| name.to_string())
                 .collect()
         }
 
@@ -278,4 +1533,813 @@ fn read_templates() {
             assert_eq!(get_files_templates(&s).len(), 2);
         }
     }
+
+    #[test]
+    fn validate_table_order() {
+        let config = r#"
+            tables: []
+            dump_first: [countries, currencies]
+            table_order: [users]
+            dump_last: [events]
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(
+            s.dump_first,
+            Some(vec!["countries".to_string(), "currencies".to_string()])
+        );
+        assert_eq!(s.dump_last, Some(vec!["events".to_string()]));
+
+        let config = "{tables: [], dump_first: [countries], dump_last: [events, countries]}";
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Table `countries` is listed in both `dump_first` and `dump_last`"
+        );
+
+        let config = "{tables: [], table_order: [users, users]}";
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Table `users` is listed twice in `table_order`"
+        );
+    }
+
+    #[test]
+    fn validate_cache_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  country:
+                    cache:
+                      rule:
+                        country_name: {}
+            "#;
+        assert!(Settings::from_yaml(config).is_ok());
+
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    cache:
+                      rule:
+                        email:
+                          uniq: true
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The `cache` rule can't be used with unique values (table `users`, column `email`)"
+        );
+
+        let config = r#"
+            tables:
+              - name: salaries
+                rules:
+                  amount:
+                    cache:
+                      rule:
+                        dp_noise:
+                          epsilon: 1
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The `cache` rule can't be used with `dp_noise`, the noise must be fresh for every value \
+            (table `salaries`, column `amount`)"
+        );
+    }
+
+    #[test]
+    fn validate_consistent_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    consistent:
+                      rule:
+                        email:
+                          uniq: true
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The `consistent` rule can't be used with unique values (table `users`, column `email`)"
+        );
+
+        let config = r#"
+            tables:
+              - name: salaries
+                rules:
+                  amount:
+                    consistent:
+                      rule:
+                        dp_noise:
+                          epsilon: 1
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The `consistent` rule can't be used with `dp_noise`, the noise must be fresh for every value \
+            (table `salaries`, column `amount`)"
+        );
+    }
+
+    #[test]
+    fn consistent_salt() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    consistent:
+                      rule:
+                        email: {}
+            consistent_salt: from config
+            "#;
+        let mut s = Settings::from_yaml(config).unwrap();
+        assert_eq!(s.consistent_salt.as_deref(), Some("from config"));
+
+        s.override_consistent_salt(None);
+        assert_eq!(s.consistent_salt.as_deref(), Some("from config"));
+        s.override_consistent_salt(Some("from env".to_string()));
+        assert_eq!(s.consistent_salt.as_deref(), Some("from env"));
+
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.consistent_salt, None);
+    }
+
+    #[test]
+    fn untrusted() {
+        let from = |config: &str| Settings::from_untrusted_yaml(config, Pepper::random());
+
+        env::set_var("DATANYMIZER_UNTRUSTED_TEST", "leaked");
+        let s = from("tables: []\nglobals:\n  note: ${DATANYMIZER_UNTRUSTED_TEST}").unwrap();
+        assert_eq!(
+            s.globals.unwrap()["note"],
+            JsonValue::from("${DATANYMIZER_UNTRUSTED_TEST}")
+        );
+
+        let err = |config: &str| from(config).unwrap_err().to_string();
+        assert_eq!(
+            err("include: [other.yml]\ntables: []"),
+            "`include` isn't allowed in this config"
+        );
+        assert_eq!(
+            err(r#"
+            tables:
+              - name: users
+                rules:
+                  note:
+                    template:
+                      format: "{{ get_env(name='HOME') }}"
+            "#),
+            "`get_env` isn't allowed in this config"
+        );
+        assert_eq!(
+            err("tables: []\nglobals:\n  key: vault:secret/data/app#key"),
+            "Secret references aren't allowed in this config"
+        );
+        assert_eq!(
+            err("tables: []\nconsistent_salt: aws-sm:anonymizer/salt"),
+            "Secret references aren't allowed in this config"
+        );
+        assert_eq!(
+            err("tables: []\ntemplates:\n  files: [/etc/passwd]"),
+            "`templates.files` isn't allowed in this config"
+        );
+        assert_eq!(
+            err(r#"
+            tables:
+              - name: users
+                query:
+                  transform_condition: "pg_sleep(10) IS NOT NULL"
+            "#),
+            "`query.transform_condition` isn't allowed in this config"
+        );
+        assert_eq!(
+            err(r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    pipeline:
+                      pipes:
+                        - sql: "(SELECT current_setting('data_directory'))"
+            "#),
+            "The `sql` rules aren't allowed in this config"
+        );
+        assert!(from(
+            r#"
+            tables:
+              - name: users
+                query:
+                  limit: 10
+                rules:
+                  email:
+                    email: {}
+            templates:
+              raw:
+                greeting: "Hi"
+            "#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn pepper() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    consistent:
+                      rule:
+                        email: {}
+                  notes:
+                    redact:
+                      salt: secret
+                      hash_suffix_len: 16
+            consistent_salt: salt
+            "#;
+        let settings = |pepper: Pepper| {
+            Settings::from_source(
+                File::from_str(config, FileFormat::Yaml),
+                Some(config),
+                None,
+                pepper,
+            )
+            .unwrap()
+        };
+        let values = |s: &Settings| {
+            let mut rules = s.transformers_for("users").unwrap().clone();
+            rules.sort_by(|a, b| a.0.cmp(&b.0));
+            rules
+                .iter()
+                .map(|(column, rule)| {
+                    rule.transform(column, "john@mail.com", &None)
+                        .unwrap()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let plain = settings(Pepper::default());
+        assert_eq!(plain.pepper().fingerprint(), None);
+        assert_eq!(plain.pepper_warning(), None);
+
+        let random = Pepper::random();
+        let first = settings(random.clone());
+        // the same pepper gives the same values, another pepper gives other values
+        assert_eq!(values(&first), values(&settings(random)));
+        let other = values(&settings(Pepper::random()));
+        assert_ne!(values(&first)[0], other[0]);
+        assert_ne!(values(&first)[1], other[1]);
+        assert_ne!(values(&first)[1], values(&plain)[1]);
+
+        assert_eq!(
+            first.pepper_warning().unwrap(),
+            "The consistent rules get a new random pepper in every run, so their values are not consistent \
+            across runs (persist the pepper with --pepper-file or use --seed for that): users.email"
+        );
+    }
+
+    #[test]
+    fn seed() {
+        let config = |seed: &str| {
+            format!(
+                "tables: [{{name: users, rules: {{email: {{consistent: {{rule: {{email: {{}}}}}}}}}}}}]\n{}",
+                seed
+            )
+        };
+        let settings = |config: String, pepper: Pepper| {
+            Settings::from_source(
+                File::from_str(&config, FileFormat::Yaml),
+                Some(&config),
+                None,
+                pepper,
+            )
+            .unwrap()
+        };
+
+        // the random pepper is replaced with the pepper of the seed
+        let seeded = settings(config("seed: 42"), Pepper::random());
+        assert_eq!(seeded.seed, Some(42));
+        assert_eq!(seeded.pepper(), &Pepper::from_seed(42));
+        assert_eq!(seeded.pepper_warning(), None);
+        // the reproducible peppers are kept
+        let pepper = Pepper::from_seed(7);
+        assert_eq!(
+            settings(config("seed: 42"), pepper.clone()).pepper(),
+            &pepper
+        );
+        assert_eq!(
+            settings(config("seed: 42"), Pepper::default()).pepper(),
+            &Pepper::default()
+        );
+
+        let unseeded = settings(config(""), Pepper::random());
+        assert_eq!(unseeded.seed, None);
+        assert!(unseeded.pepper_warning().is_some());
+    }
+
+    #[test]
+    fn validate_sql_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    sql: "md5(email) || '@example.com'"
+            "#;
+        assert!(Settings::from_yaml(config).is_ok());
+
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    pipeline:
+                      pipes:
+                        - sql: md5(email)
+                        - capitalize: ~
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `sql` rule (table `users`, column `email`): the `sql` rule can't be nested in other rules"
+        );
+
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    sql: " "
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `sql` rule (table `users`, column `email`): the expression is empty"
+        );
+    }
+
+    #[test]
+    fn validate_bytea_placeholder_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  avatar:
+                    bytea_placeholder:
+                      content: jpeg
+                      size: 512x512
+                      max_bytes: 10000
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `bytea_placeholder` rule (table `users`, column `avatar`): \
+            the placeholders can take up to 46259 bytes, it exceeds `max_bytes` (10000)"
+        );
+    }
+
+    #[test]
+    fn validate_json_rules() {
+        let config = r#"
+            tables:
+              - name: profiles
+                rules:
+                  data:
+                    json:
+                      fields:
+                        - path: email
+                          rule:
+                            email: {}
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `json` rule (table `profiles`, column `data`): \
+            invalid path `email` (a JSON pointer is expected, e.g. `/address/street`)"
+        );
+    }
+
+    #[test]
+    fn validate_interval_jitter_rules() {
+        let config = r#"
+            tables:
+              - name: subscriptions
+                rules:
+                  duration:
+                    interval_jitter:
+                      percent: 10
+                  grace_period:
+                    pipeline:
+                      pipes:
+                        - interval_jitter:
+                            max: 2 fortnights
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `interval_jitter` rule (table `subscriptions`, column `grace_period`): \
+            invalid `max`: unknown unit `fortnight`"
+        );
+    }
+
+    #[test]
+    fn validate_skip_if_rules() {
+        let config = r#"
+            tables:
+              - name: users
+                rules:
+                  email:
+                    skip_if:
+                      patterns: ["(demo"]
+                      rule:
+                        email: {}
+            "#;
+        assert!(Settings::from_yaml(config)
+            .unwrap_err()
+            .to_string()
+            .starts_with(
+                "Invalid `skip_if` rule (table `users`, column `email`): invalid pattern `(demo`: "
+            ));
+
+        let config = r#"
+            skip_patterns: ["(demo"]
+            tables:
+              - name: users
+                rules:
+                  name:
+                    person_name: {}
+            "#;
+        assert!(Settings::from_yaml(config)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid `skip_if` rule (table `users`, column `name`): "));
+    }
+
+    #[test]
+    fn validate_template_rules() {
+        let error = |config: &str| Settings::from_yaml(config).unwrap_err().to_string();
+        let rule = r#"
+            tables:
+              - name: users
+                rules:
+                  note:
+                    cache:
+                      rule:
+                        template:
+                          format: "{{ _0 "
+            "#;
+        assert!(error(rule).starts_with("Invalid `template` rule (table `users`, column `note`): "));
+
+        let files = r#"
+            tables:
+              - name: users
+                rules:
+                  note:
+                    template:
+                      format: "{{ _0 }}"
+            templates:
+              files: [/nonexistent/datanymizer/template.html]
+            "#;
+        let e = error(files);
+        assert!(e.starts_with("Invalid `template` rule (table `users`, column `note`): "));
+        assert!(
+            e.contains("/nonexistent/datanymizer/template.html"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn global_skips() {
+        let config = r#"
+            skip_domains: ["example.com"]
+            skip_patterns: ["^demo"]
+            tables:
+              - name: users
+                rules:
+                  email:
+                    email: {}
+                  name:
+                    skip_if:
+                      prefixes: ["Test "]
+                      rule:
+                        person_name: {}
+                  login:
+                    username: {}
+                  contact:
+                    cache:
+                      rule:
+                        free_email_provider: {}
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        let rules = &s.get_table("users").unwrap().rules;
+        let skip_if = |column: &str| match &rules[column] {
+            Transformers::SkipIf(t) => {
+                Some((t.domains.clone(), t.patterns.clone(), t.prefixes.len()))
+            }
+            _ => None,
+        };
+
+        let global = (
+            vec!["example.com".to_string()],
+            vec!["^demo".to_string()],
+            0,
+        );
+        assert_eq!(skip_if("email"), Some(global.clone()));
+        assert_eq!(skip_if("contact"), Some(global));
+        assert_eq!(
+            skip_if("name"),
+            Some((
+                vec!["example.com".to_string()],
+                vec!["^demo".to_string()],
+                1
+            ))
+        );
+        // not an email or name rule
+        assert_eq!(skip_if("login"), None);
+
+        let t = &rules["email"];
+        assert_eq!(
+            t.transform("users.email", "demo1@corp.com", &None),
+            Ok(None)
+        );
+        assert_eq!(t.transform("users.email", "a@example.com", &None), Ok(None));
+        assert_ne!(t.transform("users.email", "a@corp.com", &None), Ok(None));
+    }
+
+    #[test]
+    fn dp_noise_rules() {
+        let config = r#"
+            tables:
+              - name: salaries
+                rules:
+                  amount:
+                    dp_noise:
+                      epsilon: 0.5
+                      sensitivity: 1000
+                  bonus:
+                    pipeline:
+                      pipes:
+                        - dp_noise:
+                            epsilon: 0.25
+              - name: users
+                rules:
+                  email:
+                    email: {}
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        let budget: Vec<_> = s
+            .privacy_budget()
+            .into_iter()
+            .map(|(t, epsilon)| (t.name.as_str(), epsilon))
+            .collect();
+        assert_eq!(budget, vec![("salaries", 0.75)]);
+
+        let config = r#"
+            tables:
+              - name: salaries
+                rules:
+                  amount:
+                    dp_noise:
+                      epsilon: -1
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid `dp_noise` rule (table `salaries`, column `amount`): `epsilon` must be positive (it is -1)"
+        );
+    }
+
+    #[test]
+    fn dp_noise_with_seed() {
+        let config = r#"
+            tables:
+              - name: salaries
+                rules:
+                  amount:
+                    pipeline:
+                      pipes:
+                        - dp_noise:
+                            epsilon: 0.5
+            "#;
+        let error = "The `dp_noise` rule can't be used with `seed` (--seed), the noise must be fresh in every run \
+            (table `salaries`, column `amount`)";
+        assert_eq!(
+            Settings::from_yaml(&format!("{}seed: 42", config))
+                .unwrap_err()
+                .to_string(),
+            error
+        );
+
+        let mut s = Settings::from_yaml(config).unwrap();
+        assert_eq!(s.set_seed(42).unwrap_err().to_string(), error);
+        assert!(Settings::from_yaml("tables: []")
+            .unwrap()
+            .set_seed(42)
+            .is_ok());
+    }
+
+    #[test]
+    fn resolved_config() {
+        let resolved = |config: &str| Settings::from_yaml(config).unwrap().resolved_config();
+        let config = r#"
+            rule_templates:
+              masked:
+                email:
+                  redact: {}
+            tables:
+              - name: users
+                use:
+                  masked: {}
+            default_limit: 10
+            "#;
+
+        // the order of the keys and the rule templates don't matter
+        assert_eq!(
+            resolved(config),
+            resolved(
+                r#"
+                default_limit: 10
+                tables:
+                  - name: users
+                    rules:
+                      email:
+                        redact: {}
+                "#
+            )
+        );
+        assert_ne!(resolved(config), resolved(&format!("{}seed: 1", config)));
+
+        let mut s = Settings::from_yaml(config).unwrap();
+        s.set_seed(1).unwrap();
+        assert_eq!(s.resolved_config(), resolved(&format!("{}seed: 1", config)));
+    }
+
+    #[test]
+    fn includes_extension_tables() {
+        let config = r#"
+            tables: []
+            extensions:
+              postgis: include
+              timescaledb: skip
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+
+        assert!(s.includes_extension_tables("postgis", false));
+        assert!(!s.includes_extension_tables("timescaledb", true));
+        assert!(!s.includes_extension_tables("pg_stat_statements", false));
+        assert!(s.includes_extension_tables("pg_stat_statements", true));
+    }
+
+    #[test]
+    fn target_profile() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.target_profile, TargetProfile::Exact);
+        assert!(s.target_profile.pg_dump_flags().is_empty());
+        assert!(!s.target_profile.drops_if_exists());
+
+        let s = Settings::from_yaml("tables: []\ntarget_profile: dev").unwrap();
+        assert_eq!(s.target_profile, TargetProfile::Dev);
+        assert!(s.target_profile.pg_dump_flags().contains(&"--no-owner"));
+        assert!(s.target_profile.drops_if_exists());
+
+        assert_eq!("staging".parse(), Ok(TargetProfile::Staging));
+        assert!("prod".parse::<TargetProfile>().is_err());
+    }
+
+    #[test]
+    fn jobs() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.jobs, None);
+
+        let s = Settings::from_yaml("tables: []\njobs: 4").unwrap();
+        assert_eq!(s.jobs, Some(4));
+
+        let error = Settings::from_yaml("tables: []\njobs: 0").err().unwrap();
+        assert_eq!(error.to_string(), "`jobs` must be at least 1");
+    }
+
+    #[test]
+    fn partitions() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.partitions, Partitions::Parent);
+        let s = Settings::from_yaml("tables: []\npartitions: children").unwrap();
+        assert_eq!(s.partitions, Partitions::Children);
+        assert!(Settings::from_yaml("tables: []\npartitions: all").is_err());
+    }
+
+    #[test]
+    fn derivation_version() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.derivation_version, DERIVATION_VERSION);
+        let s = Settings::from_yaml("tables: []\nderivation_version: 1").unwrap();
+        assert_eq!(s.derivation_version, 1);
+
+        let e = Settings::from_yaml("tables: []\nderivation_version: 3").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Unsupported `derivation_version` 3 (the versions 1 to 2 are supported)"
+        );
+        assert!(Settings::from_yaml("tables: []\nderivation_version: 0").is_err());
+    }
+
+    #[test]
+    fn materialized_views() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.materialized_views, MaterializedViews::Refresh);
+
+        let s = Settings::from_yaml("tables: []\nmaterialized_views: skip").unwrap();
+        assert_eq!(s.materialized_views, MaterializedViews::Skip);
+        assert!(Settings::from_yaml("tables: []\nmaterialized_views: copy").is_err());
+    }
+
+    #[test]
+    fn logical_replication() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.logical_replication, LogicalReplication::Strip);
+        assert!(s.publication_map.is_empty());
+
+        let config = r#"
+            tables: []
+            logical_replication: keep
+            publication_map:
+              app_changes: app_changes_dev
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(s.logical_replication, LogicalReplication::Keep);
+        assert_eq!(
+            s.publication_map,
+            BTreeMap::from([("app_changes".to_string(), "app_changes_dev".to_string())])
+        );
+
+        let config = "tables: []\npublication_map: {a: b, c: b}";
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The publications `a` and `c` get the same name `b` in `publication_map`"
+        );
+        assert!(Settings::from_yaml("tables: []\npublication_map: {a: ''}").is_err());
+    }
+
+    #[test]
+    fn compression() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.compression, None);
+
+        let s = Settings::from_yaml("tables: []\ncompression: zstd:19").unwrap();
+        assert_eq!(
+            s.compression,
+            Some(Compression::new(CompressionMethod::Zstd, Some(19)).unwrap())
+        );
+
+        assert!(Settings::from_yaml("tables: []\ncompression: lz4").is_err());
+    }
+
+    #[test]
+    fn pii_columns() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        let patterns = s.pii_column_patterns().unwrap();
+        let is_pii = |column: &str| patterns.iter().any(|p| p.is_match(column));
+        for column in [
+            "email",
+            "Contact_EMail",
+            "first_name",
+            "LastName",
+            "phone_number",
+            "dob",
+        ] {
+            assert!(is_pii(column), "{}", column);
+        }
+        for column in ["id", "status", "created_at", "company"] {
+            assert!(!is_pii(column), "{}", column);
+        }
+
+        let s = Settings::from_yaml("tables: []\npii_columns: [\"^nick\"]").unwrap();
+        let patterns = s.pii_column_patterns().unwrap();
+        assert!(patterns[0].is_match("Nickname"));
+        assert_eq!(patterns.len(), 1);
+
+        let error = Settings::from_yaml("tables: []\npii_columns: [\"(nick\"]")
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid regex `(nick` in `pii_columns`"));
+    }
+
+    #[test]
+    fn session() {
+        let config = r#"
+            tables: []
+            session:
+              work_mem: 256MB
+              max_parallel_workers_per_gather: 0
+        "#;
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(
+            s.session,
+            BTreeMap::from([
+                (
+                    "max_parallel_workers_per_gather".to_string(),
+                    "0".to_string()
+                ),
+                ("work_mem".to_string(), "256MB".to_string()),
+            ])
+        );
+    }
 }
@@ -0,0 +1,386 @@
+use crate::{
+    transformer::{Globals, TransformError, TransformerInitContext},
+    transformers::transform_values,
+    TransformContext, Transformer, Transformers,
+};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+/// What to do with the rows of the event types without rules
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownEvents {
+    /// The payload is replaced with NULL
+    #[default]
+    NullPayload,
+    /// The payload is dumped as is (with a warning)
+    Passthrough,
+    /// The row is not dumped
+    Drop,
+}
+
+/// Anonymization of the event payloads of an audit or outbox table: the JSON payload of every row
+/// is transformed by the rules of its event type.
+///
+/// The keys of the rules are dot paths in the payload, `*` matches any key or array item.
+/// The rules are applied to the strings, numbers and booleans at the paths (all of them for objects and arrays).
+///
+/// ```yaml
+/// tables:
+///   - name: outbox
+///     outbox:
+///       type_column: event_type
+///       payload_column: payload
+///       event_rules:
+///         user_registered:
+///           email:
+///             email: {}
+///           addresses.*.phone:
+///             phone: {}
+///         order_paid: {}
+///       unknown_events: drop
+/// ```
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Outbox {
+    /// The column of the event types (`event_type` by default)
+    #[serde(default = "default_type_column")]
+    pub type_column: String,
+    /// The column of the JSON payloads (`payload` by default)
+    #[serde(default = "default_payload_column")]
+    pub payload_column: String,
+    /// The rules of the payload paths by the event types (the payloads of the event types
+    /// with empty rules are dumped as is)
+    #[serde(default)]
+    pub event_rules: BTreeMap<String, BTreeMap<String, Transformers>>,
+    #[serde(default)]
+    pub unknown_events: UnknownEvents,
+}
+
+/// The result of the payload transformation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutboxPayload {
+    /// The payload is not changed
+    Keep,
+    /// The transformed payload
+    Transformed(String),
+    /// The event type is unknown, the payload is replaced with NULL
+    Null,
+    /// The event type is unknown, the payload is dumped as is
+    Passthrough,
+    /// The event type is unknown, the row is not dumped
+    Drop,
+}
+
+fn default_type_column() -> String {
+    String::from("event_type")
+}
+
+fn default_payload_column() -> String {
+    String::from("payload")
+}
+
+impl Outbox {
+    pub(super) fn init(&mut self, ctx: &TransformerInitContext) {
+        for rules in self.event_rules.values_mut() {
+            for rule in rules.values_mut() {
+                rule.init(ctx);
+            }
+        }
+    }
+
+    pub(super) fn validate(&self, table: &str) -> Result<(), String> {
+        if self.type_column == self.payload_column {
+            return Err(format!(
+                "The type column and the payload column of the outbox table `{}` are the same (`{}`)",
+                table, self.type_column
+            ));
+        }
+        for (event_type, rules) in &self.event_rules {
+            if let Some(path) = rules
+                .keys()
+                .find(|path| path.split('.').any(|key| key.is_empty()))
+            {
+                return Err(format!(
+                    "Invalid payload path `{}` in the rules of the event type `{}` of the outbox table `{}`",
+                    path, event_type, table
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Transforms the JSON payload of the event type (NULL values are `None`, the NULL type is unknown).
+    /// `table` is used in the rule names.
+    pub fn transform(
+        &self,
+        table: &str,
+        event_type: Option<&str>,
+        payload: Option<&str>,
+        globals: &Option<Globals>,
+    ) -> Result<OutboxPayload, TransformError> {
+        let rules = match event_type.and_then(|t| self.event_rules.get(t)) {
+            Some(rules) => rules,
+            None => {
+                return Ok(match self.unknown_events {
+                    UnknownEvents::NullPayload => OutboxPayload::Null,
+                    UnknownEvents::Passthrough => OutboxPayload::Passthrough,
+                    UnknownEvents::Drop => OutboxPayload::Drop,
+                })
+            }
+        };
+        let payload = match payload {
+            Some(payload) if !rules.is_empty() => payload,
+            _ => return Ok(OutboxPayload::Keep),
+        };
+
+        let field_name = format!("{}.{}", table, self.payload_column);
+        let mut json: JsonValue = serde_json::from_str(payload).map_err(|e| TransformError {
+            field_name: field_name.clone(),
+            field_value: payload.to_string(),
+            reason: format!(
+                "Invalid JSON payload of the event type `{}`: {}",
+                event_type.unwrap_or_default(),
+                e
+            ),
+        })?;
+
+        let ctx = Some(TransformContext::new(globals, None, None, None));
+        let mut changed = false;
+        for (path, rule) in rules {
+            let rule_name = format!(
+                "{}[{}].{}",
+                field_name,
+                event_type.unwrap_or_default(),
+                path
+            );
+            let keys: Vec<_> = path.split('.').collect();
+            changed |= transform_path(&mut json, &keys, &mut |value| {
+                rule.transform(&rule_name, value, &ctx)
+            })?;
+        }
+
+        Ok(if changed {
+            OutboxPayload::Transformed(json.to_string())
+        } else {
+            OutboxPayload::Keep
+        })
+    }
+}
+
+type Transform<'a> = dyn FnMut(&str) -> Result<Option<String>, TransformError> + 'a;
+
+// Returns `true` if some values are changed
+fn transform_path(
+    value: &mut JsonValue,
+    keys: &[&str],
+    transform: &mut Transform,
+) -> Result<bool, TransformError> {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => return transform_values(value, transform),
+    };
+
+    let mut changed = false;
+    match value {
+        JsonValue::Object(map) if *key == "*" => {
+            for item in map.values_mut() {
+                changed |= transform_path(item, rest, transform)?;
+            }
+        }
+        JsonValue::Object(map) => {
+            if let Some(item) = map.get_mut(*key) {
+                changed = transform_path(item, rest, transform)?;
+            }
+        }
+        JsonValue::Array(items) if *key == "*" => {
+            for item in items {
+                changed |= transform_path(item, rest, transform)?;
+            }
+        }
+        JsonValue::Array(items) => {
+            if let Some(item) = key.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                changed = transform_path(item, rest, transform)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use serde_json::json;
+
+    fn outbox(config: &str) -> Outbox {
+        let settings = Settings::from_yaml(config).unwrap();
+        settings.tables[0].outbox.clone().unwrap()
+    }
+
+    fn transformed(result: OutboxPayload) -> JsonValue {
+        match result {
+            OutboxPayload::Transformed(payload) => serde_json::from_str(&payload).unwrap(),
+            other => panic!("not transformed: {:?}", other),
+        }
+    }
+
+    const CONFIG: &str = r#"
+        tables:
+          - name: outbox
+            outbox:
+              event_rules:
+                user_registered:
+                  email:
+                    template:
+                      format: user@example.com
+                  addresses.*.zip:
+                    template:
+                      format: "00000"
+                  age:
+                    template:
+                      format: "42"
+                  profile:
+                    template:
+                      format: "***"
+                order_paid: {}
+            "#;
+
+    #[test]
+    fn paths() {
+        let outbox = outbox(CONFIG);
+        assert_eq!(outbox.type_column, "event_type");
+        assert_eq!(outbox.payload_column, "payload");
+
+        let payload = json!({
+            "email": "john@mail.com",
+            "age": 31,
+            "addresses": [{"zip": 12345, "city": "Paris"}, {"zip": "E1 6AN"}],
+            "profile": {"name": "John", "phones": ["555-01", null], "active": true},
+            "amount": 10
+        });
+        let result = outbox
+            .transform(
+                "outbox",
+                Some("user_registered"),
+                Some(&payload.to_string()),
+                &None,
+            )
+            .unwrap();
+        assert_eq!(
+            transformed(result),
+            json!({
+                "email": "user@example.com",
+                "age": 42,
+                // the number doesn't fit, so it is a string
+                "addresses": [{"zip": "00000", "city": "Paris"}, {"zip": "00000"}],
+                "profile": {"name": "***", "phones": ["***", null], "active": "***"},
+                "amount": 10
+            })
+        );
+
+        // no values at the paths
+        assert_eq!(
+            outbox
+                .transform(
+                    "outbox",
+                    Some("user_registered"),
+                    Some(r#"{"id": 1}"#),
+                    &None
+                )
+                .unwrap(),
+            OutboxPayload::Keep
+        );
+        assert_eq!(
+            outbox
+                .transform("outbox", Some("user_registered"), None, &None)
+                .unwrap(),
+            OutboxPayload::Keep
+        );
+        // without rules
+        assert_eq!(
+            outbox
+                .transform(
+                    "outbox",
+                    Some("order_paid"),
+                    Some(r#"{"email": "a@b.c"}"#),
+                    &None
+                )
+                .unwrap(),
+            OutboxPayload::Keep
+        );
+
+        let error = outbox
+            .transform("outbox", Some("user_registered"), Some("{email"), &None)
+            .unwrap_err();
+        assert_eq!(error.field_name, "outbox.payload");
+        assert!(error
+            .reason
+            .starts_with("Invalid JSON payload of the event type `user_registered`"));
+    }
+
+    #[test]
+    fn unknown_events() {
+        assert_eq!(
+            outbox(CONFIG)
+                .transform("outbox", Some("user_deleted"), Some("{}"), &None)
+                .unwrap(),
+            OutboxPayload::Null
+        );
+        assert_eq!(
+            outbox(CONFIG)
+                .transform("outbox", None, Some("{}"), &None)
+                .unwrap(),
+            OutboxPayload::Null
+        );
+        for (policy, expected) in [
+            ("null_payload", OutboxPayload::Null),
+            ("passthrough", OutboxPayload::Passthrough),
+            ("drop", OutboxPayload::Drop),
+        ] {
+            let config = format!(
+                "tables:\n  - name: outbox\n    outbox:\n      unknown_events: {}",
+                policy
+            );
+            assert_eq!(
+                outbox(&config)
+                    .transform("outbox", Some("user_registered"), Some("{}"), &None)
+                    .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let config = r#"
+            tables:
+              - name: outbox
+                outbox:
+                  type_column: kind
+                  payload_column: kind
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The type column and the payload column of the outbox table `outbox` are the same (`kind`)"
+        );
+
+        let config = r#"
+            tables:
+              - name: outbox
+                outbox:
+                  event_rules:
+                    created:
+                      user..email:
+                        email: {}
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid payload path `user..email` in the rules of the event type `created` of the outbox table `outbox`"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use super::{table, Table};
+use serde::{Deserialize, Deserializer};
+
+/// The columns that ETL tools add to the synced tables
+const SYNC_COLUMNS: [&str; 8] = [
+    "_fivetran_id",
+    "_fivetran_synced",
+    "_fivetran_deleted",
+    "_sdc_extracted_at",
+    "_sdc_batched_at",
+    "_sdc_received_at",
+    "_airbyte_extracted_at",
+    "_airbyte_raw_id",
+];
+
+/// Built-in rule packs for the tables that ETL tools sync from SaaS products (`preset: stripe_sync`).
+/// They cover the PII columns of the tables and ignore the metadata columns.
+const PRESETS: [(&str, &str); 3] = [
+    ("stripe_sync", include_str!("presets/stripe_sync.yml")),
+    (
+        "salesforce_sync",
+        include_str!("presets/salesforce_sync.yml"),
+    ),
+    ("zendesk_sync", include_str!("presets/zendesk_sync.yml")),
+];
+
+/// Returns the tables of the preset (with the rules, not initialized)
+pub fn tables(name: &str) -> Result<Vec<Table>, String> {
+    let (_, source) = PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = PRESETS.iter().map(|(preset, _)| *preset).collect();
+            format!(
+                "Unknown preset `{}` (available presets: {})",
+                name,
+                names.join(", ")
+            )
+        })?;
+    let invalid = |e: serde_yaml::Error| format!("Invalid preset `{}`: {}", name, e);
+    let mut declared = table::declared_rules(&serde_yaml::from_str(source).map_err(invalid)?);
+    let mut tables: Vec<Table> = serde_yaml::from_str(source).map_err(invalid)?;
+    for table in &mut tables {
+        table.declared_rules = declared.remove(&table.name).unwrap_or_default();
+        for column in SYNC_COLUMNS {
+            table
+                .ignore
+                .insert(column.to_string(), "sync metadata".to_string());
+        }
+    }
+    Ok(tables)
+}
+
+/// Adds the tables of the preset to the config tables: the tables that are in the config (with or without schema)
+/// get the preset rules for the columns that have no rules and are not ignored, other tables are added.
+pub fn apply(tables: &mut Vec<Table>, name: &str, preset: Vec<Table>) {
+    for mut preset_table in preset {
+        let mut merged = false;
+        for table in tables
+            .iter_mut()
+            .filter(|t| t.bare_name() == preset_table.name)
+        {
+            let added = table.add_rules(&preset_table);
+            table.preset_rules.extend(added);
+            merged = true;
+        }
+        if !merged {
+            preset_table.preset = Some(name.to_string());
+            preset_table.preset_rules = preset_table.rules.keys().cloned().collect();
+            tables.push(preset_table);
+        }
+    }
+}
+
+/// `preset: stripe_sync` or `preset: [stripe_sync, zendesk_sync]`
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Names {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Names::deserialize(deserializer)? {
+        Names::One(name) => vec![name],
+        Names::Many(names) => names,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn presets() {
+        for (name, _) in PRESETS {
+            let config = format!("tables: []\npreset: {}", name);
+            let settings = Settings::from_yaml(&config).unwrap();
+            assert!(!settings.tables.is_empty(), "{}", name);
+            // the preset rules match the column names
+            assert_eq!(settings.lint_warnings(), Vec::<String>::new(), "{}", name);
+            for table in &settings.tables {
+                assert!(table.rules.len() > 1, "{}", table.name);
+                assert_eq!(table.ignore["_fivetran_synced"], "sync metadata");
+            }
+        }
+
+        assert_eq!(
+            tables("hubspot_sync").unwrap_err(),
+            "Unknown preset `hubspot_sync` (available presets: stripe_sync, salesforce_sync, zendesk_sync)"
+        );
+    }
+
+    #[test]
+    fn user_rules() {
+        let config = r#"
+            preset: [stripe_sync, zendesk_sync]
+            tables:
+              - name: billing.stripe_customers
+                rules:
+                  email:
+                    email: {}
+                  metadata:
+                    redact: {}
+                ignore:
+                  phone: "a company phone"
+              - name: zendesk_tickets
+                rules:
+                  subject:
+                    none: ~
+                  status:
+                    redact: {}
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+
+        let customers = s.get_table("billing.stripe_customers").unwrap();
+        // the user rules override the preset rules
+        assert_eq!(customers.rules["email"].name(), "email");
+        assert_eq!(customers.rules["metadata"].name(), "redact");
+        assert!(!customers.rules.contains_key("phone"));
+        assert_eq!(customers.ignore["phone"], "a company phone");
+        assert_eq!(customers.rules["name"].name(), "person_name");
+        assert_eq!(customers.ignore["id"], "Stripe object ID");
+        // merged into the table with schema
+        assert!(s.get_table("stripe_customers").is_none());
+        assert_eq!(customers.preset, None);
+        assert!(customers.preset_rules.contains("name"));
+        assert!(!customers.preset_rules.contains("email"));
+
+        let tickets = s.get_table("zendesk_tickets").unwrap();
+        assert_eq!(tickets.rules["subject"].name(), "none");
+        assert_eq!(tickets.rules["requester_email"].name(), "consistent");
+        // a user rule for an ignored column
+        assert_eq!(tickets.rules["status"].name(), "redact");
+        assert!(!tickets.ignore.contains_key("status"));
+
+        assert_eq!(
+            s.get_table("stripe_charges").unwrap().preset.as_deref(),
+            Some("stripe_sync")
+        );
+        assert!(s.get_table("zendesk_users").is_some());
+        assert!(s.get_table("sf_contacts").is_none());
+        assert_eq!(
+            s.preset_tables(),
+            vec![
+                (
+                    "stripe_sync",
+                    vec![
+                        "stripe_customers",
+                        "stripe_charges",
+                        "stripe_payment_methods"
+                    ]
+                ),
+                (
+                    "zendesk_sync",
+                    vec![
+                        "zendesk_users",
+                        "zendesk_tickets",
+                        "zendesk_ticket_comments"
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_preset() {
+        let config = "tables: []\npreset: stripe";
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Unknown preset `stripe` (available presets: stripe_sync, salesforce_sync, zendesk_sync)"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{de, Deserialize, Deserializer};
+
+/// The row limit of a table: the count of the rows or `none` (the global `default_limit` isn't applied).
+///
+/// ```yaml
+/// default_limit: 10000
+/// tables:
+///   - name: countries
+///     limit: none
+///   - name: events
+///     limit: 500
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowLimit {
+    Rows(u64),
+    Unlimited,
+}
+
+impl<'de> Deserialize<'de> for RowLimit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Config {
+            Rows(u64),
+            Keyword(String),
+        }
+
+        match Config::deserialize(deserializer)? {
+            Config::Rows(rows) => Ok(Self::Rows(rows)),
+            Config::Keyword(s) if s == "none" => Ok(Self::Unlimited),
+            Config::Keyword(s) => s.parse().map(Self::Rows).map_err(|_| {
+                de::Error::custom(format!("Invalid limit `{}` (a count of rows or `none`)", s))
+            }),
+        }
+    }
+}
+
+/// Sampling of a table with `TABLESAMPLE SYSTEM`: a percentage of the table pages
+/// (e.g. `sample: 1%`), so the count of the dumped rows is approximate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    percent: f64,
+}
+
+// the percentage is never NaN (it is checked on parsing)
+impl Eq for Sample {}
+
+impl Sample {
+    pub fn new(percent: f64) -> Result<Self, String> {
+        if percent > 0.0 && percent <= 100.0 {
+            Ok(Self { percent })
+        } else {
+            Err(format!(
+                "Invalid sample `{}%` (a percentage greater than 0 and not greater than 100)",
+                percent
+            ))
+        }
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.percent
+    }
+
+    /// The expected count of the sampled rows
+    pub fn rows(&self, total: u64) -> u64 {
+        (total as f64 * self.percent / 100.0).ceil() as u64
+    }
+}
+
+impl<'de> Deserialize<'de> for Sample {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Config {
+            Percent(f64),
+            Text(String),
+        }
+
+        let percent = match Config::deserialize(deserializer)? {
+            Config::Percent(percent) => percent,
+            Config::Text(s) => s.trim().trim_end_matches('%').trim().parse().map_err(|_| {
+                de::Error::custom(format!("Invalid sample `{}` (e.g. `1%` or `0.5%`)", s))
+            })?,
+        };
+        Self::new(percent).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    fn settings(tables: &str) -> Result<Settings, String> {
+        Settings::from_yaml(&format!("default_limit: 100\ntables: {}", tables))
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parse() {
+        let settings = settings(
+            "[{name: users, limit: 10}, {name: countries, limit: none}, \
+            {name: events, sample: 1%}, {name: logs, sample: 0.5}]",
+        )
+        .unwrap();
+        let table = |name| settings.find_table(&[name]);
+
+        assert_eq!(table("users").unwrap().limit, Some(RowLimit::Rows(10)));
+        assert_eq!(table("countries").unwrap().limit, Some(RowLimit::Unlimited));
+        assert_eq!(table("events").unwrap().sample.unwrap().percent(), 1.0);
+        assert_eq!(table("logs").unwrap().sample.unwrap().percent(), 0.5);
+
+        assert_eq!(settings.row_limit(table("users")), Some(10));
+        assert_eq!(settings.row_limit(table("countries")), None);
+        assert_eq!(settings.row_limit(table("events")), Some(100));
+        assert_eq!(settings.row_limit(None), Some(100));
+    }
+
+    #[test]
+    fn invalid() {
+        for (tables, error) in [
+            ("[{name: users, limit: all}]", "Invalid limit `all`"),
+            ("[{name: users, sample: lots}]", "Invalid sample `lots`"),
+            ("[{name: users, sample: 0%}]", "Invalid sample `0%`"),
+            ("[{name: users, sample: 150%}]", "Invalid sample `150%`"),
+        ] {
+            let e = settings(tables).unwrap_err();
+            assert!(e.contains(error), "{}", e);
+        }
+    }
+
+    #[test]
+    fn sampled_rows() {
+        let sample = Sample::new(1.0).unwrap();
+        assert_eq!(sample.rows(1000), 10);
+        assert_eq!(sample.rows(150), 2);
+        assert_eq!(sample.rows(0), 0);
+        assert_eq!(Sample::new(100.0).unwrap().rows(7), 7);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::Transformers;
+use config::{Config, ConfigError, File, FileFormat};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::BTreeMap;
+
+const TEMPLATES_KEY: &str = "rule_templates";
+const TABLES_KEY: &str = "tables";
+const USE_KEY: &str = "use";
+const RULES_KEY: &str = "rules";
+const IGNORE_KEY: &str = "ignore";
+const NAME_KEY: &str = "name";
+
+/// The rules of a template: the column, the rule and the template chain it comes from (e.g. `pii_contact > pii_email`)
+type TemplateRules = BTreeMap<String, (JsonValue, String)>;
+
+/// Expands the named rule templates (the `rule_templates` section) referenced by the tables with `use`.
+///
+/// ```yaml
+/// rule_templates:
+///   pii_email:
+///     email:
+///       email: { kind: Safe, uniq: true }
+///   pii_contact:
+///     use: [pii_email]
+///     phone:
+///       phone: {}
+///
+/// tables:
+///   - name: users
+///     use:
+///       pii_contact: { domain: test.dev }
+/// ```
+///
+/// The parameters (`{ domain: test.dev }`) are deep-merged into the configs of all rules of the template.
+/// The rules of the table override the rules of the templates, the own rules of a template override the rules of
+/// the templates it uses. It runs before the settings are deserialized, so the expanded rules are validated
+/// as usual (the errors of the expanded rules mention the templates).
+pub fn expand(config: Config) -> Result<Config, ConfigError> {
+    let mut root: JsonValue = config.clone().try_into()?;
+    let templates = match root.as_object_mut().and_then(|r| r.remove(TEMPLATES_KEY)) {
+        Some(JsonValue::Object(templates)) => templates,
+        Some(JsonValue::Null) | None if !uses_templates(&root) => return Ok(config),
+        Some(JsonValue::Null) | None => Map::new(),
+        Some(_) => {
+            return Err(ConfigError::Message(format!(
+                "`{}` must be a map of template names to rules",
+                TEMPLATES_KEY
+            )))
+        }
+    };
+
+    let expander = Expander { templates };
+    if let Some(tables) = root.get_mut(TABLES_KEY).and_then(|t| t.as_array_mut()) {
+        for table in tables.iter_mut().filter_map(|t| t.as_object_mut()) {
+            expander.expand_table(table).map_err(ConfigError::Message)?;
+        }
+    }
+
+    let mut expanded = Config::new();
+    expanded.merge(File::from_str(&root.to_string(), FileFormat::Json))?;
+    Ok(expanded)
+}
+
+fn uses_templates(root: &JsonValue) -> bool {
+    root.get(TABLES_KEY)
+        .and_then(|t| t.as_array())
+        .is_some_and(|tables| tables.iter().any(|t| t.get(USE_KEY).is_some()))
+}
+
+struct Expander {
+    templates: Map<String, JsonValue>,
+}
+
+impl Expander {
+    fn expand_table(&self, table: &mut Map<String, JsonValue>) -> Result<(), String> {
+        let uses = match table.remove(USE_KEY) {
+            Some(uses) => uses,
+            None => return Ok(()),
+        };
+        let table_name = table
+            .get(NAME_KEY)
+            .and_then(|n| n.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let context = format!("the table `{}`", table_name);
+
+        let template_rules = self.resolve_uses(&uses, &context, &mut vec![])?;
+        let ignored: Vec<String> = table
+            .get(IGNORE_KEY)
+            .and_then(|i| i.as_object())
+            .map(|i| i.keys().cloned().collect())
+            .unwrap_or_default();
+        let rules = table
+            .entry(RULES_KEY)
+            .or_insert_with(|| JsonValue::Object(Map::new()));
+        let rules = match rules {
+            JsonValue::Null => {
+                *rules = JsonValue::Object(Map::new());
+                rules.as_object_mut().unwrap()
+            }
+            JsonValue::Object(rules) => rules,
+            _ => return Err(format!("The rules of {} must be a map", context)),
+        };
+
+        for (column, (rule, origin)) in template_rules {
+            if rules.contains_key(&column) || ignored.contains(&column) {
+                continue;
+            }
+            check_rule(&rule).map_err(|e| {
+                format!(
+                    "Invalid rule for the column `{}` of the table `{}` (from the rule template `{}`): {}",
+                    column, table_name, origin, e
+                )
+            })?;
+            rules.insert(column, rule);
+        }
+
+        Ok(())
+    }
+
+    // `use: [a, b]` or `use: {a: {param: value}, b: ~}`
+    fn resolve_uses(
+        &self,
+        uses: &JsonValue,
+        context: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<TemplateRules, String> {
+        let uses: Vec<(&str, Option<&JsonValue>)> = match uses {
+            JsonValue::Array(names) => names
+                .iter()
+                .map(|n| n.as_str().map(|n| (n, None)))
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("`use` of {} must be a list of template names", context))?,
+            JsonValue::Object(uses) => uses
+                .iter()
+                .map(|(n, params)| (n.as_str(), Some(params).filter(|p| !p.is_null())))
+                .collect(),
+            JsonValue::String(name) => vec![(name.as_str(), None)],
+            _ => {
+                return Err(format!(
+                    "`use` of {} must be a list of template names or a map of template parameters",
+                    context
+                ))
+            }
+        };
+
+        let mut rules = TemplateRules::new();
+        for (name, params) in uses {
+            let mut template_rules = self.resolve(name, context, stack)?;
+            if let Some(params) = params {
+                for (column, (rule, _)) in template_rules.iter_mut() {
+                    *rule = with_params(rule, params).map_err(|e| {
+                        format!(
+                            "Can't apply the parameters of the rule template `{}` to the column `{}`: {}",
+                            name, column, e
+                        )
+                    })?;
+                }
+            }
+
+            for (column, (rule, origin)) in template_rules {
+                match rules.get(&column) {
+                    Some((other_rule, other_origin)) if *other_rule != rule => {
+                        return Err(format!(
+                            "The rule templates `{}` and `{}` used by {} have different rules for the column `{}`",
+                            other_origin, origin, context, column
+                        ));
+                    }
+                    _ => {
+                        rules.insert(column, (rule, origin));
+                    }
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    fn resolve(
+        &self,
+        name: &str,
+        context: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<TemplateRules, String> {
+        let template = match self.templates.get(name) {
+            Some(JsonValue::Object(template)) => template,
+            Some(_) => {
+                return Err(format!(
+                    "The rule template `{}` must be a map of columns to rules",
+                    name
+                ))
+            }
+            None => return Err(format!("Unknown rule template `{}` in {}", name, context)),
+        };
+        if stack.iter().any(|n| n == name) {
+            stack.push(name.to_string());
+            return Err(format!(
+                "The rule template `{}` uses itself ({})",
+                name,
+                stack.join(" > ")
+            ));
+        }
+
+        stack.push(name.to_string());
+        let mut rules = match template.get(USE_KEY) {
+            Some(uses) => {
+                self.resolve_uses(uses, &format!("the rule template `{}`", name), stack)?
+            }
+            None => TemplateRules::new(),
+        };
+        stack.pop();
+
+        for (_, origin) in rules.values_mut() {
+            *origin = format!("{} > {}", name, origin);
+        }
+        for (column, rule) in template.iter().filter(|(c, _)| *c != USE_KEY) {
+            rules.insert(column.clone(), (rule.clone(), name.to_string()));
+        }
+
+        Ok(rules)
+    }
+}
+
+// Merges the parameters into the config of the rule (`email: {kind: Safe}` + `{domain: test.dev}`)
+fn with_params(rule: &JsonValue, params: &JsonValue) -> Result<JsonValue, String> {
+    let (name, config) = match rule {
+        JsonValue::String(name) => (name, &JsonValue::Null),
+        JsonValue::Object(rule) if rule.len() == 1 => rule.iter().next().unwrap(),
+        _ => return Err("the rule must have one transformer".to_string()),
+    };
+
+    let mut config = match config {
+        JsonValue::Null => json!({}),
+        config => config.clone(),
+    };
+    deep_merge(&mut config, params);
+    Ok(json!({ name: config }))
+}
+
+fn deep_merge(target: &mut JsonValue, source: &JsonValue) {
+    match (target, source) {
+        (JsonValue::Object(target), JsonValue::Object(source)) => {
+            for (key, value) in source {
+                deep_merge(target.entry(key).or_insert(JsonValue::Null), value);
+            }
+        }
+        (target, source) => *target = source.clone(),
+    }
+}
+
+// The same deserialization as the rules of the settings
+fn check_rule(rule: &JsonValue) -> Result<(), String> {
+    let mut config = Config::new();
+    config
+        .merge(File::from_str(
+            &json!({ "rule": rule }).to_string(),
+            FileFormat::Json,
+        ))
+        .map_err(|e| e.to_string())?;
+    config
+        .get::<Transformers>("rule")
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        transformers::{EmailKind, EmailTransformer, PhoneTransformer},
+        Settings, Transformers,
+    };
+
+    fn email(s: &Settings, table: &str) -> EmailTransformer {
+        match &s.get_table(table).unwrap().rules["email"] {
+            Transformers::Email(t) => t.clone(),
+            t => panic!("unexpected rule {:?}", t),
+        }
+    }
+
+    const TEMPLATES: &str = r#"
+        rule_templates:
+          pii_email:
+            email:
+              email:
+                kind: Safe
+                domain: example.com
+          pii_name:
+            first_name:
+              first_name: {}
+          pii_contact:
+            use: [pii_email]
+            phone:
+              phone: {}
+    "#;
+
+    #[test]
+    fn expand() {
+        let config = format!(
+            r#"{}
+        tables:
+          - name: users
+            use: [pii_contact, pii_name]
+            rules:
+              last_name:
+                last_name: {{}}
+          - name: orders
+            rules:
+              note:
+                none: ~
+        "#,
+            TEMPLATES
+        );
+        let s = Settings::from_yaml(&config).unwrap();
+        let mut columns: Vec<_> = s.get_table("users").unwrap().rules.keys().collect();
+        columns.sort();
+        assert_eq!(columns, vec!["email", "first_name", "last_name", "phone"]);
+        assert_eq!(email(&s, "users").domain, Some("example.com".to_string()));
+        assert_eq!(s.get_table("orders").unwrap().rules.len(), 1);
+    }
+
+    #[test]
+    fn precedence() {
+        let config = format!(
+            r#"{}
+        tables:
+          - name: users
+            use:
+              pii_contact:
+                kind: Free
+                domain: test.dev
+            rules:
+              phone:
+                phone:
+                  format: "+1##########"
+          - name: admins
+            use: [pii_email]
+            rules:
+              email:
+                email:
+                  kind: Free
+          - name: customers
+            use: [pii_email]
+            ignore:
+              email: "already anonymized"
+        "#,
+            TEMPLATES
+        );
+        let s = Settings::from_yaml(&config).unwrap();
+
+        // the parameters override the template (including the used templates)
+        let t = email(&s, "users");
+        assert_eq!(t.domain, Some("test.dev".to_string()));
+        assert_eq!(t.kind, EmailKind::Free);
+        // the table rules override the templates
+        assert!(matches!(
+            s.get_table("users").unwrap().rules["phone"],
+            Transformers::Phone(PhoneTransformer {
+                format: Some(_),
+                ..
+            })
+        ));
+        let t = email(&s, "admins");
+        assert_eq!(t.kind, EmailKind::Free);
+        assert_eq!(t.domain, None);
+        // the ignored columns don't get the template rules
+        assert!(s.get_table("customers").unwrap().rules.is_empty());
+    }
+
+    #[test]
+    fn nested_own_rules() {
+        let config = r#"
+        rule_templates:
+          base:
+            email:
+              email: { kind: Safe }
+          strict:
+            use: { base: { domain: base.dev } }
+            email:
+              email: { kind: Free }
+        tables:
+          - name: users
+            use: [strict]
+        "#;
+        let s = Settings::from_yaml(config).unwrap();
+        let t = email(&s, "users");
+        assert_eq!(t.kind, EmailKind::Free);
+        assert_eq!(t.domain, None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |tables: &str| {
+            Settings::from_yaml(&format!("{}\n        tables:\n{}", TEMPLATES, tables))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("          - name: users\n            use: [pii_address]"),
+            "Unknown rule template `pii_address` in the table `users`"
+        );
+        assert!(error(
+            "          - name: users\n            use: { pii_contact: { kind: Unknown } }"
+        )
+        .starts_with(
+            "Invalid rule for the column `email` of the table `users` (from the rule template `pii_contact > pii_email`): "
+        ));
+
+        let config = r#"
+        rule_templates:
+          a:
+            use: [b]
+          b:
+            use: [a]
+        tables:
+          - name: users
+            use: [a]
+        "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The rule template `a` uses itself (a > b > a)"
+        );
+
+        let config = r#"
+        rule_templates:
+          a:
+            email:
+              email: { kind: Safe }
+          b:
+            email:
+              email: { kind: Free }
+        tables:
+          - name: users
+            use: [a, b]
+        "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The rule templates `a` and `b` used by the table `users` have different rules for the column `email`"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+
+/// Units of the sizes (as in the PostgreSQL settings, the multiplier is 1024)
+const SIZE_UNITS: [(&str, u64); 5] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("kB", 1 << 10),
+    ("B", 1),
+];
+
+/// Rules for the source databases that look like production. The dump is refused if any rule matches
+/// (unless it is confirmed with `--yes-i-know <REASON>`).
+///
+/// ```yaml
+/// safety:
+///   database_names: ["*prod*"]
+///   hosts: ["db-primary.internal", "*.prod.example.com"]
+///   max_database_size: 50GB
+///   sentinels:
+///     - setting: app.environment
+///       value: production
+///     - query: "SELECT name FROM environment"
+///       value: production
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Safety {
+    /// Patterns of the database names (`*` matches any characters, case-insensitive)
+    pub database_names: Vec<String>,
+    /// Patterns of the hosts from the database URL (`*` matches any characters, case-insensitive)
+    pub hosts: Vec<String>,
+    /// The maximum estimated size of the database, e.g. `50GB` (units: `B`, `kB`, `MB`, `GB`, `TB`)
+    pub max_database_size: Option<String>,
+    /// Settings or queries whose values mark the production databases
+    pub sentinels: Vec<Sentinel>,
+    /// `--yes-i-know` can't confirm the dump if it is `false`
+    pub allow_override: bool,
+}
+
+impl Default for Safety {
+    fn default() -> Self {
+        Self {
+            database_names: vec![],
+            hosts: vec![],
+            max_database_size: None,
+            sentinels: vec![],
+            allow_override: true,
+        }
+    }
+}
+
+/// A server setting (e.g. a custom one, like `app.environment`) or a query (its first value is compared)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Sentinel {
+    pub setting: Option<String>,
+    pub query: Option<String>,
+    pub value: String,
+}
+
+impl Safety {
+    pub fn validate(&self) -> Result<(), String> {
+        self.max_database_size_bytes()?;
+        for sentinel in &self.sentinels {
+            if sentinel.setting.is_some() == sentinel.query.is_some() {
+                return Err(format!(
+                    "A safety sentinel must have either `setting` or `query` (the value `{}`)",
+                    sentinel.value
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn max_database_size_bytes(&self) -> Result<Option<u64>, String> {
+        self.max_database_size
+            .as_deref()
+            .map(parse_size)
+            .transpose()
+    }
+}
+
+// `50GB`, `512 MB` or `1024` (bytes)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, multiplier) = SIZE_UNITS
+        .iter()
+        .find_map(|(unit, multiplier)| s.strip_suffix(unit).map(|n| (n, *multiplier)))
+        .unwrap_or((s, 1));
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| {
+            format!(
+                "Invalid size `{}` (examples: 50GB, 512MB, 100kB or a number of bytes)",
+                s
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn parse() {
+        let config = r#"
+            tables: []
+            safety:
+              database_names: ["*prod*"]
+              max_database_size: 50GB
+              sentinels:
+                - setting: app.environment
+                  value: production
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+        let safety = s.safety.unwrap();
+
+        assert_eq!(safety.database_names, vec!["*prod*"]);
+        assert!(safety.hosts.is_empty());
+        assert_eq!(safety.max_database_size_bytes(), Ok(Some(50 << 30)));
+        assert_eq!(
+            safety.sentinels[0].setting.as_deref(),
+            Some("app.environment")
+        );
+        assert!(safety.allow_override);
+
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.safety, None);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512 MB"), Ok(512 << 20));
+        assert_eq!(parse_size("100kB"), Ok(100 << 10));
+        assert_eq!(parse_size("2TB"), Ok(2 << 40));
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(
+            parse_size("lots"),
+            Err(
+                "Invalid size `lots` (examples: 50GB, 512MB, 100kB or a number of bytes)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let config = r#"
+            tables: []
+            safety:
+              sentinels:
+                - value: production
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "A safety sentinel must have either `setting` or `query` (the value `production`)"
+        );
+
+        let config = r#"
+            tables: []
+            safety:
+              max_database_size: 10 parsecs
+            "#;
+        assert!(Settings::from_yaml(config).is_err());
+    }
+}
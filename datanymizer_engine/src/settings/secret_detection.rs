@@ -0,0 +1,119 @@
+use serde::Deserialize;
+
+/// Detection of secrets (API keys, private keys, JWTs) in the text columns that are dumped as is.
+/// The values are sampled (at most `sample_size` values of a column) and never saved,
+/// the reports contain masked examples only.
+///
+/// ```yaml
+/// secret_detection:
+///   min_entropy: 4.0
+///   min_length: 20
+///   sample_size: 1000
+///   top: 5
+///   # the column names with or without the table names
+///   allow_columns: [public_key, public.webhooks.signature]
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretDetection {
+    /// The minimum Shannon entropy of the API key values (bits per character)
+    pub min_entropy: f64,
+    /// The minimum length of the API key values
+    pub min_length: usize,
+    /// The maximum count of the sampled values of a column (`0` disables the detection)
+    pub sample_size: usize,
+    /// The maximum count of the reported columns of a table (the columns with the most matches)
+    pub top: usize,
+    /// The columns that are never reported
+    pub allow_columns: Vec<String>,
+}
+
+impl Default for SecretDetection {
+    fn default() -> Self {
+        Self {
+            min_entropy: 4.0,
+            min_length: 20,
+            sample_size: 1000,
+            top: 5,
+            allow_columns: vec![],
+        }
+    }
+}
+
+impl SecretDetection {
+    pub fn is_enabled(&self) -> bool {
+        self.sample_size > 0
+    }
+
+    /// Returns `true` if the column of the table (by its names, e.g. `public.users` and `users`)
+    /// is in `allow_columns`
+    pub fn is_allowed<T: AsRef<str>>(&self, table_names: &[T], column: &str) -> bool {
+        self.allow_columns.iter().any(|allowed| {
+            allowed == column
+                || table_names
+                    .iter()
+                    .any(|table| *allowed == format!("{}.{}", table.as_ref(), column))
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.min_entropy > 0.0 && self.min_entropy <= 8.0) {
+            return Err(format!(
+                "Invalid `min_entropy` {} in `secret_detection` (bits per character, greater than 0 and not greater than 8)",
+                self.min_entropy
+            ));
+        }
+        if self.min_length == 0 {
+            return Err("`min_length` in `secret_detection` must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn parse() {
+        let settings = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(settings.secret_detection, SecretDetection::default());
+        assert!(settings.secret_detection.is_enabled());
+
+        let config = r#"
+            tables: []
+            secret_detection:
+              min_entropy: 3.5
+              sample_size: 0
+              allow_columns: [public_key, public.webhooks.signature]
+            "#;
+        let detection = Settings::from_yaml(config).unwrap().secret_detection;
+        assert_eq!(detection.min_entropy, 3.5);
+        assert_eq!(detection.min_length, 20);
+        assert!(!detection.is_enabled());
+
+        assert!(detection.is_allowed(&["public.keys", "keys"], "public_key"));
+        assert!(detection.is_allowed(&["public.webhooks", "webhooks"], "signature"));
+        assert!(!detection.is_allowed(&["other.webhooks", "webhooks"], "signature"));
+    }
+
+    #[test]
+    fn validate() {
+        for (config, error) in [
+            (
+                "{min_entropy: 9}",
+                "Invalid `min_entropy` 9 in `secret_detection`",
+            ),
+            (
+                "{min_length: 0}",
+                "`min_length` in `secret_detection` must be greater than 0",
+            ),
+        ] {
+            let e = Settings::from_yaml(&format!("{{tables: [], secret_detection: {}}}", config))
+                .unwrap_err()
+                .to_string();
+            assert!(e.starts_with(error), "{}", e);
+        }
+    }
+}
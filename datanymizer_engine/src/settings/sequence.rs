@@ -0,0 +1,40 @@
+use serde::Deserialize;
+
+/// What to do with the sequence value in the dump
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceAction {
+    /// Set the current value from the source database (even with `--no-sync-sequences`)
+    Preserve,
+    /// Restart the sequence with its start value
+    Reset,
+    /// The next value will be this one
+    StartAt(i64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn parse() {
+        let config = r#"
+            tables: []
+            sequences:
+              public.users_id_seq: preserve
+              public.orders.id: reset
+              public.items_id_seq:
+                start_at: 1000000
+            "#;
+        let s = Settings::from_yaml(config).unwrap();
+
+        assert_eq!(s.sequences.len(), 3);
+        assert_eq!(s.sequences["public.users_id_seq"], SequenceAction::Preserve);
+        assert_eq!(s.sequences["public.orders.id"], SequenceAction::Reset);
+        assert_eq!(
+            s.sequences["public.items_id_seq"],
+            SequenceAction::StartAt(1000000)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use super::safety::parse_size;
+use serde::{Deserialize, Deserializer};
+
+/// The memory for the client-side shuffling if `memory_limit` is not specified
+const DEFAULT_MEMORY_LIMIT: u64 = 64 << 20;
+
+/// Where the rows are shuffled
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMethod {
+    /// In the dumper, the rows that don't fit in `memory_limit` are spilled to temporary files
+    #[default]
+    Client,
+    /// `ORDER BY` in the dump query (the database sorts the whole table)
+    Server,
+}
+
+impl ShuffleMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Server => "server",
+        }
+    }
+}
+
+/// Shuffling of the dumped rows: the physical row order reveals the insertion order (and so the timing and
+/// volume patterns) even if all values are anonymized.
+///
+/// ```yaml
+/// tables:
+///   - name: orders
+///     shuffle_rows: true
+///   - name: events
+///     shuffle_rows:
+///       method: client
+///       # the same rows get the same order in every dump
+///       seed: 42
+///       memory_limit: 256MB
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ShuffleRows {
+    #[serde(default)]
+    pub method: ShuffleMethod,
+    /// The rows are ordered by the keyed hashes of the original rows (instead of random keys)
+    pub seed: Option<u64>,
+    /// The memory for the client-side shuffling, e.g. `256MB` (64MB by default)
+    pub memory_limit: Option<String>,
+}
+
+impl ShuffleRows {
+    pub fn validate(&self, table: &str) -> Result<(), String> {
+        self.memory_limit_bytes()
+            .map(|_| ())
+            .map_err(|e| format!("{} in `shuffle_rows` of the table `{}`", e, table))
+    }
+
+    pub fn memory_limit_bytes(&self) -> Result<u64, String> {
+        self.memory_limit
+            .as_deref()
+            .map_or(Ok(DEFAULT_MEMORY_LIMIT), parse_size)
+    }
+}
+
+// `true` (the default options), `false` or the options
+pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Option<ShuffleRows>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Config {
+        Enabled(bool),
+        Options(ShuffleRows),
+    }
+
+    Ok(match Config::deserialize(deserializer)? {
+        Config::Enabled(true) => Some(ShuffleRows::default()),
+        Config::Enabled(false) => None,
+        Config::Options(options) => Some(options),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    fn shuffle_rows(table: &str) -> Option<ShuffleRows> {
+        let config = format!("tables:\n  - name: orders\n    rules: {{}}\n{}", table);
+        Settings::from_yaml(&config).unwrap().tables[0]
+            .shuffle_rows
+            .clone()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(shuffle_rows(""), None);
+        assert_eq!(shuffle_rows("    shuffle_rows: false"), None);
+        assert_eq!(
+            shuffle_rows("    shuffle_rows: true"),
+            Some(ShuffleRows::default())
+        );
+        assert_eq!(
+            shuffle_rows(
+                "    shuffle_rows:\n      method: server\n      seed: 42\n      memory_limit: 1GB"
+            ),
+            Some(ShuffleRows {
+                method: ShuffleMethod::Server,
+                seed: Some(42),
+                memory_limit: Some("1GB".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn memory_limit() {
+        assert_eq!(
+            ShuffleRows::default().memory_limit_bytes(),
+            Ok(DEFAULT_MEMORY_LIMIT)
+        );
+
+        let config =
+            "tables:\n  - name: orders\n    rules: {}\n    shuffle_rows:\n      memory_limit: lots";
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "Invalid size `lots` (examples: 50GB, 512MB, 100kB or a number of bytes) \
+            in `shuffle_rows` of the table `orders`"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Deserializer};
+
+fn default_reset_sequences() -> bool {
+    true
+}
+
+/// The tables that are dumped without data (their DDL is kept), e.g. the event logs and the cache tables.
+///
+/// ```yaml
+/// skip_data:
+///   - audit_*
+///   - public.versions
+/// # or with the options
+/// skip_data:
+///   tables: [audit_*, public.versions]
+///   reset_sequences: false
+/// ```
+///
+/// The patterns are `pg_dump` table patterns (`*` and `?` wildcards, with or without schema).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkipData {
+    pub tables: Vec<String>,
+    /// The sequences owned by the tables are restarted (otherwise they aren't set)
+    pub reset_sequences: bool,
+}
+
+impl Default for SkipData {
+    fn default() -> Self {
+        Self {
+            tables: vec![],
+            reset_sequences: default_reset_sequences(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SkipData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Options {
+            #[serde(default)]
+            tables: Vec<String>,
+            #[serde(default = "default_reset_sequences")]
+            reset_sequences: bool,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Config {
+            Tables(Vec<String>),
+            Options(Options),
+        }
+
+        Ok(match Config::deserialize(deserializer)? {
+            Config::Tables(tables) => Self {
+                tables,
+                ..Self::default()
+            },
+            Config::Options(options) => Self {
+                tables: options.tables,
+                reset_sequences: options.reset_sequences,
+            },
+        })
+    }
+}
+
+/// The table is dumped without data (`skip_data` of a table in the config)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TableSkipData {
+    /// The sequences owned by the table are restarted (otherwise they aren't set)
+    #[serde(default = "default_reset_sequences")]
+    pub reset_sequences: bool,
+}
+
+impl Default for TableSkipData {
+    fn default() -> Self {
+        Self {
+            reset_sequences: default_reset_sequences(),
+        }
+    }
+}
+
+// `true` (the default options), `false` or the options
+pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Option<TableSkipData>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Config {
+        Enabled(bool),
+        Options(TableSkipData),
+    }
+
+    Ok(match Config::deserialize(deserializer)? {
+        Config::Enabled(true) => Some(TableSkipData::default()),
+        Config::Enabled(false) => None,
+        Config::Options(options) => Some(options),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn global() {
+        let s = Settings::from_yaml("tables: []").unwrap();
+        assert_eq!(s.skip_data, SkipData::default());
+        assert!(s.skip_data.reset_sequences);
+
+        let s = Settings::from_yaml("{tables: [], skip_data: [audit_*, public.versions]}").unwrap();
+        assert_eq!(
+            s.skip_data,
+            SkipData {
+                tables: vec!["audit_*".to_string(), "public.versions".to_string()],
+                reset_sequences: true,
+            }
+        );
+
+        let config = "{tables: [], skip_data: {tables: [cache], reset_sequences: false}}";
+        let s = Settings::from_yaml(config).unwrap();
+        assert_eq!(
+            s.skip_data,
+            SkipData {
+                tables: vec!["cache".to_string()],
+                reset_sequences: false,
+            }
+        );
+    }
+
+    #[test]
+    fn table() {
+        let skip_data = |table: &str| {
+            let config = format!("tables:\n  - name: events\n{}", table);
+            Settings::from_yaml(&config).unwrap().tables[0]
+                .skip_data
+                .clone()
+        };
+        assert_eq!(skip_data(""), None);
+        assert_eq!(skip_data("    skip_data: false"), None);
+        assert_eq!(
+            skip_data("    skip_data: true"),
+            Some(TableSkipData {
+                reset_sequences: true
+            })
+        );
+        assert_eq!(
+            skip_data("    skip_data:\n      reset_sequences: false"),
+            Some(TableSkipData {
+                reset_sequences: false
+            })
+        );
+    }
+}
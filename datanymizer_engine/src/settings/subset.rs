@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// A coherent subset of the data: the seed rows of the root tables, the rows they reference (by foreign keys,
+/// transitively) and, optionally, the rows that reference them. The tables that reference the selected tables
+/// get only the rows whose foreign keys point to the selected rows, all other tables are dumped as is.
+///
+/// ```yaml
+/// subset:
+///   roots:
+///     users: "created_at > now() - interval '30 days'"
+///   children:
+///     - table: orders
+///     - table: comments
+///       foreign_key: comments_parent_id_fkey
+///       max_depth: 5
+///       max_rows: 10000
+///   cut_foreign_keys: [users_referrer_id_fkey]
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Subset {
+    /// SQL `WHERE` conditions of the seed rows (the keys are table names, with or without schema)
+    pub roots: BTreeMap<String, String>,
+    /// Foreign keys that pull the referencing rows (children) of the selected rows
+    #[serde(default)]
+    pub children: Vec<SubsetChildren>,
+    /// Nullable foreign keys (constraint names) whose referenced rows are not pulled: the values that reference
+    /// rows out of the subset are dumped as NULL
+    #[serde(default)]
+    pub cut_foreign_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct SubsetChildren {
+    /// The referencing table (with or without schema)
+    pub table: String,
+    /// The constraint name (all foreign keys of the table by default)
+    pub foreign_key: Option<String>,
+    /// How many rounds the children are pulled in (more than 1 for self-references and cycles)
+    #[serde(default = "SubsetChildren::default_max_depth")]
+    pub max_depth: u32,
+    /// The maximum count of the rows pulled by this rule
+    pub max_rows: Option<u64>,
+}
+
+impl SubsetChildren {
+    fn default_max_depth() -> u32 {
+        1
+    }
+}
+
+impl Subset {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.roots.is_empty() {
+            return Err("The subset must have at least one root table".to_string());
+        }
+        if let Some(children) = self.children.iter().find(|c| c.max_depth == 0) {
+            return Err(format!(
+                "`max_depth` of the subset children `{}` must be at least 1",
+                children.table
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Settings;
+
+    #[test]
+    fn parse() {
+        let config = r#"
+            tables: []
+            subset:
+              roots:
+                users: "created_at > now() - interval '30 days'"
+              children:
+                - table: orders
+                - table: comments
+                  foreign_key: comments_parent_id_fkey
+                  max_depth: 5
+                  max_rows: 10000
+              cut_foreign_keys: [users_referrer_id_fkey]
+            "#;
+        let subset = Settings::from_yaml(config).unwrap().subset.unwrap();
+
+        assert_eq!(
+            subset.roots["users"],
+            "created_at > now() - interval '30 days'"
+        );
+        assert_eq!(subset.children[0].table, "orders");
+        assert_eq!(subset.children[0].foreign_key, None);
+        assert_eq!(subset.children[0].max_depth, 1);
+        assert_eq!(subset.children[0].max_rows, None);
+        assert_eq!(
+            subset.children[1].foreign_key.as_deref(),
+            Some("comments_parent_id_fkey")
+        );
+        assert_eq!(subset.children[1].max_depth, 5);
+        assert_eq!(subset.children[1].max_rows, Some(10000));
+        assert_eq!(subset.cut_foreign_keys, vec!["users_referrer_id_fkey"]);
+    }
+
+    #[test]
+    fn validate() {
+        let config = r#"
+            tables: []
+            subset:
+              roots: {}
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The subset must have at least one root table"
+        );
+
+        let config = r#"
+            tables: []
+            subset:
+              roots:
+                users: "true"
+              children:
+                - table: orders
+                  max_depth: 0
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "`max_depth` of the subset children `orders` must be at least 1"
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use super::TransformList;
+use super::{
+    shuffle_rows, skip_data, Lint, Outbox, RowLimit, Sample, ShuffleRows, TableSkipData,
+    TimestampOrder, TransformList,
+};
 use crate::Transformers;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 type Rules = HashMap<String, Transformers>;
 
@@ -19,15 +22,250 @@ pub struct Query {
 pub struct Table {
     /// Table name
     pub name: String,
-    /// Rule set for columns
+    /// Rule set for columns (including the rules of the templates in `use`)
+    #[serde(default)]
     pub rules: Rules,
     /// Order of applying rules. All rules not listed are placed at the beginning
     pub rule_order: Option<Vec<String>>,
     /// Limit and conditions for the dumping query
     pub query: Option<Query>,
+    /// The row limit (e.g. `10000`), `none` disables the global `default_limit` for the table
+    pub limit: Option<RowLimit>,
+    /// Sampling of the table pages (e.g. `1%`)
+    pub sample: Option<Sample>,
+    /// Allows rules of a table with schema (e.g. `public.users`) to replace
+    /// the conflicting rules of the same table without schema (just `users`)
+    #[serde(default, rename = "override")]
+    pub override_rules: bool,
+    /// Quasi-identifier column sets for the k-anonymity check (e.g. `[[zip, birth_year, gender]]`)
+    #[serde(default)]
+    pub quasi_identifiers: Vec<Vec<String>>,
+    /// Columns that are dumped as is on purpose, with the reasons (e.g. `id: "surrogate key"`)
+    #[serde(default)]
+    pub ignore: HashMap<String, String>,
+    /// Requires every column of the table to have a rule or to be ignored
+    /// (the global `explicit` setting is used if not specified)
+    pub explicit: Option<bool>,
+    /// Timestamp columns whose values must stay ordered (e.g. `[created_at, updated_at, deleted_at]`)
+    #[serde(default)]
+    pub timestamp_order: Vec<TimestampOrder>,
+    /// Allows a table name without schema to match tables in several schemas
+    #[serde(default)]
+    pub apply_to_all_schemas: bool,
+    /// Columns whose rules are applied even if the column types are not supported by the rules
+    #[serde(default)]
+    pub force: Vec<String>,
+    /// Shuffling of the dumped rows (`true` or the options)
+    #[serde(default, deserialize_with = "shuffle_rows::deserialize")]
+    pub shuffle_rows: Option<ShuffleRows>,
+    /// Lint options of the columns (e.g. `first_name: allow_mismatch`)
+    #[serde(default)]
+    pub lint: HashMap<String, Lint>,
+    /// Only these columns are dumped (e.g. `[id, email, created_at]`), the other columns get their defaults
+    /// (or NULL) on restore
+    #[serde(default)]
+    pub only_columns: Vec<String>,
+    /// The event payloads of an audit or outbox table are transformed by the rules of their event types
+    pub outbox: Option<Outbox>,
+    /// The table is dumped without data (`true` or the options), its DDL is kept
+    #[serde(default, deserialize_with = "skip_data::deserialize")]
+    pub skip_data: Option<TableSkipData>,
+    /// The preset that the table is added by (the tables of the config have none)
+    #[serde(skip)]
+    pub preset: Option<String>,
+    /// The columns whose rules are added by presets (the synced tables may have no such columns)
+    #[serde(skip)]
+    pub preset_rules: BTreeSet<String>,
+    /// The columns of the rules in the order of the config file (it isn't kept by the config loader)
+    #[serde(skip)]
+    pub declared_rules: Vec<String>,
+}
+
+/// The columns of the rules of the tables (by the table names) in the order of the YAML source
+/// (`tables` is the list of the tables)
+pub(super) fn declared_rules(tables: &serde_yaml::Value) -> HashMap<String, Vec<String>> {
+    let mut result = HashMap::new();
+    for table in tables.as_sequence().into_iter().flatten() {
+        let name = table.get("name").and_then(|n| n.as_str());
+        let rules = table.get("rules").and_then(|r| r.as_mapping());
+        if let (Some(name), Some(rules)) = (name, rules) {
+            let columns = rules
+                .iter()
+                .filter_map(|(column, _)| column.as_str().map(|c| c.to_string()))
+                .collect();
+            result.entry(name.to_string()).or_insert(columns);
+        }
+    }
+    result
 }
 
 impl Table {
+    /// Returns the table name without schema
+    pub fn bare_name(&self) -> &str {
+        self.name
+            .rsplit_once('.')
+            .map_or(self.name.as_str(), |(_, name)| name)
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        self.name.contains('.')
+    }
+
+    /// Adds the rules of the same table without schema (`bare`) to this table.
+    /// Rules with the same column and another transformer are only allowed with `override: true`.
+    pub fn merge_bare(&mut self, bare: &Table) -> Result<(), String> {
+        let mut conflicts: Vec<_> = bare
+            .rules
+            .iter()
+            .filter(|(column, rule)| self.rules.get(*column).is_some_and(|r| r != *rule))
+            .map(|(column, _)| column.as_str())
+            .collect();
+        if !conflicts.is_empty() && !self.override_rules {
+            conflicts.sort_unstable();
+            return Err(format!(
+                "Rules for the tables `{}` and `{}` conflict on the columns: {} (use `override: true` in `{}` to replace them)",
+                self.name,
+                bare.name,
+                conflicts.join(", "),
+                self.name
+            ));
+        }
+
+        self.add_rules(bare);
+        if self.explicit.is_none() {
+            self.explicit = bare.explicit;
+        }
+        if self.rule_order.is_none() {
+            self.rule_order = bare.rule_order.clone();
+        }
+        for column in &bare.declared_rules {
+            if !self.declared_rules.contains(column) {
+                self.declared_rules.push(column.clone());
+            }
+        }
+        if self.query.is_none() {
+            self.query = bare.query.clone();
+        }
+        if self.limit.is_none() {
+            self.limit = bare.limit;
+        }
+        if self.sample.is_none() {
+            self.sample = bare.sample;
+        }
+        if self.quasi_identifiers.is_empty() {
+            self.quasi_identifiers = bare.quasi_identifiers.clone();
+        }
+        if self.timestamp_order.is_empty() {
+            self.timestamp_order = bare.timestamp_order.clone();
+        }
+        if self.only_columns.is_empty() {
+            self.only_columns = bare.only_columns.clone();
+        }
+        if self.outbox.is_none() {
+            self.outbox = bare.outbox.clone();
+        }
+
+        Ok(())
+    }
+
+    /// Adds the rules and the ignored columns of `other` for the columns that have no rules
+    /// and are not ignored in this table. Returns the columns of the added rules.
+    pub fn add_rules(&mut self, other: &Table) -> Vec<String> {
+        let mut added = vec![];
+        for (column, rule) in &other.rules {
+            if !self.ignore.contains_key(column) && !self.rules.contains_key(column) {
+                self.rules.insert(column.clone(), rule.clone());
+                added.push(column.clone());
+            }
+        }
+        for (column, reason) in &other.ignore {
+            if !self.rules.contains_key(column) {
+                self.ignore
+                    .entry(column.clone())
+                    .or_insert_with(|| reason.clone());
+            }
+        }
+        added
+    }
+
+    /// Checks that the ignored columns have reasons and no rules
+    pub fn validate_ignore(&self) -> Result<(), String> {
+        let mut columns: Vec<_> = self.ignore.iter().collect();
+        columns.sort_unstable();
+        for (column, reason) in columns {
+            if self.rules.contains_key(column) {
+                return Err(format!(
+                    "The column `{}` of the table `{}` has a rule and is ignored at the same time",
+                    column, self.name
+                ));
+            }
+            if reason.trim().is_empty() {
+                return Err(format!(
+                    "The ignored column `{}` of the table `{}` must have a reason",
+                    column, self.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the columns of the rules are in `only_columns` (the rules of the presets may refer
+    /// to the columns that aren't dumped)
+    pub fn validate_only_columns(&self) -> Result<(), String> {
+        if self.only_columns.is_empty() {
+            return Ok(());
+        }
+        let mut omitted: Vec<_> = self
+            .rules
+            .keys()
+            .filter(|column| !self.preset_rules.contains(*column) && !self.is_dumped(column))
+            .map(|column| column.as_str())
+            .collect();
+        if !omitted.is_empty() {
+            omitted.sort_unstable();
+            return Err(format!(
+                "The columns of the rules of the table `{}` are not in `only_columns`: {}",
+                self.name,
+                omitted.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns `false` if the column isn't dumped (it isn't in `only_columns`)
+    pub fn is_dumped(&self, column: &str) -> bool {
+        self.only_columns.is_empty() || self.only_columns.iter().any(|c| c == column)
+    }
+
+    /// Returns the dumped columns that have no rules and are not ignored
+    pub fn unmentioned_columns<'a, T: AsRef<str>>(&self, columns: &'a [T]) -> Vec<&'a str> {
+        columns
+            .iter()
+            .map(|c| c.as_ref())
+            .filter(|c| {
+                self.is_dumped(c) && !self.rules.contains_key(*c) && !self.ignore.contains_key(*c)
+            })
+            .collect()
+    }
+
+    /// The columns that the table config refers to (the preset rules are not counted: the synced tables
+    /// may have no such columns)
+    pub fn referenced_columns(&self) -> BTreeSet<&str> {
+        self.rules
+            .keys()
+            .filter(|column| !self.preset_rules.contains(*column))
+            .chain(self.ignore.keys())
+            .chain(self.force.iter())
+            .chain(self.rule_order.iter().flatten())
+            .chain(self.quasi_identifiers.iter().flatten())
+            .chain(self.timestamp_order.iter().flat_map(|o| o.columns.iter()))
+            .chain(self.only_columns.iter())
+            .map(|column| column.as_str())
+            .collect()
+    }
+
     pub fn transform_list(&self) -> TransformList {
         let explicit_rule_order = self.rule_order.clone().unwrap_or_default();
         let mut transform_list: TransformList = self
@@ -35,8 +273,18 @@ pub fn transform_list(&self) -> TransformList {
             .iter()
             .map(|(key, ts)| (key.clone(), ts.clone()))
             .collect();
-        transform_list
-            .sort_by_cached_key(|(key, _)| explicit_rule_order.iter().position(|i| i == key));
+        // the rules that aren't declared in the config file (e.g., from templates and presets) follow the declared ones,
+        // sorted by the columns, so the order is the same in every run
+        transform_list.sort_by_cached_key(|(key, _)| {
+            (
+                explicit_rule_order.iter().position(|i| i == key),
+                self.declared_rules
+                    .iter()
+                    .position(|i| i == key)
+                    .unwrap_or(usize::MAX),
+                key.clone(),
+            )
+        });
 
         transform_list
     }
@@ -46,6 +294,225 @@ pub fn transform_list(&self) -> TransformList {
 mod tests {
     use super::*;
 
+    mod merge_bare {
+        use super::*;
+
+        fn table(config: &str) -> Table {
+            serde_yaml::from_str(config).unwrap()
+        }
+
+        #[test]
+        fn bare_name() {
+            assert_eq!(table("{name: users, rules: {}}").bare_name(), "users");
+            assert_eq!(table("{name: a.users, rules: {}}").bare_name(), "users");
+        }
+
+        #[test]
+        fn merge() {
+            let mut t = table(
+                r#"
+                name: tenant_a.users
+                rules:
+                  name:
+                    first_name: {}
+                "#,
+            );
+            let bare = table(
+                r#"
+                name: users
+                rules:
+                  name:
+                    first_name: {}
+                  email:
+                    email: {}
+                rule_order:
+                  - email
+                query:
+                  limit: 10
+                "#,
+            );
+
+            t.merge_bare(&bare).unwrap();
+            assert_eq!(t.rules.len(), 2);
+            assert!(matches!(t.rules["email"], Transformers::Email(_)));
+            assert_eq!(t.rule_order, Some(vec!["email".to_string()]));
+            assert_eq!(t.query.unwrap().limit, Some(10));
+        }
+
+        #[test]
+        fn conflict() {
+            let config = r#"
+                name: tenant_a.users
+                rules:
+                  name:
+                    last_name: {}
+                "#;
+            let bare = table(
+                r#"
+                name: users
+                rules:
+                  name:
+                    first_name: {}
+                "#,
+            );
+
+            let err = table(config).merge_bare(&bare).unwrap_err();
+            assert_eq!(
+                err,
+                "Rules for the tables `tenant_a.users` and `users` conflict on the columns: name (use `override: true` in `tenant_a.users` to replace them)"
+            );
+
+            let mut t = table(&format!("{}override: true\n", config));
+            t.merge_bare(&bare).unwrap();
+            assert!(matches!(t.rules["name"], Transformers::LastName(_)));
+        }
+    }
+
+    mod ignore {
+        use super::*;
+
+        fn table(config: &str) -> Table {
+            serde_yaml::from_str(config).unwrap()
+        }
+
+        #[test]
+        fn unmentioned_columns() {
+            let t = table(
+                r#"
+                name: users
+                explicit: true
+                ignore:
+                  id: surrogate key
+                rules:
+                  name:
+                    first_name: {}
+                "#,
+            );
+            assert_eq!(t.explicit, Some(true));
+            assert_eq!(t.ignore["id"], "surrogate key");
+            assert_eq!(
+                t.unmentioned_columns(&["id", "name", "email", "created_at"]),
+                vec!["email", "created_at"]
+            );
+
+            let t = table("{name: users, rules: {}, only_columns: [id, email]}");
+            assert_eq!(
+                t.unmentioned_columns(&["id", "name", "email", "created_at"]),
+                vec!["id", "email"]
+            );
+        }
+
+        #[test]
+        fn referenced_columns() {
+            let mut t = table(
+                r#"
+                name: users
+                ignore:
+                  id: surrogate key
+                rules:
+                  name:
+                    first_name: {}
+                  phone:
+                    phone: {}
+                force: [phone]
+                quasi_identifiers: [[zip, birth_year]]
+                timestamp_order: [{columns: [created_at, updated_at]}]
+                "#,
+            );
+            t.preset_rules.insert("phone".to_string());
+            assert_eq!(
+                t.referenced_columns().into_iter().collect::<Vec<_>>(),
+                vec![
+                    "birth_year",
+                    "created_at",
+                    "id",
+                    "name",
+                    "phone",
+                    "updated_at",
+                    "zip"
+                ]
+            );
+        }
+
+        #[test]
+        fn validate() {
+            let t = table("{name: users, rules: {}, ignore: {id: surrogate key}}");
+            assert!(t.validate_ignore().is_ok());
+
+            let t = table("{name: users, rules: {}, ignore: {id: ''}}");
+            assert_eq!(
+                t.validate_ignore().unwrap_err(),
+                "The ignored column `id` of the table `users` must have a reason"
+            );
+
+            let t = table("{name: users, rules: {id: {random_num: {}}}, ignore: {id: key}}");
+            assert_eq!(
+                t.validate_ignore().unwrap_err(),
+                "The column `id` of the table `users` has a rule and is ignored at the same time"
+            );
+        }
+
+        #[test]
+        fn validate_only_columns() {
+            let mut t = table(
+                r#"
+                name: users
+                only_columns: [id, email]
+                rules:
+                  email:
+                    email: {}
+                  phone:
+                    phone: {}
+                  name:
+                    first_name: {}
+                "#,
+            );
+            assert!(t.is_dumped("email"));
+            assert!(!t.is_dumped("phone"));
+            assert_eq!(
+                t.validate_only_columns().unwrap_err(),
+                "The columns of the rules of the table `users` are not in `only_columns`: name, phone"
+            );
+
+            t.preset_rules
+                .extend(["name".to_string(), "phone".to_string()]);
+            assert!(t.validate_only_columns().is_ok());
+        }
+
+        #[test]
+        fn merge() {
+            let mut t = table(
+                r#"
+                name: tenant_a.users
+                ignore:
+                  email: test data only
+                rules:
+                  id:
+                    random_num: {}
+                "#,
+            );
+            let bare = table(
+                r#"
+                name: users
+                explicit: true
+                ignore:
+                  id: surrogate key
+                  created_at: not PII
+                rules:
+                  email:
+                    email: {}
+                "#,
+            );
+
+            t.merge_bare(&bare).unwrap();
+            assert_eq!(t.rules.keys().collect::<Vec<_>>(), vec!["id"]);
+            let mut ignored: Vec<_> = t.ignore.keys().collect();
+            ignored.sort();
+            assert_eq!(ignored, vec!["created_at", "email"]);
+            assert_eq!(t.explicit, Some(true));
+        }
+    }
+
     mod transform_list {
         use super::*;
 
@@ -89,5 +556,51 @@ fn order() {
             assert_eq!(names[3], "greeting");
             assert_eq!(names[4], "options");
         }
+
+        #[test]
+        fn declared_order() {
+            let config = r#"
+                name: table1
+                rule_order:
+                  - greeting
+                rules:
+                  zip:
+                    zip_code: {}
+                  last_name:
+                    last_name: {}
+                  greeting:
+                    template:
+                      format: "dear {{ final.last_name }}"
+                  first_name:
+                    first_name: {}
+                  city:
+                    city: {}
+                "#;
+            let mut t: Table = serde_yaml::from_str(config).unwrap();
+            t.declared_rules = vec!["zip".into(), "last_name".into(), "first_name".into()];
+
+            assert_eq!(
+                rule_names(&t),
+                vec!["zip", "last_name", "first_name", "city", "greeting"]
+            );
+        }
+
+        #[test]
+        fn declared_rules_from_yaml() {
+            let config = r#"
+                - name: table1
+                  rules:
+                    zip:
+                      zip: {}
+                    city:
+                      city: {}
+                - name: table2
+                "#;
+            let tables: serde_yaml::Value = serde_yaml::from_str(config).unwrap();
+            let declared = declared_rules(&tables);
+
+            assert_eq!(declared["table1"], vec!["zip", "city"]);
+            assert!(!declared.contains_key("table2"));
+        }
     }
 }
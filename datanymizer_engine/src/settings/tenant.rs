@@ -0,0 +1,147 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// What is dumped from a table without the tenant columns
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantScope {
+    /// All rows (e.g., dictionaries shared by all tenants)
+    #[default]
+    Include,
+    /// No rows (the schema is dumped)
+    Exclude,
+    /// The rows related to the rows of the tenant by foreign keys (as in `subset`)
+    ForeignKeys,
+}
+
+impl TenantScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Include => "include",
+            Self::Exclude => "exclude",
+            Self::ForeignKeys => "foreign_keys",
+        }
+    }
+}
+
+/// Per-tenant dumps: only the rows of one tenant are dumped from the tables with the tenant column.
+///
+/// ```yaml
+/// tenant:
+///   # the first column that the table has is used (`tenant_id` by default)
+///   columns: [tenant_id, account_id]
+///   # usually it is passed with `--tenant-id`
+///   id: 42
+///   other_tables: foreign_keys
+///   tables:
+///     countries: include
+///     audit_log: exclude
+/// ```
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Tenant {
+    /// The tenant columns
+    #[serde(default = "Tenant::default_columns")]
+    pub columns: Vec<String>,
+    /// The tenant value (`--tenant-id` overrides it)
+    pub id: Option<String>,
+    /// What is dumped from the tables without the tenant columns
+    #[serde(default)]
+    pub other_tables: TenantScope,
+    /// Overrides of `other_tables` for some tables (the keys are table names, with or without schema)
+    #[serde(default)]
+    pub tables: BTreeMap<String, TenantScope>,
+}
+
+impl Tenant {
+    fn default_columns() -> Vec<String> {
+        vec!["tenant_id".to_string()]
+    }
+
+    /// The scope of a table without the tenant columns
+    pub fn scope<T: AsRef<str>>(&self, names: &[T]) -> TenantScope {
+        names
+            .iter()
+            .find_map(|name| self.tables.get(name.as_ref()))
+            .copied()
+            .unwrap_or(self.other_tables)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns.is_empty() {
+            return Err("The tenant must have at least one column".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[test]
+    fn parse() {
+        let config = r#"
+            tables: []
+            tenant:
+              columns: [tenant_id, account_id]
+              id: 42
+              other_tables: foreign_keys
+              tables:
+                countries: include
+                public.audit_log: exclude
+            "#;
+        let tenant = Settings::from_yaml(config).unwrap().tenant.unwrap();
+
+        assert_eq!(tenant.columns, vec!["tenant_id", "account_id"]);
+        assert_eq!(tenant.id.as_deref(), Some("42"));
+        assert_eq!(tenant.other_tables, TenantScope::ForeignKeys);
+        assert_eq!(
+            tenant.scope(&["public.countries", "countries"]),
+            TenantScope::Include
+        );
+        assert_eq!(
+            tenant.scope(&["public.audit_log", "audit_log"]),
+            TenantScope::Exclude
+        );
+        assert_eq!(
+            tenant.scope(&["public.orders", "orders"]),
+            TenantScope::ForeignKeys
+        );
+
+        let config = r#"
+            tables: []
+            tenant: {}
+            "#;
+        let tenant = Settings::from_yaml(config).unwrap().tenant.unwrap();
+        assert_eq!(tenant.columns, vec!["tenant_id"]);
+        assert_eq!(tenant.id, None);
+        assert_eq!(tenant.other_tables, TenantScope::Include);
+    }
+
+    #[test]
+    fn validate() {
+        let config = r#"
+            tables: []
+            tenant:
+              columns: []
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The tenant must have at least one column"
+        );
+
+        let config = r#"
+            tables: []
+            tenant: {}
+            subset:
+              roots:
+                users: "true"
+            "#;
+        assert_eq!(
+            Settings::from_yaml(config).unwrap_err().to_string(),
+            "The `tenant` and `subset` config sections can't be used together"
+        );
+    }
+}
@@ -0,0 +1,490 @@
+use crate::{
+    errors::{EngineError, UnknownColumnError},
+    transformer::TransformError,
+};
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+use std::{borrow::Cow, collections::HashMap};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+const INFINITY: &str = "infinity";
+const MINUS_INFINITY: &str = "-infinity";
+const MICROS: i64 = 1_000_000;
+
+/// How to restore the order of the values
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStrategy {
+    /// Move every value that is earlier than the previous one to the previous value
+    #[default]
+    Clamp,
+    /// Keep the earliest and the latest values and space all values evenly between them
+    Redistribute,
+}
+
+/// Timestamp columns whose values must be ordered in every row (e.g., `created_at <= updated_at <= deleted_at`).
+/// The order is restored after all column rules. NULLs are skipped (e.g., `deleted_at` of rows that are not deleted).
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct TimestampOrder {
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub strategy: OrderStrategy,
+}
+
+impl TimestampOrder {
+    pub fn validate(&self, table: &str) -> Result<(), String> {
+        if self.columns.len() < 2 {
+            return Err(format!(
+                "The `timestamp_order` of the table `{}` must have at least 2 columns",
+                table
+            ));
+        }
+        Ok(())
+    }
+
+    /// Restores the order of the column values in the row (the values are in the COPY text format)
+    pub fn apply(
+        &self,
+        table: &str,
+        column_indexes: &HashMap<String, usize>,
+        values: &mut [Cow<str>],
+    ) -> Result<(), EngineError> {
+        let mut indexes = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            match column_indexes.get(column) {
+                Some(&i) if i < values.len() => indexes.push(i),
+                _ => {
+                    return Err(EngineError::UnknownColumnError(UnknownColumnError {
+                        field_name: column.clone(),
+                    }))
+                }
+            }
+        }
+
+        let mut timestamps = Vec::with_capacity(indexes.len());
+        for (column, i) in self.columns.iter().zip(indexes) {
+            if values[i] == NULL {
+                continue;
+            }
+            match Timestamp::parse(&values[i]) {
+                Some(t) => timestamps.push((i, t)),
+                None => {
+                    return Err(EngineError::TransformFieldError(TransformError {
+                        field_name: format!("{}.{}", table, column),
+                        field_value: values[i].to_string(),
+                        reason: "Can't parse the timestamp for `timestamp_order`".to_string(),
+                    }))
+                }
+            }
+        }
+
+        let mut micros: Vec<i64> = timestamps.iter().map(|(_, t)| t.micros).collect();
+        if micros.windows(2).all(|w| w[0] <= w[1]) {
+            return Ok(());
+        }
+        match self.strategy {
+            OrderStrategy::Clamp => clamp(&mut micros),
+            OrderStrategy::Redistribute => redistribute(&mut micros),
+        }
+
+        for ((i, t), m) in timestamps.into_iter().zip(micros) {
+            if m != t.micros {
+                values[i] = Cow::Owned(t.style.format(m));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn clamp(micros: &mut [i64]) {
+    for i in 1..micros.len() {
+        if micros[i] < micros[i - 1] {
+            micros[i] = micros[i - 1];
+        }
+    }
+}
+
+fn redistribute(micros: &mut [i64]) {
+    let min = micros.iter().copied().min().unwrap_or_default();
+    let max = micros.iter().copied().max().unwrap_or_default();
+    // infinite values can't be spaced
+    if min == i64::MIN || max == i64::MAX {
+        return clamp(micros);
+    }
+
+    let steps = (micros.len() - 1) as i128;
+    for (i, m) in micros.iter_mut().enumerate() {
+        *m = min + ((max - min) as i128 * i as i128 / steps) as i64;
+    }
+}
+
+/// A parsed value: microseconds since the epoch (UTC) and the format to write the value back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timestamp {
+    micros: i64,
+    style: Style,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Date,
+    DateTime {
+        /// `T` or space
+        separator: char,
+        offset: Option<Offset>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Offset {
+    seconds: i32,
+    /// `Z`
+    zulu: bool,
+    /// `+03:00` (RFC 3339), otherwise `+03` (as PostgreSQL writes it)
+    colon: bool,
+}
+
+impl Timestamp {
+    fn parse(s: &str) -> Option<Self> {
+        let style = match s.as_bytes().get(10) {
+            _ if s == INFINITY || s == MINUS_INFINITY => return Self::infinity(s),
+            None => Style::Date,
+            Some(&c) if c == b'T' || c == b' ' => {
+                let offset = match s[10..].find(['+', '-', 'Z']) {
+                    Some(i) => Some(Offset::parse(&s[i + 10..])?),
+                    None => None,
+                };
+                Style::DateTime {
+                    separator: c as char,
+                    offset,
+                }
+            }
+            Some(_) => return None,
+        };
+
+        let micros = match style {
+            Style::Date => {
+                let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+                to_micros(date.and_hms(0, 0, 0))
+            }
+            Style::DateTime { offset, .. } => {
+                let end = s[10..].find(['+', '-', 'Z']).map_or(s.len(), |i| i + 10);
+                let datetime = NaiveDateTime::parse_from_str(
+                    &format!("{} {}", &s[..10], &s[11..end]),
+                    "%Y-%m-%d %H:%M:%S%.f",
+                )
+                .ok()?;
+                to_micros(datetime) - i64::from(offset.map_or(0, |o| o.seconds)) * MICROS
+            }
+        };
+        Some(Self { micros, style })
+    }
+
+    fn infinity(s: &str) -> Option<Self> {
+        let micros = match s {
+            INFINITY => i64::MAX,
+            MINUS_INFINITY => i64::MIN,
+            _ => return None,
+        };
+        Some(Self {
+            micros,
+            style: Style::Date,
+        })
+    }
+}
+
+impl Style {
+    fn format(&self, micros: i64) -> String {
+        match micros {
+            i64::MAX => return INFINITY.to_string(),
+            i64::MIN => return MINUS_INFINITY.to_string(),
+            _ => {}
+        }
+
+        match self {
+            // PostgreSQL truncates timestamps to dates, but the next day keeps the order
+            Self::Date => {
+                let datetime = from_micros(micros);
+                let date = if datetime.time() == chrono::NaiveTime::from_hms(0, 0, 0) {
+                    datetime.date()
+                } else {
+                    datetime.date().succ()
+                };
+                date.format("%Y-%m-%d").to_string()
+            }
+            Self::DateTime { separator, offset } => {
+                let seconds = offset.map_or(0, |o| o.seconds);
+                let local = from_micros(micros + i64::from(seconds) * MICROS);
+                let mut result = format!(
+                    "{}{}{}",
+                    local.format("%Y-%m-%d"),
+                    separator,
+                    local.format("%H:%M:%S")
+                );
+                let fraction = local.timestamp_subsec_micros();
+                if fraction > 0 {
+                    result.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+                }
+                if let Some(offset) = offset {
+                    result.push_str(&offset.format());
+                }
+                result
+            }
+        }
+    }
+}
+
+impl Offset {
+    fn parse(s: &str) -> Option<Self> {
+        if s == "Z" {
+            return Some(Self {
+                seconds: 0,
+                zulu: true,
+                colon: true,
+            });
+        }
+
+        let sign = match s.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let colon = s.contains(':');
+        let digits: String = s[1..].chars().filter(|c| *c != ':').collect();
+        let number = |range: std::ops::Range<usize>| -> Option<i32> {
+            digits.get(range).map_or(Some(0), |d| d.parse().ok())
+        };
+        if digits.len() < 2 || digits.len() > 6 {
+            return None;
+        }
+        let seconds = number(0..2)? * 3600 + number(2..4)? * 60 + number(4..6)?;
+        FixedOffset::east_opt(sign * seconds)?;
+
+        Some(Self {
+            seconds: sign * seconds,
+            zulu: false,
+            colon,
+        })
+    }
+
+    fn format(&self) -> String {
+        if self.zulu && self.seconds == 0 {
+            return "Z".to_string();
+        }
+
+        let sign = if self.seconds < 0 { '-' } else { '+' };
+        let seconds = self.seconds.abs();
+        let (hours, minutes, secs) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+        let mut result = format!("{}{:02}", sign, hours);
+        if self.colon || minutes > 0 || secs > 0 {
+            result.push_str(&format!(":{:02}", minutes));
+        }
+        if secs > 0 {
+            result.push_str(&format!(":{:02}", secs));
+        }
+        result
+    }
+}
+
+fn to_micros(datetime: NaiveDateTime) -> i64 {
+    datetime.timestamp() * MICROS + i64::from(datetime.timestamp_subsec_micros())
+}
+
+fn from_micros(micros: i64) -> NaiveDateTime {
+    chrono::Utc
+        .timestamp(
+            micros.div_euclid(MICROS),
+            (micros.rem_euclid(MICROS) * 1000) as u32,
+        )
+        .naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn order(strategy: OrderStrategy) -> TimestampOrder {
+        TimestampOrder {
+            columns: vec![
+                "created_at".to_string(),
+                "updated_at".to_string(),
+                "deleted_at".to_string(),
+            ],
+            strategy,
+        }
+    }
+
+    fn column_indexes() -> HashMap<String, usize> {
+        [
+            ("id", 0),
+            ("created_at", 1),
+            ("updated_at", 2),
+            ("deleted_at", 3),
+        ]
+        .iter()
+        .map(|(c, i)| (c.to_string(), *i))
+        .collect()
+    }
+
+    fn apply(order: &TimestampOrder, row: [&str; 4]) -> Vec<String> {
+        let mut values: Vec<Cow<str>> = row.iter().map(|v| Cow::from(*v)).collect();
+        order
+            .apply("users", &column_indexes(), &mut values)
+            .unwrap();
+        values.into_iter().map(|v| v.into_owned()).collect()
+    }
+
+    #[test]
+    fn parse_and_format() {
+        for s in [
+            "2021-03-04",
+            "2021-03-04 05:06:07",
+            "2021-03-04 05:06:07.25",
+            "2021-03-04 05:06:07+03",
+            "2021-03-04 05:06:07.123456-05:30",
+            "2021-03-04T05:06:07+00:00",
+            "2021-03-04T05:06:07.5Z",
+            "infinity",
+            "-infinity",
+        ] {
+            let t = Timestamp::parse(s).unwrap();
+            assert_eq!(t.style.format(t.micros), s);
+        }
+
+        assert_eq!(
+            Timestamp::parse("2021-03-04 05:06:07+03").unwrap().micros,
+            Timestamp::parse("2021-03-04T02:06:07Z").unwrap().micros
+        );
+        assert_eq!(Timestamp::parse("some value"), None);
+        assert_eq!(Timestamp::parse("2021-03-04 05:06"), None);
+    }
+
+    #[test]
+    fn ordered_rows_are_kept() {
+        let row = ["1", "2021-01-01 10:00:00", "2021-01-02 10:00:00", r"\N"];
+        for strategy in [OrderStrategy::Clamp, OrderStrategy::Redistribute] {
+            assert_eq!(apply(&order(strategy), row), row);
+        }
+    }
+
+    #[test]
+    fn clamp() {
+        let row = [
+            "1",
+            "2021-01-02 10:00:00+03",
+            "2021-01-01 10:00:00+03",
+            "2021-01-02 09:00:00+02",
+        ];
+        assert_eq!(
+            apply(&order(OrderStrategy::Clamp), row),
+            [
+                "1",
+                "2021-01-02 10:00:00+03",
+                "2021-01-02 10:00:00+03",
+                "2021-01-02 09:00:00+02"
+            ]
+        );
+    }
+
+    #[test]
+    fn redistribute() {
+        let row = [
+            "1",
+            "2021-01-03 00:00:00",
+            "2021-01-01 00:00:00",
+            "2021-01-02 00:00:00",
+        ];
+        assert_eq!(
+            apply(&order(OrderStrategy::Redistribute), row),
+            [
+                "1",
+                "2021-01-01 00:00:00",
+                "2021-01-02 00:00:00",
+                "2021-01-03 00:00:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn nulls_and_dates() {
+        let row = ["1", "2021-01-02 10:00:00", r"\N", "2021-01-01"];
+        assert_eq!(
+            apply(&order(OrderStrategy::Clamp), row),
+            ["1", "2021-01-02 10:00:00", r"\N", "2021-01-03"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut values: Vec<Cow<str>> = vec!["1".into(), "yesterday".into(), r"\N".into()];
+        let mut indexes = column_indexes();
+        assert!(matches!(
+            order(OrderStrategy::Clamp).apply("users", &indexes, &mut values),
+            Err(EngineError::UnknownColumnError(_))
+        ));
+
+        indexes.remove("deleted_at");
+        let order = TimestampOrder {
+            columns: vec!["created_at".to_string(), "updated_at".to_string()],
+            strategy: OrderStrategy::Clamp,
+        };
+        assert!(matches!(
+            order.apply("users", &indexes, &mut values),
+            Err(EngineError::TransformFieldError(_))
+        ));
+        assert!(TimestampOrder {
+            columns: vec!["created_at".to_string()],
+            strategy: OrderStrategy::Clamp,
+        }
+        .validate("users")
+        .is_err());
+    }
+
+    // Random shifts of ordered values (as independent date jitter does)
+    #[test]
+    fn random_shifts() {
+        let mut rng = rand::thread_rng();
+        let base = Timestamp::parse("2021-01-01 00:00:00+00").unwrap().micros;
+        let style = Timestamp::parse("2021-01-01 00:00:00+00").unwrap().style;
+        for strategy in [OrderStrategy::Clamp, OrderStrategy::Redistribute] {
+            for _ in 0..500 {
+                let values: Vec<String> = (0..3)
+                    .map(|_| {
+                        if rng.gen_bool(0.1) {
+                            NULL.to_string()
+                        } else {
+                            style.format(base + rng.gen_range(-100_000_000_000..100_000_000_000))
+                        }
+                    })
+                    .collect();
+                let row = ["1", &values[0], &values[1], &values[2]];
+                let result = apply(&order(strategy), row);
+
+                let micros: Vec<_> = result[1..]
+                    .iter()
+                    .filter(|v| *v != NULL)
+                    .map(|v| Timestamp::parse(v).unwrap().micros)
+                    .collect();
+                assert!(micros.windows(2).all(|w| w[0] <= w[1]), "{:?}", result);
+
+                let original: Vec<_> = values
+                    .iter()
+                    .filter(|v| *v != NULL)
+                    .map(|v| Timestamp::parse(v).unwrap().micros)
+                    .collect();
+                assert_eq!(micros.len(), original.len());
+                if let (Some(min), Some(max)) = (original.iter().min(), original.iter().max()) {
+                    assert!(micros.iter().all(|m| m >= min && m <= max));
+                }
+                if strategy == OrderStrategy::Clamp {
+                    assert_eq!(micros.first(), original.first());
+                } else {
+                    assert_eq!(micros.first(), original.iter().min());
+                    assert_eq!(micros.last(), original.iter().max());
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,28 @@
+use crate::memory::{self, Component};
 use anyhow::{anyhow, bail, Result};
+use once_cell::sync::Lazy;
 use serde_json::Value;
 use std::{
     collections::{hash_map::Entry, HashMap},
     sync::{Arc, RwLock},
 };
 
+/// The memory of a stored value besides the key and the strings
+const ENTRY_OVERHEAD: u64 = 64;
+
+/// The values are kept till the end of the run, so they are never released
+static MEMORY: Lazy<Arc<Component>> =
+    Lazy::new(|| memory::accountant().component(memory::TEMPLATE_STORE));
+
+fn entry_bytes(key: &str, value: &Value) -> u64 {
+    let strings = match value {
+        Value::String(s) => s.len(),
+        Value::Array(_) | Value::Object(_) => value.to_string().len(),
+        _ => 0,
+    };
+    (key.len() + strings) as u64 + ENTRY_OVERHEAD
+}
+
 pub trait KeyValueStore: Clone + Sync + Send {
     fn read_value(&self, key: &str) -> Result<Option<Value>>;
 
@@ -28,6 +46,7 @@ fn write_value(&self, key: String, value: Value) -> Result<()> {
             Ok(mut map) => match map.entry(key) {
                 Entry::Occupied(e) => Err(anyhow!("Can't overwrite the key {}", e.key())),
                 Entry::Vacant(e) => {
+                    MEMORY.add(entry_bytes(e.key(), &value));
                     e.insert(value);
                     Ok(())
                 }
@@ -39,6 +58,10 @@ fn write_value(&self, key: String, value: Value) -> Result<()> {
     fn force_write_value(&self, key: String, value: Value) -> Result<()> {
         match self.as_ref().write() {
             Ok(mut map) => {
+                MEMORY.add(entry_bytes(&key, &value));
+                if let Some(old) = map.get(&key) {
+                    MEMORY.release(entry_bytes(&key, old));
+                }
                 map.insert(key, value);
                 Ok(())
             }
@@ -75,6 +98,7 @@ fn inc_value(&self, key: String, value: Value) -> Result<()> {
                                 e.key()
                             )
                         }
+                        MEMORY.add(entry_bytes(e.key(), &value));
                         e.insert(value);
                     }
                 };
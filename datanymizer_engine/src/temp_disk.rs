@@ -0,0 +1,451 @@
+//! The temporary files of the components that spill to disk (the table staging, the row shuffling,
+//! the k-anonymity classes). All of them are created in one directory (`--temp-dir`) and accounted
+//! against one budget (`--max-temp-disk`), the free space is checked before the spills.
+//!
+//! The files are guards: they are removed when dropped (on success and on errors), and on unix they are
+//! unlinked right after they are created, so the data is freed by the OS even if the process is interrupted.
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use thiserror::Error;
+
+/// The free space is checked again after every this many written bytes of a file
+const FREE_SPACE_STEP: u64 = 64 << 20;
+
+const SIZE_UNITS: [(&str, u64); 4] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("kB", 1 << 10),
+];
+
+static FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TempDiskError {
+    #[error(
+        "The temporary files of {component} exceed --max-temp-disk {} ({} is used in {}), \
+        raise --max-temp-disk or use --temp-dir on a larger disk",
+        format_size(*max),
+        format_size(*used),
+        dir.display()
+    )]
+    Budget {
+        component: String,
+        dir: PathBuf,
+        max: u64,
+        used: u64,
+    },
+    #[error(
+        "Not enough free space for the temporary files of {component} in {} ({} is needed, {} is free), \
+        use --temp-dir on a larger disk",
+        dir.display(),
+        format_size(*needed),
+        format_size(*free)
+    )]
+    FreeSpace {
+        component: String,
+        dir: PathBuf,
+        needed: u64,
+        free: u64,
+    },
+    #[error("Can't create a temporary file of {component} in {}: {message}", dir.display())]
+    Create {
+        component: String,
+        dir: PathBuf,
+        message: String,
+    },
+}
+
+impl TempDiskError {
+    /// The name of the component whose temporary files fail
+    pub fn component(&self) -> &str {
+        match self {
+            Self::Budget { component, .. }
+            | Self::FreeSpace { component, .. }
+            | Self::Create { component, .. } => component,
+        }
+    }
+
+    /// Finds the error in the chain (the writes of the temporary files return it inside `io::Error`)
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|e| {
+            e.downcast_ref::<Self>().or_else(|| {
+                e.downcast_ref::<io::Error>()
+                    .and_then(|e| e.get_ref())
+                    .and_then(|e| e.downcast_ref::<Self>())
+            })
+        })
+    }
+}
+
+impl From<TempDiskError> for io::Error {
+    fn from(e: TempDiskError) -> Self {
+        io::Error::other(e)
+    }
+}
+
+/// The directory and the budget of the temporary files (the accountant of the used bytes)
+#[derive(Debug)]
+pub struct TempDisk {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+    used: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl Default for TempDisk {
+    /// The system temporary directory (`TMPDIR`) without a budget
+    fn default() -> Self {
+        Self::new(env::temp_dir(), None)
+    }
+}
+
+impl TempDisk {
+    pub fn new(dir: PathBuf, max_bytes: Option<u64>) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            used: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// The bytes of the existing temporary files
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// The maximum of `used` in the run
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    /// Checks that `bytes` fit in the rest of the budget and in the free space of the directory
+    /// (before a spill of a known size)
+    pub fn check(&self, component: &str, bytes: u64) -> Result<(), TempDiskError> {
+        if let Some(max) = self.max_bytes {
+            let used = self.used();
+            if used.saturating_add(bytes) > max {
+                return Err(self.budget_error(component, max, used));
+            }
+        }
+        self.check_free_space(component, bytes)
+    }
+
+    /// Creates a temporary file of the component, `expected` bytes are checked with `check`
+    pub fn create(
+        self: &Arc<Self>,
+        component: &str,
+        expected: u64,
+    ) -> Result<TempFile, TempDiskError> {
+        self.check(component, expected)?;
+
+        let path = self.dir.join(format!(
+            "datanymizer-{}-{}.tmp",
+            process::id(),
+            FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| TempDiskError::Create {
+                component: component.to_string(),
+                dir: self.dir.clone(),
+                message: e.to_string(),
+            })?;
+        #[cfg(unix)]
+        let _ = fs::remove_file(&path);
+
+        Ok(TempFile {
+            disk: self.clone(),
+            component: component.to_string(),
+            path,
+            file,
+            written: 0,
+            checked: expected,
+        })
+    }
+
+    fn reserve(&self, component: &str, bytes: u64) -> Result<(), TempDiskError> {
+        let max = self.max_bytes.unwrap_or(u64::MAX);
+        let used = self
+            .used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(bytes).filter(|total| *total <= max)
+            })
+            .map_err(|used| self.budget_error(component, max, used))?;
+        self.peak.fetch_max(used + bytes, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    fn check_free_space(&self, component: &str, bytes: u64) -> Result<(), TempDiskError> {
+        match free_space(&self.dir) {
+            Some(free) if free < bytes => Err(TempDiskError::FreeSpace {
+                component: component.to_string(),
+                dir: self.dir.clone(),
+                needed: bytes,
+                free,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn budget_error(&self, component: &str, max: u64, used: u64) -> TempDiskError {
+        TempDiskError::Budget {
+            component: component.to_string(),
+            dir: self.dir.clone(),
+            max,
+            used,
+        }
+    }
+}
+
+/// A temporary file (it is removed when dropped). The writes are accounted in the budget,
+/// it is read from the start after `rewind`.
+#[derive(Debug)]
+pub struct TempFile {
+    disk: Arc<TempDisk>,
+    component: String,
+    path: PathBuf,
+    file: File,
+    written: u64,
+    /// The written bytes that the free space is checked for
+    checked: u64,
+}
+
+impl TempFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// A handle of the same file for reading (it shares the position with the file, the reads aren't accounted)
+    pub fn try_clone(&self) -> io::Result<File> {
+        self.file.try_clone()
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len() as u64;
+        self.disk.reserve(&self.component, len)?;
+        if self.written + len > self.checked {
+            let step = FREE_SPACE_STEP.max(len);
+            if let Err(e) = self.disk.check_free_space(&self.component, step) {
+                self.disk.release(len);
+                return Err(e.into());
+            }
+            self.checked = self.written + step;
+        }
+
+        match self.file.write(buf) {
+            Ok(n) => {
+                self.written += n as u64;
+                self.disk.release(len - n as u64);
+                Ok(n)
+            }
+            Err(e) => {
+                self.disk.release(len);
+                Err(e)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        self.disk.release(self.written);
+        #[cfg(not(unix))]
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// `10GB`, `1.5MB` or `512B` (the units of the size options)
+pub fn format_size(bytes: u64) -> String {
+    for (unit, multiplier) in SIZE_UNITS {
+        if bytes >= multiplier {
+            return if bytes.is_multiple_of(multiplier) {
+                format!("{}{}", bytes / multiplier, unit)
+            } else {
+                format!("{:.1}{}", bytes as f64 / multiplier as f64, unit)
+            };
+        }
+    }
+    format!("{}B", bytes)
+}
+
+// The space available to the unprivileged users (`None` if it is unknown)
+#[cfg(unix)]
+fn free_space(dir: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: the path is a valid C string and `stat` is a valid pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    // a directory of its own for every test
+    fn temp_disk(name: &str, max_bytes: Option<u64>) -> Arc<TempDisk> {
+        let dir = env::temp_dir().join(format!("datanymizer_temp_disk_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Arc::new(TempDisk::new(dir, max_bytes))
+    }
+
+    fn is_empty(disk: &TempDisk) -> bool {
+        fs::read_dir(disk.dir()).unwrap().next().is_none()
+    }
+
+    #[test]
+    fn accounting() {
+        let disk = temp_disk("accounting", Some(100));
+        let mut first = disk.create("the first component", 0).unwrap();
+        first.write_all(&[1; 60]).unwrap();
+        let mut second = disk.create("the second component", 0).unwrap();
+        second.write_all(&[2; 30]).unwrap();
+        assert_eq!(disk.used(), 90);
+
+        let error = second.write_all(&[2; 20]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "The temporary files of the second component exceed --max-temp-disk 100B (90B is used in {}), \
+                raise --max-temp-disk or use --temp-dir on a larger disk",
+                disk.dir().display()
+            )
+        );
+        assert_eq!(disk.used(), 90);
+        assert!(disk.create("the third component", 20).is_err());
+
+        drop(first);
+        assert_eq!(disk.used(), 30);
+        second.write_all(&[2; 20]).unwrap();
+        assert_eq!(disk.used(), 50);
+        assert_eq!(disk.peak(), 90);
+
+        drop(second);
+        assert_eq!(disk.used(), 0);
+        assert!(is_empty(&disk));
+    }
+
+    #[test]
+    fn read_back() {
+        let disk = temp_disk("read_back", None);
+        let mut file = disk.create("the test", 0).unwrap();
+        file.write_all(b"some rows\n").unwrap();
+        file.rewind().unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "some rows\n");
+        assert_eq!(file.written(), 10);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unlinked() {
+        // nothing is left in the directory even if the process is killed while the file is used
+        let disk = temp_disk("unlinked", None);
+        let mut file = disk.create("the test", 0).unwrap();
+        file.write_all(b"data").unwrap();
+        assert!(!file.path().exists());
+        assert!(is_empty(&disk));
+    }
+
+    #[test]
+    fn removed_on_errors() {
+        let disk = temp_disk("removed_on_errors", Some(10));
+        let result = (|| -> anyhow::Result<()> {
+            let mut file = disk.create("the failed component", 0)?;
+            file.write_all(&[0; 20]).context("Can't spill")?;
+            Ok(())
+        })();
+        let error = result.unwrap_err();
+        assert!(matches!(
+            TempDiskError::find(&error),
+            Some(TempDiskError::Budget { component, .. }) if component == "the failed component"
+        ));
+        assert_eq!(disk.used(), 0);
+        assert!(is_empty(&disk));
+    }
+
+    #[test]
+    fn free_space() {
+        let disk = temp_disk("free_space", None);
+        assert!(disk.check("the test", 1).is_ok());
+        #[cfg(unix)]
+        assert!(matches!(
+            disk.check("the test", u64::MAX),
+            Err(TempDiskError::FreeSpace { .. })
+        ));
+        assert!(matches!(
+            Arc::new(TempDisk::new(disk.dir().join("missing"), None)).create("the test", 0),
+            Err(TempDiskError::Create { .. })
+        ));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(10 << 30), "10GB");
+        assert_eq!(format_size(3 << 19), "1.5MB");
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(0), "0B");
+    }
+}
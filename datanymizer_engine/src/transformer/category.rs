@@ -0,0 +1,116 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Column name parts (with the separators removed) that strongly suggest personal names
+const NAME_PARTS: [&str; 9] = [
+    "firstname",
+    "lastname",
+    "middlename",
+    "fullname",
+    "givenname",
+    "familyname",
+    "maidenname",
+    "surname",
+    "forename",
+];
+const PHONE_WORDS: [&str; 5] = ["mobile", "cellphone", "telephone", "msisdn", "fax"];
+const DATE_WORDS: [&str; 5] = ["date", "dob", "birthday", "birthdate", "timestamp"];
+const ADDRESS_WORDS: [&str; 7] = [
+    "address", "street", "city", "zip", "zipcode", "postcode", "postal",
+];
+/// Addresses that are not postal addresses (e.g., `ip_address`)
+const NOT_POSTAL_WORDS: [&str; 4] = ["ip", "mac", "remote", "host"];
+
+/// Categories of the values that rules generate (every transformer has one in the registry)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleCategory {
+    Name,
+    Phone,
+    Email,
+    Date,
+    Address,
+    Internet,
+    Business,
+    Text,
+    Other,
+    /// The rule fits any column (e.g., `template`, `none` or `scramble`)
+    Any,
+}
+
+impl RuleCategory {
+    /// The category that the column name strongly suggests (name, phone, email, date or address).
+    /// Returns `None` for other names, they aren't checked.
+    pub fn of_column(column: &str) -> Option<Self> {
+        let column = column.to_lowercase();
+        let words: Vec<_> = column
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let joined = words.concat();
+        let has = |list: &[&str]| words.iter().any(|w| list.contains(w));
+
+        if joined.contains("email") || words.contains(&"mail") {
+            Some(Self::Email)
+        } else if joined.contains("phone") || has(&PHONE_WORDS) {
+            Some(Self::Phone)
+        } else if NAME_PARTS.iter().any(|p| joined.contains(p)) {
+            Some(Self::Name)
+        } else if has(&DATE_WORDS) || words.last() == Some(&"at") {
+            Some(Self::Date)
+        } else if has(&ADDRESS_WORDS) && !has(&NOT_POSTAL_WORDS) {
+            Some(Self::Address)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for RuleCategory {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Name => "name",
+            Self::Phone => "phone",
+            Self::Email => "email",
+            Self::Date => "date",
+            Self::Address => "address",
+            Self::Internet => "internet",
+            Self::Business => "business",
+            Self::Text => "text",
+            Self::Other => "other",
+            Self::Any => "any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn of_column() {
+        for (column, category) in [
+            ("email", Some(RuleCategory::Email)),
+            ("contact_email_address", Some(RuleCategory::Email)),
+            ("mail", Some(RuleCategory::Email)),
+            ("phone_number", Some(RuleCategory::Phone)),
+            ("mobile", Some(RuleCategory::Phone)),
+            ("first_name", Some(RuleCategory::Name)),
+            ("LastName", Some(RuleCategory::Name)),
+            ("surname", Some(RuleCategory::Name)),
+            ("created_at", Some(RuleCategory::Date)),
+            ("birth_date", Some(RuleCategory::Date)),
+            ("dob", Some(RuleCategory::Date)),
+            ("billing_address", Some(RuleCategory::Address)),
+            ("city", Some(RuleCategory::Address)),
+            ("zip", Some(RuleCategory::Address)),
+            ("ip_address", None),
+            ("name", None),
+            ("company_name", None),
+            ("status", None),
+            ("format", None),
+            ("mailbox_id", None),
+        ] {
+            assert_eq!(RuleCategory::of_column(column), category, "{}", column);
+        }
+    }
+}
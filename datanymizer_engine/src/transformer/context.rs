@@ -39,6 +39,12 @@ pub fn prev_row_map(&self) -> Option<HashMap<&String, &str>> {
         None
     }
 
+    /// The original value of the column in the current row
+    pub fn prev_value(&self, column: &str) -> Option<&'a str> {
+        let i = *self.column_indexes?.get(column)?;
+        self.prev_row?.get(i).copied()
+    }
+
     pub fn final_row_map(&self) -> Option<HashMap<&String, &String>> {
         if let Some(row) = self.final_row {
             if let Some(column_indexes) = self.column_indexes {
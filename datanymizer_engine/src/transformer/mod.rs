@@ -1,10 +1,14 @@
+mod category;
 mod context;
+mod type_class;
 mod uniq_transformer;
 mod uniqueness;
 
+pub use category::RuleCategory;
 pub use context::TransformContext;
+pub use type_class::TypeClass;
 pub use uniq_transformer::UniqTransformer;
-pub use uniqueness::Uniqueness;
+pub use uniqueness::{UniqueAgainst, Uniqueness};
 
 use serde::Deserialize;
 use serde_json::Value;
@@ -16,7 +20,7 @@
     sync::{Arc, RwLock},
 };
 
-use crate::{settings::TemplatesCollection, LocaleConfig};
+use crate::{settings::TemplatesCollection, LocaleConfig, DERIVATION_VERSION};
 
 pub type TransformResult = result::Result<Option<String>, TransformError>;
 pub type Globals = HashMap<String, Value>;
@@ -40,6 +44,12 @@ pub struct TransformerInitContext {
     pub defaults: TransformerDefaults,
     pub template_store: TemplateStore,
     pub template_collection: TemplatesCollection,
+    /// The global salt of the `consistent` rules
+    pub consistent_salt: String,
+    /// The run pepper, it is mixed into the salts of all salted rules (empty if there is no pepper)
+    pub pepper: String,
+    /// The version of the seed derivation of the consistent values (`derivation_version`)
+    pub derivation_version: u32,
 }
 
 impl TransformerInitContext {
@@ -48,6 +58,9 @@ pub fn from_defaults(defaults: TransformerDefaults) -> Self {
             defaults,
             template_store: TemplateStore::default(),
             template_collection: TemplatesCollection::default(),
+            consistent_salt: String::new(),
+            pepper: String::new(),
+            derivation_version: DERIVATION_VERSION,
         }
     }
 }
@@ -0,0 +1,91 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Classes of the column types that rules can generate values for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeClass {
+    Text,
+    Numeric,
+    Boolean,
+    Temporal,
+    Binary,
+    Json,
+    Interval,
+}
+
+impl TypeClass {
+    /// The class of the column type (`data_type` from `information_schema.columns`).
+    /// Returns `None` for other types (e.g., `uuid`, arrays or user-defined types), they aren't checked.
+    pub fn of(data_type: &str) -> Option<Self> {
+        match data_type {
+            "text" | "character varying" | "character" | "name" => Some(Self::Text),
+            "smallint" | "integer" | "bigint" | "numeric" | "real" | "double precision" => {
+                Some(Self::Numeric)
+            }
+            "boolean" => Some(Self::Boolean),
+            "date"
+            | "timestamp without time zone"
+            | "timestamp with time zone"
+            | "time without time zone"
+            | "time with time zone" => Some(Self::Temporal),
+            "bytea" => Some(Self::Binary),
+            "json" | "jsonb" => Some(Self::Json),
+            "interval" => Some(Self::Interval),
+            _ => None,
+        }
+    }
+
+    /// Whether a rule that supports these classes can be applied to a column of this type
+    /// (`None` means any type)
+    pub fn is_compatible(supported: Option<&[Self]>, data_type: &str) -> bool {
+        match (supported, Self::of(data_type)) {
+            (Some(supported), Some(class)) => supported.contains(&class),
+            _ => true,
+        }
+    }
+}
+
+impl Display for TypeClass {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Text => "text",
+            Self::Numeric => "numeric",
+            Self::Boolean => "boolean",
+            Self::Temporal => "temporal",
+            Self::Binary => "binary",
+            Self::Json => "json",
+            Self::Interval => "interval",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn of() {
+        assert_eq!(TypeClass::of("character varying"), Some(TypeClass::Text));
+        assert_eq!(TypeClass::of("bigint"), Some(TypeClass::Numeric));
+        assert_eq!(
+            TypeClass::of("timestamp with time zone"),
+            Some(TypeClass::Temporal)
+        );
+        assert_eq!(TypeClass::of("bytea"), Some(TypeClass::Binary));
+        assert_eq!(TypeClass::of("jsonb"), Some(TypeClass::Json));
+        assert_eq!(TypeClass::of("interval"), Some(TypeClass::Interval));
+        assert_eq!(TypeClass::of("USER-DEFINED"), None);
+    }
+
+    #[test]
+    fn is_compatible() {
+        let text = Some([TypeClass::Text].as_slice());
+        assert!(TypeClass::is_compatible(text, "text"));
+        assert!(!TypeClass::is_compatible(text, "bytea"));
+        assert!(!TypeClass::is_compatible(text, "bigint"));
+        assert!(TypeClass::is_compatible(text, "uuid"));
+        assert!(!TypeClass::is_compatible(text, "jsonb"));
+        assert!(!TypeClass::is_compatible(text, "interval"));
+        assert!(TypeClass::is_compatible(None, "bytea"));
+    }
+}
@@ -138,6 +138,7 @@ fn no_uniqueness() {
             uniq: Uniqueness {
                 required: false,
                 try_count: None,
+                unique_against: None,
             },
         };
         let name = "uniq_transformer.no_uniqueness.name";
@@ -153,6 +154,7 @@ fn uniqueness_no_retries() {
             uniq: Uniqueness {
                 required: true,
                 try_count: None,
+                unique_against: None,
             },
         };
         let name = "uniq_transformer.uniqueness_no_retries.name";
@@ -168,6 +170,7 @@ fn uniqueness_diff_fields() {
             uniq: Uniqueness {
                 required: true,
                 try_count: None,
+                unique_against: None,
             },
         };
         let name1 = "uniq_transformer.uniqueness_diff_fields.name1";
@@ -184,6 +187,7 @@ fn uniqueness_one_retry() {
             uniq: Uniqueness {
                 required: true,
                 try_count: None,
+                unique_against: None,
             },
         };
         let name = "uniq_transformer.uniqueness_one_retry.name";
@@ -199,6 +203,7 @@ fn uniqueness_zero_limit() {
             uniq: Uniqueness {
                 required: true,
                 try_count: None,
+                unique_against: None,
             },
         };
         let name = "uniq_transformer.uniqueness_zero_limit.name";
@@ -213,6 +218,7 @@ fn limit_from_uniq() {
             uniq: Uniqueness {
                 required: true,
                 try_count: Some(1),
+                unique_against: None,
             },
         };
         let name = "uniq_transformer.limit_from_uniq.name";
@@ -5,6 +5,33 @@
 pub struct Uniqueness {
     pub(crate) required: bool,
     pub(crate) try_count: Option<i64>,
+    pub(crate) unique_against: Option<UniqueAgainst>,
+}
+
+/// The column with the existing values that the generated values must not collide with
+/// (they are scanned before the dump and registered in the uniqueness set)
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct UniqueAgainst {
+    /// The table name (with or without the schema)
+    pub table: String,
+    pub column: String,
+    /// The maximum count of the distinct existing values (the dump fails if there are more)
+    #[serde(default = "UniqueAgainst::default_max_values")]
+    pub max_values: u64,
+}
+
+impl UniqueAgainst {
+    pub const DEFAULT_MAX_VALUES: u64 = 10_000_000;
+
+    fn default_max_values() -> u64 {
+        Self::DEFAULT_MAX_VALUES
+    }
+}
+
+impl Uniqueness {
+    pub fn unique_against(&self) -> Option<&UniqueAgainst> {
+        self.unique_against.as_ref()
+    }
 }
 
 impl Default for Uniqueness {
@@ -25,8 +52,10 @@ fn from(config: Config) -> Self {
 impl From<FullConfig> for Uniqueness {
     fn from(full_config: FullConfig) -> Self {
         Self {
-            required: full_config.required,
+            // the values are checked against the existing ones in the uniqueness set
+            required: full_config.required || full_config.unique_against.is_some(),
             try_count: full_config.try_count,
+            unique_against: full_config.unique_against,
         }
     }
 }
@@ -40,8 +69,10 @@ enum Config {
 
 #[derive(Deserialize, Default)]
 struct FullConfig {
+    #[serde(default)]
     required: bool,
     try_count: Option<i64>,
+    unique_against: Option<UniqueAgainst>,
 }
 
 impl From<bool> for FullConfig {
@@ -68,7 +99,8 @@ fn short_deserialization() {
             deserialize(config),
             Uniqueness {
                 required: true,
-                try_count: None
+                try_count: None,
+                unique_against: None
             }
         );
     }
@@ -83,7 +115,8 @@ fn full_deserialization() {
             deserialize(config),
             Uniqueness {
                 required: true,
-                try_count: Some(5)
+                try_count: Some(5),
+                unique_against: None
             }
         );
     }
@@ -97,8 +130,42 @@ fn full_deserialization_no_count() {
             deserialize(config),
             Uniqueness {
                 required: true,
-                try_count: None
+                try_count: None,
+                unique_against: None
             }
         );
     }
+
+    #[test]
+    fn unique_against_deserialization() {
+        let config = r#"
+unique_against:
+  table: public.users
+  column: email
+"#;
+        assert_eq!(
+            deserialize(config),
+            Uniqueness {
+                required: true,
+                try_count: None,
+                unique_against: Some(UniqueAgainst {
+                    table: "public.users".to_string(),
+                    column: "email".to_string(),
+                    max_values: UniqueAgainst::DEFAULT_MAX_VALUES,
+                })
+            }
+        );
+
+        let config = r#"
+required: true
+unique_against:
+  table: users
+  column: email
+  max_values: 1000
+"#;
+        assert_eq!(
+            deserialize(config).unique_against().map(|u| u.max_values),
+            Some(1000)
+        );
+    }
 }
@@ -0,0 +1,283 @@
+use crate::{
+    transformer::{
+        TransformContext, TransformResult, TransformResultHelper, Transformer,
+        TransformerInitContext,
+    },
+    utils,
+};
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::hash::{Hash, Hasher};
+
+/// Gets a boolean value (TRUE/FALSE), with a given probability of TRUE.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   marketing_opt_in:
+///     boolean:
+///       # the probability of TRUE (0.5 by default)
+///       ratio: 0.2
+///       # `TRUE/FALSE` (default), `t/f`, `1/0`, `Y/N` or `auto` (the encoding of the original values)
+///       format: Y/N
+///       # the same original values (or values of the `key` column) get the same results
+///       consistent: true
+///       key: user_id
+///       salt: "some secret"
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct BooleanTransformer {
+    /// The probability of TRUE (from 0 to 1)
+    #[serde(deserialize_with = "deserialize_ratio")]
+    pub ratio: f64,
+    pub format: BooleanFormat,
+    /// Derives the results from the original values (keyed by `salt`) instead of generating them randomly
+    pub consistent: bool,
+    /// The column whose original value is used with `consistent` (the transformed column by default)
+    pub key: Option<String>,
+    /// The key of the `consistent` results
+    pub salt: String,
+
+    /// The run pepper (it is set on initialization)
+    #[serde(skip)]
+    pepper: String,
+}
+
+/// The encoding of the boolean values
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub enum BooleanFormat {
+    /// `TRUE` / `FALSE` (for `boolean` and text columns)
+    #[default]
+    #[serde(rename = "TRUE/FALSE")]
+    TrueFalse,
+    /// `t` / `f` (as PostgreSQL dumps `boolean` columns, or `char(1)` flags)
+    #[serde(rename = "t/f")]
+    TF,
+    /// `1` / `0` (for `smallint` or `char(1)` flags)
+    #[serde(rename = "1/0")]
+    OneZero,
+    /// `Y` / `N` (for `char(1)` flags)
+    #[serde(rename = "Y/N")]
+    YN,
+    /// The encoding of the original value (`TRUE/FALSE` if it isn't recognized)
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+impl BooleanFormat {
+    /// The encoding of the value (case-insensitive for `t/f` and `Y/N`)
+    fn detect(value: &str) -> Self {
+        match value {
+            "t" | "f" | "T" | "F" => Self::TF,
+            "1" | "0" => Self::OneZero,
+            "Y" | "N" | "y" | "n" => Self::YN,
+            _ => Self::TrueFalse,
+        }
+    }
+
+    fn encode(self, value: bool, original: &str) -> String {
+        let s = match (self, value) {
+            (Self::Auto, _) => {
+                let s = Self::detect(original).encode(value, original);
+                // keeps the case of the one-character flags
+                return if original.chars().any(|c| c.is_ascii_uppercase()) {
+                    s.to_uppercase()
+                } else {
+                    s.to_lowercase()
+                };
+            }
+            (Self::TrueFalse, true) => "TRUE",
+            (Self::TrueFalse, false) => "FALSE",
+            (Self::TF, true) => "t",
+            (Self::TF, false) => "f",
+            (Self::OneZero, true) => "1",
+            (Self::OneZero, false) => "0",
+            (Self::YN, true) => "Y",
+            (Self::YN, false) => "N",
+        };
+        s.to_string()
+    }
+}
+
+impl Default for BooleanTransformer {
+    fn default() -> Self {
+        Self {
+            ratio: 0.5,
+            format: BooleanFormat::default(),
+            consistent: false,
+            key: None,
+            salt: String::new(),
+            pepper: String::new(),
+        }
+    }
+}
+
+impl Eq for BooleanTransformer {}
+
+#[allow(clippy::derived_hash_with_manual_eq)]
+impl Hash for BooleanTransformer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ratio.to_bits().hash(state);
+        self.format.hash(state);
+        self.consistent.hash(state);
+        self.key.hash(state);
+        self.salt.hash(state);
+    }
+}
+
+fn deserialize_ratio<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ratio = f64::deserialize(deserializer)?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "`ratio` of the `boolean` rule must be between 0 and 1 (it is {}, e.g. 0.4 is 40% of TRUE)",
+            ratio
+        )))
+    }
+}
+
+impl BooleanTransformer {
+    fn value(&self, key: &str) -> bool {
+        if self.consistent {
+            let mut bytes = utils::peppered(&self.salt, &self.pepper).into_bytes();
+            bytes.push(0);
+            bytes.extend_from_slice(key.as_bytes());
+            // the top 53 bits as a fraction in [0, 1)
+            let fraction = (utils::fmix64(utils::fnv1a(&bytes)) >> 11) as f64 / (1u64 << 53) as f64;
+            fraction < self.ratio
+        } else {
+            utils::rng().gen_bool(self.ratio)
+        }
+    }
+}
+
+impl Transformer for BooleanTransformer {
+    fn transform(
+        &self,
+        _field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        let key = match (&self.key, ctx) {
+            (Some(column), Some(ctx)) => ctx.prev_value(column).unwrap_or(field_value),
+            _ => field_value,
+        };
+        TransformResult::present(self.format.encode(self.value(key), field_value))
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.pepper = ctx.pepper.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+    use std::collections::HashMap;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn transform(t: &Transformers, value: &str) -> String {
+        t.transform("table.field", value, &None).unwrap().unwrap()
+    }
+
+    #[test]
+    fn ratio() {
+        let t = BooleanTransformer {
+            ratio: 0.0,
+            ..BooleanTransformer::default()
+        };
+        assert_eq!(
+            t.transform("table.field", "t", &None),
+            Ok(Some(String::from("FALSE")))
+        );
+
+        let t = BooleanTransformer {
+            ratio: 1.0,
+            ..BooleanTransformer::default()
+        };
+        assert_eq!(
+            t.transform("table.field", "t", &None),
+            Ok(Some(String::from("TRUE")))
+        );
+
+        let t = transformer("boolean: {ratio: 0.2}");
+        let count = (0..1000).filter(|_| transform(&t, "f") == "TRUE").count();
+        assert!((100..300).contains(&count), "{} of 1000", count);
+    }
+
+    #[test]
+    fn invalid_ratio() {
+        for ratio in ["40", "-0.1", "1.5"] {
+            let config = format!("boolean: {{ratio: {}}}", ratio);
+            let err = serde_yaml::from_str::<Transformers>(&config).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("`ratio` of the `boolean` rule must be between 0 and 1"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let t = transformer("boolean: {ratio: 1, format: Y/N}");
+        assert_eq!(transform(&t, "N"), "Y");
+        let t = transformer("boolean: {ratio: 0, format: 1/0}");
+        assert_eq!(transform(&t, "1"), "0");
+        let t = transformer("boolean: {ratio: 1, format: t/f}");
+        assert_eq!(transform(&t, "FALSE"), "t");
+
+        let t = transformer("boolean: {ratio: 1, format: auto}");
+        assert_eq!(transform(&t, "f"), "t");
+        assert_eq!(transform(&t, "0"), "1");
+        assert_eq!(transform(&t, "N"), "Y");
+        assert_eq!(transform(&t, "n"), "y");
+        assert_eq!(transform(&t, "F"), "T");
+        assert_eq!(transform(&t, "false"), "true");
+        assert_eq!(transform(&t, "FALSE"), "TRUE");
+    }
+
+    #[test]
+    fn consistent() {
+        let t = transformer("boolean: {consistent: true, salt: secret}");
+        let values: Vec<String> = (0..100).map(|i| transform(&t, &i.to_string())).collect();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(&transform(&t, &i.to_string()), value);
+        }
+        let trues = values.iter().filter(|v| *v == "TRUE").count();
+        assert!((20..80).contains(&trues), "{} of 100", trues);
+    }
+
+    #[test]
+    fn consistent_by_key() {
+        let t = transformer("boolean: {consistent: true, key: user_id, format: 1/0}");
+        let mut column_indexes = HashMap::new();
+        column_indexes.insert("user_id".to_string(), 0);
+        column_indexes.insert("opt_in".to_string(), 1);
+
+        let results: Vec<String> = ["0", "1"]
+            .iter()
+            .map(|opt_in| {
+                let row = ["42", opt_in];
+                let ctx = TransformContext::new(&None, Some(&column_indexes), Some(&row), None);
+                t.transform("users.opt_in", opt_in, &Some(ctx))
+                    .unwrap()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], transform(&t, "42"));
+    }
+}
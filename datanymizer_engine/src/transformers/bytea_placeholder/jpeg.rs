@@ -0,0 +1,216 @@
+//! A minimal baseline JPEG encoder: every 8x8 block is filled with a single color (the color of the picture
+//! at the block center), so the blocks have DC coefficients only.
+
+use super::Picture;
+
+/// The quantization step of all coefficients: the quantized DC coefficient of a block is its level-shifted value
+const QUANT_STEP: u8 = 8;
+/// The standard luminance DC table (ITU T.81, K.3): the count of codes by lengths (1-16 bits)
+const DC_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+/// The size of the markers and the tables
+const HEADERS_LEN: usize = 179;
+/// The DC code (up to 6 bits), the DC value (up to 8 bits) and the end of the block (1 bit) for every component
+const MAX_MCU_BITS: usize = 3 * (6 + 8 + 1);
+
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let (width, height) = (picture.width as u16, picture.height as u16);
+    let mut jpeg = vec![0xff, 0xd8];
+
+    // JFIF 1.1, no units, the 1:1 density, no thumbnail
+    segment(&mut jpeg, 0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+
+    let mut quant = vec![0];
+    quant.extend_from_slice(&[QUANT_STEP; 64]);
+    segment(&mut jpeg, 0xdb, &quant);
+
+    let mut frame = vec![8];
+    frame.extend_from_slice(&height.to_be_bytes());
+    frame.extend_from_slice(&width.to_be_bytes());
+    // Y, Cb and Cr without subsampling, with the same quantization table
+    frame.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 0, 3, 0x11, 0]);
+    segment(&mut jpeg, 0xc0, &frame);
+
+    let mut dc_table = vec![0x00];
+    dc_table.extend_from_slice(&DC_BITS);
+    dc_table.extend(0..12);
+    segment(&mut jpeg, 0xc4, &dc_table);
+    // the AC table has the only code: the end of the block (`0`)
+    let mut ac_table = vec![0x10, 1];
+    ac_table.extend_from_slice(&[0; 15]);
+    ac_table.push(0);
+    segment(&mut jpeg, 0xc4, &ac_table);
+
+    // all components with the tables 0, the spectral selection 0-63, no approximation
+    segment(&mut jpeg, 0xda, &[3, 1, 0, 2, 0, 3, 0, 0, 63, 0]);
+
+    let codes = dc_codes();
+    let mut writer = BitWriter::new(jpeg);
+    let mut predictions = [0i32; 3];
+    for block_y in 0..picture.height.div_ceil(8) {
+        for block_x in 0..picture.width.div_ceil(8) {
+            let x = (block_x * 8 + 4).min(picture.width - 1);
+            let y = (block_y * 8 + 4).min(picture.height - 1);
+            for (i, value) in ycbcr(picture.color(x, y)).into_iter().enumerate() {
+                let dc = i32::from(value) - 128;
+                let diff = dc - predictions[i];
+                predictions[i] = dc;
+
+                let category = (32 - diff.unsigned_abs().leading_zeros()) as usize;
+                let (code, len) = codes[category];
+                writer.write(code, len);
+                if category > 0 {
+                    let bits = if diff < 0 { diff - 1 } else { diff };
+                    writer.write(bits as u32 & ((1 << category) - 1), category as u8);
+                }
+                // the end of the block
+                writer.write(0, 1);
+            }
+        }
+    }
+
+    let mut jpeg = writer.finish();
+    jpeg.extend_from_slice(&[0xff, 0xd9]);
+    jpeg
+}
+
+/// The maximum size of an encoded image (every entropy-coded byte can be stuffed)
+pub fn max_len(width: u32, height: u32) -> usize {
+    let mcus = width.div_ceil(8) as usize * height.div_ceil(8) as usize;
+    HEADERS_LEN + 2 * (mcus * MAX_MCU_BITS).div_ceil(8)
+}
+
+fn segment(jpeg: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    jpeg.extend_from_slice(&[0xff, marker]);
+    jpeg.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+    jpeg.extend_from_slice(data);
+}
+
+// The canonical Huffman codes of the DC categories (0-11) with the lengths
+fn dc_codes() -> [(u32, u8); 12] {
+    let mut codes = [(0, 0); 12];
+    let (mut code, mut category) = (0u32, 0);
+    for (i, count) in DC_BITS.iter().enumerate() {
+        for _ in 0..*count {
+            codes[category] = (code, i as u8 + 1);
+            code += 1;
+            category += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b,
+    ]
+    .map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Writes the entropy-coded data: the bits from the most significant, `0x00` after every `0xff` byte
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    len: u8,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            buffer: 0,
+            len: 0,
+        }
+    }
+
+    fn write(&mut self, bits: u32, len: u8) {
+        for i in (0..len).rev() {
+            self.buffer = (self.buffer << 1) | ((bits >> i) & 1);
+            self.len += 1;
+            if self.len == 8 {
+                self.flush_byte();
+            }
+        }
+    }
+
+    fn flush_byte(&mut self) {
+        let byte = self.buffer as u8;
+        self.out.push(byte);
+        if byte == 0xff {
+            self.out.push(0);
+        }
+        self.buffer = 0;
+        self.len = 0;
+    }
+
+    // The last byte is padded with ones
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            let padding = 8 - self.len;
+            self.write((1 << padding) - 1, padding);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picture(width: u32, height: u32) -> Picture {
+        Picture {
+            width,
+            height,
+            background: [240, 240, 240],
+            foreground: [51, 102, 204],
+            pattern: 0x5a5a,
+        }
+    }
+
+    #[test]
+    fn codes() {
+        let codes = dc_codes();
+        assert_eq!(codes[0], (0b00, 2));
+        assert_eq!(codes[1], (0b010, 3));
+        assert_eq!(codes[5], (0b110, 3));
+        assert_eq!(codes[6], (0b1110, 4));
+        assert_eq!(codes[8], (0b11_1110, 6));
+        assert_eq!(codes[11], (0b1_1111_1110, 9));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(ycbcr([0, 0, 0]), [0, 128, 128]);
+        assert_eq!(ycbcr([255, 255, 255]), [255, 128, 128]);
+        assert_eq!(ycbcr([255, 0, 0]), [76, 85, 255]);
+    }
+
+    #[test]
+    fn stuffing() {
+        let mut writer = BitWriter::new(vec![]);
+        writer.write(0xff, 8);
+        writer.write(0b101, 3);
+        assert_eq!(writer.finish(), vec![0xff, 0, 0b1011_1111]);
+    }
+
+    #[test]
+    fn encode() {
+        let jpeg = super::encode(&picture(64, 48));
+        assert_eq!(&jpeg[..4], &[0xff, 0xd8, 0xff, 0xe0]);
+        assert!(jpeg.ends_with(&[0xff, 0xd9]));
+        // the headers before the scan data
+        let scan = jpeg.windows(2).position(|w| w == [0xff, 0xda]).unwrap();
+        assert_eq!(scan + 14, HEADERS_LEN - 2);
+        // the frame dimensions
+        let frame = jpeg.windows(2).position(|w| w == [0xff, 0xc0]).unwrap();
+        assert_eq!(&jpeg[frame + 5..frame + 9], &[0, 48, 0, 64]);
+        assert!(jpeg.len() <= max_len(64, 48));
+
+        // the partial blocks
+        let jpeg = super::encode(&picture(13, 5));
+        assert!(jpeg.len() <= max_len(13, 5));
+    }
+}
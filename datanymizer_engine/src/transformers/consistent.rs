@@ -0,0 +1,164 @@
+use crate::{
+    transformer::{TransformContext, TransformResult, Transformer, TransformerInitContext},
+    utils, Derivation,
+};
+use serde::{Deserialize, Serialize};
+
+/// Makes the inner rule deterministic: its random generator is seeded with the seed derived
+/// from the pepper, the salt and the original value (see `derivation_version`), so the same original values
+/// are replaced with the same values in all columns, tables and runs (with the same pepper, the same salt
+/// and the same inner rule), even in the runs on other machines.
+/// Unlike `cache`, it doesn't store the values, so it is suitable for high-cardinality columns
+/// (e.g., emails that are used as join keys in other systems).
+///
+/// The salt is taken from the rule (`salt`), from the `consistent_salt` config option
+/// or from the `DATANYMIZER_CONSISTENT_SALT` environment variable (it overrides the option).
+/// Without a secret salt the replacements can be matched with the original values by brute force.
+///
+/// The inner rule can't generate unique values.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   email:
+///     consistent:
+///       rule:
+///         email:
+///           kind: Safe
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct ConsistentTransformer<T> {
+    /// Overrides the global salt
+    #[serde(default)]
+    pub salt: Option<String>,
+    pub rule: Box<T>,
+
+    /// The derivation with the salt in use and the run pepper (it is set on initialization)
+    #[serde(skip)]
+    derivation: Derivation,
+}
+
+impl<T> ConsistentTransformer<T> {
+    pub fn new(rule: T) -> Self {
+        Self {
+            salt: None,
+            rule: Box::new(rule),
+            derivation: Derivation::default(),
+        }
+    }
+}
+
+impl<T> Transformer for ConsistentTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        utils::with_seed(self.derivation.seed(field_value), || {
+            self.rule.transform(field_name, field_value, ctx)
+        })
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        let salt = self.salt.as_deref().unwrap_or(&ctx.consistent_salt);
+        self.derivation = Derivation::new(ctx.derivation_version, &ctx.pepper, salt);
+        self.rule.init(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        transformers::{EmailTransformer, FirstNameTransformer, RandomNumberTransformer},
+        Transformers,
+    };
+    use std::collections::HashSet;
+
+    fn consistent(rule: Transformers, salt: &str) -> ConsistentTransformer<Transformers> {
+        let mut t = ConsistentTransformer::new(rule);
+        t.init(&TransformerInitContext {
+            consistent_salt: salt.to_string(),
+            ..TransformerInitContext::default()
+        });
+        t
+    }
+
+    fn transform(t: &ConsistentTransformer<Transformers>, value: &str) -> String {
+        t.transform("field", value, &None).unwrap().unwrap()
+    }
+
+    #[test]
+    fn equal_values() {
+        let email = || Transformers::Email(EmailTransformer::default());
+        // e.g. two columns in different tables
+        let users = consistent(email(), "salt");
+        let orders = consistent(email(), "salt");
+
+        for value in ["alice@example.com", "bob@example.com", ""] {
+            let first = transform(&users, value);
+            assert_eq!(transform(&users, value), first);
+            assert_eq!(transform(&orders, value), first);
+        }
+        assert_ne!(
+            transform(&users, "alice@example.com"),
+            transform(&users, "bob@example.com")
+        );
+    }
+
+    #[test]
+    fn salts() {
+        let name = || Transformers::FirstName(FirstNameTransformer::default());
+        let values: Vec<_> = (0..20).map(|i| format!("name {}", i)).collect();
+        let outputs = |t: &ConsistentTransformer<Transformers>| {
+            values.iter().map(|v| transform(t, v)).collect::<Vec<_>>()
+        };
+
+        let first = consistent(name(), "first");
+        let second = consistent(name(), "second");
+        assert_ne!(outputs(&first), outputs(&second));
+
+        // the rule salt overrides the global one
+        let mut own = ConsistentTransformer::new(name());
+        own.salt = Some("first".to_string());
+        own.init(&TransformerInitContext {
+            consistent_salt: "second".to_string(),
+            ..TransformerInitContext::default()
+        });
+        assert_eq!(outputs(&own), outputs(&first));
+    }
+
+    #[test]
+    fn collisions() {
+        // the full `usize` range
+        let t = consistent(
+            Transformers::RandomNum(RandomNumberTransformer::default()),
+            "salt",
+        );
+
+        let outputs: HashSet<_> = (0..10_000)
+            .map(|i| transform(&t, &format!("user{}@example.com", i)))
+            .collect();
+        assert_eq!(outputs.len(), 10_000);
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = r#"
+            consistent:
+              salt: secret
+              rule:
+                first_name: {}
+        "#;
+        let t: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(matches!(t, Transformers::Consistent(c)
+            if c.salt.as_deref() == Some("secret")
+                && *c.rule == Transformers::FirstName(FirstNameTransformer::default())));
+    }
+}
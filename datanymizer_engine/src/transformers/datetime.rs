@@ -1,4 +1,7 @@
-use crate::transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer};
+use crate::{
+    transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer},
+    utils,
+};
 use chrono::prelude::*;
 use chrono::DateTime;
 use fake::{faker::chrono::raw::*, locales::EN, Fake};
@@ -88,7 +91,8 @@ fn transform(
     ) -> TransformResult {
         let from_dt = DateTime::parse_from_str(&self.from.0, BOUNDS_FORMAT)?.with_timezone(&Utc);
         let to_dt = DateTime::parse_from_str(&self.to.0, BOUNDS_FORMAT)?.with_timezone(&Utc);
-        let between: chrono::DateTime<Utc> = DateTimeBetween(EN, from_dt, to_dt).fake();
+        let between: chrono::DateTime<Utc> =
+            DateTimeBetween(EN, from_dt, to_dt).fake_with_rng(&mut utils::rng());
         let res: String = between.format(&self.format.0).to_string();
 
         TransformResult::present(res)
@@ -0,0 +1,304 @@
+use crate::{
+    transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer},
+    utils,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    f64::consts::PI,
+    hash::{Hash, Hasher},
+};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+/// Adds differential privacy noise to numbers: the aggregates (sums, means) of the column stay usable,
+/// but the individual values are protected. The noise is calibrated by `epsilon` (the privacy budget, smaller is
+/// more private) and `sensitivity` (how much one row can change the aggregate). The noise is fresh for every value.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   salary:
+///     dp_noise:
+///       # `laplace` (default) or `gaussian`
+///       mechanism: laplace
+///       epsilon: 0.5
+///       sensitivity: 1000
+///       # the original values and the results are clamped to this range
+///       clamp: [0, 500000]
+/// ```
+///
+/// The results keep the count of decimal places of the original values (integers are rounded).
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct DpNoiseTransformer {
+    pub mechanism: NoiseMechanism,
+    pub epsilon: f64,
+    pub sensitivity: f64,
+    /// The probability of exceeding the `epsilon` bound (for `gaussian` only)
+    pub delta: f64,
+    /// `[min, max]`
+    pub clamp: Option<[f64; 2]>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseMechanism {
+    #[default]
+    Laplace,
+    Gaussian,
+}
+
+impl Default for DpNoiseTransformer {
+    fn default() -> Self {
+        Self {
+            mechanism: NoiseMechanism::default(),
+            epsilon: 1.0,
+            sensitivity: 1.0,
+            delta: 1e-5,
+            clamp: None,
+        }
+    }
+}
+
+impl Eq for DpNoiseTransformer {}
+
+#[allow(clippy::derived_hash_with_manual_eq)]
+impl Hash for DpNoiseTransformer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mechanism.hash(state);
+        self.epsilon.to_bits().hash(state);
+        self.sensitivity.to_bits().hash(state);
+        self.delta.to_bits().hash(state);
+        self.clamp.map(|c| c.map(f64::to_bits)).hash(state);
+    }
+}
+
+impl DpNoiseTransformer {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.epsilon > 0.0 && self.epsilon.is_finite()) {
+            return Err(format!(
+                "`epsilon` must be positive (it is {})",
+                self.epsilon
+            ));
+        }
+        if !(self.sensitivity > 0.0 && self.sensitivity.is_finite()) {
+            return Err(format!(
+                "`sensitivity` must be positive (it is {})",
+                self.sensitivity
+            ));
+        }
+        if self.mechanism == NoiseMechanism::Gaussian && !(self.delta > 0.0 && self.delta < 1.0) {
+            return Err(format!(
+                "`delta` must be between 0 and 1 (it is {})",
+                self.delta
+            ));
+        }
+        if let Some([min, max]) = self.clamp {
+            if min > max {
+                return Err(format!(
+                    "`clamp` must be [min, max] (it is [{}, {}])",
+                    min, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The standard deviation of the noise
+    pub fn std_dev(&self) -> f64 {
+        match self.mechanism {
+            // the scale is `sensitivity / epsilon`, the variance is `2 * scale^2`
+            NoiseMechanism::Laplace => 2f64.sqrt() * self.sensitivity / self.epsilon,
+            NoiseMechanism::Gaussian => {
+                (2.0 * (1.25 / self.delta).ln()).sqrt() * self.sensitivity / self.epsilon
+            }
+        }
+    }
+
+    fn noise<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self.mechanism {
+            NoiseMechanism::Laplace => {
+                let scale = self.sensitivity / self.epsilon;
+                let u: f64 = rng.gen_range(-0.5..0.5);
+                -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+            }
+            // Box-Muller transform
+            NoiseMechanism::Gaussian => {
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                self.std_dev() * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+            }
+        }
+    }
+
+    fn clamp(&self, value: f64) -> f64 {
+        match self.clamp {
+            Some([min, max]) => value.clamp(min, max),
+            None => value,
+        }
+    }
+}
+
+// The count of decimal places (`None` for the exponent notation)
+fn decimal_places(value: &str) -> Option<usize> {
+    if value.contains(['e', 'E']) {
+        return None;
+    }
+    Some(
+        value
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len()),
+    )
+}
+
+impl Transformer for DpNoiseTransformer {
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if field_value == NULL {
+            return Ok(None);
+        }
+        let value: f64 = match field_value.trim().parse() {
+            Ok(value) => value,
+            Err(_) => {
+                return TransformResult::error(
+                    field_name,
+                    field_value,
+                    &format!("The `dp_noise` rule requires numbers: {}", field_value),
+                )
+            }
+        };
+        if !value.is_finite() {
+            return Ok(None);
+        }
+
+        let result = self.clamp(self.clamp(value) + self.noise(&mut utils::rng()));
+        TransformResult::present(match decimal_places(field_value.trim()) {
+            Some(places) => format!("{:.*}", places, result),
+            None => result.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn transform(t: &Transformers, value: &str) -> String {
+        t.transform("field", value, &None).unwrap().unwrap()
+    }
+
+    fn samples(t: &DpNoiseTransformer, count: usize) -> Vec<f64> {
+        let mut rng = rand::thread_rng();
+        (0..count).map(|_| t.noise(&mut rng)).collect()
+    }
+
+    fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn laplace() {
+        let t = DpNoiseTransformer {
+            epsilon: 0.5,
+            sensitivity: 10.0,
+            ..DpNoiseTransformer::default()
+        };
+        let (mean, std_dev) = mean_and_std_dev(&samples(&t, 100_000));
+        // the scale is 20, the standard deviation is about 28.3
+        assert!(mean.abs() < 1.0);
+        assert!((std_dev - t.std_dev()).abs() / t.std_dev() < 0.05);
+    }
+
+    #[test]
+    fn gaussian() {
+        let t = DpNoiseTransformer {
+            mechanism: NoiseMechanism::Gaussian,
+            epsilon: 0.5,
+            sensitivity: 10.0,
+            ..DpNoiseTransformer::default()
+        };
+        let (mean, std_dev) = mean_and_std_dev(&samples(&t, 100_000));
+        assert!(mean.abs() < 2.0);
+        assert!((std_dev - t.std_dev()).abs() / t.std_dev() < 0.05);
+    }
+
+    #[test]
+    fn fresh_noise() {
+        let t = transformer("dp_noise: {epsilon: 1, sensitivity: 1000}");
+        let results: Vec<_> = (0..10).map(|_| transform(&t, "50000")).collect();
+        assert!(results.iter().any(|r| *r != results[0]));
+    }
+
+    #[test]
+    fn formats() {
+        let t = transformer("dp_noise: {epsilon: 1, sensitivity: 100}");
+        assert!(transform(&t, "42").parse::<i64>().is_ok());
+        let result = transform(&t, "42.50");
+        assert_eq!(decimal_places(&result), Some(2));
+        assert!(transform(&t, "1e+20").parse::<f64>().is_ok());
+
+        assert_eq!(t.transform("field", r"\N", &None).unwrap(), None);
+        assert_eq!(t.transform("field", "NaN", &None).unwrap(), None);
+        assert_eq!(
+            t.transform("field", "abc", &None).unwrap_err().to_string(),
+            "The `dp_noise` rule requires numbers: abc"
+        );
+    }
+
+    #[test]
+    fn clamp() {
+        let t = transformer("dp_noise: {epsilon: 0.1, sensitivity: 1000, clamp: [0, 100]}");
+        for _ in 0..100 {
+            let result: i64 = transform(&t, "1000000").parse().unwrap();
+            assert!((0..=100).contains(&result));
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(DpNoiseTransformer::default().validate(), Ok(()));
+
+        let t = DpNoiseTransformer {
+            epsilon: 0.0,
+            ..DpNoiseTransformer::default()
+        };
+        assert_eq!(
+            t.validate(),
+            Err("`epsilon` must be positive (it is 0)".to_string())
+        );
+
+        let t = DpNoiseTransformer {
+            mechanism: NoiseMechanism::Gaussian,
+            delta: 1.0,
+            ..DpNoiseTransformer::default()
+        };
+        assert_eq!(
+            t.validate(),
+            Err("`delta` must be between 0 and 1 (it is 1)".to_string())
+        );
+
+        let t = DpNoiseTransformer {
+            clamp: Some([10.0, 0.0]),
+            ..DpNoiseTransformer::default()
+        };
+        assert_eq!(
+            t.validate(),
+            Err("`clamp` must be [min, max] (it is [10, 0])".to_string())
+        );
+    }
+}
@@ -7,14 +7,13 @@
 pub mod sql_value;
 
 use crate::{
-    locale::{ExtData, LocaleConfig, Localized, LocalizedFaker},
+    locale::{script, ExtData, LocaleConfig, Localized, LocalizedFaker},
     transformer::{TransformContext, TransformResult, TransformerDefaults, TransformerInitContext},
-    Transformer,
+    utils, Transformer,
 };
 use fake::{
     faker::{
         address::raw::*,
-        boolean::raw::*,
         chrono::raw::*,
         company::raw::*,
         currency::raw::*,
@@ -46,6 +45,18 @@ fn transform_with_faker(&self) -> TransformResult {
         Ok(Some(V::sql_value(self.localized_fake())))
     }
 
+    /// Generates the value in the script of the original value (it is detected by the first characters).
+    /// Falls back to the configured locale if the script is unknown.
+    fn transform_with_script(&self, field_value: &str) -> TransformResult {
+        match script::detect(field_value) {
+            Some(script) => {
+                let value = V::sql_value(self.fake_with_locale(script.locale()));
+                Ok(Some(script.transliterate(&value)))
+            }
+            None => self.transform_with_faker(),
+        }
+    }
+
     fn set_defaults_for_faker(&mut self, defaults: &TransformerDefaults) {
         if self.locale().is_none() {
             self.set_locale(Some(defaults.locale));
@@ -82,13 +93,6 @@ macro_rules! fk_config_example {
         ""
     };
 
-    ( Ratio ) => {
-        concat!(
-            "      # The probability of TRUE value\n",
-            "      ratio: 50\n"
-        )
-    };
-
     ( Count ) => {
         concat!(
             "      # Min count\n",
@@ -97,6 +101,17 @@ macro_rules! fk_config_example {
             "      max: 5\n"
         )
     };
+
+    ( Text ) => {
+        concat!(
+            "      # Generate values in the script of the original values\n",
+            "      preserve_script: false\n"
+        )
+    };
+
+    ( TextCount ) => {
+        concat!(fk_config_example!(Count), fk_config_example!(Text))
+    };
 }
 
 /// This macro defines an entire document comment for a faker-based transformer.
@@ -150,26 +165,38 @@ pub struct $tr {
         }
     };
 
-    ( $tr:ident, Ratio, $doc:expr ) => {
+    ( $tr:ident, Count, $doc:expr ) => {
         #[doc = $doc]
         #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
         #[serde(default)]
         pub struct $tr {
             pub locale: Option<LocaleConfig>,
-            pub ratio: u8,
+            pub min: usize,
+            pub max: usize,
         }
 
         impl Default for $tr {
             fn default() -> Self {
                 Self {
                     locale: None,
-                    ratio: 50,
+                    min: 2,
+                    max: 5,
                 }
             }
         }
     };
 
-    ( $tr:ident, Count, $doc:expr ) => {
+    ( $tr:ident, Text, $doc:expr ) => {
+        #[doc = $doc]
+        #[derive(Default, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
+        #[serde(default)]
+        pub struct $tr {
+            pub locale: Option<LocaleConfig>,
+            pub preserve_script: bool,
+        }
+    };
+
+    ( $tr:ident, TextCount, $doc:expr ) => {
         #[doc = $doc]
         #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
         #[serde(default)]
@@ -177,6 +204,7 @@ pub struct $tr {
             pub locale: Option<LocaleConfig>,
             pub min: usize,
             pub max: usize,
+            pub preserve_script: bool,
         }
 
         impl Default for $tr {
@@ -185,6 +213,7 @@ fn default() -> Self {
                     locale: None,
                     min: 2,
                     max: 5,
+                    preserve_script: false,
                 }
             }
         }
@@ -197,21 +226,43 @@ fn default() -> Self {
 macro_rules! impl_localized_faker {
     ( $fk:ident, $sql:ty, Empty ) => {
         fn fake<L: ExtData>(&self, l: L) -> $sql {
-            $fk(l).fake()
+            $fk(l).fake_with_rng(&mut utils::rng())
         }
     };
 
-    ( $fk:ident, $sql:ty, Ratio ) => {
+    ( $fk:ident, $sql:ty, Count ) => {
         fn fake<L: ExtData>(&self, l: L) -> $sql {
-            $fk(l, self.ratio).fake()
+            $fk(l, self.min..self.max + 1).fake_with_rng(&mut utils::rng())
         }
     };
 
-    ( $fk:ident, $sql:ty, Count ) => {
-        fn fake<L: ExtData>(&self, l: L) -> $sql {
-            $fk(l, self.min..self.max + 1).fake()
+    ( $fk:ident, $sql:ty, Text ) => {
+        impl_localized_faker!($fk, $sql, Empty);
+    };
+
+    ( $fk:ident, $sql:ty, TextCount ) => {
+        impl_localized_faker!($fk, $sql, Count);
+    };
+}
+
+/// This macro transforms a value with a transformer (the `Text` and `TextCount` signatures
+/// support the `preserve_script` option).
+macro_rules! fk_transform {
+    ( $self:ident, $value:ident, Text ) => {
+        fk_transform!($self, $value, TextCount)
+    };
+
+    ( $self:ident, $value:ident, TextCount ) => {
+        if $self.preserve_script {
+            $self.transform_with_script($value)
+        } else {
+            $self.transform_with_faker()
         }
     };
+
+    ( $self:ident, $value:ident, $cfg:ident ) => {
+        $self.transform_with_faker()
+    };
 }
 
 /// This macro defines a single faker-based transformer.
@@ -248,7 +299,7 @@ fn transform(
                 _field_value: &str,
                 _ctx: &Option<TransformContext>,
             ) -> TransformResult {
-                self.transform_with_faker()
+                fk_transform!(self, _field_value, $cfg)
             }
 
             fn init(&mut self, ctx: &TransformerInitContext) {
@@ -302,8 +353,6 @@ macro_rules! define_fk_transformers {
     ("latitude", LatitudeTransformer, Latitude, GenericFloat, Empty),
     "Gets a longitude.",
     ("longitude", LongitudeTransformer, Longitude, GenericFloat, Empty),
-    "Gets a boolean value (TRUE/FALSE), with a given probability.",
-    ("boolean", BooleanTransformer, Boolean, bool, Ratio),
     "Gets a random date (without formatting).",
     ("raw_date", RawDateTransformer, Date, GenericDate, Empty),
     "Gets a random datetime (without formatting).",
@@ -355,31 +404,31 @@ macro_rules! define_fk_transformers {
     "Gets a job title (seniority + field + position).",
     ("job_title", JobTitleTransformer, JobTitle, String, Empty),
     "Gets a \"lorem\" word.",
-    ("word", WordTransformer, Word, String, Empty),
+    ("word", WordTransformer, Word, String, Text),
     "Gets several \"lorem\" words (you can specify a count).",
-    ("words", WordsTransformer, Words, Vec<String>, Count),
+    ("words", WordsTransformer, Words, Vec<String>, TextCount),
     "Gets a \"lorem\" sentence (you can specify a count of words).",
-    ("sentence", SentenceTransformer, Sentence, String, Count),
+    ("sentence", SentenceTransformer, Sentence, String, TextCount),
     "Gets several \"lorem\" sentences (you can specify a count).",
-    ("sentences", SentencesTransformer, Sentences, Vec<String>, Count),
+    ("sentences", SentencesTransformer, Sentences, Vec<String>, TextCount),
     "Gets a \"lorem\" paragraph (you can specify a count of sentences).",
-    ("paragraph", ParagraphTransformer, Paragraph, String, Count),
+    ("paragraph", ParagraphTransformer, Paragraph, String, TextCount),
     "Gets several \"lorem\" paragraphs (you can specify a count).",
-    ("paragraphs", ParagraphsTransformer, Paragraphs, Vec<String>, Count),
+    ("paragraphs", ParagraphsTransformer, Paragraphs, Vec<String>, TextCount),
     "Gets a person name.",
-    ("person_name", PersonNameTransformer, PersonName, String, Empty),
+    ("person_name", PersonNameTransformer, PersonName, String, Text),
     "Gets the first name",
-    ("first_name", FirstNameTransformer, FirstName, String, Empty),
+    ("first_name", FirstNameTransformer, FirstName, String, Text),
     "Gets the last name",
-    ("last_name", LastNameTransformer, LastName, String, Empty),
+    ("last_name", LastNameTransformer, LastName, String, Text),
     "Gets the middle name",
-    ("middle_name", MiddleNameTransformer, MiddleName, String, Empty),
+    ("middle_name", MiddleNameTransformer, MiddleName, String, Text),
     "Gets a name suffix (e.g., `Jr.`)",
     ("name_suffix", NameSuffixTransformer, NameSuffix, String, Empty),
     "Gets a person name title (e.g., `Mr` or `Ms`).",
     ("person_title", PersonTitleTransformer, PersonTitle, String, Empty),
     "Gets a person name with title.",
-    ("person_name_with_title", PersonNameWithTitleTransformer, PersonNameWithTitle, String, Empty),
+    ("person_name_with_title", PersonNameWithTitleTransformer, PersonNameWithTitle, String, Text),
     "Gets a digit symbol (e.g., `2` or `5` for the English locale).",
     ("digit", DigitTransformer, Digit, String, Empty),
     "Gets a local phone number (for a given locale).",
@@ -421,22 +470,6 @@ fn city_name() {
             );
         }
 
-        #[test]
-        fn boolean() {
-            let cfg = r#"
-                locale: EN
-                ratio: 30
-                "#;
-            let t: BooleanTransformer = serde_yaml::from_str(cfg).unwrap();
-            assert_eq!(
-                t,
-                BooleanTransformer {
-                    locale: Some(LocaleConfig::EN),
-                    ratio: 30
-                }
-            );
-        }
-
         #[test]
         fn words() {
             let cfg = r#"
@@ -450,7 +483,8 @@ fn words() {
                 WordsTransformer {
                     locale: Some(LocaleConfig::EN),
                     min: 2,
-                    max: 3
+                    max: 3,
+                    preserve_script: false,
                 }
             );
         }
@@ -464,7 +498,8 @@ fn words_default() {
                 WordsTransformer {
                     locale: None,
                     min: 2,
-                    max: 5
+                    max: 5,
+                    preserve_script: false,
                 }
             );
         }
@@ -479,12 +514,14 @@ fn default_locale() {
         #[test]
         fn default_config() {
             let cfg = "locale: RU";
-            let t: BooleanTransformer = serde_yaml::from_str(cfg).unwrap();
+            let t: WordsTransformer = serde_yaml::from_str(cfg).unwrap();
             assert_eq!(
                 t,
-                BooleanTransformer {
+                WordsTransformer {
                     locale: Some(LocaleConfig::RU),
-                    ratio: 50
+                    min: 2,
+                    max: 5,
+                    preserve_script: false,
                 }
             );
         }
@@ -492,8 +529,8 @@ fn default_config() {
         #[test]
         fn default_all() {
             let cfg = "{}";
-            let t: BooleanTransformer = serde_yaml::from_str(cfg).unwrap();
-            assert_eq!(t, BooleanTransformer::default());
+            let t: WordsTransformer = serde_yaml::from_str(cfg).unwrap();
+            assert_eq!(t, WordsTransformer::default());
         }
     }
 
@@ -525,33 +562,12 @@ fn locale_is_some() {
         }
     }
 
-    #[test]
-    fn boolean() {
-        let t = BooleanTransformer {
-            locale: None,
-            ratio: 0,
-        };
-        assert_eq!(
-            t.transform("table.field", "t", &None),
-            Ok(Some(String::from("FALSE")))
-        );
-
-        let t = BooleanTransformer {
-            locale: None,
-            ratio: 100,
-        };
-        assert_eq!(
-            t.transform("table.field", "t", &None),
-            Ok(Some(String::from("TRUE")))
-        );
-    }
-
     #[test]
     fn city() {
         let t = CityTransformer::default();
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert!(value.len() > 1);
-        assert!(('A'..='Z').contains(&value.chars().next().unwrap()));
+        assert!(value.chars().next().unwrap().is_ascii_uppercase());
     }
 
     #[test]
@@ -559,7 +575,7 @@ fn country_name() {
         let t = CountryNameTransformer::default();
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert!(value.len() > 1);
-        assert!(('A'..='Z').contains(&value.chars().next().unwrap()));
+        assert!(value.chars().next().unwrap().is_ascii_uppercase());
     }
 
     #[test]
@@ -567,7 +583,7 @@ fn first_name() {
         let t = FirstNameTransformer::default();
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert!(value.len() > 1);
-        assert!(('A'..='Z').contains(&value.chars().next().unwrap()));
+        assert!(value.chars().next().unwrap().is_ascii_uppercase());
     }
 
     #[test]
@@ -575,7 +591,7 @@ fn last_name() {
         let t = LastNameTransformer::default();
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert!(value.len() > 1);
-        assert!(('A'..='Z').contains(&value.chars().next().unwrap()));
+        assert!(value.chars().next().unwrap().is_ascii_uppercase());
     }
 
     #[test]
@@ -600,6 +616,7 @@ fn words() {
             locale: None,
             min: 5,
             max: 5,
+            preserve_script: false,
         };
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert_eq!(value.split(" ").count(), 5);
@@ -609,17 +626,116 @@ fn words() {
     fn zh_tw_locale() {
         let t = PersonNameTransformer {
             locale: Some(LocaleConfig::ZH_TW),
+            preserve_script: false,
         };
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
-        assert!(!('A'..='Z').contains(&value.chars().next().unwrap()));
+        assert!(!value.chars().next().unwrap().is_ascii_uppercase());
     }
 
     #[test]
     fn ru_locale() {
         let t = MiddleNameTransformer {
             locale: Some(LocaleConfig::RU),
+            preserve_script: false,
         };
         let value = t.transform("table.field", "t", &None).unwrap().unwrap();
         assert!(('А'..='Я').contains(&value.chars().next().unwrap()));
     }
+
+    mod preserve_script {
+        use super::*;
+        use crate::locale::script::{detect, Script};
+
+        const ORIGINALS: [(&str, Script); 6] = [
+            ("John Smith", Script::Latin),
+            ("Иван Петров", Script::Cyrillic),
+            ("王小明", Script::Han),
+            ("محمد العلي", Script::Arabic),
+            ("Γιώργος Παπαδόπουλος", Script::Greek),
+            ("דוד כהן", Script::Hebrew),
+        ];
+
+        fn assert_preserved<T: Transformer>(t: T, originals: &[(&str, Script)]) {
+            for (original, script) in originals {
+                for _ in 0..10 {
+                    let value = t
+                        .transform("table.field", original, &None)
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(detect(&value), Some(*script), "{} -> {}", original, value);
+                }
+            }
+        }
+
+        #[test]
+        fn names() {
+            assert_preserved(
+                FirstNameTransformer {
+                    locale: None,
+                    preserve_script: true,
+                },
+                &ORIGINALS,
+            );
+            assert_preserved(
+                LastNameTransformer {
+                    locale: None,
+                    preserve_script: true,
+                },
+                &ORIGINALS,
+            );
+            assert_preserved(
+                PersonNameTransformer {
+                    locale: Some(LocaleConfig::RU),
+                    preserve_script: true,
+                },
+                &ORIGINALS,
+            );
+        }
+
+        #[test]
+        fn transliterated_text() {
+            // there are no "lorem" words for these locales, so they are transliterated
+            let originals: Vec<_> = ORIGINALS
+                .iter()
+                .filter(|(_, script)| *script != Script::Han)
+                .cloned()
+                .collect();
+            assert_preserved(
+                WordsTransformer {
+                    preserve_script: true,
+                    ..WordsTransformer::default()
+                },
+                &originals,
+            );
+        }
+
+        #[test]
+        fn unknown_script() {
+            let t = FirstNameTransformer {
+                locale: Some(LocaleConfig::RU),
+                preserve_script: true,
+            };
+            let value = t.transform("table.field", "123", &None).unwrap().unwrap();
+            assert_eq!(detect(&value), Some(Script::Cyrillic));
+        }
+
+        #[test]
+        fn disabled() {
+            let t = FirstNameTransformer::default();
+            let value = t.transform("table.field", "Иван", &None).unwrap().unwrap();
+            assert_eq!(detect(&value), Some(Script::Latin));
+        }
+
+        #[test]
+        fn deserialization() {
+            let t: PersonNameTransformer = serde_yaml::from_str("preserve_script: true").unwrap();
+            assert_eq!(
+                t,
+                PersonNameTransformer {
+                    locale: None,
+                    preserve_script: true
+                }
+            );
+        }
+    }
 }
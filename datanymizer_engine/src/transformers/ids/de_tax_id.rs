@@ -0,0 +1,111 @@
+use super::NationalId;
+use rand::{seq::SliceRandom, Rng};
+
+/// The German tax identification numbers (Steuerliche Identifikationsnummer): 11 digits,
+/// the last one is the check digit (ISO 7064, MOD 11,10).
+///
+/// In the first 10 digits (the first one is not `0`) exactly one digit occurs twice or three times
+/// (the three ones are not all adjacent), the rest of the digits occur once at most.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     de_tax_id:
+///       # the default format is `###########`
+///       format: "## ### ### ###"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DeTaxId;
+
+impl NationalId for DeTaxId {
+    const NAME: &'static str = "de_tax_id";
+    const LENGTH: usize = 11;
+    const DEFAULT_FORMAT: &'static str = "###########";
+
+    fn generate<R: Rng>(rng: &mut R) -> String {
+        let digits = loop {
+            let mut all: Vec<u8> = (0..10).collect();
+            all.shuffle(rng);
+            // the repeated digit (twice or three times) and the once occurring ones
+            let repeats = rng.gen_range(2..=3);
+            let mut digits = vec![all[0]; repeats];
+            digits.extend_from_slice(&all[1..=10 - repeats]);
+            digits.shuffle(rng);
+            if digits[0] != 0 && !digits.windows(3).any(|w| w[0] == w[1] && w[1] == w[2]) {
+                break digits;
+            }
+        };
+
+        let mut id: String = digits.iter().map(|d| char::from(b'0' + d)).collect();
+        id.push(char::from(b'0' + check_digit(&digits)));
+        id
+    }
+}
+
+/// ISO 7064, MOD 11,10
+fn check_digit(digits: &[u8]) -> u8 {
+    let mut product = 10;
+    for d in digits {
+        let mut sum = (d + product) % 10;
+        if sum == 0 {
+            sum = 10;
+        }
+        product = (sum * 2) % 11;
+    }
+    (11 - product) % 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transformer, Transformers};
+
+    fn is_valid(id: &str) -> bool {
+        let digits: Vec<u8> = id.bytes().map(|b| b.wrapping_sub(b'0')).collect();
+        if digits.len() != 11 || digits.iter().any(|d| *d > 9) || digits[0] == 0 {
+            return false;
+        }
+        let mut counts = [0; 10];
+        for d in &digits[..10] {
+            counts[*d as usize] += 1;
+        }
+        let repeated: Vec<_> = counts.iter().filter(|c| **c > 1).collect();
+        repeated.len() == 1
+            && *repeated[0] <= 3
+            && !digits[..10]
+                .windows(3)
+                .any(|w| w[0] == w[1] && w[1] == w[2])
+            && check_digit(&digits[..10]) == digits[10]
+    }
+
+    #[test]
+    fn check() {
+        // the sample of the Federal Central Tax Office
+        assert!(is_valid("86095742719"));
+        assert!(!is_valid("86095742718"));
+        assert!(!is_valid("12345678903"));
+        assert!(!is_valid("06095742719"));
+    }
+
+    #[test]
+    fn valid() {
+        let t: Transformers = serde_yaml::from_str("de_tax_id: {}").unwrap();
+        for _ in 0..1000 {
+            let id = t.transform("users.tax_id", "", &None).unwrap().unwrap();
+            assert!(is_valid(&id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn format() {
+        let t: Transformers =
+            serde_yaml::from_str("de_tax_id: {format: '## ### ### ###'}").unwrap();
+        let id = t.transform("users.tax_id", "", &None).unwrap().unwrap();
+        assert_eq!(id.len(), 14);
+        assert_eq!((&id[2..3], &id[6..7], &id[10..11]), (" ", " ", " "));
+        assert!(is_valid(&id.replace(' ', "")), "{}", id);
+    }
+}
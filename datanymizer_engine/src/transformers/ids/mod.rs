@@ -0,0 +1,169 @@
+//! The national identification and tax numbers. The values are valid for the validation code
+//! of the downstream systems (the check characters and the reserved ranges), but they are random:
+//! they may belong to real persons, so they mustn't be used outside of the test environments.
+//!
+//! Every rule generates the identifiers of its country, they don't depend on the locale.
+
+mod de_tax_id;
+mod nino;
+mod ssn;
+
+pub use de_tax_id::DeTaxId;
+pub use nino::Nino;
+pub use ssn::Ssn;
+
+use crate::{
+    transformer::{TransformContext, UniqTransformer, Uniqueness},
+    utils,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fmt::Debug, hash::Hash, marker::PhantomData};
+
+/// The placeholder of the identifier characters in the formats
+const PLACEHOLDER: char = '#';
+
+/// A kind of the identifiers
+pub trait NationalId: Debug + Clone + Default + PartialEq + Eq + Hash {
+    /// The rule name
+    const NAME: &'static str;
+    /// The characters of an identifier (without separators)
+    const LENGTH: usize;
+    const DEFAULT_FORMAT: &'static str;
+
+    /// A random valid identifier (without separators)
+    fn generate<R: Rng>(rng: &mut R) -> String;
+}
+
+pub type SsnTransformer = IdTransformer<Ssn>;
+pub type NinoTransformer = IdTransformer<Nino>;
+pub type DeTaxIdTransformer = IdTransformer<DeTaxId>;
+
+/// Generates the identifiers of a kind.
+///
+/// `format` places the characters of the identifier: every `#` is replaced with the next character,
+/// the other characters are kept (e.g., `###-##-####` for `123-45-6789`). The format must have
+/// as many `#` as the identifier has characters.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Default)]
+#[serde(try_from = "IdConfig", into = "IdConfig", bound = "")]
+pub struct IdTransformer<T: NationalId> {
+    pub format: Option<String>,
+    pub uniq: Uniqueness,
+    kind: PhantomData<T>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IdConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(default)]
+    uniq: Uniqueness,
+}
+
+impl<T: NationalId> TryFrom<IdConfig> for IdTransformer<T> {
+    type Error = String;
+
+    fn try_from(config: IdConfig) -> Result<Self, Self::Error> {
+        if let Some(format) = &config.format {
+            let placeholders = format.matches(PLACEHOLDER).count();
+            if placeholders != T::LENGTH {
+                return Err(format!(
+                    "the `{}` format `{}` must have {} `{}` (it has {})",
+                    T::NAME,
+                    format,
+                    T::LENGTH,
+                    PLACEHOLDER,
+                    placeholders
+                ));
+            }
+        }
+
+        Ok(Self {
+            format: config.format,
+            uniq: config.uniq,
+            kind: PhantomData,
+        })
+    }
+}
+
+impl<T: NationalId> From<IdTransformer<T>> for IdConfig {
+    fn from(t: IdTransformer<T>) -> Self {
+        Self {
+            format: t.format,
+            uniq: t.uniq,
+        }
+    }
+}
+
+impl<T: NationalId> IdTransformer<T> {
+    fn format(&self) -> &str {
+        self.format.as_deref().unwrap_or(T::DEFAULT_FORMAT)
+    }
+}
+
+impl<T: NationalId> UniqTransformer for IdTransformer<T> {
+    fn do_transform(
+        &self,
+        _field_name: &str,
+        _field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> String {
+        format_id(self.format(), &T::generate(&mut utils::rng()))
+    }
+
+    fn uniq(&self) -> &Uniqueness {
+        &self.uniq
+    }
+}
+
+fn format_id(format: &str, id: &str) -> String {
+    let mut chars = id.chars();
+    format
+        .chars()
+        .map(|c| match c {
+            PLACEHOLDER => chars.next().unwrap_or(PLACEHOLDER),
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transformer, Transformers};
+
+    #[test]
+    fn format() {
+        assert_eq!(format_id("###-##-####", "123456789"), "123-45-6789");
+        assert_eq!(format_id("## ### ### ###", "86095742719"), "86 095 742 719");
+    }
+
+    #[test]
+    fn config() {
+        let t: Transformers =
+            serde_yaml::from_str("ssn: {format: '#########', uniq: true}").unwrap();
+        assert!(t.is_uniq());
+        let value = t.transform("users.ssn", "", &None).unwrap().unwrap();
+        assert!(value.chars().all(|c| c.is_ascii_digit()) && value.len() == 9);
+
+        let error = serde_yaml::from_str::<Transformers>("ssn: {format: '###-##-###'}")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("the `ssn` format `###-##-###` must have 9 `#` (it has 8)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn uniq() {
+        let t: Transformers = serde_yaml::from_str("nino: {uniq: true}").unwrap();
+        let mut values: Vec<_> = (0..500)
+            .map(|_| t.transform("ids.uniq.nino", "", &None).unwrap().unwrap())
+            .collect();
+        values.sort();
+        values.dedup();
+        assert_eq!(values.len(), 500);
+    }
+}
@@ -0,0 +1,89 @@
+use super::NationalId;
+use rand::{seq::SliceRandom, Rng};
+
+/// The first letters of the prefixes (`D`, `F`, `I`, `Q`, `U` and `V` are not used)
+const FIRST_LETTERS: &[u8] = b"ABCEGHJKLMNOPRSTWXYZ";
+/// The second letters of the prefixes (`O` is not used either)
+const SECOND_LETTERS: &[u8] = b"ABCEGHJKLMNPRSTWXYZ";
+/// The prefixes that are not allocated
+const INVALID_PREFIXES: [&str; 7] = ["BG", "GB", "KN", "NK", "NT", "TN", "ZZ"];
+const SUFFIXES: &[u8] = b"ABCD";
+
+/// The UK National Insurance numbers: `QQ123456C` (a prefix of two letters, six digits and a suffix letter
+/// from `A` to `D`, there is no check character).
+///
+/// The generated prefixes have the allowed letters only and are never the administrative
+/// or temporary ones (`BG`, `GB`, `KN`, `NK`, `NT`, `TN`, `ZZ`).
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     nino:
+///       # the default format is `#########`
+///       format: "## ## ## ## #"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Nino;
+
+impl NationalId for Nino {
+    const NAME: &'static str = "nino";
+    const LENGTH: usize = 9;
+    const DEFAULT_FORMAT: &'static str = "#########";
+
+    fn generate<R: Rng>(rng: &mut R) -> String {
+        let letter = |rng: &mut R, letters: &[u8]| *letters.choose(rng).unwrap() as char;
+        let prefix = loop {
+            let prefix: String = [letter(rng, FIRST_LETTERS), letter(rng, SECOND_LETTERS)]
+                .iter()
+                .collect();
+            if !INVALID_PREFIXES.contains(&prefix.as_str()) {
+                break prefix;
+            }
+        };
+        format!(
+            "{}{:06}{}",
+            prefix,
+            rng.gen_range(0..1_000_000),
+            letter(rng, SUFFIXES)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transformer, Transformers};
+    use regex::Regex;
+
+    fn is_valid(nino: &str) -> bool {
+        let re = Regex::new(r"^[A-CEGHJ-PR-TW-Z][A-CEGHJ-NPR-TW-Z]\d{6}[A-D]$").unwrap();
+        re.is_match(nino) && !INVALID_PREFIXES.contains(&&nino[..2])
+    }
+
+    #[test]
+    fn valid() {
+        assert!(!is_valid("QQ123456C"));
+        assert!(is_valid("AB123456C"));
+        assert!(!is_valid("GB123456A"));
+        assert!(!is_valid("AO123456A"));
+        assert!(!is_valid("AB123456E"));
+
+        let t: Transformers = serde_yaml::from_str("nino: {}").unwrap();
+        for _ in 0..1000 {
+            let nino = t.transform("users.nino", "", &None).unwrap().unwrap();
+            assert!(is_valid(&nino), "{}", nino);
+        }
+    }
+
+    #[test]
+    fn format() {
+        let t: Transformers = serde_yaml::from_str("nino: {format: '## ## ## ## #'}").unwrap();
+        let nino = t.transform("users.nino", "", &None).unwrap().unwrap();
+        assert_eq!(nino.len(), 13);
+        assert_eq!(nino.matches(' ').count(), 4);
+        assert!(is_valid(&nino.replace(' ', "")), "{}", nino);
+    }
+}
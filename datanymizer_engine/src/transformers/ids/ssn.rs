@@ -0,0 +1,88 @@
+use super::NationalId;
+use rand::Rng;
+
+/// The US Social Security Numbers: `AAA-GG-SSSS` (there is no check digit).
+///
+/// The area numbers `000`, `666` and `900`-`999`, the group number `00` and the serial number `0000`
+/// are never assigned, so the generated values avoid them.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     ssn:
+///       # the default format is `###-##-####`
+///       format: "#########"
+///       uniq: true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Ssn;
+
+impl NationalId for Ssn {
+    const NAME: &'static str = "ssn";
+    const LENGTH: usize = 9;
+    const DEFAULT_FORMAT: &'static str = "###-##-####";
+
+    fn generate<R: Rng>(rng: &mut R) -> String {
+        let area = loop {
+            let area = rng.gen_range(1..900);
+            if area != 666 {
+                break area;
+            }
+        };
+        format!(
+            "{:03}{:02}{:04}",
+            area,
+            rng.gen_range(1..100),
+            rng.gen_range(1..10000)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Transformer, Transformers};
+
+    fn is_valid(ssn: &str) -> bool {
+        let digits = ssn.replace('-', "");
+        if digits.len() != 9 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return false;
+        }
+        let area: u32 = digits[0..3].parse().unwrap();
+        area != 0 && area != 666 && area < 900 && &digits[3..5] != "00" && &digits[5..] != "0000"
+    }
+
+    #[test]
+    fn valid() {
+        assert!(is_valid("123-45-6789"));
+        assert!(!is_valid("666-45-6789"));
+        assert!(!is_valid("900-45-6789"));
+        assert!(!is_valid("123-00-6789"));
+        assert!(!is_valid("123-45-0000"));
+
+        let t: Transformers = serde_yaml::from_str("ssn: {}").unwrap();
+        for _ in 0..1000 {
+            let ssn = t
+                .transform("users.ssn", "078-05-1120", &None)
+                .unwrap()
+                .unwrap();
+            assert!(is_valid(&ssn), "{}", ssn);
+            assert_eq!(ssn.len(), 11);
+            assert_eq!((&ssn[3..4], &ssn[6..7]), ("-", "-"));
+        }
+    }
+
+    #[test]
+    fn format() {
+        let t: Transformers = serde_yaml::from_str("ssn: {format: '#########'}").unwrap();
+        let ssn = t.transform("users.ssn", "", &None).unwrap().unwrap();
+        assert!(is_valid(&ssn) && ssn.len() == 9, "{}", ssn);
+
+        let t: Transformers = serde_yaml::from_str("ssn: {format: 'SSN ### ## ####'}").unwrap();
+        let ssn = t.transform("users.ssn", "", &None).unwrap().unwrap();
+        assert!(ssn.starts_with("SSN "), "{}", ssn);
+        assert!(is_valid(&ssn[4..].replace(' ', "")), "{}", ssn);
+    }
+}
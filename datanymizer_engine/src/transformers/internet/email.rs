@@ -4,6 +4,11 @@
 };
 use fake::{faker::internet::raw::*, locales::EN, Fake};
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::{Mutex, MutexGuard},
+};
 
 const CHARS: &[char] = &[
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
@@ -12,6 +17,12 @@
 
 const DEFAULT_AFFIX_SEPARATOR: char = '-';
 
+/// Hex digits of the 64-bit hash
+const MAX_HASH_LENGTH: usize = 16;
+
+const SEQUENTIAL_PREFIX: &str = "user";
+const SEQUENTIAL_DIGITS: usize = 6;
+
 /// Transformer generates random emails
 ///
 /// # Examples
@@ -79,6 +90,23 @@
 ///       prefix: 5
 ///       affix_separator: "__"
 /// ```
+///
+/// All emails can be generated in one domain. The local part can be a fake one (`fake_name`, default),
+/// a hash of the salted original value (`hash`, e.g. `3f9a0c5e7b21d4a8@test.example.dev`)
+/// or a sequential one (`sequential`, e.g. `user000001@test.example.dev`).
+/// The `hash` and `sequential` local parts are unique and the same original values get the same emails:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     email:
+///       domain: test.example.dev
+///       local_part: hash
+///       salt: "some secret"
+///       # up to 16 (default)
+///       hash_length: 12
+/// ```
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 #[serde(default)]
 pub struct EmailTransformer {
@@ -87,7 +115,20 @@ pub struct EmailTransformer {
     prefix: Option<Affix>,
     suffix: Option<Affix>,
     affix_separator: String,
+    /// The domain for all emails (instead of the domains of the `kind`)
+    pub domain: Option<String>,
+    pub local_part: LocalPart,
+    /// Salt for the `hash` local part
+    salt: Option<String>,
+    /// Length of the `hash` local part
+    hash_length: usize,
     pub uniq: Uniqueness,
+
+    #[serde(skip)]
+    assigned: Assigned,
+    /// The run pepper (it is set on initialization)
+    #[serde(skip)]
+    pepper: String,
 }
 
 impl Default for EmailTransformer {
@@ -97,30 +138,154 @@ fn default() -> Self {
             prefix: None,
             suffix: None,
             affix_separator: String::from(DEFAULT_AFFIX_SEPARATOR),
+            domain: None,
+            local_part: LocalPart::default(),
+            salt: None,
+            hash_length: MAX_HASH_LENGTH,
             uniq: Uniqueness::default(),
+            assigned: Assigned::default(),
+            pepper: String::new(),
         }
     }
 }
 
+/// How the local part (before `@`) is generated
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalPart {
+    /// A fake user name
+    #[default]
+    FakeName,
+    /// A hash of the salted original value (truncated to `hash_length`)
+    Hash,
+    /// `user000001`, `user000002`, ...
+    Sequential,
+}
+
+/// The assigned local parts for the original values (for the `hash` and `sequential` local parts)
+#[derive(Debug, Default)]
+struct Assigned(Mutex<AssignedParts>);
+
+#[derive(Debug, Default)]
+struct AssignedParts {
+    /// Hashes of the original values by the hash local parts (to resolve the truncation collisions)
+    hashes: HashMap<String, u64>,
+    /// Numbers by the hashes of the original values
+    numbers: HashMap<u64, u64>,
+}
+
+impl Assigned {
+    fn lock(&self) -> MutexGuard<'_, AssignedParts> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// The assigned values are a state, they don't affect the comparison of transformers
+impl Clone for Assigned {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for Assigned {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Assigned {}
+
+impl Hash for Assigned {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
 /// Kind of email
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug, Default)]
 pub enum EmailKind {
     /// Only for free email providers (e.g., "gmail.com", "yahoo.com", "hotmail.com")
     Free,
     /// Only for example domains (e.g., "example.com") - not real email addresses
+    #[default]
     Safe,
 }
 
-impl Default for EmailKind {
-    fn default() -> Self {
-        Self::Safe
-    }
-}
-
 impl EmailTransformer {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// The maximum length of the generated emails, if it is known (with the `domain` and
+    /// the `hash` or `sequential` local part). `rows` is the expected count of values.
+    pub fn max_length(&self, rows: u64) -> Option<usize> {
+        let domain = self.domain.as_ref()?;
+        let local_part = match self.local_part {
+            LocalPart::FakeName => return None,
+            LocalPart::Hash => self.hash_length(),
+            LocalPart::Sequential => {
+                SEQUENTIAL_PREFIX.len() + rows.to_string().len().max(SEQUENTIAL_DIGITS)
+            }
+        };
+        let affixes: usize = [&self.prefix, &self.suffix]
+            .into_iter()
+            .flatten()
+            .map(|affix| {
+                affix
+                    .max_length()
+                    .map(|len| len + self.affix_separator.chars().count())
+            })
+            .sum::<Option<usize>>()?;
+
+        Some(local_part + affixes + 1 + domain.chars().count())
+    }
+
+    fn hash_length(&self) -> usize {
+        self.hash_length.clamp(1, MAX_HASH_LENGTH)
+    }
+
+    fn salted_hash(&self, value: &str, attempt: u64) -> u64 {
+        let salt = self.salt.as_deref().unwrap_or_default();
+        let mut bytes = utils::peppered(salt, &self.pepper).into_bytes();
+        bytes.push(0);
+        bytes.extend_from_slice(value.as_bytes());
+        if attempt > 0 {
+            bytes.extend_from_slice(format!(":{}", attempt).as_bytes());
+        }
+        utils::fnv1a(&bytes)
+    }
+
+    // The truncated hash of another value is rehashed (with the attempt number)
+    fn hash_local_part(&self, value: &str) -> String {
+        let original = self.salted_hash(value, 0);
+        let mut assigned = self.assigned.lock();
+        let mut attempt = 0;
+        loop {
+            let hash = format!("{:016x}", self.salted_hash(value, attempt));
+            let local_part = hash[..self.hash_length()].to_string();
+            match assigned.hashes.get(&local_part) {
+                Some(h) if *h != original => attempt += 1,
+                Some(_) => return local_part,
+                None => {
+                    assigned.hashes.insert(local_part.clone(), original);
+                    return local_part;
+                }
+            }
+        }
+    }
+
+    fn sequential_local_part(&self, value: &str) -> String {
+        let original = self.salted_hash(value, 0);
+        let mut assigned = self.assigned.lock();
+        let next = assigned.numbers.len() as u64 + 1;
+        let number = *assigned.numbers.entry(original).or_insert(next);
+        format!(
+            "{}{:0width$}",
+            SEQUENTIAL_PREFIX,
+            number,
+            width = SEQUENTIAL_DIGITS
+        )
+    }
 }
 
 impl UniqTransformer for EmailTransformer {
@@ -131,9 +296,22 @@ fn do_transform(
         ctx: &Option<TransformContext>,
     ) -> String {
         let mut email: String = match self.kind {
-            EmailKind::Free => FreeEmail(EN).fake(),
-            EmailKind::Safe => SafeEmail(EN).fake(),
+            EmailKind::Free => FreeEmail(EN).fake_with_rng(&mut utils::rng()),
+            EmailKind::Safe => SafeEmail(EN).fake_with_rng(&mut utils::rng()),
         };
+        if self.domain.is_some() || self.local_part != LocalPart::FakeName {
+            let (name, domain) = email.split_once('@').unwrap_or((&email, ""));
+            let local_part = match self.local_part {
+                LocalPart::FakeName => name.to_string(),
+                LocalPart::Hash => self.hash_local_part(field_value),
+                LocalPart::Sequential => self.sequential_local_part(field_value),
+            };
+            email = format!(
+                "{}@{}",
+                local_part,
+                self.domain.as_deref().unwrap_or(domain)
+            );
+        }
 
         if let Some(suffix) = &self.suffix {
             let parts: Vec<&str> = email.splitn(2, '@').collect();
@@ -163,6 +341,7 @@ fn uniq(&self) -> &Uniqueness {
     }
 
     fn init(&mut self, ctx: &TransformerInitContext) {
+        self.pepper = ctx.pepper.clone();
         if let Some(Affix::Custom(tr)) = &mut self.prefix {
             tr.init(ctx);
         }
@@ -182,6 +361,14 @@ enum Affix {
 }
 
 impl Affix {
+    fn max_length(&self) -> Option<usize> {
+        match self {
+            Self::Random(len) => Some(*len),
+            Self::Fixed(str) => Some(str.chars().count()),
+            Self::Custom(_) => None,
+        }
+    }
+
     pub fn generate(
         &self,
         field_name: &str,
@@ -304,6 +491,90 @@ fn other_separator() {
         }
     }
 
+    mod local_part {
+        use super::*;
+
+        fn transformer(config: &str) -> EmailTransformer {
+            serde_yaml::from_str(config).unwrap()
+        }
+
+        fn transform(t: &EmailTransformer, value: &str) -> String {
+            t.transform("field", value, &None).unwrap().unwrap()
+        }
+
+        #[test]
+        fn domain() {
+            let t = transformer("domain: test.example.dev");
+            let email = transform(&t, "orig@domain.com");
+
+            assert!(email.ends_with("@test.example.dev"));
+            assert!(email.len() > 17);
+        }
+
+        #[test]
+        fn hash() {
+            let t = transformer(
+                r#"
+                domain: test.example.dev
+                local_part: hash
+                salt: secret
+                "#,
+            );
+            let email = transform(&t, "orig@domain.com");
+
+            assert_eq!(email.len(), 16 + 17);
+            assert_eq!(transform(&t, "orig@domain.com"), email);
+            assert_ne!(transform(&t, "other@domain.com"), email);
+
+            // the same for another transformer with the same salt
+            let other = transformer("{domain: test.example.dev, local_part: hash, salt: secret}");
+            assert_eq!(transform(&other, "orig@domain.com"), email);
+            let other = transformer("{domain: test.example.dev, local_part: hash, salt: other}");
+            assert_ne!(transform(&other, "orig@domain.com"), email);
+        }
+
+        #[test]
+        fn hash_collisions() {
+            let t = transformer("{domain: d.dev, local_part: hash, hash_length: 1}");
+            let emails: Vec<_> = (0..16)
+                .map(|i| transform(&t, &format!("user{}@domain.com", i)))
+                .collect();
+
+            let mut unique = emails.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 16);
+            assert!(emails.iter().all(|e| e.len() == 7));
+            for (i, email) in emails.iter().enumerate() {
+                assert_eq!(&transform(&t, &format!("user{}@domain.com", i)), email);
+            }
+        }
+
+        #[test]
+        fn sequential() {
+            let t = transformer("{domain: test.example.dev, local_part: sequential}");
+
+            assert_eq!(transform(&t, "a@domain.com"), "user000001@test.example.dev");
+            assert_eq!(transform(&t, "b@domain.com"), "user000002@test.example.dev");
+            assert_eq!(transform(&t, "a@domain.com"), "user000001@test.example.dev");
+        }
+
+        #[test]
+        fn max_length() {
+            let t = transformer("{domain: test.example.dev, local_part: hash, hash_length: 8}");
+            assert_eq!(t.max_length(100), Some(8 + 17));
+
+            let t = transformer("{domain: d.dev, local_part: sequential, prefix: qa, suffix: 3}");
+            assert_eq!(t.max_length(100), Some(3 + 10 + 4 + 6));
+            assert_eq!(t.max_length(12_345_678), Some(3 + 12 + 4 + 6));
+
+            assert_eq!(transformer("domain: d.dev").max_length(100), None);
+            assert_eq!(transformer("local_part: hash").max_length(100), None);
+            let t = transformer("{domain: d.dev, local_part: hash, prefix: {first_name: {}}}");
+            assert_eq!(t.max_length(100), None);
+        }
+    }
+
     #[test]
     fn init() {
         let config = r#"
@@ -319,7 +590,8 @@ fn init() {
         assert_eq!(
             transformer.prefix.unwrap(),
             Affix::Custom(Box::new(Transformers::FirstName(FirstNameTransformer {
-                locale: Some(locale)
+                locale: Some(locale),
+                preserve_script: false,
             })))
         );
     }
@@ -1,4 +1,7 @@
-use crate::transformer::{TransformContext, UniqTransformer, Uniqueness};
+use crate::{
+    transformer::{TransformContext, UniqTransformer, Uniqueness},
+    utils,
+};
 use fake::{faker::internet::raw::*, locales::EN, Fake};
 use serde::{Deserialize, Serialize};
 
@@ -61,8 +64,8 @@ fn do_transform(
         _ctx: &Option<TransformContext>,
     ) -> String {
         match self.kind {
-            Some(IpKind::V6) => IPv6(EN).fake(),
-            _ => IPv4(EN).fake(),
+            Some(IpKind::V6) => IPv6(EN).fake_with_rng(&mut utils::rng()),
+            _ => IPv4(EN).fake_with_rng(&mut utils::rng()),
         }
     }
 
@@ -2,7 +2,7 @@
 pub use email::{EmailKind, EmailTransformer};
 
 mod ip;
-pub use ip::{IpKind, IpTransformer};
+pub use ip::IpTransformer;
 
 mod password;
 pub use password::PasswordTransformer;
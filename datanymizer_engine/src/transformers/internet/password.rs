@@ -1,4 +1,7 @@
-use crate::transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer};
+use crate::{
+    transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer},
+    utils,
+};
 use fake::{faker::internet::raw::*, locales::EN, Fake};
 use serde::{Deserialize, Serialize};
 
@@ -60,7 +63,7 @@ fn transform(
         _ctx: &Option<TransformContext>,
     ) -> TransformResult {
         let range = self.min.0..self.max.0 + 1;
-        let val: String = Password(EN, range).fake();
+        let val: String = Password(EN, range).fake_with_rng(&mut utils::rng());
         TransformResult::present(val)
     }
 }
@@ -78,7 +81,7 @@ fn deserialize_default_transformer() {
             assert_eq!(p_transformer.min, MinValue(8));
             assert_eq!(p_transformer.max, MaxValue(20));
         } else {
-            assert!(false);
+            unreachable!();
         }
     }
 
@@ -95,7 +98,7 @@ fn deserialize_custom_transformer() {
             assert_eq!(p_transformer.min, MinValue(1));
             assert_eq!(p_transformer.max, MaxValue(10));
         } else {
-            assert!(false);
+            unreachable!();
         }
     }
 
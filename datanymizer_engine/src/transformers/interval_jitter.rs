@@ -0,0 +1,548 @@
+use crate::{
+    transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer},
+    utils,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_HOUR: i64 = 3600 * MICROS_PER_SECOND;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+/// The same conversions as in PostgreSQL (e.g. in `justify_interval`)
+const DAYS_PER_MONTH: i64 = 30;
+
+/// Perturbs the values of `interval` columns (e.g. subscription lengths or SLA durations) by a relative
+/// percentage or by a bounded absolute amount. The sign of the intervals is kept.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   duration:
+///     interval_jitter:
+///       # up to ±10% (all parts of the interval are scaled by the same factor)
+///       percent: 10
+///       # round the results to whole `day`s or `hour`s
+///       round: hour
+///       # negative results are replaced with `00:00:00`
+///       non_negative: true
+///   grace_period:
+///     interval_jitter:
+///       # up to ±2 days 12 hours
+///       max: 2 days 12:00:00
+/// ```
+///
+/// The values are parsed from the PostgreSQL output formats (`postgres` and `postgres_verbose`) and the results
+/// are in the `postgres` format (e.g. `1 year 2 mons 3 days 04:05:06.5`).
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(default)]
+pub struct IntervalJitterTransformer {
+    /// The maximum relative change (in percent)
+    pub percent: Option<f64>,
+    /// The maximum absolute change (an interval, e.g. `1 day` or `01:30:00`)
+    pub max: Option<String>,
+    pub round: Option<IntervalRound>,
+    pub non_negative: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum IntervalRound {
+    Day,
+    Hour,
+}
+
+impl Eq for IntervalJitterTransformer {}
+
+#[allow(clippy::derived_hash_with_manual_eq)]
+impl Hash for IntervalJitterTransformer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.percent.map(f64::to_bits).hash(state);
+        self.max.hash(state);
+        self.round.hash(state);
+        self.non_negative.hash(state);
+    }
+}
+
+impl IntervalJitterTransformer {
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.percent, &self.max) {
+            (Some(_), Some(_)) | (None, None) => {
+                Err("exactly one of `percent` and `max` must be specified".to_string())
+            }
+            (Some(percent), None) if !(percent > 0.0 && percent <= 100.0) => Err(format!(
+                "`percent` must be greater than 0 and not greater than 100 (it is {})",
+                percent
+            )),
+            (None, Some(max)) => match max.parse::<Interval>() {
+                Ok(interval) if interval.is_negative() => {
+                    Err(format!("`max` must not be negative (it is `{}`)", max))
+                }
+                Ok(_) => Ok(()),
+                Err(e) => Err(format!("invalid `max`: {}", e)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn jitter<R: Rng>(&self, value: Interval, rng: &mut R) -> Interval {
+        let u: f64 = rng.gen_range(-1.0..=1.0);
+        let mut result = match (self.percent, &self.max) {
+            (Some(percent), _) => value.scale(1.0 + u * percent / 100.0),
+            (None, Some(max)) if !value.is_zero() => {
+                let offset = max.parse::<Interval>().unwrap_or_default().scale(u);
+                let result = value.add(offset);
+                // the offsets that would change the sign are reflected
+                if result.is_negative() != value.is_negative() && !result.is_zero() {
+                    value.add(offset.scale(-1.0))
+                } else {
+                    result
+                }
+            }
+            _ => value,
+        };
+        if let Some(round) = self.round {
+            result = result.round(round);
+        }
+        if self.non_negative && result.is_negative() {
+            result = Interval::default();
+        }
+        result
+    }
+}
+
+impl Transformer for IntervalJitterTransformer {
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if field_value == NULL {
+            return Ok(None);
+        }
+        match field_value.parse::<Interval>() {
+            Ok(value) => {
+                TransformResult::present(self.jitter(value, &mut utils::rng()).to_string())
+            }
+            Err(_) => TransformResult::error(
+                field_name,
+                field_value,
+                &format!(
+                    "The `interval_jitter` rule requires intervals: {}",
+                    field_value
+                ),
+            ),
+        }
+    }
+}
+
+/// An interval as it is stored by PostgreSQL: months, days and microseconds (the parts can have different signs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+    pub months: i64,
+    pub days: i64,
+    pub micros: i64,
+}
+
+impl Interval {
+    /// The approximate length (a month is 30 days, a day is 24 hours)
+    fn approx_micros(&self) -> i128 {
+        (self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+
+    fn is_negative(&self) -> bool {
+        self.approx_micros() < 0
+    }
+
+    fn is_zero(&self) -> bool {
+        self.approx_micros() == 0
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            months: self.months + other.months,
+            days: self.days + other.days,
+            micros: self.micros + other.micros,
+        }
+    }
+
+    /// Multiplies the parts by the factor, the fractional months and days are carried to the days and the time
+    /// (so `1 mon` * 1.1 is `1 mon 3 days`)
+    fn scale(self, factor: f64) -> Self {
+        let months = self.months as f64 * factor;
+        let days = self.days as f64 * factor + months.fract() * DAYS_PER_MONTH as f64;
+        let micros = self.micros as f64 * factor + days.fract() * MICROS_PER_DAY as f64;
+        Self {
+            months: months.trunc() as i64,
+            days: days.trunc() as i64,
+            micros: micros.round() as i64,
+        }
+    }
+
+    fn round(self, round: IntervalRound) -> Self {
+        let round_micros = |unit: i64| (self.micros as f64 / unit as f64).round() as i64;
+        match round {
+            IntervalRound::Day => Self {
+                days: self.days + round_micros(MICROS_PER_DAY),
+                micros: 0,
+                ..self
+            },
+            IntervalRound::Hour => Self {
+                micros: round_micros(MICROS_PER_HOUR) * MICROS_PER_HOUR,
+                ..self
+            },
+        }
+    }
+}
+
+/// Parses the `postgres` (`1 year 2 mons -3 days +04:05:06.5`) and `postgres_verbose`
+/// (`@ 1 year 2 mons 3 days 4 hours 5 mins 6.5 secs ago`) formats
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut interval = Self::default();
+        let mut tokens = s.split_whitespace().peekable();
+        let mut is_empty = true;
+        let mut ago = false;
+        if tokens.peek() == Some(&"@") {
+            tokens.next();
+        }
+
+        while let Some(token) = tokens.next() {
+            if ago {
+                return Err(format!("unexpected `{}` after `ago`", token));
+            }
+            is_empty = false;
+            if token == "ago" {
+                ago = true;
+            } else if token.contains(':') {
+                interval.micros += parse_time(token)?;
+            } else {
+                let unit = tokens
+                    .next()
+                    .ok_or_else(|| format!("no unit after `{}`", token))?;
+                let unit = unit.strip_suffix('s').unwrap_or(unit);
+                match unit {
+                    "year" | "mon" | "month" | "day" => {
+                        let value: i64 = token
+                            .parse()
+                            .map_err(|_| format!("invalid number `{}`", token))?;
+                        match unit {
+                            "year" => interval.months += value * 12,
+                            "day" => interval.days += value,
+                            _ => interval.months += value,
+                        }
+                    }
+                    "hour" => interval.micros += parse_seconds(token)? * 3600,
+                    "min" | "minute" => interval.micros += parse_seconds(token)? * 60,
+                    "sec" | "second" => interval.micros += parse_seconds(token)?,
+                    _ => return Err(format!("unknown unit `{}`", unit)),
+                }
+            }
+        }
+        if is_empty {
+            return Err("empty interval".to_string());
+        }
+
+        if ago {
+            interval = Self {
+                months: -interval.months,
+                days: -interval.days,
+                micros: -interval.micros,
+            };
+        }
+        Ok(interval)
+    }
+}
+
+// `[+-]HH:MM[:SS[.ffffff]]`
+fn parse_time(token: &str) -> Result<i64, String> {
+    let (sign, time) = match token.as_bytes()[0] {
+        b'-' => (-1, &token[1..]),
+        b'+' => (1, &token[1..]),
+        _ => (1, token),
+    };
+    let parts: Vec<_> = time.split(':').collect();
+    let invalid = || format!("invalid time `{}`", token);
+    if parts.len() > 3 || parts.iter().any(|p| p.starts_with(['-', '+'])) {
+        return Err(invalid());
+    }
+    let hours: i64 = parts[0].parse().map_err(|_| invalid())?;
+    let minutes: i64 = parts[1].parse().map_err(|_| invalid())?;
+    let seconds = match parts.get(2) {
+        Some(seconds) => parse_seconds(seconds).map_err(|_| invalid())?,
+        None => 0,
+    };
+    if minutes >= 60 || seconds >= 60 * MICROS_PER_SECOND {
+        return Err(invalid());
+    }
+    Ok(sign * ((hours * 60 + minutes) * 60 * MICROS_PER_SECOND + seconds))
+}
+
+// The microseconds of `[+-]S[.ffffff]` (the fraction is rounded to microseconds)
+fn parse_seconds(token: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid number `{}`", token);
+    let (whole, fraction) = token.split_once('.').unwrap_or((token, ""));
+    let negative = whole.starts_with('-');
+    let whole: i64 = match whole {
+        "" | "-" | "+" => 0,
+        whole => whole.parse().map_err(|_| invalid())?,
+    };
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        (format!("0.{}", fraction)
+            .parse::<f64>()
+            .map_err(|_| invalid())?
+            * MICROS_PER_SECOND as f64)
+            .round() as i64
+    };
+    let micros = whole.abs() * MICROS_PER_SECOND + fraction;
+    Ok(if negative { -micros } else { micros })
+}
+
+/// The `postgres` output format (as in `EncodeInterval` of PostgreSQL)
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut is_zero = true;
+        let mut is_before = false;
+        for (value, unit) in [
+            (self.months / 12, "year"),
+            (self.months % 12, "mon"),
+            (self.days, "day"),
+        ] {
+            if value == 0 {
+                continue;
+            }
+            write!(
+                f,
+                "{}{}{} {}{}",
+                if is_zero { "" } else { " " },
+                if is_before && value > 0 { "+" } else { "" },
+                value,
+                unit,
+                if value != 1 { "s" } else { "" }
+            )?;
+            is_before = value < 0;
+            is_zero = false;
+        }
+
+        if is_zero || self.micros != 0 {
+            let micros = self.micros.unsigned_abs();
+            let seconds = micros / MICROS_PER_SECOND as u64;
+            write!(
+                f,
+                "{}{}{:02}:{:02}:{:02}",
+                if is_zero { "" } else { " " },
+                if self.micros < 0 {
+                    "-"
+                } else if is_before {
+                    "+"
+                } else {
+                    ""
+                },
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            )?;
+            let fraction = micros % MICROS_PER_SECOND as u64;
+            if fraction != 0 {
+                write!(f, ".{}", format!("{:06}", fraction).trim_end_matches('0'))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn transform(t: &Transformers, value: &str) -> String {
+        t.transform("field", value, &None).unwrap().unwrap()
+    }
+
+    fn interval(s: &str) -> Interval {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for value in [
+            "00:00:00",
+            "1 year",
+            "2 years 1 mon",
+            "1 year 2 mons 3 days 04:05:06.789",
+            "-1 years -2 mons +3 days -04:05:06",
+            "1 day -01:00:00",
+            "-1 days +02:03:00",
+            "3 mons -5 days",
+            "-00:00:00.000001",
+            "123:45:00",
+            "-178000000 years",
+            "1 mon 00:00:00.5",
+        ] {
+            assert_eq!(interval(value).to_string(), value);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            interval("1 year 2 mons 3 days 04:05:06.789"),
+            Interval {
+                months: 14,
+                days: 3,
+                micros: 14_706_789_000,
+            }
+        );
+        assert_eq!(
+            interval("@ 1 year 2 mons 3 days 4 hours 5 mins 6.789 secs"),
+            interval("1 year 2 mons 3 days 04:05:06.789")
+        );
+        assert_eq!(
+            interval("@ 1 day 2 hours ago").to_string(),
+            "-1 days -02:00:00"
+        );
+        assert_eq!(interval("-1.5 secs").to_string(), "-00:00:01.5");
+        assert_eq!(interval("01:30").to_string(), "01:30:00");
+
+        for value in [
+            "",
+            "1",
+            "1 week",
+            "1.5 days",
+            "01:60:00",
+            "01:-05:00",
+            "1 day ago 1 hour",
+            "P1D",
+        ] {
+            assert!(value.parse::<Interval>().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn percent() {
+        let t = transformer("interval_jitter: {percent: 10}");
+        let value = interval("1 year 2 mons 3 days 04:05:06");
+        for _ in 0..100 {
+            let result = interval(&transform(&t, "1 year 2 mons 3 days 04:05:06"));
+            let ratio = result.approx_micros() as f64 / value.approx_micros() as f64;
+            assert!((0.9..=1.1).contains(&ratio), "{}", result);
+            assert!(result.months > 0 && result.days >= 0 && result.micros >= 0);
+        }
+
+        // the sign is kept
+        for _ in 0..100 {
+            assert!(interval(&transform(&t, "-3 days -01:00:00")).is_negative());
+        }
+        assert_eq!(transform(&t, "00:00:00"), "00:00:00");
+    }
+
+    #[test]
+    fn max() {
+        let t = transformer("interval_jitter: {max: 1 day}");
+        let value = interval("2 days 12:00:00");
+        for _ in 0..100 {
+            let result = interval(&transform(&t, "2 days 12:00:00"));
+            let change = (result.approx_micros() - value.approx_micros()).abs();
+            assert!(change <= MICROS_PER_DAY as i128, "{}", result);
+        }
+
+        // the offsets are reflected
+        let t = transformer("interval_jitter: {max: 10 days}");
+        for _ in 0..100 {
+            assert!(!interval(&transform(&t, "01:00:00")).is_negative());
+            assert!(interval(&transform(&t, "-01:00:00")).is_negative());
+        }
+    }
+
+    #[test]
+    fn round() {
+        let t = transformer("interval_jitter: {percent: 50, round: day}");
+        for _ in 0..100 {
+            let result = interval(&transform(&t, "1 mon 10 days 05:00:00"));
+            assert_eq!(result.micros, 0, "{}", result);
+        }
+
+        let t = transformer("interval_jitter: {percent: 50, round: hour}");
+        for _ in 0..100 {
+            let result = interval(&transform(&t, "1 day 05:30:00"));
+            assert_eq!(result.micros % MICROS_PER_HOUR, 0, "{}", result);
+        }
+    }
+
+    #[test]
+    fn non_negative() {
+        let t = transformer("interval_jitter: {percent: 10, non_negative: true}");
+        assert_eq!(transform(&t, "-1 days"), "00:00:00");
+        assert!(!interval(&transform(&t, "1 day")).is_negative());
+    }
+
+    #[test]
+    fn formats() {
+        let t = transformer("interval_jitter: {percent: 10}");
+        assert_eq!(t.transform("field", r"\N", &None).unwrap(), None);
+        assert_eq!(
+            t.transform("field", "abc", &None).unwrap_err().to_string(),
+            "The `interval_jitter` rule requires intervals: abc"
+        );
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(interval("1 mon").scale(1.1).to_string(), "1 mon 3 days");
+        assert_eq!(interval("1 day").scale(0.5).to_string(), "12:00:00");
+        assert_eq!(
+            interval("-1 years -1 days").scale(1.5).to_string(),
+            "-1 years -6 mons -1 days -12:00:00"
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let t = |config: &str| serde_yaml::from_str::<IntervalJitterTransformer>(config).unwrap();
+        assert_eq!(t("{percent: 10}").validate(), Ok(()));
+        assert_eq!(t("{max: 1 day 02:00:00}").validate(), Ok(()));
+        assert_eq!(
+            t("{}").validate(),
+            Err("exactly one of `percent` and `max` must be specified".to_string())
+        );
+        assert_eq!(
+            t("{percent: 10, max: 1 day}").validate(),
+            Err("exactly one of `percent` and `max` must be specified".to_string())
+        );
+        assert_eq!(
+            t("{percent: 0}").validate(),
+            Err("`percent` must be greater than 0 and not greater than 100 (it is 0)".to_string())
+        );
+        assert_eq!(
+            t("{max: -1 day}").validate(),
+            Err("`max` must not be negative (it is `-1 day`)".to_string())
+        );
+        assert_eq!(
+            t("{max: 1 week}").validate(),
+            Err("invalid `max`: unknown unit `week`".to_string())
+        );
+    }
+}
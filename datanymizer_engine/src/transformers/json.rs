@@ -0,0 +1,420 @@
+use crate::transformer::{
+    TransformContext, TransformError, TransformResult, Transformer, TransformerInitContext,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+type Transform<'a> = dyn FnMut(&str) -> TransformResult + 'a;
+
+/// Anonymizes the fields of JSON values (the `json` and `jsonb` columns): the inner rule of every path
+/// is applied to the value at the path, the other keys are kept.
+///
+/// The paths are JSON pointers (`/address/street`, `~1` is `/` and `~0` is `~` in the keys), `*` matches
+/// any array item or object key (`/items/*/email`). The rules are applied to the strings, numbers
+/// and booleans at the paths (all of them for objects and arrays), the numbers and booleans keep their
+/// JSON types if the transformed values fit them, `\N` from the inner rule becomes the JSON `null`.
+///
+/// The paths that are missing in a value are skipped (`on_missing: skip`, default) or fail the dump
+/// (`on_missing: error`). NULLs are kept. The keys of the transformed `json` values are sorted
+/// (`jsonb` values have their own order).
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   data:
+///     json:
+///       fields:
+///         - path: /email
+///           rule:
+///             email: {}
+///         - path: /address/street
+///           rule:
+///             street_name: {}
+///         - path: /items/*/email
+///           rule:
+///             email: {}
+///       on_missing: error
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct JsonTransformer<T> {
+    pub fields: Vec<JsonField<T>>,
+    #[serde(default)]
+    pub on_missing: OnMissing,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct JsonField<T> {
+    /// A JSON pointer, `*` matches any array item or object key
+    pub path: String,
+    pub rule: T,
+}
+
+/// What to do with the paths that are missing in a value
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnMissing {
+    /// The path is skipped
+    #[default]
+    Skip,
+    /// Stop dumping with an error
+    Error,
+}
+
+impl<T> JsonTransformer<T> {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fields.is_empty() {
+            return Err("at least one field must be specified".to_string());
+        }
+        for field in &self.fields {
+            if !field.path.starts_with('/') {
+                return Err(format!(
+                    "invalid path `{}` (a JSON pointer is expected, e.g. `/address/street`)",
+                    field.path
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Transformer for JsonTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if field_value == NULL {
+            return Ok(None);
+        }
+
+        let error = |reason: String| TransformError {
+            field_name: field_name.to_string(),
+            field_value: field_value.to_string(),
+            reason,
+        };
+        let mut json: JsonValue = serde_json::from_str(&unescape(field_value))
+            .map_err(|e| error(format!("Invalid JSON: {}", e)))?;
+
+        let mut changed = false;
+        for field in &self.fields {
+            // the uniqueness sets of the paths are separate
+            let rule_name = format!("{}{}", field_name, field.path);
+            let keys = pointer_keys(&field.path);
+            let found = transform_pointer(&mut json, &keys, &mut changed, &mut |value| {
+                field.rule.transform(&rule_name, value, ctx)
+            })?;
+            if !found && self.on_missing == OnMissing::Error {
+                return Err(error(format!(
+                    "The path `{}` is missing in the JSON value",
+                    field.path
+                )));
+            }
+        }
+
+        Ok(changed.then(|| json.to_string()))
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        for field in &mut self.fields {
+            field.rule.init(ctx);
+        }
+    }
+}
+
+// The keys of the JSON pointer (without the leading `/`)
+fn pointer_keys(path: &str) -> Vec<String> {
+    path.split('/')
+        .skip(1)
+        .map(|key| key.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+// Returns `true` if the path is found (in all items and keys of the wildcards)
+fn transform_pointer(
+    value: &mut JsonValue,
+    keys: &[String],
+    changed: &mut bool,
+    transform: &mut Transform,
+) -> Result<bool, TransformError> {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            *changed |= transform_values(value, transform)?;
+            return Ok(true);
+        }
+    };
+
+    match value {
+        JsonValue::Object(map) if key == "*" => {
+            let mut found = true;
+            for item in map.values_mut() {
+                found &= transform_pointer(item, rest, changed, transform)?;
+            }
+            Ok(found)
+        }
+        JsonValue::Object(map) => match map.get_mut(key) {
+            Some(item) => transform_pointer(item, rest, changed, transform),
+            None => Ok(false),
+        },
+        JsonValue::Array(items) if key == "*" => {
+            let mut found = true;
+            for item in items {
+                found &= transform_pointer(item, rest, changed, transform)?;
+            }
+            Ok(found)
+        }
+        JsonValue::Array(items) => match key.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+            Some(item) => transform_pointer(item, rest, changed, transform),
+            None => Ok(false),
+        },
+        _ => Ok(false),
+    }
+}
+
+// The strings, numbers and booleans keep their JSON types if the transformed values fit them
+pub(crate) fn transform_values(
+    value: &mut JsonValue,
+    transform: &mut Transform,
+) -> Result<bool, TransformError> {
+    let transformed = match value {
+        JsonValue::Null => return Ok(false),
+        JsonValue::Object(map) => {
+            let mut changed = false;
+            for item in map.values_mut() {
+                changed |= transform_values(item, transform)?;
+            }
+            return Ok(changed);
+        }
+        JsonValue::Array(items) => {
+            let mut changed = false;
+            for item in items {
+                changed |= transform_values(item, transform)?;
+            }
+            return Ok(changed);
+        }
+        JsonValue::String(s) => transform(s)?,
+        JsonValue::Number(_) | JsonValue::Bool(_) => transform(&value.to_string())?,
+    };
+
+    match transformed {
+        Some(t) if t == NULL => *value = JsonValue::Null,
+        Some(t) => {
+            *value = match serde_json::from_str::<JsonValue>(&t) {
+                Ok(JsonValue::Number(n)) if value.is_number() => JsonValue::Number(n),
+                Ok(JsonValue::Bool(b)) if value.is_boolean() => JsonValue::Bool(b),
+                _ => JsonValue::String(t),
+            }
+        }
+        None => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+// The values are in the COPY text format: the backslashes of the JSON escapes are escaped too
+// (the server doesn't write the octal and hexadecimal sequences)
+fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => result.push('\x08'),
+            Some('f') => result.push('\x0C'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('v') => result.push('\x0B'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+    use serde_json::json;
+
+    fn json_rule(config: &str) -> Transformers {
+        let mut t: Transformers = serde_yaml::from_str(config).unwrap();
+        t.init(&TransformerInitContext::default());
+        t
+    }
+
+    fn transform(t: &Transformers, value: &str) -> JsonValue {
+        let transformed = t.transform("profiles.data", value, &None).unwrap().unwrap();
+        serde_json::from_str(&transformed).unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        let t = json_rule(
+            r#"
+            json:
+              fields:
+                - path: /email
+                  rule:
+                    template:
+                      format: "user@example.com"
+                - path: /address/street
+                  rule:
+                    template:
+                      format: "Main St"
+                - path: /age
+                  rule:
+                    template:
+                      format: "42"
+                - path: /phone
+                  rule:
+                    template:
+                      format: '\N'
+            "#,
+        );
+        let value = r#"{"email": "john@corp.com", "address": {"street": "Elm St", "zip": "12345"}, "age": 30, "phone": "555", "plan": "pro"}"#;
+        assert_eq!(
+            transform(&t, value),
+            json!({
+                "email": "user@example.com",
+                "address": {"street": "Main St", "zip": "12345"},
+                "age": 42,
+                "phone": null,
+                "plan": "pro",
+            })
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        let t = json_rule(
+            r#"
+            json:
+              fields:
+                - path: /items/*/email
+                  rule:
+                    template:
+                      format: "user@example.com"
+                - path: /contacts/*
+                  rule:
+                    redact:
+                      hash_suffix_len: 0
+            "#,
+        );
+        let value = r#"{"items": [{"email": "a@corp.com", "id": 1}, {"email": "b@corp.com", "id": 2}], "contacts": {"home": "555", "work": "556"}}"#;
+        assert_eq!(
+            transform(&t, value),
+            json!({
+                "items": [
+                    {"email": "user@example.com", "id": 1},
+                    {"email": "user@example.com", "id": 2},
+                ],
+                "contacts": {"home": "[REDACTED]", "work": "[REDACTED]"},
+            })
+        );
+    }
+
+    #[test]
+    fn missing_paths() {
+        let config = r#"
+            json:
+              fields:
+                - path: /items/*/email
+                  rule:
+                    template:
+                      format: "user@example.com"
+              on_missing: {}
+            "#;
+        let value = r#"{"items": [{"email": "a@corp.com"}, {"id": 2}]}"#;
+
+        let t = json_rule(&config.replace("{}", "skip"));
+        assert_eq!(
+            transform(&t, value),
+            json!({"items": [{"email": "user@example.com"}, {"id": 2}]})
+        );
+        // nothing is changed
+        assert_eq!(
+            t.transform("profiles.data", r#"{"id": 1}"#, &None),
+            Ok(None)
+        );
+
+        let t = json_rule(&config.replace("{}", "error"));
+        let error = t.transform("profiles.data", value, &None).unwrap_err();
+        assert_eq!(
+            error.reason,
+            "The path `/items/*/email` is missing in the JSON value"
+        );
+        assert_eq!(error.field_name, "profiles.data");
+    }
+
+    #[test]
+    fn null_and_invalid_values() {
+        let t = json_rule("{json: {fields: [{path: /email, rule: {email: {}}}]}}");
+        assert_eq!(t.transform("profiles.data", NULL, &None), Ok(None));
+
+        let error = t
+            .transform("profiles.data", "{\"email\": ", &None)
+            .unwrap_err();
+        assert!(error.reason.starts_with("Invalid JSON: "));
+    }
+
+    #[test]
+    fn copy_escapes() {
+        let t = json_rule(
+            r#"
+            json:
+              fields:
+                - path: /a~1b
+                  rule:
+                    template:
+                      format: "x"
+            "#,
+        );
+        // `{"a/b": "1", "note": "line\nquote\""}` in the COPY text format
+        let value = r#"{"a/b": "1", "note": "line\\nquote\\""}"#;
+        assert_eq!(
+            transform(&t, value),
+            json!({"a/b": "x", "note": "line\nquote\""})
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let t = |config: &str| {
+            serde_yaml::from_str::<JsonTransformer<Transformers>>(config)
+                .unwrap()
+                .validate()
+        };
+        assert_eq!(t("{fields: [{path: /email, rule: {email: {}}}]}"), Ok(()));
+        assert_eq!(
+            t("{fields: []}"),
+            Err("at least one field must be specified".to_string())
+        );
+        assert_eq!(
+            t("{fields: [{path: email, rule: {email: {}}}]}"),
+            Err(
+                "invalid path `email` (a JSON pointer is expected, e.g. `/address/street`)"
+                    .to_string()
+            )
+        );
+    }
+}
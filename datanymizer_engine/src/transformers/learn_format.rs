@@ -0,0 +1,582 @@
+use super::ScrambleTransformer;
+use crate::{
+    transformer::{
+        TransformContext, TransformResult, TransformResultHelper, Transformer,
+        TransformerInitContext, Uniqueness,
+    },
+    uniq_collector, utils, Derivation,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::{Arc, RwLock},
+};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+const DIGITS: [char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+/// Learns the format of the column from the original values and generates fresh values of the same format.
+/// At the start of the dump up to `sample_size` non-NULL values are sampled, and the character-class
+/// template is inferred: the separators are kept literally, the digit runs and the letter runs
+/// (with their case and lengths) are generated, the runs that are the same in all samples are kept too.
+/// E.g., the samples `DE-2023-XK-00412`, `DE-2021-BA-10077`, ... give the template `DE-[0-9]{4}-[A-Z]{2}-[0-9]{5}`.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   policy_number:
+///     learn_format:
+///       sample_size: 500
+///       uniq: true
+/// ```
+///
+/// When less than `min_samples` values are sampled or less than `min_agreement` percent of them
+/// have the same structure, the format isn't learned: the `fallback` rule is used (`scramble` by default)
+/// and the dumper warns about it.
+///
+/// ```yaml
+/// #...
+/// rules:
+///   reference:
+///     learn_format:
+///       min_samples: 20
+///       min_agreement: 95
+///       fallback:
+///         redact: {}
+/// ```
+///
+/// With `consistent: true` the same original values get the same results (keyed by `salt`).
+/// The learned templates are recorded in the dump manifest.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+#[serde(default)]
+pub struct LearnFormatTransformer<T> {
+    /// The max count of the sampled values
+    pub sample_size: u64,
+    /// The min count of the sampled values to learn the format
+    pub min_samples: u64,
+    /// The min share (in percent) of the sampled values with the same structure
+    pub min_agreement: u8,
+    pub uniq: Uniqueness,
+    /// The same original values get the same results
+    pub consistent: bool,
+    /// The key of the `consistent` results
+    pub salt: String,
+    /// The rule for the columns whose format isn't learned (`scramble` by default)
+    pub fallback: Option<Box<T>>,
+
+    #[serde(skip)]
+    default_fallback: ScrambleTransformer,
+    /// The derivation of the `consistent` results (it is set on initialization)
+    #[serde(skip)]
+    derivation: Derivation,
+    #[serde(skip)]
+    learned: Learned,
+}
+
+impl<T> Default for LearnFormatTransformer<T> {
+    fn default() -> Self {
+        Self {
+            sample_size: 1000,
+            min_samples: 10,
+            min_agreement: 90,
+            uniq: Uniqueness::default(),
+            consistent: false,
+            salt: String::new(),
+            fallback: None,
+            default_fallback: ScrambleTransformer::default(),
+            derivation: Derivation::default(),
+            learned: Learned::default(),
+        }
+    }
+}
+
+/// The result of the learning (it is recorded in the dump manifest)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LearnedFormat {
+    /// The template as a regular expression (`None` if the format isn't learned)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// The count of the sampled values
+    pub samples: usize,
+    /// The count of the sampled values that match the template
+    pub matched: usize,
+    /// Why the fallback rule is used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+}
+
+/// The learned template, it is shared by the clones of the rule (the dumper learns the format
+/// on the configured rule, the engine transforms the values with its clone)
+#[derive(Debug, Default)]
+struct Learned(Arc<RwLock<Option<Learning>>>);
+
+#[derive(Debug)]
+struct Learning {
+    template: Option<FormatTemplate>,
+    format: LearnedFormat,
+}
+
+impl Clone for Learned {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+// The learned template is a state, it doesn't affect the comparison of transformers
+impl PartialEq for Learned {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Learned {}
+
+impl Hash for Learned {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// The structure of a value: the runs of ASCII digits, the runs of ASCII letters and the other characters
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum Kind {
+    Digits,
+    Letters,
+    Other(char),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Case {
+    Upper,
+    Lower,
+    Mixed,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum Part {
+    Literal(String),
+    Digits { min: usize, max: usize },
+    Letters { case: Case, min: usize, max: usize },
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct FormatTemplate(Vec<Part>);
+
+fn tokenize(value: &str) -> Vec<(Kind, &str)> {
+    let mut tokens: Vec<(Kind, &str)> = vec![];
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        let kind = if c.is_ascii_digit() {
+            Kind::Digits
+        } else if c.is_ascii_alphabetic() {
+            Kind::Letters
+        } else {
+            Kind::Other(c)
+        };
+        match tokens.last_mut() {
+            Some((last, run)) if *last == kind && kind != Kind::Other(c) => {
+                *run = &value[start..i + c.len_utf8()];
+            }
+            _ => {
+                start = i;
+                tokens.push((kind, &value[i..i + c.len_utf8()]));
+            }
+        }
+    }
+    tokens
+}
+
+fn run_case(run: &str) -> Case {
+    if run.chars().all(|c| c.is_ascii_uppercase()) {
+        Case::Upper
+    } else if run.chars().all(|c| c.is_ascii_lowercase()) {
+        Case::Lower
+    } else {
+        Case::Mixed
+    }
+}
+
+fn length_range(runs: &[&str]) -> (usize, usize) {
+    let lengths = runs.iter().map(|r| r.len());
+    (
+        lengths.clone().min().unwrap_or_default(),
+        lengths.max().unwrap_or_default(),
+    )
+}
+
+impl FormatTemplate {
+    /// Infers the template from the values of the same structure
+    fn infer(values: &[Vec<(Kind, &str)>]) -> Self {
+        let len = values.first().map_or(0, Vec::len);
+        let parts = (0..len)
+            .map(|i| {
+                let runs: Vec<&str> = values.iter().map(|v| v[i].1).collect();
+                if runs.iter().all(|r| *r == runs[0]) {
+                    return Part::Literal(runs[0].to_string());
+                }
+                let (min, max) = length_range(&runs);
+                match values[0][i].0 {
+                    Kind::Digits => Part::Digits { min, max },
+                    _ => {
+                        let case = runs
+                            .iter()
+                            .map(|r| run_case(r))
+                            .reduce(|a, b| if a == b { a } else { Case::Mixed })
+                            .unwrap_or(Case::Mixed);
+                        Part::Letters { case, min, max }
+                    }
+                }
+            })
+            .collect();
+        Self(parts)
+    }
+
+    fn generate(&self) -> String {
+        let mut rng = utils::rng();
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Literal(s) => s.clone(),
+                Part::Digits { min, max } => utils::rnd_chars(rng.gen_range(*min..=*max), &DIGITS),
+                Part::Letters { case, min, max } => {
+                    let chars: Vec<char> = match case {
+                        Case::Upper => ('A'..='Z').collect(),
+                        Case::Lower => ('a'..='z').collect(),
+                        Case::Mixed => ('A'..='Z').chain('a'..='z').collect(),
+                    };
+                    utils::rnd_chars(rng.gen_range(*min..=*max), &chars)
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for FormatTemplate {
+    /// The template as a regular expression
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let quantifier = |min: usize, max: usize| {
+            if min == max {
+                format!("{{{}}}", min)
+            } else {
+                format!("{{{},{}}}", min, max)
+            }
+        };
+        for part in &self.0 {
+            match part {
+                Part::Literal(s) => write!(f, "{}", regex::escape(s))?,
+                Part::Digits { min, max } => write!(f, "[0-9]{}", quantifier(*min, *max))?,
+                Part::Letters { case, min, max } => {
+                    let class = match case {
+                        Case::Upper => "[A-Z]",
+                        Case::Lower => "[a-z]",
+                        Case::Mixed => "[A-Za-z]",
+                    };
+                    write!(f, "{}{}", class, quantifier(*min, *max))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> LearnFormatTransformer<T> {
+    /// Learns the format from the sampled (non-NULL) values, the rule uses the result from now on
+    pub fn learn<S: AsRef<str>>(&self, samples: &[S]) -> LearnedFormat {
+        let values: Vec<Vec<(Kind, &str)>> = samples.iter().map(|s| tokenize(s.as_ref())).collect();
+
+        let mut counts: HashMap<Vec<Kind>, usize> = HashMap::new();
+        let mut shapes: Vec<Vec<Kind>> = vec![];
+        for value in &values {
+            let shape: Vec<Kind> = value.iter().map(|(kind, _)| *kind).collect();
+            let count = counts.entry(shape.clone()).or_default();
+            if *count == 0 {
+                shapes.push(shape);
+            }
+            *count += 1;
+        }
+        // the first shape wins the ties
+        let dominant = shapes
+            .into_iter()
+            .fold(None, |best: Option<(Vec<Kind>, usize)>, shape| {
+                let count = counts[&shape];
+                match best {
+                    Some((_, best_count)) if best_count >= count => best,
+                    _ => Some((shape, count)),
+                }
+            });
+
+        let samples = values.len();
+        let (template, matched) = match dominant {
+            Some((shape, matched)) => {
+                let matching: Vec<_> = values
+                    .into_iter()
+                    .filter(|v| {
+                        v.len() == shape.len()
+                            && v.iter().map(|(k, _)| *k).eq(shape.iter().copied())
+                    })
+                    .collect();
+                (Some(FormatTemplate::infer(&matching)), matched)
+            }
+            None => (None, 0),
+        };
+
+        let fallback = if (samples as u64) < self.min_samples {
+            Some(format!(
+                "{} values are sampled (at least {} are required)",
+                samples, self.min_samples
+            ))
+        } else if matched * 100 < usize::from(self.min_agreement) * samples {
+            Some(format!(
+                "{} of {} sampled values have the same format ({}% are required)",
+                matched, samples, self.min_agreement
+            ))
+        } else {
+            None
+        };
+        let template = if fallback.is_some() { None } else { template };
+
+        let format = LearnedFormat {
+            template: template.as_ref().map(ToString::to_string),
+            samples,
+            matched,
+            fallback,
+        };
+        *self.learned.0.write().unwrap_or_else(|e| e.into_inner()) = Some(Learning {
+            template,
+            format: format.clone(),
+        });
+        format
+    }
+
+    /// The result of the learning (`None` if the format isn't learned yet)
+    pub fn learned(&self) -> Option<LearnedFormat> {
+        self.learned
+            .0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|l| l.format.clone())
+    }
+
+    fn generate(&self, template: &FormatTemplate, field_value: &str, attempt: i64) -> String {
+        if self.consistent {
+            let value = if attempt == 0 {
+                field_value.to_string()
+            } else {
+                format!("{}#{}", field_value, attempt)
+            };
+            utils::with_seed(self.derivation.seed(&value), || template.generate())
+        } else {
+            template.generate()
+        }
+    }
+
+    fn try_count(&self) -> i64 {
+        self.uniq.try_count.unwrap_or(3)
+    }
+}
+
+impl<T> Transformer for LearnFormatTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if field_value == NULL {
+            return TransformResult::present(NULL);
+        }
+
+        let learning = self.learned.0.read().unwrap_or_else(|e| e.into_inner());
+        let template = match learning.as_ref().and_then(|l| l.template.as_ref()) {
+            Some(template) => template,
+            None => {
+                return match &self.fallback {
+                    Some(fallback) => fallback.transform(field_name, field_value, ctx),
+                    None => self
+                        .default_fallback
+                        .transform(field_name, field_value, ctx),
+                }
+            }
+        };
+
+        if !self.uniq.required {
+            return TransformResult::present(self.generate(template, field_value, 0));
+        }
+        for attempt in 0..self.try_count() {
+            let value = self.generate(template, field_value, attempt);
+            if uniq_collector::add_to_collector(field_name, &value) {
+                return TransformResult::present(value);
+            }
+        }
+        TransformResult::error(
+            field_name,
+            field_value,
+            &format!(
+                "field: `{}` with retry limit: `{}` exceeded",
+                field_name,
+                self.try_count()
+            ),
+        )
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.derivation = Derivation::new(ctx.derivation_version, &ctx.pepper, &self.salt);
+        // the rule isn't shared with the other rules of the same config
+        self.learned = Learned::default();
+        self.default_fallback = ScrambleTransformer::default();
+        self.default_fallback.consistent = self.consistent;
+        self.default_fallback.salt = self.salt.clone();
+        self.default_fallback.init(ctx);
+        if let Some(fallback) = &mut self.fallback {
+            fallback.init(ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transformer::TransformerDefaults, Transformers};
+    use regex::Regex;
+
+    fn transformer(config: &str) -> LearnFormatTransformer<Transformers> {
+        let mut t: LearnFormatTransformer<Transformers> = serde_yaml::from_str(config).unwrap();
+        t.init(&TransformerInitContext::from_defaults(
+            TransformerDefaults::default(),
+        ));
+        t
+    }
+
+    fn transform(t: &LearnFormatTransformer<Transformers>, value: &str) -> String {
+        t.transform("table.field", value, &None).unwrap().unwrap()
+    }
+
+    fn policies() -> Vec<String> {
+        (0..20)
+            .map(|i| {
+                format!(
+                    "DE-20{:02}-{}{}-{:05}",
+                    i,
+                    (b'A' + i as u8) as char,
+                    (b'Z' - i as u8) as char,
+                    i * 4721
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn infer() {
+        let t = transformer("{}");
+        let format = t.learn(&policies());
+        assert_eq!(
+            format,
+            LearnedFormat {
+                template: Some("DE\\-[0-9]{4}\\-[A-Z]{2}\\-[0-9]{5}".to_string()),
+                samples: 20,
+                matched: 20,
+                fallback: None,
+            }
+        );
+        assert_eq!(t.learned(), Some(format));
+
+        let t = transformer("{min_samples: 3, min_agreement: 75}");
+        let format = t.learn(&["ab12", "Abc1", "x9", "q07-1"]);
+        assert_eq!(format.template.unwrap(), "[A-Za-z]{1,3}[0-9]{1,2}");
+        assert_eq!(format.matched, 3);
+    }
+
+    #[test]
+    fn fallback() {
+        let t = transformer("{}");
+        assert_eq!(t.learned(), None);
+        let result = transform(&t, "AB-123");
+        assert_eq!(result.len(), 6);
+        assert!(result.starts_with(|c: char| c.is_ascii_uppercase()));
+
+        let format = t.learn(&policies()[..5]);
+        assert_eq!(format.template, None);
+        assert_eq!(
+            format.fallback.unwrap(),
+            "5 values are sampled (at least 10 are required)"
+        );
+        assert_eq!(&transform(&t, "AB-123")[2..3], "-");
+
+        let mut samples = policies();
+        samples.extend(["x", "y-1", "1.5"].iter().map(|s| s.to_string()));
+        let format = t.learn(&samples);
+        assert_eq!(format.template, None);
+        assert_eq!(format.matched, 20);
+        assert_eq!(
+            format.fallback.unwrap(),
+            "20 of 23 sampled values have the same format (90% are required)"
+        );
+
+        let t = transformer("{fallback: {redact: {}}}");
+        t.learn(&["a"]);
+        assert!(transform(&t, "secret").starts_with("[REDACTED"));
+    }
+
+    #[test]
+    fn generate() {
+        let t = transformer("{}");
+        let format = t.learn(&policies());
+        let re = Regex::new(&format!("^{}$", format.template.unwrap())).unwrap();
+        for _ in 0..20 {
+            let value = transform(&t, "DE-2023-XK-00412");
+            assert!(re.is_match(&value), "{}", value);
+        }
+        assert_eq!(transform(&t, NULL), NULL);
+
+        // the clones share the learned template
+        let clone = transformer("{}");
+        let shared = clone.clone();
+        clone.learn(&policies());
+        assert!(re.is_match(&transform(&shared, "DE-2023-XK-00412")));
+    }
+
+    #[test]
+    fn consistent() {
+        let t = transformer("{consistent: true, salt: secret}");
+        let other = transformer("{consistent: true, salt: other}");
+        t.learn(&policies());
+        other.learn(&policies());
+
+        let value = "DE-2023-XK-00412";
+        assert_eq!(transform(&t, value), transform(&t, value));
+        assert_ne!(transform(&t, value), transform(&t, "DE-2023-XK-00413"));
+        assert_ne!(transform(&t, value), transform(&other, value));
+    }
+
+    #[test]
+    fn uniq() {
+        let t = transformer("{uniq: {required: true, try_count: 50}, min_samples: 2}");
+        t.learn(&["1", "2", "3"]);
+        let mut values: Vec<String> = (0..10)
+            .filter_map(|_| t.transform("learn_format.uniq", "5", &None).unwrap())
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+        assert_eq!(values.len(), 10);
+
+        // all the digits are used
+        let err = (0..5)
+            .map(|_| t.transform("learn_format.uniq", "5", &None))
+            .find(Result::is_err)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err.reason,
+            "field: `learn_format.uniq` with retry limit: `50` exceeded"
+        );
+    }
+}
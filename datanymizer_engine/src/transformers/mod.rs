@@ -1,4 +1,7 @@
-use super::transformer::{TransformContext, TransformResult, Transformer, TransformerInitContext};
+use super::transformer::{
+    RuleCategory, TransformContext, TransformResult, Transformer, TransformerInitContext,
+    TypeClass, UniqueAgainst,
+};
 use serde::{Deserialize, Serialize};
 
 mod none;
@@ -13,12 +16,50 @@
 mod pipeline;
 pub use pipeline::PipelineTransformer;
 
+mod cache;
+pub use cache::CacheTransformer;
+
+mod consistent;
+pub use consistent::ConsistentTransformer;
+
+mod normalize_empty;
+pub use normalize_empty::{NormalizeEmptyTransformer, OnNull};
+
+mod skip_if;
+pub(crate) use skip_if::register_kept;
+pub use skip_if::SkipIfTransformer;
+
+mod json;
+pub(crate) use json::transform_values;
+pub use json::{JsonField, JsonTransformer, OnMissing};
+
 mod capitalize;
 pub use capitalize::CapitalizeTransformer;
 
+mod scramble;
+pub use scramble::{ScrambleChars, ScrambleTransformer, UnicodeLetters};
+
+mod learn_format;
+pub use learn_format::{LearnFormatTransformer, LearnedFormat};
+
+mod redact;
+pub use redact::RedactTransformer;
+
+mod dp_noise;
+pub use dp_noise::{DpNoiseTransformer, NoiseMechanism};
+
+mod interval_jitter;
+pub use interval_jitter::{Interval, IntervalJitterTransformer, IntervalRound};
+
 mod template;
 pub use template::TemplateTransformer;
 
+mod sql;
+pub use sql::SqlTransformer;
+
+mod bytea_placeholder;
+pub use bytea_placeholder::{ByteaPlaceholderTransformer, PlaceholderContent, PlaceholderStyle};
+
 mod number;
 pub use number::RandomNumberTransformer;
 
@@ -28,6 +69,12 @@
 mod token;
 pub use token::{Base64TokenTransformer, Base64UrlTokenTransformer, HexTokenTransformer};
 
+mod boolean;
+pub use boolean::{BooleanFormat, BooleanTransformer};
+
+pub mod ids;
+pub use ids::{DeTaxIdTransformer, NinoTransformer, SsnTransformer};
+
 mod fk;
 pub use fk::sql_value::AsSqlValue;
 pub use fk::*;
@@ -37,7 +84,7 @@
 // We can box TemplateTransformer.renderer, but reducing memory usage even by several hundred
 // kilobytes is insignificant.
 macro_rules! define_transformers_enum {
-    ( $( ( $ser:literal, $var:ident, $tr:ty ) ),* ) => {
+    ( $( ( $ser:literal, $var:ident, $tr:ty, $cat:ident ) ),* ) => {
         #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
         #[allow(clippy::large_enum_variant)]
         pub enum Transformers {
@@ -48,6 +95,24 @@ pub enum Transformers {
         }
 
         impl Transformers {
+            /// Returns the rule name (as in the config)
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$var(_) => $ser,
+                    )*
+                }
+            }
+
+            // The category from the registry (the nested rules are not taken into account)
+            fn registry_category(&self) -> RuleCategory {
+                match self {
+                    $(
+                        Self::$var(_) => RuleCategory::$cat,
+                    )*
+                }
+            }
+
             fn transformer(&self) -> &dyn Transformer {
                 match self {
                     $(
@@ -68,99 +133,457 @@ fn mut_transformer(&mut self) -> &mut dyn Transformer {
 }
 
 define_transformers_enum![
-    ("none", None, NoneTransformer),
-    ("email", Email, EmailTransformer),
-    ("ip", Ip, IpTransformer),
-    ("phone", Phone, PhoneTransformer),
-    ("pipeline", Pipeline, PipelineTransformer<Transformers>),
-    ("capitalize", Capitalize, CapitalizeTransformer),
-    ("template", Template, TemplateTransformer),
-    ("random_num", RandomNum, RandomNumberTransformer),
-    ("password", Password, PasswordTransformer),
-    ("datetime", DateTime, RandomDateTimeTransformer),
-
-    ("hex_token", HexToken, HexTokenTransformer),
-    ("base64_token", Base64Token, Base64TokenTransformer),
-    ("base64url_token", Base64UrlToken, Base64UrlTokenTransformer),
-
-    ("city", City, CityTransformer),
-    ("city_prefix", CityPrefix, CityPrefixTransformer),
-    ("city_suffix", CitySuffix, CitySuffixTransformer),
-    ("country_name", CountryName, CountryNameTransformer),
-    ("country_code", CountryCode, CountryCodeTransformer),
-    ("street_suffix", StreetSuffix, StreetSuffixTransformer),
-    ("street_name", StreetName, StreetNameTransformer),
-    ("time_zone", TimeZone, TimeZoneTransformer),
-    ("state_name", StateName, StateNameTransformer),
-    ("state_abbr", StateAbbr, StateAbbrTransformer),
-    ("dwelling_type", DwellingType, DwellingTypeTransformer),
-    ("dwelling", Dwelling, DwellingTransformer),
-    ("zip_code", ZipCode, ZipCodeTransformer),
-    ("post_code", PostCode, PostCodeTransformer),
-    ("building_number", BuildingNumber, BuildingNumberTransformer),
-    ("latitude", Latitude, LatitudeTransformer),
-    ("longitude", Longitude, LongitudeTransformer),
-
-    ("boolean", Boolean, BooleanTransformer),
-
-    ("raw_date", RawDate, RawDateTransformer),
-    ("raw_datetime", RawDateTime, RawDateTimeTransformer),
-
-    ("company_suffix", CompanySuffix, CompanySuffixTransformer),
-    ("company_name", CompanyName, CompanyNameTransformer),
-    ("company_name_alt", CompanyNameAlt, CompanyNameAltTransformer),
-    ("company_motto", CompanyMotto, CompanyMottoTransformer),
-    ("company_motto_head", CompanyMottoHead, CompanyMottoHeadTransformer),
-    ("company_motto_middle", CompanyMottoMiddle, CompanyMottoMiddleTransformer),
-    ("company_motto_tail", CompanyMottoTail, CompanyMottoTailTransformer),
-    ("company_activity", CompanyActivity, CompanyActivityTransformer),
-    ("company_activity_verb", CompanyActivityVerb, CompanyActivityVerbTransformer),
-    ("company_activity_adj", CompanyActivityAdj, CompanyActivityAdjTransformer),
-    ("company_activity_noun", CompanyActivityNoun, CompanyActivityNounTransformer),
-    ("profession", Profession, ProfessionTransformer),
-    ("industry", Industry, IndustryTransformer),
-
-    ("free_email_provider", FreeEmailProvider, FreeEmailProviderTransformer),
-    ("domain_suffix", DomainSuffix, DomainSuffixTransformer),
-    ("username", Username, UsernameTransformer),
-    ("mac_address", MacAddress, MacAddressTransformer),
-    ("color", Color, ColorTransformer),
-    ("user_agent", UserAgent, UserAgentTransformer),
-    ("job_seniority", JobSeniority, JobSeniorityTransformer),
-    ("job_field", JobField, JobFieldTransformer),
-    ("job_position", JobPosition, JobPositionTransformer),
-    ("job_title", JobTitle, JobTitleTransformer),
-
-    ("word", Word, WordTransformer),
-    ("words", Words, WordsTransformer),
-    ("sentence", Sentence, SentenceTransformer),
-    ("sentences", Sentences, SentencesTransformer),
-    ("paragraph", Paragraph, ParagraphTransformer),
-    ("paragraphs", Paragraphs, ParagraphsTransformer),
-
-    ("first_name", FirstName, FirstNameTransformer),
-    ("last_name", LastName, LastNameTransformer),
-    ("middle_name", MiddleName, MiddleNameTransformer),
-    ("name_suffix", NameSuffix, NameSuffixTransformer),
-    ("person_title", PersonTitle, PersonTitleTransformer),
-    ("person_name", PersonName, PersonNameTransformer),
-    ("person_name_with_title", PersonNameWithTitle, PersonNameWithTitleTransformer),
-
-    ("digit", Digit, DigitTransformer),
-
-    ("local_phone", LocalPhone, LocalPhoneTransformer),
-    ("local_cell_phone", LocalCellPhone, LocalCellPhoneTransformer),
-
-    ("file_path", FilePath, FilePathTransformer),
-    ("file_name", FileName, FileNameTransformer),
-    ("file_extension", FileExtension, FileExtensionTransformer),
-    ("dir_path", DirPath, DirPathTransformer),
-
-    ("currency_code", CurrencyCode, CurrencyCodeTransformer),
-    ("currency_name", CurrencyName, CurrencyNameTransformer),
-    ("currency_symbol", CurrencySymbol, CurrencySymbolTransformer)
+    ("none", None, NoneTransformer, Any),
+    ("email", Email, EmailTransformer, Email),
+    ("ip", Ip, IpTransformer, Internet),
+    ("phone", Phone, PhoneTransformer, Phone),
+    ("pipeline", Pipeline, PipelineTransformer<Transformers>, Any),
+    ("cache", Cache, CacheTransformer<Transformers>, Any),
+    ("normalize_empty", NormalizeEmpty, NormalizeEmptyTransformer<Transformers>, Any),
+    ("skip_if", SkipIf, SkipIfTransformer<Transformers>, Any),
+    ("json", Json, JsonTransformer<Transformers>, Any),
+    ("consistent", Consistent, ConsistentTransformer<Transformers>, Any),
+    ("capitalize", Capitalize, CapitalizeTransformer, Any),
+    ("scramble", Scramble, ScrambleTransformer, Any),
+    ("learn_format", LearnFormat, LearnFormatTransformer<Transformers>, Any),
+    ("redact", Redact, RedactTransformer, Any),
+    ("dp_noise", DpNoise, DpNoiseTransformer, Any),
+    ("interval_jitter", IntervalJitter, IntervalJitterTransformer, Other),
+    ("template", Template, TemplateTransformer, Any),
+    ("sql", Sql, SqlTransformer, Any),
+    ("bytea_placeholder", ByteaPlaceholder, ByteaPlaceholderTransformer, Other),
+    ("random_num", RandomNum, RandomNumberTransformer, Any),
+    ("password", Password, PasswordTransformer, Internet),
+    ("datetime", DateTime, RandomDateTimeTransformer, Date),
+
+    ("hex_token", HexToken, HexTokenTransformer, Other),
+    ("base64_token", Base64Token, Base64TokenTransformer, Other),
+    ("base64url_token", Base64UrlToken, Base64UrlTokenTransformer, Other),
+
+    ("ssn", Ssn, SsnTransformer, Other),
+    ("nino", Nino, NinoTransformer, Other),
+    ("de_tax_id", DeTaxId, DeTaxIdTransformer, Other),
+
+    ("city", City, CityTransformer, Address),
+    ("city_prefix", CityPrefix, CityPrefixTransformer, Address),
+    ("city_suffix", CitySuffix, CitySuffixTransformer, Address),
+    ("country_name", CountryName, CountryNameTransformer, Address),
+    ("country_code", CountryCode, CountryCodeTransformer, Address),
+    ("street_suffix", StreetSuffix, StreetSuffixTransformer, Address),
+    ("street_name", StreetName, StreetNameTransformer, Address),
+    ("time_zone", TimeZone, TimeZoneTransformer, Other),
+    ("state_name", StateName, StateNameTransformer, Address),
+    ("state_abbr", StateAbbr, StateAbbrTransformer, Address),
+    ("dwelling_type", DwellingType, DwellingTypeTransformer, Address),
+    ("dwelling", Dwelling, DwellingTransformer, Address),
+    ("zip_code", ZipCode, ZipCodeTransformer, Address),
+    ("post_code", PostCode, PostCodeTransformer, Address),
+    ("building_number", BuildingNumber, BuildingNumberTransformer, Address),
+    ("latitude", Latitude, LatitudeTransformer, Address),
+    ("longitude", Longitude, LongitudeTransformer, Address),
+
+    ("boolean", Boolean, BooleanTransformer, Other),
+
+    ("raw_date", RawDate, RawDateTransformer, Date),
+    ("raw_datetime", RawDateTime, RawDateTimeTransformer, Date),
+
+    ("company_suffix", CompanySuffix, CompanySuffixTransformer, Business),
+    ("company_name", CompanyName, CompanyNameTransformer, Business),
+    ("company_name_alt", CompanyNameAlt, CompanyNameAltTransformer, Business),
+    ("company_motto", CompanyMotto, CompanyMottoTransformer, Business),
+    ("company_motto_head", CompanyMottoHead, CompanyMottoHeadTransformer, Business),
+    ("company_motto_middle", CompanyMottoMiddle, CompanyMottoMiddleTransformer, Business),
+    ("company_motto_tail", CompanyMottoTail, CompanyMottoTailTransformer, Business),
+    ("company_activity", CompanyActivity, CompanyActivityTransformer, Business),
+    ("company_activity_verb", CompanyActivityVerb, CompanyActivityVerbTransformer, Business),
+    ("company_activity_adj", CompanyActivityAdj, CompanyActivityAdjTransformer, Business),
+    ("company_activity_noun", CompanyActivityNoun, CompanyActivityNounTransformer, Business),
+    ("profession", Profession, ProfessionTransformer, Business),
+    ("industry", Industry, IndustryTransformer, Business),
+
+    ("free_email_provider", FreeEmailProvider, FreeEmailProviderTransformer, Email),
+    ("domain_suffix", DomainSuffix, DomainSuffixTransformer, Internet),
+    ("username", Username, UsernameTransformer, Internet),
+    ("mac_address", MacAddress, MacAddressTransformer, Internet),
+    ("color", Color, ColorTransformer, Other),
+    ("user_agent", UserAgent, UserAgentTransformer, Internet),
+    ("job_seniority", JobSeniority, JobSeniorityTransformer, Business),
+    ("job_field", JobField, JobFieldTransformer, Business),
+    ("job_position", JobPosition, JobPositionTransformer, Business),
+    ("job_title", JobTitle, JobTitleTransformer, Business),
+
+    ("word", Word, WordTransformer, Text),
+    ("words", Words, WordsTransformer, Text),
+    ("sentence", Sentence, SentenceTransformer, Text),
+    ("sentences", Sentences, SentencesTransformer, Text),
+    ("paragraph", Paragraph, ParagraphTransformer, Text),
+    ("paragraphs", Paragraphs, ParagraphsTransformer, Text),
+
+    ("first_name", FirstName, FirstNameTransformer, Name),
+    ("last_name", LastName, LastNameTransformer, Name),
+    ("middle_name", MiddleName, MiddleNameTransformer, Name),
+    ("name_suffix", NameSuffix, NameSuffixTransformer, Name),
+    ("person_title", PersonTitle, PersonTitleTransformer, Name),
+    ("person_name", PersonName, PersonNameTransformer, Name),
+    ("person_name_with_title", PersonNameWithTitle, PersonNameWithTitleTransformer, Name),
+
+    ("digit", Digit, DigitTransformer, Any),
+
+    ("local_phone", LocalPhone, LocalPhoneTransformer, Phone),
+    ("local_cell_phone", LocalCellPhone, LocalCellPhoneTransformer, Phone),
+
+    ("file_path", FilePath, FilePathTransformer, Other),
+    ("file_name", FileName, FileNameTransformer, Other),
+    ("file_extension", FileExtension, FileExtensionTransformer, Other),
+    ("dir_path", DirPath, DirPathTransformer, Other),
+
+    ("currency_code", CurrencyCode, CurrencyCodeTransformer, Other),
+    ("currency_name", CurrencyName, CurrencyNameTransformer, Other),
+    ("currency_symbol", CurrencySymbol, CurrencySymbolTransformer, Other)
 ];
 
+impl Transformers {
+    /// Returns `true` if the rule (or any nested rule) generates unique values
+    pub fn is_uniq(&self) -> bool {
+        match self {
+            Self::Email(t) => t.uniq.required,
+            Self::Ip(t) => t.uniq.required,
+            Self::Phone(t) => t.uniq.required,
+            Self::RandomNum(t) => t.uniq.required,
+            Self::Ssn(t) => t.uniq.required,
+            Self::Nino(t) => t.uniq.required,
+            Self::DeTaxId(t) => t.uniq.required,
+            Self::LearnFormat(t) => t.uniq.required || t.fallback.iter().any(|f| f.is_uniq()),
+            Self::Pipeline(t) => t.pipes.iter().any(|p| p.is_uniq()),
+            Self::Cache(t) => t.rule.is_uniq(),
+            Self::NormalizeEmpty(t) => t.rule.is_uniq(),
+            Self::SkipIf(t) => t.rule.is_uniq(),
+            Self::Json(t) => t.fields.iter().any(|f| f.rule.is_uniq()),
+            Self::Consistent(t) => t.rule.is_uniq(),
+            Self::Template(t) => t.rules.iter().flatten().any(|r| r.is_uniq()),
+            _ => false,
+        }
+    }
+
+    /// The `skip_if` rule that registers the kept values in the uniqueness set of its inner rule
+    /// (only the top-level one, the nested rules don't get the original values)
+    pub fn kept_values_rule(&self) -> Option<&SkipIfTransformer<Transformers>> {
+        match self {
+            Self::SkipIf(t) if t.register_uniq => Some(t),
+            _ => None,
+        }
+    }
+
+    /// The `unique_against` options of the unique rules (including the nested ones)
+    pub fn unique_against(&self) -> Vec<&UniqueAgainst> {
+        match self {
+            Self::Email(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::Ip(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::Phone(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::RandomNum(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::Ssn(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::Nino(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::DeTaxId(t) => t.uniq.unique_against().into_iter().collect(),
+            Self::LearnFormat(t) => t
+                .uniq
+                .unique_against()
+                .into_iter()
+                .chain(t.fallback.iter().flat_map(|f| f.unique_against()))
+                .collect(),
+            Self::Pipeline(t) => t.pipes.iter().flat_map(|p| p.unique_against()).collect(),
+            Self::Cache(t) => t.rule.unique_against(),
+            Self::NormalizeEmpty(t) => t.rule.unique_against(),
+            Self::SkipIf(t) => t.rule.unique_against(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.unique_against())
+                .collect(),
+            Self::Consistent(t) => t.rule.unique_against(),
+            Self::Template(t) => t
+                .rules
+                .iter()
+                .flatten()
+                .flat_map(|r| r.unique_against())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns `true` if the rule (or a nested rule) derives the values from the original values
+    /// (`consistent` rules and the `consistent` modes of `boolean`, `scramble` and `learn_format`)
+    pub fn is_consistent(&self) -> bool {
+        match self {
+            Self::Consistent(_) => true,
+            Self::Boolean(t) => t.consistent,
+            Self::Scramble(t) => t.consistent,
+            Self::LearnFormat(t) => t.consistent || t.fallback.iter().any(|f| f.is_consistent()),
+            Self::Pipeline(t) => t.pipes.iter().any(|p| p.is_consistent()),
+            Self::Cache(t) => t.rule.is_consistent(),
+            Self::NormalizeEmpty(t) => t.rule.is_consistent(),
+            Self::SkipIf(t) => t.rule.is_consistent(),
+            Self::Json(t) => t.fields.iter().any(|f| f.rule.is_consistent()),
+            Self::Template(t) => t.rules.iter().flatten().any(|r| r.is_consistent()),
+            _ => false,
+        }
+    }
+
+    /// The `dp_noise` rules (including the nested ones)
+    pub fn dp_noise_rules(&self) -> Vec<&DpNoiseTransformer> {
+        match self {
+            Self::DpNoise(t) => vec![t],
+            Self::Pipeline(t) => t.pipes.iter().flat_map(|p| p.dp_noise_rules()).collect(),
+            Self::Cache(t) => t.rule.dp_noise_rules(),
+            Self::NormalizeEmpty(t) => t.rule.dp_noise_rules(),
+            Self::SkipIf(t) => t.rule.dp_noise_rules(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.dp_noise_rules())
+                .collect(),
+            Self::Consistent(t) => t.rule.dp_noise_rules(),
+            Self::Template(t) => t
+                .rules
+                .iter()
+                .flatten()
+                .flat_map(|r| r.dp_noise_rules())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The `learn_format` rules (including the nested ones)
+    pub fn learn_format_rules(&self) -> Vec<&LearnFormatTransformer<Transformers>> {
+        match self {
+            Self::LearnFormat(t) => vec![t],
+            Self::Pipeline(t) => t
+                .pipes
+                .iter()
+                .flat_map(|p| p.learn_format_rules())
+                .collect(),
+            Self::Cache(t) => t.rule.learn_format_rules(),
+            Self::NormalizeEmpty(t) => t.rule.learn_format_rules(),
+            Self::SkipIf(t) => t.rule.learn_format_rules(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.learn_format_rules())
+                .collect(),
+            Self::Consistent(t) => t.rule.learn_format_rules(),
+            Self::Template(t) => t
+                .rules
+                .iter()
+                .flatten()
+                .flat_map(|r| r.learn_format_rules())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The `bytea_placeholder` rules (including the nested ones)
+    pub fn bytea_placeholder_rules(&self) -> Vec<&ByteaPlaceholderTransformer> {
+        match self {
+            Self::ByteaPlaceholder(t) => vec![t],
+            Self::Pipeline(t) => t
+                .pipes
+                .iter()
+                .flat_map(|p| p.bytea_placeholder_rules())
+                .collect(),
+            Self::Cache(t) => t.rule.bytea_placeholder_rules(),
+            Self::NormalizeEmpty(t) => t.rule.bytea_placeholder_rules(),
+            Self::SkipIf(t) => t.rule.bytea_placeholder_rules(),
+            Self::Consistent(t) => t.rule.bytea_placeholder_rules(),
+            _ => vec![],
+        }
+    }
+
+    /// The `skip_if` rules (including the nested ones)
+    pub fn skip_if_rules(&self) -> Vec<&SkipIfTransformer<Transformers>> {
+        match self {
+            Self::SkipIf(t) => {
+                let mut rules = vec![t];
+                rules.extend(t.rule.skip_if_rules());
+                rules
+            }
+            Self::Pipeline(t) => t.pipes.iter().flat_map(|p| p.skip_if_rules()).collect(),
+            Self::Cache(t) => t.rule.skip_if_rules(),
+            Self::NormalizeEmpty(t) => t.rule.skip_if_rules(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.skip_if_rules())
+                .collect(),
+            Self::Consistent(t) => t.rule.skip_if_rules(),
+            Self::Template(t) => t
+                .rules
+                .iter()
+                .flatten()
+                .flat_map(|r| r.skip_if_rules())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The `template` rules (including the nested ones)
+    pub fn template_rules(&self) -> Vec<&TemplateTransformer> {
+        match self {
+            Self::Template(t) => {
+                let mut rules = vec![t];
+                rules.extend(t.rules.iter().flatten().flat_map(|r| r.template_rules()));
+                rules
+            }
+            Self::Pipeline(t) => t.pipes.iter().flat_map(|p| p.template_rules()).collect(),
+            Self::Cache(t) => t.rule.template_rules(),
+            Self::NormalizeEmpty(t) => t.rule.template_rules(),
+            Self::SkipIf(t) => t.rule.template_rules(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.template_rules())
+                .collect(),
+            Self::Consistent(t) => t.rule.template_rules(),
+            Self::LearnFormat(t) => t.fallback.iter().flat_map(|f| f.template_rules()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The `json` rules (including the nested ones)
+    pub fn json_rules(&self) -> Vec<&JsonTransformer<Transformers>> {
+        match self {
+            Self::Json(t) => {
+                let mut rules = vec![t];
+                rules.extend(t.fields.iter().flat_map(|f| f.rule.json_rules()));
+                rules
+            }
+            Self::Pipeline(t) => t.pipes.iter().flat_map(|p| p.json_rules()).collect(),
+            Self::Cache(t) => t.rule.json_rules(),
+            Self::NormalizeEmpty(t) => t.rule.json_rules(),
+            Self::SkipIf(t) => t.rule.json_rules(),
+            Self::Consistent(t) => t.rule.json_rules(),
+            _ => vec![],
+        }
+    }
+
+    /// The `interval_jitter` rules (including the nested ones)
+    pub fn interval_jitter_rules(&self) -> Vec<&IntervalJitterTransformer> {
+        match self {
+            Self::IntervalJitter(t) => vec![t],
+            Self::Pipeline(t) => t
+                .pipes
+                .iter()
+                .flat_map(|p| p.interval_jitter_rules())
+                .collect(),
+            Self::Cache(t) => t.rule.interval_jitter_rules(),
+            Self::NormalizeEmpty(t) => t.rule.interval_jitter_rules(),
+            Self::SkipIf(t) => t.rule.interval_jitter_rules(),
+            Self::Json(t) => t
+                .fields
+                .iter()
+                .flat_map(|f| f.rule.interval_jitter_rules())
+                .collect(),
+            Self::Consistent(t) => t.rule.interval_jitter_rules(),
+            Self::Template(t) => t
+                .rules
+                .iter()
+                .flatten()
+                .flat_map(|r| r.interval_jitter_rules())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The sum of the `epsilon` values of the `dp_noise` rules
+    pub fn privacy_budget(&self) -> f64 {
+        self.dp_noise_rules().iter().map(|t| t.epsilon).sum()
+    }
+
+    /// The expression of the `sql` rule (it is evaluated by the database)
+    pub fn sql_expression(&self) -> Option<&str> {
+        match self {
+            Self::Sql(t) => Some(&t.expression),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if any nested rule is an `sql` rule (the `sql` rules can't be nested)
+    pub fn has_nested_sql(&self) -> bool {
+        let is_sql = |r: &Self| r.sql_expression().is_some() || r.has_nested_sql();
+        match self {
+            Self::Pipeline(t) => t.pipes.iter().any(is_sql),
+            Self::Cache(t) => is_sql(&t.rule),
+            Self::NormalizeEmpty(t) => is_sql(&t.rule),
+            Self::SkipIf(t) => is_sql(&t.rule),
+            Self::Json(t) => t.fields.iter().any(|f| is_sql(&f.rule)),
+            Self::Consistent(t) => is_sql(&t.rule),
+            Self::Template(t) => t.rules.iter().flatten().any(is_sql),
+            Self::LearnFormat(t) => t.fallback.iter().any(|f| is_sql(f)),
+            _ => false,
+        }
+    }
+
+    /// The category of the generated values (the last pipe of a pipeline, the rule of a cache,
+    /// a normalization, a skip or a consistent rule)
+    pub fn category(&self) -> RuleCategory {
+        match self {
+            Self::Pipeline(t) => t.pipes.last().map_or(RuleCategory::Any, |p| p.category()),
+            Self::Cache(t) => t.rule.category(),
+            Self::NormalizeEmpty(t) => t.rule.category(),
+            Self::SkipIf(t) => t.rule.category(),
+            Self::Consistent(t) => t.rule.category(),
+            _ => self.registry_category(),
+        }
+    }
+
+    /// The range of the generated integers (`[min, max]`), if it is known (the last pipe of a pipeline,
+    /// the rule of a cache, a normalization, a skip or a consistent rule)
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        match self {
+            Self::RandomNum(t) => {
+                let (min, max) = t.range();
+                Some((min as i128, max as i128))
+            }
+            // the integers are rounded
+            Self::DpNoise(t) => t
+                .clamp
+                .map(|[min, max]| (min.round() as i128, max.round() as i128)),
+            Self::Pipeline(t) => t.pipes.last().and_then(|p| p.int_range()),
+            Self::Cache(t) => t.rule.int_range(),
+            Self::NormalizeEmpty(t) => t.rule.int_range(),
+            Self::SkipIf(t) => t.rule.int_range(),
+            Self::Consistent(t) => t.rule.int_range(),
+            _ => None,
+        }
+    }
+
+    /// The column type classes the rule generates values for (`None` means any type)
+    pub fn supported_types(&self) -> Option<&'static [TypeClass]> {
+        use TypeClass::*;
+
+        match self {
+            Self::None(_) | Self::Template(_) | Self::Sql(_) => None,
+            Self::Pipeline(t) => t.pipes.last().and_then(|p| p.supported_types()),
+            Self::Cache(t) => t.rule.supported_types(),
+            Self::NormalizeEmpty(t) => t.rule.supported_types(),
+            Self::SkipIf(t) => t.rule.supported_types(),
+            Self::Consistent(t) => t.rule.supported_types(),
+            Self::Scramble(_)
+            | Self::LearnFormat(_)
+            | Self::RandomNum(_)
+            | Self::Digit(_)
+            | Self::BuildingNumber(_)
+            | Self::Latitude(_)
+            | Self::Longitude(_) => Some(&[Text, Numeric]),
+            Self::DpNoise(_) => Some(&[Numeric]),
+            Self::ByteaPlaceholder(_) => Some(&[Binary]),
+            Self::Json(_) => Some(&[Text, Json]),
+            Self::IntervalJitter(_) => Some(&[Text, Interval]),
+            Self::Boolean(_) => Some(&[Text, Numeric, Boolean]),
+            Self::DateTime(_) | Self::RawDate(_) | Self::RawDateTime(_) => Some(&[Text, Temporal]),
+            _ => Some(&[Text]),
+        }
+    }
+}
+
 impl Transformer for Transformers {
     fn transform(
         &self,
@@ -173,6 +596,9 @@ fn transform(
 
     fn init(&mut self, ctx: &TransformerInitContext) {
         self.mut_transformer().init(ctx);
+        if let Self::SkipIf(t) = self {
+            t.register_uniq = t.rule.is_uniq();
+        }
     }
 }
 
@@ -192,4 +618,181 @@ fn init() {
 
         assert!(matches!(ts, Transformers::FirstName(t) if t.locale == Some(LocaleConfig::RU)));
     }
+
+    #[test]
+    fn is_uniq() {
+        let config = r#"
+            pipeline:
+              pipes:
+                - email:
+                    uniq: true
+                - capitalize: ~
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(ts.is_uniq());
+
+        let ts: Transformers = serde_yaml::from_str("email: {}").unwrap();
+        assert!(!ts.is_uniq());
+    }
+
+    #[test]
+    fn unique_against() {
+        let config = r#"
+            skip_if:
+              prefixes: ["demo"]
+              rule:
+                pipeline:
+                  pipes:
+                    - email:
+                        uniq:
+                          unique_against:
+                            table: users
+                            column: email
+                    - capitalize: ~
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(ts.is_uniq());
+        let against = ts.unique_against();
+        assert_eq!(against.len(), 1);
+        assert_eq!(
+            (against[0].table.as_str(), against[0].column.as_str()),
+            ("users", "email")
+        );
+
+        let ts: Transformers = serde_yaml::from_str("email: {uniq: true}").unwrap();
+        assert!(ts.unique_against().is_empty());
+    }
+
+    #[test]
+    fn privacy_budget() {
+        let config = r#"
+            pipeline:
+              pipes:
+                - dp_noise:
+                    epsilon: 0.5
+                - dp_noise:
+                    epsilon: 0.25
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(ts.dp_noise_rules().len(), 2);
+        assert_eq!(ts.privacy_budget(), 0.75);
+
+        let ts: Transformers = serde_yaml::from_str("email: {}").unwrap();
+        assert_eq!(ts.privacy_budget(), 0.0);
+    }
+
+    #[test]
+    fn has_nested_sql() {
+        let ts: Transformers = serde_yaml::from_str("sql: lower(name)").unwrap();
+        assert!(!ts.has_nested_sql());
+
+        let config = r#"
+            pipeline:
+              pipes:
+                - sql: lower(name)
+                - capitalize: ~
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(ts.has_nested_sql());
+
+        let config = r#"
+            template:
+              format: "{{ _1 }}"
+              rules:
+                - cache:
+                    rule:
+                      sql: lower(name)
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(ts.has_nested_sql());
+    }
+
+    #[test]
+    fn int_range() {
+        let ts: Transformers = serde_yaml::from_str("random_num: {min: 10, max: 20}").unwrap();
+        assert_eq!(ts.int_range(), Some((10, 20)));
+
+        let ts: Transformers = serde_yaml::from_str("random_num: {}").unwrap();
+        assert_eq!(ts.int_range(), Some((0, usize::MAX as i128)));
+
+        let ts: Transformers = serde_yaml::from_str("dp_noise: {clamp: [-0.4, 99.6]}").unwrap();
+        assert_eq!(ts.int_range(), Some((0, 100)));
+
+        let ts: Transformers = serde_yaml::from_str("dp_noise: {}").unwrap();
+        assert_eq!(ts.int_range(), None);
+
+        let config = r#"
+            cache:
+              rule:
+                pipeline:
+                  pipes:
+                    - scramble: {}
+                    - random_num: {max: 5}
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(ts.int_range(), Some((0, 5)));
+    }
+
+    #[test]
+    fn supported_types() {
+        let ts: Transformers = serde_yaml::from_str("email: {}").unwrap();
+        assert_eq!(ts.supported_types(), Some([TypeClass::Text].as_slice()));
+
+        let ts: Transformers = serde_yaml::from_str("random_num: {}").unwrap();
+        assert_eq!(
+            ts.supported_types(),
+            Some([TypeClass::Text, TypeClass::Numeric].as_slice())
+        );
+
+        let ts: Transformers = serde_yaml::from_str("template: {format: x}").unwrap();
+        assert_eq!(ts.supported_types(), None);
+
+        let ts: Transformers = serde_yaml::from_str("interval_jitter: {percent: 10}").unwrap();
+        assert_eq!(
+            ts.supported_types(),
+            Some([TypeClass::Text, TypeClass::Interval].as_slice())
+        );
+
+        let config = r#"
+            pipeline:
+              pipes:
+                - email: {}
+                - datetime: {}
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(
+            ts.supported_types(),
+            Some([TypeClass::Text, TypeClass::Temporal].as_slice())
+        );
+    }
+
+    #[test]
+    fn category() {
+        let ts: Transformers = serde_yaml::from_str("email: {}").unwrap();
+        assert_eq!(ts.category(), RuleCategory::Email);
+
+        let ts: Transformers = serde_yaml::from_str("scramble: {}").unwrap();
+        assert_eq!(ts.category(), RuleCategory::Any);
+
+        let config = r#"
+            pipeline:
+              pipes:
+                - email: {}
+                - first_name: {}
+        "#;
+        let ts: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(ts.category(), RuleCategory::Name);
+
+        let ts: Transformers = serde_yaml::from_str("cache: {rule: {city: {}}}").unwrap();
+        assert_eq!(ts.category(), RuleCategory::Address);
+    }
+
+    #[test]
+    fn name() {
+        let ts = Transformers::FirstName(FirstNameTransformer::default());
+        assert_eq!(ts.name(), "first_name");
+
+        let ts = Transformers::CurrencyCode(CurrencyCodeTransformer::default());
+        assert_eq!(ts.name(), "currency_code");
+    }
 }
@@ -0,0 +1,237 @@
+use crate::transformer::{
+    TransformContext, TransformResult, TransformResultHelper, Transformer, TransformerInitContext,
+};
+use serde::{Deserialize, Serialize};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+/// Normalizes the "empty" values (e.g., `""`, `N/A` or `-`) before the inner rule:
+/// they are replaced with NULLs (by default) or with the canonical value (`to`).
+/// The values are compared with the sentinels case-insensitively, the leading and trailing whitespace
+/// is ignored.
+///
+/// The NULLs (the original ones and the normalized ones) are handled by `on_null`:
+/// `keep` (default) keeps them (the inner rule isn't called), `transform` passes them to the inner rule.
+/// The canonical values are passed to the inner rule as the original values.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   phone:
+///     normalize_empty:
+///       values: ["", "n/a", "none", "-"]
+///       rule:
+///         phone:
+///           format: "+###########"
+///   nickname:
+///     normalize_empty:
+///       to: ""
+///       rule:
+///         username: {}
+/// ```
+///
+/// The default sentinels are `""`, `n/a`, `null` and `-`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+pub struct NormalizeEmptyTransformer<T> {
+    #[serde(default = "NormalizeEmptyTransformer::<T>::default_values")]
+    pub values: Vec<String>,
+    /// The canonical value (`None` means NULL)
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub on_null: OnNull,
+    pub rule: Box<T>,
+}
+
+/// What to do with NULLs
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnNull {
+    /// NULLs stay NULLs
+    #[default]
+    Keep,
+    /// NULLs are passed to the inner rule
+    Transform,
+}
+
+impl<T> NormalizeEmptyTransformer<T> {
+    pub fn new(rule: T) -> Self {
+        Self {
+            values: Self::default_values(),
+            to: None,
+            on_null: OnNull::default(),
+            rule: Box::new(rule),
+        }
+    }
+
+    fn default_values() -> Vec<String> {
+        ["", "n/a", "null", "-"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    /// Returns the normalized value if the value is a sentinel
+    pub fn normalize(&self, field_value: &str) -> Option<&str> {
+        let value = field_value.trim().to_lowercase();
+        self.values
+            .iter()
+            .any(|v| v.trim().to_lowercase() == value)
+            .then(|| self.to.as_deref().unwrap_or(NULL))
+    }
+}
+
+impl<T> NormalizeEmptyTransformer<T>
+where
+    T: Transformer,
+{
+    /// Returns the result and `true` if the value is normalized
+    pub fn transform_normalizing(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> (TransformResult, bool) {
+        let normalized = self.normalize(field_value);
+        let value = normalized.unwrap_or(field_value);
+
+        let result = if value == NULL && self.on_null == OnNull::Keep {
+            TransformResult::present(NULL)
+        } else {
+            match self.rule.transform(field_name, value, ctx) {
+                // the inner rule keeps the value, but it is normalized
+                Ok(None) if normalized.is_some() => TransformResult::present(value),
+                result => result,
+            }
+        };
+        (result, normalized.is_some())
+    }
+}
+
+impl<T> Transformer for NormalizeEmptyTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        self.transform_normalizing(field_name, field_value, ctx).0
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.rule.init(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        transformers::{CapitalizeTransformer, NoneTransformer, RedactTransformer},
+        Transformers,
+    };
+
+    fn redact() -> Transformers {
+        let mut t = RedactTransformer::default();
+        t.hash_suffix_len = 0;
+        Transformers::Redact(t)
+    }
+
+    #[test]
+    fn sentinels() {
+        let t = NormalizeEmptyTransformer::new(redact());
+
+        for value in ["", "  ", "N/A", " n/a ", "NULL", "-"] {
+            assert_eq!(
+                t.transform_normalizing("field", value, &None),
+                (Ok(Some(NULL.to_string())), true),
+                "{:?}",
+                value
+            );
+        }
+        assert_eq!(
+            t.transform_normalizing("field", "none", &None),
+            (Ok(Some("[REDACTED]".to_string())), false)
+        );
+        // the original NULLs are kept, but they aren't counted
+        assert_eq!(
+            t.transform_normalizing("field", NULL, &None),
+            (Ok(Some(NULL.to_string())), false)
+        );
+    }
+
+    #[test]
+    fn canonical_value() {
+        let mut t = NormalizeEmptyTransformer::new(Transformers::None(NoneTransformer));
+        t.values = vec!["Unknown ".to_string()];
+        t.to = Some(String::new());
+
+        assert_eq!(
+            t.transform_normalizing("field", "UNKNOWN", &None),
+            (Ok(Some(String::new())), true)
+        );
+        assert_eq!(t.transform("field", "", &None), Ok(Some(String::new())));
+        assert_eq!(
+            t.transform_normalizing("field", "n/a", &None),
+            (Ok(Some("n/a".to_string())), false)
+        );
+
+        // the canonical value is transformed by the inner rule
+        *t.rule = Transformers::Capitalize(CapitalizeTransformer);
+        t.to = Some("unknown".to_string());
+        assert_eq!(
+            t.transform("field", "unknown", &None),
+            Ok(Some("Unknown".to_string()))
+        );
+    }
+
+    #[test]
+    fn on_null() {
+        let mut t = NormalizeEmptyTransformer::new(redact());
+        t.on_null = OnNull::Transform;
+
+        // the normalization happens first
+        assert_eq!(
+            t.transform_normalizing("field", "n/a", &None),
+            (Ok(Some("[REDACTED]".to_string())), true)
+        );
+        assert_eq!(
+            t.transform("field", NULL, &None),
+            Ok(Some("[REDACTED]".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = r#"
+            normalize_empty:
+              rule:
+                none: ~
+        "#;
+        let t: Transformers = serde_yaml::from_str(config).unwrap();
+        assert_eq!(
+            t,
+            Transformers::NormalizeEmpty(NormalizeEmptyTransformer::new(Transformers::None(
+                NoneTransformer
+            )))
+        );
+
+        let config = r#"
+            normalize_empty:
+              values: ["", "?"]
+              to: ""
+              on_null: transform
+              rule:
+                none: ~
+        "#;
+        let t: Transformers = serde_yaml::from_str(config).unwrap();
+        assert!(matches!(t, Transformers::NormalizeEmpty(n)
+            if n.values == ["", "?"] && n.to.as_deref() == Some("") && n.on_null == OnNull::Transform));
+    }
+}
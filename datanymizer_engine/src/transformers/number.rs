@@ -1,4 +1,7 @@
-use crate::transformer::{TransformContext, UniqTransformer, Uniqueness};
+use crate::{
+    transformer::{TransformContext, UniqTransformer, Uniqueness},
+    utils,
+};
 use rand::distributions::{Distribution, Uniform};
 use serde::{Deserialize, Serialize};
 
@@ -54,6 +57,13 @@ fn default() -> Self {
     }
 }
 
+impl RandomNumberTransformer {
+    /// `[min, max]`
+    pub fn range(&self) -> (usize, usize) {
+        (self.min.0, self.max.0)
+    }
+}
+
 impl UniqTransformer for RandomNumberTransformer {
     fn do_transform(
         &self,
@@ -61,7 +71,7 @@ fn do_transform(
         _field_value: &str,
         _ctx: &Option<TransformContext>,
     ) -> String {
-        let mut rng = rand::thread_rng();
+        let mut rng = utils::rng();
         Uniform::new_inclusive(self.min.0, self.max.0)
             .sample(&mut rng)
             .to_string()
@@ -1,6 +1,9 @@
 use super::deserialize_phone_format;
 use super::phone_format::PhoneFormat;
-use crate::transformer::{TransformContext, UniqTransformer, Uniqueness};
+use crate::{
+    transformer::{TransformContext, UniqTransformer, Uniqueness},
+    utils,
+};
 use fake::Fake;
 use serde::{Deserialize, Serialize};
 use std::char;
@@ -20,6 +23,7 @@
 /// * `format` - you can specify format for you phone number
 /// * `#` - any digit from `0` to `9`
 /// * `^` - any digit from `1` to `9`
+///
 /// Also, you can use any other symbols in `format`, like: `^##-00-### (##-##)`
 ///
 /// If you want to generate unique phone numbers, use this option:
@@ -57,7 +61,7 @@ fn do_transform(
         _field_value: &str,
         _ctx: &Option<TransformContext>,
     ) -> String {
-        let mut rng = rand::thread_rng();
+        let mut rng = utils::rng();
 
         self.phone_format()
             .source_format
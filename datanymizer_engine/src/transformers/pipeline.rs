@@ -78,6 +78,7 @@ fn init() {
                 Transformers::FirstName(FirstNameTransformer::default()),
                 Transformers::LastName(LastNameTransformer {
                     locale: Some(LocaleConfig::ZH_TW),
+                    preserve_script: false,
                 }),
                 Transformers::Capitalize(CapitalizeTransformer),
             ],
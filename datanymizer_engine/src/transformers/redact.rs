@@ -0,0 +1,257 @@
+use crate::{
+    transformer::{
+        TransformContext, TransformResult, TransformResultHelper, Transformer,
+        TransformerInitContext,
+    },
+    utils,
+};
+use serde::{Deserialize, Serialize};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+/// The hash is 64 bits (16 hex digits)
+const MAX_SUFFIX_LENGTH: usize = 16;
+const PADDING: char = '*';
+
+/// Replaces values with redaction markers like `[REDACTED-7f3a]`. The suffix is a keyed (by `salt`) hash
+/// of the original value, so the same original values get the same markers (in all tables with the same salt),
+/// but the original values can't be found without the key. NULLs get markers without suffixes.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     redact:
+///       # the marker label (`REDACTED` is default)
+///       label: PII
+///       # the hash suffix length (4 by default, 0 - no suffix)
+///       hash_suffix_len: 6
+///       salt: "some secret"
+///       # pad or truncate the markers to the length of the original values
+///       preserve_length: true
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+#[serde(default)]
+pub struct RedactTransformer {
+    pub label: String,
+    /// The count of hex digits of the hash (up to 16)
+    pub hash_suffix_len: usize,
+    /// The key of the hash
+    pub salt: String,
+    /// Pads (with `*`) or truncates the markers to the length of the original values
+    pub preserve_length: bool,
+
+    /// The run pepper (it is set on initialization)
+    #[serde(skip)]
+    pepper: String,
+}
+
+impl Default for RedactTransformer {
+    fn default() -> Self {
+        Self {
+            label: "REDACTED".to_string(),
+            hash_suffix_len: 4,
+            salt: String::new(),
+            preserve_length: false,
+            pepper: String::new(),
+        }
+    }
+}
+
+impl RedactTransformer {
+    /// The maximum length of the markers (`None` with `preserve_length`, they are never longer than the values)
+    pub fn max_length(&self) -> Option<usize> {
+        if self.preserve_length {
+            return None;
+        }
+        let suffix = match self.suffix_length() {
+            0 => 0,
+            len => len + 1,
+        };
+        Some(self.label.chars().count() + suffix + 2)
+    }
+
+    fn suffix_length(&self) -> usize {
+        self.hash_suffix_len.min(MAX_SUFFIX_LENGTH)
+    }
+
+    fn suffix(&self, value: &str) -> String {
+        let mut bytes = utils::peppered(&self.salt, &self.pepper).into_bytes();
+        bytes.push(0);
+        bytes.extend_from_slice(value.as_bytes());
+        let hash = format!("{:016x}", utils::fmix64(utils::fnv1a(&bytes)));
+        hash[..self.suffix_length()].to_string()
+    }
+
+    fn marker(&self, label: &str, suffix: &str) -> String {
+        if suffix.is_empty() {
+            format!("[{}]", label)
+        } else {
+            format!("[{}-{}]", label, suffix)
+        }
+    }
+
+    // The label is truncated first (the suffix is kept as long as possible)
+    fn fit(&self, suffix: &str, length: usize) -> String {
+        let marker = self.marker(&self.label, suffix);
+        let marker_length = marker.chars().count();
+        if marker_length <= length {
+            let padding: String = std::iter::repeat_n(PADDING, length - marker_length).collect();
+            return marker + &padding;
+        }
+
+        let excess = marker_length - length;
+        let label_length = self.label.chars().count();
+        if excess <= label_length {
+            let label: String = self.label.chars().take(label_length - excess).collect();
+            return self.marker(&label, suffix);
+        }
+        marker.chars().take(length).collect()
+    }
+}
+
+impl Transformer for RedactTransformer {
+    fn transform(
+        &self,
+        _field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        let suffix = if field_value == NULL {
+            String::new()
+        } else {
+            self.suffix(field_value)
+        };
+
+        TransformResult::present(if self.preserve_length && field_value != NULL {
+            self.fit(&suffix, field_value.chars().count())
+        } else {
+            self.marker(&self.label, &suffix)
+        })
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.pepper = ctx.pepper.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn transform(t: &Transformers, value: &str) -> String {
+        t.transform("field", value, &None).unwrap().unwrap()
+    }
+
+    fn suffix(marker: &str) -> &str {
+        marker
+            .trim_end_matches(']')
+            .rsplit_once('-')
+            .map_or("", |(_, s)| s)
+    }
+
+    #[test]
+    fn default() {
+        let t = transformer("redact: {}");
+        let result = transform(&t, "john@example.com");
+        assert!(result.starts_with("[REDACTED-"));
+        assert_eq!(result.len(), 15);
+        assert!(suffix(&result).chars().all(|c| c.is_ascii_hexdigit()));
+
+        assert_eq!(transform(&t, r"\N"), "[REDACTED]");
+    }
+
+    #[test]
+    fn options() {
+        let t = transformer("redact: {label: PII, hash_suffix_len: 6}");
+        let result = transform(&t, "john@example.com");
+        assert!(result.starts_with("[PII-"));
+        assert_eq!(suffix(&result).len(), 6);
+
+        let t = transformer("redact: {hash_suffix_len: 0}");
+        assert_eq!(transform(&t, "john@example.com"), "[REDACTED]");
+
+        let t = transformer("redact: {hash_suffix_len: 100}");
+        assert_eq!(suffix(&transform(&t, "john@example.com")).len(), 16);
+    }
+
+    #[test]
+    fn consistent() {
+        let t = transformer("redact: {salt: secret}");
+        let other_table = transformer("redact: {salt: secret, label: PII}");
+        let result = transform(&t, "john@example.com");
+
+        assert_eq!(result, transform(&t, "john@example.com"));
+        assert_eq!(
+            suffix(&result),
+            suffix(&transform(&other_table, "john@example.com"))
+        );
+        assert_ne!(result, transform(&t, "jane@example.com"));
+    }
+
+    #[test]
+    fn keyed() {
+        let t = transformer("redact: {salt: secret, hash_suffix_len: 16}");
+        let other_key = transformer("redact: {salt: other, hash_suffix_len: 16}");
+        let unkeyed = transformer("redact: {hash_suffix_len: 16}");
+        let value = "john@example.com";
+
+        // the suffixes can't be reproduced without the key
+        let keyed_suffix = suffix(&transform(&t, value)).to_string();
+        assert_ne!(keyed_suffix, suffix(&transform(&other_key, value)));
+        assert_ne!(keyed_suffix, suffix(&transform(&unkeyed, value)));
+        assert_ne!(
+            keyed_suffix,
+            format!("{:016x}", utils::fnv1a(value.as_bytes()))
+        );
+
+        // similar values have unrelated suffixes (about half of the bits differ)
+        let a = u64::from_str_radix(suffix(&transform(&t, "john@example.com")), 16).unwrap();
+        let b = u64::from_str_radix(suffix(&transform(&t, "john@example.con")), 16).unwrap();
+        assert!((a ^ b).count_ones() > 16);
+    }
+
+    #[test]
+    fn preserve_length() {
+        let t = transformer("redact: {salt: secret, preserve_length: true}");
+        let result = transform(&t, "john.smith@example.com");
+        assert_eq!(result.chars().count(), 22);
+        assert!(result.starts_with("[REDACTED-"));
+        assert!(result.ends_with("]*******"));
+
+        // the label is truncated first
+        let result = transform(&t, "j@example.com");
+        assert_eq!(result.chars().count(), 13);
+        assert!(result.starts_with("[REDACT-"));
+        assert_eq!(suffix(&result).len(), 4);
+
+        assert_eq!(transform(&t, "Ann").chars().count(), 3);
+        assert_eq!(transform(&t, ""), "");
+        assert_eq!(transform(&t, r"\N"), "[REDACTED]");
+    }
+
+    #[test]
+    fn max_length() {
+        let t = RedactTransformer::default();
+        assert_eq!(t.max_length(), Some(15));
+
+        let t = RedactTransformer {
+            hash_suffix_len: 0,
+            ..RedactTransformer::default()
+        };
+        assert_eq!(t.max_length(), Some(10));
+
+        let t = RedactTransformer {
+            preserve_length: true,
+            ..RedactTransformer::default()
+        };
+        assert_eq!(t.max_length(), None);
+    }
+}
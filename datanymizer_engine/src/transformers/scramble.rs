@@ -0,0 +1,323 @@
+use crate::{
+    locale::script::Script,
+    transformer::{
+        TransformContext, TransformResult, TransformResultHelper, Transformer,
+        TransformerInitContext,
+    },
+    utils,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+const DIGITS: &str = "0123456789";
+const LATIN_LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const LATIN_UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const CYRILLIC_LOWER: &str = "абвгдеёжзийклмнопрстуфхцчшщъыьэюя";
+const CYRILLIC_UPPER: &str = "АБВГДЕЁЖЗИЙКЛМНОПРСТУФХЦЧШЩЪЫЬЭЮЯ";
+const GREEK_LOWER: &str = "αβγδεζηθικλμνξοπρστυφχψω";
+const GREEK_UPPER: &str = "ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩ";
+const ARABIC: &str = "ابتثجحخدذرزسشصضطظعغفقكلمنهوي";
+const HEBREW: &str = "אבגדהוזחטיכלמנסעפצקרשת";
+/// CJK Unified Ideographs
+const HAN: (u32, u32) = (0x4E00, 0x9FFF);
+
+/// Replaces digits with random digits and letters with random letters of the same case,
+/// the other characters (punctuation, whitespace, etc.) are kept. The value length and structure
+/// are preserved, e.g., `INV-2023-004521` can be transformed to `QZT-8351-972046`.
+///
+/// # Examples
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     scramble:
+///       # don't change the first 4 characters (e.g., `INV-`)
+///       keep_prefix: 4
+///       # don't change the last 2 characters
+///       keep_suffix: 2
+///       # scramble only digits (`alphanumeric` is default)
+///       chars: digits
+/// ```
+///
+/// With `consistent: true` every character position has its own keyed permutation,
+/// so the same original values are always transformed to the same values
+/// (and different values of the same structure to different values):
+///
+/// ```yaml
+/// #...
+/// rules:
+///   field_name:
+///     scramble:
+///       consistent: true
+///       salt: "some secret"
+/// ```
+///
+/// Non-ASCII letters are preserved by default, with `unicode: scramble` they are replaced with
+/// letters of the same script (Latin, Cyrillic, Greek, Arabic, Hebrew or Han).
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ScrambleTransformer {
+    /// Count of the first characters to keep
+    pub keep_prefix: usize,
+    /// Count of the last characters to keep
+    pub keep_suffix: usize,
+    pub chars: ScrambleChars,
+    pub unicode: UnicodeLetters,
+    /// The same original values get the same results
+    pub consistent: bool,
+    /// The key of the `consistent` permutations
+    pub salt: String,
+
+    /// The run pepper (it is set on initialization)
+    #[serde(skip)]
+    pepper: String,
+}
+
+/// Which characters are scrambled
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrambleChars {
+    Digits,
+    #[default]
+    Alphanumeric,
+}
+
+/// What to do with non-ASCII letters
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnicodeLetters {
+    #[default]
+    Preserve,
+    /// Replace them with letters of the same script
+    Scramble,
+}
+
+/// The characters that a character can be replaced with
+enum Alphabet {
+    Chars(&'static str),
+    Range(u32, u32),
+}
+
+impl Alphabet {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Chars(chars) => chars.chars().count() as u64,
+            Self::Range(first, last) => u64::from(last - first + 1),
+        }
+    }
+
+    fn get(&self, index: u64) -> char {
+        match self {
+            Self::Chars(chars) => chars.chars().nth(index as usize).unwrap_or_default(),
+            Self::Range(first, _) => char::from_u32(first + index as u32).unwrap_or_default(),
+        }
+    }
+
+    /// The index of the character (characters that are not in the alphabet get some index too)
+    fn index_of(&self, c: char) -> u64 {
+        let index = match self {
+            Self::Chars(chars) => chars.chars().position(|a| a == c).map(|i| i as u64),
+            Self::Range(first, last) => (*first..=*last)
+                .contains(&(c as u32))
+                .then(|| u64::from(c as u32 - first)),
+        };
+        index.unwrap_or(u64::from(c as u32)) % self.len()
+    }
+}
+
+impl ScrambleTransformer {
+    fn alphabet(&self, c: char) -> Option<Alphabet> {
+        if c.is_ascii_digit() {
+            return Some(Alphabet::Chars(DIGITS));
+        }
+        if self.chars == ScrambleChars::Digits || !c.is_alphabetic() {
+            return None;
+        }
+        if c.is_ascii() {
+            return Some(Self::cased(c, LATIN_LOWER, LATIN_UPPER));
+        }
+        if self.unicode == UnicodeLetters::Preserve {
+            return None;
+        }
+
+        let script = Script::of(c)?;
+        Some(match script {
+            Script::Latin => Self::cased(c, LATIN_LOWER, LATIN_UPPER),
+            Script::Cyrillic => Self::cased(c, CYRILLIC_LOWER, CYRILLIC_UPPER),
+            Script::Greek => Self::cased(c, GREEK_LOWER, GREEK_UPPER),
+            Script::Arabic => Alphabet::Chars(ARABIC),
+            Script::Hebrew => Alphabet::Chars(HEBREW),
+            Script::Han => Alphabet::Range(HAN.0, HAN.1),
+        })
+    }
+
+    fn cased(c: char, lower: &'static str, upper: &'static str) -> Alphabet {
+        if c.is_uppercase() {
+            Alphabet::Chars(upper)
+        } else {
+            Alphabet::Chars(lower)
+        }
+    }
+
+    /// A keyed permutation of the alphabet for the position: `(a * index + b) mod len`,
+    /// where `a` is coprime with `len`
+    fn permute(&self, alphabet: &Alphabet, c: char, position: usize) -> char {
+        let len = alphabet.len();
+        let key = utils::fnv1a(
+            format!("{}:{}", utils::peppered(&self.salt, &self.pepper), position).as_bytes(),
+        );
+        let mut a = (key >> 32) % len;
+        while gcd(a, len) != 1 {
+            a = (a + 1) % len;
+        }
+        let b = key % len;
+
+        alphabet.get((a * alphabet.index_of(c) + b) % len)
+    }
+
+    fn scramble(&self, value: &str) -> String {
+        let count = value.chars().count();
+        let end = count.saturating_sub(self.keep_suffix);
+        let mut rng = utils::rng();
+
+        value
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if i < self.keep_prefix || i >= end {
+                    return c;
+                }
+                match self.alphabet(c) {
+                    Some(alphabet) if self.consistent => self.permute(&alphabet, c, i),
+                    Some(alphabet) => alphabet.get(rng.gen_range(0..alphabet.len())),
+                    None => c,
+                }
+            })
+            .collect()
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Transformer for ScrambleTransformer {
+    fn transform(
+        &self,
+        _field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        TransformResult::present(self.scramble(field_value))
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        self.pepper = ctx.pepper.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transformers;
+
+    fn transformer(config: &str) -> Transformers {
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    fn transform(t: &Transformers, value: &str) -> String {
+        t.transform("field", value, &None).unwrap().unwrap()
+    }
+
+    fn same_structure(a: &str, b: &str) -> bool {
+        a.chars().count() == b.chars().count()
+            && a.chars().zip(b.chars()).all(|(x, y)| {
+                (x.is_ascii_digit() && y.is_ascii_digit())
+                    || (x.is_ascii_lowercase() && y.is_ascii_lowercase())
+                    || (x.is_ascii_uppercase() && y.is_ascii_uppercase())
+                    || x == y
+            })
+    }
+
+    #[test]
+    fn structure() {
+        let t = transformer("scramble: {}");
+        let value = "INV-2023-004521 ab/Cd";
+        for _ in 0..10 {
+            assert!(same_structure(value, &transform(&t, value)));
+        }
+    }
+
+    #[test]
+    fn keep_prefix_and_suffix() {
+        let t = transformer("scramble: {keep_prefix: 4, keep_suffix: 2}");
+        let result = transform(&t, "INV-2023-004521");
+        assert!(result.starts_with("INV-"));
+        assert!(result.ends_with("21"));
+        assert!(same_structure("INV-2023-004521", &result));
+
+        assert_eq!(transform(&t, "AB1"), "AB1");
+    }
+
+    #[test]
+    fn digits_only() {
+        let t = transformer("scramble: {chars: digits}");
+        let result = transform(&t, "INV-2023-004521");
+        assert!(result.starts_with("INV-"));
+        assert!(same_structure("INV-2023-004521", &result));
+    }
+
+    #[test]
+    fn consistent() {
+        let t = transformer("scramble: {consistent: true, salt: secret}");
+        let other = transformer("scramble: {consistent: true, salt: other}");
+        let value = "INV-2023-004521";
+
+        assert_eq!(transform(&t, value), transform(&t, value));
+        assert!(same_structure(value, &transform(&t, value)));
+        assert_ne!(transform(&t, value), transform(&other, value));
+        // the permutations are bijective
+        assert_ne!(
+            transform(&t, "INV-2023-004521"),
+            transform(&t, "INV-2023-004522")
+        );
+    }
+
+    #[test]
+    fn consistent_permutation_is_bijective() {
+        let t = ScrambleTransformer {
+            consistent: true,
+            ..ScrambleTransformer::default()
+        };
+        for position in 0..5 {
+            let mut results: Vec<_> = LATIN_LOWER
+                .chars()
+                .map(|c| t.permute(&Alphabet::Chars(LATIN_LOWER), c, position))
+                .collect();
+            results.sort_unstable();
+            assert_eq!(results.into_iter().collect::<String>(), LATIN_LOWER);
+        }
+    }
+
+    #[test]
+    fn unicode_letters() {
+        let preserve = transformer("scramble: {}");
+        let result = transform(&preserve, "Жук-Éa");
+        assert!(result.starts_with("Жук-É"));
+        assert!(result.chars().last().unwrap().is_ascii_lowercase());
+
+        let scramble = transformer("scramble: {unicode: scramble}");
+        let result = transform(&scramble, "Жук 王 é");
+        let chars: Vec<char> = result.chars().collect();
+        assert!(CYRILLIC_UPPER.contains(chars[0]));
+        assert!(CYRILLIC_LOWER.contains(chars[1]));
+        assert_eq!(chars[3], ' ');
+        assert_eq!(Script::of(chars[4]), Some(Script::Han));
+        assert!(LATIN_LOWER.contains(chars[6]));
+    }
+}
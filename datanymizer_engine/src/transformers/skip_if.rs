@@ -0,0 +1,343 @@
+use crate::{
+    transformer::{
+        TransformContext, TransformError, TransformResult, Transformer, TransformerInitContext,
+    },
+    uniq_collector,
+};
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+/// The NULL value in the COPY text format
+const NULL: &str = r"\N";
+
+/// Keeps the values that are already anonymized (e.g., the seeded demo accounts) and passes
+/// the other values to the inner rule.
+///
+/// A value is kept if it matches any of the `patterns` (regexes), if its domain (the part after
+/// the last `@`) is one of the `domains` or their subdomain (case-insensitively), or if it starts
+/// with any of the `prefixes`. NULLs are never kept by `skip_if` (they are passed to the inner rule).
+///
+/// If the inner rule generates unique values, the kept values are registered in the uniqueness set
+/// of the column, so the generated values don't collide with them. The PostgreSQL dumper registers
+/// the kept values of the top-level rules before the dump; otherwise a kept value is registered when
+/// its row is dumped, and the dump fails if the inner rule has already generated it.
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   email:
+///     skip_if:
+///       domains: ["example.com"]
+///       patterns: ["^demo\\+\\d+@"]
+///       rule:
+///         email:
+///           uniq: true
+///   login:
+///     skip_if:
+///       prefixes: ["demo_"]
+///       rule:
+///         username: {}
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkipIfTransformer<T> {
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    pub rule: Box<T>,
+
+    #[serde(skip)]
+    regexes: Option<RegexSet>,
+    /// Register the kept values in the uniqueness set (the inner rule generates unique values)
+    #[serde(skip)]
+    pub(crate) register_uniq: bool,
+}
+
+impl<T> SkipIfTransformer<T> {
+    pub fn new(rule: T) -> Self {
+        Self {
+            patterns: vec![],
+            domains: vec![],
+            prefixes: vec![],
+            rule: Box::new(rule),
+            regexes: None,
+            register_uniq: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.patterns.is_empty() && self.domains.is_empty() && self.prefixes.is_empty() {
+            return Err(
+                "at least one of `patterns`, `domains` and `prefixes` must be specified"
+                    .to_string(),
+            );
+        }
+        for pattern in &self.patterns {
+            regex::Regex::new(pattern)
+                .map_err(|e| format!("invalid pattern `{}`: {}", pattern, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the value must be kept
+    pub fn matches(&self, field_value: &str) -> bool {
+        if field_value == NULL {
+            return false;
+        }
+
+        self.prefixes
+            .iter()
+            .any(|p| field_value.starts_with(p.as_str()))
+            || self.matches_domain(field_value)
+            || self
+                .regexes
+                .as_ref()
+                .is_some_and(|r| r.is_match(field_value))
+    }
+
+    /// Returns `true` if the value must be kept (the kept values of the unique rules are registered
+    /// in the uniqueness set). Fails if the inner rule has already generated the kept value.
+    pub fn skip(&self, field_name: &str, field_value: &str) -> Result<bool, TransformError> {
+        let skipped = self.matches(field_value);
+        if skipped && self.register_uniq && !register_kept(field_name, field_value) {
+            return Err(TransformError {
+                field_name: field_name.to_string(),
+                field_value: field_value.to_string(),
+                reason: format!(
+                    "The kept value of `{}` is already generated by its unique rule \
+                     (the kept values are registered before the dump only by the PostgreSQL dumper)",
+                    field_name
+                ),
+            });
+        }
+
+        Ok(skipped)
+    }
+
+    fn matches_domain(&self, field_value: &str) -> bool {
+        let domain = match field_value.rsplit_once('@') {
+            Some((_, domain)) => domain.trim().to_lowercase(),
+            None => return false,
+        };
+        self.domains.iter().any(|d| {
+            let d = d.trim_start_matches('@').to_lowercase();
+            domain == d
+                || domain
+                    .strip_suffix(d.as_str())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+}
+
+/// Registers the kept value in the uniqueness set of the rule. Returns `false` if the value is
+/// already generated by the rule (the value isn't kept before in this case).
+pub(crate) fn register_kept(field_name: &str, field_value: &str) -> bool {
+    let kept_before = !uniq_collector::add_to_collector(&kept_name(field_name), field_value);
+    uniq_collector::add_to_collector(field_name, field_value) || kept_before
+}
+
+fn kept_name(field_name: &str) -> String {
+    format!("{}#kept", field_name)
+}
+
+impl<T> PartialEq for SkipIfTransformer<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.patterns == other.patterns
+            && self.domains == other.domains
+            && self.prefixes == other.prefixes
+            && self.rule == other.rule
+    }
+}
+
+impl<T> Eq for SkipIfTransformer<T> where T: Eq {}
+
+impl<T> Hash for SkipIfTransformer<T>
+where
+    T: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.patterns.hash(state);
+        self.domains.hash(state);
+        self.prefixes.hash(state);
+        self.rule.hash(state);
+    }
+}
+
+impl<T> Transformer for SkipIfTransformer<T>
+where
+    T: Transformer,
+{
+    fn transform(
+        &self,
+        field_name: &str,
+        field_value: &str,
+        ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        if self.skip(field_name, field_value)? {
+            Ok(None)
+        } else {
+            self.rule.transform(field_name, field_value, ctx)
+        }
+    }
+
+    fn init(&mut self, ctx: &TransformerInitContext) {
+        // the invalid patterns are reported by the validation
+        self.regexes = RegexSet::new(&self.patterns).ok();
+        self.rule.init(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        transformers::{EmailTransformer, RedactTransformer},
+        Transformers,
+    };
+
+    fn redact() -> Transformers {
+        let mut t = RedactTransformer::default();
+        t.hash_suffix_len = 0;
+        Transformers::Redact(t)
+    }
+
+    fn skip_if(config: &str) -> Transformers {
+        let mut t: Transformers = serde_yaml::from_str(config).unwrap();
+        t.init(&TransformerInitContext::default());
+        t
+    }
+
+    #[test]
+    fn domains() {
+        let mut t = SkipIfTransformer::new(redact());
+        t.domains = vec!["example.com".to_string(), "@Test.org".to_string()];
+
+        for value in [
+            "demo@example.com",
+            "a@EXAMPLE.com",
+            "b@eu.example.com",
+            "c@test.org",
+        ] {
+            assert!(t.matches(value), "{:?}", value);
+            assert_eq!(t.transform("field", value, &None), Ok(None));
+        }
+        for value in ["d@myexample.com", "example.com", "e@example.com.evil", NULL] {
+            assert!(!t.matches(value), "{:?}", value);
+        }
+        assert_eq!(
+            t.transform("field", "user@gmail.com", &None),
+            Ok(Some("[REDACTED]".to_string()))
+        );
+    }
+
+    #[test]
+    fn patterns_and_prefixes() {
+        let t = skip_if(
+            r#"
+            skip_if:
+              patterns: ['^demo\+\d+@']
+              prefixes: ["test_"]
+              rule:
+                redact:
+                  hash_suffix_len: 0
+            "#,
+        );
+
+        assert_eq!(t.transform("field", "demo+1@corp.com", &None), Ok(None));
+        assert_eq!(t.transform("field", "test_user", &None), Ok(None));
+        assert_eq!(
+            t.transform("field", "demo@corp.com", &None),
+            Ok(Some("[REDACTED]".to_string()))
+        );
+        assert_eq!(
+            t.transform("field", "my_test_user", &None),
+            Ok(Some("[REDACTED]".to_string()))
+        );
+    }
+
+    #[test]
+    fn uniqueness() {
+        let field = "skip_if.uniqueness.email";
+        let mut t = SkipIfTransformer::new(Transformers::Email(EmailTransformer::default()));
+        t.domains = vec!["example.com".to_string()];
+
+        assert_eq!(t.skip(field, "demo@example.com"), Ok(true));
+        // the value isn't registered (the inner rule doesn't generate unique values)
+        assert!(uniq_collector::add_to_collector(field, "demo@example.com"));
+
+        t.register_uniq = true;
+        assert_eq!(t.skip(field, "demo2@example.com"), Ok(true));
+        assert!(!uniq_collector::add_to_collector(
+            field,
+            "demo2@example.com"
+        ));
+        // the same kept value in another row
+        assert_eq!(t.skip(field, "demo2@example.com"), Ok(true));
+    }
+
+    #[test]
+    fn late_conflict() {
+        let field = "skip_if.late_conflict.email";
+        let mut t = SkipIfTransformer::new(Transformers::Email(EmailTransformer::default()));
+        t.domains = vec!["example.com".to_string()];
+        t.register_uniq = true;
+
+        // registered before the dump
+        assert!(register_kept(field, "demo@example.com"));
+        assert_eq!(t.skip(field, "demo@example.com"), Ok(true));
+
+        // generated by the inner rule
+        assert!(uniq_collector::add_to_collector(field, "demo2@example.com"));
+        assert!(t
+            .transform(field, "demo2@example.com", &None)
+            .unwrap_err()
+            .reason
+            .starts_with("The kept value of `skip_if.late_conflict.email` is already generated"));
+    }
+
+    #[test]
+    fn register_uniq() {
+        let t = skip_if(
+            r#"
+            skip_if:
+              domains: ["example.com"]
+              rule:
+                email:
+                  uniq: true
+            "#,
+        );
+        assert!(matches!(t, Transformers::SkipIf(s) if s.register_uniq));
+
+        let t = skip_if("{skip_if: {domains: [example.com], rule: {email: {}}}}");
+        assert!(matches!(t, Transformers::SkipIf(s) if !s.register_uniq));
+    }
+
+    #[test]
+    fn validate() {
+        let t = |config: &str| {
+            serde_yaml::from_str::<SkipIfTransformer<Transformers>>(config)
+                .unwrap()
+                .validate()
+        };
+        assert_eq!(t("{prefixes: [demo], rule: {none: ~}}"), Ok(()));
+        assert_eq!(
+            t("{rule: {none: ~}}"),
+            Err(
+                "at least one of `patterns`, `domains` and `prefixes` must be specified"
+                    .to_string()
+            )
+        );
+        assert!(t("{patterns: ['(demo'], rule: {none: ~}}")
+            .unwrap_err()
+            .starts_with("invalid pattern `(demo`: "));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::transformer::{TransformContext, TransformResult, TransformResultHelper, Transformer};
+use serde::{Deserialize, Serialize};
+
+/// Replaces values with the result of a SQL expression. The expression is evaluated by the database:
+/// it replaces the column in the dump query, so the dumper gets the values that are already replaced
+/// (and the other rules of the table see them too).
+///
+/// # Example:
+///
+/// ```yaml
+/// #...
+/// rules:
+///   email:
+///     sql: "md5(email) || '@example.com'"
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+#[serde(transparent)]
+pub struct SqlTransformer {
+    /// The expression can use any columns of the table (with the original values)
+    pub expression: String,
+}
+
+impl SqlTransformer {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.expression.trim().is_empty() {
+            return Err("the expression is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Transformer for SqlTransformer {
+    // The value is already replaced by the database
+    fn transform(
+        &self,
+        _field_name: &str,
+        field_value: &str,
+        _ctx: &Option<TransformContext>,
+    ) -> TransformResult {
+        TransformResult::present(field_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Transformer, Transformers};
+
+    #[test]
+    fn parse_and_transform() {
+        let config = r#"sql: "md5(email) || '@example.com'""#;
+        let transformer: Transformers = serde_yaml::from_str(config).unwrap();
+
+        assert_eq!(
+            transformer.sql_expression(),
+            Some("md5(email) || '@example.com'")
+        );
+        assert_eq!(
+            transformer.transform("email", "d41d8cd9@example.com", &None),
+            Ok(Some("d41d8cd9@example.com".to_string()))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod random_functions;
 mod store_functions;
 
 use crate::{
@@ -11,6 +12,7 @@
 use serde_json::Value;
 use std::{
     collections::HashMap,
+    error::Error,
     hash::{Hash, Hasher},
 };
 use tera::{Context, Tera};
@@ -52,6 +54,9 @@ pub struct TemplateTransformer {
 
     #[serde(skip)]
     renderer: Tera,
+    /// The error of the shared templates or the format (it is reported by the validation)
+    #[serde(skip)]
+    init_error: Option<String>,
 }
 
 impl TemplateTransformer {
@@ -68,12 +73,40 @@ pub fn new(
             rules,
             variables,
             renderer,
+            init_error: None,
+        }
+    }
+
+    /// Checks the shared templates (`templates`) and the format after the initialization
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.init_error {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
         }
     }
 
     fn render(&self, ctx: &Context) -> tera::Result<String> {
         self.renderer.render(TEMPLATE_NAME, ctx)
     }
+
+    fn init_renderer(&mut self, ctx: &TransformerInitContext) -> tera::Result<()> {
+        let mut ext_renderer = Tera::default();
+
+        if let Some(templates) = &ctx.template_collection.raw {
+            for (name, body) in templates {
+                ext_renderer.add_raw_template(name, body)?;
+            }
+        }
+
+        if let Some(files) = &ctx.template_collection.files {
+            for file in files.iter() {
+                ext_renderer.add_template_file(file, None)?;
+            }
+        }
+
+        self.renderer.extend(&ext_renderer)?;
+        self.renderer.add_raw_template(TEMPLATE_NAME, &self.format)
+    }
 }
 
 impl PartialEq for TemplateTransformer {
@@ -86,7 +119,7 @@ fn eq(&self, other: &Self) -> bool {
 
 impl Eq for TemplateTransformer {}
 
-#[allow(clippy::derive_hash_xor_eq)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 impl Hash for TemplateTransformer {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.format.hash(state);
@@ -116,7 +149,7 @@ fn transform(
                 let key = format!("_{}", i + 1);
                 let transform_result: Option<String> =
                     rule.transform(field_name, field_value, ctx)?;
-                let value = transform_result.unwrap_or_else(|| "".to_string());
+                let value = transform_result.unwrap_or_default();
                 rules_names.insert(key, Value::String(value));
             }
         }
@@ -153,33 +186,27 @@ fn transform(
 
     fn init(&mut self, ctx: &TransformerInitContext) {
         store_functions::register(&mut self.renderer, ctx.template_store.clone());
+        random_functions::register(&mut self.renderer);
 
-        let mut ext_renderer = Tera::default();
-
-        if let Some(templates) = &ctx.template_collection.raw {
-            for (name, body) in templates {
-                ext_renderer.add_raw_template(name, body).unwrap();
-            }
-        }
-
-        if let Some(files) = &ctx.template_collection.files {
-            for file in files.iter() {
-                ext_renderer.add_template_file(&file, None).unwrap();
-            }
-        }
-
-        self.renderer.extend(&ext_renderer).unwrap();
+        self.init_error = self.init_renderer(ctx).err().map(|e| error_message(&e));
 
         if let Some(ts) = &mut self.rules {
             for t in ts {
                 t.init(ctx);
             }
         }
+    }
+}
 
-        self.renderer
-            .add_raw_template(TEMPLATE_NAME, &self.format)
-            .unwrap();
+// The message with its sources (e.g., the parse error of a template)
+fn error_message(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message = format!("{}: {}", message, e);
+        source = e.source();
     }
+    message
 }
 
 impl From<Config> for TemplateTransformer {
@@ -250,6 +277,7 @@ fn init() {
                 Transformers::City(CityTransformer::default()),
                 Transformers::PersonName(PersonNameTransformer {
                     locale: Some(LocaleConfig::ZH_TW),
+                    preserve_script: false,
                 }),
                 Transformers::None(NoneTransformer),
             ]),
@@ -290,7 +318,7 @@ fn transformer() -> Transformers {
                 let mut t: Transformers = serde_yaml::from_str(config).unwrap();
                 t.init(&TransformerInitContext::default());
 
-                return t;
+                t
             }
 
             #[test]
@@ -325,7 +353,7 @@ fn transformer() -> Transformers {
                 let mut t: Transformers = serde_yaml::from_str(config).unwrap();
                 t.init(&TransformerInitContext::default());
 
-                return t;
+                t
             }
 
             #[test]
@@ -427,7 +455,7 @@ fn transformer() -> Transformers {
                 let mut t: Transformers = serde_yaml::from_str(config).unwrap();
                 t.init(&TransformerInitContext::default());
 
-                return t;
+                t
             }
 
             #[test]
@@ -667,8 +695,10 @@ fn using_macros() {
             let templates_collection: TemplatesCollection =
                 serde_yaml::from_str(macro_config).unwrap();
             let mut t: Transformers = serde_yaml::from_str(config).unwrap();
-            let mut context = TransformerInitContext::default();
-            context.template_collection = templates_collection;
+            let context = TransformerInitContext {
+                template_collection: templates_collection,
+                ..TransformerInitContext::default()
+            };
             t.init(&context);
 
             let value = t.transform("field", "", &None).unwrap().unwrap();
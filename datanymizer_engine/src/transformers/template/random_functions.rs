@@ -0,0 +1,60 @@
+use crate::utils;
+use rand::Rng;
+use std::collections::HashMap;
+use tera::{Tera, Value};
+
+/// Replaces the built-in `get_random` function: the values come from the RNG of the rules,
+/// so they are seeded by the run seed
+pub fn register(t: &mut Tera) {
+    t.register_function("get_random", get_random);
+}
+
+fn get_random(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let start = match args.get("start") {
+        Some(start) => tera::from_value::<i32>(start.clone())
+            .map_err(|_| format!("The `start` argument must be an integer: {}", start))?,
+        None => 0,
+    };
+    let end = match args.get("end") {
+        Some(end) => tera::from_value::<i32>(end.clone())
+            .map_err(|_| format!("The `end` argument must be an integer: {}", end))?,
+        None => return Err("No end argument".into()),
+    };
+    if start >= end {
+        return Err(format!("The range is empty: {}..{}", start, end).into());
+    }
+    Ok(Value::from(utils::rng().gen_range(start..end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tera::Context;
+
+    fn render(template: &str) -> tera::Result<String> {
+        let mut t = Tera::default();
+        register(&mut t);
+        t.render_str(template, &Context::new())
+    }
+
+    #[test]
+    fn seeded() {
+        let template = "{{ get_random(start=10, end=1000) }}-{{ get_random(end=1000) }}";
+        let seed = utils::keyed_seed("salt", "value");
+        let first = utils::with_seed(seed, || render(template).unwrap());
+        assert_eq!(utils::with_seed(seed, || render(template).unwrap()), first);
+        assert_ne!(
+            utils::with_seed(utils::keyed_seed("salt", "other"), || render(template)
+                .unwrap()),
+            first
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(render("{{ get_random(start=1) }}").is_err());
+        assert!(render("{{ get_random(start=5, end=5) }}").is_err());
+        assert!(render("{{ get_random(end='a') }}").is_err());
+        assert_eq!(render("{{ get_random(start=4, end=5) }}").unwrap(), "4");
+    }
+}
@@ -1,15 +1,25 @@
+use crate::memory::{self, Component};
 use once_cell::sync::Lazy;
 use std::collections::{hash_map::DefaultHasher, HashSet};
 use std::hash::{Hash, Hasher};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+
+/// The memory of a value hash in the set (with the spare capacity of the set)
+const ENTRY_BYTES: u64 = 24;
 
 static GLOBAL_DATA: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+static MEMORY: Lazy<Arc<Component>> =
+    Lazy::new(|| memory::accountant().component(memory::UNIQUENESS_SETS));
 
 pub(crate) fn add_to_collector(name: &str, value: &str) -> bool {
     if let Ok(mut counter) = GLOBAL_DATA.lock() {
         let mut hasher = DefaultHasher::new();
         format!("{}.{}", name, value).hash(&mut hasher);
-        counter.insert(hasher.finish())
+        let inserted = counter.insert(hasher.finish());
+        if inserted {
+            MEMORY.add(ENTRY_BYTES);
+        }
+        inserted
     } else {
         false
     }
@@ -1,7 +1,92 @@
-use rand::distributions::{Distribution, Uniform};
+use rand::{
+    distributions::{Distribution, Uniform},
+    rngs::StdRng,
+    RngCore, SeedableRng,
+};
+use std::cell::RefCell;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+thread_local! {
+    /// The RNG of the rules inside `with_seed`
+    static SEEDED_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// The RNG of the rules: the thread RNG, or the seeded one inside `with_seed`
+/// (so the same seed gives the same values)
+pub struct TransformRng;
+
+impl TransformRng {
+    fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        SEEDED_RNG.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(rng) => f(rng),
+            None => f(&mut rand::thread_rng()),
+        })
+    }
+}
+
+impl RngCore for TransformRng {
+    fn next_u32(&mut self) -> u32 {
+        Self::with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        Self::with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        Self::with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        Self::with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+pub fn rng() -> TransformRng {
+    TransformRng
+}
+
+/// Runs `f` with the RNG seeded (the outer seed is restored after it, even on panics)
+pub fn with_seed<T>(seed: [u8; 32], f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<StdRng>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            SEEDED_RNG.with(|seeded| *seeded.borrow_mut() = outer);
+        }
+    }
+
+    let _restore = Restore(SEEDED_RNG.with(|seeded| seeded.replace(Some(StdRng::from_seed(seed)))));
+    f()
+}
+
+/// The 256-bit seed from the keyed (by `salt`) hashes of the value
+pub fn keyed_seed(salt: &str, value: &str) -> [u8; 32] {
+    let mut seed = [0; 32];
+    for (i, chunk) in seed.chunks_mut(8).enumerate() {
+        let mut bytes = vec![i as u8];
+        bytes.extend_from_slice(salt.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(value.as_bytes());
+        chunk.copy_from_slice(&fmix64(fnv1a(&bytes)).to_le_bytes());
+    }
+    seed
+}
+
+/// The salt with the run pepper (the salt as is without a pepper)
+pub fn peppered(salt: &str, pepper: &str) -> String {
+    if pepper.is_empty() {
+        salt.to_string()
+    } else {
+        format!("{}\u{1}{}", salt, pepper)
+    }
+}
 
 pub fn rnd_chars(len: usize, src: &[char]) -> String {
-    let rng = rand::thread_rng();
+    let rng = self::rng();
     let distribution = Uniform::<usize>::from(0..src.len());
     distribution
         .sample_iter(rng)
@@ -10,10 +95,55 @@ pub fn rnd_chars(len: usize, src: &[char]) -> String {
         .collect::<String>()
 }
 
+/// FNV-1a hash (it is stable between runs and versions, unlike the std hasher)
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The MurmurHash3 finalizer: every bit of the FNV hash affects all bits of the result
+/// (the last bytes of FNV-1a input change mostly the low bits, so the high ones have to be mixed)
+pub fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn stable_hash() {
+        assert_eq!(fnv1a(b""), FNV_OFFSET_BASIS);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn seeded_rng() {
+        use rand::Rng;
+
+        let seed = keyed_seed("salt", "value");
+        let numbers = || (0..4).map(|_| rng().gen::<u64>()).collect::<Vec<_>>();
+        let first = with_seed(seed, numbers);
+        assert_eq!(with_seed(seed, numbers), first);
+        assert_ne!(with_seed(keyed_seed("salt", "other"), numbers), first);
+        assert_ne!(with_seed(keyed_seed("other", "value"), numbers), first);
+
+        // the nested seeds don't change the outer sequence
+        let nested = with_seed(seed, || {
+            let a = rng().gen::<u64>();
+            with_seed(keyed_seed("inner", ""), || rng().gen::<u64>());
+            vec![a, rng().gen(), rng().gen(), rng().gen()]
+        });
+        assert_eq!(nested, first);
+        // the thread RNG outside
+        assert!(SEEDED_RNG.with(|seeded| seeded.borrow().is_none()));
+    }
+
     #[test]
     fn same_char() {
         let chars = vec!['a'];
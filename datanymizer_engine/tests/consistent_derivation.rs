@@ -0,0 +1,114 @@
+// The consistent values are derived from the pepper, the salt and the original values only,
+// so other processes (runs) with the same pepper give the same values
+
+use datanymizer_engine::{Engine, Pepper, Settings};
+use std::{collections::HashMap, env, process::Command};
+
+const CHILD_ENV: &str = "DATANYMIZER_TEST_DERIVATION_CHILD";
+
+const CONFIG: &str = r#"
+  source: {}
+  consistent_salt: "cross-run salt"
+  tables:
+    - name: users
+      rules:
+        email:
+          consistent:
+            rule:
+              email: {}
+        name:
+          consistent:
+            rule:
+              first_name: {}
+        age:
+          consistent:
+            rule:
+              random_num:
+                min: 18
+                max: 99
+        token:
+          consistent:
+            rule:
+              hex_token: {}
+"#;
+
+const COLUMNS: [&str; 4] = ["email", "name", "age", "token"];
+
+fn values(pepper_seed: u64, derivation_version: u32) -> Vec<String> {
+    let config = format!("{}\n  derivation_version: {}\n", CONFIG, derivation_version);
+    let settings =
+        Settings::from_yaml_with_pepper(&config, Pepper::from_seed(pepper_seed)).unwrap();
+    let engine = Engine::new(settings);
+    let column_indexes: HashMap<String, usize> = COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.to_string(), i))
+        .collect();
+
+    let mut result = vec![];
+    for row in [
+        ["jdoe@example.com", "John", "42", "deadbeef"],
+        ["asmith@example.com", "Anna", "35", "cafebabe"],
+    ] {
+        let transformed = engine
+            .process_row("users".to_string(), &column_indexes, &row)
+            .unwrap();
+        result.extend(transformed.into_iter().map(|v| v.into_owned()));
+    }
+    result
+}
+
+fn child_values(derivation_version: u32) -> Vec<String> {
+    let output = Command::new(env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "print_values",
+            "--nocapture",
+            "--test-threads",
+            "1",
+        ])
+        .env(CHILD_ENV, derivation_version.to_string())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        // the harness can print the test name on the same line
+        .filter_map(|line| line.split_once("value: "))
+        .map(|(_, value)| value.to_string())
+        .collect()
+}
+
+// Prints the values in the child process only
+#[test]
+fn print_values() {
+    if let Ok(version) = env::var(CHILD_ENV) {
+        for value in values(1, version.parse().unwrap()) {
+            println!("value: {}", value);
+        }
+    }
+}
+
+#[test]
+fn same_values_in_other_processes() {
+    for version in [1, 2] {
+        let expected = values(1, version);
+        assert_eq!(expected.len(), 8);
+        assert_eq!(child_values(version), expected);
+    }
+}
+
+#[test]
+fn other_peppers_and_versions() {
+    let base = values(1, 2);
+    assert_eq!(values(1, 2), base);
+
+    let other_pepper = values(2, 2);
+    assert_ne!(other_pepper[0], base[0]);
+    assert_ne!(other_pepper[3], base[3]);
+
+    let v1 = values(1, 1);
+    assert_ne!(v1[0], base[0]);
+    assert_ne!(v1[3], base[3]);
+}